@@ -1,9 +1,36 @@
+fn git(args: &[&str]) -> Option<String> {
+  std::process::Command::new("git")
+    .args(args)
+    .output()
+    .ok()
+    .filter(|output| output.status.success())
+    .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+    .filter(|out| !out.is_empty())
+}
+
 fn main() {
   // Set build timestamp as an environment variable for compile time
   println!(
     "cargo:rustc-env=BUILD_TIMESTAMP={}",
     chrono::Utc::now().format("%Y-%m-%d %H:%M:%S UTC")
   );
+
+  // Commit the app was built from. Packagers building from a source tarball (no .git)
+  // can pass GIT_COMMIT; otherwise it's "unknown".
+  println!("cargo:rerun-if-env-changed=GIT_COMMIT");
+  let git_commit = std::env::var("GIT_COMMIT")
+    .ok()
+    .filter(|commit| !commit.trim().is_empty())
+    .or_else(|| git(&["rev-parse", "--short=12", "HEAD"]))
+    .unwrap_or_else(|| "unknown".to_string());
+  println!("cargo:rustc-env=GIT_COMMIT={}", git_commit);
+  // Re-embed the hash when a commit or checkout moves HEAD
+  if let Some(git_dir) = git(&["rev-parse", "--git-dir"]).map(std::path::PathBuf::from) {
+    println!("cargo:rerun-if-changed={}", git_dir.join("HEAD").display());
+    if let Some(head_ref) = git(&["symbolic-ref", "-q", "HEAD"]) {
+      println!("cargo:rerun-if-changed={}", git_dir.join(head_ref).display());
+    }
+  }
   
   tauri_build::build()
 }
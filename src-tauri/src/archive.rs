@@ -0,0 +1,569 @@
+// Archive extraction for the managed LLM server bundles. Every entry is checked to
+// stay inside the destination directory before anything is written, including the
+// targets of symbolic links. Single entries can also be pulled out of a user's
+// archive so their content can be classified without unpacking everything.
+
+use crate::error::{AppError, AppErrorKind};
+use std::fs;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+use flate2::read::GzDecoder;
+use tar::{Archive, EntryType};
+use zip::ZipArchive;
+
+const S_IFMT: u32 = 0o170000;
+const S_IFLNK: u32 = 0o120000;
+
+// Largest single entry extract_entry will unpack
+pub const MAX_ENTRY_BYTES: u64 = 100 * 1024 * 1024;
+
+// Extracts a .zip or .tar.gz archive into `dest`, rejecting entries that would
+// escape it
+pub fn extract_archive(archive_path: &Path, dest: &Path) -> Result<(), String> {
+    let name = archive_path.to_string_lossy().to_lowercase();
+    fs::create_dir_all(dest)
+        .map_err(|e| format!("Failed to create directory: {}", e))?;
+
+    if name.ends_with(".zip") {
+        extract_zip(archive_path, dest)
+    } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+        extract_tar_gz(archive_path, dest)
+    } else {
+        Err(format!("Unsupported archive format: {}", archive_path.to_string_lossy()))
+    }
+}
+
+// Lexically normalizes a relative path, returning None if it is absolute or climbs
+// above its starting point
+pub fn normalize_relative(path: &Path) -> Option<PathBuf> {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => normalized.push(part),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !normalized.pop() {
+                    return None;
+                }
+            }
+            Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    Some(normalized)
+}
+
+// A symlink at `link` (relative to the archive root) pointing at `target` is only
+// allowed when the resolved target stays inside the archive root
+fn symlink_target_is_enclosed(link: &Path, target: &Path) -> bool {
+    let parent = link.parent().unwrap_or_else(|| Path::new(""));
+    normalize_relative(&parent.join(target)).is_some()
+}
+
+// Resolves the deepest part of `path` that already exists and checks it is still
+// under `dest`, so a symlink left by an earlier entry can't redirect what comes
+// next. `dest` must already be canonical.
+fn resolve_inside(dest: &Path, path: &Path, name: &str) -> Result<PathBuf, String> {
+    let mut existing = path;
+    let mut missing = Vec::new();
+    while existing.symlink_metadata().is_err() {
+        missing.push(existing.file_name().unwrap_or_default().to_os_string());
+        existing = existing.parent()
+            .ok_or_else(|| format!("Archive entry escapes the destination directory: {}", name))?;
+    }
+    let mut resolved = existing.canonicalize()
+        .map_err(|_| format!("Archive entry escapes the destination directory: {}", name))?;
+    if !resolved.starts_with(dest) {
+        return Err(format!("Archive entry escapes the destination directory: {}", name));
+    }
+    resolved.extend(missing.iter().rev());
+    Ok(resolved)
+}
+
+fn entry_path(dest: &Path, name: &str) -> Result<(PathBuf, PathBuf), String> {
+    // Archives built on Windows may use backslash separators
+    let relative = normalize_relative(Path::new(&name.replace('\\', "/")))
+        .filter(|p| !p.as_os_str().is_empty())
+        .ok_or_else(|| format!("Archive entry escapes the destination directory: {}", name))?;
+    Ok((dest.join(&relative), relative))
+}
+
+#[cfg(unix)]
+fn create_symlink(target: &Path, link: &Path) -> Result<(), String> {
+    if link.symlink_metadata().is_ok() {
+        fs::remove_file(link)
+            .map_err(|e| format!("Failed to replace existing file: {}", e))?;
+    }
+    std::os::unix::fs::symlink(target, link)
+        .map_err(|e| format!("Failed to create symlink: {}", e))
+}
+
+#[cfg(not(unix))]
+fn create_symlink(_target: &Path, link: &Path) -> Result<(), String> {
+    Err(format!(
+        "Archive contains a symbolic link, which is not supported on this platform: {}",
+        link.to_string_lossy()
+    ))
+}
+
+#[cfg(unix)]
+fn set_unix_mode(path: &Path, mode: u32) -> Result<(), String> {
+    use std::os::unix::fs::PermissionsExt;
+    // Only keep the rwx bits; setuid/setgid from an archive are never honoured
+    fs::set_permissions(path, fs::Permissions::from_mode(mode & 0o777))
+        .map_err(|e| format!("Failed to set permissions: {}", e))
+}
+
+#[cfg(not(unix))]
+fn set_unix_mode(_path: &Path, _mode: u32) -> Result<(), String> {
+    Ok(())
+}
+
+fn extract_zip(archive_path: &Path, dest: &Path) -> Result<(), String> {
+    let file = fs::File::open(archive_path)
+        .map_err(|e| format!("Failed to open ZIP file: {}", e))?;
+    let mut archive = ZipArchive::new(file)
+        .map_err(|e| format!("Failed to read ZIP archive: {}", e))?;
+    let dest = &dest.canonicalize()
+        .map_err(|e| format!("Failed to resolve destination directory: {}", e))?;
+
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)
+            .map_err(|e| format!("Failed to read file from ZIP: {}", e))?;
+        let name = entry.name().to_string();
+        if entry.enclosed_name().is_none() {
+            return Err(format!("Archive entry escapes the destination directory: {}", name));
+        }
+        let (outpath, _) = entry_path(dest, &name)?;
+        let mode = entry.unix_mode();
+
+        if entry.is_dir() {
+            let outpath = resolve_inside(dest, &outpath, &name)?;
+            fs::create_dir_all(&outpath)
+                .map_err(|e| format!("Failed to create directory: {}", e))?;
+            continue;
+        }
+
+        // Directories are created under the resolved parent, never through a symlink
+        // that leads out of `dest`
+        let parent = resolve_inside(dest, outpath.parent().unwrap_or(dest), &name)?;
+        fs::create_dir_all(&parent)
+            .map_err(|e| format!("Failed to create parent directory: {}", e))?;
+        let outpath = parent.join(outpath.file_name().unwrap_or_default());
+        // Where the entry really lands, which differs from its name once it goes
+        // through a symlink
+        let relative = outpath.strip_prefix(dest).unwrap_or(&outpath).to_path_buf();
+
+        if mode.is_some_and(|m| m & S_IFMT == S_IFLNK) {
+            // Symlink entries store their target as the file content
+            let mut target = String::new();
+            entry.read_to_string(&mut target)
+                .map_err(|e| format!("Failed to read symlink target: {}", e))?;
+            let target = PathBuf::from(target);
+            if !symlink_target_is_enclosed(&relative, &target) {
+                return Err(format!(
+                    "Archive symlink {} points outside the destination directory: {}",
+                    name,
+                    target.to_string_lossy()
+                ));
+            }
+            create_symlink(&target, &outpath)?;
+            continue;
+        }
+
+        // Never write through a symlink created by an earlier entry
+        if outpath.symlink_metadata().map(|m| m.file_type().is_symlink()).unwrap_or(false) {
+            return Err(format!("Archive entry would overwrite a symlink: {}", name));
+        }
+
+        let mut outfile = fs::File::create(&outpath)
+            .map_err(|e| format!("Failed to create file: {}", e))?;
+        std::io::copy(&mut entry, &mut outfile)
+            .map_err(|e| format!("Failed to extract file: {}", e))?;
+
+        if let Some(mode) = mode {
+            set_unix_mode(&outpath, mode)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn extract_tar_gz(archive_path: &Path, dest: &Path) -> Result<(), String> {
+    let file = fs::File::open(archive_path)
+        .map_err(|e| format!("Failed to open TAR.GZ file: {}", e))?;
+    let mut archive = Archive::new(GzDecoder::new(file));
+
+    let entries = archive.entries()
+        .map_err(|e| format!("Failed to read TAR.GZ: {}", e))?;
+    for entry in entries {
+        let mut entry = entry
+            .map_err(|e| format!("Failed to read TAR.GZ entry: {}", e))?;
+        let name = entry.path()
+            .map_err(|e| format!("Invalid TAR.GZ entry path: {}", e))?
+            .to_string_lossy()
+            .into_owned();
+        let (_, relative) = entry_path(dest, &name)?;
+
+        match entry.header().entry_type() {
+            EntryType::Symlink | EntryType::Link => {
+                let target = entry.link_name()
+                    .map_err(|e| format!("Invalid TAR.GZ link target: {}", e))?
+                    .ok_or_else(|| format!("Archive link {} has no target", name))?;
+                // Hard link targets are relative to the archive root, symlinks to the link
+                let enclosed = if entry.header().entry_type() == EntryType::Link {
+                    normalize_relative(&target).is_some()
+                } else {
+                    symlink_target_is_enclosed(&relative, &target)
+                };
+                if !enclosed {
+                    return Err(format!(
+                        "Archive link {} points outside the destination directory: {}",
+                        name,
+                        target.to_string_lossy()
+                    ));
+                }
+            }
+            _ => {}
+        }
+
+        // unpack_in additionally refuses to write through symlinks that leave `dest`
+        let unpacked = entry.unpack_in(dest)
+            .map_err(|e| format!("Failed to extract TAR.GZ entry {}: {}", name, e))?;
+        if !unpacked {
+            return Err(format!("Archive entry escapes the destination directory: {}", name));
+        }
+    }
+
+    Ok(())
+}
+
+// Extracts the one entry named `inner_path` from a .zip or .tar.gz into `dest_dir`,
+// keeping its file name so content extraction can go by the extension. Inner paths
+// with ".." or a root, entries over `max_bytes` and encrypted entries are refused.
+pub fn extract_entry(
+    archive_path: &Path,
+    inner_path: &str,
+    dest_dir: &Path,
+    max_bytes: u64,
+) -> Result<PathBuf, AppError> {
+    let inner = PathBuf::from(inner_path.replace('\\', "/"));
+    if inner.components().any(|c| matches!(c, Component::ParentDir | Component::RootDir | Component::Prefix(_))) {
+        return Err(AppError::invalid_input("Archive entry path must stay inside the archive").with_path(inner_path));
+    }
+    let wanted = normalize_relative(&inner)
+        .filter(|p| !p.as_os_str().is_empty())
+        .ok_or_else(|| AppError::invalid_input("Archive entry path is empty"))?;
+    let file_name = wanted.file_name().unwrap_or(wanted.as_os_str());
+    fs::create_dir_all(dest_dir).map_err(|e| AppError::from_io(&e, format!("Failed to create directory: {}", e)))?;
+    let dest = dest_dir.join(file_name);
+
+    let name = archive_path.to_string_lossy().to_lowercase();
+    let archive = fs::File::open(archive_path)
+        .map_err(|e| AppError::from_io(&e, format!("Failed to open archive: {}", e)).with_path(archive_path))?;
+    if name.ends_with(".zip") {
+        extract_zip_entry(archive, &wanted, &dest, max_bytes)?;
+    } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+        extract_tar_gz_entry(archive, &wanted, &dest, max_bytes)?;
+    } else {
+        return Err(AppError::unsupported("Only .zip and .tar.gz archives can be read").with_path(archive_path));
+    }
+    Ok(dest)
+}
+
+fn entry_too_large(size: u64, max_bytes: u64) -> AppError {
+    AppError::unsupported(format!(
+        "Archive entry is {} MB, over the {} MB limit",
+        size / (1024 * 1024),
+        max_bytes / (1024 * 1024)
+    ))
+}
+
+// Copies at most max_bytes, so a size field that lies can't fill the disk
+fn copy_bounded(reader: &mut impl Read, dest: &Path, max_bytes: u64) -> Result<(), AppError> {
+    let mut out = fs::File::create(dest)
+        .map_err(|e| AppError::from_io(&e, format!("Failed to create file: {}", e)))?;
+    let written = std::io::copy(&mut reader.take(max_bytes + 1), &mut out)
+        .map_err(|e| AppError::new(AppErrorKind::InvalidData, format!("Failed to extract archive entry: {}", e)))?;
+    if written > max_bytes {
+        drop(out);
+        let _ = fs::remove_file(dest);
+        return Err(entry_too_large(written, max_bytes));
+    }
+    Ok(())
+}
+
+fn extract_zip_entry(file: fs::File, wanted: &Path, dest: &Path, max_bytes: u64) -> Result<(), AppError> {
+    let mut archive = ZipArchive::new(file)
+        .map_err(|e| AppError::new(AppErrorKind::InvalidData, format!("Failed to read ZIP archive: {}", e)))?;
+    // Raw access reads only the header, which works for encrypted entries too
+    let index = (0..archive.len())
+        .find(|&i| {
+            let name = archive.by_index_raw(i).map(|entry| entry.name().replace('\\', "/")).unwrap_or_default();
+            normalize_relative(Path::new(&name)).as_deref() == Some(wanted)
+        })
+        .ok_or_else(|| AppError::not_found(format!("No entry named {} in the archive", wanted.display())))?;
+    {
+        let raw = archive.by_index_raw(index)
+            .map_err(|e| AppError::new(AppErrorKind::InvalidData, format!("Failed to read ZIP entry: {}", e)))?;
+        if raw.is_dir() {
+            return Err(AppError::invalid_input("Archive entry is a directory"));
+        }
+        if raw.size() > max_bytes {
+            return Err(entry_too_large(raw.size(), max_bytes));
+        }
+    }
+    let mut entry = match archive.by_index(index) {
+        Ok(entry) => entry,
+        // Both ZipCrypto ("Password required") and AES entries end up here
+        Err(zip::result::ZipError::UnsupportedArchive(detail))
+            if detail == zip::result::ZipError::PASSWORD_REQUIRED || detail.contains("encrypted") =>
+        {
+            return Err(AppError::new(AppErrorKind::Encrypted, "Archive entry is encrypted"));
+        }
+        Err(e) => return Err(AppError::new(AppErrorKind::InvalidData, format!("Failed to read ZIP entry: {}", e))),
+    };
+    copy_bounded(&mut entry, dest, max_bytes)
+}
+
+fn extract_tar_gz_entry(file: fs::File, wanted: &Path, dest: &Path, max_bytes: u64) -> Result<(), AppError> {
+    let invalid = |e: std::io::Error| AppError::new(AppErrorKind::InvalidData, format!("Failed to read TAR.GZ: {}", e));
+    let mut archive = Archive::new(GzDecoder::new(file));
+    for entry in archive.entries().map_err(invalid)? {
+        let mut entry = entry.map_err(invalid)?;
+        let path = entry.path().map_err(invalid)?;
+        if normalize_relative(&path).as_deref() != Some(wanted) {
+            continue;
+        }
+        if entry.header().entry_type() != EntryType::Regular {
+            return Err(AppError::invalid_input("Archive entry isn't a regular file"));
+        }
+        let size = entry.header().size().map_err(invalid)?;
+        if size > max_bytes {
+            return Err(entry_too_large(size, max_bytes));
+        }
+        return copy_bounded(&mut entry, dest, max_bytes);
+    }
+    Err(AppError::not_found(format!("No entry named {} in the archive", wanted.display())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_dir;
+    use std::io::Write;
+    use zip::write::{FileOptions, ZipWriter};
+
+    fn write_zip(path: &Path, build: impl FnOnce(&mut ZipWriter<fs::File>)) {
+        let mut writer = ZipWriter::new(fs::File::create(path).unwrap());
+        build(&mut writer);
+        writer.finish().unwrap();
+    }
+
+    fn write_tar_gz(path: &Path, entries: &[(&str, EntryType, &str)]) {
+        let encoder = flate2::write::GzEncoder::new(
+            fs::File::create(path).unwrap(),
+            flate2::Compression::default(),
+        );
+        let mut builder = tar::Builder::new(encoder);
+        for (name, entry_type, data_or_target) in entries {
+            let mut header = tar::Header::new_old();
+            // Write the raw name so malicious paths aren't rejected by the builder
+            let raw = &mut header.as_old_mut().name;
+            raw[..name.len()].copy_from_slice(name.as_bytes());
+            header.set_entry_type(*entry_type);
+            header.set_mode(0o755);
+            if *entry_type == EntryType::Regular {
+                header.set_size(data_or_target.len() as u64);
+                header.set_cksum();
+                builder.append(&header, data_or_target.as_bytes()).unwrap();
+            } else {
+                let raw = &mut header.as_old_mut().linkname;
+                raw[..data_or_target.len()].copy_from_slice(data_or_target.as_bytes());
+                header.set_size(0);
+                header.set_cksum();
+                builder.append(&header, std::io::empty()).unwrap();
+            }
+        }
+        builder.into_inner().unwrap().finish().unwrap();
+    }
+
+    #[test]
+    fn extracts_well_formed_zip() {
+        let dir = temp_dir("zip-ok");
+        let archive = dir.join("server.zip");
+        write_zip(&archive, |w| {
+            w.add_directory("llama_server/", FileOptions::default()).unwrap();
+            w.start_file("llama_server/llama_server", FileOptions::default().unix_permissions(0o755)).unwrap();
+            w.write_all(b"binary").unwrap();
+            w.start_file("llama_server\\lib\\data.txt", FileOptions::default()).unwrap();
+            w.write_all(b"data").unwrap();
+        });
+
+        let dest = dir.join("out");
+        extract_archive(&archive, &dest).unwrap();
+        assert_eq!(fs::read(dest.join("llama_server/llama_server")).unwrap(), b"binary");
+        assert_eq!(fs::read(dest.join("llama_server/lib/data.txt")).unwrap(), b"data");
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(dest.join("llama_server/llama_server")).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o755);
+        }
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn rejects_zip_slip_entries() {
+        for name in ["../evil.txt", "..\\..\\evil.exe", "/tmp/evil.txt", "a/../../evil.txt"] {
+            let dir = temp_dir("zip-slip");
+            let archive = dir.join("evil.zip");
+            write_zip(&archive, |w| {
+                w.start_file(name, FileOptions::default()).unwrap();
+                w.write_all(b"pwned").unwrap();
+            });
+
+            let dest = dir.join("out");
+            let result = extract_archive(&archive, &dest);
+            assert!(result.is_err(), "entry {:?} should be rejected", name);
+            assert!(!dir.join("evil.txt").exists());
+            let _ = fs::remove_dir_all(&dir);
+        }
+    }
+
+    #[test]
+    fn rejects_zip_symlink_escaping_destination() {
+        let dir = temp_dir("zip-link");
+        let archive = dir.join("link.zip");
+        write_zip(&archive, |w| {
+            w.add_symlink("server/link", "../../outside", FileOptions::default()).unwrap();
+        });
+
+        let result = extract_archive(&archive, &dir.join("out"));
+        assert!(result.unwrap_err().contains("points outside"));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn rejects_zip_symlink_chains_leaving_destination() {
+        let dir = temp_dir("zip-link-chain");
+        let archive = dir.join("chain.zip");
+        // Each target looks enclosed on its own, but a/b/c really sits at the root
+        // of `out`, so "../.." from there is two levels above it
+        write_zip(&archive, |w| {
+            w.add_symlink("a/b", "..", FileOptions::default()).unwrap();
+            w.add_symlink("a/b/c", "../..", FileOptions::default()).unwrap();
+            w.start_file("a/b/c/chain-evil.txt", FileOptions::default()).unwrap();
+            w.write_all(b"pwned").unwrap();
+        });
+
+        let dest = dir.join("out");
+        let result = extract_archive(&archive, &dest);
+        assert!(result.is_err());
+        assert!(!dir.join("chain-evil.txt").exists());
+        assert!(!dir.parent().unwrap().join("chain-evil.txt").exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn keeps_zip_symlinks_inside_destination() {
+        let dir = temp_dir("zip-link-ok");
+        let archive = dir.join("link.zip");
+        write_zip(&archive, |w| {
+            w.start_file("Python.framework/Versions/3.11/Python", FileOptions::default()).unwrap();
+            w.write_all(b"lib").unwrap();
+            w.add_symlink("Python.framework/Python", "Versions/3.11/Python", FileOptions::default()).unwrap();
+        });
+
+        let dest = dir.join("out");
+        extract_archive(&archive, &dest).unwrap();
+        let link = dest.join("Python.framework/Python");
+        assert!(link.symlink_metadata().unwrap().file_type().is_symlink());
+        assert_eq!(fs::read(&link).unwrap(), b"lib");
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn extracts_well_formed_tar_gz() {
+        let dir = temp_dir("tar-ok");
+        let archive = dir.join("server.tar.gz");
+        write_tar_gz(&archive, &[("llama_server/llama_server", EntryType::Regular, "binary")]);
+
+        let dest = dir.join("out");
+        extract_archive(&archive, &dest).unwrap();
+        assert_eq!(fs::read(dest.join("llama_server/llama_server")).unwrap(), b"binary");
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn rejects_tar_gz_escaping_entries() {
+        let cases: &[&[(&str, EntryType, &str)]] = &[
+            &[("../evil.txt", EntryType::Regular, "pwned")],
+            &[("/tmp/evil.txt", EntryType::Regular, "pwned")],
+            &[("link", EntryType::Symlink, "/etc")],
+            &[("dir/link", EntryType::Symlink, "../../etc")],
+            &[("hard", EntryType::Link, "../outside")],
+        ];
+        for entries in cases {
+            let dir = temp_dir("tar-slip");
+            let archive = dir.join("evil.tar.gz");
+            write_tar_gz(&archive, entries);
+
+            let result = extract_archive(&archive, &dir.join("out"));
+            assert!(result.is_err(), "entries {:?} should be rejected", entries[0].0);
+            assert!(!dir.join("evil.txt").exists());
+            let _ = fs::remove_dir_all(&dir);
+        }
+    }
+
+    #[test]
+    fn single_entries_are_extracted_from_zip_and_tar_gz() {
+        let dir = temp_dir("entry");
+        let zip_path = dir.join("scans.zip");
+        write_zip(&zip_path, |w| {
+            w.start_file("2024/invoice.txt", FileOptions::default()).unwrap();
+            w.write_all(b"Invoice 42").unwrap();
+        });
+        let extracted = extract_entry(&zip_path, "2024\\invoice.txt", &dir.join("out"), MAX_ENTRY_BYTES).unwrap();
+        assert_eq!(extracted, dir.join("out").join("invoice.txt"));
+        assert_eq!(fs::read_to_string(&extracted).unwrap(), "Invoice 42");
+
+        let tar_path = dir.join("notes.tar.gz");
+        write_tar_gz(&tar_path, &[("docs/readme.md", EntryType::Regular, "# Notes")]);
+        let extracted = extract_entry(&tar_path, "./docs/readme.md", &dir.join("out2"), MAX_ENTRY_BYTES).unwrap();
+        assert_eq!(fs::read_to_string(extracted).unwrap(), "# Notes");
+
+        let missing = extract_entry(&tar_path, "docs/other.md", &dir.join("out3"), MAX_ENTRY_BYTES).unwrap_err();
+        assert_eq!(missing.kind, AppErrorKind::NotFound);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn traversal_and_oversized_entries_are_refused() {
+        let dir = temp_dir("entry-refused");
+        let zip_path = dir.join("big.zip");
+        write_zip(&zip_path, |w| {
+            w.start_file("big.txt", FileOptions::default()).unwrap();
+            w.write_all(&[b'x'; 4096]).unwrap();
+        });
+        let out = dir.join("out");
+        for inner in ["../big.txt", "/etc/passwd", "a/../../big.txt"] {
+            let err = extract_entry(&zip_path, inner, &out, MAX_ENTRY_BYTES).unwrap_err();
+            assert_eq!(err.kind, AppErrorKind::InvalidInput, "{}", inner);
+        }
+        let err = extract_entry(&zip_path, "big.txt", &out, 1024).unwrap_err();
+        assert_eq!(err.kind, AppErrorKind::Unsupported);
+        assert!(!out.join("big.txt").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn rejects_unknown_archive_format() {
+        let dir = temp_dir("format");
+        let result = extract_archive(&dir.join("server.rar"), &dir.join("out"));
+        assert!(result.unwrap_err().contains("Unsupported archive format"));
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
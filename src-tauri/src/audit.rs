@@ -0,0 +1,498 @@
+// Audit trail of what the app read, moved, trashed and sent to LLM backends, for users
+// who have to show that afterwards. Only written while audit mode is on (settings
+// audit.enabled); with it off nothing is hashed or written. Records are JSON lines in
+// app_data_dir/audit/audit.jsonl, each carrying the SHA-256 of the one before it, so
+// editing, reordering or removing a line breaks the chain from there on. Exports are
+// signed with an Ed25519 key kept next to the log. The chain needs no secret, so a
+// bundle only counts as valid when it was signed by a key the verifier already trusts.
+use crate::error::AppError;
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+pub const BUNDLE_VERSION: u32 = 1;
+const AUDIT_DIR: &str = "audit";
+const LOG_FILE: &str = "audit.jsonl";
+const KEY_FILE: &str = "signing.key";
+// prev_hash of the first record
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+// Sequence number and hash of the last record, read from the log on first use
+struct Head {
+    log: PathBuf,
+    seq: u64,
+    hash: String,
+}
+
+static HEAD: Mutex<Option<Head>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    Read,
+    Move,
+    Trash,
+    LlmSubmission,
+    // Refused by local-only mode; path is the file when known, otherwise the URL
+    Blocked,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditEvent {
+    pub action: AuditAction,
+    pub path: String,
+    // Where a moved or trashed file went
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub destination: Option<String>,
+    // LLM submissions: the backend and its endpoint, e.g. "openai (https://api.openai.com)"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backend: Option<String>,
+    // File size for reads, moves and trashing; request size for LLM submissions
+    pub bytes: u64,
+}
+
+impl AuditEvent {
+    pub fn new(action: AuditAction, path: impl Into<String>, bytes: u64) -> Self {
+        AuditEvent { action, path: path.into(), destination: None, backend: None, bytes }
+    }
+
+    pub fn with_destination(mut self, destination: impl Into<String>) -> Self {
+        self.destination = Some(destination.into());
+        self
+    }
+
+    pub fn with_backend(mut self, backend: impl Into<String>) -> Self {
+        self.backend = Some(backend.into());
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    pub seq: u64,
+    // Unix time in milliseconds
+    pub timestamp: u64,
+    #[serde(flatten)]
+    pub event: AuditEvent,
+    pub prev_hash: String,
+    pub hash: String,
+}
+
+impl AuditRecord {
+    fn hash_matches(&self) -> bool {
+        record_hash(self.seq, self.timestamp, &self.event, &self.prev_hash) == self.hash
+    }
+}
+
+// Everything in a record except its own hash, in the order it's hashed
+#[derive(Serialize)]
+struct Unhashed<'a> {
+    seq: u64,
+    timestamp: u64,
+    #[serde(flatten)]
+    event: &'a AuditEvent,
+    prev_hash: &'a str,
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn unhex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len()).step_by(2).map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok()).collect()
+}
+
+fn record_hash(seq: u64, timestamp: u64, event: &AuditEvent, prev_hash: &str) -> String {
+    let unhashed = Unhashed { seq, timestamp, event, prev_hash };
+    let json = serde_json::to_vec(&unhashed).unwrap_or_default();
+    hex(&Sha256::digest(json))
+}
+
+fn log_path(app_data_dir: &Path) -> PathBuf {
+    app_data_dir.join(AUDIT_DIR).join(LOG_FILE)
+}
+
+// A record, or the line number and parse error of a line that isn't one
+type Line = Result<AuditRecord, (usize, String)>;
+
+// Unreadable lines are kept as errors so verification can point at them
+fn read_lines(path: &Path) -> Result<Vec<Line>, AppError> {
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(AppError::from_io(&e, format!("Failed to read the audit log: {}", e)).with_path(path)),
+    };
+    Ok(text
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| serde_json::from_str(line).map_err(|e| (index + 1, e.to_string())))
+        .collect())
+}
+
+pub fn append(app_data_dir: &Path, event: AuditEvent, now_ms: u64) -> Result<AuditRecord, AppError> {
+    let log = log_path(app_data_dir);
+    let mut head = HEAD.lock().unwrap();
+    // A log deleted since starts a new chain
+    if head.as_ref().is_none_or(|head| head.log != log) || !log.exists() {
+        let last = read_lines(&log)?.into_iter().filter_map(Result::ok).next_back();
+        *head = Some(Head {
+            seq: last.as_ref().map_or(0, |record| record.seq),
+            hash: last.map_or_else(|| GENESIS_HASH.to_string(), |record| record.hash),
+            log: log.clone(),
+        });
+    }
+    let head = head.as_mut().expect("head was just loaded");
+    let seq = head.seq + 1;
+    let hash = record_hash(seq, now_ms, &event, &head.hash);
+    let record = AuditRecord { seq, timestamp: now_ms, event, prev_hash: head.hash.clone(), hash };
+
+    let mut line = serde_json::to_string(&record)
+        .map_err(|e| AppError::io(format!("Failed to serialize audit record: {}", e)))?;
+    line.push('\n');
+    std::fs::create_dir_all(app_data_dir.join(AUDIT_DIR))
+        .map_err(|e| AppError::from_io(&e, format!("Failed to create audit directory: {}", e)))?;
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&log)
+        .and_then(|mut file| file.write_all(line.as_bytes()))
+        .map_err(|e| AppError::from_io(&e, format!("Failed to write the audit log: {}", e)).with_path(&log))?;
+    head.seq = seq;
+    head.hash = record.hash.clone();
+    Ok(record)
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ChainVerification {
+    pub records: usize,
+    pub valid: bool,
+    // The first record that doesn't hash correctly or doesn't follow the one before it
+    pub first_invalid_seq: Option<u64>,
+    pub first_invalid_line: Option<usize>,
+    pub problem: Option<String>,
+    // Hash of the last record; an auditor can note it to detect later truncation
+    pub head_hash: Option<String>,
+    // Bundles only: whether the signature matches the bundle's contents and public key
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signature_valid: Option<bool>,
+    // Bundles only: whether that public key is this install's or the one the auditor
+    // expected, rather than whatever key the bundle brought along
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signer_trusted: Option<bool>,
+    // Bundles only: SHA-256 of the public key the bundle names
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signer_fingerprint: Option<String>,
+}
+
+// The records must follow on from `prev_hash` (GENESIS_HASH for a whole log, the
+// first record's own prev_hash for an exported range)
+fn verify_records(records: &[Line], prev_hash: &str) -> ChainVerification {
+    let mut verification = ChainVerification { records: records.len(), valid: true, ..Default::default() };
+    let mut expected = (None::<u64>, prev_hash.to_string());
+    for (index, record) in records.iter().enumerate() {
+        let problem = match record {
+            Err((line, e)) => Some((None, *line, format!("Line {} is not a valid record: {}", line, e))),
+            Ok(record) if record.prev_hash != expected.1 => Some((
+                Some(record.seq),
+                index + 1,
+                format!("Record {} doesn't follow the record before it", record.seq),
+            )),
+            Ok(record) if expected.0.is_some_and(|seq| record.seq != seq + 1) => {
+                Some((Some(record.seq), index + 1, format!("Record {} is out of sequence", record.seq)))
+            }
+            Ok(record) if !record.hash_matches() => Some((
+                Some(record.seq),
+                index + 1,
+                format!("Record {} was changed after it was written", record.seq),
+            )),
+            Ok(record) => {
+                expected = (Some(record.seq), record.hash.clone());
+                None
+            }
+        };
+        if let Some((seq, line, problem)) = problem {
+            verification.valid = false;
+            verification.first_invalid_seq = seq;
+            verification.first_invalid_line = Some(line);
+            verification.problem = Some(problem);
+            return verification;
+        }
+    }
+    verification.head_hash = (!records.is_empty()).then_some(expected.1);
+    verification
+}
+
+pub fn verify_log(app_data_dir: &Path) -> Result<ChainVerification, AppError> {
+    Ok(verify_records(&read_lines(&log_path(app_data_dir))?, GENESIS_HASH))
+}
+
+// Inclusive, in Unix milliseconds; a missing end is unbounded
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct AuditRange {
+    #[serde(default)]
+    pub from_ms: Option<u64>,
+    #[serde(default)]
+    pub to_ms: Option<u64>,
+}
+
+impl AuditRange {
+    fn contains(&self, timestamp: u64) -> bool {
+        self.from_ms.is_none_or(|from| timestamp >= from) && self.to_ms.is_none_or(|to| timestamp <= to)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditBundle {
+    pub version: u32,
+    pub exported_at: u64,
+    pub range: AuditRange,
+    // Whether the whole log verified when this was exported
+    pub log_valid: bool,
+    pub records: Vec<AuditRecord>,
+    // Ed25519, hex encoded; the signature covers the bundle's JSON without it
+    pub public_key: String,
+    #[serde(default)]
+    pub signature: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportSummary {
+    pub path: String,
+    pub records: usize,
+    pub log_valid: bool,
+    pub public_key: String,
+    // What an auditor passes to verify_bundle to pin this key
+    pub fingerprint: String,
+}
+
+fn bundle_message(bundle: &AuditBundle) -> Vec<u8> {
+    let unsigned = AuditBundle { signature: String::new(), ..bundle.clone() };
+    serde_json::to_vec(&unsigned).unwrap_or_default()
+}
+
+fn fingerprint(public_key: &[u8]) -> String {
+    hex(&Sha256::digest(public_key))
+}
+
+// The key exports are signed with, if one has been created yet
+fn existing_signing_key(app_data_dir: &Path) -> Result<Option<SigningKey>, AppError> {
+    let path = app_data_dir.join(AUDIT_DIR).join(KEY_FILE);
+    let Ok(bytes) = std::fs::read(&path) else {
+        return Ok(None);
+    };
+    let bytes: [u8; 32] = bytes
+        .try_into()
+        .map_err(|_| AppError::invalid_input("The audit signing key is damaged").with_path(&path))?;
+    Ok(Some(SigningKey::from_bytes(&bytes)))
+}
+
+// Created on first export; only readable by the user where the platform allows
+fn signing_key(app_data_dir: &Path) -> Result<SigningKey, AppError> {
+    let path = app_data_dir.join(AUDIT_DIR).join(KEY_FILE);
+    if let Some(key) = existing_signing_key(app_data_dir)? {
+        return Ok(key);
+    }
+    let mut bytes = [0u8; 32];
+    getrandom::getrandom(&mut bytes).map_err(|e| AppError::io(format!("Failed to generate a signing key: {}", e)))?;
+    std::fs::create_dir_all(app_data_dir.join(AUDIT_DIR))
+        .map_err(|e| AppError::from_io(&e, format!("Failed to create audit directory: {}", e)))?;
+    std::fs::write(&path, bytes)
+        .map_err(|e| AppError::from_io(&e, format!("Failed to save the signing key: {}", e)).with_path(&path))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let _ = std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600));
+    }
+    Ok(SigningKey::from_bytes(&bytes))
+}
+
+pub fn export(
+    app_data_dir: &Path,
+    range: AuditRange,
+    destination: &Path,
+    now_ms: u64,
+) -> Result<ExportSummary, AppError> {
+    let lines = read_lines(&log_path(app_data_dir))?;
+    let log_valid = verify_records(&lines, GENESIS_HASH).valid;
+    let records: Vec<AuditRecord> =
+        lines.into_iter().filter_map(Result::ok).filter(|record| range.contains(record.timestamp)).collect();
+    let key = signing_key(app_data_dir)?;
+    let mut bundle = AuditBundle {
+        version: BUNDLE_VERSION,
+        exported_at: now_ms,
+        range,
+        log_valid,
+        records,
+        public_key: hex(key.verifying_key().as_bytes()),
+        signature: String::new(),
+    };
+    bundle.signature = hex(&key.sign(&bundle_message(&bundle)).to_bytes());
+    let json = serde_json::to_vec_pretty(&bundle)
+        .map_err(|e| AppError::io(format!("Failed to serialize the audit bundle: {}", e)))?;
+    std::fs::write(destination, json).map_err(|e| {
+        AppError::from_io(&e, format!("Failed to write the audit bundle: {}", e)).with_path(destination)
+    })?;
+    Ok(ExportSummary {
+        path: crate::paths::display_path(destination),
+        records: bundle.records.len(),
+        log_valid,
+        fingerprint: fingerprint(key.verifying_key().as_bytes()),
+        public_key: bundle.public_key,
+    })
+}
+
+// Checks the signature and that the exported records still chain together. Anyone can
+// rebuild the chain and sign it with a fresh key, so the signer must also be this
+// install's key (from `app_data_dir`) or `expected_key`, given as the hex public key
+// or its fingerprint.
+pub fn verify_bundle(
+    path: &Path,
+    app_data_dir: &Path,
+    expected_key: Option<&str>,
+) -> Result<ChainVerification, AppError> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| AppError::from_io(&e, format!("Failed to read the audit bundle: {}", e)).with_path(path))?;
+    let bundle: AuditBundle = serde_json::from_str(&text)
+        .map_err(|e| AppError::invalid_input(format!("Not an audit bundle: {}", e)).with_path(path))?;
+    if bundle.version != BUNDLE_VERSION {
+        return Err(AppError::unsupported(format!(
+            "The audit bundle is in format {} and this version of the app reads format {}",
+            bundle.version, BUNDLE_VERSION
+        ))
+        .with_path(path));
+    }
+    let public_key = unhex(&bundle.public_key).and_then(|bytes| <[u8; 32]>::try_from(bytes).ok());
+    let signature = unhex(&bundle.signature).and_then(|bytes| <[u8; 64]>::try_from(bytes).ok());
+    let signature_valid = match (public_key.map(|key| VerifyingKey::from_bytes(&key)), signature) {
+        (Some(Ok(key)), Some(signature)) => {
+            key.verify(&bundle_message(&bundle), &Signature::from_bytes(&signature)).is_ok()
+        }
+        _ => false,
+    };
+    let signer_fingerprint = public_key.map(|key| fingerprint(&key));
+    let mut trusted: Vec<String> = expected_key.map(|key| key.trim().to_ascii_lowercase()).into_iter().collect();
+    if let Some(own) = existing_signing_key(app_data_dir)? {
+        let own = own.verifying_key();
+        trusted.extend([hex(own.as_bytes()), fingerprint(own.as_bytes())]);
+    }
+    let signer_trusted = public_key.is_some_and(|key| {
+        let candidates = [hex(&key), fingerprint(&key)];
+        trusted.iter().any(|trusted| candidates.contains(trusted))
+    });
+
+    let prev_hash = bundle.records.first().map_or(GENESIS_HASH, |record| record.prev_hash.as_str());
+    let records: Vec<_> = bundle.records.iter().cloned().map(Ok).collect();
+    let mut verification = verify_records(&records, prev_hash);
+    verification.valid &= signature_valid && signer_trusted;
+    if verification.problem.is_none() {
+        if !signature_valid {
+            verification.problem = Some("The signature doesn't match the bundle".to_string());
+        } else if !signer_trusted {
+            verification.problem = Some("The bundle was signed by an unknown key".to_string());
+        }
+    }
+    verification.signature_valid = Some(signature_valid);
+    verification.signer_trusted = Some(signer_trusted);
+    verification.signer_fingerprint = signer_fingerprint;
+    Ok(verification)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_dir;
+
+    fn write_events(dir: &Path) {
+        append(dir, AuditEvent::new(AuditAction::Read, "/inbox/a.pdf", 10), 1_000).unwrap();
+        let submission = AuditEvent::new(AuditAction::LlmSubmission, "/inbox/a.pdf", 400).with_backend("openai");
+        append(dir, submission, 2_000).unwrap();
+        let moved = AuditEvent::new(AuditAction::Move, "/inbox/a.pdf", 10).with_destination("/docs/a.pdf");
+        append(dir, moved, 3_000).unwrap();
+    }
+
+    #[test]
+    fn edits_to_the_log_break_the_chain() {
+        let dir = temp_dir("chain");
+        write_events(&dir);
+        let verification = verify_log(&dir).unwrap();
+        assert!(verification.valid);
+        assert_eq!(verification.records, 3);
+
+        let log = log_path(&dir);
+        let text = std::fs::read_to_string(&log).unwrap();
+        std::fs::write(&log, text.replacen("\"bytes\":400", "\"bytes\":4", 1)).unwrap();
+        let verification = verify_log(&dir).unwrap();
+        assert!(!verification.valid);
+        assert_eq!(verification.first_invalid_seq, Some(2));
+
+        // Dropping a line is caught by the next record's prev_hash
+        let lines: Vec<&str> = text.lines().collect();
+        std::fs::write(&log, format!("{}\n{}\n", lines[0], lines[2])).unwrap();
+        let verification = verify_log(&dir).unwrap();
+        assert_eq!((verification.valid, verification.first_invalid_seq), (false, Some(3)));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn exported_ranges_are_signed() {
+        let dir = temp_dir("export");
+        write_events(&dir);
+        let bundle_path = dir.join("bundle.json");
+        let range = AuditRange { from_ms: Some(2_000), to_ms: None };
+        let summary = export(&dir, range, &bundle_path, 5_000).unwrap();
+        assert_eq!((summary.records, summary.log_valid), (2, true));
+        let verification = verify_bundle(&bundle_path, &dir, None).unwrap();
+        assert!(verification.valid);
+        assert_eq!(verification.signature_valid, Some(true));
+        assert_eq!(verification.signer_fingerprint.as_deref(), Some(summary.fingerprint.as_str()));
+
+        let text = std::fs::read_to_string(&bundle_path).unwrap();
+        std::fs::write(&bundle_path, text.replacen("\"exported_at\": 5000", "\"exported_at\": 6000", 1)).unwrap();
+        let verification = verify_bundle(&bundle_path, &dir, None).unwrap();
+        assert_eq!((verification.valid, verification.signature_valid), (false, Some(false)));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn bundles_re_signed_with_another_key_are_not_valid() {
+        let dir = temp_dir("resign");
+        write_events(&dir);
+        let bundle_path = dir.join("bundle.json");
+        let summary = export(&dir, AuditRange::default(), &bundle_path, 5_000).unwrap();
+
+        // Edit a record, rebuild the chain and sign with a fresh key of the forger's own
+        let mut bundle: AuditBundle = serde_json::from_slice(&std::fs::read(&bundle_path).unwrap()).unwrap();
+        bundle.records[1].event.bytes = 4;
+        let mut prev_hash = GENESIS_HASH.to_string();
+        for record in &mut bundle.records {
+            record.prev_hash = prev_hash;
+            record.hash = record_hash(record.seq, record.timestamp, &record.event, &record.prev_hash);
+            prev_hash = record.hash.clone();
+        }
+        let forged = SigningKey::from_bytes(&[7u8; 32]);
+        bundle.public_key = hex(forged.verifying_key().as_bytes());
+        bundle.signature = hex(&forged.sign(&bundle_message(&bundle)).to_bytes());
+        std::fs::write(&bundle_path, serde_json::to_vec(&bundle).unwrap()).unwrap();
+
+        // On the exporting install, and elsewhere with the fingerprint pinned
+        let elsewhere = temp_dir("resign-auditor");
+        for (app_data_dir, expected) in [(&dir, None), (&elsewhere, Some(summary.fingerprint.as_str()))] {
+            let verification = verify_bundle(&bundle_path, app_data_dir, expected).unwrap();
+            assert_eq!(verification.signature_valid, Some(true));
+            assert_eq!((verification.valid, verification.signer_trusted), (false, Some(false)));
+            assert_eq!(verification.problem.as_deref(), Some("The bundle was signed by an unknown key"));
+        }
+        // Pinning the forger's key is the auditor's call
+        let pinned = verify_bundle(&bundle_path, &elsewhere, Some(&bundle.public_key)).unwrap();
+        assert!(pinned.valid);
+        std::fs::remove_dir_all(&dir).unwrap();
+        std::fs::remove_dir_all(&elsewhere).unwrap();
+    }
+}
@@ -0,0 +1,269 @@
+// Progress of long analyses saved to app_data_dir/checkpoints/<id>.json, so a scan of a
+// huge tree interrupted by sleep, a crash or quitting can pick up where it stopped.
+// A checkpoint holds the analysis options and every file hashed so far with the size
+// and mtime it had; resuming walks the tree again (cheap) and only hashes files that
+// are new or changed since. The format is versioned: a checkpoint written in another
+// format is rejected rather than misread.
+use crate::error::AppError;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+pub const CHECKPOINT_VERSION: u32 = 1;
+pub const DEFAULT_CHECKPOINT_EVERY: usize = 5_000;
+const CHECKPOINT_DIR: &str = "checkpoints";
+const MAX_ID_LEN: usize = 128;
+
+// What was being analyzed; a resume must run with exactly these
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AnalysisOptions {
+    DuplicateDirectories {
+        roots: Vec<String>,
+        min_similarity: Option<f64>,
+        include_hidden: Option<bool>,
+        ignored_names: Option<Vec<String>>,
+        // Absent from checkpoints saved before it existed
+        #[serde(default)]
+        follow_symlinks: bool,
+        hydrate: bool,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HashedFile {
+    pub size: u64,
+    pub modified_ms: u64,
+    pub hash: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub version: u32,
+    pub id: String,
+    pub options: AnalysisOptions,
+    // Unix time in milliseconds
+    pub created_at: u64,
+    pub updated_at: u64,
+    // Keyed by display path
+    pub hashes: HashMap<String, HashedFile>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CheckpointSummary {
+    pub id: String,
+    pub options: AnalysisOptions,
+    pub created_at: u64,
+    pub updated_at: u64,
+    pub files_hashed: usize,
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis() as u64)
+}
+
+// Ids name files, so they're limited to letters, digits, '-' and '_'
+pub fn validate_id(id: &str) -> Result<(), AppError> {
+    let valid = (1..=MAX_ID_LEN).contains(&id.len())
+        && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(AppError::invalid_input(format!("Invalid checkpoint id \"{}\"", id)))
+    }
+}
+
+fn checkpoint_path(app_data_dir: &Path, id: &str) -> PathBuf {
+    app_data_dir.join(CHECKPOINT_DIR).join(format!("{}.json", id))
+}
+
+// Only the version is read first, so a future format can't fail on its other fields
+#[derive(Deserialize)]
+struct Versioned {
+    version: u32,
+}
+
+pub fn load(app_data_dir: &Path, id: &str) -> Result<Checkpoint, AppError> {
+    validate_id(id)?;
+    let path = checkpoint_path(app_data_dir, id);
+    let text = std::fs::read_to_string(&path)
+        .map_err(|e| AppError::from_io(&e, format!("No checkpoint {}: {}", id, e)))?;
+    let invalid = |e: serde_json::Error| AppError::invalid_input(format!("Checkpoint {} is damaged: {}", id, e));
+    let Versioned { version } = serde_json::from_str(&text).map_err(invalid)?;
+    if version != CHECKPOINT_VERSION {
+        return Err(AppError::unsupported(format!(
+            "Checkpoint {} was saved in format {} and this version of the app reads format {}; \
+             start the analysis again",
+            id, version, CHECKPOINT_VERSION
+        )));
+    }
+    serde_json::from_str(&text).map_err(invalid)
+}
+
+// Written next to the checkpoint and renamed over it, so a crash mid-save keeps the last one
+pub fn save(app_data_dir: &Path, checkpoint: &Checkpoint) -> Result<(), String> {
+    let path = checkpoint_path(app_data_dir, &checkpoint.id);
+    std::fs::create_dir_all(app_data_dir.join(CHECKPOINT_DIR))
+        .map_err(|e| format!("Failed to create checkpoint directory: {}", e))?;
+    let json = serde_json::to_vec(checkpoint).map_err(|e| format!("Failed to serialize checkpoint: {}", e))?;
+    let temp = path.with_extension("json.tmp");
+    std::fs::write(&temp, json).map_err(|e| format!("Failed to write {}: {}", temp.display(), e))?;
+    std::fs::rename(&temp, &path).map_err(|e| {
+        let _ = std::fs::remove_file(&temp);
+        format!("Failed to replace {}: {}", path.display(), e)
+    })
+}
+
+pub fn delete(app_data_dir: &Path, id: &str) -> Result<bool, AppError> {
+    validate_id(id)?;
+    match std::fs::remove_file(checkpoint_path(app_data_dir, id)) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(AppError::from(e)),
+    }
+}
+
+// Newest first; unreadable checkpoints and ones in another format are left out
+pub fn list(app_data_dir: &Path) -> Vec<CheckpointSummary> {
+    let Ok(entries) = std::fs::read_dir(app_data_dir.join(CHECKPOINT_DIR)) else { return Vec::new() };
+    let mut summaries: Vec<CheckpointSummary> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let id = name.strip_suffix(".json")?;
+            load(app_data_dir, id)
+                .map_err(|e| warn!("Skipping checkpoint {}: {}", id, e.message))
+                .ok()
+        })
+        .map(|checkpoint| CheckpointSummary {
+            id: checkpoint.id,
+            options: checkpoint.options,
+            created_at: checkpoint.created_at,
+            updated_at: checkpoint.updated_at,
+            files_hashed: checkpoint.hashes.len(),
+        })
+        .collect();
+    summaries.sort_by(|a, b| b.updated_at.cmp(&a.updated_at).then_with(|| a.id.cmp(&b.id)));
+    summaries
+}
+
+// Feeds an analysis the hashes a checkpoint already has and saves the new ones every
+// `every` files
+pub struct Checkpointer {
+    app_data_dir: PathBuf,
+    checkpoint: Checkpoint,
+    every: usize,
+    unsaved: usize,
+}
+
+impl Checkpointer {
+    pub fn start(app_data_dir: &Path, options: AnalysisOptions, every: usize) -> Self {
+        let now = now_ms();
+        let checkpoint = Checkpoint {
+            version: CHECKPOINT_VERSION,
+            id: format!("analysis-{}-{}", now, std::process::id()),
+            options,
+            created_at: now,
+            updated_at: now,
+            hashes: HashMap::new(),
+        };
+        Self::resume(app_data_dir, checkpoint, every)
+    }
+
+    pub fn resume(app_data_dir: &Path, checkpoint: Checkpoint, every: usize) -> Self {
+        Self { app_data_dir: app_data_dir.to_path_buf(), checkpoint, every: every.max(1), unsaved: 0 }
+    }
+
+    pub fn id(&self) -> &str {
+        &self.checkpoint.id
+    }
+
+    // The saved hash, while the file still has the size and mtime it had then
+    pub fn known(&self, path: &str, size: u64, modified_ms: u64) -> Option<&str> {
+        self.checkpoint
+            .hashes
+            .get(path)
+            .filter(|known| known.size == size && known.modified_ms == modified_ms)
+            .map(|known| known.hash.as_str())
+    }
+
+    pub fn record(&mut self, path: String, file: HashedFile) {
+        self.checkpoint.hashes.insert(path, file);
+        self.unsaved += 1;
+        if self.unsaved >= self.every {
+            self.flush();
+        }
+    }
+
+    pub fn flush(&mut self) {
+        if self.unsaved == 0 {
+            return;
+        }
+        self.checkpoint.updated_at = now_ms();
+        match save(&self.app_data_dir, &self.checkpoint) {
+            Ok(()) => self.unsaved = 0,
+            Err(e) => warn!("Failed to save checkpoint {}: {}", self.checkpoint.id, e),
+        }
+    }
+
+    // The analysis finished, so there's nothing left to resume
+    pub fn finish(self) {
+        if let Err(e) = delete(&self.app_data_dir, &self.checkpoint.id) {
+            warn!("Failed to delete checkpoint {}: {}", self.checkpoint.id, e.message);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_dir;
+
+    fn options() -> AnalysisOptions {
+        AnalysisOptions::DuplicateDirectories {
+            roots: vec!["/archive".into()],
+            min_similarity: None,
+            include_hidden: None,
+            ignored_names: None,
+            follow_symlinks: false,
+            hydrate: false,
+        }
+    }
+
+    #[test]
+    fn hashes_survive_until_the_analysis_finishes() {
+        let dir = temp_dir("resume");
+        let mut checkpointer = Checkpointer::start(&dir, options(), 2);
+        let id = checkpointer.id().to_string();
+        let file = |hash: &str| HashedFile { size: 10, modified_ms: 5, hash: hash.into() };
+        checkpointer.record("/archive/a".into(), file("aa"));
+        assert!(list(&dir).is_empty());
+        checkpointer.record("/archive/b".into(), file("bb"));
+        assert_eq!(list(&dir)[0].files_hashed, 2);
+
+        let saved = load(&dir, &id).unwrap();
+        assert_eq!(saved.options, options());
+        let resumed = Checkpointer::resume(&dir, saved, 2);
+        assert_eq!(resumed.known("/archive/a", 10, 5), Some("aa"));
+        // Touched since the checkpoint, so it's hashed again
+        assert_eq!(resumed.known("/archive/a", 10, 6), None);
+        resumed.finish();
+        assert!(list(&dir).is_empty());
+        assert!(!delete(&dir, &id).unwrap());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn other_formats_are_rejected() {
+        let dir = temp_dir("version");
+        std::fs::create_dir_all(dir.join(CHECKPOINT_DIR)).unwrap();
+        std::fs::write(checkpoint_path(&dir, "old"), r#"{"version":0,"hashes":[]}"#).unwrap();
+        let err = load(&dir, "old").unwrap_err();
+        assert_eq!(err.kind, crate::error::AppErrorKind::Unsupported);
+        assert!(list(&dir).is_empty());
+        assert!(load(&dir, "../settings").is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,291 @@
+// Classification results remembered per file so unchanged files aren't sent to the LLM
+// again. Entries are keyed by path and validated against size + mtime; when those
+// changed the content hash decides, so a touched or copied-back file still hits.
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+const CACHE_FILE: &str = "classification_cache.sqlite3";
+// Least recently used entries beyond this are evicted
+const MAX_ENTRIES: i64 = 50_000;
+// Entries nobody asked for in this long are dropped regardless of count
+const MAX_IDLE_SECS: i64 = 180 * 24 * 60 * 60;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CachedClassification {
+    pub path: String,
+    pub content_hash: String,
+    pub category: String,
+    // Suggested file name, without extension, the file would be moved under
+    pub destination: String,
+    pub model_id: String,
+    pub cached_at: i64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct CacheStats {
+    pub entries: u64,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+// Size and modification time, the cheap check done before hashing
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QuickHash {
+    pub size: i64,
+    pub modified_ms: i64,
+}
+
+impl QuickHash {
+    pub fn of(path: &Path) -> std::io::Result<Self> {
+        let metadata = std::fs::metadata(path)?;
+        let modified_ms = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as i64)
+            .unwrap_or(0);
+        Ok(Self {
+            size: metadata.len() as i64,
+            modified_ms,
+        })
+    }
+}
+
+// A cached entry together with the file state it was recorded for
+pub struct CacheEntry {
+    pub classification: CachedClassification,
+    pub quick_hash: QuickHash,
+}
+
+pub struct ClassificationCache {
+    conn: Connection,
+}
+
+fn now_secs() -> i64 {
+    crate::scheduler::now_secs() as i64
+}
+
+fn sql_error(e: rusqlite::Error) -> String {
+    format!("Classification cache error: {}", e)
+}
+
+impl ClassificationCache {
+    pub fn open(app_data_dir: &Path) -> Result<Self, String> {
+        std::fs::create_dir_all(app_data_dir)
+            .map_err(|e| format!("Failed to create app data directory: {}", e))?;
+        let conn = Connection::open(app_data_dir.join(CACHE_FILE)).map_err(sql_error)?;
+        Self::init(conn)
+    }
+
+    #[cfg(test)]
+    fn in_memory() -> Result<Self, String> {
+        Self::init(Connection::open_in_memory().map_err(sql_error)?)
+    }
+
+    fn init(conn: Connection) -> Result<Self, String> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS classifications (
+                path TEXT PRIMARY KEY,
+                size INTEGER NOT NULL,
+                modified_ms INTEGER NOT NULL,
+                content_hash TEXT NOT NULL,
+                category TEXT NOT NULL,
+                destination TEXT NOT NULL,
+                model_id TEXT NOT NULL,
+                cached_at INTEGER NOT NULL,
+                last_used_at INTEGER NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS classifications_last_used ON classifications (last_used_at);
+            CREATE TABLE IF NOT EXISTS cache_stats (
+                name TEXT PRIMARY KEY,
+                value INTEGER NOT NULL
+            );",
+        )
+        .map_err(sql_error)?;
+        Ok(Self { conn })
+    }
+
+    pub fn get(&self, path: &str) -> Result<Option<CacheEntry>, String> {
+        self.conn
+            .query_row(
+                "SELECT path, content_hash, category, destination, model_id, cached_at, size, modified_ms
+                 FROM classifications WHERE path = ?1",
+                params![path],
+                |row| {
+                    Ok(CacheEntry {
+                        classification: CachedClassification {
+                            path: row.get(0)?,
+                            content_hash: row.get(1)?,
+                            category: row.get(2)?,
+                            destination: row.get(3)?,
+                            model_id: row.get(4)?,
+                            cached_at: row.get(5)?,
+                        },
+                        quick_hash: QuickHash {
+                            size: row.get(6)?,
+                            modified_ms: row.get(7)?,
+                        },
+                    })
+                },
+            )
+            .optional()
+            .map_err(sql_error)
+    }
+
+    pub fn put(&self, classification: &CachedClassification, quick_hash: QuickHash) -> Result<(), String> {
+        let now = now_secs();
+        self.conn
+            .execute(
+                "INSERT OR REPLACE INTO classifications
+                 (path, size, modified_ms, content_hash, category, destination, model_id, cached_at, last_used_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?8)",
+                params![
+                    classification.path,
+                    quick_hash.size,
+                    quick_hash.modified_ms,
+                    classification.content_hash,
+                    classification.category,
+                    classification.destination,
+                    classification.model_id,
+                    now,
+                ],
+            )
+            .map_err(sql_error)?;
+        self.evict(now)
+    }
+
+    // Marks an entry as used, refreshing the quick hash when it was revalidated by content
+    pub fn touch(&self, path: &str, quick_hash: QuickHash) -> Result<(), String> {
+        self.conn
+            .execute(
+                "UPDATE classifications SET size = ?2, modified_ms = ?3, last_used_at = ?4 WHERE path = ?1",
+                params![path, quick_hash.size, quick_hash.modified_ms, now_secs()],
+            )
+            .map(|_| ())
+            .map_err(sql_error)
+    }
+
+    pub fn remove(&self, path: &str) -> Result<(), String> {
+        self.conn
+            .execute("DELETE FROM classifications WHERE path = ?1", params![path])
+            .map(|_| ())
+            .map_err(sql_error)
+    }
+
+    fn evict(&self, now: i64) -> Result<(), String> {
+        self.conn
+            .execute(
+                "DELETE FROM classifications WHERE last_used_at < ?1",
+                params![now - MAX_IDLE_SECS],
+            )
+            .map_err(sql_error)?;
+        self.conn
+            .execute(
+                "DELETE FROM classifications WHERE path IN (
+                    SELECT path FROM classifications ORDER BY last_used_at DESC LIMIT -1 OFFSET ?1
+                )",
+                params![MAX_ENTRIES],
+            )
+            .map(|_| ())
+            .map_err(sql_error)
+    }
+
+    pub fn record_lookup(&self, hit: bool) -> Result<(), String> {
+        self.conn
+            .execute(
+                "INSERT INTO cache_stats (name, value) VALUES (?1, 1)
+                 ON CONFLICT(name) DO UPDATE SET value = value + 1",
+                params![if hit { "hits" } else { "misses" }],
+            )
+            .map(|_| ())
+            .map_err(sql_error)
+    }
+
+    pub fn stats(&self) -> Result<CacheStats, String> {
+        let stat = |name: &str| -> Result<u64, String> {
+            self.conn
+                .query_row("SELECT value FROM cache_stats WHERE name = ?1", params![name], |row| row.get::<_, i64>(0))
+                .optional()
+                .map(|value| value.unwrap_or(0) as u64)
+                .map_err(sql_error)
+        };
+        let entries: i64 = self
+            .conn
+            .query_row("SELECT COUNT(*) FROM classifications", [], |row| row.get(0))
+            .map_err(sql_error)?;
+        Ok(CacheStats {
+            entries: entries as u64,
+            hits: stat("hits")?,
+            misses: stat("misses")?,
+        })
+    }
+
+    // Drops every entry and resets the hit/miss counters
+    pub fn clear(&self) -> Result<(), String> {
+        self.conn
+            .execute_batch("DELETE FROM classifications; DELETE FROM cache_stats;")
+            .map_err(sql_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn classification(path: &str, model_id: &str) -> CachedClassification {
+        CachedClassification {
+            path: path.to_string(),
+            content_hash: "abc123".to_string(),
+            category: "Finance/Invoices".to_string(),
+            destination: "acme-invoice-2024-03".to_string(),
+            model_id: model_id.to_string(),
+            cached_at: 0,
+        }
+    }
+
+    #[test]
+    fn entries_round_trip_and_can_be_replaced() {
+        let cache = ClassificationCache::in_memory().unwrap();
+        let quick_hash = QuickHash { size: 10, modified_ms: 1_000 };
+        cache.put(&classification("/docs/a.pdf", "openai:gpt-4o-mini"), quick_hash).unwrap();
+
+        let entry = cache.get("/docs/a.pdf").unwrap().unwrap();
+        assert_eq!(entry.quick_hash, quick_hash);
+        assert_eq!(entry.classification.category, "Finance/Invoices");
+        assert!(entry.classification.cached_at > 0);
+
+        cache.put(&classification("/docs/a.pdf", "ollama:llama3"), quick_hash).unwrap();
+        let entry = cache.get("/docs/a.pdf").unwrap().unwrap();
+        assert_eq!(entry.classification.model_id, "ollama:llama3");
+        assert!(cache.get("/docs/missing.pdf").unwrap().is_none());
+    }
+
+    #[test]
+    fn stale_entries_are_evicted() {
+        let cache = ClassificationCache::in_memory().unwrap();
+        let quick_hash = QuickHash { size: 1, modified_ms: 1 };
+        cache.put(&classification("/docs/old.txt", "m"), quick_hash).unwrap();
+        cache
+            .conn
+            .execute("UPDATE classifications SET last_used_at = 0", [])
+            .unwrap();
+
+        cache.put(&classification("/docs/new.txt", "m"), quick_hash).unwrap();
+        assert!(cache.get("/docs/old.txt").unwrap().is_none());
+        assert!(cache.get("/docs/new.txt").unwrap().is_some());
+    }
+
+    #[test]
+    fn lookups_are_counted_until_cleared() {
+        let cache = ClassificationCache::in_memory().unwrap();
+        cache.put(&classification("/docs/a.pdf", "m"), QuickHash { size: 1, modified_ms: 1 }).unwrap();
+        cache.record_lookup(true).unwrap();
+        cache.record_lookup(true).unwrap();
+        cache.record_lookup(false).unwrap();
+        assert_eq!(cache.stats().unwrap(), CacheStats { entries: 1, hits: 2, misses: 1 });
+
+        cache.clear().unwrap();
+        assert_eq!(cache.stats().unwrap(), CacheStats::default());
+    }
+}
@@ -0,0 +1,260 @@
+// A/B comparison of two backends' classifications of the same files, to help choose
+// between models. Categories are compared case-insensitively after normalizing path
+// separators, so "Finance\Taxes" and "finance/taxes/" agree. Every row goes into a CSV
+// report; the summary keeps the at-a-glance numbers and the most common disagreements.
+use crate::paths;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+// Disagreements listed in the summary; the report has every row
+const MAX_CONFUSION_PAIRS: usize = 25;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ClassificationOutcome {
+    pub category_path: String,
+    #[serde(default)]
+    pub suggested_filename: Option<String>,
+    // 0..1 when the backend reports one
+    #[serde(default)]
+    pub confidence: Option<f64>,
+}
+
+// A missing result means that backend failed on the file
+#[derive(Debug, Clone, Deserialize)]
+pub struct ComparedFile {
+    pub path: String,
+    pub result_a: Option<ClassificationOutcome>,
+    pub result_b: Option<ClassificationOutcome>,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ConfusionPair {
+    pub category_a: String,
+    pub category_b: String,
+    pub count: usize,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ComparisonSummary {
+    pub files: usize,
+    // Files both backends classified
+    pub compared: usize,
+    pub failed_a: usize,
+    pub failed_b: usize,
+    pub exact_matches: usize,
+    // exact_matches / compared, 0 when nothing was compared
+    pub match_rate: f64,
+    // Share of compared files whose first category segment agrees
+    pub top_level_match_rate: f64,
+    pub average_confidence_a: Option<f64>,
+    pub average_confidence_b: Option<f64>,
+    // Most frequent disagreements first
+    pub confusion: Vec<ConfusionPair>,
+    pub report_path: Option<String>,
+}
+
+// Trimmed segments joined by "/", dropping empty ones
+fn normalize_category(category: &str) -> String {
+    category
+        .split(['/', '\\'])
+        .map(str::trim)
+        .filter(|segment| !segment.is_empty())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn same_category(a: &str, b: &str) -> bool {
+    a.to_lowercase() == b.to_lowercase()
+}
+
+fn top_level(category: &str) -> &str {
+    category.split('/').next().unwrap_or_default()
+}
+
+fn average(values: &[f64]) -> Option<f64> {
+    (!values.is_empty()).then(|| values.iter().sum::<f64>() / values.len() as f64)
+}
+
+fn ratio(count: usize, total: usize) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        count as f64 / total as f64
+    }
+}
+
+pub fn summarize(rows: &[ComparedFile]) -> ComparisonSummary {
+    let mut summary = ComparisonSummary { files: rows.len(), ..Default::default() };
+    let (mut confidences_a, mut confidences_b) = (Vec::new(), Vec::new());
+    let mut top_level_matches = 0;
+    // Keyed by the lowercased pair; the first spelling seen is the one reported
+    let mut confusion: HashMap<(String, String), ConfusionPair> = HashMap::new();
+
+    for row in rows {
+        let confidence = |result: &Option<ClassificationOutcome>| result.as_ref().and_then(|r| r.confidence);
+        confidences_a.extend(confidence(&row.result_a).filter(|c| c.is_finite()));
+        confidences_b.extend(confidence(&row.result_b).filter(|c| c.is_finite()));
+        let (a, b) = match (&row.result_a, &row.result_b) {
+            (Some(a), Some(b)) => (normalize_category(&a.category_path), normalize_category(&b.category_path)),
+            (a, b) => {
+                summary.failed_a += usize::from(a.is_none());
+                summary.failed_b += usize::from(b.is_none());
+                continue;
+            }
+        };
+        summary.compared += 1;
+        if same_category(top_level(&a), top_level(&b)) {
+            top_level_matches += 1;
+        }
+        if same_category(&a, &b) {
+            summary.exact_matches += 1;
+            continue;
+        }
+        confusion
+            .entry((a.to_lowercase(), b.to_lowercase()))
+            .or_insert_with(|| ConfusionPair { category_a: a, category_b: b, count: 0 })
+            .count += 1;
+    }
+
+    summary.match_rate = ratio(summary.exact_matches, summary.compared);
+    summary.top_level_match_rate = ratio(top_level_matches, summary.compared);
+    summary.average_confidence_a = average(&confidences_a);
+    summary.average_confidence_b = average(&confidences_b);
+    let mut pairs: Vec<ConfusionPair> = confusion.into_values().collect();
+    pairs.sort_by(|x, y| {
+        y.count.cmp(&x.count).then_with(|| (&x.category_a, &x.category_b).cmp(&(&y.category_a, &y.category_b)))
+    });
+    pairs.truncate(MAX_CONFUSION_PAIRS);
+    summary.confusion = pairs;
+    summary
+}
+
+// Quoted when it holds a delimiter, quote or line break (RFC 4180)
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+// One line per file: both categories, whether they agree, confidences and suggested names
+pub fn write_report(path: &Path, rows: &[ComparedFile], label_a: &str, label_b: &str) -> std::io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    let header = [
+        "path".to_string(),
+        format!("category_{}", label_a),
+        format!("category_{}", label_b),
+        "match".to_string(),
+        format!("confidence_{}", label_a),
+        format!("confidence_{}", label_b),
+        format!("filename_{}", label_a),
+        format!("filename_{}", label_b),
+    ];
+    writeln!(out, "{}", header.iter().map(|h| csv_field(h)).collect::<Vec<_>>().join(","))?;
+    for row in rows {
+        let category = |r: &Option<ClassificationOutcome>| {
+            r.as_ref().map(|r| normalize_category(&r.category_path)).unwrap_or_default()
+        };
+        let confidence = |r: &Option<ClassificationOutcome>| {
+            r.as_ref().and_then(|r| r.confidence).map(|c| format!("{:.3}", c)).unwrap_or_default()
+        };
+        let filename = |r: &Option<ClassificationOutcome>| {
+            r.as_ref().and_then(|r| r.suggested_filename.clone()).unwrap_or_default()
+        };
+        let verdict = match (&row.result_a, &row.result_b) {
+            (Some(_), Some(_)) if same_category(&category(&row.result_a), &category(&row.result_b)) => "yes",
+            (Some(_), Some(_)) => "no",
+            (None, _) => "failed_a",
+            (_, None) => "failed_b",
+        };
+        let fields = [
+            row.path.clone(),
+            category(&row.result_a),
+            category(&row.result_b),
+            verdict.to_string(),
+            confidence(&row.result_a),
+            confidence(&row.result_b),
+            filename(&row.result_a),
+            filename(&row.result_b),
+        ];
+        writeln!(out, "{}", fields.iter().map(|f| csv_field(f)).collect::<Vec<_>>().join(","))?;
+    }
+    out.flush()
+}
+
+pub fn compare(
+    rows: &[ComparedFile],
+    report_path: Option<&Path>,
+    label_a: &str,
+    label_b: &str,
+) -> std::io::Result<ComparisonSummary> {
+    let mut summary = summarize(rows);
+    if let Some(path) = report_path {
+        write_report(path, rows, label_a, label_b)?;
+        summary.report_path = Some(paths::display_path(path));
+    }
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_dir;
+    use std::fs;
+
+    fn outcome(category: &str, confidence: Option<f64>) -> Option<ClassificationOutcome> {
+        Some(ClassificationOutcome {
+            category_path: category.to_string(),
+            suggested_filename: Some("name, with comma".to_string()),
+            confidence,
+        })
+    }
+
+    fn row(path: &str, a: Option<ClassificationOutcome>, b: Option<ClassificationOutcome>) -> ComparedFile {
+        ComparedFile { path: path.to_string(), result_a: a, result_b: b }
+    }
+
+    #[test]
+    fn agreement_and_confusion_are_counted() {
+        let rows = [
+            row("/a", outcome("Finance\\Taxes", Some(0.9)), outcome("finance/taxes/", Some(0.5))),
+            row("/b", outcome("Finance/Taxes", Some(0.7)), outcome("Finance/Invoices", None)),
+            row("/c", outcome("Photos", None), outcome("Finance/Invoices", Some(0.7))),
+            row("/d", outcome("Finance/Taxes", None), outcome("finance/invoices", None)),
+            row("/e", None, outcome("Photos", Some(0.2))),
+        ];
+        let summary = summarize(&rows);
+        assert_eq!((summary.files, summary.compared, summary.failed_a, summary.failed_b), (5, 4, 1, 0));
+        assert_eq!(summary.exact_matches, 1);
+        assert_eq!(summary.match_rate, 0.25);
+        assert_eq!(summary.top_level_match_rate, 0.75);
+        assert_eq!(summary.average_confidence_a, Some(0.8));
+        assert_eq!(summary.average_confidence_b, Some((0.5 + 0.7 + 0.2) / 3.0));
+        assert_eq!(summary.confusion[0], ConfusionPair {
+            category_a: "Finance/Taxes".into(),
+            category_b: "Finance/Invoices".into(),
+            count: 2,
+        });
+        assert_eq!(summary.confusion.len(), 2);
+        assert_eq!(summarize(&[]).match_rate, 0.0);
+    }
+
+    #[test]
+    fn report_has_a_line_per_file() {
+        let dir = temp_dir("compare-report");
+        let report = dir.join("comparison.csv");
+        let rows = [row("/x", outcome("Docs", Some(0.5)), None)];
+        let summary = compare(&rows, Some(&report), "local", "remote").unwrap();
+        assert!(summary.report_path.is_some());
+        let text = fs::read_to_string(&report).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], "path,category_local,category_remote,match,confidence_local,confidence_remote,\
+filename_local,filename_remote");
+        assert_eq!(lines[1], "/x,Docs,,failed_b,0.500,,\"name, with comma\",");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,100 @@
+// Cloud sync placeholders: files OneDrive, Dropbox, iCloud Drive and other file
+// providers list locally but only download when their contents are read. Hashing or
+// extracting one pulls the whole file from the cloud, so analysis skips them unless
+// hydration was asked for. Listing and moving them is fine; neither reads contents.
+use crate::error::{AppError, AppErrorKind};
+use crate::i18n;
+use crate::paths;
+use crate::settings;
+use std::fs::Metadata;
+use std::path::Path;
+
+// Windows Cloud Files attributes: contents are remote, or fetched on open/first read
+#[cfg(any(windows, test))]
+const FILE_ATTRIBUTE_OFFLINE: u32 = 0x1000;
+#[cfg(any(windows, test))]
+const FILE_ATTRIBUTE_RECALL_ON_OPEN: u32 = 0x4_0000;
+#[cfg(any(windows, test))]
+const FILE_ATTRIBUTE_RECALL_ON_DATA_ACCESS: u32 = 0x40_0000;
+
+// macOS marks files whose contents a file provider has evicted as dataless
+#[cfg(any(target_os = "macos", test))]
+const SF_DATALESS: u32 = 0x4000_0000;
+
+#[cfg(any(windows, test))]
+fn attributes_mark_placeholder(attributes: u32) -> bool {
+    attributes & (FILE_ATTRIBUTE_OFFLINE | FILE_ATTRIBUTE_RECALL_ON_OPEN | FILE_ATTRIBUTE_RECALL_ON_DATA_ACCESS) != 0
+}
+
+#[cfg(any(target_os = "macos", test))]
+fn flags_mark_placeholder(flags: u32) -> bool {
+    flags & SF_DATALESS != 0
+}
+
+#[cfg(windows)]
+pub fn is_placeholder_metadata(metadata: &Metadata) -> bool {
+    use std::os::windows::fs::MetadataExt;
+    attributes_mark_placeholder(metadata.file_attributes())
+}
+
+#[cfg(target_os = "macos")]
+pub fn is_placeholder_metadata(metadata: &Metadata) -> bool {
+    use std::os::macos::fs::MetadataExt;
+    flags_mark_placeholder(metadata.st_flags())
+}
+
+// Linux sync clients download everything, so there are no placeholders to find
+#[cfg(not(any(windows, target_os = "macos")))]
+pub fn is_placeholder_metadata(_metadata: &Metadata) -> bool {
+    false
+}
+
+// Reads only metadata, which never triggers a download
+pub fn is_placeholder(path: &Path) -> bool {
+    std::fs::symlink_metadata(path).is_ok_and(|metadata| is_placeholder_metadata(&metadata))
+}
+
+// Whether placeholders are read (and so downloaded) when no call says otherwise
+pub fn hydrate_by_default() -> bool {
+    settings::with(|settings| settings.scan.hydrate_cloud_placeholders)
+}
+
+// Whether reading `path` is allowed under the default hydration setting
+pub fn may_read(path: &Path) -> bool {
+    hydrate_by_default() || !is_placeholder(path)
+}
+
+// Refuses to read a placeholder unless `hydrate` is set
+pub fn ensure_local(path: &Path, hydrate: bool) -> Result<(), AppError> {
+    if hydrate || !is_placeholder(path) {
+        return Ok(());
+    }
+    Err(AppError::new(AppErrorKind::CloudPlaceholder, i18n::t("error.cloud_placeholder"))
+        .with_path(paths::display_path(path)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_dir;
+
+    #[test]
+    fn recall_and_dataless_flags_mark_placeholders() {
+        assert!(attributes_mark_placeholder(0x20 | FILE_ATTRIBUTE_RECALL_ON_DATA_ACCESS));
+        assert!(attributes_mark_placeholder(FILE_ATTRIBUTE_OFFLINE));
+        // Archive + pinned: downloaded and kept local
+        assert!(!attributes_mark_placeholder(0x20 | 0x8_0000));
+        assert!(flags_mark_placeholder(SF_DATALESS));
+        assert!(!flags_mark_placeholder(0x20));
+    }
+
+    #[test]
+    fn local_files_can_be_read() {
+        let dir = temp_dir("cloud");
+        let file = dir.join("local.txt");
+        std::fs::write(&file, b"local").unwrap();
+        assert!(!is_placeholder(&file));
+        assert!(ensure_local(&file, false).is_ok());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
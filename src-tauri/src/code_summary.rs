@@ -0,0 +1,604 @@
+// Source files reduced to what says what they are: the language, the comments at the
+// top, and the outline of declarations with function bodies collapsed to "…". There
+// are no parsers here, only per-language rules about braces, indentation and `end`,
+// so odd code may keep more than it needs to; it never drops a top-level line that
+// isn't inside a collapsed body.
+use regex::Regex;
+use serde::Serialize;
+use std::path::Path;
+use std::sync::OnceLock;
+
+// String literals with more characters than this become "…"
+const LONG_STRING_CHARS: usize = 48;
+// Lines a multi-line Python signature may span before it's treated as code
+const MAX_SIGNATURE_LINES: usize = 20;
+const ELLIPSIS: &str = "…";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Language {
+    Rust,
+    Go,
+    C,
+    Cpp,
+    CSharp,
+    Java,
+    Kotlin,
+    Scala,
+    Swift,
+    JavaScript,
+    TypeScript,
+    Php,
+    Shell,
+    Python,
+    Ruby,
+    Lua,
+}
+
+// How a language delimits bodies
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Blocks {
+    Braces,
+    // Python
+    Indentation,
+    // Ruby and Lua: a keyword line up to its `end`
+    End,
+}
+
+// Extensions read as source code
+pub const CODE_EXTENSIONS: &[(&str, Language)] = &[
+    ("rs", Language::Rust),
+    ("go", Language::Go),
+    ("c", Language::C),
+    ("h", Language::C),
+    ("cc", Language::Cpp),
+    ("cpp", Language::Cpp),
+    ("cxx", Language::Cpp),
+    ("hh", Language::Cpp),
+    ("hpp", Language::Cpp),
+    ("cs", Language::CSharp),
+    ("java", Language::Java),
+    ("kt", Language::Kotlin),
+    ("kts", Language::Kotlin),
+    ("scala", Language::Scala),
+    ("swift", Language::Swift),
+    ("js", Language::JavaScript),
+    ("mjs", Language::JavaScript),
+    ("cjs", Language::JavaScript),
+    ("jsx", Language::JavaScript),
+    ("ts", Language::TypeScript),
+    ("mts", Language::TypeScript),
+    ("tsx", Language::TypeScript),
+    ("php", Language::Php),
+    ("sh", Language::Shell),
+    ("bash", Language::Shell),
+    ("zsh", Language::Shell),
+    ("py", Language::Python),
+    ("pyw", Language::Python),
+    ("rb", Language::Ruby),
+    ("lua", Language::Lua),
+];
+
+// Interpreters named on a #! line, matched with any version suffix ("python3.12")
+const INTERPRETERS: &[(&str, Language)] = &[
+    ("python", Language::Python),
+    ("node", Language::JavaScript),
+    ("deno", Language::TypeScript),
+    ("bash", Language::Shell),
+    ("zsh", Language::Shell),
+    ("sh", Language::Shell),
+    ("ruby", Language::Ruby),
+    ("php", Language::Php),
+    ("lua", Language::Lua),
+];
+
+impl Language {
+    pub fn name(self) -> &'static str {
+        match self {
+            Language::Rust => "rust",
+            Language::Go => "go",
+            Language::C => "c",
+            Language::Cpp => "cpp",
+            Language::CSharp => "csharp",
+            Language::Java => "java",
+            Language::Kotlin => "kotlin",
+            Language::Scala => "scala",
+            Language::Swift => "swift",
+            Language::JavaScript => "javascript",
+            Language::TypeScript => "typescript",
+            Language::Php => "php",
+            Language::Shell => "shell",
+            Language::Python => "python",
+            Language::Ruby => "ruby",
+            Language::Lua => "lua",
+        }
+    }
+
+    fn blocks(self) -> Blocks {
+        match self {
+            Language::Python => Blocks::Indentation,
+            Language::Ruby | Language::Lua => Blocks::End,
+            _ => Blocks::Braces,
+        }
+    }
+
+    fn line_comments(self) -> &'static [&'static str] {
+        match self {
+            Language::Python | Language::Ruby | Language::Shell => &["#"],
+            Language::Lua => &["--"],
+            Language::Php => &["//", "#"],
+            _ => &["//"],
+        }
+    }
+
+    fn block_comments(self) -> bool {
+        !matches!(self, Language::Python | Language::Ruby | Language::Shell | Language::Lua)
+    }
+
+    // Whether '...' is a string rather than a character literal or a lifetime
+    fn single_quoted_strings(self) -> bool {
+        matches!(
+            self,
+            Language::JavaScript
+                | Language::TypeScript
+                | Language::Php
+                | Language::Shell
+                | Language::Python
+                | Language::Ruby
+                | Language::Lua
+        )
+    }
+
+    fn backtick_strings(self) -> bool {
+        matches!(self, Language::JavaScript | Language::TypeScript | Language::Go)
+    }
+}
+
+pub fn language_for_extension(extension: &str) -> Option<Language> {
+    CODE_EXTENSIONS.iter().find(|(known, _)| known.eq_ignore_ascii_case(extension)).map(|(_, language)| *language)
+}
+
+fn language_for_shebang(first_line: &str) -> Option<Language> {
+    let mut words = first_line.strip_prefix("#!")?.split_whitespace();
+    let mut program = words.next()?.rsplit('/').next()?;
+    if program == "env" {
+        program = words.find(|word| !word.starts_with('-'))?;
+    }
+    INTERPRETERS.iter().find_map(|(name, language)| {
+        let version = program.strip_prefix(name)?;
+        version.chars().all(|c| c.is_ascii_digit() || c == '.').then_some(*language)
+    })
+}
+
+// From the extension, or the #! line of files without a known one
+pub fn detect_language(path: &Path, text: &str) -> Option<Language> {
+    let extension = path.extension().map(|e| e.to_string_lossy()).unwrap_or_default();
+    language_for_extension(&extension).or_else(|| language_for_shebang(text.lines().next()?))
+}
+
+// Unix line endings, no trailing whitespace, at most one blank line in a row
+pub fn normalize_whitespace(text: &str) -> String {
+    let text = text.replace("\r\n", "\n").replace('\r', "\n");
+    let mut normalized = String::with_capacity(text.len());
+    let mut previous_blank = true;
+    for line in text.lines().map(str::trim_end) {
+        if line.is_empty() && previous_blank {
+            continue;
+        }
+        previous_blank = line.is_empty();
+        normalized.push_str(line);
+        normalized.push('\n');
+    }
+    normalized.truncate(normalized.trim_end().len());
+    normalized
+}
+
+// Lines of comments (and a Python module docstring) before the first line of code
+fn header_len(lines: &[&str], language: Language) -> usize {
+    let mut closing: Option<&str> = None;
+    for (index, line) in lines.iter().enumerate() {
+        let line = line.trim();
+        if let Some(end) = closing {
+            if line.contains(end) {
+                closing = None;
+            }
+            continue;
+        }
+        let comment = language.line_comments().iter().any(|prefix| line.starts_with(prefix));
+        if line.is_empty() || comment || (index == 0 && (line.starts_with("#!") || line.starts_with("<?php"))) {
+            continue;
+        }
+        if language.block_comments() && line.starts_with("/*") {
+            closing = (!line[2..].contains("*/")).then_some("*/");
+            continue;
+        }
+        let docstring = line.trim_start_matches(['r', 'u', 'R', 'U']);
+        if language == Language::Python && (docstring.starts_with("\"\"\"") || docstring.starts_with("'''")) {
+            let quotes = &docstring[..3];
+            closing = (!docstring[3..].contains(quotes)).then_some(quotes);
+            continue;
+        }
+        return index;
+    }
+    lines.len()
+}
+
+struct StringPatterns {
+    triple: Regex,
+    double: Regex,
+    single: Regex,
+    backtick: Regex,
+}
+
+fn string_patterns() -> &'static StringPatterns {
+    static PATTERNS: OnceLock<StringPatterns> = OnceLock::new();
+    PATTERNS.get_or_init(|| StringPatterns {
+        triple: Regex::new(r#"(?s)""".*?"""|'''.*?'''"#).unwrap(),
+        double: Regex::new(r#""(?:[^"\\\n]|\\.)*""#).unwrap(),
+        single: Regex::new(r"'(?:[^'\\\n]|\\.)*'").unwrap(),
+        backtick: Regex::new(r"(?s)`(?:[^`\\]|\\.)*`").unwrap(),
+    })
+}
+
+fn shorten_long_strings(text: &str, pattern: &Regex, quote: &str) -> String {
+    pattern
+        .replace_all(text, |found: &regex::Captures<'_>| {
+            let literal = &found[0];
+            if literal.chars().count() > LONG_STRING_CHARS + 2 * quote.len() {
+                format!("{}{}{}", quote, ELLIPSIS, quote)
+            } else {
+                literal.to_string()
+            }
+        })
+        .into_owned()
+}
+
+fn strip_long_strings(text: &str, language: Language) -> String {
+    let patterns = string_patterns();
+    let mut text = text.to_string();
+    if language == Language::Python {
+        text = shorten_long_strings(&text, &patterns.triple, "\"\"\"");
+    }
+    if language.backtick_strings() {
+        text = shorten_long_strings(&text, &patterns.backtick, "`");
+    }
+    text = shorten_long_strings(&text, &patterns.double, "\"");
+    if language.single_quoted_strings() {
+        text = shorten_long_strings(&text, &patterns.single, "'");
+    }
+    text
+}
+
+// Byte offsets of the braces on `line` outside strings and comments. `in_comment`
+// carries a /* */ comment from one line to the next.
+fn braces(line: &str, language: Language, in_comment: &mut bool) -> Vec<(usize, bool)> {
+    let bytes = line.as_bytes();
+    let mut found = Vec::new();
+    let mut index = 0;
+    let skip_quoted = |start: usize, quote: u8| {
+        let mut end = start + 1;
+        while end < bytes.len() && bytes[end] != quote {
+            end += if bytes[end] == b'\\' { 2 } else { 1 };
+        }
+        end + 1
+    };
+    while index < bytes.len() {
+        let rest = &line[index..];
+        if *in_comment {
+            match rest.find("*/") {
+                Some(end) => {
+                    *in_comment = false;
+                    index += end + 2;
+                    continue;
+                }
+                None => break,
+            }
+        }
+        let after_space = index == 0 || bytes[index - 1].is_ascii_whitespace();
+        let comment = language.line_comments().iter().any(|prefix| rest.starts_with(prefix));
+        // In shell scripts `#` only starts a comment as a word of its own (`${#list[@]}`)
+        if comment && (language != Language::Shell || after_space) {
+            break;
+        }
+        if language.block_comments() && rest.starts_with("/*") {
+            *in_comment = true;
+            index += 2;
+            continue;
+        }
+        index = match bytes[index] {
+            b'"' => skip_quoted(index, b'"'),
+            b'`' if language.backtick_strings() => skip_quoted(index, b'`'),
+            b'\'' if language.single_quoted_strings() => skip_quoted(index, b'\''),
+            // Character literals like '{'; a lone quote is a lifetime or a label
+            b'\'' if bytes.get(index + 2) == Some(&b'\'') => index + 3,
+            b'\'' if bytes.get(index + 1) == Some(&b'\\') && bytes.get(index + 3) == Some(&b'\'') => index + 4,
+            b'{' | b'}' => {
+                found.push((index, bytes[index] == b'{'));
+                index + 1
+            }
+            // Whole characters, so `rest` always starts on a char boundary
+            _ => index + rest.chars().next().map_or(1, char::len_utf8),
+        };
+    }
+    found
+}
+
+fn container_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| {
+        Regex::new(
+            r"\b(class|struct|interface|trait|impl|enum|union|namespace|module|mod|object|extension|protocol|record|extern)\b",
+        )
+        .unwrap()
+    })
+}
+
+fn function_keyword() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"\b(fn|func|function|fun|def)\b").unwrap())
+}
+
+// Type-like blocks whose members stay visible; everything else opened by a brace is a
+// body (functions, closures, object literals, top-level statements) and is collapsed
+fn opens_container(line: &str) -> bool {
+    let parameters = line.find('(').unwrap_or(line.len());
+    let before = &line[..parameters];
+    container_pattern().is_match(before) && !function_keyword().is_match(before)
+}
+
+fn indentation(line: &str) -> &str {
+    &line[..line.len() - line.trim_start().len()]
+}
+
+fn collapse_braces(lines: &[&str], language: Language) -> Vec<String> {
+    let mut kept = Vec::new();
+    let mut depth = 0usize;
+    // Depth at which the body being skipped was opened
+    let mut skipping: Option<usize> = None;
+    let mut in_comment = false;
+    let mut previous = "";
+    for line in lines {
+        let found = braces(line, language, &mut in_comment);
+        let before = depth;
+        let mut unclosed = Vec::new();
+        for (offset, open) in found {
+            if open {
+                unclosed.push(offset);
+                depth += 1;
+            } else {
+                unclosed.pop();
+                depth = depth.saturating_sub(1);
+            }
+        }
+        if let Some(opened_at) = skipping {
+            if depth <= opened_at {
+                skipping = None;
+            }
+            continue;
+        }
+        let Some(&first_open) = unclosed.first().filter(|_| depth > before) else {
+            kept.push(line.to_string());
+            if !line.trim().is_empty() {
+                previous = line;
+            }
+            continue;
+        };
+        // A brace on its own line belongs to the declaration above it
+        let opener = if line.trim() == "{" { previous } else { line };
+        if opens_container(opener) {
+            kept.push(line.to_string());
+        } else {
+            kept.push(format!("{} {} }}", line[..=first_open].trim_end(), ELLIPSIS));
+            skipping = Some(before);
+        }
+        previous = line;
+    }
+    kept
+}
+
+fn python_compound(code: &str) -> bool {
+    const KEYWORDS: [&str; 13] = [
+        "def ", "async ", "if ", "elif ", "else", "for ", "while ", "with ", "try", "except", "finally", "match ",
+        "case ",
+    ];
+    KEYWORDS.iter().any(|keyword| code.starts_with(keyword))
+}
+
+fn without_comment(line: &str) -> &str {
+    line.split(" #").next().unwrap_or(line).trim_end()
+}
+
+fn paren_balance(line: &str) -> isize {
+    line.chars()
+        .map(|c| match c {
+            '(' | '[' | '{' => 1,
+            ')' | ']' | '}' => -1,
+            _ => 0,
+        })
+        .sum()
+}
+
+// Index after the lines indented deeper than `indent` from `index` on; blank lines
+// inside the run are part of it, blank lines after it aren't
+fn skip_deeper(lines: &[&str], mut index: usize, indent: usize) -> usize {
+    let mut end = index;
+    while index < lines.len() && (lines[index].trim().is_empty() || indentation(lines[index]).len() > indent) {
+        index += 1;
+        if !lines[index - 1].trim().is_empty() {
+            end = index;
+        }
+    }
+    end
+}
+
+fn collapse_python(lines: &[&str]) -> Vec<String> {
+    let mut kept = Vec::new();
+    let mut index = 0;
+    while index < lines.len() {
+        let line = lines[index];
+        let code = line.trim();
+        if !python_compound(code) {
+            kept.push(line.to_string());
+            index += 1;
+            continue;
+        }
+        // The statement's header may span lines until its brackets close
+        let mut end = index;
+        let mut balance = paren_balance(without_comment(line));
+        while balance > 0 && end + 1 < lines.len() && end - index < MAX_SIGNATURE_LINES {
+            end += 1;
+            balance += paren_balance(without_comment(lines[end]));
+        }
+        kept.extend(lines[index..=end].iter().map(|line| line.to_string()));
+        index = end + 1;
+        // Bodies on the header's own line ("if x: return") have nothing below to skip
+        if without_comment(lines[end]).ends_with(':') {
+            let indent = indentation(line);
+            let body_start = index;
+            index = skip_deeper(lines, index, indent.len());
+            if index > body_start {
+                kept.push(format!("{}    {}", indent, ELLIPSIS));
+            }
+        }
+    }
+    kept
+}
+
+fn end_block_opener() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| {
+        Regex::new(r"^(def\s|(local\s+)?function\b|.*=\s*function\s*\(|.*\bdo(\s*\|[^|]*\|)?$)").unwrap()
+    })
+}
+
+// One-liners and Ruby's endless methods (def x = 1) have no body below
+fn one_line_block() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"\bend$|^def [\w?!.]+(\(.*\))?\s*=\s").unwrap())
+}
+
+fn collapse_end_blocks(lines: &[&str], language: Language) -> Vec<String> {
+    let mut kept = Vec::new();
+    let mut index = 0;
+    while index < lines.len() {
+        let line = lines[index];
+        let code = line.trim();
+        let comment = language.line_comments().iter().any(|prefix| code.starts_with(prefix));
+        index += 1;
+        if comment || one_line_block().is_match(code) || !end_block_opener().is_match(code) {
+            kept.push(line.to_string());
+            continue;
+        }
+        let indent = indentation(line);
+        index = skip_deeper(lines, index, indent.len());
+        let closes = lines
+            .get(index)
+            .is_some_and(|next| indentation(next) == indent && next.trim().trim_end_matches([',', ')', ';']) == "end");
+        if closes {
+            index += 1;
+        }
+        kept.push(format!("{} {} end", line.trim_end(), ELLIPSIS));
+    }
+    kept
+}
+
+// Top comments as they are, then the outline: long strings shortened and bodies collapsed
+pub fn summarize(text: &str, language: Language) -> String {
+    let normalized = normalize_whitespace(text);
+    let lines: Vec<&str> = normalized.lines().collect();
+    let header = header_len(&lines, language);
+    let body = strip_long_strings(&lines[header..].join("\n"), language);
+    let body_lines: Vec<&str> = body.lines().collect();
+    let outline = match language.blocks() {
+        Blocks::Braces => collapse_braces(&body_lines, language),
+        Blocks::Indentation => collapse_python(&body_lines),
+        Blocks::End => collapse_end_blocks(&body_lines, language),
+    };
+    let mut summary = lines[..header].join("\n");
+    summary.push('\n');
+    summary.push_str(&outline.join("\n"));
+    normalize_whitespace(&summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn language_comes_from_the_extension_or_shebang() {
+        assert_eq!(detect_language(Path::new("/src/main.RS"), ""), Some(Language::Rust));
+        assert_eq!(detect_language(Path::new("/src/App.tsx"), ""), Some(Language::TypeScript));
+        assert_eq!(detect_language(Path::new("/bin/deploy"), "#!/usr/bin/env python3.12\n"), Some(Language::Python));
+        assert_eq!(detect_language(Path::new("/bin/run"), "#!/bin/bash -e\necho hi"), Some(Language::Shell));
+        assert_eq!(
+            detect_language(Path::new("/bin/run"), "#!/usr/bin/env -S node --experimental"),
+            Some(Language::JavaScript)
+        );
+        assert_eq!(detect_language(Path::new("/notes.txt"), "#!/bin/shiny"), None);
+        assert_eq!(detect_language(Path::new("/README"), "plain text"), None);
+        assert_eq!(normalize_whitespace("a  \r\n\r\n\r\n\tb\rc\n\n"), "a\n\n\tb\nc");
+    }
+
+    #[test]
+    fn brace_languages_keep_signatures_and_containers() {
+        let body = (0..40).map(|i| format!("        let value{} = compute({}, \"{{\");\n", i, i)).collect::<String>();
+        let source = format!(
+            "//! Parses invoices.\r\n\r\nuse std::fs;\n\n/// An invoice\npub struct Invoice {{\n    pub id: u32,\n}}\n\n\
+             impl Invoice {{\n    pub fn parse(text: &str) -> Result<Self, String> {{\n{}        if text.is_empty() {{\n\
+             \x20           return Err(\"empty\".into());\n        }}\n        Ok(Self {{ id: 1 }})\n    }}\n}}\n\n\
+             const BANNER: &str = \"{}\";\n\nfn main()\n{{\n{}}}\n",
+            body,
+            "x".repeat(200),
+            body
+        );
+        let summary = summarize(&source, Language::Rust);
+        assert_eq!(
+            summary,
+            "//! Parses invoices.\n\nuse std::fs;\n\n/// An invoice\npub struct Invoice {\n    pub id: u32,\n}\n\n\
+             impl Invoice {\n    pub fn parse(text: &str) -> Result<Self, String> { … }\n}\n\n\
+             const BANNER: &str = \"…\";\n\nfn main()\n{ … }"
+        );
+        assert!(source.len() > summary.len() * 10, "{} -> {}", source.len(), summary.len());
+
+        let script = "import x from 'y';\nexport const handler = async (event) => {\n  return 1;\n};\n\
+                      export class Api extends Base {\n  get(id) {\n    return this.fetch(`/items/${id}`);\n  }\n}\n";
+        assert_eq!(
+            summarize(script, Language::JavaScript),
+            "import x from 'y';\nexport const handler = async (event) => { … }\n\
+             export class Api extends Base {\n  get(id) { … }\n}"
+        );
+    }
+
+    #[test]
+    fn non_ascii_code_outside_strings_is_stepped_over() {
+        let mut in_comment = false;
+        let line = "let café = 'é'; if ok { naïve(\"ü\") }";
+        let found = braces(line, Language::Rust, &mut in_comment);
+        assert_eq!(found, [(line.find('{').unwrap(), true), (line.rfind('}').unwrap(), false)]);
+        assert!(braces("char c = 'ß'; // {", Language::Java, &mut in_comment).is_empty());
+    }
+
+    #[test]
+    fn python_and_ruby_bodies_collapse_by_indentation() {
+        let source = "#!/usr/bin/env python3\n\"\"\"Sync photos.\n\nLong description.\n\"\"\"\nimport os\n\n\
+                      QUERY = \"\"\"\nSELECT id, name, created_at FROM photos WHERE album = ?\n\"\"\"\n\n\
+                      class Syncer(Base):\n    \"\"\"Copies albums.\"\"\"\n\n    def run(self,\n            dry_run=False):\n\
+                      \x20       for album in self.albums:\n            self.copy(album)\n\n        return True\n\n\
+                      \x20   def name(self): return 'syncer'\n\n\nif __name__ == '__main__':\n    Syncer().run()\n";
+        assert_eq!(
+            summarize(source, Language::Python),
+            "#!/usr/bin/env python3\n\"\"\"Sync photos.\n\nLong description.\n\"\"\"\nimport os\n\n\
+             QUERY = \"\"\"…\"\"\"\n\nclass Syncer(Base):\n    \"\"\"Copies albums.\"\"\"\n\n\
+             \x20   def run(self,\n            dry_run=False):\n        …\n\n    def name(self): return 'syncer'\n\n\
+             if __name__ == '__main__':\n    …"
+        );
+
+        let ruby =
+            "# Billing\nmodule Billing\n  class Invoice\n    def total(items)\n      items.sum(&:price)\n    end\n\n\
+                    \x20   def paid? = true\n  end\nend\n";
+        assert_eq!(
+            summarize(ruby, Language::Ruby),
+            "# Billing\nmodule Billing\n  class Invoice\n    def total(items) … end\n\n    def paid? = true\n  end\nend"
+        );
+    }
+}
@@ -0,0 +1,259 @@
+// Breakdown of a directory by coarse content type (documents, images, archives, ...)
+// for deciding what to organize first. Extensions are looked up in a category table
+// the caller can replace; files without one are identified by their magic bytes.
+use crate::paths;
+use crate::scan_filter::ScanFilter;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+pub const OTHER_CATEGORY: &str = "other";
+const LARGEST_FILES: usize = 5;
+
+pub const DEFAULT_CATEGORIES: &[(&str, &[&str])] = &[
+    ("documents", &[
+        "pdf", "doc", "docx", "odt", "rtf", "txt", "md", "pages", "xls", "xlsx", "ods", "csv",
+        "numbers", "ppt", "pptx", "odp", "key", "epub", "tex",
+    ]),
+    ("images", &[
+        "jpg", "jpeg", "png", "gif", "bmp", "tif", "tiff", "webp", "heic", "heif", "svg", "ico",
+        "raw", "dng", "cr2", "nef", "arw", "psd",
+    ]),
+    ("audio", &["mp3", "wav", "flac", "aac", "m4a", "ogg", "opus", "wma", "aiff"]),
+    ("video", &["mp4", "m4v", "mov", "avi", "mkv", "webm", "wmv", "flv", "mpg", "mpeg"]),
+    ("archives", &["zip", "rar", "7z", "tar", "gz", "tgz", "bz2", "xz", "zst", "dmg", "iso"]),
+    ("code", &[
+        "rs", "py", "js", "jsx", "ts", "tsx", "java", "kt", "c", "h", "cpp", "hpp", "cs", "go",
+        "rb", "php", "swift", "sh", "ps1", "html", "css", "scss", "json", "yaml", "yml", "toml",
+        "xml", "sql", "ipynb",
+    ]),
+];
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct SizedFile {
+    pub path: String,
+    pub size: u64,
+    // The scanned root the file was found under
+    pub root: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RootComposition {
+    pub root: String,
+    pub file_count: u64,
+    pub total_bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CategoryComposition {
+    pub category: String,
+    pub file_count: u64,
+    pub total_bytes: u64,
+    // Largest first
+    pub largest: Vec<SizedFile>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DirectoryComposition {
+    // Biggest categories first
+    pub categories: Vec<CategoryComposition>,
+    // Totals per scanned root, nested roots left out
+    pub roots: Vec<RootComposition>,
+    pub total_files: u64,
+    pub total_bytes: u64,
+    // Entries that couldn't be read and aren't counted anywhere
+    pub unreadable: u64,
+}
+
+// Extension (lowercase, no dot) to category. `overrides` maps category names to their
+// extensions and replaces the built-in table when given.
+pub fn category_table(overrides: Option<HashMap<String, Vec<String>>>) -> HashMap<String, String> {
+    let groups: Vec<(String, Vec<String>)> = match overrides {
+        Some(groups) => groups.into_iter().collect(),
+        None => DEFAULT_CATEGORIES
+            .iter()
+            .map(|(category, extensions)| (category.to_string(), extensions.iter().map(|e| e.to_string()).collect()))
+            .collect(),
+    };
+    groups
+        .into_iter()
+        .flat_map(|(category, extensions)| {
+            extensions
+                .into_iter()
+                .map(move |ext| (ext.trim_start_matches('.').to_lowercase(), category.clone()))
+        })
+        .collect()
+}
+
+// Extension implied by a file's first bytes, for files that don't have one
+fn sniff_extension(path: &Path) -> Option<&'static str> {
+    let mut header = [0u8; 16];
+    let read = std::fs::File::open(path).and_then(|mut f| f.read(&mut header)).ok()?;
+    sniff(&header[..read])
+}
+
+fn sniff(header: &[u8]) -> Option<&'static str> {
+    const SIGNATURES: &[(&[u8], &str)] = &[
+        (b"%PDF", "pdf"),
+        (b"\x89PNG", "png"),
+        (b"\xFF\xD8\xFF", "jpg"),
+        (b"GIF8", "gif"),
+        (b"PK\x03\x04", "zip"),
+        (b"\x1F\x8B", "gz"),
+        (b"7z\xBC\xAF", "7z"),
+        (b"Rar!", "rar"),
+        (b"ID3", "mp3"),
+        (b"fLaC", "flac"),
+        (b"OggS", "ogg"),
+        (b"{\\rtf", "rtf"),
+        (b"#!", "sh"),
+    ];
+    if header.len() >= 12 && &header[4..8] == b"ftyp" {
+        return Some(if &header[8..12] == b"heic" { "heic" } else { "mp4" });
+    }
+    if header.len() >= 12 && header.starts_with(b"RIFF") {
+        return match &header[8..12] {
+            b"WEBP" => Some("webp"),
+            b"WAVE" => Some("wav"),
+            b"AVI " => Some("avi"),
+            _ => None,
+        };
+    }
+    SIGNATURES
+        .iter()
+        .find(|(magic, _)| header.starts_with(magic))
+        .map(|(_, ext)| *ext)
+}
+
+// The file's category by extension, or by its first bytes when it has none
+pub fn category_of(path: &Path, table: &HashMap<String, String>) -> String {
+    let extension = match path.extension() {
+        Some(ext) => Some(ext.to_string_lossy().to_lowercase()),
+        None => sniff_extension(path).map(str::to_string),
+    };
+    extension
+        .and_then(|ext| table.get(&ext).cloned())
+        .unwrap_or_else(|| OTHER_CATEGORY.to_string())
+}
+
+// When walking recursively, roots nested inside another root are skipped so overlapping
+// selections aren't counted twice
+pub fn compose(
+    roots: &[PathBuf],
+    include_subdirectories: bool,
+    filter: &ScanFilter,
+    table: &HashMap<String, String>,
+) -> DirectoryComposition {
+    let mut composition = DirectoryComposition::default();
+    let mut by_category: HashMap<String, CategoryComposition> = HashMap::new();
+    let roots = if include_subdirectories {
+        paths::distinct_roots(roots)
+    } else {
+        roots.iter().enumerate().filter(|(i, root)| !roots[..*i].contains(root)).map(|(_, root)| root.clone()).collect()
+    };
+    for root in roots {
+        let root_shown = paths::display_path(&root);
+        let mut root_totals = RootComposition { root: root_shown.clone(), file_count: 0, total_bytes: 0 };
+        let walker = WalkDir::new(&root).max_depth(if include_subdirectories { usize::MAX } else { 1 });
+        let entries = walker
+            .into_iter()
+            .filter_entry(|e| e.depth() == 0 || !crate::walk_entry_skipped(filter, e));
+        for entry in entries {
+            let Some(entry) = crate::walk_entry_ok(entry) else {
+                composition.unreadable += 1;
+                continue;
+            };
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let Ok(metadata) = entry.metadata() else {
+                composition.unreadable += 1;
+                continue;
+            };
+            let category = category_of(entry.path(), table);
+
+            let size = metadata.len();
+            let stats = by_category.entry(category.clone()).or_insert_with(|| CategoryComposition {
+                category,
+                file_count: 0,
+                total_bytes: 0,
+                largest: Vec::new(),
+            });
+            stats.file_count += 1;
+            stats.total_bytes += size;
+            if stats.largest.len() < LARGEST_FILES || stats.largest.last().is_some_and(|f| f.size < size) {
+                let at = stats.largest.partition_point(|f| f.size >= size);
+                let file = SizedFile { path: paths::display_path(entry.path()), size, root: root_shown.clone() };
+                stats.largest.insert(at, file);
+                stats.largest.truncate(LARGEST_FILES);
+            }
+            root_totals.file_count += 1;
+            root_totals.total_bytes += size;
+        }
+        composition.total_files += root_totals.file_count;
+        composition.total_bytes += root_totals.total_bytes;
+        composition.roots.push(root_totals);
+    }
+    composition.categories = by_category.into_values().collect();
+    composition
+        .categories
+        .sort_by(|a, b| b.total_bytes.cmp(&a.total_bytes).then_with(|| a.category.cmp(&b.category)));
+    composition
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_dir;
+    use std::fs;
+
+    #[test]
+    fn magic_bytes_identify_extensionless_files() {
+        assert_eq!(sniff(b"%PDF-1.7\n"), Some("pdf"));
+        assert_eq!(sniff(b"\0\0\0\x18ftypisom\0\0"), Some("mp4"));
+        assert_eq!(sniff(b"RIFF\0\0\0\0WEBPVP8 "), Some("webp"));
+        assert_eq!(sniff(b"hello"), None);
+    }
+
+    #[test]
+    fn files_are_grouped_with_the_largest_listed() {
+        let dir = temp_dir("groups");
+        for i in 1..=7 {
+            fs::write(dir.join(format!("photo{}.JPG", i)), vec![0u8; i * 10]).unwrap();
+        }
+        fs::write(dir.join("scan"), b"%PDF-1.4 tiny").unwrap();
+        fs::write(dir.join("notes.xyz"), b"?").unwrap();
+        fs::create_dir(dir.join("nested")).unwrap();
+        fs::write(dir.join("nested").join("deep.rs"), b"fn main() {}").unwrap();
+
+        let table = category_table(None);
+        let composition = compose(std::slice::from_ref(&dir), false, &ScanFilter::default(), &table);
+        assert_eq!(composition.total_files, 9);
+        let images = &composition.categories[0];
+        assert_eq!((images.category.as_str(), images.file_count, images.total_bytes), ("images", 7, 280));
+        let sizes: Vec<u64> = images.largest.iter().map(|f| f.size).collect();
+        assert_eq!(sizes, [70, 60, 50, 40, 30]);
+        let names: Vec<&str> = composition.categories.iter().map(|c| c.category.as_str()).collect();
+        assert!(names.contains(&"documents") && names.contains(&"other") && !names.contains(&"code"));
+
+        // Without recursion the nested root is scanned on its own
+        let roots = [dir.clone(), dir.join("nested")];
+        let composition = compose(&roots, false, &ScanFilter::default(), &table);
+        assert_eq!((composition.roots.len(), composition.total_files), (2, 10));
+        assert!(composition.categories.iter().any(|c| c.category == "code"));
+
+        // With recursion it's part of the first root's walk and isn't counted again
+        let composition = compose(&roots, true, &ScanFilter::default(), &table);
+        assert_eq!((composition.roots.len(), composition.total_files), (1, 10));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn the_table_can_be_overridden() {
+        let table = category_table(Some(HashMap::from([("Photos".to_string(), vec![".JPG".to_string()])])));
+        assert_eq!(table.get("jpg").map(String::as_str), Some("Photos"));
+        assert!(!table.contains_key("pdf"));
+    }
+}
@@ -0,0 +1,184 @@
+// Fitting extracted file text into a classification prompt's token budget
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReductionStrategy {
+    // The beginning of the text
+    Head,
+    // Two thirds from the beginning, one third from the end
+    HeadTail,
+    // Headings plus the first sentence of every paragraph
+    Salient,
+}
+
+const ELLIPSIS: &str = "\n…\n";
+const AVG_CHARS_PER_TOKEN: usize = 4;
+
+// Heuristic used when no tokenizer is available: BPE vocabularies average about four
+// characters per token for Latin-script text, while CJK characters are roughly one
+// token each. Errs slightly high so the budget isn't overrun.
+pub fn estimate_tokens(text: &str) -> usize {
+    let (wide, narrow) = text.chars().fold((0usize, 0usize), |(wide, narrow), c| {
+        if is_wide(c) {
+            (wide + 1, narrow)
+        } else {
+            (wide, narrow + 1)
+        }
+    });
+    wide + narrow.div_ceil(AVG_CHARS_PER_TOKEN)
+}
+
+fn is_wide(c: char) -> bool {
+    matches!(c as u32,
+        0x3040..=0x30FF     // Hiragana, Katakana
+        | 0x3400..=0x4DBF   // CJK Extension A
+        | 0x4E00..=0x9FFF   // CJK Unified Ideographs
+        | 0xAC00..=0xD7AF   // Hangul syllables
+        | 0xF900..=0xFAFF)  // CJK Compatibility Ideographs
+}
+
+// Reduces `text` to at most `max_chars` characters with the given strategy
+pub fn reduce(text: &str, max_chars: usize, strategy: ReductionStrategy) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+    match strategy {
+        ReductionStrategy::Head => head(text, max_chars),
+        ReductionStrategy::HeadTail => head_tail(text, max_chars),
+        ReductionStrategy::Salient => {
+            let salient = salient_lines(text);
+            if salient.chars().count() <= max_chars {
+                salient
+            } else {
+                head(&salient, max_chars)
+            }
+        }
+    }
+}
+
+// First `max_chars` characters, backing up to a word boundary when one is close
+fn head(text: &str, max_chars: usize) -> String {
+    let end = byte_offset(text, max_chars);
+    let cut = &text[..end];
+    match cut.rfind(char::is_whitespace) {
+        Some(space) if space >= end - end / 5 => cut[..space].trim_end().to_string(),
+        _ => cut.to_string(),
+    }
+}
+
+// Last `max_chars` characters, starting at a word boundary when one is close
+fn tail(text: &str, max_chars: usize) -> String {
+    let total = text.chars().count();
+    let start = byte_offset(text, total.saturating_sub(max_chars));
+    let cut = &text[start..];
+    let slack = cut.len() / 5;
+    match cut.find(char::is_whitespace) {
+        Some(space) if space <= slack => cut[space..].trim_start().to_string(),
+        _ => cut.to_string(),
+    }
+}
+
+fn head_tail(text: &str, max_chars: usize) -> String {
+    let available = max_chars.saturating_sub(ELLIPSIS.chars().count());
+    let head_chars = available * 2 / 3;
+    format!("{}{}{}", head(text, head_chars), ELLIPSIS, tail(text, available - head_chars))
+}
+
+fn byte_offset(text: &str, chars: usize) -> usize {
+    text.char_indices().nth(chars).map(|(i, _)| i).unwrap_or(text.len())
+}
+
+// Markdown headings, short title-like lines, and all-caps section names
+fn is_heading(line: &str) -> bool {
+    let line = line.trim();
+    if line.starts_with('#') {
+        return true;
+    }
+    let letters: Vec<char> = line.chars().filter(|c| c.is_alphabetic()).collect();
+    if letters.is_empty() {
+        return false;
+    }
+    let all_caps = letters.iter().all(|c| !c.is_lowercase()) && letters.len() > 2;
+    let title_like = line.chars().count() <= 60 && !line.ends_with(['.', ',', ';', ':', '!', '?']);
+    all_caps || title_like
+}
+
+fn first_sentence(paragraph: &str) -> &str {
+    let mut chars = paragraph.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        if matches!(c, '.' | '!' | '?' | '。') {
+            let at_end = match chars.peek() {
+                None => true,
+                Some((_, next)) => next.is_whitespace(),
+            };
+            if at_end {
+                return &paragraph[..i + c.len_utf8()];
+            }
+        }
+    }
+    paragraph
+}
+
+fn salient_lines(text: &str) -> String {
+    let mut kept: Vec<String> = Vec::new();
+    for paragraph in text.split("\n\n").map(str::trim).filter(|p| !p.is_empty()) {
+        let mut lines = paragraph.lines().map(str::trim).filter(|l| !l.is_empty()).peekable();
+        // Leading heading lines are kept whole, then the body's first sentence
+        while let Some(line) = lines.peek() {
+            if !is_heading(line) {
+                break;
+            }
+            kept.push(line.to_string());
+            lines.next();
+        }
+        let body = lines.collect::<Vec<_>>().join(" ");
+        if !body.is_empty() {
+            kept.push(first_sentence(&body).to_string());
+        }
+    }
+    kept.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn estimate_counts_cjk_characters_individually() {
+        assert_eq!(estimate_tokens(""), 0);
+        assert_eq!(estimate_tokens("invoice"), 2);
+        assert_eq!(estimate_tokens("請求書"), 3);
+    }
+
+    #[test]
+    fn short_text_is_returned_unchanged() {
+        assert_eq!(reduce("Quarterly report", 100, ReductionStrategy::Salient), "Quarterly report");
+    }
+
+    #[test]
+    fn head_cuts_at_a_word_boundary() {
+        let text = "Invoice number 4521 issued to Acme Corporation for consulting services";
+        assert_eq!(reduce(text, 40, ReductionStrategy::Head), "Invoice number 4521 issued to Acme");
+    }
+
+    #[test]
+    fn head_tail_keeps_both_ends() {
+        let text = format!("Dear customer, {} Total due: $420.00", "filler ".repeat(200));
+        let reduced = reduce(&text, 80, ReductionStrategy::HeadTail);
+        assert!(reduced.chars().count() <= 80);
+        assert!(reduced.starts_with("Dear customer,"));
+        assert!(reduced.contains('…'));
+        assert!(reduced.ends_with("Total due: $420.00"));
+    }
+
+    #[test]
+    fn salient_keeps_headings_and_first_sentences() {
+        let text = "# Lease Agreement\n\nThis lease is made between the landlord and tenant. It runs for twelve months.\n\n\
+                    PAYMENT TERMS\nRent is due on the first of each month. Late fees apply after five days.";
+        assert_eq!(
+            reduce(text, 150, ReductionStrategy::Salient),
+            "# Lease Agreement\nThis lease is made between the landlord and tenant.\nPAYMENT TERMS\nRent is due on the first of each month."
+        );
+    }
+}
@@ -0,0 +1,355 @@
+// Full-text index of extracted file contents, so a folder that was indexed once can be
+// searched instantly. Text comes from the same extractors classification uses, is
+// normalized (whitespace collapsed, capped at MAX_INDEXED_BYTES) and stored in an FTS5
+// table next to the size and mtime it was extracted from; re-indexing only extracts
+// files whose size or mtime changed and drops files that are gone. Every document
+// belongs to the root it was indexed under, which delete_root removes as a unit.
+use crate::classification_cache::QuickHash;
+use crate::io_priority::IoPriority;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+const INDEX_FILE: &str = "content_index.sqlite3";
+// Text beyond this is left out of the index
+pub const MAX_INDEXED_BYTES: usize = 1024 * 1024;
+// Files larger than this aren't extracted unless the options raise the limit
+pub const DEFAULT_MAX_FILE_BYTES: u64 = 50 * 1024 * 1024;
+pub const DEFAULT_SEARCH_LIMIT: usize = 50;
+const MAX_SEARCH_LIMIT: usize = 500;
+// Bytes of context kept on either side of the first hit in a snippet
+const SNIPPET_CONTEXT_BYTES: usize = 80;
+const MAX_OFFSETS: usize = 100;
+
+// Images and other files the extractors can't get text from; anything else without a
+// dedicated extractor is read as plain text, which fails for binaries
+const NO_TEXT_EXTENSIONS: &[&str] = &[
+    "png", "jpg", "jpeg", "gif", "bmp", "webp", "heic", "tif", "tiff", "ico", "doc",
+    "zip", "gz", "tgz", "tar", "7z", "rar", "dmg", "iso", "exe", "dll", "so", "dylib",
+    "mp3", "wav", "flac", "m4a", "mp4", "mov", "mkv", "avi", "sqlite3", "db",
+];
+
+pub fn indexable(path: &Path) -> bool {
+    let extension = path.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default();
+    !NO_TEXT_EXTENSIONS.contains(&extension.as_str())
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IndexOptions {
+    pub include_subdirectories: bool,
+    pub include_hidden: Option<bool>,
+    pub max_file_bytes: u64,
+    // Low throttles extraction reads and yields between files
+    pub io_priority: IoPriority,
+}
+
+impl Default for IndexOptions {
+    fn default() -> Self {
+        Self {
+            include_subdirectories: true,
+            include_hidden: None,
+            max_file_bytes: DEFAULT_MAX_FILE_BYTES,
+            io_priority: IoPriority::Normal,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct IndexReport {
+    pub root: String,
+    pub files_seen: usize,
+    pub indexed: usize,
+    // Same size and mtime as when last indexed
+    pub unchanged: usize,
+    // Indexed before but no longer there
+    pub removed: usize,
+    // Images, binaries, files over max_file_bytes and cloud placeholders
+    pub skipped: usize,
+    pub failed: usize,
+    pub cancelled: bool,
+}
+
+// A byte range in the indexed (normalized) text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct TextRange {
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchMatch {
+    pub path: String,
+    // bm25 rank; lower is better
+    pub score: f64,
+    pub snippet: String,
+    // Where the snippet starts in the indexed text
+    pub snippet_start: usize,
+    // Every occurrence of a query term in the indexed text
+    pub offsets: Vec<TextRange>,
+}
+
+pub struct ContentIndex {
+    conn: Connection,
+}
+
+fn sql_error(e: rusqlite::Error) -> String {
+    format!("Content index error: {}", e)
+}
+
+// Collapses whitespace runs to single spaces and drops other control characters, then
+// cuts the result at MAX_INDEXED_BYTES on a character boundary
+pub fn normalize_text(text: &str) -> String {
+    let mut normalized = String::with_capacity(text.len().min(MAX_INDEXED_BYTES));
+    for word in text.split(|c: char| c.is_whitespace()).filter(|word| !word.is_empty()) {
+        if !normalized.is_empty() {
+            normalized.push(' ');
+        }
+        normalized.extend(word.chars().filter(|c| !c.is_control()));
+        if normalized.len() >= MAX_INDEXED_BYTES {
+            let mut end = MAX_INDEXED_BYTES;
+            while !normalized.is_char_boundary(end) {
+                end -= 1;
+            }
+            normalized.truncate(end);
+            break;
+        }
+    }
+    normalized
+}
+
+// A query term; `prefix` terms were written with a trailing '*'
+#[derive(Debug, PartialEq)]
+struct Term {
+    text: String,
+    prefix: bool,
+}
+
+// Words of the query, lowercased. Everything but letters, digits and a trailing '*' is
+// a separator, so user input can't inject FTS5 syntax.
+fn query_terms(query: &str) -> Vec<Term> {
+    query
+        .split_whitespace()
+        .flat_map(|word| {
+            let prefix = word.ends_with('*');
+            let words: Vec<&str> = word.split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty()).collect();
+            let last = words.len().saturating_sub(1);
+            words
+                .into_iter()
+                .enumerate()
+                .map(move |(i, w)| Term { text: w.to_lowercase(), prefix: prefix && i == last })
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+// All terms must appear: "a" "b"*
+fn fts_query(terms: &[Term]) -> String {
+    terms
+        .iter()
+        .map(|term| format!("\"{}\"{}", term.text, if term.prefix { "*" } else { "" }))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// Byte ranges of words in `text` matching any term
+fn find_offsets(text: &str, terms: &[Term]) -> Vec<TextRange> {
+    let mut offsets = Vec::new();
+    let mut start = None;
+    for (i, c) in text.char_indices().chain(std::iter::once((text.len(), ' '))) {
+        match (c.is_alphanumeric(), start) {
+            (true, None) => start = Some(i),
+            (false, Some(s)) => {
+                let word = text[s..i].to_lowercase();
+                let hit = terms.iter().any(|term| {
+                    if term.prefix { word.starts_with(&term.text) } else { word == term.text }
+                });
+                if hit {
+                    offsets.push(TextRange { start: s, end: i });
+                    if offsets.len() == MAX_OFFSETS {
+                        break;
+                    }
+                }
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    offsets
+}
+
+// Context around the first hit, or the start of the text when none was found
+fn snippet(text: &str, offsets: &[TextRange]) -> (String, usize) {
+    let (hit_start, hit_end) = offsets.first().map_or((0, 0), |range| (range.start, range.end));
+    let mut start = hit_start.saturating_sub(SNIPPET_CONTEXT_BYTES);
+    while !text.is_char_boundary(start) {
+        start -= 1;
+    }
+    let mut end = (hit_end + SNIPPET_CONTEXT_BYTES).min(text.len());
+    while !text.is_char_boundary(end) {
+        end += 1;
+    }
+    (text[start..end].to_string(), start)
+}
+
+impl ContentIndex {
+    pub fn open(app_data_dir: &Path) -> Result<Self, String> {
+        std::fs::create_dir_all(app_data_dir)
+            .map_err(|e| format!("Failed to create app data directory: {}", e))?;
+        let conn = Connection::open(app_data_dir.join(INDEX_FILE)).map_err(sql_error)?;
+        Self::init(conn)
+    }
+
+    #[cfg(test)]
+    fn in_memory() -> Result<Self, String> {
+        Self::init(Connection::open_in_memory().map_err(sql_error)?)
+    }
+
+    fn init(conn: Connection) -> Result<Self, String> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS documents (
+                id INTEGER PRIMARY KEY,
+                path TEXT NOT NULL UNIQUE,
+                root TEXT NOT NULL,
+                size INTEGER NOT NULL,
+                modified_ms INTEGER NOT NULL,
+                indexed_at INTEGER NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS documents_root ON documents (root);
+            CREATE VIRTUAL TABLE IF NOT EXISTS document_text USING fts5(text);",
+        )
+        .map_err(sql_error)?;
+        Ok(Self { conn })
+    }
+
+    // Size and mtime of every document indexed under `root`
+    pub fn documents(&self, root: &str) -> Result<HashMap<String, QuickHash>, String> {
+        let mut statement = self
+            .conn
+            .prepare("SELECT path, size, modified_ms FROM documents WHERE root = ?1")
+            .map_err(sql_error)?;
+        let rows = statement
+            .query_map(params![root], |row| {
+                Ok((row.get(0)?, QuickHash { size: row.get(1)?, modified_ms: row.get(2)? }))
+            })
+            .map_err(sql_error)?;
+        rows.collect::<Result<_, _>>().map_err(sql_error)
+    }
+
+    // A path indexed under another root moves to this one
+    pub fn put(&mut self, root: &str, path: &str, quick_hash: QuickHash, text: &str) -> Result<(), String> {
+        let tx = self.conn.transaction().map_err(sql_error)?;
+        let existing: Option<i64> = tx
+            .query_row("SELECT id FROM documents WHERE path = ?1", params![path], |row| row.get(0))
+            .optional()
+            .map_err(sql_error)?;
+        if let Some(id) = existing {
+            tx.execute("DELETE FROM document_text WHERE rowid = ?1", params![id]).map_err(sql_error)?;
+            tx.execute("DELETE FROM documents WHERE id = ?1", params![id]).map_err(sql_error)?;
+        }
+        tx.execute(
+            "INSERT INTO documents (path, root, size, modified_ms, indexed_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![path, root, quick_hash.size, quick_hash.modified_ms, crate::scheduler::now_secs() as i64],
+        )
+        .map_err(sql_error)?;
+        let id = tx.last_insert_rowid();
+        tx.execute("INSERT INTO document_text (rowid, text) VALUES (?1, ?2)", params![id, text])
+            .map_err(sql_error)?;
+        tx.commit().map_err(sql_error)
+    }
+
+    pub fn remove(&mut self, paths: &[String]) -> Result<(), String> {
+        let tx = self.conn.transaction().map_err(sql_error)?;
+        for path in paths {
+            tx.execute(
+                "DELETE FROM document_text WHERE rowid IN (SELECT id FROM documents WHERE path = ?1)",
+                params![path],
+            )
+            .map_err(sql_error)?;
+            tx.execute("DELETE FROM documents WHERE path = ?1", params![path]).map_err(sql_error)?;
+        }
+        tx.commit().map_err(sql_error)
+    }
+
+    // Number of documents removed
+    pub fn delete_root(&mut self, root: &str) -> Result<usize, String> {
+        let tx = self.conn.transaction().map_err(sql_error)?;
+        tx.execute(
+            "DELETE FROM document_text WHERE rowid IN (SELECT id FROM documents WHERE root = ?1)",
+            params![root],
+        )
+        .map_err(sql_error)?;
+        let removed = tx.execute("DELETE FROM documents WHERE root = ?1", params![root]).map_err(sql_error)?;
+        tx.commit().map_err(sql_error)?;
+        Ok(removed)
+    }
+
+    // Documents containing every word of `query` (a trailing '*' matches prefixes), best
+    // first, limited to paths starting with `path_filter`
+    pub fn search(&self, query: &str, path_filter: Option<&str>, limit: usize) -> Result<Vec<SearchMatch>, String> {
+        let terms = query_terms(query);
+        if terms.is_empty() {
+            return Err("The search query has no words to look for".to_string());
+        }
+        let limit = limit.clamp(1, MAX_SEARCH_LIMIT) as i64;
+        let mut statement = self
+            .conn
+            .prepare(
+                "SELECT documents.path, bm25(document_text), document_text.text
+                 FROM document_text JOIN documents ON documents.id = document_text.rowid
+                 WHERE document_text MATCH ?1 AND (?2 IS NULL OR substr(documents.path, 1, length(?2)) = ?2)
+                 ORDER BY bm25(document_text) LIMIT ?3",
+            )
+            .map_err(sql_error)?;
+        let rows = statement
+            .query_map(params![fts_query(&terms), path_filter, limit], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, f64>(1)?, row.get::<_, String>(2)?))
+            })
+            .map_err(sql_error)?;
+        rows.map(|row| {
+            let (path, score, text) = row.map_err(sql_error)?;
+            let offsets = find_offsets(&text, &terms);
+            let (snippet, snippet_start) = snippet(&text, &offsets);
+            Ok(SearchMatch { path, score, snippet, snippet_start, offsets })
+        })
+        .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_is_normalized_and_queries_are_sanitized() {
+        assert_eq!(normalize_text("  Sheet: Q1\n\n\tTotal\u{0}  42 "), "Sheet: Q1 Total 42");
+        assert_eq!(normalize_text(&"é".repeat(MAX_INDEXED_BYTES)).len(), MAX_INDEXED_BYTES);
+        let query = fts_query(&query_terms("Invoice\" OR acme-corp tax*"));
+        assert_eq!(query, "\"invoice\" \"or\" \"acme\" \"corp\" \"tax\"*");
+        assert!(query_terms(" -- ").is_empty());
+    }
+
+    #[test]
+    fn searches_return_snippets_offsets_and_respect_roots() {
+        let mut index = ContentIndex::in_memory().unwrap();
+        let quick_hash = QuickHash { size: 1, modified_ms: 1 };
+        let text = format!("{} Invoice from ACME for taxes 2024", "filler ".repeat(20));
+        index.put("/docs", "/docs/a.pdf", quick_hash, &text).unwrap();
+        index.put("/docs", "/docs/b.txt", quick_hash, "meeting notes").unwrap();
+        index.put("/other", "/other/c.txt", quick_hash, "acme invoice copy").unwrap();
+
+        let matches = index.search("acme invoice", Some("/docs"), 10).unwrap();
+        assert_eq!(matches.len(), 1);
+        let hit = &matches[0];
+        assert_eq!(&text[hit.offsets[0].start..hit.offsets[0].end], "Invoice");
+        assert_eq!(&text[hit.offsets[1].start..hit.offsets[1].end], "ACME");
+        assert!(hit.snippet.contains("Invoice from ACME") && hit.snippet_start > 0);
+        assert_eq!(index.search("tax*", None, 10).unwrap().len(), 1);
+
+        assert_eq!(index.documents("/docs").unwrap().len(), 2);
+        index.remove(&["/docs/b.txt".to_string()]).unwrap();
+        assert_eq!(index.delete_root("/docs").unwrap(), 1);
+        assert_eq!(index.search("invoice", None, 10).unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,212 @@
+// Organize-by-date fallback for when no LLM is configured: every file is planned into
+// <base>/<year>[/<year-month>[/<year-month-day>]] by its EXIF capture date or, failing
+// that, its modification time. Only the plan is built here; moves go through move_file.
+use crate::cloud_files;
+use crate::paths;
+use crate::rename;
+use chrono::{DateTime, Datelike, Local, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+// Folder for files whose date couldn't be determined
+pub const UNDATED_FOLDER: &str = "Undated";
+
+// Formats kamadak-exif can read capture dates from
+const EXIF_EXTENSIONS: &[&str] = &[
+    "jpg", "jpeg", "tif", "tiff", "png", "webp", "heic", "heif", "avif",
+    "dng", "cr2", "nef", "arw", "orf", "rw2", "pef",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DateGranularity {
+    Year,
+    YearMonth,
+    YearMonthDay,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DateSource {
+    Exif,
+    Modified,
+    Undated,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DatedMove {
+    pub source: String,
+    pub destination: String,
+    // YYYY-MM-DD, absent for undated files
+    pub date: Option<String>,
+    pub date_source: DateSource,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DatePlan {
+    pub moves: Vec<DatedMove>,
+    // Files already in the folder their date maps to
+    pub already_in_place: usize,
+}
+
+pub fn folder_for(date: NaiveDate, granularity: DateGranularity) -> PathBuf {
+    let year = format!("{:04}", date.year());
+    let month = format!("{}-{:02}", year, date.month());
+    match granularity {
+        DateGranularity::Year => PathBuf::from(year),
+        DateGranularity::YearMonth => [year, month].iter().collect(),
+        DateGranularity::YearMonthDay => {
+            let day = format!("{}-{:02}", month, date.day());
+            [year, month, day].iter().collect()
+        }
+    }
+}
+
+// DateTimeOriginal, the moment the shutter fired, ignoring later edits
+fn exif_date(path: &Path) -> Option<NaiveDate> {
+    let ext = path.extension()?.to_string_lossy().to_lowercase();
+    if !EXIF_EXTENSIONS.contains(&ext.as_str()) {
+        return None;
+    }
+    let file = fs::File::open(path).ok()?;
+    let exif = exif::Reader::new().read_from_container(&mut BufReader::new(file)).ok()?;
+    let field = exif.get_field(exif::Tag::DateTimeOriginal, exif::In::PRIMARY)?;
+    match &field.value {
+        exif::Value::Ascii(values) => values.first().and_then(|ascii| parse_exif_date(ascii)),
+        _ => None,
+    }
+}
+
+// "YYYY:MM:DD HH:MM:SS"; blank or zeroed dates written by some cameras don't count
+fn parse_exif_date(ascii: &[u8]) -> Option<NaiveDate> {
+    let parsed = exif::DateTime::from_ascii(ascii).ok()?;
+    NaiveDate::from_ymd_opt(parsed.year as i32, parsed.month as u32, parsed.day as u32)
+        .filter(|date| date.year() > 1900)
+}
+
+// Local-time modification date; a zero or pre-1970 mtime means the file system didn't keep one
+fn modified_date(path: &Path) -> Option<NaiveDate> {
+    let modified = fs::metadata(path).ok()?.modified().ok()?;
+    modified.duration_since(std::time::UNIX_EPOCH).ok().filter(|d| d.as_secs() > 0)?;
+    Some(DateTime::<Local>::from(modified).date_naive())
+}
+
+// Cloud placeholders are dated by modification time; reading EXIF would download them
+pub fn date_of(path: &Path, use_exif: bool) -> (Option<NaiveDate>, DateSource) {
+    if use_exif && cloud_files::may_read(path) {
+        if let Some(date) = exif_date(path) {
+            return (Some(date), DateSource::Exif);
+        }
+    }
+    match modified_date(path) {
+        Some(date) => (Some(date), DateSource::Modified),
+        None => (None, DateSource::Undated),
+    }
+}
+
+// Plans a destination for every file. Two files with the same name landing in the same
+// folder, or a name already taken there, get "name (2).ext" like rename's numbering.
+pub fn plan(base: &Path, files: &[PathBuf], granularity: DateGranularity, use_exif: bool) -> DatePlan {
+    let mut plan = DatePlan::default();
+    let mut claimed: HashSet<PathBuf> = HashSet::new();
+    for file in files {
+        let Some(name) = file.file_name() else { continue };
+        let (date, date_source) = date_of(file, use_exif);
+        let folder = match date {
+            Some(date) => base.join(folder_for(date, granularity)),
+            None => base.join(UNDATED_FOLDER),
+        };
+        let target = folder.join(name);
+        if &target == file {
+            plan.already_in_place += 1;
+            claimed.insert(target);
+            continue;
+        }
+        let taken = |candidate: &PathBuf| claimed.contains(candidate) || fs::symlink_metadata(candidate).is_ok();
+        let destination = if taken(&target) {
+            (2..)
+                .map(|n| rename::numbered(&target, n))
+                .find(|candidate| !taken(candidate))
+                .unwrap_or(target)
+        } else {
+            target
+        };
+        claimed.insert(destination.clone());
+        plan.moves.push(DatedMove {
+            source: paths::display_path(file),
+            destination: paths::display_path(&destination),
+            date: date.map(|d| d.format("%Y-%m-%d").to_string()),
+            date_source,
+        });
+    }
+    plan
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_dir;
+    use std::time::{Duration, SystemTime};
+
+    fn file_modified_at(path: &Path, local_noon: NaiveDate) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let file = fs::File::create(path).unwrap();
+        let when = local_noon.and_hms_opt(12, 0, 0).unwrap().and_local_timezone(Local).unwrap();
+        file.set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(when.timestamp() as u64)).unwrap();
+    }
+
+    #[test]
+    fn folders_follow_the_granularity() {
+        let date = NaiveDate::from_ymd_opt(2024, 3, 5).unwrap();
+        assert_eq!(folder_for(date, DateGranularity::Year), Path::new("2024"));
+        assert_eq!(folder_for(date, DateGranularity::YearMonth), Path::new("2024").join("2024-03"));
+        assert_eq!(
+            folder_for(date, DateGranularity::YearMonthDay),
+            Path::new("2024").join("2024-03").join("2024-03-05")
+        );
+    }
+
+    #[test]
+    fn exif_dates_are_parsed_and_blank_ones_rejected() {
+        assert_eq!(parse_exif_date(b"2023:07:14 18:02:55"), NaiveDate::from_ymd_opt(2023, 7, 14));
+        assert_eq!(parse_exif_date(b"0000:00:00 00:00:00"), None);
+        assert_eq!(parse_exif_date(b"    :  :     :  :  "), None);
+    }
+
+    #[test]
+    fn files_are_planned_by_modified_date_with_numbered_duplicates() {
+        let dir = temp_dir("plan");
+        let march = NaiveDate::from_ymd_opt(2024, 3, 5).unwrap();
+        file_modified_at(&dir.join("a/report.pdf"), march);
+        file_modified_at(&dir.join("b/report.pdf"), march);
+        file_modified_at(&dir.join("2024/2024-03/placed.txt"), march);
+        let files = vec![dir.join("a/report.pdf"), dir.join("b/report.pdf"), dir.join("2024/2024-03/placed.txt")];
+
+        let plan = plan(&dir, &files, DateGranularity::YearMonth, true);
+        assert_eq!(plan.already_in_place, 1);
+        let destinations: Vec<String> = plan.moves.iter().map(|m| m.destination.clone()).collect();
+        let folder = dir.join("2024").join("2024-03");
+        assert_eq!(
+            destinations,
+            [paths::display_path(folder.join("report.pdf")), paths::display_path(folder.join("report (2).pdf"))]
+        );
+        assert!(plan.moves.iter().all(|m| m.date_source == DateSource::Modified));
+        assert_eq!(plan.moves[0].date.as_deref(), Some("2024-03-05"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn files_without_a_date_go_to_undated() {
+        let dir = temp_dir("undated");
+        let file = dir.join("mystery.bin");
+        fs::File::create(&file).unwrap().set_modified(SystemTime::UNIX_EPOCH).unwrap();
+
+        let plan = plan(&dir, &[file], DateGranularity::Year, false);
+        assert_eq!(plan.moves[0].date_source, DateSource::Undated);
+        assert_eq!(plan.moves[0].destination, paths::display_path(dir.join(UNDATED_FOLDER).join("mystery.bin")));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,190 @@
+// Where moves, renames, archives and restores may write. Destinations must be inside an
+// allowed base directory (the configured list, or else the directories currently being
+// organized) and never inside a system path or the app's own data directory. Paths are
+// compared after canonicalization so a symlink can't point a destination somewhere else.
+use crate::error::{AppError, AppErrorKind};
+use crate::paths;
+use crate::settings;
+use serde::{Deserialize, Serialize};
+use std::path::{Component, Path, PathBuf};
+use std::sync::{OnceLock, RwLock};
+
+// Rule names reported in PolicyViolation errors
+pub const RULE_SYSTEM_PATH: &str = "system_path";
+pub const RULE_APP_DATA: &str = "app_data";
+pub const RULE_ALLOWED_ROOTS: &str = "allowed_roots";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DestinationPolicy {
+    // Base directories destinations must be inside. Empty means the directories
+    // selected for organization. Kept in the settings store.
+    #[serde(default)]
+    pub allowed_roots: Vec<String>,
+}
+
+#[derive(Default)]
+struct ActivePolicy {
+    organization_roots: Vec<PathBuf>,
+    app_data_dir: Option<PathBuf>,
+}
+
+static ACTIVE_POLICY: OnceLock<RwLock<ActivePolicy>> = OnceLock::new();
+
+fn active_policy() -> &'static RwLock<ActivePolicy> {
+    ACTIVE_POLICY.get_or_init(|| RwLock::new(ActivePolicy::default()))
+}
+
+pub fn current_policy() -> DestinationPolicy {
+    settings::with(|settings| settings.destination_policy.clone())
+}
+
+// The directories the user picked for the current organization run, the default allowlist
+pub fn set_organization_roots(roots: Vec<PathBuf>) {
+    active_policy().write().unwrap().organization_roots = roots;
+}
+
+// The app's data directory is always off limits
+pub fn set_app_data_dir(app_data_dir: &Path) {
+    active_policy().write().unwrap().app_data_dir = Some(app_data_dir.to_path_buf());
+}
+
+// Resolves symlinks in the longest existing prefix of `path`, then applies the rest
+// (which doesn't exist yet) lexically, so "link/../x" can't escape either way
+pub fn canonicalize_destination(path: &Path) -> PathBuf {
+    for ancestor in path.ancestors() {
+        let Ok(mut resolved) = std::fs::canonicalize(ancestor) else { continue };
+        let Ok(rest) = path.strip_prefix(ancestor) else { continue };
+        for component in rest.components() {
+            match component {
+                Component::ParentDir => {
+                    resolved.pop();
+                }
+                Component::Normal(part) => resolved.push(part),
+                _ => {}
+            }
+        }
+        return resolved;
+    }
+    path.to_path_buf()
+}
+
+fn system_paths() -> Vec<PathBuf> {
+    if cfg!(windows) {
+        let var = |name: &str, default: Option<&str>| {
+            std::env::var_os(name).map(PathBuf::from).or_else(|| default.map(PathBuf::from))
+        };
+        [
+            var("SystemRoot", Some(r"C:\Windows")),
+            var("ProgramFiles", Some(r"C:\Program Files")),
+            var("ProgramFiles(x86)", Some(r"C:\Program Files (x86)")),
+            var("ProgramData", Some(r"C:\ProgramData")),
+        ]
+        .into_iter()
+        .flatten()
+        .collect()
+    } else {
+        let mut system = vec!["/bin", "/boot", "/dev", "/etc", "/lib", "/lib64", "/proc", "/sbin", "/sys", "/usr"];
+        if cfg!(target_os = "macos") {
+            system.extend(["/System", "/Library", "/Applications", "/private/etc", "/private/var/db"]);
+        }
+        system.into_iter().map(PathBuf::from).collect()
+    }
+}
+
+// Checks against the active policy; called before anything is created or moved
+pub fn check(destination: &Path) -> Result<(), AppError> {
+    let configured = current_policy().allowed_roots;
+    let active = active_policy().read().unwrap();
+    let allowed: Vec<PathBuf> = if configured.is_empty() {
+        active.organization_roots.clone()
+    } else {
+        configured.iter().map(|root| paths::resolve_path(root)).collect()
+    };
+    check_with(destination, &allowed, &system_paths(), active.app_data_dir.as_deref())
+}
+
+fn violation(destination: &Path, rule: &'static str, message: String) -> AppError {
+    AppError::new(AppErrorKind::PolicyViolation, message)
+        .with_path(paths::display_path(destination))
+        .with_rule(rule)
+}
+
+// With no allowed roots at all (nothing selected yet), only the denylist applies
+fn check_with(
+    destination: &Path,
+    allowed_roots: &[PathBuf],
+    system: &[PathBuf],
+    app_data_dir: Option<&Path>,
+) -> Result<(), AppError> {
+    let resolved = canonicalize_destination(destination);
+    let shown = paths::display_path(&resolved);
+    if let Some(denied) = system.iter().map(|p| canonicalize_destination(p)).find(|p| resolved.starts_with(p)) {
+        let message = format!("Refusing to write to {}: it is inside the system path {}", shown, denied.display());
+        return Err(violation(destination, RULE_SYSTEM_PATH, message));
+    }
+    if app_data_dir.is_some_and(|dir| resolved.starts_with(canonicalize_destination(dir))) {
+        let message = format!("Refusing to write to {}: it is inside the app's data directory", shown);
+        return Err(violation(destination, RULE_APP_DATA, message));
+    }
+    if !allowed_roots.is_empty()
+        && !allowed_roots.iter().any(|root| resolved.starts_with(canonicalize_destination(root)))
+    {
+        let message = format!("Refusing to write to {}: it is outside the allowed directories", shown);
+        return Err(violation(destination, RULE_ALLOWED_ROOTS, message));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_dir;
+    use std::fs;
+
+    #[test]
+    fn destinations_must_stay_inside_the_allowed_roots() {
+        let dir = temp_dir("allowed");
+        let root = dir.join("root");
+        fs::create_dir_all(&root).unwrap();
+        let allowed = [root.clone()];
+
+        assert!(check_with(&root.join("Photos/new/a.jpg"), &allowed, &[], None).is_ok());
+        let err = check_with(&root.join("missing/../../outside.txt"), &allowed, &[], None).unwrap_err();
+        assert_eq!((err.kind, err.rule), (AppErrorKind::PolicyViolation, Some(RULE_ALLOWED_ROOTS)));
+        // Nothing selected means only the denylist applies
+        assert!(check_with(&dir.join("outside.txt"), &[], &[], None).is_ok());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn system_paths_and_app_data_are_denied_even_when_allowed() {
+        let dir = temp_dir("denied");
+        let system = dir.join("Windows");
+        let app_data = dir.join("app-data");
+        fs::create_dir_all(system.join("System32")).unwrap();
+        let allowed = [dir.clone()];
+
+        let denied = std::slice::from_ref(&system);
+        let err = check_with(&system.join("System32/report.pdf"), &allowed, denied, None).unwrap_err();
+        assert_eq!(err.rule, Some(RULE_SYSTEM_PATH));
+        let err = check_with(&app_data.join("cache.db"), &allowed, &[], Some(&app_data)).unwrap_err();
+        assert_eq!(err.rule, Some(RULE_APP_DATA));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_are_resolved_before_checking() {
+        let dir = temp_dir("symlink");
+        let root = dir.join("root");
+        let system = dir.join("system");
+        fs::create_dir_all(&root).unwrap();
+        fs::create_dir_all(&system).unwrap();
+        std::os::unix::fs::symlink(&system, root.join("innocent")).unwrap();
+
+        let target = root.join("innocent/new/a.txt");
+        let err = check_with(&target, std::slice::from_ref(&root), std::slice::from_ref(&system), None).unwrap_err();
+        assert_eq!(err.rule, Some(RULE_SYSTEM_PATH));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
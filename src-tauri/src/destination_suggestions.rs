@@ -0,0 +1,297 @@
+// Existing folders that fit the categories the LLM proposed, so "Taxes" lands in
+// ~/Documents/Finance/Taxes instead of a new top-level folder. Folder and category
+// names are compared as word sets: accents and case are folded, simple English plurals
+// are reduced to their singular, and a near-miss word counts for part of a match. A
+// folder's ancestors can supply words the folder name lacks ("Finance Taxes" matches
+// Finance/Taxes). No LLM is involved.
+use crate::scan_filter::ScanFilter;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+use walkdir::WalkDir;
+
+pub const DEFAULT_MAX_DEPTH: usize = 4;
+pub const DEFAULT_MIN_SCORE: f64 = 0.5;
+// Categories whose best candidate scores below this are worth asking the user about
+pub const GOOD_MATCH: f64 = 0.8;
+const MAX_CANDIDATES: usize = 5;
+// Bounds the walk on huge trees; directories past this are not considered
+const MAX_DIRECTORIES: usize = 50_000;
+// A word sharing a prefix with, or one edit away from, a category word
+const PARTIAL_WORD_MATCH: f64 = 0.8;
+const MIN_PARTIAL_WORD_LEN: usize = 4;
+// Words found only in a folder's ancestors count for less than the folder's own name
+const ANCESTOR_WEIGHT: f64 = 0.9;
+const STOP_WORDS: &[&str] = &["a", "an", "and", "the", "of", "for", "y", "et", "und", "de", "la", "le", "el"];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DestinationCandidate {
+    pub path: String,
+    // 0-1; 1 when the folder name has exactly the category's words
+    pub score: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CategorySuggestions {
+    pub category: String,
+    // Best first
+    pub candidates: Vec<DestinationCandidate>,
+    // Whether the best candidate is good enough to use without asking
+    pub matched: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DestinationSuggestions {
+    pub categories: Vec<CategorySuggestions>,
+    pub directories_scanned: usize,
+    // MAX_DIRECTORIES was reached before the walk finished
+    pub truncated: bool,
+}
+
+struct Folder {
+    path: PathBuf,
+    depth: usize,
+    words: Vec<String>,
+    // Words of the ancestors below the search root
+    ancestor_words: Vec<String>,
+}
+
+// "Tax-Returns 2023" → ["tax", "return", "2023"]. Two-digit-or-shorter numbers are
+// ordering prefixes ("01 Taxes") and are dropped with stop words.
+pub fn words(name: &str) -> Vec<String> {
+    let folded: String = name.nfkd().filter(|c| !is_combining_mark(*c)).collect::<String>().to_lowercase();
+    folded
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty() && !STOP_WORDS.contains(word))
+        .filter(|word| !(word.len() <= 2 && word.chars().all(|c| c.is_ascii_digit())))
+        .map(singular)
+        .collect()
+}
+
+// Simple English plurals: "categories" → "category", "boxes" → "box", "taxes" → "tax"
+fn singular(word: &str) -> String {
+    if word.len() <= 3 || word.ends_with("ss") || word.ends_with("us") || word.ends_with("is") {
+        return word.to_string();
+    }
+    if let Some(stem) = word.strip_suffix("ies") {
+        return format!("{}y", stem);
+    }
+    if let Some(stem) = word.strip_suffix("es") {
+        if stem.ends_with(['s', 'x', 'z']) || stem.ends_with("ch") || stem.ends_with("sh") {
+            return stem.to_string();
+        }
+    }
+    word.strip_suffix('s').unwrap_or(word).to_string()
+}
+
+fn within_one_edit(a: &str, b: &str) -> bool {
+    let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+    let (short, long) = if a.len() <= b.len() { (&a, &b) } else { (&b, &a) };
+    if long.len() - short.len() > 1 {
+        return false;
+    }
+    let prefix = short.iter().zip(long.iter()).take_while(|(x, y)| x == y).count();
+    let suffix = short[prefix..].iter().rev().zip(long[prefix..].iter().rev()).take_while(|(x, y)| x == y).count();
+    if short.len() == long.len() {
+        // One substitution, or two neighbouring letters swapped
+        let rest = short.len() - prefix - suffix;
+        rest <= 1 || (rest == 2 && short[prefix] == long[prefix + 1] && short[prefix + 1] == long[prefix])
+    } else {
+        prefix + suffix >= short.len()
+    }
+}
+
+fn word_similarity(a: &str, b: &str) -> f64 {
+    if a == b {
+        return 1.0;
+    }
+    let long_enough = a.chars().count().min(b.chars().count()) >= MIN_PARTIAL_WORD_LEN;
+    if long_enough && (a.starts_with(b) || b.starts_with(a) || within_one_edit(a, b)) {
+        PARTIAL_WORD_MATCH
+    } else {
+        0.0
+    }
+}
+
+fn best_match(word: &str, candidates: &[String]) -> f64 {
+    candidates.iter().map(|candidate| word_similarity(word, candidate)).fold(0.0, f64::max)
+}
+
+// Dice coefficient over soft word matches: shared words count twice against the
+// total word count of both names
+fn name_similarity(category: &[String], folder: &[String]) -> f64 {
+    if category.is_empty() || folder.is_empty() {
+        return 0.0;
+    }
+    let shared: f64 = category.iter().map(|word| best_match(word, folder)).sum::<f64>()
+        + folder.iter().map(|word| best_match(word, category)).sum::<f64>();
+    shared / (category.len() + folder.len()) as f64
+}
+
+// The folder's own name, or the category's words spread over the folder and its
+// ancestors when the folder name covers some of them
+fn score(category: &[String], folder: &Folder) -> f64 {
+    let own = name_similarity(category, &folder.words);
+    if folder.ancestor_words.is_empty() || category.len() < 2 {
+        return own;
+    }
+    let covered: f64 = category
+        .iter()
+        .map(|word| {
+            let in_name = best_match(word, &folder.words);
+            in_name.max(best_match(word, &folder.ancestor_words) * ANCESTOR_WEIGHT)
+        })
+        .sum();
+    // Only when the folder itself holds part of the category, so "Finance" alone
+    // doesn't match every folder beneath it
+    let names_part = category.iter().any(|word| best_match(word, &folder.words) > 0.0);
+    let spread = if names_part { covered / category.len() as f64 * ANCESTOR_WEIGHT } else { 0.0 };
+    own.max(spread)
+}
+
+fn scan(roots: &[PathBuf], filter: &ScanFilter, max_depth: usize) -> (Vec<Folder>, bool) {
+    let mut folders = Vec::new();
+    let mut seen = HashSet::new();
+    for root in roots {
+        let entries = WalkDir::new(root)
+            .min_depth(1)
+            .max_depth(max_depth)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_entry(|e| e.file_type().is_dir() && !crate::walk_entry_skipped(filter, e))
+            .filter_map(crate::walk_entry_ok);
+        for entry in entries {
+            if folders.len() >= MAX_DIRECTORIES {
+                return (folders, true);
+            }
+            if !seen.insert(entry.path().to_path_buf()) {
+                continue;
+            }
+            let ancestor_words = entry
+                .path()
+                .strip_prefix(root)
+                .ok()
+                .and_then(Path::parent)
+                .map(|parent| parent.iter().flat_map(|name| words(&name.to_string_lossy())).collect())
+                .unwrap_or_default();
+            folders.push(Folder {
+                words: words(&entry.file_name().to_string_lossy()),
+                path: entry.path().to_path_buf(),
+                depth: entry.depth(),
+                ancestor_words,
+            });
+        }
+    }
+    (folders, false)
+}
+
+// Candidates scoring at least `min_score` for each category, best first; equal scores
+// prefer the shallower folder
+pub fn suggest(
+    categories: &[String],
+    roots: &[PathBuf],
+    filter: &ScanFilter,
+    max_depth: usize,
+    min_score: f64,
+) -> DestinationSuggestions {
+    let (folders, truncated) = scan(roots, filter, max_depth);
+    let categories = categories
+        .iter()
+        .map(|category| {
+            // "Finance/Taxes" is matched as the words of all its segments
+            let category_words = words(category);
+            let mut candidates: Vec<(&Folder, f64)> = folders
+                .iter()
+                .map(|folder| (folder, score(&category_words, folder)))
+                .filter(|(_, score)| *score >= min_score)
+                .collect();
+            candidates.sort_by(|(a, a_score), (b, b_score)| {
+                b_score.total_cmp(a_score).then(a.depth.cmp(&b.depth)).then_with(|| a.path.cmp(&b.path))
+            });
+            candidates.truncate(MAX_CANDIDATES);
+            let candidates: Vec<DestinationCandidate> = candidates
+                .into_iter()
+                .map(|(folder, score)| DestinationCandidate {
+                    path: crate::paths::display_path(&folder.path),
+                    score: (score * 100.0).round() / 100.0,
+                })
+                .collect();
+            CategorySuggestions {
+                category: category.clone(),
+                matched: candidates.first().is_some_and(|best| best.score >= GOOD_MATCH),
+                candidates,
+            }
+        })
+        .collect();
+    DestinationSuggestions { categories, directories_scanned: folders.len(), truncated }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_dir;
+
+    fn folder(path: &str) -> Folder {
+        let mut names: Vec<&str> = path.split('/').collect();
+        let name = names.pop().unwrap();
+        Folder {
+            path: PathBuf::from(path),
+            depth: names.len() + 1,
+            words: words(name),
+            ancestor_words: names.into_iter().flat_map(words).collect(),
+        }
+    }
+
+    fn similarity(category: &str, path: &str) -> f64 {
+        score(&words(category), &folder(path))
+    }
+
+    #[test]
+    fn names_are_compared_without_accents_case_or_plurals() {
+        assert_eq!(words("Café Crèmes"), ["cafe", "creme"]);
+        assert_eq!(words("01 - Tax Returns & Receipts"), ["tax", "return", "receipt"]);
+        assert_eq!(similarity("Taxes", "Documents/Finance/Tax"), 1.0);
+        assert_eq!(similarity("Photographs", "Photograph"), 1.0);
+        assert_eq!(similarity("Invoice", "Invoices"), 1.0);
+        assert_eq!(similarity("Categories", "category"), 1.0);
+        assert_eq!(similarity("Résumé", "resume"), 1.0);
+        assert_eq!(similarity("Reciepts", "Receipts"), PARTIAL_WORD_MATCH);
+        assert_eq!(similarity("Photos", "Music"), 0.0);
+    }
+
+    #[test]
+    fn multi_word_categories_use_the_folder_and_its_ancestors() {
+        assert_eq!(similarity("Tax Returns", "Tax Returns"), 1.0);
+        assert_eq!(similarity("Tax Returns", "Returns"), 2.0 / 3.0);
+        let spread = similarity("Finance Taxes", "Documents/Finance/Taxes");
+        assert!(spread > 0.8, "{}", spread);
+        // An ancestor alone isn't a match for everything beneath it
+        assert_eq!(similarity("Finance Taxes", "Documents/Finance/Photos"), 0.0);
+        assert!(similarity("Finance/Taxes", "Finance/Taxes") > similarity("Finance/Taxes", "Taxes"));
+    }
+
+    #[test]
+    fn existing_folders_are_suggested_per_category() {
+        let root = temp_dir("suggest");
+        for dir in ["Documents/Finance/Taxes", "Documents/Finance/Invoices", "Pictures/Vacation Photos"] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        std::fs::create_dir_all(root.join("Taxes Old/.git")).unwrap();
+        std::fs::write(root.join("Documents/taxes.txt"), "not a folder").unwrap();
+
+        let categories = vec!["Taxes".to_string(), "Invoice".to_string(), "Recipes".to_string()];
+        let roots = std::slice::from_ref(&root);
+        let suggestions = suggest(&categories, roots, &ScanFilter::default(), DEFAULT_MAX_DEPTH, 0.5);
+        let [taxes, invoices, recipes] = &suggestions.categories[..] else { panic!("three categories") };
+        assert!(taxes.matched && invoices.matched && !recipes.matched);
+        assert_eq!(taxes.candidates[0].path, crate::paths::display_path(root.join("Documents/Finance/Taxes")));
+        assert_eq!(taxes.candidates[1].path, crate::paths::display_path(root.join("Taxes Old")));
+        assert!(taxes.candidates[1].score < 1.0);
+        assert!(recipes.candidates.is_empty());
+        // Hidden folders are skipped, files aren't folders
+        assert_eq!(suggestions.directories_scanned, 7);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
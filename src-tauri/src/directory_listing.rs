@@ -0,0 +1,430 @@
+// Listing the files under a directory for read_directory and friends. Entries that
+// can't be read (permission denied, removed mid-walk, symlink cycles) are collected
+// with the reason instead of disappearing, and long walks report progress a few times
+// a second so the UI can show where they are. Listings can be sorted and paged; see
+// ListingOptions.
+use crate::cloud_files;
+use crate::error::AppError;
+use crate::paths;
+use crate::provenance::{self, Provenance};
+use crate::scan_filter::ScanFilter;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::fs;
+use std::io::ErrorKind;
+use std::ops::ControlFlow;
+use std::path::Path;
+use std::time::{Duration, Instant, UNIX_EPOCH};
+use tracing::warn;
+use walkdir::WalkDir;
+
+// How often a walk reports progress
+pub const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DirectoryEntry {
+    pub path: String,
+    // A link to the file rather than the file itself (followed or not)
+    pub is_symlink: bool,
+    // Only stored in the cloud; reading it would download it
+    pub is_cloud_placeholder: bool,
+    pub size: u64,
+    // 0 when the file system doesn't say
+    pub modified_ms: u64,
+    // Where a download came from, when the browser recorded it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provenance: Option<Provenance>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SkipReason {
+    PermissionDenied,
+    // Removed or renamed while the walk was running
+    NotFound,
+    SymlinkCycle,
+    Other,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SkippedPath {
+    pub path: String,
+    pub reason: SkipReason,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ScanProgress {
+    pub directories_visited: u64,
+    pub files_found: u64,
+    // The directory being read
+    pub current_path: String,
+}
+
+#[derive(Debug, Default)]
+pub struct Listing {
+    pub entries: Vec<DirectoryEntry>,
+    pub skipped: Vec<SkippedPath>,
+    pub directories_visited: u64,
+    // False when the walk stopped early at stop_after
+    pub complete: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortKey {
+    // File name, ignoring case
+    Name,
+    Size,
+    Modified,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+// Sorting and paging for a listing. Without a sort, files come in the order the file
+// system gives them, which differs between platforms and runs; with one, ties are
+// broken by full path (always ascending), so the order is the same on every call and a
+// page can be resumed from next_offset. A sort needs every file before the first page
+// can be cut, so it walks the whole tree even with a limit; without one the walk stops
+// once the page is filled.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ListingOptions {
+    pub sort: Option<SortKey>,
+    pub order: SortOrder,
+    pub offset: usize,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Page {
+    pub entries: Vec<DirectoryEntry>,
+    // Files in the whole listing; None when the walk stopped after this page
+    pub total: Option<usize>,
+    // Offset of the next page, when there is one
+    pub next_offset: Option<usize>,
+    // A limit was given but the sort made the whole tree get walked
+    pub sort_forced_full_walk: bool,
+}
+
+impl ListingOptions {
+    // How many files the walk needs to find, or None for all of them. One more than
+    // the page holds, to know whether there's a next page.
+    pub fn stop_after(&self) -> Option<usize> {
+        match (self.sort, self.limit) {
+            (None, Some(limit)) => Some(self.offset.saturating_add(limit).saturating_add(1)),
+            _ => None,
+        }
+    }
+
+    pub fn compare(&self, a: &DirectoryEntry, b: &DirectoryEntry) -> Ordering {
+        let by_key = match self.sort {
+            Some(SortKey::Name) => file_name(&a.path).to_lowercase().cmp(&file_name(&b.path).to_lowercase()),
+            Some(SortKey::Size) => a.size.cmp(&b.size),
+            Some(SortKey::Modified) => a.modified_ms.cmp(&b.modified_ms),
+            None => Ordering::Equal,
+        };
+        let by_key = if self.order == SortOrder::Desc { by_key.reverse() } else { by_key };
+        by_key.then_with(|| a.path.cmp(&b.path))
+    }
+
+    pub fn page(&self, mut entries: Vec<DirectoryEntry>, complete: bool) -> Page {
+        if self.sort.is_some() {
+            entries.sort_by(|a, b| self.compare(a, b));
+        }
+        let found = entries.len();
+        let start = self.offset.min(found);
+        let end = self.limit.map_or(found, |limit| start.saturating_add(limit).min(found));
+        entries.truncate(end);
+        entries.drain(..start);
+        Page {
+            entries,
+            total: complete.then_some(found),
+            next_offset: (end < found).then_some(end),
+            sort_forced_full_walk: self.sort.is_some() && self.limit.is_some(),
+        }
+    }
+}
+
+fn file_name(path: &str) -> &str {
+    path.rsplit(['/', '\\']).next().unwrap_or(path)
+}
+
+fn skipped(path: &Path, reason: SkipReason, error: &dyn std::fmt::Display) -> SkippedPath {
+    let skipped = SkippedPath { path: paths::display_path(path), reason, message: error.to_string() };
+    warn!("Skipping {}: {}", skipped.path, skipped.message);
+    skipped
+}
+
+fn reason_for(kind: Option<ErrorKind>) -> SkipReason {
+    match kind {
+        Some(ErrorKind::PermissionDenied) => SkipReason::PermissionDenied,
+        Some(ErrorKind::NotFound) => SkipReason::NotFound,
+        _ => SkipReason::Other,
+    }
+}
+
+fn walk_error(error: &walkdir::Error, root: &Path) -> SkippedPath {
+    let path = error.path().unwrap_or(root);
+    let reason = match error.loop_ancestor() {
+        Some(_) => SkipReason::SymlinkCycle,
+        None => reason_for(error.io_error().map(std::io::Error::kind)),
+    };
+    skipped(path, reason, error)
+}
+
+fn entry(path: &Path, is_symlink: bool, metadata: Option<fs::Metadata>) -> DirectoryEntry {
+    DirectoryEntry {
+        path: paths::display_path(path),
+        is_symlink,
+        is_cloud_placeholder: metadata.as_ref().is_some_and(cloud_files::is_placeholder_metadata),
+        size: metadata.as_ref().map_or(0, |m| m.len()),
+        modified_ms: metadata
+            .as_ref()
+            .and_then(|m| m.modified().ok())
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |d| d.as_millis() as u64),
+        provenance: provenance::read(path),
+    }
+}
+
+pub fn walk_entry_skipped(filter: &ScanFilter, entry: &walkdir::DirEntry) -> bool {
+    let name = entry.file_name().to_string_lossy();
+    filter.skips(&name, entry.metadata().ok().as_ref())
+}
+
+// Files in `path`, and in its subdirectories with include_subdirectories. Only the root
+// itself failing to open is an error. The walk ends once stop_after files are found.
+// on_progress hears about it at most every PROGRESS_INTERVAL.
+pub fn list(
+    path: &str,
+    include_subdirectories: bool,
+    follow_symlinks: bool,
+    filter: &ScanFilter,
+    stop_after: Option<usize>,
+    on_progress: &mut dyn FnMut(&ScanProgress),
+) -> Result<Listing, AppError> {
+    let mut entries = Vec::new();
+    let mut listing = walk(path, include_subdirectories, follow_symlinks, filter, on_progress, &mut |entry| {
+        entries.push(entry);
+        match stop_after {
+            Some(stop_after) if entries.len() >= stop_after => ControlFlow::Break(()),
+            _ => ControlFlow::Continue(()),
+        }
+    })?;
+    listing.entries = entries;
+    Ok(listing)
+}
+
+// The walk behind list(), handing each file to on_entry as it's found instead of
+// collecting them; the returned Listing has no entries. on_entry breaking ends the walk
+// with complete set to false.
+pub fn walk(
+    path: &str,
+    include_subdirectories: bool,
+    follow_symlinks: bool,
+    filter: &ScanFilter,
+    on_progress: &mut dyn FnMut(&ScanProgress),
+    on_entry: &mut dyn FnMut(DirectoryEntry) -> ControlFlow<()>,
+) -> Result<Listing, AppError> {
+    let root = paths::normalize_path(path);
+    let mut listing = Listing { complete: true, ..Default::default() };
+    let mut progress = ScanProgress::default();
+    let mut reported = Instant::now();
+    let mut report = |progress: &ScanProgress| {
+        if reported.elapsed() >= PROGRESS_INTERVAL {
+            reported = Instant::now();
+            on_progress(progress);
+        }
+    };
+    if !include_subdirectories {
+        let read = fs::read_dir(&root).map_err(|e| AppError::from(e).with_path(path))?;
+        listing.directories_visited = 1;
+        progress.directories_visited = 1;
+        progress.current_path = paths::display_path(&root);
+        for dir_entry in read {
+            let dir_entry = match dir_entry {
+                Ok(dir_entry) => dir_entry,
+                Err(e) => {
+                    listing.skipped.push(skipped(&root, reason_for(Some(e.kind())), &e));
+                    continue;
+                }
+            };
+            let entry_path = dir_entry.path();
+            let metadata = dir_entry.metadata().ok();
+            let name = dir_entry.file_name().to_string_lossy().to_string();
+            if entry_path.is_file() && !filter.skips(&name, metadata.as_ref()) {
+                progress.files_found += 1;
+                report(&progress);
+                let is_symlink = dir_entry.file_type().is_ok_and(|t| t.is_symlink());
+                if on_entry(entry(&entry_path, is_symlink, metadata)).is_break() {
+                    listing.complete = false;
+                    break;
+                }
+            }
+        }
+        return Ok(listing);
+    }
+
+    let walk = WalkDir::new(&root)
+        .follow_links(follow_symlinks)
+        .into_iter()
+        // The chosen root is always scanned, even when it is itself hidden
+        .filter_entry(|e| e.depth() == 0 || !walk_entry_skipped(filter, e));
+    for walked in walk {
+        let walked = match walked {
+            Ok(walked) => walked,
+            // The root can't be listed at all
+            Err(e) if e.depth() == 0 && e.loop_ancestor().is_none() => {
+                let io = e.into_io_error().unwrap_or_else(|| std::io::Error::other("Failed to read directory"));
+                return Err(AppError::from(io).with_path(path));
+            }
+            Err(e) => {
+                listing.skipped.push(walk_error(&e, &root));
+                continue;
+            }
+        };
+        if walked.file_type().is_dir() {
+            listing.directories_visited += 1;
+            progress.directories_visited += 1;
+            progress.current_path = paths::display_path(walked.path());
+        } else if walked.path().is_file() {
+            progress.files_found += 1;
+            if on_entry(entry(walked.path(), walked.path_is_symlink(), walked.metadata().ok())).is_break() {
+                listing.complete = false;
+                break;
+            }
+        }
+        report(&progress);
+    }
+    Ok(listing)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_dir;
+
+    fn no_progress(_: &ScanProgress) {}
+
+    #[test]
+    fn files_are_listed_recursively_and_missing_roots_fail() {
+        let dir = temp_dir("recursive");
+        fs::create_dir_all(dir.join("a/b")).unwrap();
+        fs::write(dir.join("top.txt"), "x").unwrap();
+        fs::write(dir.join("a/b/deep.txt"), "x").unwrap();
+        let root = dir.to_string_lossy().to_string();
+        let filter = ScanFilter::new(Some(false), Some(Vec::new()));
+
+        let listing = list(&root, true, false, &filter, None, &mut no_progress).unwrap();
+        let mut names: Vec<String> = listing.entries.iter().map(|e| e.path.clone()).collect();
+        names.sort();
+        assert_eq!(names.len(), 2);
+        assert!(names[0].ends_with("deep.txt") && names[1].ends_with("top.txt"), "{:?}", names);
+        assert_eq!((listing.directories_visited, listing.skipped.len()), (3, 0));
+        assert_eq!(list(&root, false, false, &filter, None, &mut no_progress).unwrap().entries.len(), 1);
+
+        let missing = dir.join("missing").to_string_lossy().to_string();
+        assert!(list(&missing, true, false, &filter, None, &mut no_progress).is_err());
+        assert!(list(&missing, false, false, &filter, None, &mut no_progress).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn sorted_pages_are_stable_and_unsorted_walks_stop_early() {
+        let entry = |path: &str, size: u64, modified_ms: u64| DirectoryEntry {
+            path: path.to_string(),
+            is_symlink: false,
+            is_cloud_placeholder: false,
+            size,
+            modified_ms,
+            provenance: None,
+        };
+        let entries = vec![
+            entry("/d/b.txt", 5, 30),
+            entry("/c/B.txt", 5, 10),
+            entry("/a/a.txt", 9, 20),
+            entry("/e/C.txt", 1, 20),
+        ];
+        let paths = |page: &Page| page.entries.iter().map(|e| e.path.clone()).collect::<Vec<_>>();
+
+        let by_name = ListingOptions { sort: Some(SortKey::Name), limit: Some(2), ..Default::default() };
+        let first = by_name.page(entries.clone(), true);
+        // Same name ignoring case: the full path decides
+        assert_eq!(paths(&first), ["/a/a.txt", "/c/B.txt"]);
+        assert_eq!((first.total, first.next_offset, first.sort_forced_full_walk), (Some(4), Some(2), true));
+        let second = ListingOptions { offset: 2, ..by_name }.page(entries.clone(), true);
+        assert_eq!(paths(&second), ["/d/b.txt", "/e/C.txt"]);
+        assert_eq!(second.next_offset, None);
+
+        let by_size = ListingOptions { sort: Some(SortKey::Size), order: SortOrder::Desc, ..Default::default() };
+        assert_eq!(paths(&by_size.page(entries.clone(), true)), ["/a/a.txt", "/c/B.txt", "/d/b.txt", "/e/C.txt"]);
+        let by_modified = ListingOptions { sort: Some(SortKey::Modified), ..Default::default() };
+        assert_eq!(paths(&by_modified.page(entries.clone(), true)), ["/c/B.txt", "/a/a.txt", "/e/C.txt", "/d/b.txt"]);
+        assert_eq!(ListingOptions { offset: 10, ..by_modified }.page(entries, true).entries.len(), 0);
+
+        let dir = temp_dir("paged");
+        for name in ["1.txt", "2.txt", "3.txt", "4.txt", "5.txt"] {
+            fs::write(dir.join(name), "x").unwrap();
+        }
+        let root = dir.to_string_lossy().to_string();
+        let filter = ScanFilter::new(Some(false), Some(Vec::new()));
+        let unsorted = ListingOptions { offset: 1, limit: Some(2), ..Default::default() };
+        let listing = list(&root, true, false, &filter, unsorted.stop_after(), &mut no_progress).unwrap();
+        assert_eq!((listing.entries.len(), listing.complete), (4, false));
+        let page = unsorted.page(listing.entries, listing.complete);
+        assert_eq!((page.entries.len(), page.total, page.next_offset), (2, None, Some(3)));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unreadable_directories_and_cycles_are_reported() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = temp_dir("skipped");
+        fs::create_dir_all(dir.join("loop")).unwrap();
+        std::os::unix::fs::symlink(&dir, dir.join("loop/back")).unwrap();
+        fs::create_dir_all(dir.join("locked")).unwrap();
+        fs::write(dir.join("locked/secret.txt"), "x").unwrap();
+        fs::set_permissions(dir.join("locked"), fs::Permissions::from_mode(0o000)).unwrap();
+        let root = dir.to_string_lossy().to_string();
+        let filter = ScanFilter::new(Some(false), Some(Vec::new()));
+
+        let listing = list(&root, true, true, &filter, None, &mut no_progress).unwrap();
+        let reasons: Vec<SkipReason> = listing.skipped.iter().map(|s| s.reason).collect();
+        assert!(reasons.contains(&SkipReason::SymlinkCycle), "{:?}", listing.skipped);
+        // Root can read anything, so the permission check only means something for others
+        if fs::read_dir(dir.join("locked")).is_err() {
+            let locked = listing.skipped.iter().find(|s| s.reason == SkipReason::PermissionDenied).unwrap();
+            assert!(locked.path.ends_with("locked"), "{:?}", locked);
+        }
+        fs::set_permissions(dir.join("locked"), fs::Permissions::from_mode(0o755)).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_are_marked_and_dangling_ones_left_out() {
+        let dir = temp_dir("symlinks");
+        fs::write(dir.join("real.txt"), "x").unwrap();
+        std::os::unix::fs::symlink(dir.join("real.txt"), dir.join("link.txt")).unwrap();
+        std::os::unix::fs::symlink(dir.join("gone.txt"), dir.join("dangling.txt")).unwrap();
+        let root = dir.to_string_lossy().to_string();
+        let filter = ScanFilter::new(Some(false), Some(Vec::new()));
+
+        for include_subdirectories in [false, true] {
+            let listing = list(&root, include_subdirectories, false, &filter, None, &mut no_progress).unwrap();
+            let mut marked: Vec<(String, bool)> =
+                listing.entries.iter().map(|e| (file_name(&e.path).to_string(), e.is_symlink)).collect();
+            marked.sort();
+            assert_eq!(marked, [("link.txt".to_string(), true), ("real.txt".to_string(), false)]);
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,290 @@
+// Streaming listings for directories too big to send in one reply. A camera dump with a
+// million files would otherwise be one huge Vec and one huge JSON message that freezes
+// the webview. Here the walk hands files over in chunks as it finds them, waiting
+// between chunks so the frontend isn't flooded, and checks for cancellation between
+// chunks. Without a sort, nothing more than a chunk is held at once; a sort needs every
+// file before the first one can be sent, so sorted streams still collect the whole tree.
+use crate::directory_listing::{self, DirectoryEntry, ListingOptions, ScanProgress, SkippedPath};
+use crate::error::AppError;
+use crate::operations::CancelToken;
+use crate::scan_filter::ScanFilter;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::ops::ControlFlow;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_CHUNK_SIZE: usize = 1000;
+const MAX_CHUNK_SIZE: usize = 10_000;
+pub const DEFAULT_CHUNK_INTERVAL_MS: u64 = 50;
+
+// The same sorting and paging as read_directory, plus how the chunks are paced
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StreamOptions {
+    #[serde(flatten)]
+    pub listing: ListingOptions,
+    pub chunk_size: usize,
+    // Least time between two chunks
+    pub chunk_interval_ms: u64,
+}
+
+impl Default for StreamOptions {
+    fn default() -> Self {
+        Self {
+            listing: ListingOptions::default(),
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            chunk_interval_ms: DEFAULT_CHUNK_INTERVAL_MS,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct StreamSummary {
+    pub files_sent: usize,
+    // Files in the whole listing; None when the walk stopped early
+    pub total: Option<usize>,
+    pub next_offset: Option<usize>,
+    pub sort_forced_full_walk: bool,
+    pub directories_visited: u64,
+    pub skipped: Vec<SkippedPath>,
+    pub cancelled: bool,
+}
+
+struct Stream {
+    window: String,
+    token: CancelToken,
+}
+
+// Running streams by stream_id, remembering the window each one sends to so they can
+// be stopped when it closes
+#[derive(Default)]
+pub struct DirectoryStreams {
+    streams: Mutex<HashMap<String, Stream>>,
+    next_id: AtomicU64,
+}
+
+impl DirectoryStreams {
+    pub fn start(&self, window: &str) -> (String, CancelToken) {
+        let stream_id = format!("directory-stream-{}", self.next_id.fetch_add(1, Ordering::SeqCst) + 1);
+        let token = CancelToken::default();
+        let stream = Stream { window: window.to_string(), token: token.clone() };
+        self.streams.lock().unwrap().insert(stream_id.clone(), stream);
+        (stream_id, token)
+    }
+
+    // Whether the stream was running
+    pub fn cancel(&self, stream_id: &str) -> bool {
+        match self.streams.lock().unwrap().get(stream_id) {
+            Some(stream) => {
+                stream.token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Cancels and forgets every stream sending to `window`; returns how many there were
+    pub fn cancel_window(&self, window: &str) -> usize {
+        let mut streams = self.streams.lock().unwrap();
+        let before = streams.len();
+        streams.retain(|_, stream| {
+            if stream.window == window {
+                stream.token.cancel();
+            }
+            stream.window != window
+        });
+        before - streams.len()
+    }
+
+    pub fn finish(&self, stream_id: &str) {
+        self.streams.lock().unwrap().remove(stream_id);
+    }
+}
+
+// Holds chunks back so two are never sent closer together than the interval
+struct Pacer {
+    interval: Duration,
+    last: Option<Instant>,
+}
+
+impl Pacer {
+    fn wait(&mut self) {
+        if let Some(last) = self.last {
+            let since = last.elapsed();
+            if since < self.interval {
+                std::thread::sleep(self.interval - since);
+            }
+        }
+        self.last = Some(Instant::now());
+    }
+}
+
+// Lists `path` like directory_listing::list, handing files to on_chunk a chunk at a
+// time. Blocks, pacing the chunks, until the walk ends or `token` is cancelled; a
+// cancelled stream drops the files it hadn't sent yet.
+#[allow(clippy::too_many_arguments)]
+pub fn run(
+    path: &str,
+    include_subdirectories: bool,
+    follow_symlinks: bool,
+    filter: &ScanFilter,
+    options: &StreamOptions,
+    token: &CancelToken,
+    on_progress: &mut dyn FnMut(&ScanProgress),
+    on_chunk: &mut dyn FnMut(Vec<DirectoryEntry>),
+) -> Result<StreamSummary, AppError> {
+    let chunk_size = options.chunk_size.clamp(1, MAX_CHUNK_SIZE);
+    let mut pacer = Pacer { interval: Duration::from_millis(options.chunk_interval_ms), last: None };
+    let mut summary = StreamSummary::default();
+    let mut send = |chunk: Vec<DirectoryEntry>, summary: &mut StreamSummary| {
+        pacer.wait();
+        summary.files_sent += chunk.len();
+        on_chunk(chunk);
+    };
+
+    if options.listing.sort.is_some() {
+        let listing =
+            directory_listing::list(path, include_subdirectories, follow_symlinks, filter, None, on_progress)?;
+        let page = options.listing.page(listing.entries, listing.complete);
+        summary.total = page.total;
+        summary.next_offset = page.next_offset;
+        summary.sort_forced_full_walk = page.sort_forced_full_walk;
+        summary.directories_visited = listing.directories_visited;
+        summary.skipped = listing.skipped;
+        let mut entries = page.entries.into_iter().peekable();
+        while entries.peek().is_some() {
+            if token.is_cancelled() {
+                summary.cancelled = true;
+                break;
+            }
+            send(entries.by_ref().take(chunk_size).collect(), &mut summary);
+        }
+        return Ok(summary);
+    }
+
+    let ListingOptions { offset, limit, .. } = options.listing;
+    let mut seen = 0usize;
+    let mut chunk = Vec::with_capacity(chunk_size);
+    let listing =
+        directory_listing::walk(path, include_subdirectories, follow_symlinks, filter, on_progress, &mut |entry| {
+            if token.is_cancelled() {
+                summary.cancelled = true;
+                return ControlFlow::Break(());
+            }
+            let index = seen;
+            seen += 1;
+            if index < offset {
+                return ControlFlow::Continue(());
+            }
+            if limit.is_some_and(|limit| index - offset >= limit) {
+                summary.next_offset = Some(index);
+                return ControlFlow::Break(());
+            }
+            chunk.push(entry);
+            if chunk.len() < chunk_size {
+                return ControlFlow::Continue(());
+            }
+            send(std::mem::replace(&mut chunk, Vec::with_capacity(chunk_size)), &mut summary);
+            ControlFlow::Continue(())
+        })?;
+    if token.is_cancelled() {
+        summary.cancelled = true;
+    } else if !chunk.is_empty() {
+        send(chunk, &mut summary);
+    }
+    summary.total = listing.complete.then_some(seen);
+    summary.directories_visited = listing.directories_visited;
+    summary.skipped = listing.skipped;
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_dir;
+    use std::fs;
+
+    // `files` numbered files, alternating between the directory and its "sub" folder
+    fn dir_with_files(name: &str, files: usize) -> std::path::PathBuf {
+        let dir = temp_dir(name);
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        for i in 0..files {
+            let parent = if i % 2 == 0 { dir.clone() } else { dir.join("sub") };
+            fs::write(parent.join(format!("{:03}.txt", i)), "x".repeat(i)).unwrap();
+        }
+        dir
+    }
+
+    fn stream(dir: &std::path::Path, options: &StreamOptions, token: &CancelToken) -> (Vec<usize>, StreamSummary) {
+        let filter = ScanFilter::new(Some(false), Some(Vec::new()));
+        let mut chunks = Vec::new();
+        let summary = run(&dir.to_string_lossy(), true, false, &filter, options, token, &mut |_| {}, &mut |chunk| {
+            chunks.push(chunk.len())
+        })
+        .unwrap();
+        (chunks, summary)
+    }
+
+    #[test]
+    fn files_arrive_in_paced_chunks_and_share_listing_options() {
+        let dir = dir_with_files("chunks", 25);
+        let options = StreamOptions { chunk_size: 10, chunk_interval_ms: 20, ..Default::default() };
+        let started = Instant::now();
+        let (chunks, summary) = stream(&dir, &options, &CancelToken::default());
+        assert_eq!(chunks, [10, 10, 5]);
+        // Three chunks, two gaps between them
+        assert!(started.elapsed() >= Duration::from_millis(40), "{:?}", started.elapsed());
+        assert_eq!((summary.files_sent, summary.total, summary.next_offset), (25, Some(25), None));
+        assert_eq!((summary.directories_visited, summary.cancelled), (2, false));
+
+        let paged = StreamOptions {
+            listing: ListingOptions { offset: 5, limit: Some(12), ..Default::default() },
+            chunk_size: 10,
+            chunk_interval_ms: 0,
+        };
+        let (chunks, summary) = stream(&dir, &paged, &CancelToken::default());
+        assert_eq!(chunks, [10, 2]);
+        assert_eq!((summary.total, summary.next_offset), (None, Some(17)));
+
+        let mut sorted = paged.clone();
+        sorted.listing.sort = Some(directory_listing::SortKey::Size);
+        sorted.listing.order = directory_listing::SortOrder::Desc;
+        let filter = ScanFilter::new(Some(false), Some(Vec::new()));
+        let mut sizes = Vec::new();
+        let summary = run(
+            &dir.to_string_lossy(),
+            true,
+            false,
+            &filter,
+            &sorted,
+            &CancelToken::default(),
+            &mut |_| {},
+            &mut |chunk| sizes.extend(chunk.iter().map(|e| e.size)),
+        )
+        .unwrap();
+        assert_eq!(sizes, (8..=19).rev().collect::<Vec<u64>>());
+        assert_eq!((summary.total, summary.next_offset, summary.sort_forced_full_walk), (Some(25), Some(17), true));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn cancelling_stops_the_walk_and_closing_a_window_cancels_its_streams() {
+        let dir = dir_with_files("cancel", 30);
+        let streams = DirectoryStreams::default();
+        let (first, token) = streams.start("main");
+        let (second, other) = streams.start("settings");
+        assert_ne!(first, second);
+        assert!(streams.cancel(&first) && !streams.cancel("directory-stream-99"));
+        let options = StreamOptions { chunk_size: 10, chunk_interval_ms: 0, ..Default::default() };
+        let (chunks, summary) = stream(&dir, &options, &token);
+        assert!(chunks.is_empty() && summary.cancelled && summary.total.is_none(), "{:?}", summary);
+
+        assert_eq!(streams.cancel_window("settings"), 1);
+        assert!(other.is_cancelled() && !streams.cancel(&second));
+        streams.finish(&first);
+        assert_eq!(streams.cancel_window("main"), 0);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
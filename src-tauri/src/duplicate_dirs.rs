@@ -0,0 +1,592 @@
+// Finds directories whose whole content trees are copies of each other ("Backup of
+// Backup of Projects"). File hashes roll up into a Merkle hash per directory — the hash
+// of its sorted (name, child hash) pairs — so identical trees hash equal no matter
+// where they live. Optionally, directories that mostly overlap are reported with the
+// share of bytes they have in common.
+use crate::checkpoints::{Checkpointer, HashedFile};
+use crate::cloud_files;
+use crate::io_priority::{self, IoPriority};
+use crate::paths;
+use crate::progress::{ProgressEstimate, ProgressTracker, RateUnit};
+use crate::scan_filter::ScanFilter;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, UNIX_EPOCH};
+use walkdir::WalkDir;
+
+// How often find reports hashing progress
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+// Caps the pairwise comparison done for partially overlapping directories
+const MAX_SIMILARITY_PAIRS: usize = 50_000;
+const MAX_CHANGED_LISTED: usize = 100;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DuplicateDirectoryGroup {
+    pub directories: Vec<String>,
+    // Size and file count of one copy
+    pub total_size: u64,
+    pub file_count: u64,
+    // Bytes freed by keeping a single copy
+    pub reclaimable_bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SimilarDirectories {
+    pub first: String,
+    pub second: String,
+    // Shared bytes as a percentage of the larger directory
+    pub similarity: f64,
+    pub shared_bytes: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DuplicateDirectoryReport {
+    // Largest reclaimable space first; directories inside a reported group aren't repeated
+    pub groups: Vec<DuplicateDirectoryGroup>,
+    pub similar: Vec<SimilarDirectories>,
+    pub files_hashed: u64,
+    // Files that couldn't be read; their directories are treated as unique
+    pub hash_errors: u64,
+    // Cloud placeholders left unhashed so they aren't downloaded; likewise unique
+    pub cloud_placeholders: u64,
+    // Unix ms when the files were listed
+    pub scanned_at: u64,
+    // Files rewritten or gone between being listed and being hashed. They're treated as
+    // unique, so a hash that no longer matches the file never makes a duplicate.
+    pub files_changed: u64,
+    pub files_vanished: u64,
+    // The first MAX_CHANGED_LISTED of them
+    pub changed_files: Vec<ChangedFile>,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ChangedFile {
+    pub path: String,
+    pub vanished: bool,
+}
+
+#[derive(Default)]
+struct Dir {
+    path: PathBuf,
+    parent: Option<usize>,
+    children: Vec<usize>,
+    files: Vec<usize>,
+    hash: String,
+    total_size: u64,
+    file_count: u64,
+}
+
+struct File {
+    name: String,
+    path: PathBuf,
+    size: u64,
+    modified_ms: u64,
+    is_cloud_placeholder: bool,
+    // Content hash, or a token unique to this file when no other file has its size
+    hash: String,
+}
+
+// Hashing so far, reported while find reads files
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct HashProgress {
+    pub files_hashed: u64,
+    pub files_to_hash: u64,
+    pub bytes_hashed: u64,
+    pub bytes_to_hash: u64,
+    // Of files_hashed, those whose hash came from a checkpoint instead of the disk
+    pub files_from_checkpoint: u64,
+    #[serde(flatten)]
+    pub estimate: ProgressEstimate,
+}
+
+fn hash_file(path: &Path, priority: IoPriority) -> std::io::Result<String> {
+    let mut file = io_priority::open(path, priority)?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+// Cloud placeholders are only hashed with `hydrate`, which downloads them. Files are
+// read at `priority`; on_progress hears about hashing a few times a second. With a
+// checkpoint, files it has seen at the same size and mtime aren't read again and every
+// new hash is recorded in it. Symlinks are left out unless follow_symlinks is set, so a
+// link isn't reported as a copy of its target; cycles are logged and skipped.
+#[allow(clippy::too_many_arguments)]
+pub fn find(
+    roots: &[PathBuf],
+    filter: &ScanFilter,
+    follow_symlinks: bool,
+    min_similarity: Option<f64>,
+    hydrate: bool,
+    priority: IoPriority,
+    on_progress: &mut dyn FnMut(&HashProgress),
+    checkpoint: Option<&mut Checkpointer>,
+) -> DuplicateDirectoryReport {
+    let _thread_priority = io_priority::ThreadPriority::enter(priority);
+    let listing = list(roots, filter, follow_symlinks);
+    analyze(listing, min_similarity, hydrate, priority, on_progress, checkpoint)
+}
+
+struct Listing {
+    dirs: Vec<Dir>,
+    files: Vec<File>,
+    scanned_at: u64,
+}
+
+fn modified_ms(metadata: &fs::Metadata) -> u64 {
+    metadata.modified().ok().and_then(|t| t.duration_since(UNIX_EPOCH).ok()).map_or(0, |d| d.as_millis() as u64)
+}
+
+enum Recheck {
+    Same,
+    Changed,
+    Vanished,
+}
+
+// Whether the file still has the size and mtime it was listed with. A file that can't
+// be looked at for another reason is left for hashing to fail on.
+fn recheck(file: &File) -> Recheck {
+    match fs::metadata(&file.path) {
+        Ok(metadata) if metadata.len() != file.size || modified_ms(&metadata) != file.modified_ms => Recheck::Changed,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Recheck::Vanished,
+        _ => Recheck::Same,
+    }
+}
+
+fn list(roots: &[PathBuf], filter: &ScanFilter, follow_symlinks: bool) -> Listing {
+    let scanned_at = crate::journal::now_ms();
+    let mut dirs: Vec<Dir> = Vec::new();
+    let mut files: Vec<File> = Vec::new();
+
+    for root in paths::distinct_roots(roots) {
+        let mut index: HashMap<PathBuf, usize> = HashMap::new();
+        let entries = WalkDir::new(&root)
+            .follow_links(follow_symlinks)
+            .into_iter()
+            .filter_entry(|e| e.depth() == 0 || !crate::walk_entry_skipped(filter, e))
+            .filter_map(crate::walk_entry_ok);
+        for entry in entries {
+            let parent = entry.path().parent().and_then(|p| index.get(p).copied());
+            if entry.file_type().is_dir() {
+                let id = dirs.len();
+                dirs.push(Dir { path: entry.path().to_path_buf(), parent, ..Default::default() });
+                if let Some(parent) = parent {
+                    dirs[parent].children.push(id);
+                }
+                index.insert(entry.path().to_path_buf(), id);
+            } else if entry.file_type().is_file() {
+                let Some(parent) = parent else { continue };
+                dirs[parent].files.push(files.len());
+                let metadata = entry.metadata().ok();
+                files.push(File {
+                    name: entry.file_name().to_string_lossy().to_string(),
+                    path: entry.path().to_path_buf(),
+                    size: metadata.as_ref().map(|m| m.len()).unwrap_or(0),
+                    modified_ms: metadata.as_ref().map_or(0, modified_ms),
+                    is_cloud_placeholder: metadata.as_ref().is_some_and(cloud_files::is_placeholder_metadata),
+                    hash: String::new(),
+                });
+            }
+        }
+    }
+    Listing { dirs, files, scanned_at }
+}
+
+// Hashes and groups a listing. Each file is checked against its listing just before it's
+// read and again after, so one changed or removed meanwhile is counted and set apart
+// instead of failing the search or leaving a stale hash.
+fn analyze(
+    listing: Listing,
+    min_similarity: Option<f64>,
+    hydrate: bool,
+    priority: IoPriority,
+    on_progress: &mut dyn FnMut(&HashProgress),
+    mut checkpoint: Option<&mut Checkpointer>,
+) -> DuplicateDirectoryReport {
+    let Listing { mut dirs, mut files, scanned_at } = listing;
+    let mut report = DuplicateDirectoryReport { scanned_at, ..Default::default() };
+
+    // Only files sharing a size with another file can be duplicates, so only those are read
+    let mut sizes: HashMap<u64, usize> = HashMap::new();
+    for file in &files {
+        *sizes.entry(file.size).or_default() += 1;
+    }
+    let needs_hash = |file: &File| sizes[&file.size] >= 2 && (hydrate || !file.is_cloud_placeholder);
+    let mut progress = HashProgress::default();
+    for file in files.iter().filter(|file| needs_hash(file)) {
+        progress.files_to_hash += 1;
+        progress.bytes_to_hash += file.size;
+    }
+    let mut tracker = ProgressTracker::new(RateUnit::Bytes, Some(progress.bytes_to_hash));
+    let mut reported = Instant::now();
+    for file in files.iter_mut() {
+        file.hash = if sizes[&file.size] < 2 {
+            format!("unique:{}", file.path.display())
+        } else if file.is_cloud_placeholder && !hydrate {
+            report.cloud_placeholders += 1;
+            format!("unreadable:{}", file.path.display())
+        } else {
+            let key = paths::display_path(&file.path);
+            let changed = |report: &mut DuplicateDirectoryReport, vanished: bool| {
+                if vanished {
+                    report.files_vanished += 1;
+                } else {
+                    report.files_changed += 1;
+                }
+                if report.changed_files.len() < MAX_CHANGED_LISTED {
+                    report.changed_files.push(ChangedFile { path: key.clone(), vanished });
+                }
+                format!("changed:{}", file.path.display())
+            };
+            let hash = match recheck(file) {
+                Recheck::Changed => changed(&mut report, false),
+                Recheck::Vanished => changed(&mut report, true),
+                Recheck::Same => {
+                    let known = checkpoint.as_deref().and_then(|c| c.known(&key, file.size, file.modified_ms));
+                    if let Some(known) = known {
+                        report.files_hashed += 1;
+                        progress.files_from_checkpoint += 1;
+                        known.to_string()
+                    } else {
+                        let hashed = hash_file(&file.path, priority);
+                        io_priority::between_files(priority);
+                        match (hashed, recheck(file)) {
+                            (Ok(hash), Recheck::Same) => {
+                                report.files_hashed += 1;
+                                if let Some(checkpoint) = checkpoint.as_deref_mut() {
+                                    let (size, modified_ms) = (file.size, file.modified_ms);
+                                    checkpoint.record(key, HashedFile { size, modified_ms, hash: hash.clone() });
+                                }
+                                hash
+                            }
+                            (_, Recheck::Vanished) => changed(&mut report, true),
+                            (Ok(_), Recheck::Changed) => changed(&mut report, false),
+                            (Err(_), _) => {
+                                report.files_hashed += 1;
+                                report.hash_errors += 1;
+                                format!("unreadable:{}", file.path.display())
+                            }
+                        }
+                    }
+                }
+            };
+            progress.files_hashed += 1;
+            progress.bytes_hashed += file.size;
+            let done = progress.files_hashed == progress.files_to_hash;
+            if done || reported.elapsed() >= PROGRESS_INTERVAL {
+                reported = Instant::now();
+                progress.estimate = tracker.update(progress.bytes_hashed);
+                on_progress(&progress);
+            }
+            hash
+        };
+    }
+    if let Some(checkpoint) = checkpoint {
+        checkpoint.flush();
+    }
+
+    // Walks visit parents before children, so the reverse order finishes children first
+    for id in (0..dirs.len()).rev() {
+        let mut entries: Vec<String> = dirs[id]
+            .files
+            .iter()
+            .map(|&f| format!("f\0{}\0{}", files[f].name, files[f].hash))
+            .collect();
+        let mut total_size: u64 = dirs[id].files.iter().map(|&f| files[f].size).sum();
+        let mut file_count = dirs[id].files.len() as u64;
+        for &child in &dirs[id].children {
+            let name = dirs[child].path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+            entries.push(format!("d\0{}\0{}", name, dirs[child].hash));
+            total_size += dirs[child].total_size;
+            file_count += dirs[child].file_count;
+        }
+        entries.sort();
+        let mut hasher = Sha256::new();
+        for entry in &entries {
+            hasher.update(entry.as_bytes());
+            hasher.update(b"\n");
+        }
+        let dir = &mut dirs[id];
+        dir.hash = format!("{:x}", hasher.finalize());
+        dir.total_size = total_size;
+        dir.file_count = file_count;
+    }
+
+    let mut by_hash: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (id, dir) in dirs.iter().enumerate() {
+        if dir.file_count > 0 {
+            by_hash.entry(dir.hash.as_str()).or_default().push(id);
+        }
+    }
+    let duplicated: HashSet<usize> = by_hash.values().filter(|ids| ids.len() > 1).flatten().copied().collect();
+    for ids in by_hash.values().filter(|ids| ids.len() > 1) {
+        // When every copy's parent is itself a duplicate, the parents' group covers this one
+        if ids.iter().all(|id| dirs[*id].parent.is_some_and(|p| duplicated.contains(&p))) {
+            continue;
+        }
+        let first = &dirs[ids[0]];
+        let mut directories: Vec<String> = ids.iter().map(|&id| paths::display_path(&dirs[id].path)).collect();
+        directories.sort();
+        report.groups.push(DuplicateDirectoryGroup {
+            directories,
+            total_size: first.total_size,
+            file_count: first.file_count,
+            reclaimable_bytes: first.total_size * (ids.len() as u64 - 1),
+        });
+    }
+    report.groups.sort_by(|a, b| {
+        b.reclaimable_bytes.cmp(&a.reclaimable_bytes).then_with(|| a.directories.cmp(&b.directories))
+    });
+
+    if let Some(min_similarity) = min_similarity {
+        report.similar = similar_directories(&dirs, &files, &duplicated, min_similarity);
+    }
+    report
+}
+
+// Content of every directory, recursively, as hash -> (copies, size)
+fn contents(dirs: &[Dir], files: &[File]) -> Vec<HashMap<String, (u64, u64)>> {
+    let mut contents: Vec<HashMap<String, (u64, u64)>> = vec![HashMap::new(); dirs.len()];
+    for id in (0..dirs.len()).rev() {
+        let mut content = std::mem::take(&mut contents[id]);
+        for &f in &dirs[id].files {
+            content.entry(files[f].hash.clone()).or_insert((0, files[f].size)).0 += 1;
+        }
+        for &child in &dirs[id].children {
+            for (hash, &(count, size)) in &contents[child] {
+                content.entry(hash.clone()).or_insert((0, size)).0 += count;
+            }
+        }
+        contents[id] = content;
+    }
+    contents
+}
+
+fn is_ancestor(dirs: &[Dir], ancestor: usize, mut id: usize) -> bool {
+    while let Some(parent) = dirs[id].parent {
+        if parent == ancestor {
+            return true;
+        }
+        id = parent;
+    }
+    false
+}
+
+fn similar_directories(
+    dirs: &[Dir],
+    files: &[File],
+    duplicated: &HashSet<usize>,
+    min_similarity: f64,
+) -> Vec<SimilarDirectories> {
+    let contents = contents(dirs, files);
+    // Candidate pairs share at least one file that exists in more than one place
+    let mut holders: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (id, content) in contents.iter().enumerate() {
+        if duplicated.contains(&id) {
+            continue;
+        }
+        for hash in content.keys().filter(|h| !h.starts_with("unique:") && !h.starts_with("unreadable:")) {
+            holders.entry(hash.as_str()).or_default().push(id);
+        }
+    }
+    let mut pairs: HashSet<(usize, usize)> = HashSet::new();
+    'collect: for ids in holders.values() {
+        for (i, &a) in ids.iter().enumerate() {
+            for &b in &ids[i + 1..] {
+                if is_ancestor(dirs, a, b) || is_ancestor(dirs, b, a) {
+                    continue;
+                }
+                pairs.insert((a.min(b), a.max(b)));
+                if pairs.len() >= MAX_SIMILARITY_PAIRS {
+                    break 'collect;
+                }
+            }
+        }
+    }
+
+    let mut similar: Vec<(usize, usize, SimilarDirectories)> = pairs
+        .into_iter()
+        .filter_map(|(a, b)| {
+            let larger = dirs[a].total_size.max(dirs[b].total_size);
+            if larger == 0 {
+                return None;
+            }
+            let shared_bytes: u64 = contents[a]
+                .iter()
+                .filter_map(|(hash, &(count, size))| contents[b].get(hash).map(|&(other, _)| count.min(other) * size))
+                .sum();
+            let similarity = shared_bytes as f64 * 100.0 / larger as f64;
+            (similarity >= min_similarity).then(|| {
+                (a, b, SimilarDirectories {
+                    first: paths::display_path(&dirs[a].path),
+                    second: paths::display_path(&dirs[b].path),
+                    similarity: (similarity * 10.0).round() / 10.0,
+                    shared_bytes,
+                })
+            })
+        })
+        .collect();
+    // A pair whose parents are also a reported pair is already covered by them
+    let reported: HashSet<(usize, usize)> = similar.iter().map(|(a, b, _)| (*a, *b)).collect();
+    similar.retain(|(a, b, _)| match (dirs[*a].parent, dirs[*b].parent) {
+        (Some(pa), Some(pb)) => !reported.contains(&(pa.min(pb), pa.max(pb))),
+        _ => true,
+    });
+    let mut similar: Vec<SimilarDirectories> = similar.into_iter().map(|(_, _, s)| s).collect();
+    similar.sort_by(|a, b| b.shared_bytes.cmp(&a.shared_bytes).then_with(|| a.first.cmp(&b.first)));
+    similar
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_dir;
+    use std::fs;
+
+    fn find_in(roots: &[PathBuf], min_similarity: Option<f64>) -> DuplicateDirectoryReport {
+        find(roots, &ScanFilter::default(), false, min_similarity, false, IoPriority::Normal, &mut |_| {}, None)
+    }
+
+    fn write(dir: &Path, relative: &str, content: &str) {
+        let path = dir.join(relative);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn identical_trees_are_reported_once_at_the_top() {
+        let dir = temp_dir("exact");
+        for copy in ["Projects", "Backup of Projects"] {
+            write(&dir, &format!("{}/readme.md", copy), "hello");
+            write(&dir, &format!("{}/src/main.rs", copy), "fn main() {}");
+        }
+        write(&dir, "Other/readme.md", "hello, but different");
+
+        let mut last = HashProgress::default();
+        let filter = ScanFilter::default();
+        let mut on_progress = |progress: &HashProgress| last = progress.clone();
+        let report = find(std::slice::from_ref(&dir), &filter, false, None, false, IoPriority::Low, &mut on_progress, None);
+        assert_eq!((last.files_hashed, last.files_to_hash, last.bytes_hashed), (4, 4, 34));
+        assert_eq!(report.groups.len(), 1);
+        let group = &report.groups[0];
+        assert_eq!(group.directories.len(), 2);
+        assert!(group.directories[0].ends_with("Backup of Projects"));
+        assert_eq!((group.file_count, group.total_size, group.reclaimable_bytes), (2, 17, 17));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn resumed_searches_only_read_changed_files() {
+        let dir = temp_dir("resume");
+        let app_data = dir.join("app-data");
+        let tree = dir.join("tree");
+        for copy in ["Projects", "Backup of Projects"] {
+            write(&tree, &format!("{}/readme.md", copy), "hello");
+        }
+        let options = crate::checkpoints::AnalysisOptions::DuplicateDirectories {
+            roots: vec![paths::display_path(&tree)],
+            min_similarity: None,
+            include_hidden: None,
+            ignored_names: None,
+            follow_symlinks: false,
+            hydrate: false,
+        };
+        let search = |checkpointer: &mut Checkpointer| {
+            let mut last = HashProgress::default();
+            let mut on_progress = |progress: &HashProgress| last = progress.clone();
+            let filter = ScanFilter::default();
+            let roots = std::slice::from_ref(&tree);
+            let report = find(roots, &filter, false, None, false, IoPriority::Normal, &mut on_progress, Some(checkpointer));
+            (report.groups.len(), last.files_hashed, last.files_from_checkpoint)
+        };
+        let mut first = Checkpointer::start(&app_data, options, 1);
+        assert_eq!(search(&mut first), (1, 2, 0));
+        let id = first.id().to_string();
+
+        let mut resumed = Checkpointer::resume(&app_data, crate::checkpoints::load(&app_data, &id).unwrap(), 1);
+        assert_eq!(search(&mut resumed), (1, 2, 2));
+        // Same size, new content and mtime
+        write(&tree, "Projects/readme.md", "HELLO");
+        let modified = fs::metadata(tree.join("Projects/readme.md")).unwrap().modified().unwrap();
+        let file = fs::File::options().write(true).open(tree.join("Projects/readme.md")).unwrap();
+        file.set_modified(modified + Duration::from_secs(5)).unwrap();
+        assert_eq!(search(&mut resumed), (0, 2, 1));
+        resumed.finish();
+        assert!(crate::checkpoints::list(&app_data).is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn files_changed_after_listing_are_reported_instead_of_grouped() {
+        let dir = temp_dir("changed");
+        for name in ["Photos", "Music", "Docs"] {
+            write(&dir, &format!("{}/a.txt", name), name);
+            write(&dir, &format!("{} copy/a.txt", name), name);
+        }
+        let listing = list(std::slice::from_ref(&dir), &ScanFilter::default(), false);
+        assert!(listing.scanned_at > 0);
+
+        fs::remove_file(dir.join("Photos copy/a.txt")).unwrap();
+        // Same size, new mtime
+        let file = fs::File::options().write(true).open(dir.join("Music/a.txt")).unwrap();
+        file.set_modified(std::time::SystemTime::now() + Duration::from_secs(60)).unwrap();
+        write(&dir, "Docs/a.txt", "Docs, but longer");
+
+        let report = analyze(listing, None, false, IoPriority::Normal, &mut |_| {}, None);
+        assert_eq!((report.files_changed, report.files_vanished, report.hash_errors), (2, 1, 0));
+        assert_eq!(report.files_hashed, 3);
+        assert!(report.groups.is_empty(), "{:?}", report.groups);
+        let vanished: Vec<&ChangedFile> = report.changed_files.iter().filter(|f| f.vanished).collect();
+        assert_eq!(vanished.len(), 1);
+        assert_eq!(vanished[0].path, paths::display_path(dir.join("Photos copy/a.txt")));
+
+        // Listed again, what's left of the copies match once more
+        let report = find_in(std::slice::from_ref(&dir), None);
+        assert_eq!((report.files_changed, report.files_vanished, report.groups.len()), (0, 0, 1));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn mostly_shared_directories_get_a_similarity() {
+        let dir = temp_dir("similar");
+        let big = "x".repeat(900);
+        write(&dir, "A/big.bin", &big);
+        write(&dir, "A/note.txt", &"a".repeat(100));
+        write(&dir, "B/big.bin", &big);
+        write(&dir, "B/note.txt", &"b".repeat(100));
+
+        let report = find_in(&[dir.join("A"), dir.join("B")], Some(80.0));
+        assert!(report.groups.is_empty());
+        assert_eq!(report.similar.len(), 1);
+        assert_eq!(report.similar[0].similarity, 90.0);
+        assert_eq!(report.similar[0].shared_bytes, 900);
+
+        let report = find_in(std::slice::from_ref(&dir), Some(95.0));
+        assert!(report.similar.is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn linked_directories_are_only_compared_when_followed() {
+        let dir = temp_dir("linked");
+        write(&dir, "Projects/readme.md", "hello");
+        write(&dir, "Projects/src/main.rs", "fn main() {}");
+        std::os::unix::fs::symlink(dir.join("Projects"), dir.join("Projects link")).unwrap();
+        let roots = std::slice::from_ref(&dir);
+        let search = |follow_symlinks| {
+            let filter = ScanFilter::default();
+            find(roots, &filter, follow_symlinks, None, false, IoPriority::Normal, &mut |_| {}, None)
+        };
+
+        assert!(search(false).groups.is_empty());
+        let followed = search(true);
+        assert_eq!(followed.groups.len(), 1);
+        assert_eq!(followed.groups[0].directories.len(), 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,345 @@
+// Error type returned by every Tauri command. It serializes to
+// { kind, message, path?, source?, retry_after_ms?, rule?, elapsed_ms?, is_recoverable?,
+// prompt_tokens?, context_tokens? }
+// so the frontend can branch on `kind` instead of matching message text; `message`
+// keeps the wording commands used when they returned plain strings.
+use crate::i18n;
+use serde::Serialize;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum AppErrorKind {
+    NotFound,
+    PermissionDenied,
+    Unsupported,
+    Io,
+    Network,
+    LlmBackend,
+    Cancelled,
+    InvalidInput,
+    // Another process holds the file open (e.g. a Windows sharing violation)
+    FileInUse,
+    // The file exists but its contents are corrupt or can't be decoded
+    InvalidData,
+    // The destination name is already taken
+    AlreadyExists,
+    // The content is password protected (e.g. an encrypted zip entry)
+    Encrypted,
+    // The destination is outside the allowed roots or inside a protected system path
+    PolicyViolation,
+    // The operation didn't finish within its deadline and was abandoned
+    Timeout,
+    // The file is a cloud placeholder and reading it would download it
+    CloudPlaceholder,
+    // The drive or share the path is on was unplugged or disconnected
+    VolumeUnavailable,
+    // The prompt plus the reply it asked for don't fit the model's context window
+    ContextOverflow,
+}
+
+// How long callers are told to wait before retrying a file that is in use
+pub const FILE_IN_USE_RETRY_AFTER_MS: u64 = 2_000;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AppError {
+    pub kind: AppErrorKind,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    // Underlying error text, when the message wraps one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    // Set when the operation may succeed if retried after this long
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_after_ms: Option<u32>,
+    // The policy rule that rejected the operation, for PolicyViolation
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rule: Option<&'static str>,
+    // How long a timed-out operation ran before it was abandoned
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub elapsed_ms: Option<u32>,
+    // Whether the user can fix this themselves (redownload, enter a password), when known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_recoverable: Option<bool>,
+    // For ContextOverflow, as reported by the backend. Boxed to keep AppError small,
+    // since every command returns it.
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    pub token_counts: Option<Box<TokenCounts>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TokenCounts {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prompt_tokens: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub context_tokens: Option<u64>,
+}
+
+impl AppError {
+    pub fn new(kind: AppErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+            path: None,
+            source: None,
+            retry_after_ms: None,
+            rule: None,
+            elapsed_ms: None,
+            is_recoverable: None,
+            token_counts: None,
+        }
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new(AppErrorKind::NotFound, message)
+    }
+
+    pub fn unsupported(message: impl Into<String>) -> Self {
+        Self::new(AppErrorKind::Unsupported, message)
+    }
+
+    pub fn io(message: impl Into<String>) -> Self {
+        Self::new(AppErrorKind::Io, message)
+    }
+
+    pub fn network(message: impl Into<String>) -> Self {
+        Self::new(AppErrorKind::Network, message)
+    }
+
+    pub fn llm_backend(message: impl Into<String>) -> Self {
+        Self::new(AppErrorKind::LlmBackend, message)
+    }
+
+    pub fn cancelled(message: impl Into<String>) -> Self {
+        Self::new(AppErrorKind::Cancelled, message)
+    }
+
+    pub fn invalid_input(message: impl Into<String>) -> Self {
+        Self::new(AppErrorKind::InvalidInput, message)
+    }
+
+    // Classifies an io::Error by its ErrorKind; `message` is the user-facing text.
+    // Files locked by another process get their own message and a retry hint.
+    pub fn from_io(err: &std::io::Error, message: impl Into<String>) -> Self {
+        if is_file_in_use(err) {
+            return Self::new(AppErrorKind::FileInUse, i18n::t("error.file_in_use"))
+                .with_source(err)
+                .with_retry_after(FILE_IN_USE_RETRY_AFTER_MS);
+        }
+        Self::new(io_error_kind(err.kind()), message).with_source(err)
+    }
+
+    // Classifies a reqwest error; malformed requests are the caller's fault, the rest is network
+    pub fn from_request(err: &reqwest::Error, message: impl Into<String>) -> Self {
+        let kind = if err.is_builder() {
+            AppErrorKind::InvalidInput
+        } else {
+            AppErrorKind::Network
+        };
+        Self::new(kind, message).with_source(err)
+    }
+
+    pub fn with_path(mut self, path: impl AsRef<Path>) -> Self {
+        self.path = Some(path.as_ref().to_string_lossy().into_owned());
+        self
+    }
+
+    pub fn with_source(mut self, source: impl std::fmt::Display) -> Self {
+        self.source = Some(source.to_string());
+        self
+    }
+
+    pub fn with_retry_after(mut self, retry_after_ms: u64) -> Self {
+        // u32 milliseconds is weeks, and leaves AppError room for token_counts
+        self.retry_after_ms = Some(retry_after_ms.min(u32::MAX as u64) as u32);
+        self
+    }
+
+    pub fn with_rule(mut self, rule: &'static str) -> Self {
+        self.rule = Some(rule);
+        self
+    }
+
+    pub fn with_elapsed(mut self, elapsed: std::time::Duration) -> Self {
+        self.elapsed_ms = Some(elapsed.as_millis().min(u32::MAX as u128) as u32);
+        self
+    }
+
+    pub fn with_recoverable(mut self, recoverable: bool) -> Self {
+        self.is_recoverable = Some(recoverable);
+        self
+    }
+
+    pub fn with_token_counts(mut self, prompt_tokens: Option<u64>, context_tokens: Option<u64>) -> Self {
+        self.token_counts = Some(Box::new(TokenCounts { prompt_tokens, context_tokens }));
+        self
+    }
+}
+
+// ERROR_SHARING_VIOLATION / ERROR_LOCK_VIOLATION on Windows (Outlook PSTs, running VM
+// disks, downloads still being written); EBUSY / ETXTBSY elsewhere
+pub fn is_file_in_use(err: &std::io::Error) -> bool {
+    let in_use_codes: &[i32] = if cfg!(windows) { &[32, 33] } else { &[16, 26] };
+    err.raw_os_error().is_some_and(|code| in_use_codes.contains(&code))
+}
+
+fn io_error_kind(kind: std::io::ErrorKind) -> AppErrorKind {
+    use std::io::ErrorKind;
+    match kind {
+        ErrorKind::NotFound => AppErrorKind::NotFound,
+        ErrorKind::PermissionDenied => AppErrorKind::PermissionDenied,
+        ErrorKind::Unsupported => AppErrorKind::Unsupported,
+        ErrorKind::InvalidInput => AppErrorKind::InvalidInput,
+        ErrorKind::InvalidData => AppErrorKind::InvalidData,
+        ErrorKind::AlreadyExists => AppErrorKind::AlreadyExists,
+        ErrorKind::ConnectionRefused
+        | ErrorKind::ConnectionReset
+        | ErrorKind::ConnectionAborted
+        | ErrorKind::NotConnected
+        | ErrorKind::AddrInUse
+        | ErrorKind::AddrNotAvailable
+        | ErrorKind::TimedOut => AppErrorKind::Network,
+        _ => AppErrorKind::Io,
+    }
+}
+
+impl std::fmt::Display for AppError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl From<std::io::Error> for AppError {
+    fn from(err: std::io::Error) -> Self {
+        Self::from_io(&err, err.to_string())
+    }
+}
+
+impl From<reqwest::Error> for AppError {
+    fn from(err: reqwest::Error) -> Self {
+        Self::from_request(&err, crate::describe_request_error(&err))
+    }
+}
+
+impl From<crate::llm_backend::BackendError> for AppError {
+    fn from(err: crate::llm_backend::BackendError) -> Self {
+        match err {
+            crate::llm_backend::BackendError::ContextOverflow { prompt_tokens, context_tokens, .. } => {
+                Self::new(AppErrorKind::ContextOverflow, i18n::t("error.context_overflow"))
+                    .with_source(&err)
+                    .with_token_counts(prompt_tokens, context_tokens)
+            }
+            err => Self::llm_backend(err.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    #[test]
+    fn missing_file_serializes_as_not_found_with_path() {
+        let path = std::env::temp_dir().join("file-organizer-missing-file.txt");
+        let _ = std::fs::remove_file(&path);
+        let path = path.to_string_lossy().into_owned();
+
+        let err = block_on(crate::file_content_json(path.clone(), None, None, None, true)).unwrap_err();
+        let value = serde_json::to_value(&err).unwrap();
+
+        assert_eq!(value["kind"], "NotFound");
+        assert_eq!(value["path"], json!(path));
+        assert!(value["source"].is_string());
+    }
+
+    #[test]
+    fn permission_denied_serializes_with_its_own_kind() {
+        let io_err = std::io::Error::new(std::io::ErrorKind::PermissionDenied, "Permission denied (os error 13)");
+        let err = AppError::from_io(&io_err, "Failed to create file: Permission denied (os error 13)")
+            .with_path("/locked/report.txt");
+
+        assert_eq!(
+            serde_json::to_value(&err).unwrap(),
+            json!({
+                "kind": "PermissionDenied",
+                "message": "Failed to create file: Permission denied (os error 13)",
+                "path": "/locked/report.txt",
+                "source": "Permission denied (os error 13)",
+            })
+        );
+        assert_eq!(err.to_string(), "Failed to create file: Permission denied (os error 13)");
+    }
+
+    #[test]
+    fn connection_refused_serializes_as_network() {
+        // Bind then drop a listener so the port is known to be closed
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let url = format!("http://127.0.0.1:{}/", port);
+
+        let err = block_on(crate::send_http_request(
+            crate::network::build_http_client(),
+            url,
+            "GET".to_string(),
+            Default::default(),
+            None,
+            None,
+            Some(2_000),
+            Some(0),
+            None,
+        ))
+        .unwrap_err();
+        let value = serde_json::to_value(&err).unwrap();
+
+        assert_eq!(value["kind"], "Network");
+        assert!(value["message"].as_str().unwrap().starts_with("Connection failed"));
+        assert!(value.get("path").is_none());
+    }
+
+    #[test]
+    fn locked_files_serialize_as_file_in_use_with_retry_hint() {
+        let code = if cfg!(windows) { 32 } else { 16 };
+        let io_err = std::io::Error::from_raw_os_error(code);
+        let err = AppError::from_io(&io_err, format!("Failed to move file: {}", io_err)).with_path("C:/mail/archive.pst");
+        let value = serde_json::to_value(&err).unwrap();
+
+        assert_eq!(value["kind"], "FileInUse");
+        assert_eq!(value["message"], "File is in use by another process");
+        assert_eq!(value["retry_after_ms"], FILE_IN_USE_RETRY_AFTER_MS);
+        assert_eq!(value["path"], "C:/mail/archive.pst");
+    }
+
+    #[test]
+    fn context_overflow_carries_the_token_counts() {
+        let err: AppError = crate::llm_backend::BackendError::ContextOverflow {
+            message: "HTTP 400: Requested tokens (5210) exceed context window of 4096".to_string(),
+            prompt_tokens: Some(5210),
+            context_tokens: Some(4096),
+        }
+        .into();
+        let value = serde_json::to_value(&err).unwrap();
+
+        assert_eq!(value["kind"], "ContextOverflow");
+        assert_eq!((value["prompt_tokens"].as_u64(), value["context_tokens"].as_u64()), (Some(5210), Some(4096)));
+        assert!(value["source"].as_str().unwrap().contains("exceed context window"));
+    }
+
+    #[test]
+    fn plain_messages_omit_optional_fields() {
+        let err = AppError::cancelled("User cancelled folder selection");
+        assert_eq!(
+            serde_json::to_value(&err).unwrap(),
+            json!({ "kind": "Cancelled", "message": "User cancelled folder selection" })
+        );
+    }
+}
@@ -0,0 +1,269 @@
+// User-configured command line converters for formats the built-in extractors don't
+// read (.dwg, lab instrument files). A handler maps extensions to a command template
+// such as "dwg2txt {input}" and its stdout becomes the file's text. Templates only come
+// from settings. They're split into arguments once, the path is substituted into
+// those arguments, and the program is started directly, never through a shell, so a
+// file name can't add arguments or commands.
+use crate::error::{AppError, AppErrorKind};
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+pub const INPUT_PLACEHOLDER: &str = "{input}";
+pub const DEFAULT_TIMEOUT_MS: u64 = 20_000;
+pub const DEFAULT_MAX_OUTPUT_BYTES: usize = 4 * 1024 * 1024;
+// Kept for error messages; the rest of stderr is read and dropped
+const MAX_STDERR_BYTES: usize = 4 * 1024;
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExternalExtractor {
+    // Without the dot, matched case-insensitively
+    pub extensions: Vec<String>,
+    // Program and arguments; {input} is replaced by the file's path, which is appended
+    // as the last argument when the template doesn't mention it
+    pub command: String,
+    #[serde(default)]
+    pub timeout_ms: Option<u64>,
+    #[serde(default)]
+    pub max_output_bytes: Option<usize>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExternalExtractorSettings {
+    pub enabled: bool,
+    pub handlers: Vec<ExternalExtractor>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExtractorOutput {
+    pub text: String,
+    // Output beyond max_output_bytes was cut off and the converter stopped
+    pub truncated: bool,
+    pub exit_code: Option<i32>,
+    pub stderr: String,
+    pub elapsed_ms: u64,
+}
+
+impl ExternalExtractor {
+    pub fn handles(&self, path: &Path) -> bool {
+        let Some(ext) = path.extension().map(|ext| ext.to_string_lossy()) else { return false };
+        self.extensions.iter().any(|handled| handled.trim_start_matches('.').eq_ignore_ascii_case(&ext))
+    }
+}
+
+// The enabled handler for `path`, if any; the first match wins
+pub fn handler_for(path: &Path) -> Option<ExternalExtractor> {
+    crate::settings::with(|s| {
+        let extractors = &s.external_extractors;
+        extractors.enabled.then(|| extractors.handlers.iter().find(|handler| handler.handles(path)).cloned())?
+    })
+}
+
+// Splits on whitespace outside quotes; single quotes are literal, inside double quotes
+// a backslash escapes the next character
+fn split_template(template: &str) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let (mut quote, mut started) = (None, false);
+    let mut chars = template.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some('"'), '\\') => current.extend(chars.next()),
+            (Some(open), c) if c == open => quote = None,
+            (Some(_), c) => current.push(c),
+            (None, '"' | '\'') => {
+                quote = Some(c);
+                started = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if started || !current.is_empty() {
+                    args.push(std::mem::take(&mut current));
+                }
+                started = false;
+            }
+            (None, c) => current.push(c),
+        }
+    }
+    if quote.is_some() {
+        return Err(format!("Unclosed quote in \"{}\"", template));
+    }
+    if started || !current.is_empty() {
+        args.push(current);
+    }
+    Ok(args)
+}
+
+pub fn command_line(template: &str, input: &str) -> Result<Vec<String>, String> {
+    let mut args = split_template(template)?;
+    if args.is_empty() {
+        return Err("The command is empty".to_string());
+    }
+    if args[0].contains(INPUT_PLACEHOLDER) {
+        return Err("The program name can't be the input file".to_string());
+    }
+    let mentioned = args.iter().any(|arg| arg.contains(INPUT_PLACEHOLDER));
+    for arg in args.iter_mut() {
+        *arg = arg.replace(INPUT_PLACEHOLDER, input);
+    }
+    if !mentioned {
+        args.push(input.to_string());
+    }
+    Ok(args)
+}
+
+// Reads everything, keeping the first `limit` bytes; reports whether more came
+fn read_capped(mut source: impl Read, limit: usize) -> (Vec<u8>, bool) {
+    let mut kept = Vec::new();
+    let mut chunk = [0u8; 8192];
+    loop {
+        match source.read(&mut chunk) {
+            Ok(0) | Err(_) => return (kept, false),
+            Ok(read) if kept.len() + read > limit => {
+                kept.extend_from_slice(&chunk[..limit - kept.len()]);
+                return (kept, true);
+            }
+            Ok(read) => kept.extend_from_slice(&chunk[..read]),
+        }
+    }
+}
+
+fn failed(handler: &ExternalExtractor, message: String) -> AppError {
+    AppError::io(format!("Extractor \"{}\" failed: {}", handler.command, message))
+}
+
+// Runs the converter on `input`. A non-zero exit or empty output is an error; output
+// past the cap is cut off and the converter stopped.
+pub fn run(handler: &ExternalExtractor, input: &Path) -> Result<ExtractorOutput, AppError> {
+    let started = Instant::now();
+    let args = command_line(&handler.command, &input.to_string_lossy()).map_err(AppError::invalid_input)?;
+    let timeout = Duration::from_millis(handler.timeout_ms.unwrap_or(DEFAULT_TIMEOUT_MS));
+    let cap = handler.max_output_bytes.unwrap_or(DEFAULT_MAX_OUTPUT_BYTES);
+    let mut child = Command::new(&args[0])
+        .args(&args[1..])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| failed(handler, format!("couldn't start {}: {}", args[0], e)))?;
+    let stdout = child.stdout.take().expect("stdout is piped");
+    let stderr = child.stderr.take().expect("stderr is piped");
+    let overflowed = Arc::new(AtomicBool::new(false));
+    let stdout_reader = {
+        let overflowed = overflowed.clone();
+        std::thread::spawn(move || {
+            let (output, truncated) = read_capped(stdout, cap);
+            overflowed.store(truncated, Ordering::Relaxed);
+            output
+        })
+    };
+    // Drained to the end after the first MAX_STDERR_BYTES, so a chatty converter doesn't
+    // block on a full stderr pipe
+    let stderr_reader = std::thread::spawn(move || {
+        let mut stderr = stderr;
+        let (kept, truncated) = read_capped(&mut stderr, MAX_STDERR_BYTES);
+        if truncated {
+            let _ = std::io::copy(&mut stderr, &mut std::io::sink());
+        }
+        kept
+    });
+
+    let status = loop {
+        if let Some(status) = child.try_wait().map_err(|e| failed(handler, e.to_string()))? {
+            break Some(status);
+        }
+        // Past the cap nothing drains stdout, so the converter would block on a full pipe
+        if overflowed.load(Ordering::Relaxed) {
+            let _ = child.kill();
+            let _ = child.wait();
+            break None;
+        }
+        if started.elapsed() >= timeout {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(AppError::new(
+                AppErrorKind::Timeout,
+                format!("Extractor \"{}\" took longer than {}ms and was stopped", handler.command, timeout.as_millis()),
+            )
+            .with_elapsed(started.elapsed()));
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    };
+    let output = stdout_reader.join().unwrap_or_default();
+    let truncated = overflowed.load(Ordering::Relaxed);
+    let stderr = String::from_utf8_lossy(&stderr_reader.join().unwrap_or_default()).trim().to_string();
+    let exit_code = status.and_then(|status| status.code());
+    if !truncated && !status.is_some_and(|status| status.success()) {
+        let code = exit_code.map_or("no exit code".to_string(), |code| format!("exit code {}", code));
+        let detail = if stderr.is_empty() { String::new() } else { format!(": {}", stderr) };
+        return Err(failed(handler, format!("{}{}", code, detail)));
+    }
+    let text = String::from_utf8_lossy(&output).into_owned();
+    if text.trim().is_empty() {
+        return Err(failed(handler, "no output".to_string()));
+    }
+    Ok(ExtractorOutput { text, truncated, exit_code, stderr, elapsed_ms: started.elapsed().as_millis() as u64 })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn handler(command: &str) -> ExternalExtractor {
+        ExternalExtractor {
+            extensions: vec!["DWG".into(), ".lab".into()],
+            command: command.into(),
+            timeout_ms: None,
+            max_output_bytes: None,
+        }
+    }
+
+    #[test]
+    fn the_path_is_one_argument_whatever_it_contains() {
+        let path = "/tmp/a b; rm -rf ~ $(x).dwg";
+        assert_eq!(command_line("dwg2txt {input}", path).unwrap(), ["dwg2txt", path]);
+        assert_eq!(
+            command_line(r#""/opt/Lab Tools/conv" --in={input} -o -"#, path).unwrap(),
+            ["/opt/Lab Tools/conv", &format!("--in={}", path), "-o", "-"]
+        );
+        assert_eq!(command_line("conv 'x y' \"a\\\"b\"", "/f").unwrap(), ["conv", "x y", "a\"b", "/f"]);
+        assert!(command_line("  ", path).is_err());
+        assert!(command_line("{input} --text", path).is_err());
+        assert!(command_line("conv \"unclosed", path).is_err());
+
+        assert!(handler("x").handles(Path::new("/plans/floor.dwg")));
+        assert!(handler("x").handles(Path::new("/data/run.LAB")));
+        assert!(!handler("x").handles(Path::new("/data/run.txt")));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn output_is_captured_capped_and_timed_out() {
+        let dir = crate::test_support::temp_dir("external-extractors-run");
+        let input = dir.join("it's a plan.dwg");
+        std::fs::write(&input, "layer 1\n".repeat(10_000)).unwrap();
+
+        let output = run(&handler("cat {input}"), &input).unwrap();
+        assert_eq!((output.text.len(), output.truncated, output.exit_code), (80_000, false, Some(0)));
+
+        let capped = run(&ExternalExtractor { max_output_bytes: Some(100), ..handler("cat") }, &input).unwrap();
+        assert_eq!((capped.text.len(), capped.truncated), (100, true));
+
+        let slow = ExternalExtractor { timeout_ms: Some(100), ..handler("tail -f {input}") };
+        assert_eq!(run(&slow, &input).unwrap_err().kind, AppErrorKind::Timeout);
+        let chatty = ExternalExtractor {
+            timeout_ms: Some(5_000),
+            ..handler("sh -c 'head -c 200000 /dev/zero | tr \"\\0\" x >&2; echo converted'")
+        };
+        let output = run(&chatty, &input).unwrap();
+        assert_eq!((output.text.as_str(), output.stderr.len()), ("converted\n", MAX_STDERR_BYTES));
+        assert!(run(&handler("false"), &input).is_err());
+        assert!(run(&handler("file-organizer-no-such-converter"), &input).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,192 @@
+// Deadline and concurrency limit for content extraction. Extractors run on the blocking
+// pool and the caller stops waiting once the timeout passes. A blocking task can't be
+// cancelled, so a hung extractor is abandoned and keeps its thread until it returns or
+// the app exits.
+use crate::error::{AppError, AppErrorKind};
+use crate::settings;
+use serde::Serialize;
+use std::panic;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, Once};
+use std::time::{Duration, Instant};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+pub const DEFAULT_TIMEOUT_MS: u64 = 30_000;
+// Waiting requests report their place in the queue this often
+pub const QUEUE_HINT_INTERVAL: Duration = Duration::from_secs(1);
+
+// Threads with this name have their panics caught and reported as extraction errors,
+// so the default hook's message is suppressed for them
+pub const PDF_WORKER_THREAD: &str = "pdf-extract-worker";
+
+static QUIET_PANIC_HOOK: Once = Once::new();
+
+pub fn timeout() -> Duration {
+    Duration::from_millis(settings::with(|settings| settings.extraction.timeout_ms))
+}
+
+// Installs, once, a panic hook that stays quiet on PDF worker threads and defers to
+// the previous hook everywhere else. Swapping the hook around each extraction raced
+// with panics on unrelated threads.
+pub fn install_quiet_panic_hook() {
+    QUIET_PANIC_HOOK.call_once(|| {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if std::thread::current().name() != Some(PDF_WORKER_THREAD) {
+                previous(info);
+            }
+        }));
+    });
+}
+
+// Runs `extract` on the blocking pool, giving up after `timeout`
+pub async fn run_with_timeout<T, F>(timeout: Duration, extract: F) -> Result<T, AppError>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T, AppError> + Send + 'static,
+{
+    let started = Instant::now();
+    match tokio::time::timeout(timeout, tokio::task::spawn_blocking(extract)).await {
+        Ok(joined) => joined.map_err(|e| AppError::io(format!("Extraction task failed: {}", e)))?,
+        Err(_) => {
+            let elapsed = started.elapsed();
+            Err(AppError::new(
+                AppErrorKind::Timeout,
+                format!("Extraction took longer than {:.1}s and was abandoned", elapsed.as_secs_f64()),
+            )
+            .with_elapsed(elapsed))
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct QueueStatus {
+    // 1 when next in line
+    pub position: u64,
+    pub waiting_ms: u64,
+}
+
+// Caps how many extractions run at once so a folder-wide classification doesn't parse
+// dozens of PDFs and workbooks in parallel. Waiters are served in arrival order.
+pub struct ExtractionLimiter {
+    semaphore: Arc<Semaphore>,
+    permits: Mutex<usize>,
+    next_ticket: AtomicU64,
+    granted: AtomicU64,
+}
+
+pub fn default_concurrency() -> usize {
+    std::thread::available_parallelism().map(|n| n.get()).unwrap_or(4)
+}
+
+impl ExtractionLimiter {
+    pub fn new(permits: usize) -> Self {
+        let permits = permits.max(1);
+        Self {
+            semaphore: Arc::new(Semaphore::new(permits)),
+            permits: Mutex::new(permits),
+            next_ticket: AtomicU64::new(0),
+            granted: AtomicU64::new(0),
+        }
+    }
+
+    pub fn permits(&self) -> usize {
+        *self.permits.lock().unwrap()
+    }
+
+    // Extractions already running keep their permits; lowering the limit takes effect as
+    // they finish
+    pub fn set_permits(&self, permits: usize) {
+        let permits = permits.max(1);
+        let mut current = self.permits.lock().unwrap();
+        if permits > *current {
+            self.semaphore.add_permits(permits - *current);
+        } else if permits < *current {
+            let surplus = (*current - permits) as u32;
+            let semaphore = self.semaphore.clone();
+            tauri::async_runtime::spawn(async move {
+                if let Ok(taken) = semaphore.acquire_many_owned(surplus).await {
+                    taken.forget();
+                }
+            });
+        }
+        *current = permits;
+    }
+
+    // Waits for a permit, calling `on_wait` every QUEUE_HINT_INTERVAL while queued
+    pub async fn acquire(&self, mut on_wait: impl FnMut(QueueStatus)) -> OwnedSemaphorePermit {
+        let ticket = self.next_ticket.fetch_add(1, Ordering::SeqCst);
+        let started = Instant::now();
+        let acquire = self.semaphore.clone().acquire_owned();
+        tokio::pin!(acquire);
+        loop {
+            match tokio::time::timeout(QUEUE_HINT_INTERVAL, &mut acquire).await {
+                Ok(permit) => {
+                    self.granted.fetch_add(1, Ordering::SeqCst);
+                    return permit.expect("the extraction semaphore is never closed");
+                }
+                Err(_) => on_wait(QueueStatus {
+                    position: ticket.saturating_sub(self.granted.load(Ordering::SeqCst)) + 1,
+                    waiting_ms: started.elapsed().as_millis() as u64,
+                }),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Shut down without waiting for abandoned extractors to finish
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        let runtime = tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap();
+        let output = runtime.block_on(future);
+        runtime.shutdown_background();
+        output
+    }
+
+    #[test]
+    fn slow_extractors_time_out() {
+        let result: Result<(), AppError> = block_on(run_with_timeout(Duration::from_millis(50), || {
+            std::thread::sleep(Duration::from_secs(2));
+            Ok(())
+        }));
+        let err = result.unwrap_err();
+        assert_eq!(err.kind, AppErrorKind::Timeout);
+        assert!(err.elapsed_ms.is_some_and(|ms| (50..2_000).contains(&ms)));
+    }
+
+    #[test]
+    fn fast_extractors_return_their_result() {
+        let result = block_on(run_with_timeout(Duration::from_secs(5), || Ok("text".to_string())));
+        assert_eq!(result.unwrap(), "text");
+        let result: Result<(), AppError> =
+            block_on(run_with_timeout(Duration::from_secs(5), || Err(AppError::invalid_input("bad"))));
+        assert_eq!(result.unwrap_err().kind, AppErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn waiting_requests_are_told_their_place_in_the_queue() {
+        block_on(async {
+            let limiter = Arc::new(ExtractionLimiter::new(1));
+            let held = limiter.acquire(|_| {}).await;
+            let hints = Arc::new(Mutex::new(Vec::new()));
+
+            let waiter = {
+                let (limiter, hints) = (limiter.clone(), hints.clone());
+                tokio::spawn(async move {
+                    let _permit = limiter.acquire(|status| hints.lock().unwrap().push(status)).await;
+                })
+            };
+            tokio::time::sleep(QUEUE_HINT_INTERVAL + Duration::from_millis(200)).await;
+            drop(held);
+            waiter.await.unwrap();
+
+            let hints = hints.lock().unwrap();
+            assert_eq!(hints.len(), 1);
+            assert_eq!(hints[0].position, 1);
+            assert!(hints[0].waiting_ms >= 1_000);
+        });
+    }
+}
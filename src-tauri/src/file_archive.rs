@@ -0,0 +1,453 @@
+// Archiving files the user chose (old projects, unused downloads) into one .tar.gz
+// bundle, and restoring them from it. Files are stored under their path relative to a
+// base root. The archive is written under a temporary name and read back in full, every
+// member checked against the size recorded when the files were scanned, before it takes
+// its real name; originals are only removed after that. A file that changed between the
+// scan and being archived or removed stops the whole operation.
+use crate::archive;
+use crate::destination_policy;
+use crate::directory_listing::PROGRESS_INTERVAL;
+use crate::error::{AppError, AppErrorKind};
+use crate::paths;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use tar::{Archive, Builder, EntryType, Header};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ArchivePhase {
+    Archiving,
+    Verifying,
+    Removing,
+    Restoring,
+}
+
+// While restoring, files_total and bytes_total stay 0 until the end: the archive has no
+// index to read them from up front
+#[derive(Debug, Clone, Serialize)]
+pub struct ArchiveProgress {
+    pub phase: ArchivePhase,
+    pub files_done: usize,
+    pub files_total: usize,
+    pub bytes_done: u64,
+    pub bytes_total: u64,
+}
+
+// A file as it was when scanned, and the name it's stored under
+#[derive(Debug, Clone)]
+pub struct PlannedFile {
+    pub source: PathBuf,
+    // Relative to the base root, with '/' separators
+    pub member: String,
+    pub size: u64,
+    modified: Option<SystemTime>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ArchivedFile {
+    pub path: String,
+    pub member: String,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ArchiveReport {
+    pub archive_path: String,
+    pub files: Vec<ArchivedFile>,
+    pub total_bytes: u64,
+    // Size of the .tar.gz itself
+    pub archive_bytes: u64,
+    pub originals_removed: usize,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RestoreReport {
+    pub restored: Vec<String>,
+    // Left alone because something already exists at the path
+    pub conflicts: Vec<String>,
+    pub total_bytes: u64,
+}
+
+fn progress_reporter<'a>(on_progress: &'a mut dyn FnMut(&ArchiveProgress)) -> impl FnMut(&ArchiveProgress, bool) + 'a {
+    let mut reported: Option<Instant> = None;
+    move |progress, force| {
+        if force || reported.is_none_or(|at| at.elapsed() >= PROGRESS_INTERVAL) {
+            reported = Some(Instant::now());
+            on_progress(progress);
+        }
+    }
+}
+
+fn changed_error(file: &PlannedFile) -> AppError {
+    AppError::new(
+        AppErrorKind::InvalidData,
+        format!("{} changed after it was scanned", paths::display_path(&file.source)),
+    )
+    .with_path(&file.source)
+}
+
+fn unchanged(file: &PlannedFile, metadata: &fs::Metadata) -> bool {
+    metadata.is_file() && metadata.len() == file.size && metadata.modified().ok() == file.modified
+}
+
+fn is_archive_name(path: &Path) -> bool {
+    let name = path.to_string_lossy().to_lowercase();
+    name.ends_with(".tar.gz") || name.ends_with(".tgz")
+}
+
+// Checks the files are regular files under base_root and records their size and
+// modified time. The same file listed twice is archived once.
+pub fn plan(files: &[String], base_root: &str) -> Result<Vec<PlannedFile>, AppError> {
+    let base =
+        fs::canonicalize(paths::normalize_path(base_root)).map_err(|e| AppError::from(e).with_path(base_root))?;
+    let mut seen = HashSet::new();
+    let mut planned = Vec::new();
+    for file in files {
+        let source = fs::canonicalize(paths::normalize_path(file)).map_err(|e| AppError::from(e).with_path(file))?;
+        let metadata = fs::metadata(&source).map_err(|e| AppError::from(e).with_path(file))?;
+        if !metadata.is_file() {
+            return Err(AppError::invalid_input(format!("{} is not a file", file)).with_path(file));
+        }
+        let relative = source.strip_prefix(&base).map_err(|_| {
+            AppError::invalid_input(format!("{} is not under {}", file, paths::display_path(&base))).with_path(file)
+        })?;
+        if !seen.insert(source.clone()) {
+            continue;
+        }
+        let member = relative.components().map(|c| c.as_os_str().to_string_lossy()).collect::<Vec<_>>().join("/");
+        planned.push(PlannedFile { member, size: metadata.len(), modified: metadata.modified().ok(), source });
+    }
+    if planned.is_empty() {
+        return Err(AppError::invalid_input("No files to archive"));
+    }
+    Ok(planned)
+}
+
+// Counts what passes through, to catch a file that shrank while it was copied
+struct Counted<R> {
+    inner: R,
+    count: u64,
+}
+
+impl<R: Read> Read for Counted<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.count += read as u64;
+        Ok(read)
+    }
+}
+
+fn write_archive(
+    files: &[PlannedFile],
+    temp_path: &Path,
+    report: &mut dyn FnMut(&ArchiveProgress, bool),
+) -> Result<(), AppError> {
+    let bytes_total = files.iter().map(|f| f.size).sum();
+    let output = fs::File::create(temp_path).map_err(|e| AppError::from(e).with_path(temp_path))?;
+    let mut builder = Builder::new(GzEncoder::new(output, Compression::default()));
+    let mut progress = ArchiveProgress {
+        phase: ArchivePhase::Archiving,
+        files_done: 0,
+        files_total: files.len(),
+        bytes_done: 0,
+        bytes_total,
+    };
+    for file in files {
+        let source = fs::File::open(&file.source).map_err(|e| AppError::from(e).with_path(&file.source))?;
+        let metadata = source.metadata().map_err(|e| AppError::from(e).with_path(&file.source))?;
+        if !unchanged(file, &metadata) {
+            return Err(changed_error(file));
+        }
+        let mut header = Header::new_gnu();
+        header.set_metadata(&metadata);
+        header.set_size(file.size);
+        let mut data = Counted { inner: source.take(file.size), count: 0 };
+        builder
+            .append_data(&mut header, &file.member, &mut data)
+            .map_err(|e| AppError::from(e).with_path(&file.source))?;
+        let still = fs::metadata(&file.source).map_err(|e| AppError::from(e).with_path(&file.source))?;
+        if data.count != file.size || !unchanged(file, &still) {
+            return Err(changed_error(file));
+        }
+        progress.files_done += 1;
+        progress.bytes_done += file.size;
+        report(&progress, false);
+    }
+    report(&progress, true);
+    let output = builder.into_inner().and_then(|gz| gz.finish()).map_err(|e| AppError::from(e).with_path(temp_path))?;
+    output.sync_all().map_err(|e| AppError::from(e).with_path(temp_path))
+}
+
+// Reads every member back, checking it's there with the size that was written
+fn verify_archive(
+    files: &[PlannedFile],
+    archive_path: &Path,
+    report: &mut dyn FnMut(&ArchiveProgress, bool),
+) -> Result<(), AppError> {
+    let invalid = |detail: String| AppError::new(AppErrorKind::InvalidData, detail).with_path(archive_path);
+    let mut expected: HashMap<&str, u64> = files.iter().map(|f| (f.member.as_str(), f.size)).collect();
+    let bytes_total = files.iter().map(|f| f.size).sum();
+    let mut progress = ArchiveProgress {
+        phase: ArchivePhase::Verifying,
+        files_done: 0,
+        files_total: files.len(),
+        bytes_done: 0,
+        bytes_total,
+    };
+    let input = fs::File::open(archive_path).map_err(|e| AppError::from(e).with_path(archive_path))?;
+    let mut archive = Archive::new(GzDecoder::new(input));
+    let entries = archive.entries().map_err(|e| invalid(format!("Failed to read the archive back: {}", e)))?;
+    for entry in entries {
+        let mut entry = entry.map_err(|e| invalid(format!("Failed to read the archive back: {}", e)))?;
+        let member = entry.path().map_err(|e| invalid(e.to_string()))?.to_string_lossy().into_owned();
+        let Some(size) = expected.remove(member.as_str()) else {
+            return Err(invalid(format!("The archive holds {}, which wasn't written", member)));
+        };
+        let read = io::copy(&mut entry, &mut io::sink()).map_err(|e| invalid(format!("{}: {}", member, e)))?;
+        if entry.header().size().ok() != Some(size) || read != size {
+            return Err(invalid(format!("{} is {} bytes in the archive, expected {}", member, read, size)));
+        }
+        progress.files_done += 1;
+        progress.bytes_done += size;
+        report(&progress, false);
+    }
+    report(&progress, true);
+    match expected.keys().next() {
+        Some(missing) => Err(invalid(format!("{} is missing from the archive", missing))),
+        None => Ok(()),
+    }
+}
+
+// Writes `files` to a new archive at archive_path and verifies it. Nothing is left at
+// archive_path, or under the temporary name, when this fails.
+pub fn create(
+    files: &[PlannedFile],
+    archive_path: &Path,
+    on_progress: &mut dyn FnMut(&ArchiveProgress),
+) -> Result<ArchiveReport, AppError> {
+    if !is_archive_name(archive_path) {
+        return Err(AppError::invalid_input("The archive name must end in .tar.gz or .tgz").with_path(archive_path));
+    }
+    if fs::symlink_metadata(archive_path).is_ok() {
+        return Err(AppError::new(AppErrorKind::AlreadyExists, "The archive already exists").with_path(archive_path));
+    }
+    destination_policy::check(archive_path)?;
+    let file_name = archive_path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    let temp_path = archive_path.with_file_name(format!(".file-organizer-{}.partial", file_name));
+    let mut report = progress_reporter(on_progress);
+    let written = write_archive(files, &temp_path, &mut report)
+        .and_then(|()| verify_archive(files, &temp_path, &mut report))
+        .and_then(|()| fs::rename(&temp_path, archive_path).map_err(|e| AppError::from(e).with_path(archive_path)));
+    if let Err(e) = written {
+        let _ = fs::remove_file(&temp_path);
+        return Err(e);
+    }
+    Ok(ArchiveReport {
+        archive_path: paths::display_path(archive_path),
+        files: files
+            .iter()
+            .map(|f| ArchivedFile { path: paths::display_path(&f.source), member: f.member.clone(), size: f.size })
+            .collect(),
+        total_bytes: files.iter().map(|f| f.size).sum(),
+        archive_bytes: fs::metadata(archive_path).map_or(0, |m| m.len()),
+        originals_removed: 0,
+    })
+}
+
+// Deletes the archived originals. Every one is checked first, so a file changed since
+// the scan stops this before anything is deleted.
+pub fn remove_originals(
+    files: &[PlannedFile],
+    on_progress: &mut dyn FnMut(&ArchiveProgress),
+) -> Result<usize, AppError> {
+    for file in files {
+        match fs::metadata(&file.source) {
+            Ok(metadata) if unchanged(file, &metadata) => {}
+            _ => return Err(changed_error(file)),
+        }
+    }
+    let mut report = progress_reporter(on_progress);
+    let bytes_total = files.iter().map(|f| f.size).sum();
+    let mut progress = ArchiveProgress {
+        phase: ArchivePhase::Removing,
+        files_done: 0,
+        files_total: files.len(),
+        bytes_done: 0,
+        bytes_total,
+    };
+    for file in files {
+        fs::remove_file(&file.source).map_err(|e| AppError::from(e).with_path(&file.source))?;
+        progress.files_done += 1;
+        progress.bytes_done += file.size;
+        report(&progress, false);
+    }
+    report(&progress, true);
+    Ok(progress.files_done)
+}
+
+// Extracts an archive made by create() under restore_root, putting each file back at its
+// relative path with its modified time. Existing files are never overwritten; they're
+// reported as conflicts and the archived copy stays in the archive.
+pub fn restore(
+    archive_path: &Path,
+    restore_root: &Path,
+    on_progress: &mut dyn FnMut(&ArchiveProgress),
+) -> Result<RestoreReport, AppError> {
+    let invalid = |detail: String| AppError::new(AppErrorKind::InvalidData, detail).with_path(archive_path);
+    destination_policy::check(restore_root)?;
+    let input = fs::File::open(archive_path).map_err(|e| AppError::from(e).with_path(archive_path))?;
+    let mut archive = Archive::new(GzDecoder::new(input));
+    let mut result = RestoreReport::default();
+    let mut report = progress_reporter(on_progress);
+    let mut progress = ArchiveProgress {
+        phase: ArchivePhase::Restoring,
+        files_done: 0,
+        files_total: 0,
+        bytes_done: 0,
+        bytes_total: 0,
+    };
+    let entries = archive.entries().map_err(|e| invalid(format!("Failed to read the archive: {}", e)))?;
+    for entry in entries {
+        let mut entry = entry.map_err(|e| invalid(format!("Failed to read the archive: {}", e)))?;
+        let member = entry.path().map_err(|e| invalid(e.to_string()))?.to_string_lossy().into_owned();
+        if entry.header().entry_type() != EntryType::Regular {
+            return Err(invalid(format!("{} is not a regular file; this isn't an archive made here", member)));
+        }
+        let relative = archive::normalize_relative(Path::new(&member))
+            .filter(|p| !p.as_os_str().is_empty())
+            .ok_or_else(|| invalid(format!("Archive entry escapes the destination directory: {}", member)))?;
+        let target = restore_root.join(relative);
+        if fs::symlink_metadata(&target).is_ok() {
+            result.conflicts.push(paths::display_path(&target));
+            continue;
+        }
+        // A symlink under restore_root could still lead somewhere the policy denies
+        destination_policy::check(&target)?;
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent).map_err(|e| AppError::from(e).with_path(parent))?;
+        }
+        let mut output = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&target)
+            .map_err(|e| AppError::from(e).with_path(&target))?;
+        let size = io::copy(&mut entry, &mut output).map_err(|e| AppError::from(e).with_path(&target))?;
+        if let Ok(mtime) = entry.header().mtime() {
+            let _ = output.set_modified(UNIX_EPOCH + std::time::Duration::from_secs(mtime));
+        }
+        result.restored.push(paths::display_path(&target));
+        result.total_bytes += size;
+        progress.files_done += 1;
+        progress.bytes_done += size;
+        report(&progress, false);
+    }
+    progress.files_total = progress.files_done;
+    progress.bytes_total = progress.bytes_done;
+    report(&progress, true);
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_dir;
+
+    fn no_progress(_: &ArchiveProgress) {}
+
+    fn shown(path: &Path) -> String {
+        paths::display_path(path)
+    }
+
+    #[test]
+    fn files_round_trip_through_a_verified_archive() {
+        let dir = temp_dir("round-trip");
+        let base = dir.join("projects");
+        fs::create_dir_all(base.join("old/src")).unwrap();
+        fs::write(base.join("old/src/main.rs"), "fn main() {}").unwrap();
+        fs::write(base.join("old/notes.txt"), "x".repeat(5000)).unwrap();
+        let listed = [shown(&base.join("old/src/main.rs")), shown(&base.join("old/notes.txt"))];
+        let mut files = listed.to_vec();
+        files.push(listed[0].clone());
+
+        let planned = plan(&files, &shown(&base)).unwrap();
+        let members: Vec<&str> = planned.iter().map(|f| f.member.as_str()).collect();
+        assert_eq!(members, ["old/src/main.rs", "old/notes.txt"]);
+        let archive_path = dir.join("unused-2023.tar.gz");
+        let report = create(&planned, &archive_path, &mut no_progress).unwrap();
+        assert_eq!((report.files.len(), report.total_bytes), (2, 5012));
+        assert!(report.archive_bytes > 0 && report.archive_bytes < 5012);
+        assert!(!dir.join(".file-organizer-unused-2023.tar.gz.partial").exists());
+        assert_eq!(create(&planned, &archive_path, &mut no_progress).unwrap_err().kind, AppErrorKind::AlreadyExists);
+
+        assert_eq!(remove_originals(&planned, &mut no_progress).unwrap(), 2);
+        assert!(!base.join("old/notes.txt").exists());
+        fs::create_dir_all(base.join("old")).unwrap();
+        fs::write(base.join("old/notes.txt"), "written since").unwrap();
+        let restored = restore(&archive_path, &base, &mut no_progress).unwrap();
+        assert_eq!(restored.restored, [shown(&base.join("old/src/main.rs"))]);
+        assert_eq!(restored.conflicts, [shown(&base.join("old/notes.txt"))]);
+        assert_eq!(fs::read_to_string(base.join("old/src/main.rs")).unwrap(), "fn main() {}");
+        assert_eq!(fs::read_to_string(base.join("old/notes.txt")).unwrap(), "written since");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn files_outside_the_base_or_changed_since_the_scan_stop_everything() {
+        let dir = temp_dir("changed");
+        let base = dir.join("base");
+        fs::create_dir_all(&base).unwrap();
+        fs::write(base.join("a.txt"), "one").unwrap();
+        fs::write(base.join("b.txt"), "two").unwrap();
+        fs::write(dir.join("outside.txt"), "out").unwrap();
+
+        let outside = plan(&[shown(&dir.join("outside.txt"))], &shown(&base)).unwrap_err();
+        assert_eq!(outside.kind, AppErrorKind::InvalidInput);
+        let bad_name =
+            create(&plan(&[shown(&base.join("a.txt"))], &shown(&base)).unwrap(), &dir.join("x.zip"), &mut no_progress);
+        assert_eq!(bad_name.unwrap_err().kind, AppErrorKind::InvalidInput);
+
+        let planned = plan(&[shown(&base.join("a.txt")), shown(&base.join("b.txt"))], &shown(&base)).unwrap();
+        fs::write(base.join("b.txt"), "two, edited").unwrap();
+        let archive_path = dir.join("bundle.tgz");
+        assert_eq!(create(&planned, &archive_path, &mut no_progress).unwrap_err().kind, AppErrorKind::InvalidData);
+        assert!(!archive_path.exists() && !dir.join(".file-organizer-bundle.tgz.partial").exists());
+
+        // Archived fine, then edited before removal: nothing is deleted
+        let planned = plan(&[shown(&base.join("a.txt")), shown(&base.join("b.txt"))], &shown(&base)).unwrap();
+        create(&planned, &archive_path, &mut no_progress).unwrap();
+        fs::write(base.join("b.txt"), "edited again, longer").unwrap();
+        assert_eq!(remove_originals(&planned, &mut no_progress).unwrap_err().kind, AppErrorKind::InvalidData);
+        assert!(base.join("a.txt").exists() && base.join("b.txt").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn archives_and_restores_follow_the_destination_policy() {
+        let dir = temp_dir("policy");
+        let base = dir.join("base");
+        let app_data = dir.join("app-data");
+        fs::create_dir_all(&base).unwrap();
+        fs::create_dir_all(&app_data).unwrap();
+        fs::write(base.join("a.txt"), "one").unwrap();
+        destination_policy::set_app_data_dir(&app_data);
+
+        let planned = plan(&[shown(&base.join("a.txt"))], &shown(&base)).unwrap();
+        let denied = create(&planned, &app_data.join("bundle.tar.gz"), &mut no_progress).unwrap_err();
+        assert_eq!(denied.kind, AppErrorKind::PolicyViolation);
+        assert!(fs::read_dir(&app_data).unwrap().next().is_none());
+
+        let archive_path = dir.join("bundle.tar.gz");
+        create(&planned, &archive_path, &mut no_progress).unwrap();
+        let denied = restore(&archive_path, &app_data, &mut no_progress).unwrap_err();
+        assert_eq!(denied.kind, AppErrorKind::PolicyViolation);
+        assert!(!app_data.join("a.txt").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
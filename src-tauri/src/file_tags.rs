@@ -0,0 +1,267 @@
+// Category tags stored with the file itself so other tools and later scans can see how
+// it was organized: the user.fileorganizer.tags extended attribute on macOS and Linux,
+// an NTFS alternate data stream on Windows. File systems with neither (FAT and exFAT
+// USB sticks, some network shares) get a hidden JSON sidecar next to the file.
+// Attributes and streams follow a rename; sidecars and cross-device copies are carried
+// over by follow_move and copy_tags.
+use crate::error::AppError;
+use crate::paths;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+#[cfg(unix)]
+const XATTR_NAME: &str = "user.fileorganizer.tags";
+#[cfg(windows)]
+const STREAM_NAME: &str = "fileorganizer.tags";
+const SIDECAR_SUFFIX: &str = ".fileorganizer-tags.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TagStorage {
+    #[cfg(unix)]
+    ExtendedAttribute,
+    #[cfg(windows)]
+    AlternateStream,
+    Sidecar,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FileTags {
+    pub path: String,
+    pub tags: Vec<String>,
+    pub storage: TagStorage,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Sidecar {
+    tags: Vec<String>,
+}
+
+// ".report.pdf.fileorganizer-tags.json" beside "report.pdf"; hidden so scans skip it
+fn sidecar_path(path: &Path) -> Option<PathBuf> {
+    let name = path.file_name()?.to_string_lossy();
+    Some(path.with_file_name(format!(".{}{}", name, SIDECAR_SUFFIX)))
+}
+
+pub fn is_sidecar(path: &Path) -> bool {
+    path.file_name()
+        .map(|name| name.to_string_lossy())
+        .is_some_and(|name| name.starts_with('.') && name.ends_with(SIDECAR_SUFFIX))
+}
+
+// Trimmed, without blanks or repeats, in the order given
+fn normalize(tags: Vec<String>) -> Vec<String> {
+    let mut seen = HashSet::new();
+    tags.into_iter()
+        .map(|tag| tag.trim().to_string())
+        .filter(|tag| !tag.is_empty() && seen.insert(tag.clone()))
+        .collect()
+}
+
+fn decode(bytes: &[u8]) -> Option<Vec<String>> {
+    serde_json::from_slice(bytes).ok()
+}
+
+#[cfg(unix)]
+fn read_native(path: &Path) -> std::io::Result<Option<(Vec<String>, TagStorage)>> {
+    let value = xattr::get(path, XATTR_NAME)?;
+    Ok(value.and_then(|bytes| decode(&bytes)).map(|tags| (tags, TagStorage::ExtendedAttribute)))
+}
+
+#[cfg(unix)]
+fn write_native(path: &Path, encoded: Option<&[u8]>) -> std::io::Result<TagStorage> {
+    match encoded {
+        Some(bytes) => xattr::set(path, XATTR_NAME, bytes)?,
+        None => match xattr::remove(path, XATTR_NAME) {
+            // ENODATA / ENOATTR: there was nothing to remove
+            Err(e) if e.raw_os_error() == Some(if cfg!(target_os = "macos") { 93 } else { 61 }) => {}
+            result => result?,
+        },
+    }
+    Ok(TagStorage::ExtendedAttribute)
+}
+
+#[cfg(windows)]
+fn stream_path(path: &Path) -> PathBuf {
+    let mut stream = path.as_os_str().to_os_string();
+    stream.push(":");
+    stream.push(STREAM_NAME);
+    PathBuf::from(stream)
+}
+
+#[cfg(windows)]
+fn read_native(path: &Path) -> std::io::Result<Option<(Vec<String>, TagStorage)>> {
+    match fs::read(stream_path(path)) {
+        Ok(bytes) => Ok(decode(&bytes).map(|tags| (tags, TagStorage::AlternateStream))),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound && path.exists() => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(windows)]
+fn write_native(path: &Path, encoded: Option<&[u8]>) -> std::io::Result<TagStorage> {
+    match encoded {
+        Some(bytes) => fs::write(stream_path(path), bytes)?,
+        None => match fs::remove_file(stream_path(path)) {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            result => result?,
+        },
+    }
+    Ok(TagStorage::AlternateStream)
+}
+
+#[cfg(not(any(unix, windows)))]
+fn read_native(_path: &Path) -> std::io::Result<Option<(Vec<String>, TagStorage)>> {
+    Err(std::io::ErrorKind::Unsupported.into())
+}
+
+#[cfg(not(any(unix, windows)))]
+fn write_native(_path: &Path, _encoded: Option<&[u8]>) -> std::io::Result<TagStorage> {
+    Err(std::io::ErrorKind::Unsupported.into())
+}
+
+// A missing file or a denied write is the caller's problem; anything else means the
+// file system can't hold native tags and the sidecar is used instead
+fn native_unavailable(err: &std::io::Error) -> bool {
+    !matches!(err.kind(), std::io::ErrorKind::NotFound | std::io::ErrorKind::PermissionDenied)
+}
+
+fn read_sidecar(path: &Path) -> Option<Vec<String>> {
+    let bytes = fs::read(sidecar_path(path)?).ok()?;
+    serde_json::from_slice::<Sidecar>(&bytes).ok().map(|sidecar| sidecar.tags)
+}
+
+pub fn remove_sidecar(path: &Path) {
+    if let Some(sidecar) = sidecar_path(path).filter(|sidecar| sidecar.exists()) {
+        if let Err(e) = fs::remove_file(&sidecar) {
+            warn!("Failed to remove tag sidecar {}: {}", sidecar.display(), e);
+        }
+    }
+}
+
+// The file's tags and where they were found; None when it has none
+pub fn read_tags(path: &Path) -> Result<Option<FileTags>, AppError> {
+    fs::symlink_metadata(path).map_err(|e| AppError::from(e).with_path(paths::display_path(path)))?;
+    let native = read_native(path).unwrap_or_else(|e| {
+        if !native_unavailable(&e) {
+            warn!("Failed to read tags of {}: {}", path.display(), e);
+        }
+        None
+    });
+    let found = native.or_else(|| read_sidecar(path).map(|tags| (tags, TagStorage::Sidecar)));
+    Ok(found.map(|(tags, storage)| FileTags { path: paths::display_path(path), tags, storage }))
+}
+
+// Replaces the file's tags; an empty list removes them
+pub fn write_tags(path: &Path, tags: Vec<String>) -> Result<Option<TagStorage>, AppError> {
+    let display = paths::display_path(path);
+    let metadata = fs::symlink_metadata(path).map_err(|e| AppError::from(e).with_path(&display))?;
+    if metadata.is_dir() {
+        return Err(AppError::invalid_input("Tags can only be set on files").with_path(&display));
+    }
+    let tags = normalize(tags);
+    let encoded = if tags.is_empty() {
+        None
+    } else {
+        Some(serde_json::to_vec(&tags).map_err(|e| AppError::io(e.to_string()))?)
+    };
+    match write_native(path, encoded.as_deref()) {
+        Ok(storage) => {
+            remove_sidecar(path);
+            Ok(encoded.map(|_| storage))
+        }
+        Err(e) if native_unavailable(&e) => {
+            let sidecar = sidecar_path(path).ok_or_else(|| AppError::invalid_input("Not a file path"))?;
+            if encoded.is_none() {
+                remove_sidecar(path);
+                return Ok(None);
+            }
+            let json = serde_json::to_vec_pretty(&Sidecar { tags }).map_err(|e| AppError::io(e.to_string()))?;
+            fs::write(&sidecar, json).map_err(|e| AppError::from(e).with_path(paths::display_path(&sidecar)))?;
+            Ok(Some(TagStorage::Sidecar))
+        }
+        Err(e) => Err(AppError::from_io(&e, format!("Failed to save tags: {}", e)).with_path(&display)),
+    }
+}
+
+// After `from` was renamed to `to`: native tags went along, a sidecar is moved beside it
+pub fn follow_move(from: &Path, to: &Path) {
+    let (Some(old), Some(new)) = (sidecar_path(from), sidecar_path(to)) else { return };
+    if old.exists() {
+        if let Err(e) = fs::rename(&old, &new) {
+            warn!("Failed to move tag sidecar {}: {}", old.display(), e);
+        }
+    }
+}
+
+// For copies, which don't bring extended attributes or streams with them
+pub fn copy_tags(from: &Path, to: &Path) -> Result<(), AppError> {
+    match read_tags(from)? {
+        Some(found) => write_tags(to, found.tags).map(|_| ()),
+        None => Ok(()),
+    }
+}
+
+// Tagged files directly inside `dir`, or anywhere below it with `recursive`
+pub fn tags_in_directory(dir: &Path, recursive: bool) -> Result<Vec<FileTags>, AppError> {
+    let display = paths::display_path(dir);
+    if !fs::metadata(dir).map_err(|e| AppError::from(e).with_path(&display))?.is_dir() {
+        return Err(AppError::invalid_input(format!("{} is not a directory", display)).with_path(&display));
+    }
+    let walker = walkdir::WalkDir::new(dir).min_depth(1).max_depth(if recursive { usize::MAX } else { 1 });
+    Ok(walker
+        .into_iter()
+        .filter_map(crate::walk_entry_ok)
+        .filter(|entry| entry.file_type().is_file() && !is_sidecar(entry.path()))
+        .filter_map(|entry| read_tags(entry.path()).ok().flatten())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_dir;
+
+    #[test]
+    fn tags_are_normalized_and_removed_with_an_empty_list() {
+        assert_eq!(normalize(vec![" Taxes ".into(), "".into(), "2024".into(), "Taxes".into()]), ["Taxes", "2024"]);
+        let dir = temp_dir("write");
+        let file = dir.join("return.pdf");
+        fs::write(&file, b"pdf").unwrap();
+
+        let storage = write_tags(&file, vec!["Finance".into(), "Taxes".into()]).unwrap().unwrap();
+        let found = read_tags(&file).unwrap().unwrap();
+        assert_eq!((found.tags, found.storage), (vec!["Finance".to_string(), "Taxes".to_string()], storage));
+
+        assert_eq!(write_tags(&file, vec![]).unwrap(), None);
+        assert!(read_tags(&file).unwrap().is_none());
+        assert!(!sidecar_path(&file).unwrap().exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn sidecars_follow_moves_and_are_left_out_of_listings() {
+        let dir = temp_dir("sidecar");
+        fs::create_dir_all(dir.join("sorted")).unwrap();
+        let file = dir.join("photo.jpg");
+        fs::write(&file, b"jpg").unwrap();
+        let sidecar = sidecar_path(&file).unwrap();
+        fs::write(&sidecar, br#"{"tags":["Photos"]}"#).unwrap();
+        assert!(is_sidecar(&sidecar));
+
+        let listed = tags_in_directory(&dir, false).unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].storage, TagStorage::Sidecar);
+
+        let moved = dir.join("sorted/photo.jpg");
+        fs::rename(&file, &moved).unwrap();
+        follow_move(&file, &moved);
+        assert_eq!(read_tags(&moved).unwrap().unwrap().tags, ["Photos"]);
+        assert!(tags_in_directory(&dir, false).unwrap().is_empty());
+        assert_eq!(tags_in_directory(&dir, true).unwrap().len(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
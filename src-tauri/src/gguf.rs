@@ -0,0 +1,409 @@
+// Minimal GGUF header reader used to validate model files before handing them
+// to the managed server. Only the key/value metadata and tensor table are read;
+// tensor data is never loaded.
+
+use std::fs;
+use std::io::{BufReader, ErrorKind, Read};
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+
+const GGUF_MAGIC: &[u8; 4] = b"GGUF";
+const GGUF_DEFAULT_ALIGNMENT: u64 = 32;
+// Guard against absurd lengths in corrupted headers before allocating
+const MAX_STRING_LEN: u64 = 16 * 1024 * 1024;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GgufMetadata {
+    pub version: u32,
+    pub architecture: String,
+    pub name: Option<String>,
+    pub quantization: Option<String>,
+    pub context_length: Option<u64>,
+    pub block_count: Option<u64>,
+    // tokenizer.chat_template, the Jinja template instruction-tuned models expect
+    // their conversations in
+    pub chat_template: Option<String>,
+    // Which well-known format that template is, named like the server's --chat-format
+    pub chat_format: Option<String>,
+    pub tensor_count: u64,
+    pub file_size: u64,
+}
+
+// Maps general.file_type (llama_ftype) to the usual quantization label
+fn file_type_name(file_type: u32) -> Option<&'static str> {
+    let name = match file_type {
+        0 => "F32",
+        1 => "F16",
+        2 => "Q4_0",
+        3 => "Q4_1",
+        7 => "Q8_0",
+        8 => "Q5_0",
+        9 => "Q5_1",
+        10 => "Q2_K",
+        11 => "Q3_K_S",
+        12 => "Q3_K_M",
+        13 => "Q3_K_L",
+        14 => "Q4_K_S",
+        15 => "Q4_K_M",
+        16 => "Q5_K_S",
+        17 => "Q5_K_M",
+        18 => "Q6_K",
+        19 => "IQ2_XXS",
+        20 => "IQ2_XS",
+        21 => "Q2_K_S",
+        22 => "IQ3_XS",
+        23 => "IQ3_XXS",
+        24 => "IQ1_S",
+        25 => "IQ4_NL",
+        26 => "IQ3_S",
+        27 => "IQ3_M",
+        28 => "IQ2_S",
+        29 => "IQ2_M",
+        30 => "IQ4_XS",
+        31 => "IQ1_M",
+        32 => "BF16",
+        _ => return None,
+    };
+    Some(name)
+}
+
+// Recognizes the common chat template families by their turn markers
+pub fn chat_format_of(template: &str) -> Option<&'static str> {
+    let format = if template.contains("<|im_start|>") {
+        "chatml"
+    } else if template.contains("<|start_header_id|>") {
+        "llama-3"
+    } else if template.contains("<start_of_turn>") {
+        "gemma"
+    } else if template.contains("<|user|>") {
+        if template.contains("<|end|>") { "phi-3" } else { "zephyr" }
+    } else if template.contains("[INST]") {
+        if template.contains("<<SYS>>") { "llama-2" } else { "mistral-instruct" }
+    } else {
+        return None;
+    };
+    Some(format)
+}
+
+// Byte size of fixed-size GGUF value types; None for strings and arrays
+fn scalar_size(value_type: u32) -> Option<u64> {
+    match value_type {
+        0 | 1 | 7 => Some(1),
+        2..=3 => Some(2),
+        4..=6 => Some(4),
+        10..=12 => Some(8),
+        _ => None,
+    }
+}
+
+enum Value {
+    Uint(u64),
+    Str(String),
+    Other,
+}
+
+struct HeaderReader<R: Read> {
+    inner: R,
+    file_size: u64,
+    position: u64,
+}
+
+impl<R: Read> HeaderReader<R> {
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), String> {
+        self.inner.read_exact(buf).map_err(|e| {
+            if e.kind() == ErrorKind::UnexpectedEof {
+                format!(
+                    "File truncated: header ends after {} of {} bytes",
+                    self.position, self.file_size
+                )
+            } else {
+                format!("Failed to read GGUF header: {}", e)
+            }
+        })?;
+        self.position += buf.len() as u64;
+        Ok(())
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        let mut buf = [0u8; 4];
+        self.read_exact(&mut buf)?;
+        Ok(u32::from_le_bytes(buf))
+    }
+
+    fn u64(&mut self) -> Result<u64, String> {
+        let mut buf = [0u8; 8];
+        self.read_exact(&mut buf)?;
+        Ok(u64::from_le_bytes(buf))
+    }
+
+    // GGUF v1 used 32-bit lengths and counts, v2+ use 64-bit
+    fn count(&mut self, version: u32) -> Result<u64, String> {
+        if version == 1 {
+            self.u32().map(u64::from)
+        } else {
+            self.u64()
+        }
+    }
+
+    fn skip(&mut self, len: u64) -> Result<(), String> {
+        // A corrupt array header can ask for more than u64::MAX bytes in total
+        let end = self.position.checked_add(len).filter(|&end| end <= self.file_size);
+        if end.is_none() {
+            return Err(format!(
+                "File truncated: header needs {} more bytes after byte {} but file is {} bytes",
+                len, self.position, self.file_size
+            ));
+        }
+        let copied = std::io::copy(&mut (&mut self.inner).take(len), &mut std::io::sink())
+            .map_err(|e| format!("Failed to read GGUF header: {}", e))?;
+        self.position += copied;
+        Ok(())
+    }
+
+    fn string(&mut self, version: u32) -> Result<String, String> {
+        let len = self.count(version)?;
+        if len > MAX_STRING_LEN {
+            return Err(format!("Corrupted GGUF header: string length {} is implausible", len));
+        }
+        let mut buf = vec![0u8; len as usize];
+        self.read_exact(&mut buf)?;
+        Ok(String::from_utf8_lossy(&buf).into_owned())
+    }
+
+    fn value(&mut self, version: u32, value_type: u32) -> Result<Value, String> {
+        let value = match value_type {
+            4 => Value::Uint(self.u32()? as u64),
+            10 => Value::Uint(self.u64()?),
+            8 => Value::Str(self.string(version)?),
+            9 => {
+                let item_type = self.u32()?;
+                let len = self.count(version)?;
+                match scalar_size(item_type) {
+                    // Fixed-size arrays (e.g. tokenizer scores) are skipped in one go
+                    Some(size) => self.skip(len.saturating_mul(size))?,
+                    None => {
+                        for _ in 0..len {
+                            self.value(version, item_type)?;
+                        }
+                    }
+                }
+                Value::Other
+            }
+            other => match scalar_size(other) {
+                Some(size) => {
+                    self.skip(size)?;
+                    Value::Other
+                }
+                None => return Err(format!("Corrupted GGUF header: unknown value type {}", other)),
+            },
+        };
+        Ok(value)
+    }
+}
+
+// Reads and validates the GGUF header of a model file, returning its metadata
+pub fn read_gguf_metadata(path: &Path) -> Result<GgufMetadata, String> {
+    let file = fs::File::open(path)
+        .map_err(|e| format!("Failed to open model file: {}", e))?;
+    let file_size = file.metadata()
+        .map_err(|e| format!("Failed to read model file metadata: {}", e))?
+        .len();
+
+    let mut reader = HeaderReader {
+        inner: BufReader::new(file),
+        file_size,
+        position: 0,
+    };
+
+    let mut magic = [0u8; 4];
+    if file_size < 4 || reader.read_exact(&mut magic).is_err() || &magic != GGUF_MAGIC {
+        return Err(format!("Not a GGUF file: {}", path.to_string_lossy()));
+    }
+
+    let version = reader.u32()?;
+    if !(1..=3).contains(&version) {
+        return Err(format!("Unsupported GGUF version: {}", version));
+    }
+
+    let tensor_count = reader.count(version)?;
+    let kv_count = reader.count(version)?;
+
+    let mut architecture = None;
+    let mut name = None;
+    let mut file_type = None;
+    let mut chat_template = None;
+    let mut alignment = GGUF_DEFAULT_ALIGNMENT;
+    let mut context_lengths: Vec<(String, u64)> = Vec::new();
+    let mut block_counts: Vec<(String, u64)> = Vec::new();
+
+    for _ in 0..kv_count {
+        let key = reader.string(version)?;
+        let value_type = reader.u32()?;
+        match (key.as_str(), reader.value(version, value_type)?) {
+            ("general.architecture", Value::Str(s)) => architecture = Some(s),
+            ("general.name", Value::Str(s)) => name = Some(s),
+            ("general.file_type", Value::Uint(v)) => file_type = Some(v as u32),
+            ("general.alignment", Value::Uint(v)) if v > 0 => alignment = v,
+            ("tokenizer.chat_template", Value::Str(s)) => chat_template = Some(s),
+            (k, Value::Uint(v)) if k.ends_with(".context_length") => {
+                context_lengths.push((k.trim_end_matches(".context_length").to_string(), v));
+            }
+            (k, Value::Uint(v)) if k.ends_with(".block_count") => {
+                block_counts.push((k.trim_end_matches(".block_count").to_string(), v));
+            }
+            _ => {}
+        }
+    }
+
+    // Tensor infos: the largest data offset gives a lower bound on the file size
+    let mut max_offset = 0u64;
+    for _ in 0..tensor_count {
+        reader.string(version)?;
+        let n_dims = reader.u32()?;
+        for _ in 0..n_dims {
+            reader.count(version)?;
+        }
+        reader.u32()?; // tensor type
+        max_offset = max_offset.max(reader.u64()?);
+    }
+
+    let expected_min = reader.position
+        .div_ceil(alignment)
+        .checked_mul(alignment)
+        .and_then(|data_start| data_start.checked_add(max_offset));
+    if tensor_count > 0 && expected_min.is_none_or(|expected_min| expected_min >= file_size) {
+        return Err(format!(
+            "File truncated: expected more than {} bytes, found {}",
+            expected_min.map_or_else(|| "u64::MAX".to_string(), |n| n.to_string()),
+            file_size
+        ));
+    }
+
+    let architecture = match architecture {
+        Some(arch) => arch,
+        None => return Err("Unsupported GGUF file: missing general.architecture".to_string()),
+    };
+
+    let context_length = context_lengths
+        .iter()
+        .find(|(prefix, _)| *prefix == architecture)
+        .map(|(_, v)| *v);
+    let block_count = block_counts
+        .iter()
+        .find(|(prefix, _)| *prefix == architecture)
+        .map(|(_, v)| *v);
+
+    Ok(GgufMetadata {
+        version,
+        architecture,
+        name,
+        quantization: file_type.map(|t| {
+            file_type_name(t)
+                .map(|s| s.to_string())
+                .unwrap_or_else(|| format!("type {}", t))
+        }),
+        context_length,
+        block_count,
+        chat_format: chat_template.as_deref().and_then(chat_format_of).map(str::to_string),
+        chat_template,
+        tensor_count,
+        file_size,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_dir;
+
+    // GGUF v3 header pieces, little-endian like the format
+    fn string(bytes: &mut Vec<u8>, text: &str) {
+        bytes.extend((text.len() as u64).to_le_bytes());
+        bytes.extend(text.as_bytes());
+    }
+
+    // The key/values follow, starting with general.architecture
+    fn header(tensor_count: u64, kv_count: u64) -> Vec<u8> {
+        let mut bytes = b"GGUF".to_vec();
+        bytes.extend(3u32.to_le_bytes());
+        bytes.extend(tensor_count.to_le_bytes());
+        bytes.extend(kv_count.to_le_bytes());
+        string(&mut bytes, "general.architecture");
+        bytes.extend(8u32.to_le_bytes());
+        string(&mut bytes, "llama");
+        bytes
+    }
+
+    fn block_count(bytes: &mut Vec<u8>) {
+        string(bytes, "llama.block_count");
+        bytes.extend(4u32.to_le_bytes());
+        bytes.extend(32u32.to_le_bytes());
+    }
+
+    // An array of u64 (type 10) claiming `len` items, with no items after it
+    fn u64_array(bytes: &mut Vec<u8>, len: u64) {
+        string(bytes, "tokenizer.ggml.scores");
+        bytes.extend(9u32.to_le_bytes());
+        bytes.extend(10u32.to_le_bytes());
+        bytes.extend(len.to_le_bytes());
+    }
+
+    fn read(dir: &Path, name: &str, bytes: &[u8]) -> Result<GgufMetadata, String> {
+        let path = dir.join(name);
+        fs::write(&path, bytes).unwrap();
+        read_gguf_metadata(&path)
+    }
+
+    #[test]
+    fn truncated_headers_are_errors() {
+        let dir = temp_dir("gguf-truncated");
+        let mut full = header(0, 2);
+        block_count(&mut full);
+        let metadata = read(&dir, "full.gguf", &full).unwrap();
+        assert_eq!((metadata.architecture.as_str(), metadata.block_count), ("llama", Some(32)));
+
+        for len in 0..full.len() {
+            assert!(read(&dir, "cut.gguf", &full[..len]).is_err(), "cut at {}", len);
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn oversized_arrays_and_offsets_are_errors() {
+        let dir = temp_dir("gguf-oversized");
+        for len in [16, u64::MAX / 8 + 1, u64::MAX] {
+            let mut bytes = header(0, 2);
+            u64_array(&mut bytes, len);
+            let err = read(&dir, "array.gguf", &bytes).unwrap_err();
+            assert!(err.starts_with("File truncated"), "{}: {}", len, err);
+        }
+
+        // A tensor whose data offset runs past the end of u64
+        let mut bytes = header(1, 1);
+        string(&mut bytes, "token_embd.weight");
+        bytes.extend(1u32.to_le_bytes());
+        bytes.extend(4096u64.to_le_bytes());
+        bytes.extend(0u32.to_le_bytes());
+        bytes.extend(u64::MAX.to_le_bytes());
+        let err = read(&dir, "offset.gguf", &bytes).unwrap_err();
+        assert!(err.starts_with("File truncated"), "{}", err);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn chat_formats_are_recognized_by_their_turn_markers() {
+        let cases = [
+            ("{% for m in messages %}<|im_start|>{{ m.role }}\n{{ m.content }}<|im_end|>{% endfor %}", Some("chatml")),
+            ("<|start_header_id|>{{ message['role'] }}<|end_header_id|>\n\n", Some("llama-3")),
+            ("<start_of_turn>{{ role }}\n{{ message['content'] | trim }}<end_of_turn>\n", Some("gemma")),
+            ("<|user|>\n{{ message['content'] }}<|end|>\n<|assistant|>\n", Some("phi-3")),
+            ("<|user|>\n{{ message['content'] }}</s>", Some("zephyr")),
+            ("[INST] <<SYS>>\n{{ system }}\n<</SYS>>\n\n{{ user }} [/INST]", Some("llama-2")),
+            ("{{ bos_token }}[INST] {{ message['content'] }} [/INST]", Some("mistral-instruct")),
+            ("{{ messages[0]['content'] }}", None),
+        ];
+        for (template, expected) in cases {
+            assert_eq!(chat_format_of(template), expected, "{}", template);
+        }
+    }
+}
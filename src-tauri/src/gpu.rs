@@ -0,0 +1,149 @@
+// What GPU the managed server can use: the backend (Metal, CUDA, Vulkan or CPU), the
+// device name and VRAM where they can be found, and how many layers of a model fit.
+use serde::{Deserialize, Serialize};
+use std::process::Command;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GpuCapabilities {
+    pub backend: String, // "metal" | "cuda" | "vulkan" | "cpu"
+    pub metal: bool,
+    pub cuda: bool,
+    pub vulkan: bool,
+    pub device_name: String, // "unknown" when it could not be determined
+    pub vram_bytes: Option<u64>,
+    pub unified_memory: bool,
+    pub recommended_gpu_layers: Option<u64>,
+}
+
+// Parses `nvidia-smi --query-gpu=name,memory.total --format=csv,noheader,nounits`
+// output, returning the first device's name and memory in bytes
+#[cfg(not(target_os = "macos"))]
+fn parse_nvidia_smi_output(output: &str) -> Option<(String, Option<u64>)> {
+    let line = output.lines().map(|l| l.trim()).find(|l| !l.is_empty())?;
+    let mut parts = line.rsplitn(2, ',');
+    let memory_mib = parts.next()?.trim().parse::<u64>().ok();
+    let name = parts.next()?.trim().to_string();
+    Some((name, memory_mib.map(|mib| mib * 1024 * 1024)))
+}
+
+#[cfg(not(target_os = "macos"))]
+fn detect_cuda_device() -> Option<(String, Option<u64>)> {
+    let output = Command::new("nvidia-smi")
+        .args(["--query-gpu=name,memory.total", "--format=csv,noheader,nounits"])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    parse_nvidia_smi_output(&String::from_utf8_lossy(&output.stdout))
+}
+
+#[cfg(not(target_os = "macos"))]
+fn detect_vulkan_device_name() -> Option<String> {
+    let output = Command::new("vulkaninfo").arg("--summary").output().ok()?;
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find_map(|line| {
+            let (key, value) = line.split_once('=')?;
+            (key.trim() == "deviceName").then(|| value.trim().to_string())
+        })
+}
+
+#[cfg(target_os = "macos")]
+fn sysctl_value(name: &str) -> Option<String> {
+    let output = Command::new("sysctl").args(["-n", name]).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+// Number of layers to offload so the model fits in the available VRAM, leaving
+// headroom for the KV cache and compute buffers
+pub fn recommend_gpu_layers(vram_bytes: u64, model_size: u64, block_count: u64) -> u64 {
+    const RESERVED_BYTES: u64 = 768 * 1024 * 1024;
+    if model_size == 0 || block_count == 0 {
+        return 0;
+    }
+    let usable = (vram_bytes / 10 * 9).saturating_sub(RESERVED_BYTES);
+    let bytes_per_layer = (model_size / block_count).max(1);
+    (usable / bytes_per_layer).min(block_count)
+}
+
+pub fn detect_gpu() -> GpuCapabilities {
+    let mut caps = GpuCapabilities {
+        backend: "cpu".to_string(),
+        metal: false,
+        cuda: false,
+        vulkan: false,
+        device_name: "unknown".to_string(),
+        vram_bytes: None,
+        unified_memory: false,
+        recommended_gpu_layers: None,
+    };
+
+    #[cfg(target_os = "macos")]
+    {
+        // The macOS server runs on MLX/Metal; GPU memory is shared with the system
+        caps.metal = true;
+        caps.backend = "metal".to_string();
+        caps.unified_memory = true;
+        // device_name stays unknown: machdep.cpu.brand_string names the CPU, not the GPU.
+        // Metal typically allows roughly two thirds of unified memory for the GPU
+        caps.vram_bytes = sysctl_value("hw.memsize")
+            .and_then(|v| v.parse::<u64>().ok())
+            .map(|total| total / 3 * 2);
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    {
+        if let Some((name, vram)) = detect_cuda_device() {
+            caps.cuda = true;
+            caps.backend = "cuda".to_string();
+            caps.device_name = name;
+            caps.vram_bytes = vram;
+        }
+        caps.vulkan = crate::is_vulkan_available();
+        if caps.vulkan && !caps.cuda {
+            caps.backend = "vulkan".to_string();
+            if let Some(name) = detect_vulkan_device_name() {
+                caps.device_name = name;
+            }
+        }
+    }
+    caps
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MIB: u64 = 1024 * 1024;
+    const GIB: u64 = 1024 * MIB;
+
+    #[cfg(not(target_os = "macos"))]
+    #[test]
+    fn nvidia_smi_output_gives_the_first_device() {
+        let output = "\nNVIDIA GeForce RTX 4090, 24564\nNVIDIA GeForce RTX 3060, 12288\n";
+        assert_eq!(parse_nvidia_smi_output(output), Some(("NVIDIA GeForce RTX 4090".to_string(), Some(24564 * MIB))));
+        // Names may contain commas; memory is always the last column
+        assert_eq!(
+            parse_nvidia_smi_output("NVIDIA RTX 6000, Ada Generation, 49140"),
+            Some(("NVIDIA RTX 6000, Ada Generation".to_string(), Some(49140 * MIB)))
+        );
+        assert_eq!(parse_nvidia_smi_output("NVIDIA T4, [N/A]"), Some(("NVIDIA T4".to_string(), None)));
+        assert_eq!(parse_nvidia_smi_output(""), None);
+        assert_eq!(parse_nvidia_smi_output("No devices were found"), None);
+    }
+
+    #[test]
+    fn gpu_layers_fit_the_usable_vram() {
+        // 4 GiB over 32 layers is 128 MiB a layer
+        assert_eq!(recommend_gpu_layers(8 * GIB, 4 * GIB, 32), 32);
+        assert_eq!(recommend_gpu_layers(2 * GIB, 4 * GIB, 32), 8);
+        // Less than the reserved headroom leaves nothing to offload
+        assert_eq!(recommend_gpu_layers(512 * MIB, 4 * GIB, 32), 0);
+        assert_eq!(recommend_gpu_layers(8 * GIB, 0, 32), 0);
+        assert_eq!(recommend_gpu_layers(8 * GIB, 4 * GIB, 0), 0);
+    }
+}
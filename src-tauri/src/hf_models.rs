@@ -0,0 +1,248 @@
+// Browsing GGUF models on the Hugging Face Hub, so a model can be picked and
+// downloaded without leaving the app. The optional user token (for gated repos) lives
+// in settings and only ever travels in the Authorization header.
+use crate::error::{AppError, AppErrorKind};
+use serde::{Deserialize, Serialize};
+use std::path::{Component, Path, PathBuf};
+
+const HUB_URL: &str = "https://huggingface.co";
+const DEFAULT_SEARCH_LIMIT: u32 = 30;
+const MAX_SEARCH_LIMIT: u32 = 100;
+// Used when a rate-limited response doesn't say how long to wait
+const DEFAULT_RETRY_AFTER_MS: u64 = 60_000;
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct SearchFilters {
+    pub author: Option<String>,
+    // "downloads" (default), "likes", "lastModified" or "trending"
+    pub sort: Option<String>,
+    pub limit: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HfModel {
+    pub id: String,
+    #[serde(default)]
+    pub downloads: u64,
+    #[serde(default)]
+    pub likes: u64,
+    #[serde(default, rename(deserialize = "lastModified"))]
+    pub last_modified: Option<String>,
+    // false, "auto" or "manual" on the Hub; true here when a token is needed
+    #[serde(default, deserialize_with = "deserialize_gated")]
+    pub gated: bool,
+}
+
+fn deserialize_gated<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<bool, D::Error> {
+    let value = serde_json::Value::deserialize(deserializer)?;
+    Ok(!matches!(value, serde_json::Value::Bool(false) | serde_json::Value::Null))
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HfModelFile {
+    pub filename: String,
+    pub size: u64,
+    pub quantization: Option<String>,
+    // From the file's LFS pointer; None for files not stored in LFS
+    pub sha256: Option<String>,
+    pub download_url: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TreeEntry {
+    #[serde(rename = "type")]
+    kind: String,
+    path: String,
+    #[serde(default)]
+    size: u64,
+    lfs: Option<LfsPointer>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LfsPointer {
+    oid: String,
+    size: u64,
+}
+
+// "owner/name" with the characters the Hub allows
+pub fn validate_repo_id(repo_id: &str) -> Result<(), AppError> {
+    let valid_part = |part: &str| {
+        !part.is_empty()
+            && part != "."
+            && part != ".."
+            && part.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    };
+    match repo_id.split_once('/') {
+        Some((owner, name)) if valid_part(owner) && valid_part(name) => Ok(()),
+        _ => Err(AppError::invalid_input(format!("\"{}\" is not a Hugging Face repo id (owner/name)", repo_id))),
+    }
+}
+
+// A path inside the repo: relative, with no ".." components
+fn validate_repo_path(filename: &str) -> Result<(), AppError> {
+    let path = Path::new(filename);
+    if filename.is_empty() || !path.components().all(|c| matches!(c, Component::Normal(_))) {
+        return Err(AppError::invalid_input(format!("\"{}\" is not a file in the repo", filename)));
+    }
+    Ok(())
+}
+
+// Where a downloaded model goes: app_data_dir/models/<owner>--<name>/<filename>
+pub fn model_path(app_data_dir: &Path, repo_id: &str, filename: &str) -> Result<PathBuf, AppError> {
+    validate_repo_id(repo_id)?;
+    validate_repo_path(filename)?;
+    Ok(app_data_dir.join("models").join(repo_id.replace('/', "--")).join(filename))
+}
+
+pub fn download_url(repo_id: &str, filename: &str) -> String {
+    format!("{}/{}/resolve/main/{}", HUB_URL, repo_id, filename)
+}
+
+// The quantization label in a GGUF filename ("Llama-3.2-1B-Instruct-Q4_K_M.gguf",
+// "phi-2.q8_0.gguf"), uppercased
+pub fn quantization_of(filename: &str) -> Option<String> {
+    let name = Path::new(filename).file_name()?.to_string_lossy();
+    let stem = name.strip_suffix(".gguf").unwrap_or(&name);
+    stem.split(['-', '.']).rev().map(str::to_ascii_uppercase).find(|token| {
+        let digits_after = |prefix: &str| {
+            token.strip_prefix(prefix).is_some_and(|rest| rest.starts_with(|c: char| c.is_ascii_digit()))
+        };
+        matches!(token.as_str(), "F16" | "F32" | "BF16") || digits_after("Q") || digits_after("IQ")
+    })
+}
+
+fn gguf_files(repo_id: &str, tree: Vec<TreeEntry>) -> Vec<HfModelFile> {
+    let mut files: Vec<HfModelFile> = tree
+        .into_iter()
+        .filter(|entry| entry.kind == "file" && entry.path.to_ascii_lowercase().ends_with(".gguf"))
+        .map(|entry| HfModelFile {
+            quantization: quantization_of(&entry.path),
+            size: entry.lfs.as_ref().map_or(entry.size, |lfs| lfs.size),
+            sha256: entry.lfs.map(|lfs| lfs.oid),
+            download_url: download_url(repo_id, &entry.path),
+            filename: entry.path,
+        })
+        .collect();
+    files.sort_by(|a, b| a.size.cmp(&b.size).then_with(|| a.filename.cmp(&b.filename)));
+    files
+}
+
+// Maps a Hub response status to the structured error; rate limits carry the wait
+pub fn hub_error(status: reqwest::StatusCode, retry_after_secs: Option<u64>, what: &str) -> AppError {
+    let message = format!("Hugging Face returned {} for {}", status, what);
+    match status.as_u16() {
+        429 => AppError::network(format!("Hugging Face rate limit reached while fetching {}", what))
+            .with_retry_after(retry_after_secs.map_or(DEFAULT_RETRY_AFTER_MS, |secs| secs * 1000)),
+        401 | 403 => AppError::new(
+            AppErrorKind::PermissionDenied,
+            format!("{} is gated or private; accept its terms on Hugging Face and set an access token", what),
+        ),
+        404 => AppError::not_found(message),
+        _ => AppError::network(message),
+    }
+}
+
+async fn get_json<T: serde::de::DeserializeOwned>(url: &str, token: Option<&str>, what: &str) -> Result<T, AppError> {
+    let mut request = crate::network::build_http_client().get(url);
+    if let Some(token) = token {
+        request = request.bearer_auth(token);
+    }
+    let response = request.send().await?;
+    let status = response.status();
+    if !status.is_success() {
+        let retry_after = response
+            .headers()
+            .get(reqwest::header::RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse().ok());
+        return Err(hub_error(status, retry_after, what));
+    }
+    response
+        .json()
+        .await
+        .map_err(|e| AppError::new(AppErrorKind::InvalidData, format!("Unexpected response for {}: {}", what, e)))
+}
+
+// Repos with GGUF files matching `query`, most downloaded first unless filters say otherwise
+pub async fn search(query: &str, filters: &SearchFilters, token: Option<&str>) -> Result<Vec<HfModel>, AppError> {
+    let mut url = reqwest::Url::parse(&format!("{}/api/models", HUB_URL)).expect("static URL");
+    {
+        let mut params = url.query_pairs_mut();
+        params.append_pair("search", query.trim());
+        params.append_pair("filter", "gguf");
+        params.append_pair("sort", filters.sort.as_deref().unwrap_or("downloads"));
+        params.append_pair("direction", "-1");
+        let limit = filters.limit.unwrap_or(DEFAULT_SEARCH_LIMIT).clamp(1, MAX_SEARCH_LIMIT);
+        params.append_pair("limit", &limit.to_string());
+        if let Some(author) = filters.author.as_deref().filter(|author| !author.trim().is_empty()) {
+            params.append_pair("author", author.trim());
+        }
+    }
+    get_json(url.as_str(), token, "the model search").await
+}
+
+// The repo's .gguf files, smallest first
+pub async fn list_files(repo_id: &str, token: Option<&str>) -> Result<Vec<HfModelFile>, AppError> {
+    validate_repo_id(repo_id)?;
+    let url = format!("{}/api/models/{}/tree/main?recursive=true", HUB_URL, repo_id);
+    let tree: Vec<TreeEntry> = get_json(&url, token, repo_id).await?;
+    Ok(gguf_files(repo_id, tree))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quantization_comes_from_the_filename() {
+        let cases = [
+            ("Llama-3.2-1B-Instruct-Q4_K_M.gguf", Some("Q4_K_M")),
+            ("phi-2.q8_0.gguf", Some("Q8_0")),
+            ("gemma-2-2b-it-IQ3_XXS.gguf", Some("IQ3_XXS")),
+            ("Q6_K/qwen2.5-7b-instruct-q6_k-00001-of-00002.gguf", Some("Q6_K")),
+            ("tinyllama-1.1b-chat.F16.gguf", Some("F16")),
+            ("mmproj-model.gguf", None),
+        ];
+        for (filename, expected) in cases {
+            assert_eq!(quantization_of(filename).as_deref(), expected, "{}", filename);
+        }
+    }
+
+    #[test]
+    fn only_gguf_files_are_listed_with_their_lfs_hash() {
+        let tree: Vec<TreeEntry> = serde_json::from_value(serde_json::json!([
+            { "type": "file", "path": "README.md", "size": 5000 },
+            { "type": "directory", "path": "Q8_0", "size": 0 },
+            { "type": "file", "path": "model-Q8_0.gguf", "size": 135,
+              "lfs": { "oid": "ab12", "size": 8_000_000_000u64, "pointerSize": 135 } },
+            { "type": "file", "path": "model-Q4_K_M.gguf", "size": 135,
+              "lfs": { "oid": "cd34", "size": 4_000_000_000u64, "pointerSize": 135 } },
+        ]))
+        .unwrap();
+        let files = gguf_files("someone/model-GGUF", tree);
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].filename, "model-Q4_K_M.gguf");
+        assert_eq!((files[0].size, files[0].sha256.as_deref()), (4_000_000_000, Some("cd34")));
+        assert_eq!(files[0].download_url, "https://huggingface.co/someone/model-GGUF/resolve/main/model-Q4_K_M.gguf");
+    }
+
+    #[test]
+    fn repo_ids_and_filenames_cant_escape_the_models_folder() {
+        let base = Path::new("/data");
+        let path = model_path(base, "someone/model-GGUF", "Q4/model.gguf").unwrap();
+        assert!(path.ends_with("someone--model-GGUF/Q4/model.gguf"));
+        assert!(model_path(base, "someone/../etc", "model.gguf").is_err());
+        assert!(model_path(base, "someone/model", "../model.gguf").is_err());
+        assert!(model_path(base, "someone/model", "/etc/passwd").is_err());
+        assert!(model_path(base, "no-owner", "model.gguf").is_err());
+    }
+
+    #[test]
+    fn rate_limits_carry_a_retry_hint() {
+        let err = hub_error(reqwest::StatusCode::TOO_MANY_REQUESTS, Some(30), "the model search");
+        assert_eq!((err.kind, err.retry_after_ms), (AppErrorKind::Network, Some(30_000)));
+        let err = hub_error(reqwest::StatusCode::FORBIDDEN, None, "meta-llama/Llama-3.2-1B");
+        assert_eq!(err.kind, AppErrorKind::PermissionDenied);
+    }
+}
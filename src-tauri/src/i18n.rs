@@ -0,0 +1,175 @@
+// Translations for strings the backend shows to people: OS notifications, error messages
+// the UI displays as-is and verification details. Catalogs are flat JSON files in
+// src-tauri/locales, embedded at build time; a key missing from one falls back to
+// English. Text sent to models (extractor markers like "Sheet:", prompts) is not
+// translated, so classification behaves the same in every locale.
+use crate::settings;
+use chrono::{DateTime, Datelike, Local, Timelike};
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+pub const DEFAULT_LOCALE: &str = "en";
+
+const CATALOGS: &[(&str, &str)] = &[
+    ("en", include_str!("../locales/en.json")),
+    ("de", include_str!("../locales/de.json")),
+    ("fr", include_str!("../locales/fr.json")),
+    ("es", include_str!("../locales/es.json")),
+];
+
+type Catalog = HashMap<String, String>;
+
+fn catalogs() -> &'static HashMap<&'static str, Catalog> {
+    static PARSED: OnceLock<HashMap<&'static str, Catalog>> = OnceLock::new();
+    PARSED.get_or_init(|| {
+        CATALOGS
+            .iter()
+            .map(|(locale, json)| {
+                let catalog = serde_json::from_str(json)
+                    .unwrap_or_else(|e| panic!("Invalid message catalog for {}: {}", locale, e));
+                (*locale, catalog)
+            })
+            .collect()
+    })
+}
+
+pub fn supported_locales() -> Vec<&'static str> {
+    CATALOGS.iter().map(|(locale, _)| *locale).collect()
+}
+
+// The supported locale for a BCP 47 tag like "de-AT" or "fr_CA", by its language
+pub fn supported_locale(tag: &str) -> Option<&'static str> {
+    let language = tag.split(['-', '_']).next().unwrap_or_default().to_ascii_lowercase();
+    CATALOGS.iter().map(|(locale, _)| *locale).find(|locale| *locale == language)
+}
+
+pub fn current_locale() -> &'static str {
+    settings::with(|settings| settings.locale.as_deref().and_then(supported_locale)).unwrap_or(DEFAULT_LOCALE)
+}
+
+fn lookup(locale: &str, key: &str) -> String {
+    [locale, DEFAULT_LOCALE]
+        .iter()
+        .find_map(|locale| catalogs().get(locale)?.get(key))
+        .cloned()
+        .unwrap_or_else(|| key.to_string())
+}
+
+// Replaces {name} placeholders in one pass; unknown ones are left as they are
+fn fill(template: &str, args: &[(&str, &str)]) -> String {
+    let mut filled = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        filled.push_str(&rest[..open]);
+        let after = &rest[open..];
+        let value = after
+            .find('}')
+            .and_then(|close| args.iter().find(|(name, _)| *name == &after[1..close]).map(|(_, v)| (close, *v)));
+        match value {
+            Some((close, value)) => {
+                filled.push_str(value);
+                rest = &after[close + 1..];
+            }
+            None => {
+                filled.push('{');
+                rest = &after[1..];
+            }
+        }
+    }
+    filled.push_str(rest);
+    filled
+}
+
+pub fn t(key: &str) -> String {
+    lookup(current_locale(), key)
+}
+
+pub fn tf(key: &str, args: &[(&str, &str)]) -> String {
+    fill(&lookup(current_locale(), key), args)
+}
+
+// Byte counts as "512 bytes", "1.5 MB" (binary multiples, one decimal), with the
+// locale's units and decimal separator
+pub fn format_size(bytes: u64) -> String {
+    format_size_in(current_locale(), bytes)
+}
+
+fn format_size_in(locale: &str, bytes: u64) -> String {
+    let units = lookup(locale, "format.size_units");
+    let units: Vec<&str> = units.split_whitespace().collect();
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < units.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        return format!("{} {}", bytes, units.first().copied().unwrap_or("B"));
+    }
+    let number = format!("{:.1}", value).replace('.', &lookup(locale, "format.decimal_separator"));
+    format!("{} {}", number, units[unit])
+}
+
+// Local date and time in the locale's numeric style, e.g. 3/7/2024 9:05 AM or 07.03.2024 09:05
+pub fn format_date_time(time: DateTime<Local>) -> String {
+    format_date_time_in(current_locale(), time)
+}
+
+fn format_date_time_in(locale: &str, time: DateTime<Local>) -> String {
+    let (pm, hour12) = time.hour12();
+    let am_pm = lookup(locale, if pm { "format.pm" } else { "format.am" });
+    let parts = [
+        ("year", time.year().to_string()),
+        ("month", time.month().to_string()),
+        ("month2", format!("{:02}", time.month())),
+        ("day", time.day().to_string()),
+        ("day2", format!("{:02}", time.day())),
+        ("hour2", format!("{:02}", time.hour())),
+        ("hour12", hour12.to_string()),
+        ("minute2", format!("{:02}", time.minute())),
+        ("am_pm", am_pm),
+    ];
+    let args: Vec<(&str, &str)> = parts.iter().map(|(name, value)| (*name, value.as_str())).collect();
+    format!("{} {}", fill(&lookup(locale, "format.date"), &args), fill(&lookup(locale, "format.time"), &args))
+        .trim_end()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn every_catalog_has_the_english_keys() {
+        let english = &catalogs()[DEFAULT_LOCALE];
+        for (locale, catalog) in catalogs() {
+            let mut missing: Vec<&String> = english.keys().filter(|key| !catalog.contains_key(*key)).collect();
+            missing.sort();
+            assert!(missing.is_empty(), "{} is missing {:?}", locale, missing);
+        }
+    }
+
+    #[test]
+    fn locales_are_matched_by_language_and_placeholders_filled() {
+        assert_eq!(supported_locale("de-AT"), Some("de"));
+        assert_eq!(supported_locale("FR_ca"), Some("fr"));
+        assert_eq!(supported_locale("ja"), None);
+        let filled = fill("{path}: {total} {unknown} {", &[("path", "/a"), ("total", "{path}")]);
+        assert_eq!(filled, "/a: {path} {unknown} {");
+        assert_eq!(lookup("de", "no.such.key"), "no.such.key");
+    }
+
+    #[test]
+    fn sizes_and_dates_follow_the_locale() {
+        assert_eq!(format_size_in("en", 512), "512 bytes");
+        assert_eq!(format_size_in("en", 1536), "1.5 KB");
+        assert_eq!(format_size_in("fr", 3 * 1024 * 1024), "3,0 Mo");
+        assert_eq!(format_size_in("de", u64::MAX), "16777216,0 TB");
+
+        let time = Local.with_ymd_and_hms(2024, 3, 7, 21, 5, 0).unwrap();
+        assert_eq!(format_date_time_in("en", time), "3/7/2024 9:05 PM");
+        assert_eq!(format_date_time_in("de", time), "07.03.2024 21:05");
+        assert_eq!(format_date_time_in("es", time), "07/03/2024 21:05");
+    }
+}
@@ -0,0 +1,208 @@
+// Keeps background analysis (hashing a whole disk for duplicates, indexing) from making
+// the rest of the machine crawl. Low priority asks the OS to serve the scan's reads
+// last (the idle I/O class on Linux, a low I/O priority hint on each file handle on
+// Windows) and, on every platform, caps the scan's read rate with a token bucket that
+// can be changed while a scan runs. Normal priority reads at full speed.
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_THROTTLE_BYTES_PER_SEC: u64 = 20 * 1024 * 1024;
+// A quarter second of reads may burst above the rate
+const BURST_SECONDS: f64 = 0.25;
+// Long waits are cut into slices so un-throttling takes effect promptly
+const MAX_WAIT_SLICE: Duration = Duration::from_millis(100);
+
+// Bytes per second for low-priority reads; 0 means unlimited
+static THROTTLE_BYTES_PER_SEC: AtomicU64 = AtomicU64::new(DEFAULT_THROTTLE_BYTES_PER_SEC);
+// Shared by every low-priority scan, so two scans together stay under the rate
+static BUCKET: Mutex<Option<Bucket>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IoPriority {
+    #[default]
+    Normal,
+    Low,
+}
+
+struct Bucket {
+    // Negative while readers are waiting off a debt
+    tokens: f64,
+    refilled: Instant,
+}
+
+pub fn throttle() -> u64 {
+    THROTTLE_BYTES_PER_SEC.load(Ordering::Relaxed)
+}
+
+pub fn set_throttle(bytes_per_sec: u64) {
+    THROTTLE_BYTES_PER_SEC.store(bytes_per_sec, Ordering::Relaxed);
+    // Debt run up at the old rate doesn't carry over
+    *BUCKET.lock().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+// Takes `bytes` from the bucket, sleeping off any shortfall at the current rate
+pub fn consume(bytes: u64) {
+    let rate = throttle();
+    if rate == 0 || bytes == 0 {
+        return;
+    }
+    let wait = {
+        let mut bucket = BUCKET.lock().unwrap_or_else(|e| e.into_inner());
+        let now = Instant::now();
+        let bucket = bucket.get_or_insert(Bucket { tokens: 0.0, refilled: now });
+        let refill = now.duration_since(bucket.refilled).as_secs_f64() * rate as f64;
+        bucket.tokens = (bucket.tokens + refill).min(rate as f64 * BURST_SECONDS) - bytes as f64;
+        bucket.refilled = now;
+        (-bucket.tokens).max(0.0) / rate as f64
+    };
+    let deadline = Instant::now() + Duration::from_secs_f64(wait);
+    while let Some(left) = deadline.checked_duration_since(Instant::now()).filter(|left| !left.is_zero()) {
+        if throttle() == 0 {
+            return;
+        }
+        std::thread::sleep(left.min(MAX_WAIT_SLICE));
+    }
+}
+
+// Lowers the calling thread's I/O class until dropped. Blocking-pool threads are
+// reused, so the previous class is put back.
+pub struct ThreadPriority {
+    #[cfg(target_os = "linux")]
+    previous: Option<libc::c_long>,
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    // From linux/ioprio.h
+    const IOPRIO_WHO_PROCESS: libc::c_long = 1;
+    const IOPRIO_CLASS_IDLE: libc::c_long = 3;
+    const IOPRIO_CLASS_SHIFT: libc::c_long = 13;
+
+    // With who = 0 these apply to the calling thread only
+    pub fn get() -> Option<libc::c_long> {
+        let priority = unsafe { libc::syscall(libc::SYS_ioprio_get, IOPRIO_WHO_PROCESS, 0) };
+        (priority >= 0).then_some(priority)
+    }
+
+    pub fn set(priority: libc::c_long) -> bool {
+        unsafe { libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, 0, priority) == 0 }
+    }
+
+    pub fn idle() -> libc::c_long {
+        IOPRIO_CLASS_IDLE << IOPRIO_CLASS_SHIFT
+    }
+}
+
+impl ThreadPriority {
+    pub fn enter(priority: IoPriority) -> Self {
+        #[cfg(target_os = "linux")]
+        {
+            let previous = (priority == IoPriority::Low)
+                .then(linux::get)
+                .flatten()
+                .filter(|_| linux::set(linux::idle()));
+            Self { previous }
+        }
+        #[cfg(not(target_os = "linux"))]
+        {
+            let _ = priority;
+            Self {}
+        }
+    }
+}
+
+impl Drop for ThreadPriority {
+    fn drop(&mut self) {
+        #[cfg(target_os = "linux")]
+        if let Some(previous) = self.previous {
+            linux::set(previous);
+        }
+    }
+}
+
+// A file opened for a scan: low-priority reads are hinted to the OS where it has
+// per-handle hints and always pass through the throttle
+pub struct ScanReader {
+    file: File,
+    throttled: bool,
+}
+
+pub fn open(path: &Path, priority: IoPriority) -> io::Result<ScanReader> {
+    let file = File::open(path)?;
+    let throttled = priority == IoPriority::Low;
+    #[cfg(windows)]
+    if throttled {
+        set_low_priority_hint(&file);
+    }
+    Ok(ScanReader { file, throttled })
+}
+
+#[cfg(windows)]
+fn set_low_priority_hint(file: &File) {
+    use std::os::windows::io::AsRawHandle;
+    use windows_sys::Win32::Storage::FileSystem::{
+        FileIoPriorityHintInfo, IoPriorityHintLow, SetFileInformationByHandle, FILE_IO_PRIORITY_HINT_INFO,
+    };
+    let hint = FILE_IO_PRIORITY_HINT_INFO { PriorityHint: IoPriorityHintLow };
+    // Best effort: some file systems (network shares) don't take the hint
+    unsafe {
+        SetFileInformationByHandle(
+            file.as_raw_handle(),
+            FileIoPriorityHintInfo,
+            &hint as *const _ as *const std::ffi::c_void,
+            std::mem::size_of::<FILE_IO_PRIORITY_HINT_INFO>() as u32,
+        );
+    }
+}
+
+impl Read for ScanReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.file.read(buf)?;
+        if self.throttled {
+            consume(read as u64);
+        }
+        Ok(read)
+    }
+}
+
+// Lets other work at the disk between files of a low-priority scan
+pub fn between_files(priority: IoPriority) {
+    if priority == IoPriority::Low {
+        std::thread::yield_now();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_dir;
+
+    #[test]
+    fn throttled_reads_stay_near_the_rate() {
+        let dir = temp_dir("io-priority");
+        let path = dir.join("data");
+        std::fs::write(&path, vec![7u8; 3 * 1024 * 1024]).unwrap();
+        set_throttle(4 * 1024 * 1024);
+        let started = Instant::now();
+        let mut read = Vec::new();
+        open(&path, IoPriority::Low).unwrap().read_to_end(&mut read).unwrap();
+        let elapsed = started.elapsed().as_secs_f64();
+        // 3 MiB at 4 MiB/s with a 1 MiB burst allowance takes at least half a second
+        assert!(elapsed >= 0.45, "read took {}s", elapsed);
+        assert!(elapsed < 2.0, "read took {}s", elapsed);
+
+        set_throttle(0);
+        let started = Instant::now();
+        open(&path, IoPriority::Low).unwrap().read_to_end(&mut Vec::new()).unwrap();
+        assert!(started.elapsed() < Duration::from_millis(400));
+        set_throttle(DEFAULT_THROTTLE_BYTES_PER_SEC);
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(read.len(), 3 * 1024 * 1024);
+    }
+}
@@ -0,0 +1,162 @@
+// Record of the moves made by each organization batch, one JSON line per move in
+// app_data_dir/journal/<batch_id>.jsonl. A line is appended as soon as its move is done,
+// so an interrupted batch still has a record of everything that happened.
+use crate::error::AppError;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tracing::warn;
+
+const JOURNAL_DIR: &str = "journal";
+const MAX_BATCH_ID_LEN: usize = 128;
+
+// Concurrent moves append to the same file
+static APPEND_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub from: String,
+    pub to: String,
+    pub size: u64,
+    // SHA-256 of the source taken just before the move, when hash capture was on
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    // Unix time in milliseconds
+    pub moved_at: u64,
+    // Set when the source was moved to the trash because this identical file was
+    // already at the destination; undoing moves it back from `to` like any other entry
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duplicate_of: Option<String>,
+    // Set when the file was put into the .tar.gz at `to` under this name rather than
+    // moved there
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub archive_member: Option<String>,
+}
+
+pub fn now_ms() -> u64 {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |d| d.as_millis() as u64)
+}
+
+// Size (and optionally hash) of a file about to be moved
+pub struct Snapshot {
+    pub size: u64,
+    pub sha256: Option<String>,
+}
+
+pub fn snapshot(path: &Path, capture_hash: bool) -> Snapshot {
+    let size = std::fs::symlink_metadata(path).map(|m| m.len()).unwrap_or(0);
+    let sha256 = capture_hash
+        .then(|| crate::sha256_file(path))
+        .and_then(|hash| hash.map_err(|e| warn!("Not recording a hash for {}: {}", path.display(), e)).ok());
+    Snapshot { size, sha256 }
+}
+
+// Batch ids name files, so they're limited to letters, digits, '-' and '_'
+pub fn validate_batch_id(batch_id: &str) -> Result<(), AppError> {
+    let valid = (1..=MAX_BATCH_ID_LEN).contains(&batch_id.len())
+        && batch_id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(AppError::invalid_input(format!("Invalid batch id \"{}\"", batch_id)))
+    }
+}
+
+fn journal_path(app_data_dir: &Path, batch_id: &str) -> PathBuf {
+    app_data_dir.join(JOURNAL_DIR).join(format!("{}.jsonl", batch_id))
+}
+
+pub fn append(app_data_dir: &Path, batch_id: &str, entry: &JournalEntry) -> Result<(), String> {
+    let path = journal_path(app_data_dir, batch_id);
+    let mut line = serde_json::to_string(entry).map_err(|e| format!("Failed to serialize journal entry: {}", e))?;
+    line.push('\n');
+    let _guard = APPEND_LOCK.lock().unwrap();
+    std::fs::create_dir_all(app_data_dir.join(JOURNAL_DIR))
+        .map_err(|e| format!("Failed to create journal directory: {}", e))?;
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .and_then(|mut file| file.write_all(line.as_bytes()))
+        .map_err(|e| format!("Failed to write journal {}: {}", path.display(), e))
+}
+
+// A line cut short by a crash is logged and skipped
+pub fn load(app_data_dir: &Path, batch_id: &str) -> Result<Vec<JournalEntry>, AppError> {
+    validate_batch_id(batch_id)?;
+    let path = journal_path(app_data_dir, batch_id);
+    let text = std::fs::read_to_string(&path).map_err(|e| {
+        AppError::from_io(&e, format!("No journal for batch {}: {}", batch_id, e))
+    })?;
+    Ok(text
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| {
+            serde_json::from_str(line)
+                .map_err(|e| warn!("Skipping unreadable journal line in {}: {}", path.display(), e))
+                .ok()
+        })
+        .collect())
+}
+
+// Rewrites a batch's journal with just `entries`, removing it when there are none left,
+// once the rest have been undone
+pub fn replace(app_data_dir: &Path, batch_id: &str, entries: &[JournalEntry]) -> Result<(), AppError> {
+    validate_batch_id(batch_id)?;
+    let path = journal_path(app_data_dir, batch_id);
+    let _guard = APPEND_LOCK.lock().unwrap();
+    let result = if entries.is_empty() {
+        std::fs::remove_file(&path)
+    } else {
+        let mut text = String::new();
+        for entry in entries {
+            let line = serde_json::to_string(entry)
+                .map_err(|e| AppError::io(format!("Failed to serialize journal entry: {}", e)))?;
+            text.push_str(&line);
+            text.push('\n');
+        }
+        let temp = path.with_extension("jsonl.tmp");
+        std::fs::write(&temp, text).and_then(|()| std::fs::rename(&temp, &path))
+    };
+    result.map_err(|e| AppError::from_io(&e, format!("Failed to rewrite journal {}: {}", path.display(), e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_dir;
+    use crate::error::AppErrorKind;
+    use std::fs;
+
+    #[test]
+    fn entries_are_appended_and_truncated_lines_skipped() {
+        let dir = temp_dir("append");
+        let entry = JournalEntry {
+            from: "/a".into(),
+            to: "/b".into(),
+            size: 3,
+            sha256: None,
+            moved_at: 1,
+            duplicate_of: None,
+            archive_member: None,
+        };
+        append(&dir, "batch-1", &entry).unwrap();
+        append(&dir, "batch-1", &JournalEntry { to: "/c".into(), ..entry.clone() }).unwrap();
+        let path = journal_path(&dir, "batch-1");
+        let mut file = fs::OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"{\"from\":\"/d\",\"to\"").unwrap();
+
+        let entries = load(&dir, "batch-1").unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0], entry);
+        assert_eq!(load(&dir, "missing").unwrap_err().kind, AppErrorKind::NotFound);
+        assert_eq!(load(&dir, "../escape").unwrap_err().kind, AppErrorKind::InvalidInput);
+
+        replace(&dir, "batch-1", &entries[1..]).unwrap();
+        assert_eq!(load(&dir, "batch-1").unwrap(), &entries[1..]);
+        replace(&dir, "batch-1", &[]).unwrap();
+        assert_eq!(load(&dir, "batch-1").unwrap_err().kind, AppErrorKind::NotFound);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
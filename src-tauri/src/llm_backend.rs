@@ -0,0 +1,690 @@
+// LLM backends that file classification can be routed through. Each backend turns a
+// provider-neutral ClassifyRequest into the request shape its server expects and
+// reports what it can do, so callers don't need to know which server they talk to.
+
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+const CLASSIFY_TIMEOUT: Duration = Duration::from_secs(120);
+const HEALTH_TIMEOUT: Duration = Duration::from_secs(5);
+const DEFAULT_MAX_TOKENS: u32 = 4096;
+
+#[derive(Debug, Clone)]
+pub enum BackendError {
+    // Connection failures, timeouts and 5xx responses; another backend may succeed
+    Unavailable(String),
+    // 4xx responses and unusable output; another backend won't fix the request
+    Rejected(String),
+    // The server refused the prompt as too long for its context window. Counts are
+    // whatever the server's message gave away.
+    ContextOverflow {
+        message: String,
+        prompt_tokens: Option<u64>,
+        context_tokens: Option<u64>,
+    },
+}
+
+impl BackendError {
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, BackendError::Unavailable(_))
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            BackendError::Unavailable(_) => "unavailable",
+            BackendError::Rejected(_) => "rejected",
+            BackendError::ContextOverflow { .. } => "context_overflow",
+        }
+    }
+}
+
+impl std::fmt::Display for BackendError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BackendError::Unavailable(message)
+            | BackendError::Rejected(message)
+            | BackendError::ContextOverflow { message, .. } => f.write_str(message),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ClassifyRequest {
+    pub system_message: String,
+    pub prompt: String,
+    pub image_base64: Option<String>,
+    pub mime_type: Option<String>,
+    pub max_tokens: Option<u32>,
+    // The file whose content this is, for the audit log
+    #[serde(default)]
+    pub source_path: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ClassifyResponse {
+    pub content: String,
+    pub backend: String,
+    // False when an image was supplied but the backend can't take images
+    pub image_sent: bool,
+    // True when a backend further down the fallback chain served the request
+    pub fell_back: bool,
+    pub usage: TokenUsage,
+    // Placeholders put into the prompt before it was sent, per type (see redaction)
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub redactions: BTreeMap<String, usize>,
+}
+
+// Token counts and timings, as far as the server reported them
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct TokenUsage {
+    pub prompt_tokens: Option<u64>,
+    pub completion_tokens: Option<u64>,
+    // Prompt processing and generation time, from servers that time them separately
+    pub prompt_ms: Option<f64>,
+    pub generation_ms: Option<f64>,
+}
+
+// Reads usage from any of the response layouts we talk to: OpenAI-style `usage`
+// (plus llama.cpp's `timings`), Anthropic's input/output tokens, Ollama's eval counts
+// and nanosecond durations, and Gemini's usageMetadata
+pub fn usage_of(data: &Value) -> TokenUsage {
+    let number = |pointers: &[&str]| pointers.iter().find_map(|p| data.pointer(p).and_then(Value::as_u64));
+    let millis = |pointer: &str, nanos: &str| {
+        data.pointer(pointer)
+            .and_then(Value::as_f64)
+            .or_else(|| data.pointer(nanos).and_then(Value::as_f64).map(|ns| ns / 1_000_000.0))
+    };
+    TokenUsage {
+        prompt_tokens: number(&[
+            "/usage/prompt_tokens",
+            "/usage/input_tokens",
+            "/prompt_eval_count",
+            "/usageMetadata/promptTokenCount",
+        ]),
+        completion_tokens: number(&[
+            "/usage/completion_tokens",
+            "/usage/output_tokens",
+            "/eval_count",
+            "/usageMetadata/candidatesTokenCount",
+        ]),
+        prompt_ms: millis("/timings/prompt_ms", "/prompt_eval_duration"),
+        generation_ms: millis("/timings/predicted_ms", "/eval_duration"),
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BackendCapabilities {
+    pub vision: bool,
+    pub json_mode: bool,
+    pub max_context: Option<u64>,
+}
+
+// Backend selection as stored in app state and sent by the frontend
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum BackendConfig {
+    // The managed llama/mlx server started by start_llm_server
+    Managed {
+        #[serde(default)]
+        model: Option<String>,
+    },
+    // Any remote or local HTTP endpoint (openai, groq, lmstudio, ollama, anthropic, gemini, custom)
+    Remote {
+        provider: String,
+        base_url: String,
+        #[serde(default)]
+        api_key: Option<String>,
+        model: String,
+        #[serde(default)]
+        headers: HashMap<String, String>,
+        #[serde(default)]
+        supports_vision: bool,
+        #[serde(default)]
+        max_context: Option<u64>,
+    },
+}
+
+impl BackendConfig {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            BackendConfig::Managed { .. } => "managed",
+            BackendConfig::Remote { .. } => "remote",
+        }
+    }
+
+    // The managed server, or an HTTP endpoint on this machine (Ollama, LM Studio)
+    pub fn is_local(&self) -> bool {
+        match self {
+            BackendConfig::Managed { .. } => true,
+            BackendConfig::Remote { base_url, .. } => reqwest::Url::parse(base_url.trim()).is_ok_and(|url| {
+                url.host_str().is_some_and(|host| {
+                    let host = host.trim_start_matches('[').trim_end_matches(']');
+                    host.eq_ignore_ascii_case("localhost")
+                        || host.parse::<std::net::IpAddr>().is_ok_and(|ip| ip.is_loopback())
+                })
+            }),
+        }
+    }
+}
+
+pub trait LlmBackend {
+    fn name(&self) -> String;
+    fn capabilities(&self) -> BackendCapabilities;
+    async fn health(&self) -> Result<(), String>;
+    async fn classify(&self, request: &ClassifyRequest) -> Result<ClassifyResponse, BackendError>;
+}
+
+pub struct ManagedServerBackend {
+    pub host: String,
+    pub port: u16,
+    pub model: Option<String>,
+    pub vision: bool,
+    pub max_context: Option<u64>,
+}
+
+pub struct RemoteBackend {
+    pub provider: String,
+    pub base_url: String,
+    pub api_key: Option<String>,
+    pub model: String,
+    pub headers: HashMap<String, String>,
+    pub supports_vision: bool,
+    pub max_context: Option<u64>,
+}
+
+// Runtime-selected backend; dispatches to the concrete implementation
+pub enum Backend {
+    Managed(ManagedServerBackend),
+    Remote(RemoteBackend),
+}
+
+impl LlmBackend for Backend {
+    fn name(&self) -> String {
+        match self {
+            Backend::Managed(b) => b.name(),
+            Backend::Remote(b) => b.name(),
+        }
+    }
+
+    fn capabilities(&self) -> BackendCapabilities {
+        match self {
+            Backend::Managed(b) => b.capabilities(),
+            Backend::Remote(b) => b.capabilities(),
+        }
+    }
+
+    async fn health(&self) -> Result<(), String> {
+        match self {
+            Backend::Managed(b) => b.health().await,
+            Backend::Remote(b) => b.health().await,
+        }
+    }
+
+    async fn classify(&self, request: &ClassifyRequest) -> Result<ClassifyResponse, BackendError> {
+        match self {
+            Backend::Managed(b) => b.classify(request).await,
+            Backend::Remote(b) => b.classify(request).await,
+        }
+    }
+}
+
+// Returns the image to send, if any, given what the backend supports
+fn image_for(request: &ClassifyRequest, capabilities: &BackendCapabilities) -> Option<(String, String)> {
+    if !capabilities.vision {
+        return None;
+    }
+    match (&request.image_base64, &request.mime_type) {
+        (Some(data), Some(mime)) => Some((data.clone(), mime.clone())),
+        _ => None,
+    }
+}
+
+fn openai_messages(request: &ClassifyRequest, image: Option<&(String, String)>) -> Value {
+    let user_content = match image {
+        Some((data, mime)) => json!([
+            { "type": "image_url", "image_url": { "url": format!("data:{};base64,{}", mime, data) } },
+            { "type": "text", "text": request.prompt },
+        ]),
+        None => json!(request.prompt),
+    };
+    json!([
+        { "role": "system", "content": request.system_message },
+        { "role": "user", "content": user_content },
+    ])
+}
+
+async fn post_json(
+    url: &str,
+    headers: &HashMap<String, String>,
+    body: &Value,
+) -> Result<Value, BackendError> {
+    let client = crate::network::build_http_client();
+    let mut request = client.post(url).timeout(CLASSIFY_TIMEOUT).json(body);
+    for (key, value) in headers {
+        request = request.header(key, value);
+    }
+
+    let response = request.send().await
+        .map_err(|e| BackendError::Unavailable(format!("Request failed: {}", e)))?;
+    let status = response.status();
+    let text = response.text().await
+        .map_err(|e| BackendError::Unavailable(format!("Failed to read response: {}", e)))?;
+    // Some servers answer an oversized prompt with a 500, so this is checked first
+    if !status.is_success() {
+        if let Some((prompt_tokens, context_tokens)) = context_overflow(&text) {
+            let message = format!("HTTP {}: {}", status.as_u16(), text);
+            return Err(BackendError::ContextOverflow { message, prompt_tokens, context_tokens });
+        }
+    }
+    if status.is_server_error() {
+        return Err(BackendError::Unavailable(format!("HTTP {}: {}", status.as_u16(), text)));
+    }
+    if !status.is_success() {
+        return Err(BackendError::Rejected(format!("HTTP {}: {}", status.as_u16(), text)));
+    }
+    serde_json::from_str(&text)
+        .map_err(|e| BackendError::Rejected(format!("Failed to parse response: {}", e)))
+}
+
+// The first number after `marker`, skipping spaces and an opening parenthesis
+fn number_after(text: &str, marker: &str) -> Option<u64> {
+    let rest = &text[text.find(marker)? + marker.len()..];
+    let rest = rest.trim_start_matches([' ', '(']);
+    let digits = rest.split(|c: char| !c.is_ascii_digit()).next()?;
+    digits.parse().ok()
+}
+
+// Recognizes "prompt too long" errors from the servers we talk to and pulls out
+// (prompt tokens, context size) where the message has them
+fn context_overflow(body: &str) -> Option<(Option<u64>, Option<u64>)> {
+    let text = body.to_lowercase();
+    if text.contains("exceed context window") {
+        // llama-cpp-python (the managed server)
+        Some((number_after(&text, "requested tokens"), number_after(&text, "context window of")))
+    } else if text.contains("maximum context length") {
+        // OpenAI and compatible servers
+        Some((number_after(&text, "resulted in"), number_after(&text, "maximum context length is")))
+    } else if text.contains("exceed_context_size") || text.contains("exceeds the available context size") {
+        // llama.cpp's server, which puts the counts in the error object
+        let error = serde_json::from_str::<Value>(body).ok().and_then(|v| v.get("error").cloned());
+        let count = |key: &str| error.as_ref().and_then(|e| e.get(key)).and_then(Value::as_u64);
+        Some((count("n_prompt_tokens"), count("n_ctx")))
+    } else if text.contains("prompt is too long") {
+        // Anthropic
+        Some((number_after(&text, "prompt is too long:"), number_after(&text, "tokens >")))
+    } else {
+        None
+    }
+}
+
+fn text_at(value: &Value, pointer: &str) -> String {
+    value.pointer(pointer).and_then(Value::as_str).unwrap_or_default().to_string()
+}
+
+impl LlmBackend for ManagedServerBackend {
+    fn name(&self) -> String {
+        format!("managed ({}:{})", self.host, self.port)
+    }
+
+    fn capabilities(&self) -> BackendCapabilities {
+        // The managed servers don't forward response_format, so no JSON mode
+        BackendCapabilities {
+            vision: self.vision,
+            json_mode: false,
+            max_context: self.max_context,
+        }
+    }
+
+    async fn health(&self) -> Result<(), String> {
+        let url = format!("http://{}:{}/v1/models", self.host, self.port);
+        let response = crate::network::build_http_client().get(&url).timeout(HEALTH_TIMEOUT).send().await
+            .map_err(|e| format!("Managed LLM server is not reachable: {}", e))?;
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(format!("Managed LLM server responded with status {}", response.status()))
+        }
+    }
+
+    async fn classify(&self, request: &ClassifyRequest) -> Result<ClassifyResponse, BackendError> {
+        let image = image_for(request, &self.capabilities());
+        let mut body = json!({
+            "messages": openai_messages(request, image.as_ref()),
+            "temperature": 0.2,
+            "max_tokens": request.max_tokens.unwrap_or(DEFAULT_MAX_TOKENS),
+            "stream": false,
+        });
+        if let Some(model) = &self.model {
+            body["model"] = json!(model);
+        }
+
+        let url = format!("http://{}:{}/v1/chat/completions", self.host, self.port);
+        let data = post_json(&url, &HashMap::new(), &body).await?;
+        Ok(ClassifyResponse {
+            content: text_at(&data, "/choices/0/message/content"),
+            backend: self.name(),
+            image_sent: image.is_some(),
+            fell_back: false,
+            usage: usage_of(&data),
+            redactions: BTreeMap::new(),
+        })
+    }
+}
+
+impl RemoteBackend {
+    fn endpoint(&self) -> String {
+        let base = self.base_url.trim_end_matches('/');
+        match self.provider.as_str() {
+            "ollama" => format!("{}/api/chat", base),
+            "anthropic" => format!("{}/v1/messages", base),
+            "gemini" => format!("{}/v1beta/models/{}:generateContent", base, self.model),
+            _ => format!("{}/v1/chat/completions", base),
+        }
+    }
+
+    fn request_headers(&self) -> HashMap<String, String> {
+        let mut headers = HashMap::new();
+        if let Some(api_key) = &self.api_key {
+            match self.provider.as_str() {
+                "anthropic" => {
+                    headers.insert("x-api-key".to_string(), api_key.clone());
+                    headers.insert("anthropic-version".to_string(), "2023-06-01".to_string());
+                }
+                // A header rather than ?key=, which would end up in proxy and server logs
+                "gemini" => {
+                    headers.insert("x-goog-api-key".to_string(), api_key.clone());
+                }
+                _ => {
+                    headers.insert("Authorization".to_string(), format!("Bearer {}", api_key));
+                }
+            }
+        }
+        // Custom headers win over the defaults
+        headers.extend(self.headers.clone());
+        headers
+    }
+
+    fn request_body(&self, request: &ClassifyRequest, image: Option<&(String, String)>) -> Value {
+        let max_tokens = request.max_tokens.unwrap_or(DEFAULT_MAX_TOKENS);
+        let json_mode = self.capabilities().json_mode;
+        match self.provider.as_str() {
+            "ollama" => {
+                let mut user = json!({ "role": "user", "content": request.prompt });
+                if let Some((data, _)) = image {
+                    user["images"] = json!([data]);
+                }
+                let mut body = json!({
+                    "model": self.model,
+                    "messages": [{ "role": "system", "content": request.system_message }, user],
+                    "stream": false,
+                });
+                if json_mode {
+                    body["format"] = json!("json");
+                }
+                body
+            }
+            "anthropic" => {
+                let mut content = Vec::new();
+                if let Some((data, mime)) = image {
+                    content.push(json!({
+                        "type": "image",
+                        "source": { "type": "base64", "media_type": mime, "data": data },
+                    }));
+                }
+                content.push(json!({ "type": "text", "text": request.prompt }));
+                json!({
+                    "model": self.model,
+                    "max_tokens": max_tokens,
+                    "system": request.system_message,
+                    "messages": [{ "role": "user", "content": content }],
+                })
+            }
+            "gemini" => {
+                let mut parts = Vec::new();
+                if let Some((data, mime)) = image {
+                    parts.push(json!({ "inline_data": { "mime_type": mime, "data": data } }));
+                }
+                parts.push(json!({ "text": format!("{}\n\n{}", request.system_message, request.prompt) }));
+                let mut generation_config = json!({ "temperature": 0.2, "maxOutputTokens": max_tokens });
+                if json_mode {
+                    generation_config["responseMimeType"] = json!("application/json");
+                }
+                json!({ "contents": [{ "parts": parts }], "generationConfig": generation_config })
+            }
+            _ => {
+                let mut body = json!({
+                    "model": self.model,
+                    "messages": openai_messages(request, image),
+                    "temperature": 0.2,
+                    "max_tokens": max_tokens,
+                    "stream": false,
+                });
+                if json_mode {
+                    body["response_format"] = json!({ "type": "json_object" });
+                }
+                body
+            }
+        }
+    }
+
+    fn response_content(&self, data: &Value) -> String {
+        match self.provider.as_str() {
+            "ollama" => text_at(data, "/message/content"),
+            "anthropic" => text_at(data, "/content/0/text"),
+            "gemini" => text_at(data, "/candidates/0/content/parts/0/text"),
+            _ => text_at(data, "/choices/0/message/content"),
+        }
+    }
+}
+
+impl LlmBackend for RemoteBackend {
+    fn name(&self) -> String {
+        format!("{} ({})", self.provider, self.base_url)
+    }
+
+    fn capabilities(&self) -> BackendCapabilities {
+        BackendCapabilities {
+            vision: self.supports_vision,
+            // Only providers known to honour a JSON output switch
+            json_mode: matches!(self.provider.as_str(), "openai" | "groq" | "ollama" | "gemini"),
+            max_context: self.max_context,
+        }
+    }
+
+    async fn health(&self) -> Result<(), String> {
+        let base = self.base_url.trim_end_matches('/');
+        let url = match self.provider.as_str() {
+            "ollama" => format!("{}/api/tags", base),
+            "gemini" => format!("{}/v1beta/models", base),
+            _ => format!("{}/v1/models", base),
+        };
+        let mut request = crate::network::build_http_client().get(&url).timeout(HEALTH_TIMEOUT);
+        for (key, value) in self.request_headers() {
+            request = request.header(key, value);
+        }
+        let response = request.send().await
+            .map_err(|e| format!("{} is not reachable: {}", self.name(), e))?;
+        // Some providers don't expose a model list; any answer below 500 means the server is up
+        if response.status().is_server_error() {
+            Err(format!("{} responded with status {}", self.name(), response.status()))
+        } else {
+            Ok(())
+        }
+    }
+
+    async fn classify(&self, request: &ClassifyRequest) -> Result<ClassifyResponse, BackendError> {
+        let image = image_for(request, &self.capabilities());
+        let body = self.request_body(request, image.as_ref());
+        let data = post_json(&self.endpoint(), &self.request_headers(), &body).await?;
+        Ok(ClassifyResponse {
+            content: self.response_content(&data),
+            backend: self.name(),
+            image_sent: image.is_some(),
+            fell_back: false,
+            usage: usage_of(&data),
+            redactions: BTreeMap::new(),
+        })
+    }
+}
+
+fn default_backend_timeout() -> u64 {
+    120
+}
+
+fn default_failure_threshold() -> u32 {
+    3
+}
+
+fn default_cooldown_minutes() -> u64 {
+    5
+}
+
+// Backends tried after the selected one when it is unreachable, and how eagerly a
+// failing backend is skipped
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FallbackSettings {
+    #[serde(default)]
+    pub chain: Vec<BackendConfig>,
+    #[serde(default = "default_backend_timeout")]
+    pub timeout_secs: u64,
+    // Consecutive failures before a backend is skipped
+    #[serde(default = "default_failure_threshold")]
+    pub failure_threshold: u32,
+    // How long a backend is skipped once the threshold is reached
+    #[serde(default = "default_cooldown_minutes")]
+    pub cooldown_minutes: u64,
+}
+
+impl Default for FallbackSettings {
+    fn default() -> Self {
+        FallbackSettings {
+            chain: Vec::new(),
+            timeout_secs: default_backend_timeout(),
+            failure_threshold: default_failure_threshold(),
+            cooldown_minutes: default_cooldown_minutes(),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct CircuitBreaker {
+    consecutive_failures: u32,
+    open_until: Option<Instant>,
+}
+
+impl CircuitBreaker {
+    pub fn is_open(&self, now: Instant) -> bool {
+        self.open_until.is_some_and(|until| now < until)
+    }
+
+    pub fn record_success(&mut self) {
+        *self = CircuitBreaker::default();
+    }
+
+    pub fn record_failure(&mut self, now: Instant, settings: &FallbackSettings) {
+        self.consecutive_failures += 1;
+        if self.consecutive_failures >= settings.failure_threshold {
+            // After the cooldown the backend gets one more try before tripping again
+            self.open_until = Some(now + Duration::from_secs(settings.cooldown_minutes * 60));
+            self.consecutive_failures = 0;
+        }
+    }
+}
+
+// Fallback settings plus a circuit breaker per backend, keyed by backend name
+#[derive(Debug, Default)]
+pub struct FallbackState {
+    pub settings: FallbackSettings,
+    pub breakers: HashMap<String, CircuitBreaker>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn usage_is_read_from_each_response_layout() {
+        let llama_cpp = json!({
+            "usage": { "prompt_tokens": 812, "completion_tokens": 40 },
+            "timings": { "prompt_ms": 420.5, "predicted_ms": 1600.0 },
+        });
+        let ollama = json!({
+            "prompt_eval_count": 812,
+            "eval_count": 40,
+            "prompt_eval_duration": 420_500_000u64,
+            "eval_duration": 1_600_000_000u64,
+        });
+        for data in [llama_cpp, ollama] {
+            let usage = usage_of(&data);
+            assert_eq!((usage.prompt_tokens, usage.completion_tokens), (Some(812), Some(40)));
+            assert_eq!((usage.prompt_ms, usage.generation_ms), (Some(420.5), Some(1600.0)));
+        }
+        let anthropic = usage_of(&json!({ "usage": { "input_tokens": 90, "output_tokens": 12 } }));
+        assert_eq!((anthropic.prompt_tokens, anthropic.completion_tokens), (Some(90), Some(12)));
+        assert_eq!(anthropic.prompt_ms, None);
+        assert_eq!(usage_of(&json!({ "choices": [] })), TokenUsage::default());
+    }
+
+    #[test]
+    fn context_overflow_messages_yield_their_counts() {
+        let cases = [
+            (
+                r#"{"error":{"message":"Requested tokens (5210) exceed context window of 4096"}}"#,
+                Some((Some(5210), Some(4096))),
+            ),
+            (
+                "This model's maximum context length is 8192 tokens. However, your messages resulted in 9120 tokens.",
+                Some((Some(9120), Some(8192))),
+            ),
+            (
+                r#"{"error":{"code":400,"type":"exceed_context_size_error","n_prompt_tokens":3000,"n_ctx":2048}}"#,
+                Some((Some(3000), Some(2048))),
+            ),
+            ("prompt is too long: 210000 tokens > 200000 maximum", Some((Some(210000), Some(200000)))),
+            (r#"{"error":"model not found"}"#, None),
+        ];
+        for (body, expected) in cases {
+            assert_eq!(context_overflow(body), expected, "{}", body);
+        }
+    }
+
+    #[test]
+    fn gemini_keys_go_in_a_header_not_the_url() {
+        let gemini = RemoteBackend {
+            provider: "gemini".into(),
+            base_url: "https://generativelanguage.googleapis.com/".into(),
+            api_key: Some("secret-key".into()),
+            model: "gemini-3-flash".into(),
+            headers: HashMap::new(),
+            supports_vision: false,
+            max_context: None,
+        };
+        assert_eq!(
+            gemini.endpoint(),
+            "https://generativelanguage.googleapis.com/v1beta/models/gemini-3-flash:generateContent"
+        );
+        assert_eq!(gemini.request_headers().get("x-goog-api-key").map(String::as_str), Some("secret-key"));
+    }
+
+    #[test]
+    fn loopback_endpoints_count_as_local() {
+        let remote = |base_url: &str| BackendConfig::Remote {
+            provider: "custom".into(),
+            base_url: base_url.into(),
+            api_key: None,
+            model: "m".into(),
+            headers: HashMap::new(),
+            supports_vision: false,
+            max_context: None,
+        };
+        assert!(BackendConfig::Managed { model: None }.is_local());
+        for local in ["http://localhost:11434", "http://127.0.0.1:1234/v1", "http://[::1]:8080"] {
+            assert!(remote(local).is_local(), "{}", local);
+        }
+        for off_machine in ["https://api.openai.com/v1", "http://192.168.1.5:11434", "localhost:11434"] {
+            assert!(!remote(off_machine).is_local(), "{}", off_machine);
+        }
+    }
+}
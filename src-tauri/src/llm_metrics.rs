@@ -0,0 +1,161 @@
+// Per-request LLM metrics and aggregates since startup (or the last reset), so users
+// can tell whether the model or content extraction is the slow part and compare
+// CPU and GPU settings by generation speed.
+use crate::llm_backend::TokenUsage;
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+
+// Individual records kept for the "recent" list
+pub const RECENT_RECORDS: usize = 200;
+// Latencies kept for the percentiles; older ones drop out
+const LATENCY_WINDOW: usize = 10_000;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RequestRecord {
+    // Unix seconds
+    pub at: u64,
+    pub backend: String,
+    // Time before this backend was asked: reloading an idle server, earlier backends
+    // in the fallback chain failing
+    pub wait_ms: u64,
+    pub latency_ms: u64,
+    #[serde(flatten)]
+    pub usage: TokenUsage,
+    // Generation speed from the server's own timing where it has one, else over the
+    // whole request
+    pub tokens_per_second: Option<f64>,
+    // BackendError kind when the request failed
+    pub failure: Option<String>,
+}
+
+impl RequestRecord {
+    pub fn new(at: u64, backend: String, wait_ms: u64, latency_ms: u64, usage: TokenUsage) -> Self {
+        let generation_ms = usage.generation_ms.unwrap_or(latency_ms as f64);
+        let tokens_per_second = usage
+            .completion_tokens
+            .filter(|_| generation_ms > 0.0)
+            .map(|tokens| tokens as f64 * 1000.0 / generation_ms);
+        RequestRecord { at, backend, wait_ms, latency_ms, usage, tokens_per_second, failure: None }
+    }
+
+    pub fn failed(mut self, kind: &str) -> Self {
+        self.failure = Some(kind.to_string());
+        self
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LlmMetricsSummary {
+    pub since: u64,
+    pub requests: u64,
+    pub failures: HashMap<String, u64>,
+    pub p50_latency_ms: Option<u64>,
+    pub p95_latency_ms: Option<u64>,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    // Completion tokens over generation time, across requests that reported both
+    pub generation_tokens_per_second: Option<f64>,
+    // Newest first
+    pub recent: Vec<RequestRecord>,
+}
+
+pub struct LlmMetrics {
+    since: u64,
+    requests: u64,
+    failures: HashMap<String, u64>,
+    latencies: VecDeque<u64>,
+    prompt_tokens: u64,
+    completion_tokens: u64,
+    timed_tokens: u64,
+    timed_ms: f64,
+    recent: VecDeque<RequestRecord>,
+}
+
+impl LlmMetrics {
+    pub fn new(now: u64) -> Self {
+        LlmMetrics {
+            since: now,
+            requests: 0,
+            failures: HashMap::new(),
+            latencies: VecDeque::new(),
+            prompt_tokens: 0,
+            completion_tokens: 0,
+            timed_tokens: 0,
+            timed_ms: 0.0,
+            recent: VecDeque::new(),
+        }
+    }
+
+    pub fn record(&mut self, record: RequestRecord) {
+        self.requests += 1;
+        if let Some(kind) = &record.failure {
+            *self.failures.entry(kind.clone()).or_default() += 1;
+        } else {
+            if self.latencies.len() == LATENCY_WINDOW {
+                self.latencies.pop_front();
+            }
+            self.latencies.push_back(record.latency_ms);
+        }
+        self.prompt_tokens += record.usage.prompt_tokens.unwrap_or(0);
+        self.completion_tokens += record.usage.completion_tokens.unwrap_or(0);
+        if let (Some(tokens), Some(ms)) = (record.usage.completion_tokens, record.usage.generation_ms) {
+            self.timed_tokens += tokens;
+            self.timed_ms += ms;
+        }
+        if self.recent.len() == RECENT_RECORDS {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(record);
+    }
+
+    // `last` caps how many individual records come back
+    pub fn summary(&self, last: usize) -> LlmMetricsSummary {
+        let mut latencies: Vec<u64> = self.latencies.iter().copied().collect();
+        latencies.sort_unstable();
+        let percentile = |p: f64| -> Option<u64> {
+            let rank = ((latencies.len() as f64 * p).ceil() as usize).max(1);
+            latencies.get(rank - 1).copied()
+        };
+        LlmMetricsSummary {
+            since: self.since,
+            requests: self.requests,
+            failures: self.failures.clone(),
+            p50_latency_ms: percentile(0.5),
+            p95_latency_ms: percentile(0.95),
+            prompt_tokens: self.prompt_tokens,
+            completion_tokens: self.completion_tokens,
+            generation_tokens_per_second: (self.timed_ms > 0.0)
+                .then(|| self.timed_tokens as f64 * 1000.0 / self.timed_ms),
+            recent: self.recent.iter().rev().take(last).cloned().collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(latency_ms: u64, completion_tokens: Option<u64>, generation_ms: Option<f64>) -> RequestRecord {
+        let usage = TokenUsage { completion_tokens, generation_ms, ..Default::default() };
+        RequestRecord::new(0, "managed".into(), 0, latency_ms, usage)
+    }
+
+    #[test]
+    fn summary_reports_percentiles_speed_and_failures() {
+        let mut metrics = LlmMetrics::new(100);
+        for latency in 1..=100 {
+            metrics.record(record(latency * 10, Some(20), Some(500.0)));
+        }
+        metrics.record(record(5_000, None, None).failed("unavailable"));
+
+        let summary = metrics.summary(3);
+        assert_eq!(summary.requests, 101);
+        assert_eq!((summary.p50_latency_ms, summary.p95_latency_ms), (Some(500), Some(950)));
+        assert_eq!(summary.generation_tokens_per_second, Some(40.0));
+        assert_eq!(summary.failures["unavailable"], 1);
+        assert_eq!(summary.recent.len(), 3);
+        assert!(summary.recent[0].failure.is_some());
+        // Without server timings, speed is over the whole request
+        assert_eq!(record(2_000, Some(50), None).tokens_per_second, Some(25.0));
+    }
+}
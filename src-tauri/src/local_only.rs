@@ -0,0 +1,158 @@
+// Local-only mode (settings local_only): a guarantee that nothing read from files leaves
+// the machine. Requests carrying a payload may only go to hosts that resolve to
+// loopback addresses, and the LLM router only uses the managed server. Hostnames are
+// resolved rather than trusted by name, every address they resolve to must be
+// loopback, and the request is then pinned to those addresses so a second lookup
+// can't send it elsewhere. Redirects aren't followed, since they could point anywhere.
+use crate::error::{AppError, AppErrorKind};
+use crate::llm_backend::BackendConfig;
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+
+pub const RULE: &str = "local_only";
+
+pub fn enabled() -> bool {
+    crate::settings::with(|s| s.local_only)
+}
+
+pub fn violation(message: impl Into<String>) -> AppError {
+    AppError::new(AppErrorKind::PolicyViolation, message).with_rule(RULE)
+}
+
+// IPv4-mapped IPv6 addresses (::ffff:127.0.0.1) count as their IPv4 address
+pub fn is_loopback_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => ip.is_loopback(),
+        IpAddr::V6(ip) => ip.is_loopback() || ip.to_ipv4_mapped().is_some_and(|ip| ip.is_loopback()),
+    }
+}
+
+// The addresses `url`'s host resolves to when every one of them is loopback. `resolve`
+// is only asked about hostnames; IP literals are checked as they are.
+pub fn loopback_addresses(
+    url: &reqwest::Url,
+    resolve: impl FnOnce(&str, u16) -> std::io::Result<Vec<SocketAddr>>,
+) -> Result<Vec<SocketAddr>, String> {
+    let host = url.host_str().ok_or_else(|| format!("{} has no host", url))?;
+    let port = url.port_or_known_default().unwrap_or(80);
+    let literal = host.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>();
+    let addresses = match literal {
+        Ok(ip) => vec![SocketAddr::new(ip, port)],
+        Err(_) => resolve(host, port).map_err(|e| format!("Couldn't resolve {}: {}", host, e))?,
+    };
+    if addresses.is_empty() {
+        return Err(format!("{} doesn't resolve to any address", host));
+    }
+    match addresses.iter().find(|address| !is_loopback_ip(address.ip())) {
+        Some(public) => Err(format!("{} resolves to {}, which is not on this machine", host, public.ip())),
+        None => Ok(addresses),
+    }
+}
+
+pub fn resolve(host: &str, port: u16) -> std::io::Result<Vec<SocketAddr>> {
+    (host, port).to_socket_addrs().map(Iterator::collect)
+}
+
+// Client for a request carrying file-derived data. None when local-only mode is off (use
+// the shared client); otherwise a client that bypasses proxies and is pinned to the
+// loopback addresses checked here, or a PolicyViolation.
+pub async fn guard_request(url: &str, carries_payload: bool) -> Result<Option<reqwest::Client>, AppError> {
+    if !carries_payload || !enabled() {
+        return Ok(None);
+    }
+    let parsed = reqwest::Url::parse(url).map_err(|e| AppError::invalid_input(format!("Invalid URL {}: {}", url, e)))?;
+    let checked = parsed.clone();
+    let addresses = tokio::task::spawn_blocking(move || loopback_addresses(&checked, resolve))
+        .await
+        .map_err(|e| AppError::io(format!("Address check failed: {}", e)))?
+        .map_err(|reason| violation(format!("Local-only mode blocked a request to {}: {}", url, reason)))?;
+    pinned_client(parsed.host_str().unwrap_or_default(), &addresses).map(Some)
+}
+
+// Redirects aren't followed: a loopback server answering 307 or 308 with a public
+// Location would otherwise have the body sent on there
+fn pinned_client(host: &str, addresses: &[SocketAddr]) -> Result<reqwest::Client, AppError> {
+    reqwest::Client::builder()
+        .no_proxy()
+        .redirect(reqwest::redirect::Policy::none())
+        .resolve_to_addrs(host, addresses)
+        .build()
+        .map_err(|e| AppError::network(format!("Failed to create HTTP client: {}", e)))
+}
+
+// Only the managed server counts; even an endpoint on localhost is another program
+// that could forward what it's given
+pub fn allows_backend(backend: &BackendConfig) -> bool {
+    matches!(backend, BackendConfig::Managed { .. })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    fn url(text: &str) -> reqwest::Url {
+        reqwest::Url::parse(text).unwrap()
+    }
+
+    fn resolving_to(ips: &[IpAddr]) -> impl FnOnce(&str, u16) -> std::io::Result<Vec<SocketAddr>> + '_ {
+        move |_, port| Ok(ips.iter().map(|&ip| SocketAddr::new(ip, port)).collect())
+    }
+
+    fn never_resolved(_: &str, _: u16) -> std::io::Result<Vec<SocketAddr>> {
+        panic!("IP literals must not be resolved")
+    }
+
+    #[test]
+    fn ip_literals_are_checked_without_resolving() {
+        assert!(loopback_addresses(&url("http://127.0.0.1:8080/v1"), never_resolved).is_ok());
+        assert!(loopback_addresses(&url("http://127.8.9.10/"), never_resolved).is_ok());
+        assert!(loopback_addresses(&url("http://[::1]:11434/"), never_resolved).is_ok());
+        assert!(loopback_addresses(&url("http://[::ffff:127.0.0.1]/"), never_resolved).is_ok());
+        assert!(loopback_addresses(&url("http://10.0.0.2/"), never_resolved).is_err());
+        assert!(loopback_addresses(&url("https://[2001:db8::1]/"), never_resolved).is_err());
+        assert!(loopback_addresses(&url("http://0.0.0.0/"), never_resolved).is_err());
+    }
+
+    #[test]
+    fn hostnames_must_resolve_only_to_loopback() {
+        let loopback = [IpAddr::V4(Ipv4Addr::LOCALHOST), IpAddr::V6(Ipv6Addr::LOCALHOST)];
+        let addresses = loopback_addresses(&url("http://localhost:1234/"), resolving_to(&loopback)).unwrap();
+        assert_eq!(addresses, [SocketAddr::new(loopback[0], 1234), SocketAddr::new(loopback[1], 1234)]);
+
+        // "localhost" is only trusted for what it resolves to
+        let public = [IpAddr::V4(Ipv4Addr::new(93, 184, 216, 34))];
+        assert!(loopback_addresses(&url("http://localhost/"), resolving_to(&public)).is_err());
+
+        // One public address among loopback ones is enough to refuse
+        let mixed = [IpAddr::V4(Ipv4Addr::LOCALHOST), IpAddr::V4(Ipv4Addr::new(93, 184, 216, 34))];
+        let err = loopback_addresses(&url("https://sneaky.example/"), resolving_to(&mixed)).unwrap_err();
+        assert!(err.contains("93.184.216.34"), "{}", err);
+
+        assert!(loopback_addresses(&url("http://nowhere.invalid/"), resolving_to(&[])).is_err());
+        let failing = |_: &str, _: u16| Err(std::io::Error::other("no such host"));
+        assert!(loopback_addresses(&url("http://nowhere.invalid/"), failing).is_err());
+    }
+
+    #[test]
+    fn redirects_off_the_machine_are_not_followed() {
+        use std::io::{Read, Write};
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0u8; 4096];
+            let _ = stream.read(&mut request).unwrap();
+            let response = "HTTP/1.1 307 Temporary Redirect\r\nLocation: http://203.0.113.7/collect\r\n\
+                            Content-Length: 0\r\nConnection: close\r\n\r\n";
+            stream.write_all(response.as_bytes()).unwrap();
+        });
+
+        let client = pinned_client("127.0.0.1", &[address]).unwrap();
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        let url = format!("http://127.0.0.1:{}/v1/chat/completions", address.port());
+        let request = client.post(url).body("file contents").timeout(std::time::Duration::from_secs(5));
+        let response = runtime.block_on(async { request.send().await }).unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::TEMPORARY_REDIRECT);
+        server.join().unwrap();
+    }
+}
@@ -0,0 +1,183 @@
+// Application log. Events go to stderr as before and, once the app data directory is
+// known, to daily files under <app_data_dir>/logs so packaged builds keep a record.
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::OnceLock;
+use tracing::level_filters::LevelFilter;
+use tracing::Level;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::filter::Targets;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{fmt, reload, Registry};
+
+const LOG_DIR: &str = "logs";
+const LOG_FILE_PREFIX: &str = "file-organizer";
+const LOG_FILE_SUFFIX: &str = "log";
+// One file per day, so about a week of history
+const MAX_LOG_FILES: usize = 7;
+
+struct Logging {
+    dir: Option<PathBuf>,
+    filter: reload::Handle<Targets, Registry>,
+}
+
+static LOGGING: OnceLock<Logging> = OnceLock::new();
+
+fn default_level() -> LevelFilter {
+    if cfg!(debug_assertions) {
+        LevelFilter::DEBUG
+    } else {
+        LevelFilter::INFO
+    }
+}
+
+// The chosen level applies to this crate; dependencies (reqwest, hyper, ...) only
+// report warnings, otherwise debug level drowns the log in connection pool chatter
+fn targets(level: LevelFilter) -> Targets {
+    Targets::new()
+        .with_target(env!("CARGO_CRATE_NAME"), level)
+        .with_default(LevelFilter::WARN.min(level))
+}
+
+pub fn log_dir(app_data_dir: &Path) -> PathBuf {
+    app_data_dir.join(LOG_DIR)
+}
+
+// Installs the global subscriber. Without an app data directory (or when the log
+// directory can't be created) logging continues on stderr only.
+pub fn init(app_data_dir: Option<&Path>) {
+    if LOGGING.get().is_some() {
+        return;
+    }
+    let (filter, handle) = reload::Layer::new(targets(default_level()));
+
+    let mut file_error = None;
+    let dir = app_data_dir.map(log_dir);
+    let file_layer = dir.as_ref().and_then(|dir| {
+        let appender = RollingFileAppender::builder()
+            .rotation(Rotation::DAILY)
+            .filename_prefix(LOG_FILE_PREFIX)
+            .filename_suffix(LOG_FILE_SUFFIX)
+            .max_log_files(MAX_LOG_FILES)
+            .build(dir);
+        match appender {
+            Ok(appender) => Some(fmt::layer().with_ansi(false).with_writer(appender)),
+            Err(e) => {
+                file_error = Some(format!("Failed to open log directory {}: {}", dir.display(), e));
+                None
+            }
+        }
+    });
+    let dir = if file_layer.is_some() { dir } else { None };
+
+    let installed = tracing_subscriber::registry()
+        .with(filter)
+        .with(fmt::layer().with_writer(std::io::stderr))
+        .with(file_layer)
+        .try_init();
+    if let Err(e) = installed {
+        eprintln!("Failed to install logger: {}", e);
+        return;
+    }
+    let _ = LOGGING.set(Logging { dir, filter: handle });
+
+    if let Some(e) = file_error {
+        tracing::warn!("{}", e);
+    }
+}
+
+pub fn set_level(level: &str) -> Result<(), String> {
+    let level = LevelFilter::from_str(level.trim())
+        .map_err(|_| format!("Unknown log level '{}'; use error, warn, info, debug, trace or off", level))?;
+    let logging = LOGGING.get().ok_or("Logging is not initialized")?;
+    logging
+        .filter
+        .reload(targets(level))
+        .map_err(|e| format!("Failed to change log level: {}", e))?;
+    tracing::info!("Log level set to {}", level);
+    Ok(())
+}
+
+// Log files oldest first. Rotated names embed the date, so name order is age order.
+pub fn log_files() -> Vec<PathBuf> {
+    let Some(dir) = LOGGING.get().and_then(|logging| logging.dir.as_ref()) else {
+        return Vec::new();
+    };
+    let mut files: Vec<PathBuf> = std::fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| {
+                    path.file_name()
+                        .and_then(|name| name.to_str())
+                        .is_some_and(|name| name.starts_with(LOG_FILE_PREFIX) && name.ends_with(LOG_FILE_SUFFIX))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    files.sort();
+    files
+}
+
+// Level of a formatted line ("<timestamp>  INFO target: message"); None for the
+// continuation lines of a multi-line message
+fn line_level(line: &str) -> Option<Level> {
+    line.split_whitespace().nth(1).and_then(|token| Level::from_str(token).ok())
+}
+
+// The last `lines` lines at `level_filter` or more severe, oldest first. Continuation
+// lines follow the level of the event they belong to.
+pub fn recent_lines(lines: usize, level_filter: LevelFilter) -> Vec<String> {
+    let mut collected: Vec<String> = Vec::new();
+    for file in log_files().iter().rev() {
+        let Ok(content) = std::fs::read_to_string(file) else {
+            continue;
+        };
+        // Read forwards to track levels, then keep the newest matches
+        let mut level = None;
+        let matching: Vec<&str> = content
+            .lines()
+            .filter(|line| {
+                if let Some(line_level) = line_level(line) {
+                    level = Some(line_level);
+                }
+                level.is_some_and(|level| level_filter >= level)
+            })
+            .collect();
+        let take = lines.saturating_sub(collected.len()).min(matching.len());
+        collected.extend(matching[matching.len() - take..].iter().rev().map(|line| line.to_string()));
+        if collected.len() >= lines {
+            break;
+        }
+    }
+    collected.reverse();
+    collected
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn levels_are_read_from_formatted_lines() {
+        assert_eq!(
+            line_level("2024-03-20T10:15:00.123456Z  INFO file_organizer_desktop: Server ready after 1.2s"),
+            Some(Level::INFO)
+        );
+        assert_eq!(
+            line_level("2024-03-20T10:15:00.123456Z ERROR file_organizer_desktop::scheduler: Failed to save"),
+            Some(Level::ERROR)
+        );
+        assert_eq!(line_level("  - /tmp/llama-server/bin"), None);
+    }
+
+    #[test]
+    fn dependencies_are_capped_at_warn() {
+        let filter = targets(LevelFilter::DEBUG);
+        assert!(filter.would_enable(env!("CARGO_CRATE_NAME"), &Level::DEBUG));
+        assert!(!filter.would_enable("hyper::proto", &Level::DEBUG));
+        assert!(filter.would_enable("hyper::proto", &Level::WARN));
+        assert!(!targets(LevelFilter::ERROR).would_enable("hyper::proto", &Level::WARN));
+    }
+}
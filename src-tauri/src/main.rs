@@ -5,23 +5,94 @@
 
 use std::fs;
 use std::path::Path;
-use std::io::{Write, Read};
+use std::io::{Write, Read, BufRead, BufReader};
 use std::thread;
 use std::panic;
-use std::sync::{Mutex, OnceLock, Arc};
+use std::sync::{Mutex, Arc};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::process::{Child, Command, Stdio};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
 use tauri::{command, AppHandle, Manager, CustomMenuItem, Menu, MenuItem, Submenu, WindowMenuEvent, State};
 use walkdir::WalkDir;
 use docx_rs::*;
-use calamine::{Reader, open_workbook, Xlsx};
-use image::GenericImageView;
+use calamine::{Reader, Xlsx};
 use base64::Engine;
 use serde::{Deserialize, Serialize};
-#[cfg(not(unix))]
-use zip::ZipArchive;
-use flate2::read::GzDecoder;
-use tar::Archive;
+use tracing::{debug, error, info, warn};
+
+mod archive;
+mod audit;
+mod checkpoints;
+mod classification_cache;
+mod classification_compare;
+mod cloud_files;
+mod code_summary;
+mod composition;
+mod content_index;
+mod content_budget;
+mod date_organize;
+mod destination_policy;
+mod destination_suggestions;
+mod directory_listing;
+mod directory_stream;
+mod duplicate_dirs;
+mod error;
+mod external_extractors;
+mod extraction;
+mod file_archive;
+mod file_tags;
+mod gguf;
+mod gpu;
+mod hf_models;
+mod i18n;
+mod io_priority;
+mod journal;
+mod llm_backend;
+mod llm_metrics;
+mod local_only;
+mod logging;
+mod model_catalog;
+mod moves;
+mod network;
+mod office_files;
+mod open_with;
+mod operations;
+mod paths;
+mod pipeline;
+mod progress;
+mod provenance;
+mod redaction;
+mod rename;
+mod runs;
+mod sampling;
+mod scan_filter;
+mod scheduler;
+mod settings;
+mod shortcuts;
+mod shutdown;
+mod spotlight;
+mod staging;
+#[cfg(test)]
+mod test_support;
+mod text_encoding;
+mod thumbnails;
+mod tree_summary;
+mod unused_dirs;
+mod verification;
+mod volumes;
+mod vision_image;
+
+use classification_cache::{CacheStats, CachedClassification, ClassificationCache, QuickHash};
+use content_budget::ReductionStrategy;
+use redaction::{Redacted, RedactionRules};
+use error::AppError;
+use scan_filter::ScanFilter;
+use directory_listing::{walk_entry_skipped, DirectoryEntry};
+
+use llm_backend::{
+    Backend, BackendCapabilities, BackendConfig, BackendError, ClassifyRequest, ClassifyResponse,
+    FallbackSettings, FallbackState, LlmBackend,
+};
 
 // Managed LLM Server types
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -31,6 +102,13 @@ pub struct ManagedLLMServerInfo {
     pub path: Option<String>,
     pub port: Option<u16>,
     pub error: Option<String>,
+    pub model_metadata: Option<gguf::GgufMetadata>,
+    // Whether the warm-up completion ran, and how long it took
+    #[serde(default)]
+    pub warmed_up: bool,
+    pub warm_up_ms: Option<u64>,
+    // Unix seconds when the idle timer will stop the server, if one is set
+    pub idle_unload_at: Option<u64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -41,32 +119,79 @@ pub struct ManagedLLMConfig {
     pub model_filename: Option<String>,
     pub model_path: Option<String>,
     pub log_level: String,
+    #[serde(default)]
+    pub auto_port: bool,
     pub env_vars: HashMap<String, String>,
     pub mmproj_repo_id: Option<String>,
     pub mmproj_filename: Option<String>,
     pub chat_format: Option<String>,
+    #[serde(default = "default_startup_timeout")]
+    pub startup_timeout: u64, // seconds to wait for the server to answer /v1/models
+    #[serde(default = "default_restart_policy")]
+    pub restart_policy: String, // "never" | "on-failure" | "always"
+    #[serde(default = "default_max_restart_attempts")]
+    pub max_restart_attempts: u32,
+    // Runs a one-token completion once the server is up, so the first file doesn't
+    // wait for shader compilation and cache warm-up
+    #[serde(default)]
+    pub warm_up: bool,
+    // Stops the server to free its memory after this many minutes without a request;
+    // the next request starts it again. None or 0 keeps it loaded.
+    #[serde(default)]
+    pub idle_unload_minutes: Option<u64>,
+}
+
+fn default_startup_timeout() -> u64 {
+    60
+}
+
+fn default_restart_policy() -> String {
+    "on-failure".to_string()
+}
+
+fn default_max_restart_attempts() -> u32 {
+    3
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct ServerProcessInfo {
     pid: u32,
     config: ManagedLLMConfig,
+    model_metadata: Option<gguf::GgufMetadata>,
+    #[serde(default)]
+    warm_up_ms: Option<u64>,
+    // Most recent stderr lines from the server, reported when it crashes
+    #[serde(skip)]
+    stderr_tail: Arc<Mutex<VecDeque<String>>>,
 }
 
 // Global state for the managed LLM server process and its config
 // Stores: Optional Child handle (None if orphaned), and ServerProcessInfo with PID and config
 type ManagedLLMState = Arc<Mutex<Option<(Option<Child>, ServerProcessInfo)>>>;
 
+// The LLM backend selected by the frontend; None until select_llm_backend is called
+type LlmBackendState = Arc<Mutex<Option<BackendConfig>>>;
+// Fallback chain and circuit breakers used by llm_classify
+type LlmFallbackState = Arc<Mutex<FallbackState>>;
+// Timings and token counts of every llm_classify attempt
+type LlmMetricsState = Arc<Mutex<llm_metrics::LlmMetrics>>;
+
 // Helper functions for PID file management and process control
 
 fn get_pid_file_path(app_data_dir: &std::path::PathBuf) -> std::path::PathBuf {
     app_data_dir.join("llm-server").join("server.pid")
 }
 
-fn write_pid_file(app_data_dir: &std::path::PathBuf, pid: u32, config: &ManagedLLMConfig) -> Result<(), String> {
+fn write_pid_file(
+    app_data_dir: &std::path::PathBuf,
+    pid: u32,
+    config: &ManagedLLMConfig,
+    server_exe: &Path
+) -> Result<(), String> {
     let pid_file = get_pid_file_path(app_data_dir);
     let pid_data = serde_json::json!({
         "pid": pid,
+        "exe": server_exe.to_string_lossy(),
         "port": config.port,
         "host": config.host,
         "started_at": std::time::SystemTime::now()
@@ -78,11 +203,13 @@ fn write_pid_file(app_data_dir: &std::path::PathBuf, pid: u32, config: &ManagedL
     fs::write(&pid_file, pid_data.to_string())
         .map_err(|e| format!("Failed to write PID file: {}", e))?;
     
-    eprintln!("Wrote PID {} to file: {}", pid, pid_file.to_string_lossy());
+    info!("Wrote PID {} to file: {}", pid, pid_file.to_string_lossy());
     Ok(())
 }
 
-fn read_pid_file(app_data_dir: &std::path::PathBuf) -> Option<(u32, u16, String)> {
+// Returns (pid, port, host, server binary path); PID files written by older versions
+// have no binary path
+fn read_pid_file(app_data_dir: &std::path::PathBuf) -> Option<(u32, u16, String, Option<std::path::PathBuf>)> {
     let pid_file = get_pid_file_path(app_data_dir);
     if !pid_file.exists() {
         return None;
@@ -94,15 +221,16 @@ fn read_pid_file(app_data_dir: &std::path::PathBuf) -> Option<(u32, u16, String)
     let pid = pid_json["pid"].as_u64()? as u32;
     let port = pid_json["port"].as_u64()? as u16;
     let host = pid_json["host"].as_str()?.to_string();
+    let exe = pid_json["exe"].as_str().map(std::path::PathBuf::from);
     
-    Some((pid, port, host))
+    Some((pid, port, host, exe))
 }
 
 fn remove_pid_file(app_data_dir: &std::path::PathBuf) {
     let pid_file = get_pid_file_path(app_data_dir);
     if pid_file.exists() {
         let _ = fs::remove_file(&pid_file);
-        eprintln!("Removed PID file: {}", pid_file.to_string_lossy());
+        info!("Removed PID file: {}", pid_file.to_string_lossy());
     }
 }
 
@@ -132,14 +260,14 @@ fn is_process_running(pid: u32) -> bool {
 
 #[cfg(target_os = "windows")]
 fn kill_process_by_pid(pid: u32) -> Result<(), String> {
-    eprintln!("Killing process with PID: {}", pid);
+    info!("Killing process with PID: {}", pid);
     let output = std::process::Command::new("taskkill")
         .args(&["/F", "/T", "/PID", &pid.to_string()])
         .output()
         .map_err(|e| format!("Failed to execute taskkill: {}", e))?;
     
     if output.status.success() {
-        eprintln!("Successfully killed process {}", pid);
+        info!("Successfully killed process {}", pid);
         Ok(())
     } else {
         Err(format!("Failed to kill process: {}", String::from_utf8_lossy(&output.stderr)))
@@ -148,20 +276,20 @@ fn kill_process_by_pid(pid: u32) -> Result<(), String> {
 
 #[cfg(target_os = "windows")]
 fn kill_process_by_name(process_name: &str) -> Result<(), String> {
-    eprintln!("Killing all processes with name: {}", process_name);
+    info!("Killing all processes with name: {}", process_name);
     let output = std::process::Command::new("taskkill")
         .args(&["/F", "/IM", process_name])
         .output()
         .map_err(|e| format!("Failed to execute taskkill: {}", e))?;
     
     if output.status.success() {
-        eprintln!("Successfully killed processes named {}", process_name);
+        info!("Successfully killed processes named {}", process_name);
         Ok(())
     } else {
         // Don't treat as error if process not found
         let stderr = String::from_utf8_lossy(&output.stderr);
         if stderr.contains("not found") {
-            eprintln!("No processes found with name {}", process_name);
+            info!("No processes found with name {}", process_name);
             Ok(())
         } else {
             Err(format!("Failed to kill process: {}", stderr))
@@ -171,74 +299,189 @@ fn kill_process_by_name(process_name: &str) -> Result<(), String> {
 
 #[cfg(unix)]
 fn kill_process_by_pid(pid: u32) -> Result<(), String> {
-    eprintln!("Killing process with PID: {}", pid);
+    info!("Killing process with PID: {}", pid);
     let output = std::process::Command::new("kill")
         .args(&["-9", &pid.to_string()])
         .output()
         .map_err(|e| format!("Failed to execute kill: {}", e))?;
     
     if output.status.success() {
-        eprintln!("Successfully killed process {}", pid);
+        info!("Successfully killed process {}", pid);
         Ok(())
     } else {
         Err(format!("Failed to kill process: {}", String::from_utf8_lossy(&output.stderr)))
     }
 }
 
+// Returns the PID listening on a TCP port, if the platform tools can tell us
+#[cfg(unix)]
+fn find_port_owner(port: u16) -> Option<u32> {
+    let output = std::process::Command::new("lsof")
+        .args(["-nP", &format!("-iTCP:{}", port), "-sTCP:LISTEN", "-t"])
+        .output()
+        .ok()?;
+    String::from_utf8_lossy(&output.stdout).lines().next()?.trim().parse().ok()
+}
+
+#[cfg(target_os = "windows")]
+fn find_port_owner(port: u16) -> Option<u32> {
+    let output = std::process::Command::new("netstat")
+        .args(["-ano", "-p", "TCP"])
+        .output()
+        .ok()?;
+    let suffix = format!(":{}", port);
+    String::from_utf8_lossy(&output.stdout).lines().find_map(|line| {
+        let columns: Vec<&str> = line.split_whitespace().collect();
+        if columns.len() >= 5 && columns[1].ends_with(&suffix) && columns[3] == "LISTENING" {
+            columns[4].parse().ok()
+        } else {
+            None
+        }
+    })
+}
+
+// Picks the port for the managed server: the configured one if it is free, otherwise
+// a free ephemeral port when auto_port is set
+fn resolve_server_port(config: &ManagedLLMConfig) -> Result<u16, String> {
+    match std::net::TcpListener::bind((config.host.as_str(), config.port)) {
+        Ok(_) => Ok(config.port),
+        Err(e) if e.kind() == std::io::ErrorKind::AddrInUse => {
+            if config.auto_port {
+                let listener = std::net::TcpListener::bind((config.host.as_str(), 0))
+                    .map_err(|e| format!("Failed to find a free port: {}", e))?;
+                let port = listener.local_addr()
+                    .map_err(|e| format!("Failed to find a free port: {}", e))?
+                    .port();
+                info!("Port {} is in use, starting server on free port {}", config.port, port);
+                Ok(port)
+            } else {
+                match find_port_owner(config.port) {
+                    Some(pid) => Err(format!("Port {} already in use by PID {}", config.port, pid)),
+                    None => Err(format!("Port {} already in use", config.port)),
+                }
+            }
+        }
+        // Other bind errors (e.g. an unresolvable host) are left for the server to report
+        Err(e) => {
+            warn!("Could not check availability of port {}: {}", config.port, e);
+            Ok(config.port)
+        }
+    }
+}
+
+// Path of the server binary that start_llm_server launches
+fn managed_server_exe(app_data_dir: &Path) -> std::path::PathBuf {
+    let server_dir = app_data_dir.join("llm-server");
+    if cfg!(target_os = "windows") {
+        server_dir.join("llama_server").join("llama_server").join("llama_server.exe")
+    } else if cfg!(target_os = "macos") {
+        server_dir.join("mlx_server").join("mlx_server")
+    } else {
+        server_dir.join("llama_server").join("llama_server")
+    }
+}
+
+// Checks that `pid` is running `expected_exe`, so a PID reused by an unrelated
+// process after a crash is never adopted or killed
+fn is_managed_server_process(pid: u32, expected_exe: &Path) -> bool {
+    let mut system = sysinfo::System::new();
+    let pid = sysinfo::Pid::from_u32(pid);
+    if !system.refresh_process(pid) {
+        return false;
+    }
+    let process = match system.process(pid) {
+        Some(process) => process,
+        None => return false,
+    };
+
+    let expected = fs::canonicalize(expected_exe).unwrap_or_else(|_| expected_exe.to_path_buf());
+    let matches = |path: &Path| {
+        fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf()) == expected
+    };
+    process.exe().is_some_and(matches)
+        || process.cmd().first().is_some_and(|arg| matches(Path::new(arg)))
+}
+
 async fn try_reconnect_orphaned_server(
     app_data_dir: &std::path::PathBuf,
     state: &ManagedLLMState
 ) -> Result<(), String> {
-    if let Some((pid, port, host)) = read_pid_file(app_data_dir) {
-        eprintln!("Found PID file: PID={}, host={}, port={}", pid, host, port);
+    if let Some((pid, port, host, exe)) = read_pid_file(app_data_dir) {
+        debug!("Found PID file: PID={}, host={}, port={}", pid, host, port);
         
         // Check if process is still running
         if !is_process_running(pid) {
-            eprintln!("Process {} is not running, cleaning up PID file", pid);
+            info!("Process {} is not running, cleaning up PID file", pid);
+            remove_pid_file(app_data_dir);
+            return Ok(());
+        }
+
+        // The PID may have been reused since the app went away
+        let expected_exe = exe.unwrap_or_else(|| managed_server_exe(app_data_dir));
+        if !is_managed_server_process(pid, &expected_exe) {
+            info!("Process {} is not our server binary, cleaning up PID file", pid);
             remove_pid_file(app_data_dir);
             return Ok(());
         }
         
         // Verify it's actually our server by checking if it responds
-        let client = reqwest::Client::new();
+        let client = network::build_http_client();
         let test_url = format!("http://{}:{}/v1/models", host, port);
         
-        eprintln!("Verifying orphaned server at: {}", test_url);
+        debug!("Verifying orphaned server at: {}", test_url);
         match client.get(&test_url)
             .timeout(std::time::Duration::from_secs(3))
             .send()
             .await 
         {
             Ok(response) if response.status().is_success() => {
-                eprintln!("Orphaned server is responsive, reconnecting...");
+                info!("Orphaned server is responsive, reconnecting...");
                 
-                // Reconnect by storing process info without Child handle
-                let config = ManagedLLMConfig {
-                    port,
-                    host,
-                    model: None,
-                    model_filename: None,
-                    model_path: None,
-                    log_level: "info".to_string(),
-                    env_vars: HashMap::new(),
-                    mmproj_repo_id: None,
-                    mmproj_filename: None,
-                    chat_format: None,
+                // Reconnect by storing process info without Child handle, keeping the
+                // saved configuration so restarts use the same model
+                let saved = settings::with(|settings| settings.managed_llm.clone());
+                let config = match saved {
+                    Some(saved) => ManagedLLMConfig { port, host, ..saved },
+                    None => ManagedLLMConfig {
+                        port,
+                        host,
+                        model: None,
+                        model_filename: None,
+                        model_path: None,
+                        log_level: "info".to_string(),
+                        auto_port: false,
+                        env_vars: HashMap::new(),
+                        mmproj_repo_id: None,
+                        mmproj_filename: None,
+                        chat_format: None,
+                        startup_timeout: default_startup_timeout(),
+                        restart_policy: default_restart_policy(),
+                        max_restart_attempts: default_max_restart_attempts(),
+                        warm_up: false,
+                        idle_unload_minutes: None,
+                    },
                 };
                 
                 let process_info = ServerProcessInfo {
                     pid,
                     config,
+                    model_metadata: None,
+                    warm_up_ms: None,
+                    stderr_tail: Default::default(),
                 };
                 
                 let mut state_guard = state.lock().unwrap();
                 *state_guard = Some((None, process_info)); // None = orphaned process
                 
-                eprintln!("Successfully reconnected to orphaned server");
+                info!("Successfully reconnected to orphaned server");
                 Ok(())
             }
             _ => {
-                eprintln!("Process exists but server not responding, cleaning up");
+                // A hung leftover would keep holding the port and GPU memory
+                warn!("Orphaned server {} is not responding, terminating it", pid);
+                if let Err(e) = kill_process_by_pid(pid) {
+                    warn!("Failed to terminate orphaned server: {}", e);
+                }
                 remove_pid_file(app_data_dir);
                 Ok(())
             }
@@ -248,88 +491,246 @@ async fn try_reconnect_orphaned_server(
     }
 }
 
-// OS-specific files to skip
-const OS_SPECIFIC_FILES: &[&str] = &[
-    ".DS_Store", ".ds_store",
-    "Thumbs.db", "thumbs.db",
-    "desktop.ini", "Desktop.ini",
-    ".Spotlight-V100", ".Trashes", ".fseventsd",
-    "ehthumbs.db", "ehthumbs_vista.db",
-    ".AppleDouble", ".LSOverride",
-    "Icon\r", ".DocumentRevisions-V100", ".TemporaryItems",
-    "$RECYCLE.BIN", "System Volume Information",
-];
+// Unreadable entries are logged and skipped rather than failing the whole walk. With
+// follow_links enabled walkdir reports symlink cycles here instead of looping forever.
+fn walk_entry_ok(entry: walkdir::Result<walkdir::DirEntry>) -> Option<walkdir::DirEntry> {
+    match entry {
+        Ok(entry) => Some(entry),
+        Err(e) => {
+            if e.loop_ancestor().is_some() {
+                warn!("Skipping symlink cycle: {}", e);
+            } else {
+                warn!("Skipping unreadable entry: {}", e);
+            }
+            None
+        }
+    }
+}
+
+// Symlinked files are listed as the link itself; dangling links are skipped because
+// is_file() is false when the target is missing. Symlinked directories are only
+// descended into when follow_symlinks is set. Hidden entries are skipped unless
+// include_hidden is set, and ignored_names replaces the default junk list
+// (.DS_Store, Thumbs.db, desktop.ini, ...). Subdirectories that can't be read are
+// returned in `skipped`. With a scan_id, progress goes out as directory-scan-progress
+// events a few times a second and directory-scan-complete ends the scan. `options`
+// sorts and pages the files (see directory_listing::ListingOptions).
+#[command]
+#[allow(clippy::too_many_arguments)]
+async fn read_directory(
+    app: AppHandle,
+    path: String,
+    include_subdirectories: bool,
+    follow_symlinks: Option<bool>,
+    include_hidden: Option<bool>,
+    ignored_names: Option<Vec<String>>,
+    scan_id: Option<String>,
+    options: Option<directory_listing::ListingOptions>,
+) -> Result<DirectoryListing, AppError> {
+    let filter = ScanFilter::new(include_hidden, ignored_names);
+    let options = options.unwrap_or_default();
+    let stop_after = options.stop_after();
+    let started = std::time::Instant::now();
+    let events = scan_id.clone().map(|scan_id| (app, scan_id));
+    let progress_events = events.clone();
+    let listing = tauri::async_runtime::spawn_blocking(move || {
+        let follow_symlinks = follow_symlinks.unwrap_or(false);
+        let mut tracker = progress::ProgressTracker::new(progress::RateUnit::Items, None);
+        directory_listing::list(&path, include_subdirectories, follow_symlinks, &filter, stop_after, &mut |p| {
+            if let Some((app, scan_id)) = &progress_events {
+                let _ = app.emit_all("directory-scan-progress", DirectoryScanProgress {
+                    scan_id: scan_id.clone(),
+                    progress: p.clone(),
+                    estimate: tracker.update(p.files_found),
+                });
+            }
+        })
+    })
+    .await
+    .map_err(|e| AppError::io(format!("Failed to list files: {}", e)))?;
+    if let Some((app, scan_id)) = events {
+        let summary = listing.as_ref().ok();
+        let _ = app.emit_all("directory-scan-complete", DirectoryScanComplete {
+            scan_id,
+            directories_visited: summary.map_or(0, |l| l.directories_visited),
+            files_found: summary.map_or(0, |l| l.entries.len() as u64),
+            skipped: summary.map_or(0, |l| l.skipped.len()),
+            elapsed_ms: started.elapsed().as_millis() as u64,
+            error: listing.as_ref().err().map(|e| e.message.clone()),
+        });
+    }
+    let listing = listing?;
+    let page = options.page(listing.entries, listing.complete);
+    Ok(DirectoryListing {
+        files: page.entries.into_iter().map(|entry| entry.path).collect(),
+        skipped: listing.skipped,
+        total: page.total,
+        next_offset: page.next_offset,
+        sort_forced_full_walk: page.sort_forced_full_walk,
+    })
+}
+
+#[derive(Debug, Serialize)]
+struct DirectoryListing {
+    files: Vec<String>,
+    // Subdirectories and entries that couldn't be read, with the reason
+    skipped: Vec<directory_listing::SkippedPath>,
+    // Files in the whole listing; None when an unsorted walk stopped after this page
+    total: Option<usize>,
+    next_offset: Option<usize>,
+    sort_forced_full_walk: bool,
+}
+
+#[derive(Clone, Serialize)]
+struct DirectoryScanProgress {
+    scan_id: String,
+    #[serde(flatten)]
+    progress: directory_listing::ScanProgress,
+    // Files found per second; the total isn't known, so there's no ETA
+    #[serde(flatten)]
+    estimate: progress::ProgressEstimate,
+}
+
+#[derive(Clone, Serialize)]
+struct DirectoryScanComplete {
+    scan_id: String,
+    directories_visited: u64,
+    files_found: u64,
+    skipped: usize,
+    elapsed_ms: u64,
+    // The directory itself couldn't be listed
+    error: Option<String>,
+}
 
-// OS-specific directories to skip
-const OS_SPECIFIC_DIRS: &[&str] = &[
-    ".Spotlight-V100", ".Trashes", ".fseventsd",
-    ".DocumentRevisions-V100", ".TemporaryItems",
-    "$RECYCLE.BIN", "System Volume Information",
-    "__MACOSX", ".AppleDouble",
-];
+type DirectoryStreamState = Arc<directory_stream::DirectoryStreams>;
 
-fn is_hidden_or_os_file(name: &str) -> bool {
-    name.starts_with('.') || OS_SPECIFIC_FILES.contains(&name)
+#[derive(Clone, Serialize)]
+struct DirectoryEntriesChunk {
+    stream_id: String,
+    entries: Vec<DirectoryEntry>,
 }
 
-fn is_hidden_or_os_dir(name: &str) -> bool {
-    name.starts_with('.') || OS_SPECIFIC_DIRS.contains(&name)
+#[derive(Clone, Serialize)]
+struct DirectoryStreamComplete {
+    stream_id: String,
+    #[serde(flatten)]
+    summary: directory_stream::StreamSummary,
+    elapsed_ms: u64,
+    // The directory itself couldn't be listed
+    error: Option<String>,
 }
 
+// read_directory for directories too big for one reply. Returns a stream_id at once; the
+// files follow to the calling window as directory-entries events, then one
+// directory-stream-complete. Progress goes out as directory-scan-progress under the
+// stream_id.
 #[command]
-async fn read_directory(path: String, include_subdirectories: bool) -> Result<Vec<String>, String> {
-    if include_subdirectories {
-        let entries = WalkDir::new(&path)
-            .into_iter()
-            .filter_entry(|e| {
-                // Skip hidden directories and OS-specific directories
-                let name = e.file_name().to_string_lossy();
-                !is_hidden_or_os_dir(&name)
-            })
-            .filter_map(|e| e.ok())
-            .filter(|e| {
-                if !e.path().is_file() {
-                    return false;
-                }
-                let name = e.file_name().to_string_lossy();
-                !is_hidden_or_os_file(&name)
-            })
-            .map(|e| e.path().to_string_lossy().into_owned())
-            .collect::<Vec<String>>();
-        Ok(entries)
-    } else {
-        let entries = fs::read_dir(path)
-            .map_err(|e| e.to_string())?
-            .filter_map(|res| res.ok())
-            .filter(|entry| {
-                if !entry.path().is_file() {
-                    return false;
-                }
-                let name = entry.file_name().to_string_lossy().to_string();
-                !is_hidden_or_os_file(&name)
-            })
-            .map(|e| e.path().to_string_lossy().into_owned())
-            .collect::<Vec<String>>();
-        Ok(entries)
-    }
+#[allow(clippy::too_many_arguments)]
+fn start_directory_stream(
+    window: tauri::Window,
+    streams: State<'_, DirectoryStreamState>,
+    path: String,
+    include_subdirectories: bool,
+    follow_symlinks: Option<bool>,
+    include_hidden: Option<bool>,
+    ignored_names: Option<Vec<String>>,
+    options: Option<directory_stream::StreamOptions>,
+) -> Result<String, AppError> {
+    let filter = ScanFilter::new(include_hidden, ignored_names);
+    let options = options.unwrap_or_default();
+    let streams = streams.inner().clone();
+    let (stream_id, token) = streams.start(window.label());
+    let task_stream_id = stream_id.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let started = std::time::Instant::now();
+        let stream_id = task_stream_id;
+        let mut tracker = progress::ProgressTracker::new(progress::RateUnit::Items, None);
+        let result = directory_stream::run(
+            &path,
+            include_subdirectories,
+            follow_symlinks.unwrap_or(false),
+            &filter,
+            &options,
+            &token,
+            &mut |p| {
+                let _ = window.emit("directory-scan-progress", DirectoryScanProgress {
+                    scan_id: stream_id.clone(),
+                    progress: p.clone(),
+                    estimate: tracker.update(p.files_found),
+                });
+            },
+            &mut |entries| {
+                let _ = window.emit("directory-entries", DirectoryEntriesChunk {
+                    stream_id: stream_id.clone(),
+                    entries,
+                });
+            },
+        );
+        streams.finish(&stream_id);
+        if let Err(e) = &result {
+            warn!("Directory stream {} failed: {}", stream_id, e.message);
+        }
+        let _ = window.emit("directory-stream-complete", DirectoryStreamComplete {
+            stream_id,
+            elapsed_ms: started.elapsed().as_millis() as u64,
+            error: result.as_ref().err().map(|e| e.message.clone()),
+            summary: result.unwrap_or_default(),
+        });
+    });
+    Ok(stream_id)
+}
+
+// Stops a stream after the chunk in flight; directory-stream-complete still follows,
+// with cancelled set. Whether the stream was running.
+#[command]
+fn cancel_directory_stream(streams: State<'_, DirectoryStreamState>, stream_id: String) -> Result<bool, AppError> {
+    Ok(streams.cancel(&stream_id))
 }
 
+// read_directory with each file marked as a cloud placeholder or not
 #[command]
-async fn list_subdirectories(path: String) -> Result<Vec<String>, String> {
-    let base_path = Path::new(&path);
-    let entries: Vec<String> = WalkDir::new(&path)
+async fn read_directory_entries(
+    path: String,
+    include_subdirectories: bool,
+    follow_symlinks: Option<bool>,
+    include_hidden: Option<bool>,
+    ignored_names: Option<Vec<String>>,
+) -> Result<Vec<DirectoryEntry>, AppError> {
+    let filter = ScanFilter::new(include_hidden, ignored_names);
+    list_files(&path, include_subdirectories, follow_symlinks.unwrap_or(false), &filter)
+}
+
+// Files without progress reporting; unreadable subdirectories are logged and left out
+fn list_files(
+    path: &str,
+    include_subdirectories: bool,
+    follow_symlinks: bool,
+    filter: &ScanFilter,
+) -> Result<Vec<DirectoryEntry>, AppError> {
+    directory_listing::list(path, include_subdirectories, follow_symlinks, filter, None, &mut |_| {})
+        .map(|listing| listing.entries)
+}
+
+#[command]
+async fn list_subdirectories(
+    path: String,
+    follow_symlinks: Option<bool>,
+    include_hidden: Option<bool>,
+    ignored_names: Option<Vec<String>>,
+) -> Result<Vec<String>, AppError> {
+    let base_path = paths::normalize_path(&path);
+    let filter = ScanFilter::new(include_hidden, ignored_names);
+    let entries: Vec<String> = WalkDir::new(&base_path)
         .min_depth(1) // Skip the root directory itself
+        .follow_links(follow_symlinks.unwrap_or(false))
         .into_iter()
-        .filter_entry(|e| {
-            // Skip hidden directories and OS-specific directories
-            let name = e.file_name().to_string_lossy();
-            !is_hidden_or_os_dir(&name)
-        })
-        .filter_map(|e| e.ok())
-        .filter(|e| e.path().is_dir())
+        .filter_entry(|e| !walk_entry_skipped(&filter, e))
+        .filter_map(walk_entry_ok)
+        // file_type() only reports a linked directory as a directory when links are followed
+        .filter(|e| e.file_type().is_dir())
         .filter_map(|entry| {
             // Get relative path from the base directory
-            entry.path().strip_prefix(base_path).ok().map(|rel| {
+            entry.path().strip_prefix(&base_path).ok().map(|rel| {
                 rel.to_string_lossy().to_string()
             })
         })
@@ -337,1059 +738,5485 @@ async fn list_subdirectories(path: String) -> Result<Vec<String>, String> {
     Ok(entries)
 }
 
+// Counts and sizes per coarse content type, plus the largest files of each.
+// `path` is one directory or a list of them; `categories` maps category names to
+// extensions and replaces the built-in table.
 #[command]
-async fn http_request(
-    url: String,
-    method: String,
-    headers: std::collections::HashMap<String, String>,
-    body: Option<String>,
-) -> Result<String, String> {
-    let client = reqwest::Client::new();
-    
-    let mut request = match method.to_uppercase().as_str() {
-        "GET" => client.get(&url),
-        "POST" => client.post(&url),
-        "PUT" => client.put(&url),
-        "DELETE" => client.delete(&url),
-        _ => return Err(format!("Unsupported HTTP method: {}", method)),
-    };
-
-    // Add headers
-    for (key, value) in headers {
-        request = request.header(key, value);
-    }
+async fn get_directory_composition(
+    path: paths::PathList,
+    include_subdirectories: bool,
+    categories: Option<HashMap<String, Vec<String>>>,
+    include_hidden: Option<bool>,
+    ignored_names: Option<Vec<String>>,
+) -> Result<composition::DirectoryComposition, AppError> {
+    let roots = existing_directories(path.into_vec())?;
+    let filter = ScanFilter::new(include_hidden, ignored_names);
+    let table = composition::category_table(categories);
+    tauri::async_runtime::spawn_blocking(move || composition::compose(&roots, include_subdirectories, &filter, &table))
+        .await
+        .map_err(|e| AppError::io(format!("Directory composition task failed: {}", e)))
+}
 
-    // Add body if present
-    if let Some(body_content) = body {
-        request = request.body(body_content);
-    }
+// Directories under `path` (one or a list) with nothing modified or opened in the last
+// unused_days, nested ones collapsed into the highest, largest first. min_bytes leaves
+// out ones too small to be worth archiving. Symlinks are only followed with follow_symlinks.
+#[command]
+async fn find_unused_directories(
+    path: paths::PathList,
+    unused_days: u64,
+    min_bytes: Option<u64>,
+    follow_symlinks: Option<bool>,
+    include_hidden: Option<bool>,
+    ignored_names: Option<Vec<String>>,
+) -> Result<unused_dirs::UnusedDirectoryReport, AppError> {
+    let roots = existing_directories(path.into_vec())?;
+    let filter = ScanFilter::new(include_hidden, ignored_names);
+    let cutoff_ms = (scheduler::now_secs() * 1000).saturating_sub(unused_days.saturating_mul(24 * 60 * 60 * 1000));
+    let follow_symlinks = follow_symlinks.unwrap_or(false);
+    tauri::async_runtime::spawn_blocking(move || {
+        unused_dirs::find(&roots, &filter, follow_symlinks, cutoff_ms, min_bytes.unwrap_or(0))
+    })
+    .await
+    .map_err(|e| AppError::io(format!("Unused directory search failed: {}", e)))
+}
 
-    // Execute request
-    let response = request
-        .send()
+// Shortcuts under `path` (one or a list): .lnk shell links, Finder aliases, and .url and
+// .webloc web links. Targets that exist are listed as referenced; shortcuts whose
+// target is gone come back separately as broken. Symlinks are only followed with
+// follow_symlinks.
+#[command]
+async fn find_shortcuts(
+    path: paths::PathList,
+    follow_symlinks: Option<bool>,
+    include_hidden: Option<bool>,
+    ignored_names: Option<Vec<String>>,
+) -> Result<shortcuts::ShortcutReport, AppError> {
+    let roots = existing_directories(path.into_vec())?;
+    let filter = ScanFilter::new(include_hidden, ignored_names);
+    let follow_symlinks = follow_symlinks.unwrap_or(false);
+    tauri::async_runtime::spawn_blocking(move || shortcuts::find(&roots, &filter, follow_symlinks))
         .await
-        .map_err(|e| format!("Request failed: {}", e))?;
+        .map_err(|e| AppError::io(format!("Shortcut search failed: {}", e)))
+}
 
-    // Get status for error handling
-    let status = response.status();
-    
-    // Get response text
-    let text = response
-        .text()
+// Spotlight's last-used date, download origin, kind and authors of a file. None on
+// platforms without Spotlight, or when it has nothing for the file's volume.
+#[command]
+async fn get_spotlight_metadata(path: String) -> Result<Option<spotlight::SpotlightMetadata>, AppError> {
+    let fs_path = paths::resolve_path(&path);
+    fs::symlink_metadata(&fs_path).map_err(|e| AppError::from(e).with_path(&path))?;
+    tauri::async_runtime::spawn_blocking(move || spotlight::metadata(&fs_path))
         .await
-        .map_err(|e| format!("Failed to read response: {}", e))?;
+        .map_err(|e| AppError::io(format!("Spotlight lookup failed: {}", e)))
+}
 
-    if !status.is_success() {
-        return Err(format!("HTTP {}: {}", status.as_u16(), text));
-    }
+// The URL a downloaded file came from and the page that linked to it, as the browser
+// recorded them. None when it didn't, or on platforms that keep no such record.
+#[command]
+async fn get_file_provenance(path: String) -> Result<Option<provenance::Provenance>, AppError> {
+    let fs_path = paths::resolve_path(&path);
+    fs::symlink_metadata(&fs_path).map_err(|e| AppError::from(e).with_path(&path))?;
+    tauri::async_runtime::spawn_blocking(move || provenance::read(&fs_path))
+        .await
+        .map_err(|e| AppError::io(format!("Provenance lookup failed: {}", e)))
+}
 
-    Ok(text)
+// Chooses up to sample_size files under `path` to classify before the whole directory:
+// "stratified" (default) by extension and size bucket, "random", "largest" or "newest".
+// The same seed over the same files picks the same sample; the seed used is returned.
+#[command]
+#[allow(clippy::too_many_arguments)]
+async fn sample_directory_for_classification(
+    path: String,
+    sample_size: usize,
+    strategy: Option<sampling::SampleStrategy>,
+    seed: Option<u64>,
+    include_subdirectories: Option<bool>,
+    include_hidden: Option<bool>,
+    ignored_names: Option<Vec<String>>,
+    categories: Option<HashMap<String, Vec<String>>>,
+) -> Result<sampling::ClassificationSample, AppError> {
+    let root = existing_directories(vec![path])?.remove(0);
+    let filter = ScanFilter::new(include_hidden, ignored_names);
+    let table = composition::category_table(categories);
+    let strategy = strategy.unwrap_or_default();
+    let seed = seed.unwrap_or_else(sampling::new_seed);
+    let include_subdirectories = include_subdirectories.unwrap_or(true);
+    tauri::async_runtime::spawn_blocking(move || {
+        let (population, cloud_placeholders) = sampling::candidates(&root, include_subdirectories, &filter, &table);
+        let files = sampling::sample(population.clone(), sample_size, strategy, seed);
+        sampling::ClassificationSample {
+            strategy,
+            seed,
+            population: population.len(),
+            composition: sampling::composition_of(&population, &files),
+            files,
+            cloud_placeholders,
+        }
+    })
+    .await
+    .map_err(|e| AppError::io(format!("Sampling task failed: {}", e)))
 }
 
+// Agreement between two backends' results for the same files: match rates, average
+// confidence and the most common disagreements. With report_path every row is also
+// written there as CSV; label_a and label_b name the backends in its header.
 #[command]
-async fn save_diagnostic_logs(content: String, filename: String) -> Result<String, String> {
-    // Get the user's home directory
-    let home_dir = dirs::home_dir()
-        .ok_or_else(|| "Could not find home directory".to_string())?;
-    
-    // Create a path in the user's Downloads folder
-    let downloads_dir = home_dir.join("Downloads");
-    let file_path = downloads_dir.join(&filename);
-    
-    // Write the content to the file
-    let mut file = fs::File::create(&file_path)
-        .map_err(|e| format!("Failed to create file: {}", e))?;
-    
-    file.write_all(content.as_bytes())
-        .map_err(|e| format!("Failed to write to file: {}", e))?;
-    
-    // Return the full path where the file was saved
-    Ok(file_path.to_string_lossy().to_string())
+async fn compare_classifications(
+    rows: Vec<classification_compare::ComparedFile>,
+    report_path: Option<String>,
+    label_a: Option<String>,
+    label_b: Option<String>,
+) -> Result<classification_compare::ComparisonSummary, AppError> {
+    let label_a = label_a.unwrap_or_else(|| "a".to_string());
+    let label_b = label_b.unwrap_or_else(|| "b".to_string());
+    tauri::async_runtime::spawn_blocking(move || {
+        let report = report_path.as_deref().map(paths::resolve_path);
+        classification_compare::compare(&rows, report.as_deref(), &label_a, &label_b).map_err(|e| {
+            let display = report_path.unwrap_or_default();
+            AppError::from_io(&e, format!("Failed to write comparison report: {}", e)).with_path(display)
+        })
+    })
+    .await
+    .map_err(|e| AppError::io(format!("Comparison task failed: {}", e)))?
 }
 
-static PANIC_HOOK_LOCK: OnceLock<Mutex<()>> = OnceLock::new();
+// Normalized roots for a multi-root command, failing on the first one that isn't a directory
+fn existing_directories(roots: Vec<String>) -> Result<Vec<std::path::PathBuf>, AppError> {
+    roots
+        .iter()
+        .map(|root| {
+            let path = paths::normalize_path(root);
+            if path.is_dir() {
+                Ok(path)
+            } else {
+                Err(AppError::not_found("Directory not found").with_path(root))
+            }
+        })
+        .collect()
+}
 
-fn extract_pdf_text(path: &str) -> Result<String, String> {
-    let owned_path = path.to_owned();
-    let handle = thread::spawn(move || {
-        let lock = PANIC_HOOK_LOCK.get_or_init(|| Mutex::new(())).lock().unwrap();
-        let original_hook = panic::take_hook();
-        panic::set_hook(Box::new(|_| {}));
-        let extraction_result = panic::catch_unwind(|| pdf_extract::extract_text(&owned_path));
-        panic::set_hook(original_hook);
-        drop(lock);
-        extraction_result
-    });
+// Directories whose entire content trees are identical, across all given roots.
+// With min_similarity (a percentage) directories sharing at least that share of
+// their bytes are reported too. Cloud placeholders aren't hashed unless hydrate is set.
+// With io_priority "low" hashing yields the disk to everything else and is held to the
+// scan throttle. Progress goes out as duplicate-scan-progress events. With
+// checkpoint_every the hashes are saved every that many files, so an interrupted
+// search can be picked up with resume_analysis; the checkpoint is removed once the
+// search completes. Symlinks are only followed with follow_symlinks, so by default a
+// link and its target aren't reported as copies of each other.
+#[command]
+#[allow(clippy::too_many_arguments)]
+async fn find_duplicate_directories(
+    app: AppHandle,
+    roots: paths::PathList,
+    min_similarity: Option<f64>,
+    follow_symlinks: Option<bool>,
+    include_hidden: Option<bool>,
+    ignored_names: Option<Vec<String>>,
+    hydrate: Option<bool>,
+    io_priority: Option<io_priority::IoPriority>,
+    checkpoint_every: Option<usize>,
+) -> Result<duplicate_dirs::DuplicateDirectoryReport, AppError> {
+    let roots = roots.into_vec();
+    let hydrate = hydrate.unwrap_or_else(cloud_files::hydrate_by_default);
+    let options = checkpoints::AnalysisOptions::DuplicateDirectories {
+        roots,
+        min_similarity,
+        include_hidden,
+        ignored_names,
+        follow_symlinks: follow_symlinks.unwrap_or(false),
+        hydrate,
+    };
+    let checkpointer = match checkpoint_every {
+        Some(every) => {
+            let checkpointer = checkpoints::Checkpointer::start(&checkpoint_data_dir(&app)?, options.clone(), every);
+            info!("Duplicate directory search checkpointed as {}", checkpointer.id());
+            Some(checkpointer)
+        }
+        None => None,
+    };
+    run_analysis(app, options, io_priority.unwrap_or_default(), checkpointer).await
+}
 
-    match handle.join() {
-        Ok(Ok(Ok(text))) => Ok(text),
-        Ok(Ok(Err(e))) => Err(format!(
-            "Failed to extract text from PDF: {}. This PDF may have complex fonts or encoding issues.",
-            e
-        )),
-        Ok(Err(_)) | Err(_) => Err(
-            "Failed to extract text from PDF: The PDF contains unsupported fonts or encoding that cannot be processed.".to_string(),
-        ),
-    }
+fn checkpoint_data_dir(app: &AppHandle) -> Result<std::path::PathBuf, AppError> {
+    app.path_resolver().app_data_dir().ok_or_else(|| AppError::io("Failed to get app data directory"))
 }
 
-fn extract_docx_text(path: &str) -> Result<String, String> {
-    let mut file = fs::File::open(path)
-        .map_err(|e| format!("Failed to open DOCX file: {}", e))?;
-    
-    let mut buffer = Vec::new();
-    file.read_to_end(&mut buffer)
-        .map_err(|e| format!("Failed to read DOCX file: {}", e))?;
-    
-    let docx = read_docx(&buffer)
-        .map_err(|e| format!("Failed to parse DOCX file: {}", e))?;
-    
-    // Extract text from paragraphs
-    let mut text = String::new();
-    for child in &docx.document.children {
-        if let DocumentChild::Paragraph(para) = child {
-            for child in &para.children {
-                if let ParagraphChild::Run(run) = child {
-                    for child in &run.children {
-                        if let RunChild::Text(t) = child {
-                            text.push_str(&t.text);
-                            text.push(' ');
-                        }
-                    }
-                }
-            }
-            text.push('\n');
+async fn run_analysis(
+    app: AppHandle,
+    options: checkpoints::AnalysisOptions,
+    priority: io_priority::IoPriority,
+    mut checkpointer: Option<checkpoints::Checkpointer>,
+) -> Result<duplicate_dirs::DuplicateDirectoryReport, AppError> {
+    let checkpoints::AnalysisOptions::DuplicateDirectories {
+        roots,
+        min_similarity,
+        include_hidden,
+        ignored_names,
+        follow_symlinks,
+        hydrate,
+    } = options;
+    let roots = existing_directories(roots)?;
+    let filter = ScanFilter::new(include_hidden, ignored_names);
+    tauri::async_runtime::spawn_blocking(move || {
+        let report = duplicate_dirs::find(
+            &roots,
+            &filter,
+            follow_symlinks,
+            min_similarity,
+            hydrate,
+            priority,
+            &mut |progress| {
+                let _ = app.emit_all("duplicate-scan-progress", progress);
+            },
+            checkpointer.as_mut(),
+        );
+        if let Some(checkpointer) = checkpointer {
+            checkpointer.finish();
         }
+        report
+    })
+    .await
+    .map_err(|e| AppError::io(format!("Duplicate directory search failed: {}", e)))
+}
+
+// Continues an interrupted analysis from its checkpoint: only files that are new or
+// whose size or mtime changed since are read again. When options are given they must
+// match the ones the analysis was started with.
+#[command]
+async fn resume_analysis(
+    app: AppHandle,
+    checkpoint_id: String,
+    options: Option<checkpoints::AnalysisOptions>,
+    io_priority: Option<io_priority::IoPriority>,
+    checkpoint_every: Option<usize>,
+) -> Result<duplicate_dirs::DuplicateDirectoryReport, AppError> {
+    let app_data_dir = checkpoint_data_dir(&app)?;
+    let checkpoint = checkpoints::load(&app_data_dir, &checkpoint_id)?;
+    if options.as_ref().is_some_and(|options| *options != checkpoint.options) {
+        return Err(AppError::invalid_input(format!(
+            "Checkpoint {} was made with different options; start a new analysis instead",
+            checkpoint_id
+        )));
     }
-    
-    Ok(text)
+    let options = checkpoint.options.clone();
+    let every = checkpoint_every.unwrap_or(checkpoints::DEFAULT_CHECKPOINT_EVERY);
+    let checkpointer = checkpoints::Checkpointer::resume(&app_data_dir, checkpoint, every);
+    run_analysis(app, options, io_priority.unwrap_or_default(), Some(checkpointer)).await
 }
 
-fn extract_xlsx_text(path: &str) -> Result<String, String> {
-    let mut workbook: Xlsx<_> = open_workbook(path)
-        .map_err(|e| format!("Failed to open Excel file: {}", e))?;
-    
-    let mut text = String::new();
-    
-    // Iterate through all sheets
-    for sheet_name in workbook.sheet_names().to_vec() {
-        text.push_str(&format!("Sheet: {}\n", sheet_name));
-        
-        if let Ok(range) = workbook.worksheet_range(&sheet_name) {
-            for row in range.rows() {
-                for cell in row {
-                    // Use get_string() method to convert cell to string
-                    let cell_str = cell.to_string();
-                    if !cell_str.is_empty() {
-                        text.push_str(&cell_str);
-                        text.push('\t');
-                    }
-                }
-                text.push('\n');
-            }
-        }
-        text.push('\n');
+// Interrupted analyses that can be resumed, most recently saved first
+#[command]
+async fn list_checkpoints(app: AppHandle) -> Result<Vec<checkpoints::CheckpointSummary>, AppError> {
+    let app_data_dir = checkpoint_data_dir(&app)?;
+    tauri::async_runtime::spawn_blocking(move || checkpoints::list(&app_data_dir))
+        .await
+        .map_err(|e| AppError::io(format!("Failed to list checkpoints: {}", e)))
+}
+
+// False when there was no such checkpoint
+#[command]
+fn delete_checkpoint(app: AppHandle, checkpoint_id: String) -> Result<bool, AppError> {
+    checkpoints::delete(&checkpoint_data_dir(&app)?, &checkpoint_id)
+}
+
+// Mounted drives and volumes with their space, and whether each is removable or a
+// network share. volume-mounted / volume-unmounted events report changes.
+#[command]
+async fn list_volumes() -> Result<Vec<volumes::Volume>, AppError> {
+    tauri::async_runtime::spawn_blocking(volumes::list)
+        .await
+        .map_err(|e| AppError::io(format!("Failed to list volumes: {}", e)))
+}
+
+// Read rate, in MB/s, for low-priority scans; 0 lifts the limit. Applies right away,
+// including to scans already running.
+#[command]
+fn set_scan_throttle(mb_per_second: f64) -> Result<(), AppError> {
+    if !mb_per_second.is_finite() || mb_per_second < 0.0 {
+        return Err(AppError::invalid_input("mb_per_second must be 0 or more"));
     }
-    
-    Ok(text)
+    io_priority::set_throttle((mb_per_second * 1024.0 * 1024.0) as u64);
+    Ok(())
 }
 
-fn encode_image_base64(path: &str) -> Result<String, String> {
-    let img = image::open(path)
-        .map_err(|e| format!("Failed to open image: {}", e))?;
-    
-    // Resize large images to reduce token usage
-    let (width, height) = img.dimensions();
-    let max_dimension = 1024;
-    
-    let resized_img = if width > max_dimension || height > max_dimension {
-        let scale = max_dimension as f32 / width.max(height) as f32;
-        let new_width = (width as f32 * scale) as u32;
-        let new_height = (height as f32 * scale) as u32;
-        img.resize(new_width, new_height, image::imageops::FilterType::Lanczos3)
-    } else {
-        img
-    };
-    
-    // Encode as JPEG for smaller size
-    let mut buffer = Vec::new();
-    let mut cursor = std::io::Cursor::new(&mut buffer);
-    
-    resized_img.write_to(&mut cursor, image::ImageFormat::Jpeg)
-        .map_err(|e| format!("Failed to encode image: {}", e))?;
-    
-    Ok(base64::engine::general_purpose::STANDARD.encode(&buffer))
+#[command]
+fn get_scan_throttle() -> Result<f64, AppError> {
+    Ok(io_priority::throttle() as f64 / (1024.0 * 1024.0))
 }
 
-#[derive(serde::Serialize)]
-struct FileContent {
-    text: Option<String>,
-    image_base64: Option<String>,
-    mime_type: Option<String>,
+// Baseline organization that needs no LLM: plans every file under path into
+// Year/Month folders by EXIF capture date (when use_exif is set) or modification
+// time. Nothing is moved; the frontend executes the plan with move_file.
+#[command]
+async fn plan_date_organization(
+    path: String,
+    include_subdirectories: bool,
+    granularity: date_organize::DateGranularity,
+    use_exif: bool,
+    include_hidden: Option<bool>,
+    ignored_names: Option<Vec<String>>,
+) -> Result<date_organize::DatePlan, AppError> {
+    let filter = ScanFilter::new(include_hidden, ignored_names);
+    let files: Vec<std::path::PathBuf> = list_files(&path, include_subdirectories, false, &filter)?
+        .iter()
+        .map(|entry| paths::resolve_path(&entry.path))
+        .collect();
+    let base = paths::normalize_path(&path);
+    tauri::async_runtime::spawn_blocking(move || date_organize::plan(&base, &files, granularity, use_exif))
+        .await
+        .map_err(|e| AppError::io(format!("Date planning task failed: {}", e)))
 }
 
+// Nested per-directory overview (counts, sizes, top extensions, sample names) for
+// asking the LLM to propose a folder structure. max_chars bounds the serialized
+// summary; when it's exceeded the deepest directories are left out first.
 #[command]
-async fn read_file_content(path: String) -> Result<String, String> {
-    let path_lower = path.to_lowercase();
-    let content: FileContent;
-    
-    if path_lower.ends_with(".pdf") {
-        // Extract text from PDF
-        let text = extract_pdf_text(&path)?;
-        content = FileContent {
-            text: Some(text),
-            image_base64: None,
-            mime_type: Some("application/pdf".to_string()),
-        };
-    } else if path_lower.ends_with(".docx") {
-        // Extract text from DOCX
-        let text = extract_docx_text(&path)?;
-        content = FileContent {
-            text: Some(text),
-            image_base64: None,
-            mime_type: Some("application/vnd.openxmlformats-officedocument.wordprocessingml.document".to_string()),
-        };
-    } else if path_lower.ends_with(".doc") {
-        // DOC files are not supported by docx-rs, treat as unsupported
-        return Err("DOC format not supported. Please convert to DOCX.".to_string());
-    } else if path_lower.ends_with(".xlsx") || path_lower.ends_with(".xls") {
-        // Extract text from Excel
-        let text = extract_xlsx_text(&path)?;
-        content = FileContent {
-            text: Some(text),
-            image_base64: None,
-            mime_type: Some("application/vnd.openxmlformats-officedocument.spreadsheetml.sheet".to_string()),
-        };
-    } else if path_lower.ends_with(".png") || path_lower.ends_with(".jpg") || 
-              path_lower.ends_with(".jpeg") || path_lower.ends_with(".gif") || 
-              path_lower.ends_with(".bmp") || path_lower.ends_with(".webp") {
-        // Encode image as base64
-        let image_data = encode_image_base64(&path)?;
-        let mime = if path_lower.ends_with(".png") {
-            "image/png"
-        } else if path_lower.ends_with(".jpg") || path_lower.ends_with(".jpeg") {
-            "image/jpeg"
-        } else if path_lower.ends_with(".gif") {
-            "image/gif"
-        } else if path_lower.ends_with(".bmp") {
-            "image/bmp"
-        } else if path_lower.ends_with(".webp") {
-            "image/webp"
-        } else {
-            "image/jpeg"
-        };
-        
-        content = FileContent {
-            text: None,
-            image_base64: Some(image_data),
-            mime_type: Some(mime.to_string()),
-        };
-    } else {
-        // Plain text file
-        let text = fs::read_to_string(&path).map_err(|e| e.to_string())?;
-        content = FileContent {
-            text: Some(text),
-            image_base64: None,
-            mime_type: Some("text/plain".to_string()),
-        };
-    }
-    
-    // Serialize as JSON
-    serde_json::to_string(&content).map_err(|e| format!("Failed to serialize content: {}", e))
+async fn summarize_directory_tree(
+    path: String,
+    max_depth: Option<usize>,
+    max_entries_per_dir: Option<usize>,
+    max_chars: Option<usize>,
+    include_hidden: Option<bool>,
+    ignored_names: Option<Vec<String>>,
+) -> Result<tree_summary::TreeSummary, AppError> {
+    let root = paths::normalize_path(&path);
+    let filter = ScanFilter::new(include_hidden, ignored_names);
+    tauri::async_runtime::spawn_blocking(move || {
+        tree_summary::summarize(
+            &root,
+            &filter,
+            max_depth.unwrap_or(tree_summary::DEFAULT_MAX_DEPTH),
+            max_entries_per_dir.unwrap_or(tree_summary::DEFAULT_MAX_ENTRIES_PER_DIR),
+            max_chars.unwrap_or(tree_summary::DEFAULT_MAX_CHARS),
+        )
+    })
+    .await
+    .map_err(|e| AppError::io(format!("Directory summary task failed: {}", e)))?
+    .map_err(|e| AppError::from(e).with_path(&path))
 }
 
+// Existing folders under search_roots matching each proposed category, scored 0-1 by
+// word similarity of the folder (and ancestor) names. Categories with `matched` false
+// have no good existing folder and are the ones worth asking the user about.
 #[command]
-async fn move_file(from: String, to: String) -> Result<(), String> {
-    let to_path = Path::new(&to);
-    if let Some(parent) = to_path.parent() {
-        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+async fn suggest_destinations(
+    categories: Vec<String>,
+    search_roots: Vec<String>,
+    max_depth: Option<usize>,
+    min_score: Option<f64>,
+    include_hidden: Option<bool>,
+    ignored_names: Option<Vec<String>>,
+) -> Result<destination_suggestions::DestinationSuggestions, AppError> {
+    let roots: Vec<std::path::PathBuf> = search_roots.iter().map(paths::normalize_path).collect();
+    if let Some(missing) = roots.iter().find(|root| !root.is_dir()) {
+        return Err(AppError::not_found(format!("{} is not a directory", missing.display())).with_path(missing));
     }
-    fs::rename(from, to).map_err(|e| e.to_string())
+    let filter = ScanFilter::new(include_hidden, ignored_names);
+    tauri::async_runtime::spawn_blocking(move || {
+        destination_suggestions::suggest(
+            &categories,
+            &roots,
+            &filter,
+            max_depth.unwrap_or(destination_suggestions::DEFAULT_MAX_DEPTH),
+            min_score.unwrap_or(destination_suggestions::DEFAULT_MIN_SCORE),
+        )
+    })
+    .await
+    .map_err(|e| AppError::io(format!("Destination suggestion task failed: {}", e)))
 }
 
-#[tauri::command]
-async fn pick_directory(_app: AppHandle) -> Result<Vec<String>, String> {
-    use rfd::FileDialog;
-    use std::path::PathBuf;
+const ACCESS_PROBE_CLEANUP_ATTEMPTS: u32 = 3;
 
-        // Open the dialog
-    let folders: Option<Vec<PathBuf>> = FileDialog::new()
-        .set_title("Select one or more directories")
-        .pick_folders();
+#[derive(Debug, Clone, Serialize)]
+struct AccessFailure {
+    // "exists", "directory", "list", "write", "cleanup" or "protected_location"
+    check: String,
+    message: String,
+    suggestion: String,
+}
 
-    // Handle the result
-    match folders {
-        Some(paths) => {
-            if paths.is_empty() {
-                // This case might happen if the dialog logic allows "OK" with no selection
-                eprintln!("No directories were selected.");
-                return Err("No directories selected".to_string());
-            } else {
-                eprintln!("You selected the following directories:");
-                let strs: Vec<String> = paths.iter().map(|p| p.to_string_lossy().into_owned()).collect();
-                for p in &strs {
-                    eprintln!("- {}", p);
-                }
-                return Ok(strs);
-            }
-        }
-        None => {
-            // This happens if the user presses "Cancel" or closes the dialog
-            eprintln!("Dialog was canceled. No directories selected.");
-            return Err("User cancelled folder selection".to_string());
-        }
-    }
+#[derive(Debug, Clone, Serialize)]
+struct DirectoryAccessReport {
+    path: String,
+    exists: bool,
+    is_directory: bool,
+    can_list: bool,
+    can_write: bool,
+    // Set when the path is under a macOS privacy-protected folder (Desktop, Documents, Downloads)
+    protected_location: Option<String>,
+    failures: Vec<AccessFailure>,
 }
 
-#[tauri::command]
-async fn open_file(path: String) -> Result<(), String> {
-    use std::process::Command;
-    
-    #[cfg(target_os = "macos")]
-    {
-        Command::new("open")
-            .arg(&path)
-            .spawn()
-            .map_err(|e| format!("Failed to open file: {}", e))?;
-    }
-    
-    #[cfg(target_os = "windows")]
-    {
-        Command::new("cmd")
-            .args(["/C", "start", "", &path])
-            .spawn()
-            .map_err(|e| format!("Failed to open file: {}", e))?;
+impl DirectoryAccessReport {
+    fn fail(&mut self, check: &str, message: String, suggestion: &str) {
+        self.failures.push(AccessFailure {
+            check: check.to_string(),
+            message,
+            suggestion: suggestion.to_string(),
+        });
     }
-    
-    #[cfg(target_os = "linux")]
-    {
-        Command::new("xdg-open")
-            .arg(&path)
-            .spawn()
-            .map_err(|e| format!("Failed to open file: {}", e))?;
+}
+
+// macOS gates these folders behind privacy (TCC) permissions, which show up as
+// "Operation not permitted" rather than a normal permissions problem
+fn tcc_protected_folder(path: &Path) -> Option<String> {
+    if !cfg!(target_os = "macos") {
+        return None;
     }
-    
-    Ok(())
+    let home = dirs::home_dir()?;
+    ["Desktop", "Documents", "Downloads"]
+        .iter()
+        .find(|folder| path.starts_with(home.join(folder)))
+        .map(|folder| folder.to_string())
 }
 
-// Managed LLM Server Commands
+// Creates and removes a hidden file to prove the directory is writable. Deletion is
+// retried so a transient lock (antivirus, indexer) doesn't leave the probe behind.
+fn probe_directory_write(dir: &Path) -> Result<(), (String, std::io::Error)> {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    let probe_path = dir.join(format!(".file-organizer-access-check-{}-{}", std::process::id(), nanos));
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct AppVersionInfo {
-    pub version: String,
-    pub build_timestamp: String,
-}
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&probe_path)
+        .map_err(|e| ("write".to_string(), e))?;
+    let write_result = file.write_all(b"access check");
+    drop(file);
 
-#[command]
-fn get_app_version() -> AppVersionInfo {
-    AppVersionInfo {
-        version: env!("CARGO_PKG_VERSION").to_string(),
-        build_timestamp: env!("BUILD_TIMESTAMP").to_string(),
-    }
+    let mut attempt = 0;
+    let cleanup_result = loop {
+        attempt += 1;
+        match fs::remove_file(&probe_path) {
+            Ok(()) => break Ok(()),
+            Err(e) if attempt < ACCESS_PROBE_CLEANUP_ATTEMPTS => {
+                warn!("Failed to remove access probe {} (attempt {}): {}", probe_path.display(), attempt, e);
+                thread::sleep(std::time::Duration::from_millis(100 * attempt as u64));
+            }
+            Err(e) => break Err(e),
+        }
+    };
+
+    write_result.map_err(|e| ("write".to_string(), e))?;
+    cleanup_result.map_err(|e| ("cleanup".to_string(), e))
 }
 
+// Checks up front that a directory can be scanned and organized, so permission
+// problems are reported once instead of file by file during a run
 #[command]
-async fn get_llm_server_status(app: AppHandle, state: State<'_, ManagedLLMState>) -> Result<ManagedLLMServerInfo, String> {
-    let app_data_dir = app.path_resolver()
-        .app_data_dir()
-        .ok_or("Could not get app data directory")?;
-    
-    let server_dir = app_data_dir.join("llm-server");
-    
-    // Try multiple possible locations for the server executable
-    let possible_paths = if cfg!(target_os = "windows") {
-        vec![
-            server_dir.join("llama_server").join("llama_server.exe"),
-            server_dir.join("llama_server.exe"),
-            server_dir.join("llama_server").join("llama_server").join("llama_server.exe"),
-        ]
-    } else if cfg!(target_os = "macos") {
-        vec![
-            server_dir.join("mlx_server").join("mlx_server"),
-        ]
-    } else {
-        vec![
-            server_dir.join("llama_server").join("llama_server"),
-            server_dir.join("llama_server"),
-            server_dir.join("llama_server").join("llama_server").join("llama_server"),
-        ]
+async fn check_directory_access(path: String) -> Result<DirectoryAccessReport, AppError> {
+    let dir = paths::normalize_path(&path);
+    let mut report = DirectoryAccessReport {
+        path: path.clone(),
+        exists: false,
+        is_directory: false,
+        can_list: false,
+        can_write: false,
+        protected_location: tcc_protected_folder(Path::new(&path)),
+        failures: Vec::new(),
     };
-    
-    let server_exe = possible_paths.iter().find(|path| path.exists()).cloned();
-    
-    eprintln!("Checking for server executable in possible paths:");
-    for (i, path) in possible_paths.iter().enumerate() {
-        eprintln!("  {}: {} (exists: {})", i, path.to_string_lossy(), path.exists());
-    }
-    eprintln!("Server directory exists: {}", server_dir.exists());
-    if server_dir.exists() {
-        eprintln!("Contents of server directory:");
-        if let Ok(entries) = fs::read_dir(&server_dir) {
-            for entry in entries.flatten() {
-                eprintln!("  - {}", entry.path().to_string_lossy());
-            }
-        }
-    }
 
-    let server_exe = match server_exe {
-        Some(path) => path,
-        None => {
-            return Ok(ManagedLLMServerInfo {
-                status: "not_downloaded".to_string(),
-                version: None,
-                path: None,
-                port: None,
-                error: None,
-            });
+    match fs::metadata(&dir) {
+        Ok(metadata) => {
+            report.exists = true;
+            report.is_directory = metadata.is_dir();
         }
-    };
-
-    // Try to read stored version, fallback to hardcoded "1.0.0" if not found
-    let stored_version = read_downloaded_version(&app_data_dir)
-        .or_else(|| Some("1.0.0".to_string()));
-
-    // Get the host and port from the stored config, or use defaults
-    let (host, port) = {
-        let state_guard = state.lock().unwrap();
-        if let Some((_, process_info)) = state_guard.as_ref() {
-            (process_info.config.host.clone(), process_info.config.port)
-        } else {
-            ("127.0.0.1".to_string(), 8000)
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            report.fail("exists", format!("{} does not exist", path), "Check the path, or pick the folder again if it was moved or renamed.");
+            return Ok(report);
         }
-    };
+        Err(e) => {
+            // Exists but can't be inspected, typically a permissions problem on a parent
+            report.exists = true;
+            report.fail("exists", format!("Cannot read {}: {}", path, e), "Make sure your user account can access this folder and its parent folders.");
+            return Ok(report);
+        }
+    }
+    if !report.is_directory {
+        report.fail("directory", format!("{} is not a directory", path), "Select a folder rather than a file.");
+        return Ok(report);
+    }
 
-    // Check if process is running by trying to connect
-    let client = reqwest::Client::new();
-    let test_url = format!("http://{}:{}/v1/models", host, port);
-    
-    eprintln!("Testing server health at: {}", test_url);
-    match client.get(&test_url).timeout(std::time::Duration::from_secs(5)).send().await {
-        Ok(response) => {
-            eprintln!("Server responded with status: {}", response.status());
-            let status_code = response.status();
-            // Read the response body to properly close the connection
-            let _ = response.bytes().await;
-            
-            if status_code.is_success() {
-                Ok(ManagedLLMServerInfo {
-                    status: "running".to_string(),
-                    version: stored_version.clone(),
-                    path: Some(server_exe.to_string_lossy().to_string()),
-                    port: Some(port),
-                    error: None,
-                })
+    match fs::read_dir(&dir) {
+        Ok(_) => report.can_list = true,
+        Err(e) => {
+            let denied = e.kind() == std::io::ErrorKind::PermissionDenied;
+            if let (true, Some(folder)) = (denied, report.protected_location.clone()) {
+                report.fail(
+                    "protected_location",
+                    format!("macOS is blocking access to your {} folder: {}", folder, e),
+                    "Open System Settings > Privacy & Security > Full Disk Access (or Files and Folders) and allow File Organizer, then restart the app.",
+                );
             } else {
-                eprintln!("Server responded but with error status: {}", status_code);
-                Ok(ManagedLLMServerInfo {
-                    status: "downloaded".to_string(),
-                    version: stored_version.clone(),
-                    path: Some(server_exe.to_string_lossy().to_string()),
-                    port: Some(port),
-                    error: Some(format!("Server responded with status: {}", status_code)),
-                })
+                report.fail("list", format!("Cannot list {}: {}", path, e), "Check that your user account has read permission on this folder.");
             }
+            return Ok(report);
         }
-        Err(e) => {
-            eprintln!("Failed to connect to server: {}", e);
-            Ok(ManagedLLMServerInfo {
-                status: "downloaded".to_string(),
-                version: stored_version.clone(),
-                path: Some(server_exe.to_string_lossy().to_string()),
-                port: Some(port),
-                error: Some(format!("Connection failed: {}", e)),
-            })
+    }
+
+    match probe_directory_write(&dir) {
+        Ok(()) => report.can_write = true,
+        Err((check, e)) if check == "cleanup" => {
+            // The write itself worked; only the probe file was left behind
+            report.can_write = true;
+            report.fail(
+                "cleanup",
+                format!("Could not remove the temporary access check file in {}: {}", path, e),
+                "Delete the hidden .file-organizer-access-check file from this folder manually.",
+            );
+        }
+        Err((_, e)) => {
+            let suggestion = if e.kind() == std::io::ErrorKind::PermissionDenied && report.protected_location.is_some() {
+                "Allow File Organizer under System Settings > Privacy & Security > Full Disk Access, then restart the app."
+            } else if e.kind() == std::io::ErrorKind::PermissionDenied {
+                "Files can be scanned but not moved. Grant write permission on this folder or choose another one."
+            } else {
+                "Files can be scanned but not moved. Check that the disk isn't read-only or full."
+            };
+            report.fail("write", format!("Cannot write to {}: {}", path, e), suggestion);
         }
     }
+
+    Ok(report)
 }
 
-// Helper function to check if Vulkan runtime is available on Windows
-#[cfg(target_os = "windows")]
-fn is_vulkan_available() -> bool {
-    use std::path::PathBuf;
-    
-    // Check for vulkan-1.dll in System32
-    let system32 = std::env::var("SystemRoot")
-        .map(|root| PathBuf::from(root).join("System32"))
-        .unwrap_or_else(|_| PathBuf::from("C:\\Windows\\System32"));
-    
-    let vulkan_dll = system32.join("vulkan-1.dll");
-    
-    if vulkan_dll.exists() {
-        eprintln!("Vulkan runtime detected at: {}", vulkan_dll.display());
-        return true;
-    }
-    
-    eprintln!("Vulkan runtime not found in System32");
-    false
+const HTTP_DEFAULT_TIMEOUT_MS: u64 = 120_000;
+const HTTP_DEFAULT_RETRIES: u32 = 2;
+
+#[derive(Debug, Clone, Serialize)]
+struct HttpResponse {
+    status: u16,
+    // Lowercased header names; repeated headers are joined with ", "
+    headers: HashMap<String, String>,
+    // Base64-encoded when the request set response_as_base64
+    body: String,
+    // Final URL after any redirects
+    url: String,
+    // Number of attempts made, including retries
+    attempts: u32,
 }
 
-#[cfg(target_os = "linux")]
-fn is_vulkan_available() -> bool {
-    use std::process::Command;
+// Names the failure class (timeout, DNS, connection, TLS) so logs say what went wrong
+fn describe_request_error(e: &reqwest::Error) -> String {
+    let mut details = e.to_string();
+    let mut source = std::error::Error::source(e);
+    while let Some(inner) = source {
+        details.push_str(": ");
+        details.push_str(&inner.to_string());
+        source = inner.source();
+    }
+    let lowered = details.to_lowercase();
 
-    let output = match Command::new("vulkaninfo").output() {
-        Ok(output) => output,
-        Err(e) => {
-            eprintln!("Failed to run vulkaninfo: {}", e);
-            return false;
-        }
-    };
-    if output.status.success() {
-        eprintln!("Vulkan runtime detected");
-        return true;
+    if e.is_timeout() {
+        format!("Request timed out: {}", details)
+    } else if lowered.contains("dns error") || lowered.contains("failed to lookup address") {
+        format!("DNS lookup failed: {}", details)
+    } else if lowered.contains("certificate") || lowered.contains("tls") || lowered.contains("ssl") {
+        format!("TLS error: {}", details)
+    } else if e.is_connect() {
+        format!("Connection failed: {}", details)
+    } else {
+        format!("Request failed: {}", details)
     }
-    eprintln!("Vulkan runtime not found");
-    return false;
 }
 
-// Stub function for macOS - Vulkan is not used on macOS, but this function
-// needs to exist for the type checker even though it will never be called
-#[cfg(target_os = "macos")]
-fn is_vulkan_available() -> bool {
-    false
+// In local-only mode, requests with a body may only go to this machine
+#[command]
+#[allow(clippy::too_many_arguments)]
+async fn http_request(
+    app: AppHandle,
+    url: String,
+    method: String,
+    headers: std::collections::HashMap<String, String>,
+    body: Option<String>,
+    body_base64: Option<String>,
+    timeout_ms: Option<u64>,
+    retries: Option<u32>,
+    response_as_base64: Option<bool>,
+) -> Result<HttpResponse, AppError> {
+    let carries_payload = body.is_some() || body_base64.is_some();
+    let client = match guard_outbound(&app, &url, carries_payload).await? {
+        Some(pinned) => pinned,
+        None => network::build_http_client(),
+    };
+    send_http_request(client, url, method, headers, body, body_base64, timeout_ms, retries, response_as_base64)
+        .await
 }
 
+// The local-only check for a request, with an audit record when it's refused
+async fn guard_outbound(
+    app: &AppHandle,
+    url: &str,
+    carries_payload: bool,
+) -> Result<Option<reqwest::Client>, AppError> {
+    local_only::guard_request(url, carries_payload).await.inspect_err(|e| {
+        warn!("{}", e.message);
+        audit(app, || audit::AuditEvent::new(audit::AuditAction::Blocked, url, 0));
+    })
+}
 
-#[command]
-async fn download_llm_server(app: AppHandle, version: String) -> Result<String, String> {
-    let app_data_dir = app.path_resolver()
-        .app_data_dir()
-        .ok_or("Could not get app data directory")?;
-    
-    let server_dir = app_data_dir.join("llm-server");
-    fs::create_dir_all(&server_dir).map_err(|e| format!("Failed to create server directory: {}", e))?;
+#[allow(clippy::too_many_arguments)]
+async fn send_http_request(
+    client: reqwest::Client,
+    url: String,
+    method: String,
+    headers: std::collections::HashMap<String, String>,
+    body: Option<String>,
+    body_base64: Option<String>,
+    timeout_ms: Option<u64>,
+    retries: Option<u32>,
+    response_as_base64: Option<bool>,
+) -> Result<HttpResponse, AppError> {
+    let method = match method.to_uppercase().as_str() {
+        "GET" => reqwest::Method::GET,
+        "POST" => reqwest::Method::POST,
+        "PUT" => reqwest::Method::PUT,
+        "DELETE" => reqwest::Method::DELETE,
+        "PATCH" => reqwest::Method::PATCH,
+        "HEAD" => reqwest::Method::HEAD,
+        _ => return Err(AppError::unsupported(format!("Unsupported HTTP method: {}", method))),
+    };
 
-    // Determine platform and download URL
-    let (filename, extract_dir) = if cfg!(target_os = "windows") {
-        // Check if Vulkan is available for Windows
-        if is_vulkan_available() {
-            eprintln!("Using Vulkan-enabled server");
-            ("llama_server-windows-vulkan.zip", "llama_server")
-        } else {
-            eprintln!("Using CPU-only server (Vulkan not available)");
-            ("llama_server-windows-cpu.zip", "llama_server")
+    // Binary payloads come in base64 since invoke arguments are JSON
+    let body_bytes = match (body, body_base64) {
+        (Some(_), Some(_)) => return Err(AppError::invalid_input("Pass either body or body_base64, not both")),
+        (Some(text), None) => Some(text.into_bytes()),
+        (None, Some(encoded)) => Some(
+            base64::engine::general_purpose::STANDARD
+                .decode(encoded)
+                .map_err(|e| AppError::invalid_input(format!("Invalid body_base64: {}", e)))?
+        ),
+        (None, None) => None,
+    };
+
+    let timeout = std::time::Duration::from_millis(timeout_ms.unwrap_or(HTTP_DEFAULT_TIMEOUT_MS));
+    // Only idempotent methods are safe to send twice
+    let idempotent = matches!(
+        method,
+        reqwest::Method::GET | reqwest::Method::HEAD | reqwest::Method::PUT | reqwest::Method::DELETE
+    );
+    let max_attempts = if idempotent { retries.unwrap_or(HTTP_DEFAULT_RETRIES) + 1 } else { 1 };
+
+    let mut attempt = 0;
+    let response = loop {
+        attempt += 1;
+        let mut request = client.request(method.clone(), &url).timeout(timeout);
+        for (key, value) in &headers {
+            request = request.header(key, value);
         }
-    } else if cfg!(target_os = "macos") {
-        ("mlx_server-macos.zip", "mlx_server")
-    } else {
-        if is_vulkan_available() {
-            eprintln!("Using Vulkan-enabled server");
-            ("llama_server-linux-vulkan.tar.gz", "llama_server")
-        } else {
-            eprintln!("Using CPU-only server (Vulkan not available)");
-            ("llama_server-linux-cpu.tar.gz", "llama_server")
+        if let Some(bytes) = &body_bytes {
+            request = request.body(bytes.clone());
         }
+
+        let retry_reason = match request.send().await {
+            Ok(response) if response.status().is_server_error() && attempt < max_attempts => {
+                format!("HTTP {}", response.status().as_u16())
+            }
+            Ok(response) => break response,
+            Err(e) if e.is_connect() && attempt < max_attempts => describe_request_error(&e),
+            Err(e) => return Err(e.into()),
+        };
+
+        let delay = std::time::Duration::from_millis(500u64 << (attempt - 1).min(6));
+        warn!(
+            "{} {} failed ({}), retrying in {}ms (attempt {}/{})",
+            method, url, retry_reason, delay.as_millis(), attempt + 1, max_attempts
+        );
+        tokio::time::sleep(delay).await;
     };
 
-    let download_url = format!(
-        "https://github.com/BorisBesky/file-organizer-desktop/releases/download/llm-v{}/{}",
-        version, filename
-    );
-    eprintln!("Download URL: {}", download_url);
-    eprintln!("Version: {}", version);
-    eprintln!("Filename: {}", filename);
-    eprintln!("Extract dir: {}", extract_dir);
-    eprintln!("Server dir: {}", server_dir.to_string_lossy());
+    let status = response.status().as_u16();
+    let final_url = response.url().to_string();
 
-    let archive_path = server_dir.join(filename);
+    // HeaderMap names are already lowercase
+    let mut response_headers: HashMap<String, String> = HashMap::new();
+    for name in response.headers().keys() {
+        let values: Vec<String> = response.headers()
+            .get_all(name)
+            .iter()
+            .map(|v| String::from_utf8_lossy(v.as_bytes()).into_owned())
+            .collect();
+        response_headers.insert(name.as_str().to_string(), values.join(", "));
+    }
     
-    // Download the file
-    let client = reqwest::Client::new();
-    let response = client.get(&download_url)
-        .send()
+    let bytes = response
+        .bytes()
         .await
-        .map_err(|e| format!("Failed to download server: {}", e))?;
+        .map_err(|e| AppError::from_request(&e, format!("Failed to read response: {}", describe_request_error(&e))))?;
+    let text = if response_as_base64.unwrap_or(false) {
+        base64::engine::general_purpose::STANDARD.encode(&bytes)
+    } else {
+        String::from_utf8_lossy(&bytes).into_owned()
+    };
 
-    if !response.status().is_success() {
-        return Err(format!("Download failed with status: {}", response.status()));
+    // Non-2xx responses are returned as-is; the caller decides what counts as an error
+    Ok(HttpResponse {
+        status,
+        headers: response_headers,
+        body: text,
+        url: final_url,
+        attempts: attempt,
+    })
+}
+
+// In-flight streaming requests by request_id, so they can be cancelled
+type HttpStreamState = Arc<Mutex<HashMap<String, tauri::async_runtime::JoinHandle<()>>>>;
+
+// Frames are batched into one event per interval so a fast stream can't flood the webview
+const HTTP_STREAM_FLUSH_INTERVAL: std::time::Duration = std::time::Duration::from_millis(50);
+
+#[derive(Clone, Serialize)]
+struct HttpStreamChunk {
+    request_id: String,
+    // SSE data payloads in arrival order; several frames may be coalesced into one event
+    frames: Vec<String>,
+}
+
+#[derive(Clone, Serialize)]
+struct HttpStreamDone {
+    request_id: String,
+    cancelled: bool,
+}
+
+#[derive(Clone, Serialize)]
+struct HttpStreamError {
+    request_id: String,
+    status: Option<u16>,
+    error: String,
+}
+
+// Pulls complete SSE events out of `buffer` and returns the data payload of each.
+// Multi-line data fields are joined with newlines; other fields are ignored.
+fn drain_sse_events(buffer: &mut Vec<u8>) -> Vec<String> {
+    let mut events = Vec::new();
+    while let Some(end) = buffer.windows(2).position(|w| w == b"\n\n") {
+        let block: Vec<u8> = buffer.drain(..end + 2).collect();
+        let block = String::from_utf8_lossy(&block);
+        let data: Vec<&str> = block
+            .lines()
+            .filter_map(|line| line.strip_prefix("data:"))
+            .map(|value| value.strip_prefix(' ').unwrap_or(value))
+            .collect();
+        if !data.is_empty() {
+            events.push(data.join("\n"));
+        }
     }
+    events
+}
 
-    let mut file = fs::File::create(&archive_path)
-        .map_err(|e| format!("Failed to create archive file: {}", e))?;
-    
-    let content = response.bytes().await
-        .map_err(|e| format!("Failed to read response: {}", e))?;
-    
-    std::io::copy(&mut content.as_ref(), &mut file)
-        .map_err(|e| format!("Failed to write archive: {}", e))?;
+async fn run_http_stream(
+    window: &tauri::Window,
+    request_id: &str,
+    request: reqwest::RequestBuilder,
+) -> Result<(), HttpStreamError> {
+    let stream_error = |status: Option<u16>, error: String| HttpStreamError {
+        request_id: request_id.to_string(),
+        status,
+        error,
+    };
 
-    // Extract the archive
-    let extract_path = server_dir.join(extract_dir);
-    if extract_path.exists() {
-        fs::remove_dir_all(&extract_path)
-            .map_err(|e| format!("Failed to remove existing server: {}", e))?;
+    let mut response = request.send().await
+        .map_err(|e| stream_error(None, describe_request_error(&e)))?;
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        return Err(stream_error(Some(status.as_u16()), format!("HTTP {}: {}", status.as_u16(), body)));
     }
 
-    if filename.ends_with(".zip") {
-        #[cfg(unix)]
-        {
-            // Use system unzip on Unix (macOS/Linux) - properly preserves symlinks and permissions
-            use std::process::Command;
-            
-            let output = Command::new("unzip")
-                .args(&["-o", archive_path.to_str().unwrap(), "-d", server_dir.to_str().unwrap()])
-                .output()
-                .map_err(|e| format!("Failed to run unzip: {}", e))?;
-            
-            if !output.status.success() {
-                return Err(format!(
-                    "Failed to extract ZIP: {}",
-                    String::from_utf8_lossy(&output.stderr)
-                ));
-            }
-            
-            eprintln!("Extracted ZIP using system unzip");
+    let mut buffer: Vec<u8> = Vec::new();
+    let mut pending: Vec<String> = Vec::new();
+    let mut last_flush = std::time::Instant::now();
+    loop {
+        let chunk = response.chunk().await
+            .map_err(|e| stream_error(Some(status.as_u16()), describe_request_error(&e)))?;
+        let finished = chunk.is_none();
+        if let Some(chunk) = chunk {
+            // CRLF line endings are normalised so frames split on a blank line
+            buffer.extend(chunk.iter().filter(|b| **b != b'\r'));
+            pending.extend(drain_sse_events(&mut buffer));
         }
-        
-        #[cfg(not(unix))]
-        {
-            // Extract ZIP file using Rust library on Windows
-            let file = fs::File::open(&archive_path)
-                .map_err(|e| format!("Failed to open ZIP file: {}", e))?;
-            let mut archive = ZipArchive::new(file)
-                .map_err(|e| format!("Failed to read ZIP archive: {}", e))?;
-
-            for i in 0..archive.len() {
-                let mut file = archive.by_index(i)
-                    .map_err(|e| format!("Failed to read file from ZIP: {}", e))?;
-                let outpath = extract_path.join(file.name());
-                
-                if file.name().ends_with('/') {
-                    fs::create_dir_all(&outpath)
-                        .map_err(|e| format!("Failed to create directory: {}", e))?;
-                } else {
-                    if let Some(p) = outpath.parent() {
-                        fs::create_dir_all(p)
-                            .map_err(|e| format!("Failed to create parent directory: {}", e))?;
-                    }
-                    let mut outfile = fs::File::create(&outpath)
-                        .map_err(|e| format!("Failed to create file: {}", e))?;
-                    std::io::copy(&mut file, &mut outfile)
-                        .map_err(|e| format!("Failed to extract file: {}", e))?;
-                }
-            }
+
+        if !pending.is_empty() && (finished || last_flush.elapsed() >= HTTP_STREAM_FLUSH_INTERVAL) {
+            let _ = window.emit("http-stream-chunk", HttpStreamChunk {
+                request_id: request_id.to_string(),
+                frames: std::mem::take(&mut pending),
+            });
+            last_flush = std::time::Instant::now();
+        }
+
+        if finished {
+            return Ok(());
         }
-    } else if filename.ends_with(".tar.gz") {
-        // Extract TAR.GZ file (Linux/macOS)
-        let file = fs::File::open(&archive_path)
-            .map_err(|e| format!("Failed to open TAR.GZ file: {}", e))?;
-        let gz = GzDecoder::new(file);
-        let mut archive = Archive::new(gz);
-        
-        archive.unpack(&server_dir)
-            .map_err(|e| format!("Failed to extract TAR.GZ: {}", e))?;
     }
+}
 
-    // Clean up archive file
-    fs::remove_file(&archive_path)
-        .map_err(|e| format!("Failed to remove archive: {}", e))?;
+// Starts a streaming request and forwards its Server-Sent Events to the calling window
+// as http-stream-chunk events, ending with http-stream-done or http-stream-error
+#[command]
+#[allow(clippy::too_many_arguments)]
+async fn http_request_stream(
+    window: tauri::Window,
+    streams: State<'_, HttpStreamState>,
+    request_id: String,
+    url: String,
+    method: String,
+    headers: HashMap<String, String>,
+    body: Option<String>,
+    timeout_ms: Option<u64>,
+) -> Result<(), AppError> {
+    let client = match guard_outbound(&window.app_handle(), &url, body.is_some()).await? {
+        Some(pinned) => pinned,
+        None => network::build_http_client(),
+    };
+    let mut request = match method.to_uppercase().as_str() {
+        "GET" => client.get(&url),
+        "POST" => client.post(&url),
+        _ => return Err(AppError::unsupported(format!("Unsupported HTTP method for streaming: {}", method))),
+    };
+    for (key, value) in headers {
+        request = request.header(key, value);
+    }
+    if let Some(body_content) = body {
+        request = request.body(body_content);
+    }
+    // Streams run long, so only bound them when the caller asks
+    if let Some(timeout_ms) = timeout_ms {
+        request = request.timeout(std::time::Duration::from_millis(timeout_ms));
+    }
 
-    eprintln!("Extraction completed. Checking extracted files:");
-    if extract_path.exists() {
-        eprintln!("Extract path exists: {}", extract_path.to_string_lossy());
-        if let Ok(entries) = fs::read_dir(&extract_path) {
-            for entry in entries.flatten() {
-                eprintln!("  - {}", entry.path().to_string_lossy());
+    let streams_handle = streams.inner().clone();
+    let mut active = streams.lock().unwrap();
+    if active.contains_key(&request_id) {
+        return Err(AppError::invalid_input(format!("A stream with id {} is already running", request_id)));
+    }
+
+    let task_request_id = request_id.clone();
+    let handle = tauri::async_runtime::spawn(async move {
+        let result = run_http_stream(&window, &task_request_id, request).await;
+        streams_handle.lock().unwrap().remove(&task_request_id);
+        match result {
+            Ok(()) => {
+                let _ = window.emit("http-stream-done", HttpStreamDone {
+                    request_id: task_request_id,
+                    cancelled: false,
+                });
+            }
+            Err(e) => {
+                warn!("Stream {} failed: {}", e.request_id, e.error);
+                let _ = window.emit("http-stream-error", e);
             }
         }
-    } else {
-        eprintln!("Extract path does not exist: {}", extract_path.to_string_lossy());
-    }
+    });
+    active.insert(request_id, handle);
+    Ok(())
+}
 
-    // Make executable on Unix systems
-    #[cfg(unix)]
-    {
-        let server_exe = if cfg!(target_os = "macos") {
-            extract_path.join("mlx_server")
-        } else {
-            extract_path.join("llama_server")
-        };
-        
-        use std::os::unix::fs::PermissionsExt;
-        let mut perms = fs::metadata(&server_exe)
-            .map_err(|e| format!("Failed to get file metadata: {}", e))?
-            .permissions();
-        perms.set_mode(0o755);
-        fs::set_permissions(&server_exe, perms)
-            .map_err(|e| format!("Failed to set executable permissions: {}", e))?;
-    }
+#[command]
+fn cancel_http_stream(
+    window: tauri::Window,
+    streams: State<'_, HttpStreamState>,
+    request_id: String,
+) -> Result<(), AppError> {
+    let handle = streams.lock().unwrap().remove(&request_id)
+        .ok_or_else(|| AppError::not_found(format!("No stream with id {}", request_id)))?;
+    // Aborting the task drops the response, which closes the connection
+    handle.abort();
+    let _ = window.emit("http-stream-done", HttpStreamDone {
+        request_id,
+        cancelled: true,
+    });
+    Ok(())
+}
 
-    // Store the downloaded version
-    if let Err(e) = store_downloaded_version(&app_data_dir, &version) {
-        eprintln!("Warning: Failed to store version metadata: {}", e);
+#[command]
+fn get_network_config() -> Result<network::NetworkConfig, AppError> {
+    Ok(network::current_config())
+}
+
+// Takes effect immediately for every later request; in-flight requests keep their client
+#[command]
+fn set_network_config(app: AppHandle, config: network::NetworkConfig) -> Result<(), AppError> {
+    let app_data_dir = app.path_resolver().app_data_dir()
+        .ok_or_else(|| AppError::io("Failed to get app data directory"))?;
+    network::apply_config(config.clone()).map_err(AppError::invalid_input)?;
+    network::save_config(&app_data_dir, &config).map_err(AppError::io)
+}
+
+#[command]
+fn get_destination_policy() -> Result<destination_policy::DestinationPolicy, AppError> {
+    Ok(destination_policy::current_policy())
+}
+
+#[command]
+fn set_destination_policy(policy: destination_policy::DestinationPolicy) -> Result<(), AppError> {
+    settings::update(|settings| {
+        settings.destination_policy = policy;
+        Ok(())
+    })
+    .map(|_| ())
+    .map_err(AppError::io)
+}
+
+// The directories being organized; moves are confined to them unless the policy lists
+// its own allowed roots
+#[command]
+fn set_organization_roots(roots: Vec<String>) -> Result<(), AppError> {
+    destination_policy::set_organization_roots(roots.iter().map(|root| paths::resolve_path(root)).collect());
+    Ok(())
+}
+
+// Pushes settings that live in managed state rather than being read on use
+fn apply_runtime_settings(limiter: &ExtractionLimiterState, settings: &settings::Settings) {
+    limiter.set_permits(settings.extraction.concurrency.unwrap_or_else(extraction::default_concurrency));
+}
+
+#[command]
+fn get_settings() -> Result<settings::Settings, AppError> {
+    Ok(settings::current())
+}
+
+// Replaces every setting; older layouts are migrated first
+#[command]
+fn set_settings(
+    limiter: State<'_, ExtractionLimiterState>,
+    settings: serde_json::Value,
+) -> Result<settings::Settings, AppError> {
+    let settings = settings::migrate(settings).map_err(AppError::invalid_input)?;
+    let saved = settings::replace(settings).map_err(AppError::io)?;
+    apply_runtime_settings(&limiter, &saved);
+    Ok(saved)
+}
+
+// Merges `patch` into the saved settings; a null value resets that field
+#[command]
+fn patch_settings(
+    limiter: State<'_, ExtractionLimiterState>,
+    patch: serde_json::Value,
+) -> Result<settings::Settings, AppError> {
+    if !patch.is_object() {
+        return Err(AppError::invalid_input("A settings patch must be a JSON object"));
     }
+    let saved = settings::patch(&patch).map_err(AppError::invalid_input)?;
+    apply_runtime_settings(&limiter, &saved);
+    Ok(saved)
+}
 
-    Ok(extract_path.to_string_lossy().to_string())
+#[command]
+fn export_settings(path: String) -> Result<(), AppError> {
+    let exported = settings::without_secrets(&settings::current());
+    settings::write_to(&paths::resolve_path(&path), &exported).map_err(|e| AppError::io(e).with_path(&path))
 }
 
 #[command]
-async fn update_llm_server(
-    app: AppHandle,
-    version: String,
-    config: ManagedLLMConfig,
-    state: State<'_, ManagedLLMState>
-) -> Result<String, String> {
-    eprintln!("Starting LLM server update to version: {}", version);
-    
-    let app_data_dir = app.path_resolver()
-        .app_data_dir()
-        .ok_or("Could not get app data directory")?;
+fn import_settings(
+    limiter: State<'_, ExtractionLimiterState>,
+    path: String,
+) -> Result<settings::Settings, AppError> {
+    let imported = settings::read_from(&paths::resolve_path(&path))
+        .map_err(|e| AppError::invalid_input(e).with_path(&path))?;
+    let saved = settings::replace(imported).map_err(AppError::io)?;
+    apply_runtime_settings(&limiter, &saved);
+    Ok(saved)
+}
+
+// Language for notifications and messages built by the backend. Tags like "de-AT" use
+// their language; unsupported ones are rejected. Returns the locale now in use.
+#[command]
+fn set_locale(locale: String) -> Result<String, AppError> {
+    let supported = i18n::supported_locale(&locale).ok_or_else(|| {
+        AppError::unsupported(format!(
+            "Locale \"{}\" isn't supported; choose one of {}",
+            locale,
+            i18n::supported_locales().join(", ")
+        ))
+    })?;
+    settings::update(|settings| {
+        settings.locale = Some(supported.to_string());
+        Ok(())
+    })
+    .map_err(AppError::io)?;
+    Ok(supported.to_string())
+}
+
+#[command]
+fn get_locale() -> Result<String, AppError> {
+    Ok(i18n::current_locale().to_string())
+}
+
+// Tries `config` (or the active settings) against `url` without saving anything
+#[command]
+async fn test_network_config(
+    url: String,
+    config: Option<network::NetworkConfig>,
+) -> Result<network::NetworkTestResult, AppError> {
+    let config = config.unwrap_or_else(network::current_config);
+    network::test_config(&config, &url).await.map_err(AppError::invalid_input)
+}
+
+// JPEG preview of an image, or of an MP4/MOV's embedded cover art, at most max_px on
+// its longer side (256 by default)
+#[command]
+async fn generate_thumbnail(app: AppHandle, path: String, max_px: Option<u32>) -> Result<thumbnails::Thumbnail, AppError> {
+    let app_data_dir = app.path_resolver().app_data_dir()
+        .ok_or_else(|| AppError::not_found("Failed to get app data directory"))?;
+    let fs_path = paths::resolve_path(&path);
+    cloud_files::ensure_local(&fs_path, cloud_files::hydrate_by_default()).map_err(|e| e.with_path(&path))?;
+    let max_px = max_px.unwrap_or(thumbnails::DEFAULT_MAX_PX);
+    tokio::task::spawn_blocking(move || thumbnails::thumbnail(&app_data_dir, &fs_path, max_px))
+        .await
+        .map_err(|e| AppError::io(format!("Failed to generate thumbnail: {}", e)))?
+        .map_err(|e| e.with_path(&path))
+}
+
+#[command]
+async fn clear_thumbnail_cache(app: AppHandle) -> Result<(), AppError> {
+    let app_data_dir = app.path_resolver().app_data_dir()
+        .ok_or_else(|| AppError::not_found("Failed to get app data directory"))?;
+    thumbnails::clear_cache(&app_data_dir)
+}
+
+// Daily log files appended to a saved diagnostics bundle
+const DIAGNOSTIC_LOG_FILES: usize = 3;
+const DEFAULT_LOG_LINES: usize = 500;
+
+// Saves the frontend's diagnostics to Downloads, headed by the app info block and
+// followed by the backend's recent log files
+#[command]
+async fn save_diagnostic_logs(app: AppHandle, content: String, filename: String) -> Result<String, AppError> {
+    // Get the user's home directory
+    let home_dir = dirs::home_dir()
+        .ok_or_else(|| AppError::not_found("Could not find home directory"))?;
     
-    let server_dir = app_data_dir.join("llm-server");
+    // Create a path in the user's Downloads folder
+    let downloads_dir = home_dir.join("Downloads");
+    let file_path = downloads_dir.join(&filename);
     
-    // Determine the server directory based on platform
-    let extract_dir = if cfg!(target_os = "windows") {
-        "llama_server"
-    } else if cfg!(target_os = "macos") {
-        "mlx_server"
-    } else {
-        "llama_server"
-    };
+    // Write the content to the file
+    let mut file = fs::File::create(&file_path)
+        .map_err(|e| AppError::from_io(&e, format!("Failed to create file: {}", e)).with_path(&file_path))?;
     
-    let server_path = server_dir.join(extract_dir);
-    let backup_path = server_dir.join(format!("{}_backup", extract_dir));
+    let info = collect_app_info(&app).await.to_report();
+    file.write_all(info.as_bytes())
+        .and_then(|_| file.write_all(content.as_bytes()))
+        .map_err(|e| AppError::from_io(&e, format!("Failed to write to file: {}", e)).with_path(&file_path))?;
+
+    // Append the backend's own log so the bundle doesn't depend on what the frontend captured
+    let log_files = logging::log_files();
+    let recent = &log_files[log_files.len().saturating_sub(DIAGNOSTIC_LOG_FILES)..];
+    for log_file in recent {
+        let log = match fs::read_to_string(log_file) {
+            Ok(log) => log,
+            Err(e) => {
+                warn!("Skipping log file {} in diagnostics: {}", log_file.display(), e);
+                continue;
+            }
+        };
+        let name = log_file.file_name().unwrap_or_default().to_string_lossy();
+        write!(file, "\n\n===== {} =====\n{}", name, log)
+            .map_err(|e| AppError::from_io(&e, format!("Failed to write to file: {}", e)).with_path(&file_path))?;
+    }
     
-    // Step 1: Stop the server if running
-    eprintln!("Stopping server...");
-    let was_running = {
-        let server_state = state.lock().unwrap();
-        server_state.is_some()
+    // Return the full path where the file was saved
+    Ok(file_path.to_string_lossy().to_string())
+}
+
+// Most recent application log lines for the diagnostics screen, oldest first
+#[command]
+async fn get_app_logs(lines: Option<usize>, level_filter: Option<String>) -> Result<Vec<String>, AppError> {
+    let level_filter = match level_filter {
+        Some(level) => level
+            .parse()
+            .map_err(|_| AppError::invalid_input(format!("Unknown log level '{}'", level)))?,
+        None => tracing::level_filters::LevelFilter::TRACE,
     };
+    let lines = lines.unwrap_or(DEFAULT_LOG_LINES);
+    tokio::task::spawn_blocking(move || logging::recent_lines(lines, level_filter))
+        .await
+        .map_err(|e| AppError::io(format!("Failed to read logs: {}", e)))
+}
+
+#[command]
+async fn set_log_level(level: String) -> Result<(), AppError> {
+    logging::set_level(&level).map_err(AppError::invalid_input)
+}
+
+// pdf_extract panics on some fonts and encodings; it runs on a named worker thread
+// whose panics the quiet hook keeps out of the log
+fn extract_pdf_text(path: &Path) -> Result<String, String> {
+    extraction::install_quiet_panic_hook();
+    let owned_path = path.to_owned();
+    let handle = thread::Builder::new()
+        .name(extraction::PDF_WORKER_THREAD.to_string())
+        .spawn(move || panic::catch_unwind(|| pdf_extract::extract_text(&owned_path)))
+        .map_err(|e| format!("Failed to start PDF extraction: {}", e))?;
+
+    match handle.join() {
+        Ok(Ok(Ok(text))) => Ok(text),
+        Ok(Ok(Err(e))) => Err(format!(
+            "Failed to extract text from PDF: {}. This PDF may have complex fonts or encoding issues.",
+            e
+        )),
+        Ok(Err(_)) | Err(_) => Err(
+            "Failed to extract text from PDF: The PDF contains unsupported fonts or encoding that cannot be processed.".to_string(),
+        ),
+    }
+}
+
+// `package` is the file's zip package, as office_files::read_package returns it
+fn extract_docx_text(package: &[u8]) -> Result<String, String> {
+    let docx = read_docx(package)
+        .map_err(|e| format!("Failed to parse DOCX file: {}", e))?;
     
-    if was_running {
-        if let Err(e) = stop_llm_server(app.clone(), state.clone()).await {
-            eprintln!("Warning: Failed to stop server: {}", e);
-            // Continue anyway
+    // Extract text from paragraphs
+    let mut text = String::new();
+    for child in &docx.document.children {
+        if let DocumentChild::Paragraph(para) = child {
+            for child in &para.children {
+                if let ParagraphChild::Run(run) = child {
+                    for child in &run.children {
+                        if let RunChild::Text(t) = child {
+                            text.push_str(&t.text);
+                            text.push(' ');
+                        }
+                    }
+                }
+            }
+            text.push('\n');
         }
-        // Wait for server to fully stop
-        std::thread::sleep(std::time::Duration::from_secs(2));
     }
     
-    // Step 2: Backup existing server directory
-    if server_path.exists() {
-        eprintln!("Backing up existing server...");
-        
-        // Remove old backup if it exists
-        if backup_path.exists() {
-            fs::remove_dir_all(&backup_path)
-                .map_err(|e| format!("Failed to remove old backup: {}", e))?;
-        }
-        
+    Ok(text)
+}
+
+fn extract_xlsx_text(package: Vec<u8>) -> Result<String, String> {
+    let mut workbook = Xlsx::new(std::io::Cursor::new(package))
+        .map_err(|e| format!("Failed to open Excel file: {}", e))?;
+    
+    let mut text = String::new();
+    
+    // Iterate through all sheets
+    for sheet_name in workbook.sheet_names().to_vec() {
+        // Model input, not UI text, so it stays English whatever the locale
+        text.push_str(&format!("Sheet: {}\n", sheet_name));
+        
+        if let Ok(range) = workbook.worksheet_range(&sheet_name) {
+            for row in range.rows() {
+                for cell in row {
+                    // Use get_string() method to convert cell to string
+                    let cell_str = cell.to_string();
+                    if !cell_str.is_empty() {
+                        text.push_str(&cell_str);
+                        text.push('\t');
+                    }
+                }
+                text.push('\n');
+            }
+        }
+        text.push('\n');
+    }
+    
+    Ok(text)
+}
+
+#[derive(serde::Serialize)]
+struct FileContent {
+    text: Option<String>,
+    image_base64: Option<String>,
+    mime_type: Option<String>,
+    // Encoding plain text was decoded from, e.g. "windows-1252"
+    encoding: Option<String>,
+    // For source code: the detected language and the file's length before any summary
+    language: Option<String>,
+    line_count: Option<usize>,
+}
+
+type ExtractionLimiterState = Arc<extraction::ExtractionLimiter>;
+
+// Cancellation tokens for running operations, by operation_id
+type OperationsState = Arc<operations::Operations>;
+
+// The error returned once a cancelled operation stops. The first caller to notice also
+// sends "operation-cancelled", confirming that nothing more will be done for it.
+fn operation_cancelled(
+    app: &AppHandle,
+    token: &operations::CancelToken,
+    operation_id: &str,
+    completed: usize,
+    skipped: Option<usize>,
+) -> AppError {
+    if token.take_confirmation() {
+        info!("Operation {} cancelled after {} completed", operation_id, completed);
+        let _ = app.emit_all("operation-cancelled", operations::OperationCancelled {
+            operation_id: operation_id.to_string(),
+            completed,
+            skipped,
+        });
+    }
+    AppError::cancelled("The operation was cancelled")
+}
+
+// Cancels batch moves and renames, scans and queued extractions started with this
+// operation_id. Returns false if no such operation is running.
+#[command]
+fn cancel_operation(operations: State<'_, OperationsState>, operation_id: String) -> Result<bool, AppError> {
+    Ok(operations.cancel(&operation_id))
+}
+
+// Releases the token of an operation made of per-file calls once its loop is done
+#[command]
+fn finish_operation(operations: State<'_, OperationsState>, operation_id: String) -> Result<(), AppError> {
+    operations.finish(&operation_id);
+    Ok(())
+}
+
+#[derive(Clone, Serialize)]
+struct ExtractionQueued {
+    path: String,
+    position: u64,
+    waiting_ms: u64,
+}
+
+// Waits for an extraction slot, emitting extraction-queued every second while queued
+async fn acquire_extraction_permit(
+    app: &AppHandle,
+    limiter: &ExtractionLimiterState,
+    path: &str,
+) -> tokio::sync::OwnedSemaphorePermit {
+    limiter
+        .acquire(|status| {
+            debug!("{} is queued for extraction at position {}", path, status.position);
+            let _ = app.emit_all("extraction-queued", ExtractionQueued {
+                path: path.to_string(),
+                position: status.position,
+                waiting_ms: status.waiting_ms,
+            });
+        })
+        .await
+}
+
+#[command]
+fn get_extraction_concurrency(limiter: State<'_, ExtractionLimiterState>) -> Result<usize, AppError> {
+    Ok(limiter.permits())
+}
+
+#[command]
+fn set_extraction_concurrency(limiter: State<'_, ExtractionLimiterState>, permits: usize) -> Result<(), AppError> {
+    if permits == 0 {
+        return Err(AppError::invalid_input("permits must be at least 1"));
+    }
+    settings::update(|settings| {
+        settings.extraction.concurrency = Some(permits);
+        Ok(())
+    })
+    .map_err(AppError::io)?;
+    limiter.set_permits(permits);
+    Ok(())
+}
+
+// Images come back re-encoded at most max_dimension pixels on their longer side (1024 by
+// default) at the given JPEG quality (75 by default). Runs once an extraction slot is free.
+// With an operation_id, cancelling that operation gives up a place in the queue. Cloud
+// placeholders fail with CloudPlaceholder unless hydrate is set. `password` opens
+// password protected DOCX and XLSX files. Source code is outlined, with function bodies
+// collapsed, unless summarize_code is false.
+#[command]
+#[allow(clippy::too_many_arguments)]
+async fn read_file_content(
+    app: AppHandle,
+    limiter: State<'_, ExtractionLimiterState>,
+    operations: State<'_, OperationsState>,
+    path: String,
+    quality: Option<u8>,
+    max_dimension: Option<u32>,
+    operation_id: Option<String>,
+    hydrate: Option<bool>,
+    password: Option<String>,
+    summarize_code: Option<bool>,
+) -> Result<String, AppError> {
+    let summarize_code = summarize_code.unwrap_or(true);
+    cloud_files::ensure_local(&paths::resolve_path(&path), hydrate.unwrap_or_else(cloud_files::hydrate_by_default))
+        .map_err(|e| e.with_path(&path))?;
+    let audited_path = path.clone();
+    let Some(operation_id) = operation_id else {
+        let _permit = acquire_extraction_permit(&app, &limiter, &path).await;
+        let content = file_content_json(path, quality, max_dimension, password, summarize_code).await?;
+        audit_read(&app, &audited_path);
+        return Ok(content);
+    };
+    let token = operations.token(&operation_id);
+    if token.is_cancelled() {
+        return Err(operation_cancelled(&app, &token, &operation_id, token.completed(), None));
+    }
+    let _permit = tokio::select! {
+        permit = acquire_extraction_permit(&app, &limiter, &path) => permit,
+        _ = token.cancelled() => {
+            return Err(operation_cancelled(&app, &token, &operation_id, token.completed(), None));
+        }
+    };
+    let content = file_content_json(path, quality, max_dimension, password, summarize_code).await?;
+    token.record_completed();
+    audit_read(&app, &audited_path);
+    Ok(content)
+}
+
+fn audit_read(app: &AppHandle, path: &str) {
+    audit(app, || {
+        let path = paths::resolve_path(path);
+        let size = fs::metadata(&path).map_or(0, |m| m.len());
+        audit::AuditEvent::new(audit::AuditAction::Read, paths::display_path(&path), size)
+    });
+}
+
+// Appends to the audit log while audit mode is on. With it off `event` isn't called and
+// nothing is hashed or written. A failed write is logged rather than failing the action.
+fn audit(app: &AppHandle, event: impl FnOnce() -> audit::AuditEvent) {
+    if !settings::with(|s| s.audit.enabled) {
+        return;
+    }
+    let result = app_data_dir_of(app).and_then(|dir| audit::append(&dir, event(), journal::now_ms()));
+    if let Err(e) = result {
+        warn!("Failed to write audit record: {}", e.message);
+    }
+}
+
+async fn file_content_json(
+    path: String,
+    quality: Option<u8>,
+    max_dimension: Option<u32>,
+    password: Option<String>,
+    summarize_code: bool,
+) -> Result<String, AppError> {
+    let defaults = vision_image::EncodeOptions::default();
+    let image_options = vision_image::EncodeOptions {
+        quality: quality.unwrap_or(defaults.quality),
+        max_dimension: max_dimension.unwrap_or(defaults.max_dimension),
+    };
+    let content = load_file_content_with_timeout(path, image_options, password, summarize_code).await?;
+    
+    // Serialize as JSON
+    serde_json::to_string(&content).map_err(|e| AppError::io(format!("Failed to serialize content: {}", e)))
+}
+
+static ARCHIVE_READ_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+// Content of one file inside a .zip or .tar.gz, without unpacking the rest. The entry
+// is extracted into a private temp directory, run through the same extractor as a
+// regular file of its type, and removed again.
+#[command]
+async fn read_archived_file_content(
+    app: AppHandle,
+    limiter: State<'_, ExtractionLimiterState>,
+    archive_path: String,
+    inner_path: String,
+) -> Result<String, AppError> {
+    let shown = format!("{}!/{}", archive_path, inner_path);
+    let archive = paths::resolve_path(&archive_path);
+    cloud_files::ensure_local(&archive, cloud_files::hydrate_by_default()).map_err(|e| e.with_path(&archive_path))?;
+    let _permit = acquire_extraction_permit(&app, &limiter, &shown).await;
+    let scratch = std::env::temp_dir().join(format!(
+        "file-organizer-archived-{}-{}",
+        std::process::id(),
+        ARCHIVE_READ_COUNTER.fetch_add(1, Ordering::SeqCst)
+    ));
+    let content = extraction::run_with_timeout(extraction::timeout(), move || {
+        let result = archive::extract_entry(&archive, &inner_path, &scratch, archive::MAX_ENTRY_BYTES)
+            .and_then(|extracted| {
+                load_file_content(&paths::display_path(&extracted), Default::default(), None, true)
+            });
+        let _ = fs::remove_dir_all(&scratch);
+        result
+    })
+    .await
+    // Errors name the archive; the temp path means nothing to the user
+    .map_err(|mut e| {
+        e.path = Some(shown);
+        e
+    })?;
+    serde_json::to_string(&content).map_err(|e| AppError::io(format!("Failed to serialize content: {}", e)))
+}
+
+// load_file_content on the blocking pool, abandoned with a Timeout error once the
+// extraction timeout passes
+async fn load_file_content_with_timeout(
+    path: String,
+    image_options: vision_image::EncodeOptions,
+    password: Option<String>,
+    summarize_code: bool,
+) -> Result<FileContent, AppError> {
+    let extract_path = path.clone();
+    extraction::run_with_timeout(extraction::timeout(), move || {
+        load_file_content(&extract_path, image_options, password.as_deref(), summarize_code)
+    })
+        .await
+        .map_err(|e| match e.path {
+            Some(_) => e,
+            None => e.with_path(&path),
+        })
+}
+
+// Runs `handler` on `sample_path` whether or not external extractors are enabled, so a
+// handler can be tried out before it's switched on
+#[command]
+async fn test_external_extractor(
+    handler: external_extractors::ExternalExtractor,
+    sample_path: String,
+) -> Result<external_extractors::ExtractorOutput, AppError> {
+    let path = paths::resolve_path(&sample_path);
+    tokio::task::spawn_blocking(move || external_extractors::run(&handler, &path))
+        .await
+        .map_err(|e| AppError::io(format!("Extractor test failed: {}", e)))?
+        .map_err(|e| e.with_path(&sample_path))
+}
+
+#[command]
+fn get_extraction_timeout() -> Result<u64, AppError> {
+    Ok(extraction::timeout().as_millis() as u64)
+}
+
+#[command]
+fn set_extraction_timeout(timeout_ms: u64) -> Result<(), AppError> {
+    if timeout_ms == 0 {
+        return Err(AppError::invalid_input("timeout_ms must be at least 1"));
+    }
+    settings::update(|settings| {
+        settings.extraction.timeout_ms = timeout_ms;
+        Ok(())
+    })
+    .map(|_| ())
+    .map_err(AppError::io)
+}
+
+// Runs the extractor matching the file's extension: a user-configured converter from
+// settings external_extractors when one handles it, otherwise the built-in ones. With
+// summarize_code, source files come back as an outline (see code_summary). Images are re-encoded for vision
+// models according to image_options. DOCX and XLSX files are checked by office_files
+// first, so empty, truncated and protected files get errors people can act on.
+fn load_file_content(
+    path: &str,
+    image_options: vision_image::EncodeOptions,
+    password: Option<&str>,
+    summarize_code: bool,
+) -> Result<FileContent, AppError> {
+    let path_lower = path.to_lowercase();
+    let fs_path = paths::resolve_path(path);
+    let content: FileContent;
+
+    if let Some(handler) = external_extractors::handler_for(&fs_path) {
+        return match external_extractors::run(&handler, &fs_path) {
+            Ok(output) => Ok(FileContent {
+                text: Some(output.text),
+                image_base64: None,
+                mime_type: Some("text/plain".to_string()),
+                encoding: None,
+                language: None,
+                line_count: None,
+            }),
+            Err(e) => {
+                warn!("External extractor for {} failed: {}", path, e.message);
+                Err(AppError::unsupported(format!("No extractor could read this file ({})", e.message)).with_path(path))
+            }
+        };
+    }
+    
+    if path_lower.ends_with(".pdf") {
+        // Extract text from PDF
+        let text = extract_pdf_text(&fs_path)
+            .map_err(|e| AppError::io(e).with_path(path))?;
+        content = FileContent {
+            text: Some(text),
+            image_base64: None,
+            mime_type: Some("application/pdf".to_string()),
+            encoding: None,
+            language: None,
+            line_count: None,
+        };
+    } else if path_lower.ends_with(".docx") {
+        // Extract text from DOCX
+        let package = office_files::read_package(&fs_path, password).map_err(|e| e.with_path(path))?;
+        let text = extract_docx_text(&package)
+            .map_err(|e| AppError::io(e).with_path(path))?;
+        content = FileContent {
+            text: Some(text),
+            image_base64: None,
+            mime_type: Some("application/vnd.openxmlformats-officedocument.wordprocessingml.document".to_string()),
+            encoding: None,
+            language: None,
+            line_count: None,
+        };
+    } else if path_lower.ends_with(".doc") {
+        // DOC files are not supported by docx-rs, treat as unsupported
+        return Err(AppError::unsupported("DOC format not supported. Please convert to DOCX.").with_path(path));
+    } else if path_lower.ends_with(".xlsx") || path_lower.ends_with(".xls") {
+        // Extract text from Excel
+        let package = if path_lower.ends_with(".xlsx") {
+            office_files::read_package(&fs_path, password).map_err(|e| e.with_path(path))?
+        } else {
+            fs::read(&fs_path).map_err(|e| AppError::from(e).with_path(path))?
+        };
+        let text = extract_xlsx_text(package)
+            .map_err(|e| AppError::io(e).with_path(path))?;
+        content = FileContent {
+            text: Some(text),
+            image_base64: None,
+            mime_type: Some("application/vnd.openxmlformats-officedocument.spreadsheetml.sheet".to_string()),
+            encoding: None,
+            language: None,
+            line_count: None,
+        };
+    } else if path_lower.ends_with(".png") || path_lower.ends_with(".jpg") || 
+              path_lower.ends_with(".jpeg") || path_lower.ends_with(".gif") || 
+              path_lower.ends_with(".bmp") || path_lower.ends_with(".webp") {
+        // Re-encode for the vision model
+        let image = vision_image::encode(&fs_path, image_options).map_err(|e| e.with_path(path))?;
+        content = FileContent {
+            text: None,
+            image_base64: Some(base64::engine::general_purpose::STANDARD.encode(&image.bytes)),
+            mime_type: Some(image.mime_type.to_string()),
+            encoding: None,
+            language: None,
+            line_count: None,
+        };
+    } else {
+        // Plain text file, in whatever encoding it was saved with
+        let bytes = fs::read(&fs_path).map_err(|e| AppError::from(e).with_path(path))?;
+        let decoded = text_encoding::decode(&bytes).map_err(|e| e.with_path(path))?;
+        let language = code_summary::detect_language(&fs_path, &decoded.text);
+        let line_count = language.map(|_| decoded.text.lines().count());
+        let text = match language {
+            Some(language) if summarize_code => code_summary::summarize(&decoded.text, language),
+            _ => decoded.text,
+        };
+        content = FileContent {
+            text: Some(text),
+            image_base64: None,
+            mime_type: Some("text/plain".to_string()),
+            encoding: Some(decoded.encoding.to_string()),
+            language: language.map(|language| language.name().to_string()),
+            line_count,
+        };
+    }
+    
+    Ok(content)
+}
+
+// Waits between move attempts on an in-use file: three attempts over about two seconds
+const MOVE_RETRY_DELAYS_MS: [u64; 2] = [500, 1_500];
+
+// When `from` is a symlink the link itself is moved (rename never follows links),
+// so the file it points to stays where it is. With dry_run the move is validated
+// and reported as simulated without creating folders or moving anything.
+// Moves made one call at a time under an operation_id stop once it's cancelled.
+// Moves under a batch_id are recorded in that batch's journal for verify_organization;
+// capture_hash also records the file's SHA-256, which costs a full read.
+// on_conflict (default "error") decides what happens when the destination name is
+// taken. With dedupe_at_destination, a file already there (or an earlier numbered copy
+// of it) with the source's size and hash means the move is skipped; trash_duplicate_source
+// then moves the source to app_data_dir/trash/<batch_id>, journaled so undo_batch can
+// put it back.
+// The outcome's action says which of these happened.
+#[command]
+#[allow(clippy::too_many_arguments)]
+async fn move_file(
+    app: AppHandle,
+    operations: State<'_, OperationsState>,
+    from: String,
+    to: String,
+    retry: Option<bool>,
+    dry_run: Option<bool>,
+    operation_id: Option<String>,
+    batch_id: Option<String>,
+    capture_hash: Option<bool>,
+    on_conflict: Option<rename::OnConflict>,
+    dedupe_at_destination: Option<bool>,
+    trash_duplicate_source: Option<bool>,
+) -> Result<moves::MoveOutcome, AppError> {
+    let token = operation_id.as_deref().map(|id| operations.token(id));
+    if let (Some(token), Some(id)) = (&token, &operation_id) {
+        if token.is_cancelled() {
+            return Err(operation_cancelled(&app, token, id, token.completed(), None));
+        }
+    }
+    let dry_run = dry_run.unwrap_or(false);
+    let trash_duplicates = trash_duplicate_source.unwrap_or(false);
+    if trash_duplicates && batch_id.is_none() {
+        return Err(AppError::invalid_input(
+            "trash_duplicate_source needs a batch_id so the trashed file can be restored",
+        ));
+    }
+    let from_path = paths::resolve_path(&from);
+    let to_path = paths::normalize_path(&to);
+    let dedupe = dedupe_at_destination.unwrap_or(false);
+    let on_conflict = on_conflict.unwrap_or_default();
+    let resolution = tokio::task::spawn_blocking(move || {
+        moves::resolve_destination(&from_path, &to_path, dedupe, on_conflict)
+    })
+    .await
+    .map_err(|e| AppError::io(format!("Failed to check the destination: {}", e)))??;
+    let not_moved = |to: &Path, action| moves::MoveOutcome {
+        from: paths::display_path(paths::resolve_path(&from)),
+        to: paths::display_path(to),
+        simulated: dry_run,
+        action,
+        trashed_to: None,
+    };
+    let outcome = match resolution {
+        moves::Resolution::Move(destination, action) => {
+            let to = paths::display_path(&destination);
+            let mut outcome = match batch_id.filter(|_| !dry_run) {
+                Some(batch_id) => {
+                    journaled_move(&app, &batch_id, from, to, retry, capture_hash.unwrap_or(false)).await?
+                }
+                None => {
+                    let outcome = move_path(from, to, retry, Some(dry_run)).await?;
+                    if !dry_run {
+                        audit(&app, || {
+                            let size = fs::metadata(&outcome.to).map_or(0, |m| m.len());
+                            audit::AuditEvent::new(audit::AuditAction::Move, outcome.from.clone(), size)
+                                .with_destination(outcome.to.clone())
+                        });
+                    }
+                    outcome
+                }
+            };
+            outcome.action = action;
+            outcome
+        }
+        moves::Resolution::Skip => not_moved(&paths::normalize_path(&to), moves::MoveAction::Skipped),
+        moves::Resolution::Duplicate(copy) => match batch_id.filter(|_| trash_duplicates && !dry_run) {
+            Some(batch_id) => trash_duplicate(&app, &batch_id, &from, &copy, capture_hash.unwrap_or(false)).await?,
+            None if trash_duplicates => not_moved(&copy, moves::MoveAction::SourceTrashed),
+            None => not_moved(&copy, moves::MoveAction::SkippedDuplicate),
+        },
+    };
+    if let Some(token) = token {
+        token.record_completed();
+    }
+    Ok(outcome)
+}
+
+// A failed journal write is logged rather than failing a move that already happened
+async fn journaled_move(
+    app: &AppHandle,
+    batch_id: &str,
+    from: String,
+    to: String,
+    retry: Option<bool>,
+    capture_hash: bool,
+) -> Result<moves::MoveOutcome, AppError> {
+    journal::validate_batch_id(batch_id)?;
+    let app_data_dir = app.path_resolver().app_data_dir()
+        .ok_or_else(|| AppError::not_found("Failed to get app data directory"))?;
+    let source = paths::resolve_path(&from);
+    let snapshot = tokio::task::spawn_blocking(move || journal::snapshot(&source, capture_hash))
+        .await
+        .map_err(|e| AppError::io(format!("Failed to read file before moving: {}", e)))?;
+    let outcome = move_path(from, to, retry, None).await?;
+    let entry = journal::JournalEntry {
+        from: outcome.from.clone(),
+        to: outcome.to.clone(),
+        size: snapshot.size,
+        sha256: snapshot.sha256,
+        moved_at: journal::now_ms(),
+        duplicate_of: None,
+        archive_member: None,
+    };
+    if let Err(e) = journal::append(&app_data_dir, batch_id, &entry) {
+        warn!("{}", e);
+    }
+    audit(app, || {
+        audit::AuditEvent::new(audit::AuditAction::Move, entry.from, entry.size).with_destination(entry.to)
+    });
+    Ok(outcome)
+}
+
+// Moves a source whose identical copy is already at the destination into the batch's
+// trash folder and journals it like a move, so undoing the batch restores it
+async fn trash_duplicate(
+    app: &AppHandle,
+    batch_id: &str,
+    from: &str,
+    copy: &Path,
+    capture_hash: bool,
+) -> Result<moves::MoveOutcome, AppError> {
+    journal::validate_batch_id(batch_id)?;
+    let app_data_dir = app.path_resolver().app_data_dir()
+        .ok_or_else(|| AppError::not_found("Failed to get app data directory"))?;
+    let source = paths::resolve_path(from);
+    let trash_dir = app_data_dir.join("trash").join(batch_id);
+    let trashed_source = source.clone();
+    let (snapshot, trashed) = tokio::task::spawn_blocking(move || {
+        let snapshot = journal::snapshot(&trashed_source, capture_hash);
+        moves::move_to_trash(&trashed_source, &trash_dir).map(|trashed| (snapshot, trashed))
+    })
+    .await
+    .map_err(|e| AppError::io(format!("Failed to trash duplicate: {}", e)))??;
+    let entry = journal::JournalEntry {
+        from: paths::display_path(&source),
+        to: paths::display_path(&trashed),
+        size: snapshot.size,
+        sha256: snapshot.sha256,
+        moved_at: journal::now_ms(),
+        duplicate_of: Some(paths::display_path(copy)),
+        archive_member: None,
+    };
+    if let Err(e) = journal::append(&app_data_dir, batch_id, &entry) {
+        warn!("{}", e);
+    }
+    audit(app, || {
+        let event = audit::AuditEvent::new(audit::AuditAction::Trash, entry.from.clone(), entry.size);
+        event.with_destination(entry.to.clone())
+    });
+    Ok(moves::MoveOutcome {
+        from: entry.from,
+        to: paths::display_path(copy),
+        simulated: false,
+        action: moves::MoveAction::SourceTrashed,
+        trashed_to: Some(entry.to),
+    })
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct UndoFailure {
+    from: String,
+    to: String,
+    error: AppError,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct UndoReport {
+    batch_id: String,
+    restored: usize,
+    // Files packed into an archive; restore_archive puts those back
+    archived: usize,
+    failures: Vec<UndoFailure>,
+}
+
+// Reverses a batch's moves, newest first: each file goes from where the journal says it
+// went back to where it was, including sources trash_duplicate_source put in the batch's
+// trash folder. Undone entries leave the journal, and the trash folder goes once it's
+// empty; entries that failed stay for another try.
+#[command]
+async fn undo_batch(app: AppHandle, batch_id: String) -> Result<UndoReport, AppError> {
+    let app_data_dir = app_data_dir_of(&app)?;
+    let entries = journal::load(&app_data_dir, &batch_id)?;
+    let mut report = UndoReport { batch_id: batch_id.clone(), restored: 0, archived: 0, failures: Vec::new() };
+    let mut remaining = Vec::new();
+    for entry in entries.into_iter().rev() {
+        if entry.archive_member.is_some() {
+            report.archived += 1;
+            remaining.push(entry);
+            continue;
+        }
+        match move_path(entry.to.clone(), entry.from.clone(), Some(true), None).await {
+            Ok(_) => {
+                report.restored += 1;
+                audit(&app, || {
+                    audit::AuditEvent::new(audit::AuditAction::Move, entry.to.clone(), entry.size)
+                        .with_destination(entry.from.clone())
+                });
+            }
+            Err(error) => {
+                report.failures.push(UndoFailure { from: entry.from.clone(), to: entry.to.clone(), error });
+                remaining.push(entry);
+            }
+        }
+    }
+    remaining.reverse();
+    journal::replace(&app_data_dir, &batch_id, &remaining)?;
+    // Only removed when nothing is left in it
+    let _ = fs::remove_dir(app_data_dir.join("trash").join(&batch_id));
+    info!(
+        "Undid batch {}: {} restored, {} failed, {} archived",
+        batch_id, report.restored, report.failures.len(), report.archived
+    );
+    Ok(report)
+}
+
+#[derive(Clone, Serialize)]
+struct ArchiveProgressEvent {
+    archive_path: String,
+    #[serde(flatten)]
+    progress: file_archive::ArchiveProgress,
+    // Over the bytes of the current phase
+    #[serde(flatten)]
+    estimate: progress::ProgressEstimate,
+}
+
+// Sends archive-progress events, with the rate starting over at each phase
+fn archive_progress_emitter(app: AppHandle, archive_path: String) -> impl FnMut(&file_archive::ArchiveProgress) {
+    let mut phase = None;
+    let mut tracker = progress::ProgressTracker::new(progress::RateUnit::Bytes, None);
+    move |p: &file_archive::ArchiveProgress| {
+        if phase != Some(p.phase) {
+            phase = Some(p.phase);
+            // Restoring doesn't know its total until the end
+            let total = (p.phase != file_archive::ArchivePhase::Restoring).then_some(p.bytes_total);
+            tracker = progress::ProgressTracker::new(progress::RateUnit::Bytes, total);
+        }
+        let _ = app.emit_all("archive-progress", ArchiveProgressEvent {
+            archive_path: archive_path.clone(),
+            progress: p.clone(),
+            estimate: tracker.update(p.bytes_done),
+        });
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct ArchiveFilesResult {
+    #[serde(flatten)]
+    report: file_archive::ArchiveReport,
+    // Journal the archived files were recorded under
+    batch_id: String,
+}
+
+// Bundles `paths` into a new .tar.gz at archive_path, stored relative to base_root, and
+// with remove_originals deletes them once the archive has been read back and checked.
+// Every file is journaled under batch_id (generated when not given) before anything is
+// removed. Progress goes out as archive-progress events.
+#[command]
+async fn archive_files(
+    app: AppHandle,
+    paths: Vec<String>,
+    base_root: String,
+    archive_path: String,
+    remove_originals: bool,
+    batch_id: Option<String>,
+) -> Result<ArchiveFilesResult, AppError> {
+    let _in_flight = shutdown::SHUTDOWN.enter().ok_or_else(|| AppError::cancelled("The app is shutting down"))?;
+    let batch_id = batch_id.unwrap_or_else(|| format!("archive-{}", journal::now_ms()));
+    journal::validate_batch_id(&batch_id)?;
+    let app_data_dir = app_data_dir_of(&app)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let archive = paths::normalize_path(&archive_path);
+        let mut on_progress = archive_progress_emitter(app.clone(), archive_path.clone());
+        let planned = file_archive::plan(&paths, &base_root)?;
+        let mut report = file_archive::create(&planned, &archive, &mut on_progress)?;
+        for file in &report.files {
+            let entry = journal::JournalEntry {
+                from: file.path.clone(),
+                to: report.archive_path.clone(),
+                size: file.size,
+                sha256: None,
+                moved_at: journal::now_ms(),
+                duplicate_of: None,
+                archive_member: Some(file.member.clone()),
+            };
+            // Without a record of where they went, the originals aren't removed
+            journal::append(&app_data_dir, &batch_id, &entry)
+                .map_err(|e| AppError::io(format!("{}; the archive was kept and no originals were removed", e)))?;
+        }
+        if remove_originals {
+            report.originals_removed = file_archive::remove_originals(&planned, &mut on_progress)?;
+            for file in &report.files {
+                audit(&app, || {
+                    let event = audit::AuditEvent::new(audit::AuditAction::Move, file.path.clone(), file.size);
+                    event.with_destination(format!("{}/{}", report.archive_path, file.member))
+                });
+            }
+        }
+        let (archived, removed) = (report.files.len(), report.originals_removed);
+        info!("Archived {} files into {} ({} removed)", archived, report.archive_path, removed);
+        Ok(ArchiveFilesResult { report, batch_id })
+    })
+    .await
+    .map_err(|e| AppError::io(format!("Archive task failed: {}", e)))?
+}
+
+// Puts the files of an archive made by archive_files back under restore_root (usually
+// the base_root it was made from). Existing files are never overwritten; they come back
+// as conflicts.
+#[command]
+async fn unarchive_files(
+    app: AppHandle,
+    archive_path: String,
+    restore_root: String,
+) -> Result<file_archive::RestoreReport, AppError> {
+    let _in_flight = shutdown::SHUTDOWN.enter().ok_or_else(|| AppError::cancelled("The app is shutting down"))?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let archive = paths::normalize_path(&archive_path);
+        let mut on_progress = archive_progress_emitter(app, archive_path.clone());
+        let report = file_archive::restore(&archive, &paths::normalize_path(&restore_root), &mut on_progress)?;
+        info!("Restored {} files from {} ({} conflicts)", report.restored.len(), archive_path, report.conflicts.len());
+        Ok(report)
+    })
+    .await
+    .map_err(|e| AppError::io(format!("Unarchive task failed: {}", e)))?
+}
+
+// Checks a batch's moves against its journal: destinations present with the recorded
+// size and hash, sources gone, and no temp files left in the destination folders
+#[command]
+async fn verify_organization(app: AppHandle, batch_id: String) -> Result<verification::VerificationReport, AppError> {
+    let app_data_dir = app.path_resolver().app_data_dir()
+        .ok_or_else(|| AppError::not_found("Failed to get app data directory"))?;
+    tokio::task::spawn_blocking(move || {
+        let entries = journal::load(&app_data_dir, &batch_id)?;
+        Ok(verification::verify(&batch_id, &entries))
+    })
+    .await
+    .map_err(|e| AppError::io(format!("Verification task failed: {}", e)))?
+}
+
+// Writes the audit records in `range` (all of them by default) to `path` as a signed
+// bundle that verify_audit_log can check on any machine
+#[command]
+async fn export_audit_log(
+    app: AppHandle,
+    range: Option<audit::AuditRange>,
+    path: String,
+) -> Result<audit::ExportSummary, AppError> {
+    let app_data_dir = app_data_dir_of(&app)?;
+    tokio::task::spawn_blocking(move || {
+        audit::export(&app_data_dir, range.unwrap_or_default(), &paths::normalize_path(&path), journal::now_ms())
+    })
+    .await
+    .map_err(|e| AppError::io(format!("Audit export failed: {}", e)))?
+}
+
+// Checks the hash chain of this app's audit log, or of an exported bundle (with its
+// signature) when bundle_path is given. A bundle must be signed by this install's key
+// or by expected_key, the public key or fingerprint the auditor was given at export.
+#[command]
+async fn verify_audit_log(
+    app: AppHandle,
+    bundle_path: Option<String>,
+    expected_key: Option<String>,
+) -> Result<audit::ChainVerification, AppError> {
+    let app_data_dir = app_data_dir_of(&app)?;
+    tokio::task::spawn_blocking(move || match bundle_path {
+        Some(bundle) => audit::verify_bundle(&paths::normalize_path(&bundle), &app_data_dir, expected_key.as_deref()),
+        None => audit::verify_log(&app_data_dir),
+    })
+    .await
+    .map_err(|e| AppError::io(format!("Audit verification failed: {}", e)))?
+}
+
+// For file content the frontend sends itself (its own fetch doesn't pass through
+// http_request): a PolicyViolation when local-only mode doesn't allow sending it to `url`
+#[command]
+async fn check_network_policy(app: AppHandle, url: String, path: Option<String>) -> Result<(), AppError> {
+    local_only::guard_request(&url, true).await.map(|_| ()).inspect_err(|e| {
+        warn!("{}", e.message);
+        audit(&app, || {
+            let event = audit::AuditEvent::new(audit::AuditAction::Blocked, path.unwrap_or(url.clone()), 0);
+            event.with_backend(url)
+        });
+    })
+}
+
+// For LLM requests the frontend sends itself rather than through llm_classify. Does
+// nothing while audit mode is off.
+#[command]
+fn record_llm_submission(app: AppHandle, path: String, backend: String, bytes: u64) -> Result<(), AppError> {
+    audit(&app, || audit::AuditEvent::new(audit::AuditAction::LlmSubmission, path, bytes).with_backend(backend));
+    Ok(())
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct StagingFailure {
+    path: String,
+    error: AppError,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct StagingReport {
+    batch: staging::StagedBatch,
+    failures: Vec<StagingFailure>,
+}
+
+fn app_data_dir_of(app: &AppHandle) -> Result<std::path::PathBuf, AppError> {
+    app.path_resolver().app_data_dir().ok_or_else(|| AppError::not_found("Failed to get app data directory"))
+}
+
+// Moves files into <root>/_Organizer Staging/<batch_id>/ for review instead of straight
+// to proposed_destinations (same order as paths). The batch's manifest remembers both
+// ends, so commit_staged can finish or undo each move, even after a restart. Staging
+// into an existing batch adds to it. Files that can't be staged are reported and left
+// where they are.
+#[command]
+async fn stage_files(
+    app: AppHandle,
+    batch_id: String,
+    root: String,
+    paths: Vec<String>,
+    proposed_destinations: Vec<String>,
+) -> Result<StagingReport, AppError> {
+    journal::validate_batch_id(&batch_id)?;
+    if paths.len() != proposed_destinations.len() {
+        return Err(AppError::invalid_input(format!(
+            "{} paths but {} proposed destinations",
+            paths.len(),
+            proposed_destinations.len()
+        )));
+    }
+    let app_data_dir = app_data_dir_of(&app)?;
+    let root_path = paths::normalize_path(&root);
+    let mut batch = match staging::load(&app_data_dir, &batch_id) {
+        Ok(batch) => batch,
+        Err(e) if e.kind == error::AppErrorKind::NotFound => {
+            staging::StagedBatch::new(&batch_id, &paths::display_path(&root_path), journal::now_ms())
+        }
+        Err(e) => return Err(e),
+    };
+    let folder = staging::staging_folder(Path::new(&batch.root), &batch_id);
+    let mut failures = Vec::new();
+    for (path, proposed) in paths.into_iter().zip(proposed_destinations) {
+        let staged = match staging::staged_path(&folder, &paths::resolve_path(&path), &batch) {
+            Ok(staged) => paths::display_path(&staged),
+            Err(error) => {
+                failures.push(StagingFailure { path, error });
+                continue;
+            }
+        };
+        match journaled_move(&app, &batch_id, path.clone(), staged, Some(true), false).await {
+            Ok(outcome) => {
+                batch.files.push(staging::StagedFile {
+                    original: outcome.from,
+                    staged: outcome.to,
+                    proposed: paths::display_path(paths::normalize_path(&proposed)),
+                    status: staging::StagedStatus::Staged,
+                });
+                staging::save(&app_data_dir, &batch).map_err(AppError::io)?;
+            }
+            Err(error) => failures.push(StagingFailure { path, error }),
+        }
+    }
+    Ok(StagingReport { batch, failures })
+}
+
+// Finishes staged moves: approvals maps a file's original path to true (move it on to
+// its proposed destination) or false (put it back). Files left out stay staged. When
+// none are left the manifest and the empty staging folder are removed.
+#[command]
+async fn commit_staged(
+    app: AppHandle,
+    batch_id: String,
+    approvals: HashMap<String, bool>,
+) -> Result<StagingReport, AppError> {
+    let app_data_dir = app_data_dir_of(&app)?;
+    let mut batch = staging::load(&app_data_dir, &batch_id)?;
+    let mut failures = Vec::new();
+    for index in 0..batch.files.len() {
+        let file = batch.files[index].clone();
+        let Some(&approved) = approvals.get(&file.original) else { continue };
+        if file.status != staging::StagedStatus::Staged {
+            continue;
+        }
+        let (to, status) = if approved {
+            (file.proposed.clone(), staging::StagedStatus::Committed)
+        } else {
+            (file.original.clone(), staging::StagedStatus::Restored)
+        };
+        match journaled_move(&app, &batch_id, file.staged.clone(), to, Some(true), false).await {
+            Ok(_) => {
+                batch.files[index].status = status;
+                staging::save(&app_data_dir, &batch).map_err(AppError::io)?;
+            }
+            Err(error) => failures.push(StagingFailure { path: file.original, error }),
+        }
+    }
+    if batch.pending() == 0 {
+        staging::finish(&app_data_dir, &batch);
+    }
+    Ok(StagingReport { batch, failures })
+}
+
+// Batches with files still in staging, oldest first; `expiring` marks ones older than
+// the staging.expiry_days setting
+#[command]
+fn list_staged_batches(app: AppHandle) -> Result<Vec<staging::StagedBatchSummary>, AppError> {
+    let app_data_dir = app_data_dir_of(&app)?;
+    let expiry_days = settings::with(|settings| settings.staging.expiry_days);
+    Ok(staging::list(&app_data_dir, expiry_days, journal::now_ms()))
+}
+
+#[command]
+fn get_staged_batch(app: AppHandle, batch_id: String) -> Result<staging::StagedBatch, AppError> {
+    staging::load(&app_data_dir_of(&app)?, &batch_id)
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct RunFailure {
+    from: String,
+    to: String,
+    error: AppError,
+}
+
+// What one execute_plan or resume_run call did
+#[derive(Debug, Clone, Serialize)]
+struct RunReport {
+    run_id: String,
+    batch_id: String,
+    total: usize,
+    // Index this call started at; 0 unless resumed
+    resumed_from: usize,
+    moved: usize,
+    // Found moved already when resuming (the cursor hadn't been saved yet)
+    already_moved: usize,
+    skipped: usize,
+    failures: Vec<RunFailure>,
+    // Stopped through its operation_id; the run can be resumed
+    cancelled: bool,
+}
+
+#[derive(Clone, Serialize)]
+struct RunProgress {
+    run_id: String,
+    done: usize,
+    total: usize,
+    #[serde(flatten)]
+    estimate: progress::ProgressEstimate,
+}
+
+const RUN_PROGRESS_INTERVAL: std::time::Duration = std::time::Duration::from_millis(250);
+
+// Makes the moves of `plan` from the writer's cursor on, checking each again just
+// before it's made. A run that gets to the end has its files deleted; a cancelled one
+// is kept for resume_run.
+async fn run_plan(
+    app: &AppHandle,
+    operations: &OperationsState,
+    plan: runs::RunPlan,
+    mut writer: runs::RunCursorWriter,
+    operation_id: Option<String>,
+) -> Result<RunReport, AppError> {
+    let token = operation_id.as_deref().map(|id| operations.token(id));
+    let app_data_dir = app_data_dir_of(app)?;
+    let journaled: Arc<HashSet<String>> = Arc::new(match journal::load(&app_data_dir, &plan.batch_id) {
+        Ok(entries) => entries.into_iter().map(|entry| entry.from).collect(),
+        Err(_) => HashSet::new(),
+    });
+    let total = plan.moves.len();
+    let mut report = RunReport {
+        run_id: plan.run_id.clone(),
+        batch_id: plan.batch_id.clone(),
+        total,
+        resumed_from: writer.cursor().next,
+        moved: 0,
+        already_moved: 0,
+        skipped: 0,
+        failures: Vec::new(),
+        cancelled: false,
+    };
+    let mut tracker = progress::ProgressTracker::new(progress::RateUnit::Items, Some(total as u64));
+    let mut reported: Option<std::time::Instant> = None;
+    while let Some(planned) = plan.moves.get(writer.cursor().next).cloned() {
+        if token.as_ref().is_some_and(|token| token.is_cancelled()) {
+            report.cancelled = true;
+            break;
+        }
+        let (check, journaled, on_conflict) = (planned.clone(), journaled.clone(), plan.on_conflict);
+        let remaining = tauri::async_runtime::spawn_blocking(move || {
+            runs::check_remaining(&check, on_conflict, &journaled)
+        })
+        .await
+        .unwrap_or_else(|e| runs::Remaining::Invalid(AppError::io(format!("Failed to check the move: {}", e))));
+        let outcome = match remaining {
+            runs::Remaining::Move(destination) => {
+                let to = paths::display_path(&destination);
+                let from = planned.from.clone();
+                journaled_move(app, &plan.batch_id, from, to, Some(true), plan.capture_hash).await.map(|_| {
+                    report.moved += 1;
+                    runs::Step::Moved
+                })
+            }
+            runs::Remaining::AlreadyMoved => {
+                report.already_moved += 1;
+                Ok(runs::Step::Moved)
+            }
+            runs::Remaining::Skip => {
+                report.skipped += 1;
+                Ok(runs::Step::Skipped)
+            }
+            runs::Remaining::Invalid(error) => Err(error),
+        };
+        let step = outcome.unwrap_or_else(|error| {
+            report.failures.push(RunFailure { from: planned.from, to: planned.to, error });
+            runs::Step::Failed
+        });
+        writer.advance(step);
+        if let Some(token) = &token {
+            token.record_completed();
+        }
+        let done = writer.cursor().next;
+        if done == total || reported.is_none_or(|at| at.elapsed() >= RUN_PROGRESS_INTERVAL) {
+            reported = Some(std::time::Instant::now());
+            let _ = app.emit_all("run-progress", RunProgress {
+                run_id: plan.run_id.clone(),
+                done,
+                total,
+                estimate: tracker.update(done as u64),
+            });
+        }
+    }
+    if report.cancelled {
+        writer.stop();
+    } else {
+        writer.finish();
+    }
+    let (moved, failed) = (report.moved + report.already_moved, report.failures.len());
+    info!("Run {}: {} moved, {} skipped, {} failed of {}", plan.run_id, moved, report.skipped, failed, total);
+    if let (Some(token), Some(id)) = (token, operation_id) {
+        if report.cancelled {
+            let done = moved + report.skipped + failed;
+            operation_cancelled(app, &token, &id, done, Some(total - report.resumed_from - done));
+        }
+        operations.finish(&id);
+    }
+    Ok(report)
+}
+
+// Makes the moves of an organization plan in order, journaled under batch_id (generated
+// when not given) like move_file with a batch_id. The whole plan is saved before the
+// first move, with a cursor kept up to date as moves finish, so a run cut short by a
+// crash shows up in get_interrupted_runs. Progress goes out as run-progress events;
+// cancelling operation_id stops the run between moves and keeps it resumable.
+#[command]
+async fn execute_plan(
+    app: AppHandle,
+    operations: State<'_, OperationsState>,
+    moves: Vec<runs::PlannedMove>,
+    batch_id: Option<String>,
+    on_conflict: Option<rename::OnConflict>,
+    capture_hash: Option<bool>,
+    operation_id: Option<String>,
+) -> Result<RunReport, AppError> {
+    let now = journal::now_ms();
+    let batch_id = batch_id.unwrap_or_else(|| format!("run-{}", now));
+    journal::validate_batch_id(&batch_id)?;
+    let plan = runs::RunPlan {
+        version: runs::RUN_VERSION,
+        run_id: format!("run-{}-{}", now, std::process::id()),
+        batch_id,
+        created_at: now,
+        on_conflict: on_conflict.unwrap_or_default(),
+        capture_hash: capture_hash.unwrap_or(false),
+        moves,
+    };
+    let writer = runs::RunCursorWriter::start(&app_data_dir_of(&app)?, &plan, runs::CURSOR_EVERY)?;
+    run_plan(&app, &operations, plan, writer, operation_id).await
+}
+
+// Runs saved by execute_plan that stopped before the end (a crash, quitting, or a
+// cancel), newest first
+#[command]
+fn get_interrupted_runs(app: AppHandle) -> Result<Vec<runs::InterruptedRun>, AppError> {
+    Ok(runs::list_interrupted(&app_data_dir_of(&app)?))
+}
+
+// Carries on with an interrupted run from its cursor. Every remaining move is checked
+// again first: a source that's gone fails (or counts as moved if the batch journal has
+// it), and destinations have to pass the current destination policy.
+#[command]
+async fn resume_run(
+    app: AppHandle,
+    operations: State<'_, OperationsState>,
+    run_id: String,
+    operation_id: Option<String>,
+) -> Result<RunReport, AppError> {
+    let app_data_dir = app_data_dir_of(&app)?;
+    let (plan, cursor) = runs::load(&app_data_dir, &run_id)?;
+    let writer = runs::RunCursorWriter::resume(&app_data_dir, &run_id, cursor, runs::CURSOR_EVERY)?;
+    run_plan(&app, &operations, plan, writer, operation_id).await
+}
+
+// Forgets an interrupted run without making its remaining moves
+#[command]
+fn discard_run(app: AppHandle, run_id: String) -> Result<bool, AppError> {
+    runs::delete(&app_data_dir_of(&app)?, &run_id)
+}
+
+// Run at startup: batches left in staging past the expiry get a notification
+fn warn_expiring_staged_batches(app: &AppHandle, app_data_dir: &Path) {
+    let expiry_days = settings::with(|settings| settings.staging.expiry_days);
+    let expiring: Vec<_> = staging::list(app_data_dir, expiry_days, journal::now_ms())
+        .into_iter()
+        .filter(|batch| batch.expiring)
+        .collect();
+    if expiring.is_empty() {
+        return;
+    }
+    let files: usize = expiring.iter().map(|batch| batch.staged).sum();
+    warn!("{} staged batches ({} files) are older than {} days", expiring.len(), files, expiry_days);
+    let body = i18n::tf(
+        "notification.staging_expiring.body",
+        &[("files", &files.to_string()), ("days", &expiry_days.to_string())],
+    );
+    if let Err(e) = send_notification(app, &i18n::t("notification.staging_expiring.title"), &body, "info") {
+        warn!("{}", e.message);
+    }
+    let _ = app.emit_all("staged-batches-expiring", expiring);
+}
+
+async fn move_path(
+    from: String,
+    to: String,
+    retry: Option<bool>,
+    dry_run: Option<bool>,
+) -> Result<moves::MoveOutcome, AppError> {
+    let to_path = paths::normalize_path(&to);
+    let from_path = paths::resolve_path(&from);
+    moves::validate_move(&from_path, &to_path)?;
+    let simulated = dry_run.unwrap_or(false);
+    let outcome = moves::MoveOutcome {
+        from: paths::display_path(&from_path),
+        to: paths::display_path(&to_path),
+        simulated,
+        action: moves::MoveAction::Moved,
+        trashed_to: None,
+    };
+    if simulated {
+        return Ok(outcome);
+    }
+    if let Some(parent) = to_path.parent() {
+        fs::create_dir_all(parent).map_err(|e| AppError::from(e).with_path(paths::display_path(parent)))?;
+    }
+
+    // With retry, a file that is briefly locked (e.g. a download still being written)
+    // gets a few more attempts before the in-use error is returned
+    let delays: &[u64] = if retry.unwrap_or(false) { &MOVE_RETRY_DELAYS_MS } else { &[] };
+    let mut attempt = 0;
+    loop {
+        match fs::rename(&from_path, &to_path) {
+            Ok(()) => {
+                file_tags::follow_move(&from_path, &to_path);
+                return Ok(outcome);
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::CrossesDevices && from_path.is_file() => {
+                moves::move_across_devices(&from_path, &to_path)?;
+                return Ok(outcome);
+            }
+            Err(e) if error::is_file_in_use(&e) && attempt < delays.len() => {
+                debug!("{} is in use, retrying move in {}ms", from, delays[attempt]);
+                tokio::time::sleep(std::time::Duration::from_millis(delays[attempt])).await;
+                attempt += 1;
+            }
+            Err(e) => return Err(AppError::from(e).with_path(&from)),
+        }
+    }
+}
+
+// Saves the file's category tags with the file (extended attribute, NTFS stream or a
+// hidden sidecar, whichever the file system supports); an empty list removes them.
+// Returns where they were stored.
+#[command]
+async fn set_file_tags(path: String, tags: Vec<String>) -> Result<Option<file_tags::TagStorage>, AppError> {
+    let fs_path = paths::resolve_path(&path);
+    tokio::task::spawn_blocking(move || file_tags::write_tags(&fs_path, tags))
+        .await
+        .map_err(|e| AppError::io(format!("Failed to save tags: {}", e)))?
+}
+
+#[command]
+async fn get_file_tags(path: String) -> Result<Vec<String>, AppError> {
+    let fs_path = paths::resolve_path(&path);
+    let found = tokio::task::spawn_blocking(move || file_tags::read_tags(&fs_path))
+        .await
+        .map_err(|e| AppError::io(format!("Failed to read tags: {}", e)))??;
+    Ok(found.map(|found| found.tags).unwrap_or_default())
+}
+
+// Every tagged file in a directory (and below it with recursive), so previously
+// organized files can be shown
+#[command]
+async fn get_directory_tags(path: String, recursive: Option<bool>) -> Result<Vec<file_tags::FileTags>, AppError> {
+    let fs_path = paths::resolve_path(&path);
+    let recursive = recursive.unwrap_or(false);
+    tokio::task::spawn_blocking(move || file_tags::tags_in_directory(&fs_path, recursive))
+        .await
+        .map_err(|e| AppError::io(format!("Failed to read tags: {}", e)))?
+}
+
+// Records renames that happened in the batch journal, like journaled_move does for moves,
+// so they can be verified and undone along with the batch's moves
+fn journal_renames(app: &AppHandle, batch_id: &str, outcomes: &[rename::RenameOutcome]) {
+    let Ok(app_data_dir) = app_data_dir_of(app) else { return };
+    let renamed = outcomes.iter().filter(|o| !o.simulated && o.status == rename::RenameStatus::Renamed);
+    for outcome in renamed {
+        let size = fs::symlink_metadata(paths::resolve_path(&outcome.to)).map_or(0, |m| m.len());
+        let entry = journal::JournalEntry {
+            from: outcome.from.clone(),
+            to: outcome.to.clone(),
+            size,
+            sha256: None,
+            moved_at: journal::now_ms(),
+            duplicate_of: None,
+            archive_member: None,
+        };
+        if let Err(e) = journal::append(&app_data_dir, batch_id, &entry) {
+            warn!("{}", e);
+        }
+        audit(app, || audit::AuditEvent::new(audit::AuditAction::Move, entry.from, size).with_destination(entry.to));
+    }
+}
+
+// Renames a file within its directory. The original extension is kept unless new_name
+// has one; on_conflict (default "error") decides what happens when the name is taken.
+// dry_run reports the outcome without renaming. With a batch_id the rename is journaled.
+#[command]
+async fn rename_file(
+    app: AppHandle,
+    path: String,
+    new_name: String,
+    on_conflict: Option<rename::OnConflict>,
+    dry_run: Option<bool>,
+    batch_id: Option<String>,
+) -> Result<rename::RenameOutcome, AppError> {
+    if let Some(batch_id) = &batch_id {
+        journal::validate_batch_id(batch_id)?;
+    }
+    let source = paths::resolve_path(&path);
+    let on_conflict = on_conflict.unwrap_or_default();
+    let outcome = rename::rename_file(&source, &new_name, on_conflict, dry_run.unwrap_or(false)).map_err(|e| {
+        match e.path {
+            Some(_) => e,
+            None => e.with_path(&path),
+        }
+    })?;
+    if let Some(batch_id) = &batch_id {
+        journal_renames(&app, batch_id, std::slice::from_ref(&outcome));
+    }
+    Ok(outcome)
+}
+
+// Renames many files at once. Conflicts inside the batch (two files mapping to the same
+// name) and invalid names are reported before anything is renamed. With a batch_id each
+// rename is journaled.
+#[command]
+#[allow(clippy::too_many_arguments)]
+async fn rename_files(
+    app: AppHandle,
+    operations: State<'_, OperationsState>,
+    plan: Vec<rename::RenamePlanItem>,
+    on_conflict: Option<rename::OnConflict>,
+    dry_run: Option<bool>,
+    operation_id: Option<String>,
+    batch_id: Option<String>,
+) -> Result<rename::RenameBatchReport, AppError> {
+    if let Some(batch_id) = &batch_id {
+        journal::validate_batch_id(batch_id)?;
+    }
+    let on_conflict = on_conflict.unwrap_or_default();
+    let dry_run = dry_run.unwrap_or(false);
+    let token = operation_id.as_deref().map(|id| operations.token(id));
+    let batch_token = token.clone();
+    let report = tauri::async_runtime::spawn_blocking(move || {
+        rename::rename_files(&plan, on_conflict, dry_run, batch_token.as_ref())
+    })
+    .await
+    .map_err(|e| AppError::io(format!("Rename task failed: {}", e)))?;
+    if let Some(batch_id) = &batch_id {
+        journal_renames(&app, batch_id, &report.outcomes);
+    }
+    if let (Some(token), Some(id)) = (token, operation_id) {
+        if report.cancelled {
+            operation_cancelled(&app, &token, &id, report.outcomes.len(), Some(report.skipped.len()));
+        }
+        operations.finish(&id);
+    }
+    Ok(report)
+}
+
+#[tauri::command]
+async fn pick_directory(_app: AppHandle) -> Result<Vec<String>, AppError> {
+    use rfd::FileDialog;
+    use std::path::PathBuf;
+
+        // Open the dialog
+    let folders: Option<Vec<PathBuf>> = FileDialog::new()
+        .set_title("Select one or more directories")
+        .pick_folders();
+
+    // Handle the result
+    match folders {
+        Some(paths) => {
+            if paths.is_empty() {
+                // This case might happen if the dialog logic allows "OK" with no selection
+                debug!("No directories were selected.");
+                return Err(AppError::cancelled("No directories selected"));
+            } else {
+                debug!("You selected the following directories:");
+                let strs: Vec<String> = paths.iter().map(|p| p.to_string_lossy().into_owned()).collect();
+                for p in &strs {
+                    debug!("- {}", p);
+                }
+                return Ok(strs);
+            }
+        }
+        None => {
+            // This happens if the user presses "Cancel" or closes the dialog
+            debug!("Dialog was canceled. No directories selected.");
+            return Err(AppError::cancelled("User cancelled folder selection"));
+        }
+    }
+}
+
+// Files whose type runs as a program (see settings open_file.dangerous_extensions) are
+// revealed in the file manager instead, unless allow_dangerous is set
+#[tauri::command]
+async fn open_file(path: String, allow_dangerous: Option<bool>) -> Result<open_with::OpenOutcome, AppError> {
+    let path = std::path::PathBuf::from(path);
+    let dangerous = settings::with(|s| open_with::is_dangerous(&path, &s.open_file.dangerous_extensions));
+    if dangerous && !allow_dangerous.unwrap_or(false) {
+        info!("Revealing {} instead of opening it", path.display());
+        open_with::reveal(&path)?;
+        return Ok(open_with::OpenOutcome::Revealed);
+    }
+    open_with::open_default(&path)?;
+    Ok(open_with::OpenOutcome::Opened)
+}
+
+// Applications registered for the file's type, the default first
+#[tauri::command]
+async fn list_applications_for(path: String) -> Result<Vec<open_with::Application>, AppError> {
+    tauri::async_runtime::spawn_blocking(move || open_with::applications_for(Path::new(&path)))
+        .await
+        .map_err(|e| AppError::io(format!("Failed to look up applications: {}", e)))?
+}
+
+#[tauri::command]
+async fn open_file_with(path: String, app_id: String) -> Result<(), AppError> {
+    tauri::async_runtime::spawn_blocking(move || open_with::open_with(Path::new(&path), &app_id))
+        .await
+        .map_err(|e| AppError::io(format!("Failed to open file: {}", e)))?
+}
+
+// Managed LLM Server Commands
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AppVersionInfo {
+    pub version: String,
+    pub build_timestamp: String,
+}
+
+#[command]
+fn get_app_version() -> AppVersionInfo {
+    AppVersionInfo {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        build_timestamp: env!("BUILD_TIMESTAMP").to_string(),
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AppInfo {
+    pub version: String,
+    pub build_timestamp: String,
+    // Short hash, or "unknown" for builds from a source tarball
+    pub git_commit: String,
+    pub os: String,
+    pub os_version: Option<String>,
+    pub arch: String,
+    pub tauri_version: String,
+    // GPU acceleration the managed LLM server can use: "metal", "cuda", "vulkan" or "cpu"
+    pub gpu_backend: String,
+    pub app_data_dir: Option<String>,
+}
+
+impl AppInfo {
+    // Header block for diagnostic bundles
+    fn to_report(&self) -> String {
+        format!(
+            "===== App info =====\nVersion: {}\nBuilt: {}\nCommit: {}\nOS: {} {} ({})\nTauri: {}\nGPU backend: {}\nApp data: {}\n\n",
+            self.version,
+            self.build_timestamp,
+            self.git_commit,
+            self.os,
+            self.os_version.as_deref().unwrap_or(""),
+            self.arch,
+            self.tauri_version,
+            self.gpu_backend,
+            self.app_data_dir.as_deref().unwrap_or("unknown"),
+        )
+    }
+}
+
+async fn collect_app_info(app: &AppHandle) -> AppInfo {
+    let gpu_backend = match detect_gpu_capabilities(None).await {
+        Ok(caps) => caps.backend,
+        Err(e) => {
+            warn!("GPU detection failed while collecting app info: {}", e);
+            "unknown".to_string()
+        }
+    };
+    AppInfo {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        build_timestamp: env!("BUILD_TIMESTAMP").to_string(),
+        git_commit: env!("GIT_COMMIT").to_string(),
+        os: std::env::consts::OS.to_string(),
+        os_version: sysinfo::System::long_os_version(),
+        arch: std::env::consts::ARCH.to_string(),
+        tauri_version: tauri::VERSION.to_string(),
+        gpu_backend,
+        app_data_dir: app.path_resolver().app_data_dir().map(|dir| paths::display_path(&dir)),
+    }
+}
+
+// Version, build and platform details for the About dialog and support requests
+#[command]
+async fn get_app_info(app: AppHandle) -> Result<AppInfo, AppError> {
+    Ok(collect_app_info(&app).await)
+}
+
+#[command]
+async fn get_llm_server_status(app: AppHandle, state: State<'_, ManagedLLMState>) -> Result<ManagedLLMServerInfo, AppError> {
+    let app_data_dir = app.path_resolver()
+        .app_data_dir()
+        .ok_or_else(|| AppError::io("Could not get app data directory"))?;
+    
+    let server_dir = app_data_dir.join("llm-server");
+    
+    // Try multiple possible locations for the server executable
+    let possible_paths = if cfg!(target_os = "windows") {
+        vec![
+            server_dir.join("llama_server").join("llama_server.exe"),
+            server_dir.join("llama_server.exe"),
+            server_dir.join("llama_server").join("llama_server").join("llama_server.exe"),
+        ]
+    } else if cfg!(target_os = "macos") {
+        vec![
+            server_dir.join("mlx_server").join("mlx_server"),
+        ]
+    } else {
+        vec![
+            server_dir.join("llama_server").join("llama_server"),
+            server_dir.join("llama_server"),
+            server_dir.join("llama_server").join("llama_server").join("llama_server"),
+        ]
+    };
+    
+    let server_exe = possible_paths.iter().find(|path| path.exists()).cloned();
+    
+    debug!("Checking for server executable in possible paths:");
+    for (i, path) in possible_paths.iter().enumerate() {
+        debug!("  {}: {} (exists: {})", i, path.to_string_lossy(), path.exists());
+    }
+    debug!("Server directory exists: {}", server_dir.exists());
+    if server_dir.exists() {
+        debug!("Contents of server directory:");
+        if let Ok(entries) = fs::read_dir(&server_dir) {
+            for entry in entries.flatten() {
+                debug!("  - {}", entry.path().to_string_lossy());
+            }
+        }
+    }
+
+    let server_exe = match server_exe {
+        Some(path) => path,
+        None => {
+            return Ok(ManagedLLMServerInfo {
+                status: "not_downloaded".to_string(),
+                version: None,
+                path: None,
+                port: None,
+                error: None,
+                model_metadata: None,
+                warmed_up: false,
+                warm_up_ms: None,
+                idle_unload_at: None,
+            });
+        }
+    };
+
+    // Prefer the VERSION file shipped inside the archive, then the recorded download version
+    let stored_version = server_exe.parent()
+        .and_then(read_archive_version_file)
+        .or_else(|| read_downloaded_version(&app_data_dir));
+
+    // Get the host and port from the stored config (which holds the port actually in
+    // use), then the PID file, or use defaults
+    let (host, port, model_metadata, warm_up_ms, idle_unload_at) = {
+        let state_guard = state.lock().unwrap();
+        if let Some((_, process_info)) = state_guard.as_ref() {
+            (
+                process_info.config.host.clone(),
+                process_info.config.port,
+                process_info.model_metadata.clone(),
+                process_info.warm_up_ms,
+                idle_unload_at(&process_info.config),
+            )
+        } else if let Some((_, port, host, _)) = read_pid_file(&app_data_dir) {
+            (host, port, None, None, None)
+        } else {
+            ("127.0.0.1".to_string(), 8000, None, None, None)
+        }
+    };
+
+    // Check if process is running by trying to connect
+    let client = network::build_http_client();
+    let test_url = format!("http://{}:{}/v1/models", host, port);
+    
+    debug!("Testing server health at: {}", test_url);
+    match client.get(&test_url).timeout(std::time::Duration::from_secs(5)).send().await {
+        Ok(response) => {
+            debug!("Server responded with status: {}", response.status());
+            let status_code = response.status();
+            // Read the response body to properly close the connection
+            let _ = response.bytes().await;
+            
+            if status_code.is_success() {
+                Ok(ManagedLLMServerInfo {
+                    status: "running".to_string(),
+                    version: stored_version.clone(),
+                    path: Some(server_exe.to_string_lossy().to_string()),
+                    port: Some(port),
+                    error: None,
+                    model_metadata: model_metadata.clone(),
+                    warmed_up: warm_up_ms.is_some(),
+                    warm_up_ms,
+                    idle_unload_at,
+                })
+            } else {
+                warn!("Server responded but with error status: {}", status_code);
+                Ok(ManagedLLMServerInfo {
+                    status: "downloaded".to_string(),
+                    version: stored_version.clone(),
+                    path: Some(server_exe.to_string_lossy().to_string()),
+                    port: Some(port),
+                    error: Some(format!("Server responded with status: {}", status_code)),
+                    model_metadata: model_metadata.clone(),
+                    warmed_up: warm_up_ms.is_some(),
+                    warm_up_ms,
+                    idle_unload_at,
+                })
+            }
+        }
+        Err(e) => {
+            warn!("Failed to connect to server: {}", e);
+            Ok(ManagedLLMServerInfo {
+                status: "downloaded".to_string(),
+                version: stored_version.clone(),
+                path: Some(server_exe.to_string_lossy().to_string()),
+                port: Some(port),
+                error: Some(format!("Connection failed: {}", e)),
+                model_metadata: model_metadata.clone(),
+                warmed_up: warm_up_ms.is_some(),
+                warm_up_ms,
+                idle_unload_at,
+            })
+        }
+    }
+}
+
+// Helper function to check if Vulkan runtime is available on Windows
+#[cfg(target_os = "windows")]
+fn is_vulkan_available() -> bool {
+    use std::path::PathBuf;
+    
+    // Check for vulkan-1.dll in System32
+    let system32 = std::env::var("SystemRoot")
+        .map(|root| PathBuf::from(root).join("System32"))
+        .unwrap_or_else(|_| PathBuf::from("C:\\Windows\\System32"));
+    
+    let vulkan_dll = system32.join("vulkan-1.dll");
+    
+    if vulkan_dll.exists() {
+        info!("Vulkan runtime detected at: {}", vulkan_dll.display());
+        return true;
+    }
+    
+    info!("Vulkan runtime not found in System32");
+    false
+}
+
+#[cfg(target_os = "linux")]
+fn is_vulkan_available() -> bool {
+    use std::process::Command;
+
+    let output = match Command::new("vulkaninfo").output() {
+        Ok(output) => output,
+        Err(e) => {
+            warn!("Failed to run vulkaninfo: {}", e);
+            return false;
+        }
+    };
+    if output.status.success() {
+        info!("Vulkan runtime detected");
+        return true;
+    }
+    info!("Vulkan runtime not found");
+    return false;
+}
+
+// Stub function for macOS - Vulkan is not used on macOS, but this function
+// needs to exist for the type checker even though it will never be called
+#[cfg(target_os = "macos")]
+fn is_vulkan_available() -> bool {
+    false
+}
+
+// nvidia-smi, vulkaninfo and the GGUF read all block, so they run off the async runtime
+#[command]
+async fn detect_gpu_capabilities(model_path: Option<String>) -> Result<gpu::GpuCapabilities, AppError> {
+    tokio::task::spawn_blocking(move || {
+        let mut caps = gpu::detect_gpu();
+        if let Some(model_path) = model_path {
+            let metadata = gguf::read_gguf_metadata(Path::new(&model_path))
+                .map_err(|e| AppError::invalid_input(e).with_path(&model_path))?;
+            caps.recommended_gpu_layers = match (caps.backend.as_str(), caps.vram_bytes, metadata.block_count) {
+                ("cpu", _, _) => Some(0),
+                (_, Some(vram), Some(blocks)) => Some(gpu::recommend_gpu_layers(vram, metadata.file_size, blocks)),
+                _ => None,
+            };
+        }
+
+        info!("Detected GPU capabilities: {:?}", caps);
+        Ok(caps)
+    })
+    .await
+    .map_err(|e| AppError::io(format!("GPU detection failed: {}", e)))?
+}
+
+fn detect_hardware() -> model_catalog::Hardware {
+    let mut system = sysinfo::System::new();
+    system.refresh_memory();
+    let cpu_cores = system
+        .physical_core_count()
+        .or_else(|| std::thread::available_parallelism().ok().map(|n| n.get()))
+        .unwrap_or(1);
+    let gpu = gpu::detect_gpu();
+    model_catalog::Hardware {
+        total_ram_bytes: system.total_memory(),
+        cpu_cores,
+        gpu_backend: gpu.backend,
+        vram_bytes: gpu.vram_bytes,
+        unified_memory: gpu.unified_memory,
+    }
+}
+
+// Catalog models ranked for this machine's RAM, cores and GPU. use_case is "text"
+// (default) or "vision". A model_catalog.json in the app data directory replaces the
+// bundled catalog.
+#[command]
+async fn recommend_models(
+    app: AppHandle,
+    use_case: Option<String>,
+) -> Result<model_catalog::RecommendationReport, AppError> {
+    let mut catalog_paths = Vec::new();
+    if let Some(app_data_dir) = app.path_resolver().app_data_dir() {
+        catalog_paths.push(app_data_dir.join(model_catalog::CATALOG_FILE));
+    }
+    if let Some(bundled) = app.path_resolver().resolve_resource(format!("resources/{}", model_catalog::CATALOG_FILE)) {
+        catalog_paths.push(bundled);
+    }
+    let use_case = use_case.unwrap_or_else(|| model_catalog::DEFAULT_USE_CASE.to_string());
+    tokio::task::spawn_blocking(move || {
+        let catalog = model_catalog::load(&catalog_paths)?;
+        let hardware = detect_hardware();
+        let recommendations = model_catalog::recommend(&catalog, &hardware, &use_case)?;
+        Ok(model_catalog::RecommendationReport { hardware, use_case, recommendations })
+    })
+    .await
+    .map_err(|e| AppError::io(format!("Model recommendation failed: {}", e)))?
+}
+
+const DOWNLOAD_MAX_ATTEMPTS: u32 = 4;
+const DOWNLOAD_PROGRESS_INTERVAL: std::time::Duration = std::time::Duration::from_millis(250);
+
+#[derive(Debug, Clone, Serialize)]
+struct DownloadProgress {
+    downloaded_bytes: u64,
+    total_bytes: Option<u64>,
+    attempt: u32,
+    #[serde(flatten)]
+    estimate: progress::ProgressEstimate,
+}
+
+enum DownloadError {
+    // Network hiccups and 5xx responses, worth retrying
+    Transient(String),
+    Fatal(String),
+}
+
+// What to fetch and where progress goes. The token, if any, is sent as a bearer
+// Authorization header.
+struct DownloadSource<'a> {
+    url: &'a str,
+    token: Option<&'a str>,
+    progress_event: &'a str,
+}
+
+// Streams `url` into `dest`, continuing from the end of an existing partial file
+// with a Range request when the server supports it
+async fn download_to_file(
+    app: &AppHandle,
+    client: &reqwest::Client,
+    source: &DownloadSource<'_>,
+    dest: &Path,
+    attempt: u32,
+) -> Result<(), DownloadError> {
+    let existing_len = fs::metadata(dest).map(|m| m.len()).unwrap_or(0);
+
+    let mut request = client.get(source.url);
+    if let Some(token) = source.token {
+        request = request.bearer_auth(token);
+    }
+    if existing_len > 0 {
+        info!("Resuming download from byte {}", existing_len);
+        request = request.header(reqwest::header::RANGE, format!("bytes={}-", existing_len));
+    }
+
+    let mut response = request
+        .send()
+        .await
+        .map_err(|e| DownloadError::Transient(format!("Failed to download: {}", e)))?;
+
+    let status = response.status();
+    if status == reqwest::StatusCode::RANGE_NOT_SATISFIABLE {
+        // The partial file doesn't match what the server has; start over
+        let _ = fs::remove_file(dest);
+        return Err(DownloadError::Transient("Server rejected resume range".to_string()));
+    }
+    if !status.is_success() {
+        let message = format!("Download failed with status: {}", status);
+        return Err(if status.is_server_error() {
+            DownloadError::Transient(message)
+        } else {
+            DownloadError::Fatal(message)
+        });
+    }
+
+    // A plain 200 means the server ignored the Range header, so rewrite from scratch
+    let resumed = status == reqwest::StatusCode::PARTIAL_CONTENT;
+    let mut downloaded = if resumed { existing_len } else { 0 };
+    let total = response.content_length().map(|len| len + downloaded);
+
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(resumed)
+        .truncate(!resumed)
+        .open(dest)
+        .map_err(|e| DownloadError::Fatal(format!("Failed to create download file: {}", e)))?;
+
+    // Resumed bytes were already there, so the rate starts from them
+    let mut tracker = progress::ProgressTracker::new(progress::RateUnit::Bytes, total);
+    tracker.update(downloaded);
+    let mut emit_progress = |downloaded: u64| {
+        let _ = app.emit_all(source.progress_event, DownloadProgress {
+            downloaded_bytes: downloaded,
+            total_bytes: total,
+            attempt,
+            estimate: tracker.update(downloaded),
+        });
+    };
+
+    let mut last_emit: Option<std::time::Instant> = None;
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|e| DownloadError::Transient(format!("Failed to read response: {}", e)))?
+    {
+        // Stop between chunks so the partial file holds whole writes and can be resumed
+        if shutdown::SHUTDOWN.is_shutting_down() {
+            let _ = file.sync_data();
+            return Err(DownloadError::Fatal(format!(
+                "Download interrupted by shutdown after {} bytes",
+                downloaded
+            )));
+        }
+        file.write_all(&chunk)
+            .map_err(|e| DownloadError::Fatal(format!("Failed to write download: {}", e)))?;
+        downloaded += chunk.len() as u64;
+
+        if last_emit.is_none_or(|t| t.elapsed() >= DOWNLOAD_PROGRESS_INTERVAL) {
+            emit_progress(downloaded);
+            last_emit = Some(std::time::Instant::now());
+        }
+    }
+    emit_progress(downloaded);
+
+    if let Some(total) = total {
+        if downloaded < total {
+            return Err(DownloadError::Transient(format!(
+                "Connection closed after {} of {} bytes",
+                downloaded, total
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+async fn download_with_retry(
+    app: &AppHandle,
+    client: &reqwest::Client,
+    source: &DownloadSource<'_>,
+    dest: &Path,
+) -> Result<(), String> {
+    let mut attempt = 1;
+    loop {
+        match download_to_file(app, client, source, dest, attempt).await {
+            Ok(()) => return Ok(()),
+            Err(DownloadError::Fatal(e)) => return Err(e),
+            Err(DownloadError::Transient(e)) if attempt < DOWNLOAD_MAX_ATTEMPTS => {
+                let delay = std::time::Duration::from_secs(1 << (attempt - 1));
+                warn!("Download attempt {} failed: {}. Retrying in {:?}", attempt, e, delay);
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            Err(DownloadError::Transient(e)) => {
+                return Err(format!("Download failed after {} attempts: {}", attempt, e));
+            }
+        }
+    }
+}
+
+fn sha256_file(path: &Path) -> Result<String, String> {
+    use sha2::{Digest, Sha256};
+
+    let mut file = fs::File::open(paths::normalize_path(path))
+        .map_err(|e| format!("Failed to open file for hashing: {}", e))?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer)
+            .map_err(|e| format!("Failed to read file for hashing: {}", e))?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+// Finds the hash for `filename` in sha256sum-style output ("<hash>  <name>" or "<hash> *<name>")
+fn parse_checksums(checksums: &str, filename: &str) -> Option<String> {
+    checksums.lines().find_map(|line| {
+        let (hash, name) = line.trim().split_once(char::is_whitespace)?;
+        let name = name.trim_start().trim_start_matches('*');
+        (name == filename).then(|| hash.to_string())
+    })
+}
+
+// Fetches the hash of `filename` from the SHA256SUMS file published with an llm-v
+// release. A release without one is an error: its archives can't be verified.
+async fn fetch_release_checksum(
+    client: &reqwest::Client,
+    version: &str,
+    filename: &str,
+) -> Result<String, String> {
+    let url = format!(
+        "https://github.com/BorisBesky/file-organizer-desktop/releases/download/llm-v{}/SHA256SUMS",
+        version
+    );
+    let response = client.get(&url)
+        .send()
+        .await
+        .map_err(|e| format!("Failed to fetch checksums: {}", e))?;
+
+    if response.status() == reqwest::StatusCode::NOT_FOUND {
+        return Err(format!("Release llm-v{} publishes no checksums, so {} can't be verified", version, filename));
+    }
+    if !response.status().is_success() {
+        return Err(format!("Failed to fetch checksums: status {}", response.status()));
+    }
+
+    let text = response.text()
+        .await
+        .map_err(|e| format!("Failed to read checksums: {}", e))?;
+    parse_checksums(&text, filename).ok_or_else(|| format!("Checksum file does not list {}", filename))
+}
+
+type SchedulerState = Arc<Mutex<Vec<scheduler::Schedule>>>;
+
+// How often the scheduler checks for due scans
+const SCHEDULER_TICK: std::time::Duration = std::time::Duration::from_secs(30);
+
+#[derive(Clone, Serialize)]
+struct ScheduledScanComplete {
+    schedule_id: String,
+    path: String,
+    started_at: u64,
+    total_files: u64,
+    new_file_count: u64,
+    error: Option<String>,
+}
+
+// Inventories the scheduled directory: file counts and sizes by extension, plus the
+// files that changed since the previous run
+#[tracing::instrument(skip_all, fields(schedule = %schedule.id))]
+async fn run_scheduled_scan(schedule: &scheduler::Schedule) -> scheduler::ScheduledScanResult {
+    let mut result = scheduler::ScheduledScanResult {
+        schedule_id: schedule.id.clone(),
+        path: schedule.path.clone(),
+        started_at: scheduler::now_secs(),
+        finished_at: 0,
+        total_files: 0,
+        total_bytes: 0,
+        by_extension: Default::default(),
+        new_file_count: 0,
+        new_files: Vec::new(),
+        error: None,
+    };
+
+    let filter = ScanFilter::new(Some(schedule.options.include_hidden), schedule.options.ignored_names.clone());
+    let listing = list_files(
+        &schedule.path,
+        schedule.options.include_subdirectories,
+        schedule.options.follow_symlinks,
+        &filter,
+    );
+    match listing {
+        Ok(entries) => {
+            let since = schedule.last_run.unwrap_or(0);
+            for file in entries.into_iter().map(|entry| entry.path) {
+                let Ok(metadata) = fs::metadata(paths::resolve_path(&file)) else {
+                    continue;
+                };
+                let extension = Path::new(&file)
+                    .extension()
+                    .map(|ext| ext.to_string_lossy().to_lowercase())
+                    .unwrap_or_default();
+                let stats = result.by_extension.entry(extension).or_default();
+                stats.count += 1;
+                stats.bytes += metadata.len();
+                result.total_files += 1;
+                result.total_bytes += metadata.len();
+
+                let modified = metadata.modified().ok()
+                    .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+                    .map(|d| d.as_secs())
+                    .unwrap_or(0);
+                if modified >= since {
+                    result.new_file_count += 1;
+                    if result.new_files.len() < scheduler::MAX_NEW_FILES_LISTED {
+                        result.new_files.push(file);
+                    }
+                }
+            }
+        }
+        Err(e) => result.error = Some(e.message),
+    }
+
+    result.finished_at = scheduler::now_secs();
+    result
+}
+
+async fn run_scheduler(app: AppHandle, state: SchedulerState, app_data_dir: std::path::PathBuf) {
+    loop {
+        let now = scheduler::now_secs();
+        let due: Vec<scheduler::Schedule> = state.lock().unwrap().iter()
+            .filter(|schedule| schedule.next_run <= now)
+            .cloned()
+            .collect();
+
+        for schedule in due {
+            info!("Running scheduled scan {} of {}", schedule.id, schedule.path);
+            let result = run_scheduled_scan(&schedule).await;
+            if let Err(e) = scheduler::store_result(&app_data_dir, &result, schedule.keep_results) {
+                error!("Failed to store scheduled scan result: {}", e);
+            }
+
+            {
+                let mut schedules = state.lock().unwrap();
+                // The schedule may have been deleted while it ran
+                if let Some(stored) = schedules.iter_mut().find(|s| s.id == schedule.id) {
+                    stored.last_run = Some(result.started_at);
+                    stored.next_run = stored.spec.next_run_after(result.finished_at).unwrap_or(u64::MAX);
+                }
+                if let Err(e) = scheduler::save_schedules(&app_data_dir, &schedules) {
+                    warn!("Failed to save schedules: {}", e);
+                }
+            }
+
+            let _ = app.emit_all("scheduled-scan-complete", ScheduledScanComplete {
+                schedule_id: result.schedule_id.clone(),
+                path: result.path.clone(),
+                started_at: result.started_at,
+                total_files: result.total_files,
+                new_file_count: result.new_file_count,
+                error: result.error.clone(),
+            });
+            if schedule.notify {
+                let sent = match &result.error {
+                    Some(error) => send_notification(
+                        &app,
+                        &i18n::t("notification.scheduled_scan_failed.title"),
+                        &i18n::tf("notification.scheduled_scan_failed.body", &[("path", &result.path), ("error", error)]),
+                        "error",
+                    ),
+                    None => send_notification(
+                        &app,
+                        &i18n::t("notification.scheduled_scan_complete.title"),
+                        &i18n::tf(
+                            "notification.scheduled_scan_complete.body",
+                            &[
+                                ("path", &result.path),
+                                ("total", &result.total_files.to_string()),
+                                ("new", &result.new_file_count.to_string()),
+                            ],
+                        ),
+                        "success",
+                    ),
+                };
+                if let Err(e) = sent {
+                    warn!("{}", e);
+                }
+            }
+        }
+
+        tokio::time::sleep(SCHEDULER_TICK).await;
+    }
+}
+
+// Restores saved schedules, applies each one's missed run policy and starts the runner
+fn start_scheduler(app: &AppHandle, state: &SchedulerState) {
+    let Some(app_data_dir) = app.path_resolver().app_data_dir() else {
+        warn!("Scheduled scans disabled: could not get app data directory");
+        return;
+    };
+
+    let now = scheduler::now_secs();
+    let mut schedules = scheduler::load_schedules(&app_data_dir);
+    for schedule in &mut schedules {
+        if scheduler::reconcile_missed_run(schedule, now) {
+            info!("Scheduled scan {} missed a run while the app was closed; running it now", schedule.id);
+        }
+    }
+    if let Err(e) = scheduler::save_schedules(&app_data_dir, &schedules) {
+        warn!("Failed to save schedules: {}", e);
+    }
+    *state.lock().unwrap() = schedules;
+
+    tauri::async_runtime::spawn(run_scheduler(app.clone(), state.clone(), app_data_dir));
+}
+
+// cron_or_interval is an interval like "6h" or a five-field cron expression
+#[command]
+#[allow(clippy::too_many_arguments)]
+fn create_schedule(
+    app: AppHandle,
+    state: State<'_, SchedulerState>,
+    path: String,
+    cron_or_interval: String,
+    options: Option<scheduler::ScanOptions>,
+    missed_run_policy: Option<String>,
+    notify: Option<bool>,
+    keep_results: Option<usize>,
+) -> Result<scheduler::Schedule, AppError> {
+    let app_data_dir = app.path_resolver().app_data_dir()
+        .ok_or_else(|| AppError::io("Could not get app data directory"))?;
+    if !paths::normalize_path(&path).is_dir() {
+        return Err(AppError::not_found(format!("{} is not a directory", path)).with_path(&path));
+    }
+    let missed_run_policy = missed_run_policy.unwrap_or_else(|| "run".to_string());
+    if missed_run_policy != "run" && missed_run_policy != "skip" {
+        return Err(AppError::invalid_input(format!("Unknown missed run policy: {}", missed_run_policy)));
+    }
+
+    let spec = scheduler::ScheduleSpec::parse(&cron_or_interval).map_err(AppError::invalid_input)?;
+    let now = scheduler::now_secs();
+    let next_run = spec.next_run_after(now)
+        .ok_or_else(|| AppError::invalid_input(format!("'{}' never runs", cron_or_interval)))?;
+    let mut schedule = scheduler::Schedule {
+        id: format!("schedule-{}", now),
+        path,
+        spec,
+        options: options.unwrap_or_default(),
+        missed_run_policy,
+        notify: notify.unwrap_or(false),
+        keep_results: keep_results.unwrap_or(scheduler::DEFAULT_KEEP_RESULTS).max(1),
+        created_at: now,
+        last_run: None,
+        next_run,
+    };
+
+    let mut schedules = state.lock().unwrap();
+    // Ids are second-based; keep them unique when several are created at once
+    while schedules.iter().any(|s| s.id == schedule.id) {
+        schedule.id.push('a');
+    }
+    schedules.push(schedule.clone());
+    scheduler::save_schedules(&app_data_dir, &schedules).map_err(AppError::io)?;
+    Ok(schedule)
+}
+
+#[command]
+fn list_schedules(state: State<'_, SchedulerState>) -> Result<Vec<scheduler::Schedule>, AppError> {
+    Ok(state.lock().unwrap().clone())
+}
+
+#[command]
+fn delete_schedule(app: AppHandle, state: State<'_, SchedulerState>, id: String) -> Result<(), AppError> {
+    let app_data_dir = app.path_resolver().app_data_dir()
+        .ok_or_else(|| AppError::io("Could not get app data directory"))?;
+    let mut schedules = state.lock().unwrap();
+    let before = schedules.len();
+    schedules.retain(|schedule| schedule.id != id);
+    if schedules.len() == before {
+        return Err(AppError::not_found(format!("No schedule with id {}", id)));
+    }
+    scheduler::save_schedules(&app_data_dir, &schedules).map_err(AppError::io)?;
+    scheduler::delete_results(&app_data_dir, &id);
+    Ok(())
+}
+
+// Stored results for a schedule, newest first
+#[command]
+fn get_schedule_results(app: AppHandle, id: String) -> Result<Vec<scheduler::ScheduledScanResult>, AppError> {
+    let app_data_dir = app.path_resolver().app_data_dir()
+        .ok_or_else(|| AppError::io("Could not get app data directory"))?;
+    scheduler::validate_id(&id).map_err(AppError::invalid_input)?;
+    Ok(scheduler::load_results(&app_data_dir, &id))
+}
+
+// Shows an OS notification. "error" notifications also play the default sound so a
+// failure is noticed while the user is in another app.
+fn send_notification(app: &AppHandle, title: &str, body: &str, kind: &str) -> Result<(), AppError> {
+    let mut notification = tauri::api::notification::Notification::new(&app.config().tauri.bundle.identifier)
+        .title(title)
+        .body(body);
+    if kind == "error" {
+        notification = notification.sound(tauri::api::notification::Sound::Default);
+    }
+    notification.show()
+        .map_err(|e| AppError::unsupported(format!("Failed to show notification: {}", e)))
+}
+
+// kind is "info", "success" or "error"
+#[command]
+fn notify(app: AppHandle, title: String, body: String, kind: Option<String>) -> Result<(), AppError> {
+    send_notification(&app, &title, &body, kind.as_deref().unwrap_or("info"))
+}
+
+fn huggingface_token() -> Option<String> {
+    settings::with(|settings| settings.huggingface_token.clone()).filter(|token| !token.trim().is_empty())
+}
+
+// Hugging Face repos with GGUF files matching query. Uses the token saved in settings
+// (huggingface_token), if any, so gated repos show up.
+#[command]
+async fn search_hf_models(
+    query: String,
+    filters: Option<hf_models::SearchFilters>,
+) -> Result<Vec<hf_models::HfModel>, AppError> {
+    hf_models::search(&query, &filters.unwrap_or_default(), huggingface_token().as_deref()).await
+}
+
+// The .gguf files in a repo with size, quantization (from the filename), LFS sha256
+// and download URL
+#[command]
+async fn list_hf_model_files(repo_id: String) -> Result<Vec<hf_models::HfModelFile>, AppError> {
+    hf_models::list_files(&repo_id, huggingface_token().as_deref()).await
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct DownloadedModel {
+    path: String,
+    sha256: Option<String>,
+    metadata: gguf::GgufMetadata,
+}
+
+// Downloads one GGUF from a Hugging Face repo into app_data_dir/models, resuming a
+// partial download and checking it against the sha256 in the repo's LFS metadata.
+// Progress goes out as model-download-progress events.
+#[command]
+async fn download_hf_model(app: AppHandle, repo_id: String, filename: String) -> Result<DownloadedModel, AppError> {
+    let _in_flight = shutdown::SHUTDOWN.enter().ok_or_else(|| AppError::cancelled("The app is shutting down"))?;
+    let app_data_dir = app.path_resolver()
+        .app_data_dir()
+        .ok_or_else(|| AppError::io("Could not get app data directory"))?;
+    let dest = hf_models::model_path(&app_data_dir, &repo_id, &filename)?;
+    let token = huggingface_token();
+    let file = hf_models::list_files(&repo_id, token.as_deref())
+        .await?
+        .into_iter()
+        .find(|file| file.filename == filename)
+        .ok_or_else(|| AppError::not_found(format!("{} has no file {}", repo_id, filename)))?;
+
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| AppError::from_io(&e, format!("Failed to create models directory: {}", e)).with_path(parent))?;
+    }
+    let partial_path = dest.with_extension("gguf.part");
+    let source = DownloadSource {
+        url: &file.download_url,
+        token: token.as_deref(),
+        progress_event: "model-download-progress",
+    };
+    info!("Downloading {} from {}", filename, repo_id);
+    download_with_retry(&app, &network::build_http_client(), &source, &partial_path).await
+        .map_err(AppError::network)?;
+
+    if let Some(expected) = &file.sha256 {
+        let hashed_path = partial_path.clone();
+        let actual = tokio::task::spawn_blocking(move || sha256_file(&hashed_path))
+            .await
+            .map_err(|e| AppError::io(format!("Hashing failed: {}", e)))?
+            .map_err(|e| AppError::io(e).with_path(&partial_path))?;
+        if !actual.eq_ignore_ascii_case(expected) {
+            let _ = fs::remove_file(&partial_path);
+            return Err(AppError::new(
+                error::AppErrorKind::InvalidData,
+                format!("Checksum mismatch for {}: expected {}, got {}", filename, expected, actual),
+            ));
+        }
+    }
+    let metadata = gguf::read_gguf_metadata(&partial_path).map_err(|e| {
+        let _ = fs::remove_file(&partial_path);
+        AppError::new(error::AppErrorKind::InvalidData, e)
+    })?;
+    fs::rename(&partial_path, &dest)
+        .map_err(|e| AppError::from_io(&e, format!("Failed to finalize model file: {}", e)).with_path(&dest))?;
+
+    Ok(DownloadedModel { path: paths::display_path(&dest), sha256: file.sha256, metadata })
+}
+
+#[command]
+async fn download_llm_server(
+    app: AppHandle,
+    version: String,
+    notify_on_complete: Option<bool>,
+) -> Result<String, AppError> {
+    let result = install_llm_server(app.clone(), version.clone()).await;
+    if notify_on_complete.unwrap_or(false) {
+        let sent = match &result {
+            Ok(_) => send_notification(
+                &app,
+                &i18n::t("notification.llm_server_ready.title"),
+                &i18n::tf("notification.llm_server_ready.body", &[("version", &version)]),
+                "success",
+            ),
+            Err(e) => send_notification(&app, &i18n::t("notification.llm_server_failed.title"), &e.message, "error"),
+        };
+        if let Err(e) = sent {
+            warn!("{}", e);
+        }
+    }
+    result
+}
+
+#[tracing::instrument(skip(app))]
+async fn install_llm_server(app: AppHandle, version: String) -> Result<String, AppError> {
+    let _in_flight = shutdown::SHUTDOWN.enter().ok_or_else(|| AppError::cancelled("The app is shutting down"))?;
+    let app_data_dir = app.path_resolver()
+        .app_data_dir()
+        .ok_or_else(|| AppError::io("Could not get app data directory"))?;
+    
+    let server_dir = app_data_dir.join("llm-server");
+    fs::create_dir_all(&server_dir)
+        .map_err(|e| AppError::from_io(&e, format!("Failed to create server directory: {}", e)).with_path(&server_dir))?;
+
+    // Determine platform and download URL
+    let (filename, extract_dir) = if cfg!(target_os = "windows") {
+        // Check if Vulkan is available for Windows
+        if is_vulkan_available() {
+            info!("Using Vulkan-enabled server");
+            ("llama_server-windows-vulkan.zip", "llama_server")
+        } else {
+            info!("Using CPU-only server (Vulkan not available)");
+            ("llama_server-windows-cpu.zip", "llama_server")
+        }
+    } else if cfg!(target_os = "macos") {
+        ("mlx_server-macos.zip", "mlx_server")
+    } else {
+        if is_vulkan_available() {
+            info!("Using Vulkan-enabled server");
+            ("llama_server-linux-vulkan.tar.gz", "llama_server")
+        } else {
+            info!("Using CPU-only server (Vulkan not available)");
+            ("llama_server-linux-cpu.tar.gz", "llama_server")
+        }
+    };
+
+    let download_url = format!(
+        "https://github.com/BorisBesky/file-organizer-desktop/releases/download/llm-v{}/{}",
+        version, filename
+    );
+    debug!("Download URL: {}", download_url);
+    debug!("Version: {}", version);
+    debug!("Filename: {}", filename);
+    debug!("Extract dir: {}", extract_dir);
+    debug!("Server dir: {}", server_dir.to_string_lossy());
+
+    let archive_path = server_dir.join(filename);
+    // The version is part of the name so a partial download of another release is
+    // never resumed into this one
+    let partial_name = format!("{}.{}.part", filename, pipeline::sanitize_file_name(&version));
+    let partial_path = server_dir.join(&partial_name);
+    if let Ok(entries) = fs::read_dir(&server_dir) {
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let stale = name.starts_with(filename) && name.ends_with(".part") && name != partial_name;
+            if stale {
+                let _ = fs::remove_file(entry.path());
+            }
+        }
+    }
+
+    // Nothing is downloaded for a release whose archives can't be verified
+    let client = network::build_http_client();
+    let expected = fetch_release_checksum(&client, &version, filename).await.map_err(AppError::network)?;
+
+    // Stream the archive to disk, resuming a previous partial download if present
+    let source = DownloadSource { url: &download_url, token: None, progress_event: "llm-server-download-progress" };
+    download_with_retry(&app, &client, &source, &partial_path).await
+        .map_err(AppError::network)?;
+
+    // Verify against the checksum published with the release before extracting
+    let actual = sha256_file(&partial_path)
+        .map_err(|e| AppError::io(e).with_path(&partial_path))?;
+    if !actual.eq_ignore_ascii_case(&expected) {
+        let _ = fs::remove_file(&partial_path);
+        return Err(AppError::invalid_input(format!(
+            "Checksum mismatch for {}: expected {}, got {}",
+            filename, expected, actual
+        )));
+    }
+    info!("Checksum verified for {}: {}", filename, actual);
+
+    fs::rename(&partial_path, &archive_path)
+        .map_err(|e| AppError::from_io(&e, format!("Failed to finalize archive: {}", e)).with_path(&archive_path))?;
+
+    // Extract into a staging directory first; the installed server is only replaced
+    // once the new one has been checked
+    let extract_path = server_dir.join(extract_dir);
+    let staging_root = server_dir.join(format!("{}.staging", extract_dir));
+    if staging_root.exists() {
+        fs::remove_dir_all(&staging_root)
+            .map_err(|e| AppError::from_io(&e, format!("Failed to clear staging directory: {}", e)).with_path(&staging_root))?;
+    }
+    let staged_path = staging_root.join(extract_dir);
+    let staged = stage_llm_server(&archive_path, &staging_root, &staged_path, &version);
+    // Clean up archive file
+    let _ = fs::remove_file(&archive_path);
+    if let Err(e) = staged {
+        let _ = fs::remove_dir_all(&staging_root);
+        return Err(e);
+    }
+
+    // Swap the staged server in, keeping the old one until the rename has worked
+    let replaced_path = server_dir.join(format!("{}.replaced", extract_dir));
+    let _ = fs::remove_dir_all(&replaced_path);
+    if extract_path.exists() {
+        fs::rename(&extract_path, &replaced_path)
+            .map_err(|e| AppError::from_io(&e, format!("Failed to remove existing server: {}", e)).with_path(&extract_path))?;
+    }
+    if let Err(e) = fs::rename(&staged_path, &extract_path) {
+        if replaced_path.exists() {
+            let _ = fs::rename(&replaced_path, &extract_path);
+        }
+        let _ = fs::remove_dir_all(&staging_root);
+        return Err(AppError::from_io(&e, format!("Failed to install server: {}", e)).with_path(&extract_path));
+    }
+    let _ = fs::remove_dir_all(&replaced_path);
+    let _ = fs::remove_dir_all(&staging_root);
+
+    // Store the downloaded version
+    if let Err(e) = store_downloaded_version(&app_data_dir, &version) {
+        warn!("Failed to store version metadata: {}", e);
+    }
+
+    Ok(extract_path.to_string_lossy().to_string())
+}
+
+// Extracts the server archive under `staging_root`, makes the binary executable and
+// checks the VERSION baked into it, leaving the result at `staged_path`
+fn stage_llm_server(archive_path: &Path, staging_root: &Path, staged_path: &Path, version: &str) -> Result<(), AppError> {
+    // Windows bundles are extracted one level deeper, matching the paths used by
+    // get_llm_server_status and start_llm_server
+    let extract_dest = if cfg!(target_os = "windows") {
+        staged_path.to_path_buf()
+    } else {
+        staging_root.to_path_buf()
+    };
+    archive::extract_archive(archive_path, &extract_dest)
+        .map_err(|e| AppError::io(e).with_path(archive_path))?;
+
+    debug!("Extraction completed. Checking extracted files:");
+    if staged_path.exists() {
+        debug!("Extract path exists: {}", staged_path.to_string_lossy());
+        if let Ok(entries) = fs::read_dir(staged_path) {
+            for entry in entries.flatten() {
+                debug!("  - {}", entry.path().to_string_lossy());
+            }
+        }
+    } else {
+        debug!("Extract path does not exist: {}", staged_path.to_string_lossy());
+    }
+
+    // Make executable on Unix systems
+    #[cfg(unix)]
+    {
+        let server_exe = if cfg!(target_os = "macos") {
+            staged_path.join("mlx_server")
+        } else {
+            staged_path.join("llama_server")
+        };
+        
+        use std::os::unix::fs::PermissionsExt;
+        let mut perms = fs::metadata(&server_exe)
+            .map_err(|e| AppError::from_io(&e, format!("Failed to get file metadata: {}", e)).with_path(&server_exe))?
+            .permissions();
+        perms.set_mode(0o755);
+        fs::set_permissions(&server_exe, perms)
+            .map_err(|e| AppError::from_io(&e, format!("Failed to set executable permissions: {}", e)).with_path(&server_exe))?;
+    }
+
+    // Cross-check the version baked into the archive against the release we asked for
+    if let Some(archive_version) = read_archive_version_file(staged_path) {
+        if archive_version.trim_start_matches('v') != version.trim().trim_start_matches('v') {
+            return Err(AppError::invalid_input(format!(
+                "Downloaded archive reports version {} but version {} was requested",
+                archive_version, version
+            )));
+        }
+    }
+    Ok(())
+}
+
+#[command]
+async fn update_llm_server(
+    app: AppHandle,
+    version: String,
+    config: ManagedLLMConfig,
+    state: State<'_, ManagedLLMState>
+) -> Result<String, AppError> {
+    info!("Starting LLM server update to version: {}", version);
+    
+    let app_data_dir = app.path_resolver()
+        .app_data_dir()
+        .ok_or_else(|| AppError::io("Could not get app data directory"))?;
+    
+    let server_dir = app_data_dir.join("llm-server");
+    
+    // Determine the server directory based on platform
+    let extract_dir = if cfg!(target_os = "windows") {
+        "llama_server"
+    } else if cfg!(target_os = "macos") {
+        "mlx_server"
+    } else {
+        "llama_server"
+    };
+    
+    let server_path = server_dir.join(extract_dir);
+    let backup_path = server_dir.join(format!("{}_backup", extract_dir));
+    
+    // Step 1: Stop the server if running
+    info!("Stopping server...");
+    let was_running = {
+        let server_state = state.lock().unwrap();
+        server_state.is_some()
+    };
+    
+    if was_running {
+        if let Err(e) = stop_llm_server(app.clone(), state.clone()).await {
+            warn!("Failed to stop server: {}", e);
+            // Continue anyway
+        }
+        // Wait for server to fully stop
+        std::thread::sleep(std::time::Duration::from_secs(2));
+    }
+    
+    // Step 2: Backup existing server directory
+    if server_path.exists() {
+        info!("Backing up existing server...");
+        
+        // Remove old backup if it exists
+        if backup_path.exists() {
+            fs::remove_dir_all(&backup_path)
+                .map_err(|e| AppError::from_io(&e, format!("Failed to remove old backup: {}", e)).with_path(&backup_path))?;
+        }
+        
         // Create backup
         fs::rename(&server_path, &backup_path)
-            .map_err(|e| format!("Failed to create backup: {}", e))?;
+            .map_err(|e| AppError::from_io(&e, format!("Failed to create backup: {}", e)).with_path(&server_path))?;
+        
+        info!("Backup created at: {}", backup_path.to_string_lossy());
+    } else {
+        info!("No existing server found, performing fresh installation");
+    }
+    
+    // Step 3: Download and extract new version
+    info!("Downloading new server version...");
+    let download_result = install_llm_server(app.clone(), version.clone()).await;
+    
+    match download_result {
+        Ok(_) => {
+            info!("Download successful, verifying installation...");
+            
+            // Step 4: Try to start the server with new version
+            if was_running {
+                info!("Attempting to start updated server...");
+                let start_result = start_llm_server(app.clone(), Some(config.clone()), state.clone()).await;
+                
+                match start_result {
+                    Ok(_) => {
+                        // Step 5a: Success - remove backup
+                        info!("Server started successfully, removing backup...");
+                        if backup_path.exists() {
+                            if let Err(e) = fs::remove_dir_all(&backup_path) {
+                                warn!("Failed to remove backup: {}", e);
+                                // Not a critical error, update was successful
+                            }
+                        }
+                        Ok(format!("Successfully updated to version {}", version))
+                    }
+                    Err(e) => {
+                        // Step 5b: Failed to start - restore backup
+                        error!("Failed to start new server: {}, restoring backup...", e);
+                        
+                        // Remove the failed new installation
+                        if server_path.exists() {
+                            if let Err(remove_err) = fs::remove_dir_all(&server_path) {
+                                warn!("Failed to remove failed installation: {}", remove_err);
+                            }
+                        }
+                        
+                        // Restore from backup
+                        if backup_path.exists() {
+                            fs::rename(&backup_path, &server_path)
+                                .map_err(|e| AppError::from_io(&e, format!("Failed to restore backup: {}", e)).with_path(&backup_path))?;
+                            
+                            info!("Backup restored, attempting to start old server...");
+                            // Try to restart the old server
+                            if let Err(restart_err) = start_llm_server(app, Some(config), state).await {
+                                warn!("Failed to restart old server: {}", restart_err);
+                            }
+                        }
+                        
+                        Err(AppError::new(e.kind, format!("Update failed: {}. Restored previous version.", e)))
+                    }
+                }
+            } else {
+                // Server wasn't running, just remove backup
+                info!("Update completed (server was not running)");
+                if backup_path.exists() {
+                    if let Err(e) = fs::remove_dir_all(&backup_path) {
+                        warn!("Failed to remove backup: {}", e);
+                    }
+                }
+                Ok(format!("Successfully updated to version {}", version))
+            }
+        }
+        Err(e) => {
+            // Step 5c: Download failed - restore backup
+            error!("Download failed: {}, restoring backup...", e);
+            
+            if backup_path.exists() {
+                // Remove any partial download
+                if server_path.exists() {
+                    if let Err(remove_err) = fs::remove_dir_all(&server_path) {
+                        warn!("Failed to remove partial download: {}", remove_err);
+                    }
+                }
+                
+                // Restore backup
+                fs::rename(&backup_path, &server_path)
+                    .map_err(|e| AppError::from_io(&e, format!("Failed to restore backup: {}", e)).with_path(&backup_path))?;
+                
+                info!("Backup restored");
+                
+                // Try to restart the old server if it was running
+                if was_running {
+                    if let Err(restart_err) = start_llm_server(app, Some(config), state).await {
+                        warn!("Failed to restart old server: {}", restart_err);
+                    }
+                }
+            }
+            
+            Err(AppError::new(e.kind, format!("Update failed: {}. Previous version restored.", e)))
+        }
+    }
+}
+
+// Bumped whenever the managed server is started or stopped on purpose. A supervisor
+// only acts while the generation it was spawned for is still current.
+static SUPERVISOR_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+const SUPERVISOR_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(2);
+// A restarted server that stays up this long resets the restart attempt counter
+const SUPERVISOR_STABLE_UPTIME: std::time::Duration = std::time::Duration::from_secs(60);
+const STDERR_TAIL_LINES: usize = 50;
+
+const STARTUP_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(500);
+
+// Unix seconds of the last request sent to the managed server, for idle unloading
+static MANAGED_LAST_USED: AtomicU64 = AtomicU64::new(0);
+// Set when the idle timer stopped the server; the next request starts it again
+static MANAGED_IDLE_UNLOADED: AtomicBool = AtomicBool::new(false);
+// Held while an idle-unloaded server is started again, so concurrent requests reload it once
+static MANAGED_RELOAD: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+const IDLE_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(30);
+
+#[derive(Clone, Serialize)]
+struct LLMServerStarting {
+    elapsed_secs: u64,
+    timeout_secs: u64,
+}
+
+#[derive(Clone, Serialize)]
+struct LLMServerCrashed {
+    exit_code: Option<i32>,
+    stderr_tail: Vec<String>,
+    will_restart: bool,
+    attempt: u32,
+}
+
+// Validates the model, spawns the server binary and writes the PID file. Shared by
+// start_llm_server and the crash supervisor.
+fn spawn_llm_server(
+    app: &AppHandle,
+    config: &ManagedLLMConfig
+) -> Result<(Child, ServerProcessInfo), String> {
+    // Settle the port before spawning so the stored config carries the real one
+    let port = resolve_server_port(config)?;
+    let config = ManagedLLMConfig { port, ..config.clone() };
+
+    let app_data_dir = app.path_resolver()
+        .app_data_dir()
+        .ok_or("Could not get app data directory")?;
+    
+    let server_exe = managed_server_exe(&app_data_dir);
+
+    if !server_exe.exists() {
+        return Err("Server binary not found. Please download it first.".to_string());
+    }
+
+    // Validate local GGUF models up front so a truncated or wrong file fails with a
+    // clear message instead of an opaque loader error from the server process
+    let model_metadata = match &config.model_path {
+        Some(model_path) if model_path.to_lowercase().ends_with(".gguf") => {
+            let metadata = gguf::read_gguf_metadata(Path::new(model_path))?;
+            info!(
+                "Validated GGUF model: architecture={}, quantization={:?}, context_length={:?}, chat_format={:?}",
+                metadata.architecture, metadata.quantization, metadata.context_length, metadata.chat_format
+            );
+            if metadata.chat_template.is_none() && config.chat_format.is_none() {
+                warn!("Model has no chat template and no chat_format is set; replies may be poor");
+            }
+            Some(metadata)
+        }
+        _ => None,
+    };
+
+    let mut cmd = Command::new(&server_exe);
+    
+    // Add command-line arguments (preferred method)
+    cmd.arg("--host").arg(&config.host);
+    cmd.arg("--port").arg(config.port.to_string());
+    cmd.arg("--log-level").arg(&config.log_level);
+    
+    // Add model arguments if specified
+    if let Some(model) = &config.model {
+        cmd.arg("--model").arg(model);
+    }
+    if let Some(model_filename) = &config.model_filename {
+        cmd.arg("--filename").arg(model_filename);
+    }
+    if let Some(model_path) = &config.model_path {
+        cmd.arg("--model-path").arg(model_path);
+    }
+    
+    // Add multi-modal arguments if specified
+    if let Some(mmproj_repo_id) = &config.mmproj_repo_id {
+        cmd.arg("--mmproj-repo-id").arg(mmproj_repo_id);
+    }
+    if let Some(mmproj_filename) = &config.mmproj_filename {
+        cmd.arg("--mmproj-filename").arg(mmproj_filename);
+    }
+    if let Some(chat_format) = &config.chat_format {
+        cmd.arg("--chat-format").arg(chat_format);
+    }
+
+    // Configure process creation for proper cleanup on Windows
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::process::CommandExt;
+        // CREATE_NEW_PROCESS_GROUP (0x00000200) - allows us to kill the process tree
+        // CREATE_NO_WINDOW (0x08000000) - prevents console window from appearing
+        const CREATE_NEW_PROCESS_GROUP: u32 = 0x00000200;
+        const CREATE_NO_WINDOW: u32 = 0x08000000;
+        cmd.creation_flags(CREATE_NEW_PROCESS_GROUP | CREATE_NO_WINDOW);
+    }
+
+    // Start the server process
+    debug!("Starting server with command: {:?}", server_exe);
+    debug!("Command-line arguments: --host {} --port {} --log-level {}", 
+              config.host, config.port, config.log_level);
+    
+    cmd.stdout(Stdio::null()).stderr(Stdio::piped());
+
+    let mut child = cmd.spawn()
+        .map_err(|e| format!("Failed to start server: {}", e))?;
+
+    // Keep echoing stderr to the log, remembering the last lines for crash reports
+    let stderr_tail = Arc::new(Mutex::new(VecDeque::new()));
+    if let Some(stderr) = child.stderr.take() {
+        let tail = stderr_tail.clone();
+        thread::spawn(move || {
+            for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+                debug!("{}", line);
+                let mut tail = tail.lock().unwrap();
+                if tail.len() == STDERR_TAIL_LINES {
+                    tail.pop_front();
+                }
+                tail.push_back(line);
+            }
+        });
+    }
+
+    info!("Server process started with PID: {:?}", child.id());
+
+    let pid = child.id();
+    
+    // Write PID file for orphan detection
+    write_pid_file(&app_data_dir, pid, &config, &server_exe)?;
+    
+    let process_info = ServerProcessInfo {
+        pid,
+        config,
+        model_metadata,
+        warm_up_ms: None,
+        stderr_tail,
+    };
+
+    Ok((child, process_info))
+}
+
+fn stderr_tail_lines(info: &ServerProcessInfo) -> Vec<String> {
+    info.stderr_tail.lock().unwrap().iter().cloned().collect()
+}
+
+// Appends the server's last stderr lines to an error message
+fn with_stderr_tail(message: String, info: &ServerProcessInfo) -> String {
+    let tail = stderr_tail_lines(info);
+    if tail.is_empty() {
+        message
+    } else {
+        format!("{}\n{}", message, tail.join("\n"))
+    }
+}
+
+// Polls /v1/models on the server held in `state` until it answers 200, failing if the
+// process exits, is stopped, or the timeout passes first
+async fn wait_for_server_ready(
+    app: &AppHandle,
+    state: &ManagedLLMState,
+    timeout: std::time::Duration
+) -> Result<(), String> {
+    let started = std::time::Instant::now();
+    let client = network::build_http_client();
+
+    loop {
+        let (test_url, exited) = {
+            let mut state_guard = state.lock().unwrap();
+            let polled = match state_guard.as_mut() {
+                Some((Some(child), _)) => child.try_wait()
+                    .map_err(|e| format!("Error checking server status: {}", e))?,
+                Some((None, _)) => None,
+                None => return Err("Server was stopped while starting".to_string()),
+            };
+            match polled {
+                Some(status) => (String::new(), state_guard.take().map(|(_, info)| (status, info))),
+                None => {
+                    let config = &state_guard.as_ref().unwrap().1.config;
+                    (format!("http://{}:{}/v1/models", config.host, config.port), None)
+                }
+            }
+        };
+
+        if let Some((status, info)) = exited {
+            if let Some(app_data_dir) = app.path_resolver().app_data_dir() {
+                remove_pid_file(&app_data_dir);
+            }
+            // Give the stderr reader a moment to drain what the server printed last
+            tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+            let message = format!("Server process exited during startup with status: {}", status);
+            return Err(with_stderr_tail(message, &info));
+        }
+
+        if let Ok(response) = client.get(&test_url).timeout(std::time::Duration::from_secs(2)).send().await {
+            let status_code = response.status();
+            // Read the response body to properly close the connection
+            let _ = response.bytes().await;
+            if status_code.is_success() {
+                info!("Server ready after {:.1}s", started.elapsed().as_secs_f32());
+                return Ok(());
+            }
+        }
+
+        if started.elapsed() >= timeout {
+            let message = format!("Server did not become ready within {} seconds", timeout.as_secs());
+            return Err(match state.lock().unwrap().as_ref() {
+                Some((_, info)) => with_stderr_tail(message, info),
+                None => message,
+            });
+        }
+
+        let _ = app.emit_all("llm-server-starting", LLMServerStarting {
+            elapsed_secs: started.elapsed().as_secs(),
+            timeout_secs: timeout.as_secs(),
+        });
+        tokio::time::sleep(STARTUP_POLL_INTERVAL).await;
+    }
+}
+
+// Watches the server process started for `generation` and restarts it according to
+// its restart policy when it exits without stop_llm_server being called
+async fn supervise_llm_server(app: AppHandle, state: ManagedLLMState, generation: u64) {
+    let is_current = || SUPERVISOR_GENERATION.load(Ordering::SeqCst) == generation;
+    let mut attempts = 0u32;
+    let mut started_at = std::time::Instant::now();
+
+    loop {
+        tokio::time::sleep(SUPERVISOR_POLL_INTERVAL).await;
+
+        let exited = {
+            let mut state_guard = state.lock().unwrap();
+            if !is_current() {
+                return;
+            }
+            let polled = match state_guard.as_mut() {
+                Some((Some(child), _)) => child.try_wait(),
+                // Nothing left to supervise
+                _ => return,
+            };
+            match polled {
+                Ok(Some(status)) => state_guard.take().map(|(_, info)| (status, info)),
+                Ok(None) => None,
+                Err(e) => {
+                    warn!("Failed to poll managed LLM server process: {}", e);
+                    None
+                }
+            }
+        };
+
+        let (status, info) = match exited {
+            Some(exited) => exited,
+            None => {
+                if attempts > 0 && started_at.elapsed() >= SUPERVISOR_STABLE_UPTIME {
+                    attempts = 0;
+                }
+                continue;
+            }
+        };
+
+        error!("Managed LLM server (PID {}) exited unexpectedly with status: {}", info.pid, status);
+        if let Some(app_data_dir) = app.path_resolver().app_data_dir() {
+            remove_pid_file(&app_data_dir);
+        }
+
+        let stderr_tail = stderr_tail_lines(&info);
+        let config = info.config;
+        let wants_restart = match config.restart_policy.as_str() {
+            "always" => true,
+            "on-failure" => !status.success(),
+            _ => false,
+        };
+        let will_restart = wants_restart && attempts < config.max_restart_attempts;
+        let _ = app.emit_all("llm-server-crashed", LLMServerCrashed {
+            exit_code: status.code(),
+            stderr_tail,
+            will_restart,
+            attempt: attempts + 1,
+        });
+
+        if !will_restart {
+            if wants_restart {
+                error!("Giving up on the managed LLM server after {} restart attempts", attempts);
+            }
+            return;
+        }
+
+        // Exponential backoff; failed spawns count towards the attempt limit too
+        loop {
+            attempts += 1;
+            let delay = std::time::Duration::from_secs(1 << (attempts - 1).min(5));
+            info!(
+                "Restarting managed LLM server in {}s (attempt {}/{})",
+                delay.as_secs(), attempts, config.max_restart_attempts
+            );
+            tokio::time::sleep(delay).await;
+            if !is_current() {
+                return;
+            }
+
+            match spawn_llm_server(&app, &config) {
+                Ok((mut child, process_info)) => {
+                    let mut state_guard = state.lock().unwrap();
+                    if !is_current() || state_guard.is_some() {
+                        // Stopped or started manually while we were spawning
+                        let _ = child.kill();
+                        let _ = child.wait();
+                        if state_guard.is_none() {
+                            if let Some(app_data_dir) = app.path_resolver().app_data_dir() {
+                                remove_pid_file(&app_data_dir);
+                            }
+                        }
+                        return;
+                    }
+                    info!("Managed LLM server restarted with PID {}", process_info.pid);
+                    *state_guard = Some((Some(child), process_info));
+                    started_at = std::time::Instant::now();
+                    break;
+                }
+                Err(e) => {
+                    warn!("Failed to restart managed LLM server: {}", e);
+                    if attempts >= config.max_restart_attempts {
+                        error!("Giving up on the managed LLM server after {} restart attempts", attempts);
+                        return;
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn mark_managed_server_used() {
+    MANAGED_LAST_USED.store(scheduler::now_secs(), Ordering::SeqCst);
+}
+
+fn idle_unload_at(config: &ManagedLLMConfig) -> Option<u64> {
+    let minutes = config.idle_unload_minutes.filter(|minutes| *minutes > 0)?;
+    Some(MANAGED_LAST_USED.load(Ordering::SeqCst) + minutes * 60)
+}
+
+// A throwaway one-token completion; returns how long it took in milliseconds
+async fn warm_up_llm_server(config: &ManagedLLMConfig) -> Result<u64, BackendError> {
+    let backend = llm_backend::ManagedServerBackend {
+        host: config.host.clone(),
+        port: config.port,
+        model: config.model.clone(),
+        vision: false,
+        max_context: None,
+    };
+    let request = ClassifyRequest {
+        system_message: "Reply with one word.".to_string(),
+        prompt: "Ready?".to_string(),
+        image_base64: None,
+        mime_type: None,
+        max_tokens: Some(1),
+        source_path: None,
+    };
+    let started = std::time::Instant::now();
+    backend.classify(&request).await?;
+    Ok(started.elapsed().as_millis() as u64)
+}
+
+#[derive(Clone, Serialize)]
+struct ModelUnloaded {
+    idle_minutes: u64,
+}
+
+// Stops the server started for `generation` once it has gone idle_minutes without a
+// request. The next llm_classify starts it again.
+async fn unload_when_idle(app: AppHandle, generation: u64, idle_minutes: u64) {
+    loop {
+        tokio::time::sleep(IDLE_CHECK_INTERVAL).await;
+        if SUPERVISOR_GENERATION.load(Ordering::SeqCst) != generation {
+            return;
+        }
+        let idle_secs = scheduler::now_secs().saturating_sub(MANAGED_LAST_USED.load(Ordering::SeqCst));
+        if idle_secs < idle_minutes * 60 {
+            continue;
+        }
+        // A reload in progress means a request is about to use the server
+        let Ok(_reload) = MANAGED_RELOAD.try_lock() else { continue };
+        info!("Unloading the managed LLM server after {} idle minutes", idle_minutes);
+        let state = app.state::<ManagedLLMState>();
+        if let Err(e) = stop_llm_server(app.clone(), state).await {
+            warn!("Failed to unload the idle managed LLM server: {}", e);
+            return;
+        }
+        MANAGED_IDLE_UNLOADED.store(true, Ordering::SeqCst);
+        let _ = app.emit_all("model-unloaded", ModelUnloaded { idle_minutes });
+        return;
+    }
+}
+
+// Starts the managed server again if the idle timer stopped it, announcing the pause
+// with a model-reloading event
+async fn reload_idle_llm_server(app: &AppHandle) {
+    let _reload = MANAGED_RELOAD.lock().await;
+    if !MANAGED_IDLE_UNLOADED.load(Ordering::SeqCst) {
+        return;
+    }
+    info!("Reloading the managed LLM server after it was unloaded while idle");
+    let _ = app.emit_all("model-reloading", ());
+    let state = app.state::<ManagedLLMState>();
+    if let Err(e) = start_llm_server(app.clone(), None, state).await {
+        warn!("Failed to reload the managed LLM server: {}", e);
+    }
+}
+
+#[command]
+async fn start_llm_server(
+    app: AppHandle,
+    config: Option<ManagedLLMConfig>,
+    state: State<'_, ManagedLLMState>
+) -> Result<String, AppError> {
+    // A new config is saved for later starts; without one, the saved config is used
+    let config = match config {
+        Some(config) => {
+            let saved = config.clone();
+            if let Err(e) = settings::update(|settings| {
+                settings.managed_llm = Some(saved);
+                Ok(())
+            }) {
+                warn!("Failed to save the managed LLM configuration: {}", e);
+            }
+            config
+        }
+        None => settings::with(|settings| settings.managed_llm.clone())
+            .ok_or_else(|| AppError::invalid_input("No managed LLM server configuration has been saved"))?,
+    };
+    debug!("Received config for starting server: {:?}", config);
+    
+    // Stop any existing server first
+    let _ = stop_llm_server(app.clone(), state.clone()).await;
+
+    let (child, process_info) = spawn_llm_server(&app, &config).map_err(AppError::llm_backend)?;
+    let pid = process_info.pid;
+    let port = process_info.config.port;
+
+    let generation = {
+        let mut state_guard = state.lock().unwrap();
+        *state_guard = Some((Some(child), process_info));
+        debug!("Stored server process with PID {} in state", pid);
+        SUPERVISOR_GENERATION.fetch_add(1, Ordering::SeqCst) + 1
+    };
+
+    info!("Server process stored, waiting for it to answer on port {}...", port);
+
+    let startup_timeout = std::time::Duration::from_secs(config.startup_timeout);
+    if let Err(e) = wait_for_server_ready(&app, state.inner(), startup_timeout).await {
+        error!("Server startup failed: {}", e);
+        // Don't leave a half-started server holding the port
+        let _ = stop_llm_server(app.clone(), state.clone()).await;
+        return Err(AppError::llm_backend(e));
+    }
+
+    if config.warm_up {
+        let started_config = state.lock().unwrap().as_ref().map(|(_, info)| info.config.clone());
+        if let Some(started_config) = started_config {
+            match warm_up_llm_server(&started_config).await {
+                Ok(warm_up_ms) => {
+                    info!("Managed LLM server warmed up in {} ms", warm_up_ms);
+                    if let Some((_, info)) = state.lock().unwrap().as_mut() {
+                        info.warm_up_ms = Some(warm_up_ms);
+                    }
+                }
+                Err(e) => warn!("Managed LLM server warm-up failed: {}", e),
+            }
+        }
+    }
+    mark_managed_server_used();
+
+    // Only supervise once the server has come up; startup failures are reported directly
+    if config.restart_policy != "never" {
+        tauri::async_runtime::spawn(supervise_llm_server(app.clone(), state.inner().clone(), generation));
+    }
+    if let Some(idle_minutes) = config.idle_unload_minutes.filter(|minutes| *minutes > 0) {
+        tauri::async_runtime::spawn(unload_when_idle(app.clone(), generation, idle_minutes));
+    }
+
+    Ok(format!("Server started on {}:{}", config.host, port))
+}
+
+#[command]
+async fn stop_llm_server(app: AppHandle, state: State<'_, ManagedLLMState>) -> Result<String, AppError> {
+    info!("Attempting to stop LLM server...");
+
+    // Disarm the supervisor so an intentional stop is not undone by a restart
+    SUPERVISOR_GENERATION.fetch_add(1, Ordering::SeqCst);
+    // Likewise a server stopped on purpose isn't started again by the next request
+    MANAGED_IDLE_UNLOADED.store(false, Ordering::SeqCst);
+    
+    let app_data_dir = app.path_resolver()
+        .app_data_dir()
+        .ok_or_else(|| AppError::io("Could not get app data directory"))?;
+    
+    let mut state_guard = state.lock().unwrap();
+    debug!("Got lock on state");
+    
+    let has_process = state_guard.is_some();
+    debug!("State has process: {}", has_process);
+    
+    if let Some((child_opt, process_info)) = state_guard.take() {
+        let pid = process_info.pid;
+        debug!("Found server process with PID: {}", pid);
+        
+        // On Windows, use taskkill to forcefully terminate the process tree first
+        #[cfg(target_os = "windows")]
+        {
+            debug!("Using taskkill to terminate process tree for PID: {}", pid);
+            let output = std::process::Command::new("taskkill")
+                .args(&["/F", "/T", "/PID", &pid.to_string()])
+                .output();
+            
+            match output {
+                Ok(output) => {
+                    if output.status.success() {
+                        info!("Successfully killed process tree with taskkill");
+                    } else {
+                        warn!("Taskkill failed: {}", String::from_utf8_lossy(&output.stderr));
+                    }
+                }
+                Err(e) => {
+                    warn!("Failed to execute taskkill: {}", e);
+                }
+            }
+            
+            // Give the process a moment to terminate
+            std::thread::sleep(std::time::Duration::from_millis(500));
+        }
+        
+        // Also try to kill via Child handle (if we have it)
+        if let Some(mut child) = child_opt {
+            debug!("Also killing via Child handle");
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+        
+        // On Unix systems, kill by PID
+        #[cfg(unix)]
+        {
+            let _ = kill_process_by_pid(pid);
+        }
+        
+        // Verify the process is actually dead
+        if is_process_running(pid) {
+            warn!("Process {} may still be running after kill attempt", pid);
+        } else {
+            info!("Confirmed: Process {} has terminated", pid);
+        }
+        
+        // Clean up PID file
+        remove_pid_file(&app_data_dir);
         
-        eprintln!("Backup created at: {}", backup_path.to_string_lossy());
+        Ok("Server stopped".to_string())
     } else {
-        eprintln!("No existing server found, performing fresh installation");
+        info!("No server process found in state");
+        Ok("Server was not running".to_string())
     }
-    
-    // Step 3: Download and extract new version
-    eprintln!("Downloading new server version...");
-    let download_result = download_llm_server(app.clone(), version.clone()).await;
-    
-    match download_result {
-        Ok(_) => {
-            eprintln!("Download successful, verifying installation...");
-            
-            // Step 4: Try to start the server with new version
-            if was_running {
-                eprintln!("Attempting to start updated server...");
-                let start_result = start_llm_server(app.clone(), config.clone(), state.clone()).await;
-                
-                match start_result {
-                    Ok(_) => {
-                        // Step 5a: Success - remove backup
-                        eprintln!("Server started successfully, removing backup...");
-                        if backup_path.exists() {
-                            if let Err(e) = fs::remove_dir_all(&backup_path) {
-                                eprintln!("Warning: Failed to remove backup: {}", e);
-                                // Not a critical error, update was successful
-                            }
-                        }
-                        Ok(format!("Successfully updated to version {}", version))
-                    }
-                    Err(e) => {
-                        // Step 5b: Failed to start - restore backup
-                        eprintln!("Failed to start new server: {}, restoring backup...", e);
-                        
-                        // Remove the failed new installation
-                        if server_path.exists() {
-                            if let Err(remove_err) = fs::remove_dir_all(&server_path) {
-                                eprintln!("Warning: Failed to remove failed installation: {}", remove_err);
-                            }
-                        }
-                        
-                        // Restore from backup
-                        if backup_path.exists() {
-                            fs::rename(&backup_path, &server_path)
-                                .map_err(|e| format!("Failed to restore backup: {}", e))?;
-                            
-                            eprintln!("Backup restored, attempting to start old server...");
-                            // Try to restart the old server
-                            if let Err(restart_err) = start_llm_server(app, config, state).await {
-                                eprintln!("Warning: Failed to restart old server: {}", restart_err);
-                            }
-                        }
-                        
-                        Err(format!("Update failed: {}. Restored previous version.", e))
-                    }
-                }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManagedServerModel {
+    pub id: String,
+    pub owned_by: Option<String>,
+    pub created: Option<f64>,
+}
+
+#[derive(Deserialize)]
+struct ManagedServerModelList {
+    data: Vec<ManagedServerModel>,
+}
+
+// Lists the models loaded by the running managed server via its OpenAI-compatible
+// /v1/models endpoint
+#[command]
+async fn list_managed_server_models(state: State<'_, ManagedLLMState>) -> Result<Vec<ManagedServerModel>, AppError> {
+    let (host, port) = {
+        let state_guard = state.lock().unwrap();
+        match state_guard.as_ref() {
+            Some((_, process_info)) => (process_info.config.host.clone(), process_info.config.port),
+            None => return Err(AppError::llm_backend("Managed LLM server is not running")),
+        }
+    };
+
+    let url = format!("http://{}:{}/v1/models", host, port);
+    let response = network::build_http_client()
+        .get(&url)
+        .timeout(std::time::Duration::from_secs(10))
+        .send()
+        .await
+        .map_err(|e| {
+            if e.is_connect() {
+                AppError::llm_backend(format!("Managed LLM server is not running on {}:{}", host, port)).with_source(&e)
             } else {
-                // Server wasn't running, just remove backup
-                eprintln!("Update completed (server was not running)");
-                if backup_path.exists() {
-                    if let Err(e) = fs::remove_dir_all(&backup_path) {
-                        eprintln!("Warning: Failed to remove backup: {}", e);
-                    }
+                AppError::from_request(&e, format!("Failed to list models: {}", e))
+            }
+        })?;
+
+    if !response.status().is_success() {
+        return Err(AppError::llm_backend(format!("Failed to list models: server responded with status {}", response.status())));
+    }
+
+    let models: ManagedServerModelList = response.json().await
+        .map_err(|e| AppError::llm_backend(format!("Failed to parse model list: {}", e)))?;
+    Ok(models.data)
+}
+
+// Builds the selected backend, reading the managed server's address and model details
+// from its state at call time so restarts and port changes are picked up
+fn resolve_llm_backend(config: &BackendConfig, managed_state: &ManagedLLMState) -> Result<Backend, String> {
+    match config {
+        BackendConfig::Managed { model } => {
+            let state_guard = managed_state.lock().unwrap();
+            let (_, process_info) = state_guard.as_ref()
+                .ok_or("Managed LLM server is not running")?;
+            Ok(Backend::Managed(llm_backend::ManagedServerBackend {
+                host: process_info.config.host.clone(),
+                port: process_info.config.port,
+                model: model.clone().or_else(|| process_info.config.model.clone()),
+                // Images need a multi-modal projector, which is set up via chat_format
+                vision: process_info.config.chat_format.is_some(),
+                max_context: process_info.model_metadata.as_ref().and_then(|m| m.context_length),
+            }))
+        }
+        BackendConfig::Remote { provider, base_url, api_key, model, headers, supports_vision, max_context } => {
+            Ok(Backend::Remote(llm_backend::RemoteBackend {
+                provider: provider.clone(),
+                base_url: base_url.clone(),
+                api_key: api_key.clone(),
+                model: model.clone(),
+                headers: headers.clone(),
+                supports_vision: *supports_vision,
+                max_context: *max_context,
+            }))
+        }
+    }
+}
+
+fn selected_llm_backend(backend_state: &LlmBackendState) -> Result<BackendConfig, String> {
+    backend_state.lock().unwrap().clone()
+        .ok_or_else(|| "No LLM backend selected".to_string())
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct ActiveBackendInfo {
+    kind: String,
+    name: Option<String>,
+    capabilities: Option<BackendCapabilities>,
+    healthy: bool,
+    error: Option<String>,
+}
+
+fn check_llm_backend(config: &BackendConfig) -> Result<(), AppError> {
+    if local_only::enabled() && !local_only::allows_backend(config) {
+        return Err(local_only::violation("Local-only mode only allows the built-in LLM server"));
+    }
+    if let BackendConfig::Remote { base_url, .. } = config {
+        if base_url.trim().is_empty() {
+            return Err(AppError::invalid_input("Remote backend needs a base URL"));
+        }
+    }
+    Ok(())
+}
+
+#[command]
+fn select_llm_backend(config: BackendConfig, backend_state: State<'_, LlmBackendState>) -> Result<(), AppError> {
+    check_llm_backend(&config)?;
+    info!("Selected LLM backend: {}", config.kind());
+    *backend_state.lock().unwrap() = Some(config);
+    Ok(())
+}
+
+#[command]
+async fn get_active_backend(
+    backend_state: State<'_, LlmBackendState>,
+    managed_state: State<'_, ManagedLLMState>
+) -> Result<Option<ActiveBackendInfo>, AppError> {
+    let config = match backend_state.lock().unwrap().clone() {
+        Some(config) => config,
+        None => return Ok(None),
+    };
+
+    let info = match resolve_llm_backend(&config, managed_state.inner()) {
+        Ok(backend) => {
+            let health = backend.health().await;
+            ActiveBackendInfo {
+                kind: config.kind().to_string(),
+                name: Some(backend.name()),
+                capabilities: Some(backend.capabilities()),
+                healthy: health.is_ok(),
+                error: health.err(),
+            }
+        }
+        Err(e) => ActiveBackendInfo {
+            kind: config.kind().to_string(),
+            name: None,
+            capabilities: None,
+            healthy: false,
+            error: Some(e),
+        },
+    };
+    Ok(Some(info))
+}
+
+#[derive(Clone, Serialize)]
+struct LlmBackendFallback {
+    served_by: String,
+    // One "backend: reason" entry per backend that was skipped or failed
+    failures: Vec<String>,
+}
+
+// Classifies through the selected backend, falling back along the configured chain
+// when a backend is unreachable or failing. Requests a backend rejects are not retried.
+#[command]
+async fn llm_classify(
+    app: AppHandle,
+    request: ClassifyRequest,
+    backend_state: State<'_, LlmBackendState>,
+    fallback_state: State<'_, LlmFallbackState>,
+    managed_state: State<'_, ManagedLLMState>,
+    metrics: State<'_, LlmMetricsState>,
+) -> Result<ClassifyResponse, AppError> {
+    let primary = selected_llm_backend(backend_state.inner()).map_err(AppError::invalid_input)?;
+    classify_with(app, request, primary, fallback_state, managed_state, metrics).await
+}
+
+// llm_classify with `primary` in place of the selected backend
+async fn classify_with(
+    app: AppHandle,
+    request: ClassifyRequest,
+    primary: BackendConfig,
+    fallback_state: State<'_, LlmFallbackState>,
+    managed_state: State<'_, ManagedLLMState>,
+    metrics: State<'_, LlmMetricsState>,
+) -> Result<ClassifyResponse, AppError> {
+    let received = std::time::Instant::now();
+    let _in_flight = shutdown::SHUTDOWN.enter().ok_or_else(|| AppError::cancelled("The app is shutting down"))?;
+    let settings = fallback_state.lock().unwrap().settings.clone();
+    let timeout = std::time::Duration::from_secs(settings.timeout_secs);
+
+    let mut candidates = vec![primary];
+    candidates.extend(settings.chain.iter().cloned());
+    if candidates.iter().any(|config| matches!(config, BackendConfig::Managed { .. })) {
+        reload_idle_llm_server(&app).await;
+    }
+
+    let redaction_settings = settings::with(|s| s.redaction.clone());
+    let local_only = local_only::enabled();
+    let mut blocked = false;
+    let mut failures = Vec::new();
+    for (index, config) in candidates.iter().enumerate() {
+        if local_only && !local_only::allows_backend(config) {
+            blocked = true;
+            let backend =
+                resolve_llm_backend(config, managed_state.inner()).map_or(config.kind().to_string(), |b| b.name());
+            failures.push(format!("{}: blocked by local-only mode", backend));
+            audit(&app, || {
+                let path = request.source_path.clone().unwrap_or_default();
+                audit::AuditEvent::new(audit::AuditAction::Blocked, path, 0).with_backend(backend)
+            });
+            continue;
+        }
+        let backend = match resolve_llm_backend(config, managed_state.inner()) {
+            Ok(backend) => backend,
+            Err(e) => {
+                failures.push(format!("{}: {}", config.kind(), e));
+                continue;
+            }
+        };
+        let name = backend.name();
+
+        let skipped = fallback_state.lock().unwrap().breakers
+            .get(&name)
+            .is_some_and(|breaker| breaker.is_open(std::time::Instant::now()));
+        if skipped {
+            failures.push(format!("{}: skipped after repeated failures", name));
+            continue;
+        }
+
+        // Redacted per backend, since the chain can fall back from a local backend to a remote one
+        let redaction = redaction::applies(config, redaction_settings.enabled, redaction_settings.redact_always)
+            .then(|| redaction::redact_sensitive_content(&request.prompt, &redaction_settings.rules))
+            .transpose()?;
+        let outgoing = match &redaction {
+            Some(redacted) => {
+                std::borrow::Cow::Owned(ClassifyRequest { prompt: redacted.text.clone(), ..request.clone() })
+            }
+            None => std::borrow::Cow::Borrowed(&request),
+        };
+
+        let wait_ms = received.elapsed().as_millis() as u64;
+        audit(&app, || {
+            let bytes = outgoing.system_message.len()
+                + outgoing.prompt.len()
+                + outgoing.image_base64.as_ref().map_or(0, String::len);
+            let path = outgoing.source_path.clone().unwrap_or_default();
+            audit::AuditEvent::new(audit::AuditAction::LlmSubmission, path, bytes as u64).with_backend(name.clone())
+        });
+        let sent = std::time::Instant::now();
+        let result = match tokio::time::timeout(timeout, backend.classify(&outgoing)).await {
+            Ok(result) => result,
+            Err(_) => Err(BackendError::Unavailable(format!("Timed out after {} seconds", timeout.as_secs()))),
+        };
+        if matches!(backend, Backend::Managed(_)) {
+            mark_managed_server_used();
+        }
+        let usage = result.as_ref().map(|response| response.usage.clone()).unwrap_or_default();
+        let record = llm_metrics::RequestRecord::new(
+            scheduler::now_secs(),
+            name.clone(),
+            wait_ms,
+            sent.elapsed().as_millis() as u64,
+            usage,
+        );
+        let record = match &result {
+            Ok(_) => record,
+            Err(e) => record.failed(e.kind()),
+        };
+        metrics.lock().unwrap().record(record);
+
+        match result {
+            Ok(mut response) => {
+                response.redactions = redaction.map(|redacted| redacted.counts).unwrap_or_default();
+                fallback_state.lock().unwrap().breakers.entry(name.clone()).or_default().record_success();
+                if index > 0 {
+                    info!("LLM request served by fallback backend {}", name);
+                    response.fell_back = true;
+                    let _ = app.emit_all("llm-backend-fallback", LlmBackendFallback {
+                        served_by: name,
+                        failures,
+                    });
                 }
-                Ok(format!("Successfully updated to version {}", version))
+                return Ok(response);
+            }
+            Err(e) if e.is_retryable() => {
+                warn!("LLM backend {} unavailable: {}", name, e);
+                {
+                    let mut fallback = fallback_state.lock().unwrap();
+                    let FallbackState { settings, breakers } = &mut *fallback;
+                    breakers.entry(name.clone()).or_default()
+                        .record_failure(std::time::Instant::now(), settings);
+                }
+                failures.push(format!("{}: {}", name, e));
             }
+            Err(e) => return Err(e.into()),
         }
-        Err(e) => {
-            // Step 5c: Download failed - restore backup
-            eprintln!("Download failed: {}, restoring backup...", e);
-            
-            if backup_path.exists() {
-                // Remove any partial download
-                if server_path.exists() {
-                    if let Err(remove_err) = fs::remove_dir_all(&server_path) {
-                        eprintln!("Warning: Failed to remove partial download: {}", remove_err);
+    }
+
+    let message = format!("All LLM backends failed:\n{}", failures.join("\n"));
+    if blocked {
+        return Err(local_only::violation(message));
+    }
+    Err(AppError::llm_backend(message))
+}
+
+// Aggregates since startup or the last reset, plus up to `last` individual requests
+// (20 by default), newest first
+#[command]
+fn get_llm_metrics(
+    last: Option<usize>,
+    metrics: State<'_, LlmMetricsState>,
+) -> Result<llm_metrics::LlmMetricsSummary, AppError> {
+    Ok(metrics.lock().unwrap().summary(last.unwrap_or(20).min(llm_metrics::RECENT_RECORDS)))
+}
+
+#[command]
+fn reset_llm_metrics(metrics: State<'_, LlmMetricsState>) -> Result<(), AppError> {
+    *metrics.lock().unwrap() = llm_metrics::LlmMetrics::new(scheduler::now_secs());
+    Ok(())
+}
+
+#[command]
+fn get_llm_fallback_settings(fallback_state: State<'_, LlmFallbackState>) -> Result<FallbackSettings, AppError> {
+    Ok(fallback_state.lock().unwrap().settings.clone())
+}
+
+#[command]
+fn set_llm_fallback_settings(
+    settings: FallbackSettings,
+    fallback_state: State<'_, LlmFallbackState>
+) -> Result<(), AppError> {
+    if settings.timeout_secs == 0 {
+        return Err(AppError::invalid_input("Backend timeout must be at least 1 second"));
+    }
+    if settings.failure_threshold == 0 {
+        return Err(AppError::invalid_input("Failure threshold must be at least 1"));
+    }
+    let mut fallback = fallback_state.lock().unwrap();
+    fallback.settings = settings;
+    // Start the new chain with a clean slate
+    fallback.breakers.clear();
+    Ok(())
+}
+
+// Opened on first use, since the app data directory isn't known until the app is running
+type ClassificationCacheState = Arc<Mutex<Option<ClassificationCache>>>;
+
+fn with_classification_cache<T>(
+    app: &AppHandle,
+    state: &ClassificationCacheState,
+    f: impl FnOnce(&ClassificationCache) -> Result<T, String>,
+) -> Result<T, AppError> {
+    let mut guard = state.lock().unwrap();
+    if guard.is_none() {
+        let app_data_dir = app.path_resolver().app_data_dir()
+            .ok_or_else(|| AppError::not_found("Failed to get app data directory"))?;
+        *guard = Some(ClassificationCache::open(&app_data_dir).map_err(AppError::io)?);
+    }
+    f(guard.as_ref().unwrap()).map_err(AppError::io)
+}
+
+// Records a successful classification. content_hash is the file's SHA-256 (hex) and
+// is computed here when the caller doesn't have it.
+#[command]
+async fn cache_classification(
+    app: AppHandle,
+    cache: State<'_, ClassificationCacheState>,
+    path: String,
+    content_hash: Option<String>,
+    category: String,
+    destination: String,
+    model_id: String,
+) -> Result<(), AppError> {
+    let real_path = paths::resolve_path(&path);
+    let quick_hash = QuickHash::of(&real_path)
+        .map_err(|e| AppError::from_io(&e, format!("Failed to read file metadata: {}", e)).with_path(&path))?;
+    let content_hash = match content_hash.filter(|hash| !hash.trim().is_empty()) {
+        Some(hash) => hash,
+        None => {
+            let hashed_path = real_path.clone();
+            tokio::task::spawn_blocking(move || sha256_file(&hashed_path))
+                .await
+                .map_err(|e| AppError::io(format!("Failed to hash file: {}", e)))?
+                .map_err(|e| AppError::io(e).with_path(&path))?
+        }
+    };
+    let classification = CachedClassification {
+        path,
+        content_hash,
+        category,
+        destination,
+        model_id,
+        cached_at: 0,
+    };
+    with_classification_cache(&app, &cache, |cache| cache.put(&classification, quick_hash))
+}
+
+// Returns the cached classification when the file is unchanged and was classified by
+// model_id. Size and mtime are checked first; only when they differ is the file hashed.
+#[command]
+async fn get_cached_classification(
+    app: AppHandle,
+    cache: State<'_, ClassificationCacheState>,
+    path: String,
+    model_id: String,
+) -> Result<Option<CachedClassification>, AppError> {
+    let cache = cache.inner().clone();
+    let entry = with_classification_cache(&app, &cache, |cache| cache.get(&path))?
+        .filter(|entry| entry.classification.model_id == model_id);
+
+    let hit = match entry {
+        None => None,
+        Some(entry) => {
+            let real_path = paths::resolve_path(&path);
+            match QuickHash::of(&real_path) {
+                Ok(quick_hash) if quick_hash == entry.quick_hash => Some((entry.classification, quick_hash)),
+                Ok(quick_hash) => {
+                    let hashed_path = real_path.clone();
+                    let content_hash = tokio::task::spawn_blocking(move || sha256_file(&hashed_path))
+                        .await
+                        .ok()
+                        .and_then(Result::ok);
+                    if content_hash.as_deref() == Some(entry.classification.content_hash.as_str()) {
+                        Some((entry.classification, quick_hash))
+                    } else {
+                        with_classification_cache(&app, &cache, |cache| cache.remove(&path))?;
+                        None
                     }
                 }
-                
-                // Restore backup
-                fs::rename(&backup_path, &server_path)
-                    .map_err(|e| format!("Failed to restore backup: {}", e))?;
-                
-                eprintln!("Backup restored");
-                
-                // Try to restart the old server if it was running
-                if was_running {
-                    if let Err(restart_err) = start_llm_server(app, config, state).await {
-                        eprintln!("Warning: Failed to restart old server: {}", restart_err);
+                Err(e) => {
+                    if e.kind() == std::io::ErrorKind::NotFound {
+                        with_classification_cache(&app, &cache, |cache| cache.remove(&path))?;
                     }
+                    None
                 }
             }
-            
-            Err(format!("Update failed: {}. Previous version restored.", e))
         }
+    };
+
+    with_classification_cache(&app, &cache, |cache| {
+        cache.record_lookup(hit.is_some())?;
+        if let Some((classification, quick_hash)) = &hit {
+            cache.touch(&classification.path, *quick_hash)?;
+        }
+        Ok(())
+    })?;
+    Ok(hit.map(|(classification, _)| classification))
+}
+
+#[command]
+async fn get_classification_cache_stats(
+    app: AppHandle,
+    cache: State<'_, ClassificationCacheState>,
+) -> Result<CacheStats, AppError> {
+    with_classification_cache(&app, &cache, |cache| cache.stats())
+}
+
+#[command]
+async fn clear_classification_cache(
+    app: AppHandle,
+    cache: State<'_, ClassificationCacheState>,
+) -> Result<(), AppError> {
+    with_classification_cache(&app, &cache, |cache| cache.clear())?;
+    info!("Classification cache cleared");
+    Ok(())
+}
+
+// Opened on first use, like the classification cache
+type ContentIndexState = Arc<Mutex<Option<content_index::ContentIndex>>>;
+
+fn with_content_index<T>(
+    app: &AppHandle,
+    state: &ContentIndexState,
+    f: impl FnOnce(&mut content_index::ContentIndex) -> Result<T, String>,
+) -> Result<T, AppError> {
+    let mut guard = state.lock().unwrap();
+    if guard.is_none() {
+        let app_data_dir = app.path_resolver().app_data_dir()
+            .ok_or_else(|| AppError::not_found("Failed to get app data directory"))?;
+        *guard = Some(content_index::ContentIndex::open(&app_data_dir).map_err(AppError::io)?);
+    }
+    f(guard.as_mut().unwrap()).map_err(AppError::io)
+}
+
+// Extracts the text of every file under `path` into the content index. Files with the
+// same size and mtime as last time are skipped, and files that disappeared are dropped,
+// so re-indexing a folder only pays for what changed.
+#[command]
+async fn index_directory(
+    app: AppHandle,
+    index: State<'_, ContentIndexState>,
+    limiter: State<'_, ExtractionLimiterState>,
+    operations: State<'_, OperationsState>,
+    path: String,
+    options: Option<content_index::IndexOptions>,
+    operation_id: Option<String>,
+) -> Result<content_index::IndexReport, AppError> {
+    let options = options.unwrap_or_default();
+    let root_path = paths::normalize_path(&path);
+    if !root_path.is_dir() {
+        return Err(AppError::invalid_input(format!("{} is not a directory", path)).with_path(&path));
+    }
+    let root = paths::display_path(&root_path);
+    let filter = ScanFilter::new(options.include_hidden, None);
+    let (listed_root, include_subdirectories) = (root.clone(), options.include_subdirectories);
+    let entries = tokio::task::spawn_blocking(move || list_files(&listed_root, include_subdirectories, false, &filter))
+        .await
+        .map_err(|e| AppError::io(format!("Failed to list files: {}", e)))??;
+
+    let index = index.inner().clone();
+    let known = with_content_index(&app, &index, |index| index.documents(&root))?;
+    let seen: std::collections::HashSet<&str> = entries.iter().map(|entry| entry.path.as_str()).collect();
+    let mut stale: Vec<String> = known.keys().filter(|path| !seen.contains(path.as_str())).cloned().collect();
+    let mut report = content_index::IndexReport {
+        root: root.clone(),
+        files_seen: entries.len(),
+        removed: stale.len(),
+        ..Default::default()
+    };
+
+    let token = operation_id.as_deref().map(|id| operations.token(id));
+    let hydrate = cloud_files::hydrate_by_default();
+    for (position, entry) in entries.iter().enumerate() {
+        if let (Some(token), Some(id)) = (&token, &operation_id) {
+            if token.is_cancelled() {
+                report.cancelled = true;
+                operation_cancelled(&app, token, id, position, Some(entries.len() - position));
+                break;
+            }
+        }
+        let fs_path = paths::resolve_path(&entry.path);
+        let Ok(quick_hash) = QuickHash::of(&fs_path) else {
+            report.failed += 1;
+            continue;
+        };
+        if known.get(&entry.path) == Some(&quick_hash) {
+            report.unchanged += 1;
+            continue;
+        }
+        // A changed file that can't be indexed any more mustn't keep its old text
+        if known.contains_key(&entry.path) {
+            stale.push(entry.path.clone());
+        }
+        if (entry.is_cloud_placeholder && !hydrate)
+            || quick_hash.size as u64 > options.max_file_bytes
+            || !content_index::indexable(&fs_path)
+        {
+            report.skipped += 1;
+            continue;
+        }
+        if options.io_priority == io_priority::IoPriority::Low {
+            let size = quick_hash.size as u64;
+            let _ = tokio::task::spawn_blocking(move || io_priority::consume(size)).await;
+        }
+        let permit = acquire_extraction_permit(&app, &limiter, &entry.path).await;
+        // Search needs all of the text, not an outline
+        let content = load_file_content_with_timeout(entry.path.clone(), Default::default(), None, false).await;
+        drop(permit);
+        match content {
+            Ok(FileContent { text: Some(text), .. }) => {
+                let text = content_index::normalize_text(&text);
+                with_content_index(&app, &index, |index| index.put(&root, &entry.path, quick_hash, &text))?;
+                stale.retain(|path| path != &entry.path);
+                report.indexed += 1;
+            }
+            // Images, binary files and formats without an extractor
+            Ok(_) => report.skipped += 1,
+            Err(e) if matches!(e.kind, error::AppErrorKind::InvalidData | error::AppErrorKind::Unsupported) => {
+                report.skipped += 1
+            }
+            Err(e) => {
+                debug!("Not indexing {}: {}", entry.path, e.message);
+                report.failed += 1;
+            }
+        }
+        if let Some(token) = &token {
+            token.record_completed();
+        }
+        if options.io_priority == io_priority::IoPriority::Low {
+            tokio::task::yield_now().await;
+        }
+    }
+    with_content_index(&app, &index, |index| index.remove(&stale))?;
+    if let Some(id) = &operation_id {
+        operations.finish(id);
+    }
+    info!(
+        "Indexed {}: {} indexed, {} unchanged, {} removed, {} skipped, {} failed",
+        root, report.indexed, report.unchanged, report.removed, report.skipped, report.failed
+    );
+    Ok(report)
+}
+
+// Searches indexed text for documents containing every word of `query`; a trailing '*'
+// matches word prefixes. path_filter limits results to paths starting with it.
+#[command]
+async fn search_indexed_content(
+    app: AppHandle,
+    index: State<'_, ContentIndexState>,
+    query: String,
+    path_filter: Option<String>,
+    limit: Option<usize>,
+) -> Result<Vec<content_index::SearchMatch>, AppError> {
+    let path_filter = path_filter.map(|filter| paths::display_path(paths::normalize_path(&filter)));
+    let limit = limit.unwrap_or(content_index::DEFAULT_SEARCH_LIMIT);
+    let index = index.inner().clone();
+    tokio::task::spawn_blocking(move || {
+        with_content_index(&app, &index, |index| index.search(&query, path_filter.as_deref(), limit))
+    })
+    .await
+    .map_err(|e| AppError::io(format!("Search failed: {}", e)))?
+}
+
+// Drops everything indexed under `path` by index_directory; returns how many documents
+#[command]
+async fn delete_index(app: AppHandle, index: State<'_, ContentIndexState>, path: String) -> Result<usize, AppError> {
+    let root = paths::display_path(paths::normalize_path(&path));
+    let removed = with_content_index(&app, &index, |index| index.delete_root(&root))?;
+    info!("Removed {} documents indexed under {}", removed, root);
+    Ok(removed)
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct PreparedContent {
+    text: String,
+    mime_type: Option<String>,
+    // Placeholders put into the text per type, when the backend it's for gets redacted text
+    #[serde(skip_serializing_if = "std::collections::BTreeMap::is_empty")]
+    redactions: std::collections::BTreeMap<String, usize>,
+    original_tokens: usize,
+    kept_tokens: usize,
+    // Strategy that was requested; applied_strategy is None when the text already fit
+    strategy: ReductionStrategy,
+    applied_strategy: Option<ReductionStrategy>,
+    // Always "heuristic" (content_budget::estimate_tokens): neither bundled server
+    // exposes its model's tokenizer
+    token_counter: String,
+}
+
+// Redacted copy of `text` when the settings call for it with this backend; with no
+// backend known only redact_always applies. `rules` replaces the saved rules.
+fn redact_for(
+    backend: Option<&BackendConfig>,
+    text: String,
+    rules: Option<RedactionRules>,
+) -> Result<Redacted, AppError> {
+    let redaction = settings::with(|s| s.redaction.clone());
+    let applies = match backend {
+        Some(backend) => redaction::applies(backend, redaction.enabled, redaction.redact_always),
+        None => redaction.redact_always,
+    };
+    if !applies {
+        return Ok(Redacted { text, counts: Default::default() });
+    }
+    redaction::redact_sensitive_content(&text, rules.as_ref().unwrap_or(&redaction.rules))
+}
+
+// Replaces PII in `text` with typed placeholders ([EMAIL_1]) using `rules`, or the saved
+// rules. Given a backend, text is only redacted when the settings call for it there.
+#[command]
+async fn redact_sensitive_content(
+    text: String,
+    rules: Option<RedactionRules>,
+    backend: Option<BackendConfig>,
+) -> Result<Redacted, AppError> {
+    tokio::task::spawn_blocking(move || match backend {
+        Some(backend) => redact_for(Some(&backend), text, rules),
+        None => {
+            let rules = rules.unwrap_or_else(|| settings::with(|s| s.redaction.rules.clone()));
+            redaction::redact_sensitive_content(&text, &rules)
+        }
+    })
+    .await
+    .map_err(|e| AppError::io(format!("Redaction failed: {}", e)))?
+}
+
+// Re-counts after each cut, since characters per token differ between parts of a text
+const MAX_FIT_ATTEMPTS: usize = 4;
+
+// "Downloaded from: <url> (linked from <page>)", for the model to weigh along with the text
+fn provenance_line(provenance: &provenance::Provenance) -> String {
+    let source = provenance.source_url.as_deref().or(provenance.referrer_url.as_deref()).unwrap_or_default();
+    match (&provenance.source_url, &provenance.referrer_url) {
+        (Some(_), Some(referrer)) => format!("Downloaded from: {} (linked from {})", source, referrer),
+        _ => format!("Downloaded from: {}", source),
+    }
+}
+
+// `text` with the provenance line in front, for a download whose origin was recorded
+fn with_provenance(path: &Path, text: String) -> String {
+    match provenance::read(path) {
+        Some(provenance) => format!("{}\n\n{}", provenance_line(&provenance), text),
+        None => text,
     }
 }
 
+// Extracts a file's text and cuts it down to max_tokens with the given strategy
+// ("head", "head_tail" or "salient"; head by default). Text for a remote backend
+// (`backend`, or the selected one) is redacted first, as the redaction settings say.
+// Downloads start with a "Downloaded from:" line when the browser recorded the URL.
 #[command]
-async fn start_llm_server(
+#[allow(clippy::too_many_arguments)]
+async fn prepare_classification_content(
     app: AppHandle,
-    config: ManagedLLMConfig,
-    state: State<'_, ManagedLLMState>
-) -> Result<String, String> {
-    eprintln!("Received config for starting server: {:?}", config);
-    
-    // Stop any existing server first
-    let _ = stop_llm_server(app.clone(), state.clone()).await;
+    limiter: State<'_, ExtractionLimiterState>,
+    backend_state: State<'_, LlmBackendState>,
+    path: String,
+    max_tokens: usize,
+    strategy: Option<ReductionStrategy>,
+    backend: Option<BackendConfig>,
+) -> Result<PreparedContent, AppError> {
+    if max_tokens == 0 {
+        return Err(AppError::invalid_input("max_tokens must be at least 1"));
+    }
+    let strategy = strategy.unwrap_or(ReductionStrategy::Head);
+    cloud_files::ensure_local(&paths::resolve_path(&path), cloud_files::hydrate_by_default())
+        .map_err(|e| e.with_path(&path))?;
 
-    let app_data_dir = app.path_resolver()
-        .app_data_dir()
-        .ok_or("Could not get app data directory")?;
-    
-    let server_dir = app_data_dir.join("llm-server");
-    let server_exe = if cfg!(target_os = "windows") {
-        server_dir.join("llama_server").join("llama_server").join("llama_server.exe")
-    } else if cfg!(target_os = "macos") {
-        server_dir.join("mlx_server").join("mlx_server")
-    } else {
-        server_dir.join("llama_server").join("llama_server")
-    };
+    let permit = acquire_extraction_permit(&app, &limiter, &path).await;
+    let content = load_file_content_with_timeout(path.clone(), Default::default(), None, true).await?;
+    drop(permit);
+    let text = content.text
+        .ok_or_else(|| AppError::unsupported("File has no text content to fit into a token budget").with_path(&path))?;
+    let text = with_provenance(&paths::resolve_path(&path), text);
+    let backend = backend.or_else(|| backend_state.lock().unwrap().clone());
+    let Redacted { text, counts: redactions } = redact_for(backend.as_ref(), text, None)?;
 
-    if !server_exe.exists() {
-        return Err("Server binary not found. Please download it first.".to_string());
-    }
+    let original_tokens = content_budget::estimate_tokens(&text);
+    let token_counter = "heuristic".to_string();
 
-    let mut cmd = Command::new(&server_exe);
-    
-    // Add command-line arguments (preferred method)
-    cmd.arg("--host").arg(&config.host);
-    cmd.arg("--port").arg(config.port.to_string());
-    cmd.arg("--log-level").arg(&config.log_level);
-    
-    // Add model arguments if specified
-    if let Some(model) = &config.model {
-        cmd.arg("--model").arg(model);
-    }
-    if let Some(model_filename) = &config.model_filename {
-        cmd.arg("--filename").arg(model_filename);
-    }
-    if let Some(model_path) = &config.model_path {
-        cmd.arg("--model-path").arg(model_path);
+    if original_tokens <= max_tokens {
+        return Ok(PreparedContent {
+            text,
+            mime_type: content.mime_type,
+            redactions,
+            original_tokens,
+            kept_tokens: original_tokens,
+            strategy,
+            applied_strategy: None,
+            token_counter,
+        });
     }
-    
-    // Add multi-modal arguments if specified
-    if let Some(mmproj_repo_id) = &config.mmproj_repo_id {
-        cmd.arg("--mmproj-repo-id").arg(mmproj_repo_id);
-    }
-    if let Some(mmproj_filename) = &config.mmproj_filename {
-        cmd.arg("--mmproj-filename").arg(mmproj_filename);
+
+    // Start from the text's average characters per token, then shrink in proportion
+    // to the overshoot until the count fits
+    let mut max_chars = text.chars().count() * max_tokens / original_tokens;
+    let mut kept = String::new();
+    let mut kept_tokens = 0;
+    for _ in 0..MAX_FIT_ATTEMPTS {
+        kept = content_budget::reduce(&text, max_chars, strategy);
+        kept_tokens = content_budget::estimate_tokens(&kept);
+        if kept_tokens <= max_tokens || max_chars == 0 {
+            break;
+        }
+        max_chars = max_chars * max_tokens / kept_tokens;
     }
-    if let Some(chat_format) = &config.chat_format {
-        cmd.arg("--chat-format").arg(chat_format);
+    debug!(
+        "Reduced {} from {} to {} tokens ({:?}, {})",
+        path, original_tokens, kept_tokens, strategy, token_counter
+    );
+
+    Ok(PreparedContent {
+        text: kept,
+        mime_type: content.mime_type,
+        redactions,
+        original_tokens,
+        kept_tokens,
+        strategy,
+        applied_strategy: Some(strategy),
+        token_counter,
+    })
+}
+
+#[derive(Clone, Serialize)]
+struct PipelineProgress {
+    pipeline_id: String,
+    done: usize,
+    total: usize,
+    #[serde(flatten)]
+    estimate: progress::ProgressEstimate,
+}
+
+// State shared by the file tasks of one run_classification_pipeline
+struct PipelineRun {
+    app: AppHandle,
+    id: String,
+    options: pipeline::PipelineOptions,
+    roots: Vec<std::path::PathBuf>,
+    batch_id: String,
+    max_text_chars: usize,
+    backend: BackendConfig,
+    extract_slots: tokio::sync::Semaphore,
+    llm_slots: tokio::sync::Semaphore,
+    // Moves go one at a time, so numbered names and the journal don't race
+    move_lock: tokio::sync::Mutex<()>,
+    destinations: Mutex<pipeline::Destinations>,
+    token: Option<operations::CancelToken>,
+    // Cancelled by the first failure when stop_on_error is set
+    stop: operations::CancelToken,
+    total: usize,
+    done: std::sync::atomic::AtomicUsize,
+    progress: Mutex<(progress::ProgressTracker, Option<std::time::Instant>)>,
+}
+
+impl PipelineRun {
+    fn check_running(&self) -> Result<(), AppError> {
+        if self.token.as_ref().is_some_and(|token| token.is_cancelled()) {
+            return Err(AppError::cancelled("The operation was cancelled"));
+        }
+        if self.stop.is_cancelled() {
+            return Err(AppError::cancelled("Stopped after an earlier file failed"));
+        }
+        Ok(())
     }
 
-    // Configure process creation for proper cleanup on Windows
-    #[cfg(target_os = "windows")]
-    {
-        use std::os::windows::process::CommandExt;
-        // CREATE_NEW_PROCESS_GROUP (0x00000200) - allows us to kill the process tree
-        // CREATE_NO_WINDOW (0x08000000) - prevents console window from appearing
-        const CREATE_NEW_PROCESS_GROUP: u32 = 0x00000200;
-        const CREATE_NO_WINDOW: u32 = 0x08000000;
-        cmd.creation_flags(CREATE_NEW_PROCESS_GROUP | CREATE_NO_WINDOW);
+    fn emit(&self, result: &pipeline::FileResult, stage: pipeline::Stage) {
+        let _ = self.app.emit_all("pipeline-file", pipeline::FileStage {
+            pipeline_id: self.id.clone(),
+            path: result.path.clone(),
+            stage,
+            category_path: result.category_path.clone(),
+            destination: result.destination.clone(),
+            error: result.error.clone(),
+        });
     }
 
-    // Start the server process
-    eprintln!("Starting server with command: {:?}", server_exe);
-    eprintln!("Command-line arguments: --host {} --port {} --log-level {}", 
-              config.host, config.port, config.log_level);
-    
-    cmd.stdout(Stdio::null()).stderr(Stdio::inherit()); // Show stderr in terminal
+    fn file_finished(&self) {
+        if let Some(token) = &self.token {
+            token.record_completed();
+        }
+        let done = self.done.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
+        let mut progress = self.progress.lock().unwrap();
+        let (tracker, reported) = &mut *progress;
+        if done == self.total || reported.is_none_or(|at| at.elapsed() >= RUN_PROGRESS_INTERVAL) {
+            *reported = Some(std::time::Instant::now());
+            let _ = self.app.emit_all("pipeline-progress", PipelineProgress {
+                pipeline_id: self.id.clone(),
+                done,
+                total: self.total,
+                estimate: tracker.update(done as u64),
+            });
+        }
+    }
+}
 
-    let mut child = cmd.spawn()
-        .map_err(|e| format!("Failed to start server: {}", e))?;
+fn categorized_by_extension(result: &mut pipeline::FileResult, source: &Path, note: String) {
+    result.method = Some(pipeline::Method::Extension);
+    result.category_path = Some(pipeline::extension_category(source));
+    result.suggested_filename = source.file_stem().map(|stem| stem.to_string_lossy().into_owned());
+    result.note = Some(note);
+}
 
-    eprintln!("Server process started with PID: {:?}", child.id());
+// Extract, prepare and classify stages: fills in the file's category and name. Files
+// with nothing to send are categorized by extension, as the frontend's scan does.
+async fn pipeline_classify(
+    run: &PipelineRun,
+    result: &mut pipeline::FileResult,
+    source: &Path,
+) -> Result<(), AppError> {
+    let path = result.path.clone();
+    if let Some(model_id) = &run.options.model_id {
+        let cached = get_cached_classification(run.app.clone(), run.app.state(), path.clone(), model_id.clone()).await;
+        if let Ok(Some(cached)) = cached {
+            result.method = Some(pipeline::Method::Cache);
+            result.category_path = Some(cached.category);
+            result.suggested_filename = Some(cached.destination);
+            return Ok(());
+        }
+    }
 
-    // Wait a moment to see if the process crashes immediately
-    tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
-    
-    // Check if the process is still running
-    match child.try_wait() {
-        Ok(Some(status)) => {
-            return Err(format!("Server process exited immediately with status: {}", status));
+    let content = {
+        let _slot = run.extract_slots.acquire().await.map_err(|_| AppError::cancelled("The pipeline was closed"))?;
+        run.check_running()?;
+        run.emit(result, pipeline::Stage::Extracting);
+        let limiter = run.app.state::<ExtractionLimiterState>();
+        let _permit = acquire_extraction_permit(&run.app, &limiter, &path).await;
+        match cloud_files::ensure_local(source, cloud_files::hydrate_by_default()) {
+            Ok(()) => load_file_content_with_timeout(path.clone(), Default::default(), None, true).await,
+            Err(e) => Err(e),
         }
-        Ok(None) => {
-            eprintln!("Server process still running after 1 second");
+    };
+    let content = match content {
+        Ok(content) if content.text.is_some() || content.image_base64.is_some() => content,
+        Ok(_) => {
+            categorized_by_extension(result, source, "The file has no text or image to classify".to_string());
+            return Ok(());
         }
+        Err(e) if e.kind == error::AppErrorKind::Cancelled => return Err(e),
         Err(e) => {
-            return Err(format!("Error checking server status: {}", e));
+            categorized_by_extension(result, source, e.message);
+            return Ok(());
+        }
+    };
+
+    let text = content.text.map(|text| {
+        content_budget::reduce(&with_provenance(source, text), run.max_text_chars, ReductionStrategy::Head)
+    });
+    let file_name = source.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+    let categories = &run.options.categories;
+    let request = ClassifyRequest {
+        system_message: run.options.system_message.clone()
+            .unwrap_or_else(|| pipeline::DEFAULT_SYSTEM_MESSAGE.to_string()),
+        prompt: pipeline::build_prompt(run.options.prompt_template.as_deref(), &file_name, text.as_deref(), categories),
+        image_base64: if text.is_none() { content.image_base64 } else { None },
+        mime_type: content.mime_type,
+        max_tokens: run.options.max_tokens,
+        source_path: Some(path.clone()),
+    };
+
+    let response = {
+        let _slot = run.llm_slots.acquire().await.map_err(|_| AppError::cancelled("The pipeline was closed"))?;
+        run.check_running()?;
+        run.emit(result, pipeline::Stage::Classifying);
+        let app = run.app.clone();
+        classify_with(app, request, run.backend.clone(), run.app.state(), run.app.state(), run.app.state()).await?
+    };
+    result.method = Some(pipeline::Method::Llm);
+    result.backend = Some(response.backend);
+    let stem = source.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+    match pipeline::parse_classification(&response.content) {
+        Some(classification) => {
+            let category = pipeline::match_category(&classification.category_path, categories);
+            if let Some(model_id) = &run.options.model_id {
+                let (cache, name) = (run.app.state(), classification.suggested_filename.clone());
+                let cached =
+                    cache_classification(run.app.clone(), cache, path, None, category.clone(), name, model_id.clone());
+                if let Err(e) = cached.await {
+                    warn!("Failed to cache the classification of {}: {}", result.path, e.message);
+                }
+            }
+            result.category_path = Some(category);
+            result.suggested_filename = Some(classification.suggested_filename);
+        }
+        None => {
+            result.category_path = Some(pipeline::UNCATEGORIZED.to_string());
+            result.suggested_filename = Some(stem);
+            result.note = Some("The model's answer had no category; filed as uncategorized".to_string());
         }
     }
+    Ok(())
+}
 
-    // Store the process handle and config
-    let pid = child.id();
-    
-    // Write PID file for orphan detection
-    write_pid_file(&app_data_dir, pid, &config)?;
-    
-    // Create process info
-    let process_info = ServerProcessInfo {
-        pid,
-        config: config.clone(),
+// Destination and move stages. A dry run still checks the destination the way the
+// move would, so the plan shows what would fail.
+async fn pipeline_place(run: &PipelineRun, result: &mut pipeline::FileResult, source: &Path) -> Result<(), AppError> {
+    let root = pipeline::root_for(source, &run.roots);
+    let destination = run.destinations.lock().unwrap().assign(
+        &root,
+        result.category_path.as_deref().unwrap_or(pipeline::UNCATEGORIZED),
+        result.suggested_filename.as_deref().unwrap_or_default(),
+        source,
+    );
+    result.destination = Some(paths::display_path(&destination));
+    if destination == source {
+        result.status = pipeline::FileStatus::Skipped;
+        result.note = Some("The file is already at its destination".to_string());
+        return Ok(());
+    }
+
+    let _moving = run.move_lock.lock().await;
+    run.check_running()?;
+    let planned = runs::PlannedMove { from: result.path.clone(), to: paths::display_path(&destination) };
+    let on_conflict = run.options.on_conflict.unwrap_or_default();
+    let remaining = tauri::async_runtime::spawn_blocking(move || {
+        runs::check_remaining(&planned, on_conflict, &HashSet::new())
+    })
+    .await
+    .map_err(|e| AppError::io(format!("Failed to check the move: {}", e)))?;
+    let destination = match remaining {
+        runs::Remaining::Move(destination) => paths::display_path(&destination),
+        runs::Remaining::AlreadyMoved | runs::Remaining::Skip => {
+            result.status = pipeline::FileStatus::Skipped;
+            result.note = Some("The destination is taken".to_string());
+            return Ok(());
+        }
+        runs::Remaining::Invalid(error) => return Err(error),
     };
-    
-    {
-        let mut state_guard = state.lock().unwrap();
-        *state_guard = Some((Some(child), process_info));
-        eprintln!("Stored server process with PID {} in state", pid);
+    result.destination = Some(destination.clone());
+    if run.options.dry_run {
+        return Ok(());
     }
+    run.emit(result, pipeline::Stage::Moving);
+    let outcome = journaled_move(&run.app, &run.batch_id, result.path.clone(), destination, Some(true), false).await?;
+    result.destination = Some(outcome.to);
+    result.status = pipeline::FileStatus::Moved;
+    Ok(())
+}
 
-    eprintln!("Server process stored, waiting for initialization...");
-    
-    // Give the server more time to start up
-    tokio::time::sleep(tokio::time::Duration::from_secs(3)).await;
-    
-    // Test if the server is responding
-    let test_url = format!("http://{}:{}/v1/models", config.host, config.port);
-    eprintln!("Testing server startup at: {}", test_url);
-    
-    let client = reqwest::Client::new();
-    match client.get(&test_url).timeout(std::time::Duration::from_secs(10)).send().await {
-        Ok(response) => {
-            eprintln!("Server startup test successful: {}", response.status());
-            // Read the response body to properly close the connection
-            let _ = response.bytes().await;
+async fn pipeline_file(run: &PipelineRun, path: String) -> pipeline::FileResult {
+    let source = paths::resolve_path(&path);
+    let mut result = pipeline::FileResult::new(path, pipeline::FileStatus::Planned);
+    let outcome = match pipeline_classify(run, &mut result, &source).await {
+        Ok(()) => pipeline_place(run, &mut result, &source).await,
+        Err(e) => Err(e),
+    };
+    match outcome {
+        Ok(()) => {}
+        Err(e) if e.kind == error::AppErrorKind::Cancelled => {
+            result.status = pipeline::FileStatus::Skipped;
+            result.note = Some(e.message);
         }
         Err(e) => {
-            eprintln!("Server startup test failed: {}", e);
+            if run.options.stop_on_error && !run.stop.is_cancelled() {
+                warn!("Stopping pipeline {} after {} failed: {}", run.id, result.path, e.message);
+                run.stop.cancel();
+            }
+            result.status = pipeline::FileStatus::Failed;
+            result.error = Some(e);
         }
     }
-
-    Ok(format!("Server started on {}:{}", config.host, config.port))
+    let stage = match result.status {
+        pipeline::FileStatus::Planned | pipeline::FileStatus::Moved => pipeline::Stage::Done,
+        pipeline::FileStatus::Skipped => pipeline::Stage::Skipped,
+        pipeline::FileStatus::Failed => pipeline::Stage::Failed,
+    };
+    run.emit(&result, stage);
+    run.file_finished();
+    result
 }
 
+// Classifies `paths` and moves each into <root>/<category>/<suggested name>, all in the
+// backend: files are extracted extract_concurrency at a time, cut to max_text_chars and
+// sent llm_concurrency at a time through the same router as llm_classify, then moved
+// journaled under batch_id (or, with dry_run, only planned; execute_plan can make the
+// approved moves). Each stage a file enters goes out as a pipeline-file event, with
+// pipeline-progress a few times a second. Cancelling operation_id, or a failure with
+// stop_on_error, lets files already started finish and skips the rest.
 #[command]
-async fn stop_llm_server(app: AppHandle, state: State<'_, ManagedLLMState>) -> Result<String, String> {
-    eprintln!("Attempting to stop LLM server...");
-    
-    let app_data_dir = app.path_resolver()
-        .app_data_dir()
-        .ok_or("Could not get app data directory")?;
-    
-    let mut state_guard = state.lock().unwrap();
-    eprintln!("Got lock on state");
-    
-    let has_process = state_guard.is_some();
-    eprintln!("State has process: {}", has_process);
-    
-    if let Some((child_opt, process_info)) = state_guard.take() {
-        let pid = process_info.pid;
-        eprintln!("Found server process with PID: {}", pid);
-        
-        // On Windows, use taskkill to forcefully terminate the process tree first
-        #[cfg(target_os = "windows")]
-        {
-            eprintln!("Using taskkill to terminate process tree for PID: {}", pid);
-            let output = std::process::Command::new("taskkill")
-                .args(&["/F", "/T", "/PID", &pid.to_string()])
-                .output();
-            
-            match output {
-                Ok(output) => {
-                    if output.status.success() {
-                        eprintln!("Successfully killed process tree with taskkill");
-                    } else {
-                        eprintln!("Taskkill failed: {}", String::from_utf8_lossy(&output.stderr));
-                    }
-                }
-                Err(e) => {
-                    eprintln!("Failed to execute taskkill: {}", e);
-                }
-            }
-            
-            // Give the process a moment to terminate
-            std::thread::sleep(std::time::Duration::from_millis(500));
-        }
-        
-        // Also try to kill via Child handle (if we have it)
-        if let Some(mut child) = child_opt {
-            eprintln!("Also killing via Child handle");
-            let _ = child.kill();
-            let _ = child.wait();
+async fn run_classification_pipeline(
+    app: AppHandle,
+    operations: State<'_, OperationsState>,
+    backend_state: State<'_, LlmBackendState>,
+    limiter: State<'_, ExtractionLimiterState>,
+    paths: Vec<String>,
+    options: Option<pipeline::PipelineOptions>,
+) -> Result<pipeline::PipelineReport, AppError> {
+    let options = options.unwrap_or_default();
+    if options.extract_concurrency == Some(0) || options.llm_concurrency == Some(0) {
+        return Err(AppError::invalid_input("Concurrency must be at least 1"));
+    }
+    // options.backend is for this run only; the selected backend stays as it is
+    let backend = match options.backend.clone() {
+        Some(backend) => {
+            check_llm_backend(&backend)?;
+            backend
         }
-        
-        // On Unix systems, kill by PID
-        #[cfg(unix)]
-        {
-            let _ = kill_process_by_pid(pid);
+        None => selected_llm_backend(backend_state.inner()).map_err(AppError::invalid_input)?,
+    };
+    let now = journal::now_ms();
+    let batch_id = options.batch_id.clone().unwrap_or_else(|| format!("pipeline-{}", now));
+    journal::validate_batch_id(&batch_id)?;
+
+    let extract_concurrency = options.extract_concurrency.unwrap_or_else(|| limiter.permits());
+    let llm_concurrency = options.llm_concurrency.unwrap_or(match &backend {
+        BackendConfig::Managed { .. } => pipeline::MANAGED_LLM_CONCURRENCY,
+        BackendConfig::Remote { .. } => pipeline::REMOTE_LLM_CONCURRENCY,
+    });
+    let started = std::time::Instant::now();
+    let total = paths.len();
+    let run = Arc::new(PipelineRun {
+        app: app.clone(),
+        id: format!("pipeline-{}-{}", now, std::process::id()),
+        roots: options.roots.iter().map(|root| paths::resolve_path(root)).collect(),
+        batch_id,
+        max_text_chars: options.max_text_chars.unwrap_or(pipeline::DEFAULT_MAX_TEXT_CHARS),
+        backend,
+        extract_slots: tokio::sync::Semaphore::new(extract_concurrency),
+        llm_slots: tokio::sync::Semaphore::new(llm_concurrency),
+        move_lock: tokio::sync::Mutex::new(()),
+        destinations: Mutex::new(pipeline::Destinations::default()),
+        token: options.operation_id.as_deref().map(|id| operations.token(id)),
+        stop: operations::CancelToken::default(),
+        total,
+        done: std::sync::atomic::AtomicUsize::new(0),
+        progress: Mutex::new((progress::ProgressTracker::new(progress::RateUnit::Items, Some(total as u64)), None)),
+        options,
+    });
+    info!(
+        "Pipeline {}: {} files, {} extractions and {} LLM requests at a time{}",
+        run.id, total, extract_concurrency, llm_concurrency, if run.options.dry_run { " (dry run)" } else { "" }
+    );
+
+    // Files started but not finished are capped, so extracted text doesn't pile up
+    // while it waits for the LLM
+    let window = Arc::new(tokio::sync::Semaphore::new(extract_concurrency + llm_concurrency));
+    let mut tasks = Vec::with_capacity(total);
+    for path in &paths {
+        let Ok(slot) = window.clone().acquire_owned().await else { break };
+        if run.check_running().is_err() {
+            break;
         }
-        
-        // Verify the process is actually dead
-        if is_process_running(pid) {
-            eprintln!("Warning: Process {} may still be running after kill attempt", pid);
-        } else {
-            eprintln!("Confirmed: Process {} has terminated", pid);
+        let (run, path) = (run.clone(), path.clone());
+        tasks.push(tauri::async_runtime::spawn(async move {
+            let _slot = slot;
+            pipeline_file(&run, path).await
+        }));
+    }
+    let mut results = Vec::with_capacity(total);
+    for (task, path) in tasks.into_iter().zip(&paths) {
+        results.push(task.await.unwrap_or_else(|e| {
+            pipeline::FileResult::failed(path.clone(), AppError::io(format!("Pipeline task failed: {}", e)))
+        }));
+    }
+    let note = run.check_running().err().map(|e| e.message);
+    results.extend(paths[results.len()..].iter().map(|path| pipeline::FileResult {
+        note: note.clone(),
+        ..pipeline::FileResult::new(path.clone(), pipeline::FileStatus::Skipped)
+    }));
+
+    let batch_id = (!run.options.dry_run).then(|| run.batch_id.clone());
+    let mut report = pipeline::PipelineReport::new(run.id.clone(), batch_id, run.options.dry_run, results);
+    report.cancelled = run.token.as_ref().is_some_and(|token| token.is_cancelled());
+    report.stopped_on_error = run.stop.is_cancelled();
+    report.elapsed_ms = started.elapsed().as_millis() as u64;
+    info!(
+        "Pipeline {}: {} planned, {} moved, {} skipped, {} failed of {} in {} ms",
+        run.id, report.planned, report.moved, report.skipped, report.failed, total, report.elapsed_ms
+    );
+    if let (Some(token), Some(id)) = (&run.token, &run.options.operation_id) {
+        if report.cancelled {
+            let completed = report.planned + report.moved + report.failed;
+            operation_cancelled(&app, token, id, completed, Some(report.skipped));
         }
-        
-        // Clean up PID file
-        remove_pid_file(&app_data_dir);
-        
-        Ok("Server stopped".to_string())
-    } else {
-        eprintln!("No server process found in state");
-        Ok("Server was not running".to_string())
+        operations.finish(id);
     }
+    Ok(report)
 }
 
 #[command]
-async fn get_llm_server_info(app: AppHandle, state: State<'_, ManagedLLMState>) -> Result<ManagedLLMServerInfo, String> {
+async fn get_llm_server_info(app: AppHandle, state: State<'_, ManagedLLMState>) -> Result<ManagedLLMServerInfo, AppError> {
     get_llm_server_status(app, state).await
 }
 
@@ -1430,7 +6257,7 @@ fn compare_versions(version1: &str, version2: &str) -> Option<bool> {
 
 // Generic function to fetch latest version from GitHub releases by tag prefix
 async fn check_latest_version_by_prefix(tag_prefix: &str) -> Result<Option<String>, String> {
-    let client = reqwest::Client::new();
+    let client = network::build_http_client();
     let url = "https://api.github.com/repos/BorisBesky/file-organizer-desktop/releases";
     
     let response = client
@@ -1458,7 +6285,7 @@ async fn check_latest_version_by_prefix(tag_prefix: &str) -> Result<Option<Strin
         let is_prerelease = release["prerelease"].as_bool().unwrap_or(false);
         
         if let Some(tag_name) = release["tag_name"].as_str() {
-            eprintln!("Found release: {} (draft: {}, prerelease: {})", tag_name, is_draft, is_prerelease);
+            debug!("Found release: {} (draft: {}, prerelease: {})", tag_name, is_draft, is_prerelease);
         }
         
         if is_draft || is_prerelease {
@@ -1469,16 +6296,16 @@ async fn check_latest_version_by_prefix(tag_prefix: &str) -> Result<Option<Strin
             if tag_name.starts_with("llm-v") {
                 // Extract version from tag (e.g., "llm-v1.0.0" -> "1.0.0")
                 let version = tag_name.strip_prefix("llm-v").unwrap_or(tag_name).to_string();
-                eprintln!("Found LLM version: {}", version);
+                debug!("Found LLM version: {}", version);
                 
                 // If we haven't found any version yet, or this version is newer
                 if latest_version.is_none() {
-                    eprintln!("Setting as first latest: {}", version);
+                    debug!("Setting as first latest: {}", version);
                     latest_version = Some(version);
                 } else if let Some(ref current_latest) = latest_version {
-                    eprintln!("Comparing {} with current latest {}", version, current_latest);
+                    debug!("Comparing {} with current latest {}", version, current_latest);
                     if let Some(true) = compare_versions(&version, current_latest) {
-                        eprintln!("Updating latest to: {}", version);
+                        debug!("Updating latest to: {}", version);
                         latest_version = Some(version);
                     }
                 }
@@ -1486,7 +6313,7 @@ async fn check_latest_version_by_prefix(tag_prefix: &str) -> Result<Option<Strin
         }
     }
     
-    eprintln!("Final latest {} version: {:?}", tag_prefix, latest_version);
+    debug!("Final latest {} version: {:?}", tag_prefix, latest_version);
     Ok(latest_version)
 }
 
@@ -1520,7 +6347,7 @@ pub struct AppUpdateInfo {
 async fn check_llm_server_update(
     app: AppHandle,
     state: State<'_, ManagedLLMState>
-) -> Result<LLMServerUpdateInfo, String> {
+) -> Result<LLMServerUpdateInfo, AppError> {
     // Get current installed version
     let status = get_llm_server_status(app, state).await?;
     let current_version = status.version.clone();
@@ -1529,11 +6356,11 @@ async fn check_llm_server_update(
     let latest_version = match check_llm_server_latest_version().await {
         Ok(Some(version)) => Some(version),
         Ok(None) => {
-            eprintln!("No llm-v* releases found on GitHub");
+            info!("No llm-v* releases found on GitHub");
             None
         }
         Err(e) => {
-            eprintln!("Failed to check for updates: {}", e);
+            warn!("Failed to check for updates: {}", e);
             None
         }
     };
@@ -1558,7 +6385,7 @@ async fn check_llm_server_update(
 }
 
 #[command]
-async fn check_app_update() -> Result<AppUpdateInfo, String> {
+async fn check_app_update() -> Result<AppUpdateInfo, AppError> {
     // Get current app version from Cargo.toml
     let current_version = env!("CARGO_PKG_VERSION").to_string();
     
@@ -1566,11 +6393,11 @@ async fn check_app_update() -> Result<AppUpdateInfo, String> {
     let latest_version = match check_app_latest_version().await {
         Ok(Some(version)) => Some(version),
         Ok(None) => {
-            eprintln!("No v* releases found on GitHub");
+            info!("No v* releases found on GitHub");
             None
         }
         Err(e) => {
-            eprintln!("Failed to check for app updates: {}", e);
+            warn!("Failed to check for app updates: {}", e);
             None
         }
     };
@@ -1615,6 +6442,21 @@ fn store_downloaded_version(app_data_dir: &std::path::PathBuf, version: &str) ->
     Ok(())
 }
 
+// Read the VERSION file packaged with the server. Depending on how the archive was
+// extracted it sits either directly in the server directory or one level below it.
+fn read_archive_version_file(server_path: &Path) -> Option<String> {
+    let candidates = [
+        server_path.join("VERSION"),
+        server_path.join("llama_server").join("VERSION"),
+        server_path.join("mlx_server").join("VERSION"),
+    ];
+    candidates.iter().find_map(|path| {
+        let version = fs::read_to_string(path).ok()?;
+        let version = version.trim();
+        (!version.is_empty()).then(|| version.to_string())
+    })
+}
+
 // Read stored version
 fn read_downloaded_version(app_data_dir: &std::path::PathBuf) -> Option<String> {
     let version_file = get_version_metadata_path(app_data_dir);
@@ -1703,6 +6545,64 @@ fn handle_menu_event(event: WindowMenuEvent) {
     }
 }
 
+// Ordered exit: refuse new downloads and LLM requests, give running ones a bounded
+// grace period to stop at a consistent point, then stop the managed server. Safe to
+// call more than once, as both the main window closing and the exit request end up here.
+fn shutdown_app(state: &ManagedLLMState, app_data_dir: Option<std::path::PathBuf>) {
+    let running = shutdown::SHUTDOWN.begin(shutdown::GRACE_PERIOD);
+    if running > 0 {
+        warn!("{} tasks were still running after the shutdown grace period", running);
+    }
+    shutdown_managed_server(state, app_data_dir);
+}
+
+// Stops the managed server when the app goes away
+fn shutdown_managed_server(state: &ManagedLLMState, app_data_dir: Option<std::path::PathBuf>) {
+    // Disarm the supervisor so it doesn't restart the server we're stopping
+    SUPERVISOR_GENERATION.fetch_add(1, Ordering::SeqCst);
+    let mut state_guard = state.lock().unwrap();
+    if let Some((child_opt, process_info)) = state_guard.take() {
+        let pid = process_info.pid;
+        info!("Stopping LLM server with PID: {}", pid);
+        
+        // On Windows, use taskkill first for forceful termination
+        #[cfg(target_os = "windows")]
+        {
+            let _ = kill_process_by_pid(pid);
+            // Brief wait to ensure termination
+            std::thread::sleep(std::time::Duration::from_millis(300));
+        }
+        
+        // Also kill via Child handle if available
+        if let Some(mut child) = child_opt {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+        
+        // On Unix, kill by PID
+        #[cfg(unix)]
+        {
+            let _ = kill_process_by_pid(pid);
+        }
+        
+        // Clean up PID file
+        if let Some(app_data_dir) = app_data_dir {
+            remove_pid_file(&app_data_dir);
+        }
+        
+        info!("LLM server stopped on app exit");
+    } else {
+        info!("No LLM server was running on exit");
+    }
+    
+    // Final safety measure: kill any remaining llama_server.exe processes by name
+    #[cfg(target_os = "windows")]
+    {
+        info!("Final cleanup: killing any remaining llama_server.exe processes");
+        let _ = kill_process_by_name("llama_server.exe");
+    }
+}
+
 fn main() {
     let menu = create_menu();
     
@@ -1713,21 +6613,57 @@ fn main() {
     let llm_state_setup = llm_state.clone();
     // Clone for the window event closure
     let llm_state_window = llm_state.clone();
+    // Clone for the exit handler
+    let llm_state_exit = llm_state.clone();
+
+    let scheduler_state = Arc::new(Mutex::new(Vec::new())) as SchedulerState;
+    let extraction_limiter =
+        Arc::new(extraction::ExtractionLimiter::new(extraction::default_concurrency())) as ExtractionLimiterState;
     
     tauri::Builder::default()
         .menu(menu)
         .on_menu_event(handle_menu_event)
         .manage(llm_state)
+        .manage(Arc::new(Mutex::new(None::<BackendConfig>)) as LlmBackendState)
+        .manage(Arc::new(Mutex::new(FallbackState::default())) as LlmFallbackState)
+        .manage(Arc::new(Mutex::new(llm_metrics::LlmMetrics::new(scheduler::now_secs()))) as LlmMetricsState)
+        .manage(Arc::new(Mutex::new(HashMap::new())) as HttpStreamState)
+        .manage(scheduler_state.clone())
+        .manage(Arc::new(Mutex::new(None::<ClassificationCache>)) as ClassificationCacheState)
+        .manage(Arc::new(Mutex::new(None::<content_index::ContentIndex>)) as ContentIndexState)
+        .manage(extraction_limiter)
+        .manage(Arc::new(operations::Operations::default()) as OperationsState)
+        .manage(Arc::new(directory_stream::DirectoryStreams::default()) as DirectoryStreamState)
         .setup(move |app| {
             // Try to reconnect to orphaned server on startup
             let app_handle = app.handle();
             let state = llm_state_setup.clone();
+
+            logging::init(app_handle.path_resolver().app_data_dir().as_deref());
+
+            // Apply proxy / CA settings and the saved settings before anything runs
+            if let Some(app_data_dir) = app_handle.path_resolver().app_data_dir() {
+                network::load_saved_config(&app_data_dir);
+                destination_policy::set_app_data_dir(&app_data_dir);
+                let saved = settings::load_saved_settings(&app_data_dir);
+                apply_runtime_settings(&app_handle.state::<ExtractionLimiterState>(), &saved);
+                warn_expiring_staged_batches(&app_handle, &app_data_dir);
+            }
+
+            start_scheduler(&app_handle, &scheduler_state);
+
+            // The UI hears about drives coming and going; moves on a vanished one fail fast
+            let volume_events = app_handle.clone();
+            volumes::watch(move |change, volume| {
+                info!("{}: {}", change.event_name(), volume.mount_point);
+                let _ = volume_events.emit_all(change.event_name(), volume);
+            });
             
             tauri::async_runtime::spawn(async move {
                 if let Some(app_data_dir) = app_handle.path_resolver().app_data_dir() {
-                    eprintln!("Checking for orphaned LLM server processes...");
+                    info!("Checking for orphaned LLM server processes...");
                     if let Err(e) = try_reconnect_orphaned_server(&app_data_dir, &state).await {
-                        eprintln!("Failed to reconnect to orphaned server: {}", e);
+                        warn!("Failed to reconnect to orphaned server: {}", e);
                     }
                 }
             });
@@ -1736,65 +6672,82 @@ fn main() {
         })
         .on_window_event(move |event| {
             if let tauri::WindowEvent::Destroyed = event.event() {
-                eprintln!("Window closing, shutting down LLM server if running...");
-                
-                // Get app data dir for PID file cleanup
-                let app_data_dir = event.window().app_handle().path_resolver().app_data_dir();
-                
-                // Stop the LLM server
-                let mut state_guard = llm_state_window.lock().unwrap();
-                if let Some((child_opt, process_info)) = state_guard.take() {
-                    let pid = process_info.pid;
-                    eprintln!("Stopping LLM server with PID: {}", pid);
-                    
-                    // On Windows, use taskkill first for forceful termination
-                    #[cfg(target_os = "windows")]
-                    {
-                        let _ = kill_process_by_pid(pid);
-                        // Brief wait to ensure termination
-                        std::thread::sleep(std::time::Duration::from_millis(300));
-                    }
-                    
-                    // Also kill via Child handle if available
-                    if let Some(mut child) = child_opt {
-                        let _ = child.kill();
-                        let _ = child.wait();
-                    }
-                    
-                    // On Unix, kill by PID
-                    #[cfg(unix)]
-                    {
-                        let _ = kill_process_by_pid(pid);
-                    }
-                    
-                    // Clean up PID file
-                    if let Some(app_data_dir) = app_data_dir {
-                        remove_pid_file(&app_data_dir);
-                    }
-                    
-                    eprintln!("LLM server stopped on app exit");
-                } else {
-                    eprintln!("No LLM server was running on exit");
-                }
-                
-                // Final safety measure: kill any remaining llama_server.exe processes by name
-                #[cfg(target_os = "windows")]
-                {
-                    eprintln!("Final cleanup: killing any remaining llama_server.exe processes");
-                    let _ = kill_process_by_name("llama_server.exe");
+                let window = event.window();
+                let stopped = window.state::<DirectoryStreamState>().cancel_window(window.label());
+                if stopped > 0 {
+                    info!("Stopped {} directory stream(s) for closed window {}", stopped, window.label());
                 }
+                info!("Window closing, shutting down LLM server if running...");
+                let app_data_dir = event.window().app_handle().path_resolver().app_data_dir();
+                shutdown_app(&llm_state_window, app_data_dir);
             }
         })
         .invoke_handler(tauri::generate_handler![
             read_directory,
+            read_directory_entries,
+            start_directory_stream,
+            cancel_directory_stream,
             list_subdirectories,
+            summarize_directory_tree,
+            get_directory_composition,
+            find_unused_directories,
+            get_spotlight_metadata,
+            get_file_provenance,
+            find_shortcuts,
+            sample_directory_for_classification,
+            compare_classifications,
+            find_duplicate_directories,
+            resume_analysis,
+            list_checkpoints,
+            delete_checkpoint,
+            list_volumes,
+            set_scan_throttle,
+            get_scan_throttle,
+            plan_date_organization,
             pick_directory,
             read_file_content,
+            get_extraction_timeout,
+            test_external_extractor,
+            get_extraction_concurrency,
+            set_extraction_concurrency,
+            cancel_operation,
+            finish_operation,
+            get_settings,
+            set_settings,
+            patch_settings,
+            export_settings,
+            import_settings,
+            set_locale,
+            get_locale,
+            set_extraction_timeout,
+            read_archived_file_content,
+            generate_thumbnail,
+            clear_thumbnail_cache,
             move_file,
+            verify_organization,
+            archive_files,
+            unarchive_files,
+            export_audit_log,
+            verify_audit_log,
+            record_llm_submission,
+            check_network_policy,
+            set_file_tags,
+            get_file_tags,
+            get_directory_tags,
+            index_directory,
+            search_indexed_content,
+            delete_index,
+            rename_file,
+            rename_files,
             http_request,
             save_diagnostic_logs,
+            get_app_logs,
+            set_log_level,
             open_file,
+            list_applications_for,
+            open_file_with,
             get_app_version,
+            get_app_info,
             get_llm_server_status,
             download_llm_server,
             update_llm_server,
@@ -1802,8 +6755,59 @@ fn main() {
             stop_llm_server,
             get_llm_server_info,
             check_llm_server_update,
-            check_app_update
+            check_app_update,
+            detect_gpu_capabilities,
+            list_managed_server_models,
+            select_llm_backend,
+            get_active_backend,
+            llm_classify,
+            get_llm_metrics,
+            search_hf_models,
+            list_hf_model_files,
+            download_hf_model,
+            recommend_models,
+            suggest_destinations,
+            stage_files,
+            commit_staged,
+            list_staged_batches,
+            get_staged_batch,
+            execute_plan,
+            undo_batch,
+            get_interrupted_runs,
+            resume_run,
+            discard_run,
+            reset_llm_metrics,
+            get_llm_fallback_settings,
+            set_llm_fallback_settings,
+            cache_classification,
+            get_cached_classification,
+            get_classification_cache_stats,
+            clear_classification_cache,
+            prepare_classification_content,
+            run_classification_pipeline,
+            redact_sensitive_content,
+            http_request_stream,
+            cancel_http_stream,
+            get_network_config,
+            get_destination_policy,
+            set_destination_policy,
+            set_organization_roots,
+            set_network_config,
+            test_network_config,
+            check_directory_access,
+            notify,
+            create_schedule,
+            list_schedules,
+            delete_schedule,
+            get_schedule_results
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(move |app_handle, event| {
+            // Exiting via the menu or with several windows open skips the Destroyed hook
+            if let tauri::RunEvent::ExitRequested { .. } = event {
+                info!("Exit requested, shutting down LLM server if running...");
+                shutdown_app(&llm_state_exit, app_handle.path_resolver().app_data_dir());
+            }
+        });
 }
\ No newline at end of file
@@ -0,0 +1,263 @@
+// Picking a starting model for the detected hardware. The catalog of known-good GGUFs
+// is a JSON data file (resources/model_catalog.json, or model_catalog.json in the app
+// data directory, which wins) so it can be updated without a new build. Each entry is
+// scored by quality tier, scaled down when the estimated generation speed on this
+// machine is too slow for classifying files one after another.
+use crate::error::AppError;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+pub const CATALOG_VERSION: u32 = 1;
+pub const CATALOG_FILE: &str = "model_catalog.json";
+pub const DEFAULT_USE_CASE: &str = "text";
+
+// Rough memory bandwidth in bytes per second. Generating a token reads every weight
+// once, so tokens per second is about bandwidth over model size.
+const CUDA_BANDWIDTH: f64 = 300e9;
+const VULKAN_BANDWIDTH: f64 = 150e9;
+const METAL_BANDWIDTH: f64 = 100e9;
+const CPU_BANDWIDTH_PER_CORE: f64 = 2.5e9;
+// Beyond this many cores, CPU generation is bound by memory, not compute
+const CPU_BANDWIDTH_CORES: usize = 8;
+// Speed at which a model counts as fast enough; slower ones lose score proportionally
+const TARGET_TOKENS_PER_SECOND: f64 = 10.0;
+const FULL_OFFLOAD_BONUS: f64 = 5.0;
+const GB: f64 = 1024.0 * 1024.0 * 1024.0;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Catalog {
+    pub version: u32,
+    pub models: Vec<CatalogEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CatalogEntry {
+    pub name: String,
+    pub repo_id: String,
+    pub filename: String,
+    // Vision models: the projector file in the same repo
+    #[serde(default)]
+    pub mmproj_filename: Option<String>,
+    pub size_bytes: u64,
+    pub min_ram_bytes: u64,
+    // VRAM needed to offload every layer with room for the context
+    pub full_offload_vram_bytes: u64,
+    // 1 (smallest) to 5 (best answers)
+    pub quality_tier: u8,
+    // "text", "vision"
+    pub use_cases: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Hardware {
+    pub total_ram_bytes: u64,
+    pub cpu_cores: usize,
+    // "metal" | "cuda" | "vulkan" | "cpu", as detect_gpu_capabilities reports it
+    pub gpu_backend: String,
+    pub vram_bytes: Option<u64>,
+    pub unified_memory: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Offload {
+    Full,
+    Partial,
+    None,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Recommendation {
+    #[serde(flatten)]
+    pub model: CatalogEntry,
+    pub score: f64,
+    pub offload: Offload,
+    pub estimated_tokens_per_second: f64,
+    pub explanation: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RecommendationReport {
+    pub hardware: Hardware,
+    pub use_case: String,
+    // Best first; models that don't fit in RAM are left out
+    pub recommendations: Vec<Recommendation>,
+}
+
+// The first of `paths` that exists
+pub fn load(paths: &[PathBuf]) -> Result<Catalog, AppError> {
+    let path = paths
+        .iter()
+        .find(|path| path.is_file())
+        .ok_or_else(|| AppError::not_found("The model catalog is missing"))?;
+    parse(path)
+}
+
+fn parse(path: &Path) -> Result<Catalog, AppError> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| AppError::from_io(&e, format!("Failed to read the model catalog: {}", e)).with_path(path))?;
+    let catalog: Catalog = serde_json::from_str(&text)
+        .map_err(|e| AppError::invalid_input(format!("The model catalog is damaged: {}", e)).with_path(path))?;
+    if catalog.version != CATALOG_VERSION {
+        return Err(AppError::unsupported(format!(
+            "The model catalog is in format {} and this version of the app reads format {}",
+            catalog.version, CATALOG_VERSION
+        ))
+        .with_path(path));
+    }
+    Ok(catalog)
+}
+
+fn gpu_bandwidth(backend: &str) -> Option<f64> {
+    match backend {
+        "cuda" => Some(CUDA_BANDWIDTH),
+        "vulkan" => Some(VULKAN_BANDWIDTH),
+        "metal" => Some(METAL_BANDWIDTH),
+        _ => None,
+    }
+}
+
+fn cpu_bandwidth(cores: usize) -> f64 {
+    cores.clamp(1, CPU_BANDWIDTH_CORES) as f64 * CPU_BANDWIDTH_PER_CORE
+}
+
+fn gigabytes(bytes: u64) -> String {
+    format!("{:.0}", (bytes as f64 / GB).max(1.0))
+}
+
+fn speed(tokens_per_second: f64) -> String {
+    if tokens_per_second < 1.0 {
+        "under 1 tok/s".to_string()
+    } else {
+        format!("~{:.0} tok/s", tokens_per_second)
+    }
+}
+
+// None when the model doesn't fit in RAM
+pub fn score(entry: &CatalogEntry, hardware: &Hardware) -> Option<Recommendation> {
+    if entry.min_ram_bytes > hardware.total_ram_bytes || entry.size_bytes == 0 {
+        return None;
+    }
+    let cpu = cpu_bandwidth(hardware.cpu_cores);
+    let (offload, gpu_fraction, gpu) = match (gpu_bandwidth(&hardware.gpu_backend), hardware.vram_bytes) {
+        (Some(gpu), Some(vram)) if vram >= entry.full_offload_vram_bytes => (Offload::Full, 1.0, gpu),
+        (Some(gpu), Some(vram)) if vram > 0 => {
+            (Offload::Partial, vram as f64 / entry.full_offload_vram_bytes as f64, gpu)
+        }
+        _ => (Offload::None, 0.0, cpu),
+    };
+    let seconds_per_token = entry.size_bytes as f64 * (gpu_fraction / gpu + (1.0 - gpu_fraction) / cpu);
+    let tokens_per_second = 1.0 / seconds_per_token;
+
+    let vram = gigabytes(hardware.vram_bytes.unwrap_or(0));
+    let explanation = match offload {
+        Offload::Full if hardware.unified_memory => {
+            format!("Fits fully in the {} GB of memory your GPU can use, expect {}", vram, speed(tokens_per_second))
+        }
+        Offload::Full => format!("Fits fully on your {} GB GPU, expect {}", vram, speed(tokens_per_second)),
+        Offload::Partial => format!(
+            "About {:.0}% fits on your {} GB GPU and the rest runs on the CPU, expect {}",
+            gpu_fraction * 100.0,
+            vram,
+            speed(tokens_per_second)
+        ),
+        Offload::None => format!("CPU-only, expect {}", speed(tokens_per_second)),
+    };
+    let speed_factor = (tokens_per_second / TARGET_TOKENS_PER_SECOND).min(1.0);
+    let bonus = if offload == Offload::Full { FULL_OFFLOAD_BONUS } else { 0.0 };
+    Some(Recommendation {
+        model: entry.clone(),
+        score: f64::from(entry.quality_tier) * 10.0 * speed_factor + bonus,
+        offload,
+        estimated_tokens_per_second: tokens_per_second,
+        explanation,
+    })
+}
+
+// Entries for `use_case`, best first; ties go to the smaller download
+pub fn recommend(catalog: &Catalog, hardware: &Hardware, use_case: &str) -> Result<Vec<Recommendation>, AppError> {
+    let entries: Vec<&CatalogEntry> =
+        catalog.models.iter().filter(|entry| entry.use_cases.iter().any(|u| u == use_case)).collect();
+    if entries.is_empty() {
+        let mut known: Vec<&str> = catalog.models.iter().flat_map(|e| e.use_cases.iter().map(String::as_str)).collect();
+        known.sort_unstable();
+        known.dedup();
+        return Err(AppError::invalid_input(format!(
+            "No catalog models for use case \"{}\" (known: {})",
+            use_case,
+            known.join(", ")
+        )));
+    }
+    let mut recommendations: Vec<Recommendation> = entries.into_iter().filter_map(|e| score(e, hardware)).collect();
+    recommendations.sort_by(|a, b| {
+        b.score.total_cmp(&a.score).then_with(|| a.model.size_bytes.cmp(&b.model.size_bytes))
+    });
+    Ok(recommendations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GIB: u64 = 1024 * 1024 * 1024;
+
+    fn catalog() -> Catalog {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("resources").join(CATALOG_FILE);
+        parse(&path).unwrap()
+    }
+
+    fn hardware(ram_gib: u64, cores: usize, backend: &str, vram_gib: Option<u64>) -> Hardware {
+        Hardware {
+            total_ram_bytes: ram_gib * GIB,
+            cpu_cores: cores,
+            gpu_backend: backend.into(),
+            vram_bytes: vram_gib.map(|gib| gib * GIB),
+            unified_memory: backend == "metal",
+        }
+    }
+
+    fn top(hardware: &Hardware) -> Recommendation {
+        recommend(&catalog(), hardware, DEFAULT_USE_CASE).unwrap().remove(0)
+    }
+
+    #[test]
+    fn cpu_only_machines_get_a_model_that_keeps_up() {
+        let best = top(&hardware(16, 8, "cpu", None));
+        assert_eq!(best.model.filename, "Llama-3.2-3B-Instruct-Q4_K_M.gguf");
+        assert_eq!(best.offload, Offload::None);
+        assert_eq!(best.explanation, "CPU-only, expect ~10 tok/s");
+
+        // Only the two smallest models fit in 4 GB
+        let small = recommend(&catalog(), &hardware(4, 4, "cpu", None), DEFAULT_USE_CASE).unwrap();
+        assert_eq!(small.len(), 2);
+        assert_eq!(small[0].model.filename, "Llama-3.2-1B-Instruct-Q4_K_M.gguf");
+    }
+
+    #[test]
+    fn gpus_get_the_best_model_that_fits_fully() {
+        let best = top(&hardware(32, 8, "cuda", Some(8)));
+        assert_eq!(best.model.filename, "Meta-Llama-3.1-8B-Instruct-Q6_K.gguf");
+        assert!(best.explanation.starts_with("Fits fully on your 8 GB GPU"), "{}", best.explanation);
+
+        let best = top(&hardware(32, 8, "cuda", Some(12)));
+        assert_eq!(best.model.filename, "Qwen2.5-14B-Instruct-Q4_K_M.gguf");
+
+        // A 24 GB Mac lets Metal use about 16 GB
+        let best = top(&Hardware { vram_bytes: Some(24 * GIB / 3 * 2), ..hardware(24, 8, "metal", None) });
+        assert_eq!(best.offload, Offload::Full);
+        assert!(best.explanation.contains("memory your GPU can use"), "{}", best.explanation);
+
+        let recommendations = recommend(&catalog(), &hardware(32, 8, "cuda", Some(4)), DEFAULT_USE_CASE).unwrap();
+        let partial = recommendations.iter().find(|r| r.model.filename.starts_with("Qwen2.5-14B")).unwrap();
+        assert_eq!(partial.offload, Offload::Partial);
+        assert!(partial.explanation.starts_with("About 36% fits on your 4 GB GPU"), "{}", partial.explanation);
+    }
+
+    #[test]
+    fn use_cases_filter_the_catalog() {
+        let vision = recommend(&catalog(), &hardware(16, 8, "cpu", None), "vision").unwrap();
+        assert!(vision.iter().all(|r| r.model.mmproj_filename.is_some()));
+        let err = recommend(&catalog(), &hardware(16, 8, "cpu", None), "audio").unwrap_err();
+        assert!(err.message.contains("known: text, vision"), "{}", err.message);
+    }
+}
@@ -0,0 +1,256 @@
+// Checks a move has to pass before anything is touched. move_file runs them for real
+// and simulated moves alike, so a dry run fails exactly where the real move would.
+// When the destination name is taken, resolve_destination decides between moving
+// anyway (and failing), numbering, skipping, or recognizing an identical copy; files
+// are only hashed on such a collision, so ordinary moves stay a rename.
+use crate::destination_policy;
+use crate::error::{AppError, AppErrorKind};
+use crate::file_tags;
+use crate::i18n;
+use crate::paths;
+use crate::rename::{self, OnConflict};
+use crate::volumes;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MoveOutcome {
+    pub from: String,
+    pub to: String,
+    // Set for dry runs: nothing was moved
+    pub simulated: bool,
+    pub action: MoveAction,
+    // Where a duplicate source went, for source_trashed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trashed_to: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MoveAction {
+    Moved,
+    // The name was taken by a different file; moved under a numbered name
+    KeptBoth,
+    // The name was taken by a different file and on_conflict was skip
+    Skipped,
+    // An identical copy is already at the destination (`to`); the source stayed put
+    SkippedDuplicate,
+    // An identical copy is already at the destination; the source went to the trash
+    SourceTrashed,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Resolution {
+    // Moved or KeptBoth
+    Move(PathBuf, MoveAction),
+    Skip,
+    // The identical file already there
+    Duplicate(PathBuf),
+}
+
+fn taken_by_another(from: &Path, path: &Path) -> bool {
+    fs::symlink_metadata(path).is_ok() && fs::canonicalize(path).ok() != fs::canonicalize(from).ok()
+}
+
+// What to do when moving `from` to `to`. With on_conflict error a taken name still
+// resolves to a move, so validate_move reports it as before.
+pub fn resolve_destination(
+    from: &Path,
+    to: &Path,
+    dedupe: bool,
+    on_conflict: OnConflict,
+) -> Result<Resolution, AppError> {
+    if !taken_by_another(from, to) {
+        return Ok(Resolution::Move(to.to_path_buf(), MoveAction::Moved));
+    }
+    let source = fs::symlink_metadata(from).map_err(|e| AppError::from(e).with_path(paths::display_path(from)))?;
+    if dedupe && source.is_file() {
+        if let Some(copy) = identical_copy(from, source.len(), to)? {
+            return Ok(Resolution::Duplicate(copy));
+        }
+    }
+    match on_conflict {
+        OnConflict::Error => Ok(Resolution::Move(to.to_path_buf(), MoveAction::Moved)),
+        OnConflict::Skip => Ok(Resolution::Skip),
+        OnConflict::Number => (2..=rename::MAX_NUMBERED)
+            .map(|n| rename::numbered(to, n))
+            .find(|candidate| !taken_by_another(from, candidate))
+            .map(|free| Resolution::Move(free, MoveAction::KeptBoth))
+            .ok_or_else(|| {
+                AppError::new(AppErrorKind::AlreadyExists, "No free numbered name is left")
+                    .with_path(paths::display_path(to))
+            }),
+    }
+}
+
+// `to` or an earlier numbered copy of it ("name (2).ext", ...) with the source's size
+// and hash. The source is hashed once, and only if some candidate has its size.
+fn identical_copy(from: &Path, size: u64, to: &Path) -> Result<Option<PathBuf>, AppError> {
+    let candidates = std::iter::once(to.to_path_buf())
+        .chain((2..=rename::MAX_NUMBERED).map(|n| rename::numbered(to, n)))
+        .map_while(|candidate| fs::metadata(&candidate).ok().map(|metadata| (candidate, metadata)));
+    let mut source_hash = None;
+    for (candidate, metadata) in candidates {
+        if !metadata.is_file() || metadata.len() != size {
+            continue;
+        }
+        if source_hash.is_none() {
+            let hash = crate::sha256_file(from).map_err(|e| AppError::io(e).with_path(paths::display_path(from)))?;
+            source_hash = Some(hash);
+        }
+        match crate::sha256_file(&candidate) {
+            Ok(hash) if Some(&hash) == source_hash.as_ref() => return Ok(Some(candidate)),
+            Ok(_) => {}
+            Err(e) => warn!("Not comparing with {}: {}", candidate.display(), e),
+        }
+    }
+    Ok(None)
+}
+
+// Moves a duplicate source into trash_dir (app_data_dir/trash/<batch_id>), numbering
+// it if that name is taken. The batch journal records the move, so undo_batch can reverse it.
+pub fn move_to_trash(from: &Path, trash_dir: &Path) -> Result<PathBuf, AppError> {
+    let name = from
+        .file_name()
+        .ok_or_else(|| AppError::invalid_input("Nothing to trash").with_path(paths::display_path(from)))?;
+    fs::create_dir_all(trash_dir).map_err(|e| AppError::from(e).with_path(paths::display_path(trash_dir)))?;
+    let named = trash_dir.join(name);
+    let target = std::iter::once(named.clone())
+        .chain((2..=rename::MAX_NUMBERED).map(|n| rename::numbered(&named, n)))
+        .find(|candidate| fs::symlink_metadata(candidate).is_err())
+        .ok_or_else(|| {
+            AppError::new(AppErrorKind::AlreadyExists, "No free name is left in the trash")
+                .with_path(paths::display_path(&named))
+        })?;
+    match fs::rename(from, &target) {
+        Ok(()) => {
+            file_tags::follow_move(from, &target);
+            Ok(target)
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::CrossesDevices => {
+            move_across_devices(from, &target)?;
+            Ok(target)
+        }
+        Err(e) => Err(AppError::from(e).with_path(paths::display_path(from))),
+    }
+}
+
+pub fn validate_move(from: &Path, to: &Path) -> Result<(), AppError> {
+    volumes::ensure_mounted(from)?;
+    volumes::ensure_mounted(to)?;
+    destination_policy::check(to)?;
+    fs::symlink_metadata(from).map_err(|e| AppError::from(e).with_path(paths::display_path(from)))?;
+
+    // Anything else already at the destination would be overwritten. A case-only change
+    // on a case-insensitive file system finds the source itself, which is fine.
+    if let Ok(existing) = fs::symlink_metadata(to) {
+        if fs::canonicalize(to).ok() != fs::canonicalize(from).ok() {
+            return Err(AppError::new(AppErrorKind::AlreadyExists, existing_file_message(&existing))
+                .with_path(paths::display_path(to)));
+        }
+    }
+
+    // Missing folders are created inside the nearest existing ancestor, which has to be
+    // a writable directory. Windows ignores the read-only attribute on directories.
+    if let Some((ancestor, metadata)) = to.ancestors().skip(1).find_map(|a| fs::metadata(a).ok().map(|m| (a, m))) {
+        if !metadata.is_dir() {
+            return Err(AppError::invalid_input(format!("{} is not a directory", paths::display_path(ancestor)))
+                .with_path(paths::display_path(ancestor)));
+        }
+        if cfg!(unix) && metadata.permissions().readonly() {
+            return Err(AppError::new(AppErrorKind::PermissionDenied, i18n::t("error.destination_read_only"))
+                .with_path(paths::display_path(ancestor)));
+        }
+    }
+    Ok(())
+}
+
+// Says how big and how old the file in the way is, so the user can tell which to keep
+fn existing_file_message(existing: &fs::Metadata) -> String {
+    match existing.modified() {
+        Ok(modified) if existing.is_file() => i18n::tf(
+            "error.destination_exists_details",
+            &[
+                ("size", &i18n::format_size(existing.len())),
+                ("modified", &i18n::format_date_time(modified.into())),
+            ],
+        ),
+        _ => i18n::t("error.destination_exists"),
+    }
+}
+
+// rename can't cross volumes, so a file is copied instead, with its tags (copies don't
+// bring extended attributes or streams along), and the original removed. If the
+// original can't be removed the copy is deleted again, leaving things as they were.
+pub fn move_across_devices(from: &Path, to: &Path) -> Result<(), AppError> {
+    fs::copy(from, to).map_err(|e| AppError::from(e).with_path(paths::display_path(to)))?;
+    if let Err(e) = file_tags::copy_tags(from, to) {
+        warn!("Tags of {} weren't copied: {}", from.display(), e.message);
+    }
+    if let Err(e) = fs::remove_file(from) {
+        let _ = fs::remove_file(to);
+        return Err(AppError::from(e).with_path(paths::display_path(from)));
+    }
+    file_tags::remove_sidecar(from);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_dir;
+
+    #[test]
+    fn moves_are_validated_without_touching_anything() {
+        let dir = temp_dir("validate");
+        fs::write(dir.join("a.txt"), b"a").unwrap();
+        fs::write(dir.join("taken.txt"), b"b").unwrap();
+        fs::write(dir.join("plain-file"), b"c").unwrap();
+
+        assert!(validate_move(&dir.join("a.txt"), &dir.join("new/folders/a.txt")).is_ok());
+        assert!(!dir.join("new").exists());
+
+        let err = validate_move(&dir.join("a.txt"), &dir.join("taken.txt")).unwrap_err();
+        assert_eq!(err.kind, AppErrorKind::AlreadyExists);
+        let err = validate_move(&dir.join("missing.txt"), &dir.join("b.txt")).unwrap_err();
+        assert_eq!(err.kind, AppErrorKind::NotFound);
+        let err = validate_move(&dir.join("a.txt"), &dir.join("plain-file/a.txt")).unwrap_err();
+        assert_eq!(err.kind, AppErrorKind::InvalidInput);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn identical_copies_at_the_destination_are_recognized() {
+        let dir = temp_dir("dedupe");
+        fs::create_dir_all(dir.join("out")).unwrap();
+        fs::write(dir.join("report.pdf"), b"same").unwrap();
+        fs::write(dir.join("other.pdf"), b"else").unwrap();
+        fs::write(dir.join("out/report.pdf"), b"diff").unwrap();
+        fs::write(dir.join("out/report (2).pdf"), b"same").unwrap();
+        let (source, to) = (dir.join("report.pdf"), dir.join("out/report.pdf"));
+
+        let resolve = |from: &Path, dedupe, on_conflict| resolve_destination(from, &to, dedupe, on_conflict).unwrap();
+        assert_eq!(resolve(&source, true, OnConflict::Error), Resolution::Duplicate(dir.join("out/report (2).pdf")));
+        assert_eq!(resolve(&source, false, OnConflict::Error), Resolution::Move(to.clone(), MoveAction::Moved));
+        assert_eq!(resolve(&source, false, OnConflict::Skip), Resolution::Skip);
+        // Same size, different bytes: not a duplicate, so the conflict policy applies
+        assert_eq!(
+            resolve(&dir.join("other.pdf"), true, OnConflict::Number),
+            Resolution::Move(dir.join("out/report (3).pdf"), MoveAction::KeptBoth)
+        );
+        let free = dir.join("out/new.pdf");
+        assert_eq!(
+            resolve_destination(&source, &free, true, OnConflict::Skip).unwrap(),
+            Resolution::Move(free, MoveAction::Moved)
+        );
+
+        let trash = dir.join("trash/batch-1");
+        fs::create_dir_all(&trash).unwrap();
+        fs::write(trash.join("report.pdf"), b"earlier").unwrap();
+        assert_eq!(move_to_trash(&source, &trash).unwrap(), trash.join("report (2).pdf"));
+        assert!(!source.exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
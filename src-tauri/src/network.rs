@@ -0,0 +1,221 @@
+// Outbound HTTP settings (proxy, custom CA) shared by every reqwest call in the app
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::{OnceLock, RwLock};
+use tracing::warn;
+
+const NETWORK_CONFIG_FILE: &str = "network_config.json";
+
+// Hosts that never go through an explicit proxy, so the managed server stays reachable
+const PROXY_BYPASS: &str = "localhost,127.0.0.1,::1";
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct NetworkConfig {
+    #[serde(default)]
+    pub proxy_url: Option<String>,
+    #[serde(default)]
+    pub proxy_username: Option<String>,
+    #[serde(default)]
+    pub proxy_password: Option<String>,
+    // PEM file with one or more extra root certificates, e.g. a TLS-intercepting proxy's CA
+    #[serde(default)]
+    pub ca_bundle_path: Option<String>,
+    // Honour HTTP(S)_PROXY / NO_PROXY and the OS proxy settings when no proxy_url is set
+    #[serde(default = "default_use_system_proxy")]
+    pub use_system_proxy: bool,
+}
+
+fn default_use_system_proxy() -> bool {
+    true
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        Self {
+            proxy_url: None,
+            proxy_username: None,
+            proxy_password: None,
+            ca_bundle_path: None,
+            use_system_proxy: default_use_system_proxy(),
+        }
+    }
+}
+
+struct ActiveNetwork {
+    config: NetworkConfig,
+    client: reqwest::Client,
+}
+
+static ACTIVE_NETWORK: OnceLock<RwLock<ActiveNetwork>> = OnceLock::new();
+
+fn active_network() -> &'static RwLock<ActiveNetwork> {
+    ACTIVE_NETWORK.get_or_init(|| {
+        let config = NetworkConfig::default();
+        let client = create_client(&config).unwrap_or_default();
+        RwLock::new(ActiveNetwork { config, client })
+    })
+}
+
+fn non_empty(value: &Option<String>) -> Option<&str> {
+    value.as_deref().map(str::trim).filter(|v| !v.is_empty())
+}
+
+fn create_client(config: &NetworkConfig) -> Result<reqwest::Client, String> {
+    let mut builder = reqwest::Client::builder();
+
+    if let Some(proxy_url) = non_empty(&config.proxy_url) {
+        let mut proxy = reqwest::Proxy::all(proxy_url)
+            .map_err(|e| format!("Invalid proxy URL '{}': {}", proxy_url, e))?
+            .no_proxy(reqwest::NoProxy::from_string(PROXY_BYPASS));
+        if let Some(username) = non_empty(&config.proxy_username) {
+            proxy = proxy.basic_auth(username, config.proxy_password.as_deref().unwrap_or_default());
+        }
+        builder = builder.proxy(proxy);
+    } else if !config.use_system_proxy {
+        builder = builder.no_proxy();
+    }
+
+    if let Some(ca_path) = non_empty(&config.ca_bundle_path) {
+        let pem = std::fs::read(ca_path)
+            .map_err(|e| format!("Failed to read CA bundle {}: {}", ca_path, e))?;
+        let certificates = reqwest::Certificate::from_pem_bundle(&pem)
+            .map_err(|e| format!("Invalid CA bundle {}: {}", ca_path, e))?;
+        if certificates.is_empty() {
+            return Err(format!("CA bundle {} contains no certificates", ca_path));
+        }
+        for certificate in certificates {
+            builder = builder.add_root_certificate(certificate);
+        }
+    }
+
+    builder.build().map_err(|e| format!("Failed to create HTTP client: {}", e))
+}
+
+// Returns a client configured with the current network settings. Clients share their
+// connection pool, so this is cheap to call per request.
+pub fn build_http_client() -> reqwest::Client {
+    active_network().read().unwrap().client.clone()
+}
+
+pub fn current_config() -> NetworkConfig {
+    active_network().read().unwrap().config.clone()
+}
+
+// Validates the config by building a client from it, then makes it the active one
+pub fn apply_config(config: NetworkConfig) -> Result<(), String> {
+    let client = create_client(&config)?;
+    *active_network().write().unwrap() = ActiveNetwork { config, client };
+    Ok(())
+}
+
+fn config_path(app_data_dir: &Path) -> PathBuf {
+    app_data_dir.join(NETWORK_CONFIG_FILE)
+}
+
+pub fn save_config(app_data_dir: &Path, config: &NetworkConfig) -> Result<(), String> {
+    std::fs::create_dir_all(app_data_dir)
+        .map_err(|e| format!("Failed to create app data directory: {}", e))?;
+    let json = serde_json::to_string_pretty(config)
+        .map_err(|e| format!("Failed to serialize network config: {}", e))?;
+    std::fs::write(config_path(app_data_dir), json)
+        .map_err(|e| format!("Failed to write network config: {}", e))
+}
+
+// Loads the saved config at startup. A broken config (e.g. a CA bundle that was
+// since deleted) is logged and the defaults are kept so the app still starts.
+pub fn load_saved_config(app_data_dir: &Path) {
+    let path = config_path(app_data_dir);
+    if !path.exists() {
+        return;
+    }
+    let config = match std::fs::read_to_string(&path)
+        .map_err(|e| e.to_string())
+        .and_then(|json| serde_json::from_str::<NetworkConfig>(&json).map_err(|e| e.to_string()))
+    {
+        Ok(config) => config,
+        Err(e) => {
+            warn!("Ignoring unreadable network config {}: {}", path.display(), e);
+            return;
+        }
+    };
+    if let Err(e) = apply_config(config) {
+        warn!("Failed to apply saved network config: {}", e);
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NetworkTestResult {
+    pub ok: bool,
+    pub status: Option<u16>,
+    // "ok", "http", "timeout", "dns", "tls", "proxy", "connect" or "other"
+    pub category: String,
+    pub error: Option<String>,
+    pub elapsed_ms: u64,
+}
+
+fn error_category(e: &reqwest::Error, details: &str) -> &'static str {
+    let lowered = details.to_lowercase();
+    if e.is_timeout() {
+        "timeout"
+    } else if lowered.contains("proxy") || lowered.contains("407") {
+        "proxy"
+    } else if lowered.contains("dns error") || lowered.contains("failed to lookup address") {
+        "dns"
+    } else if lowered.contains("certificate") || lowered.contains("tls") || lowered.contains("ssl") {
+        "tls"
+    } else if e.is_connect() {
+        "connect"
+    } else {
+        "other"
+    }
+}
+
+// Sends a HEAD request through the given config and reports how it failed, if it did
+pub async fn test_config(config: &NetworkConfig, url: &str) -> Result<NetworkTestResult, String> {
+    let client = create_client(config)?;
+    let started = std::time::Instant::now();
+    let result = client
+        .head(url)
+        .timeout(std::time::Duration::from_secs(15))
+        .send()
+        .await;
+    let elapsed_ms = started.elapsed().as_millis() as u64;
+
+    Ok(match result {
+        Ok(response) => {
+            let status = response.status();
+            // Anything below 400 proves the network path works; servers that reject HEAD still answered
+            let ok = status.as_u16() < 400 || status == reqwest::StatusCode::METHOD_NOT_ALLOWED;
+            let category = if status == reqwest::StatusCode::PROXY_AUTHENTICATION_REQUIRED {
+                "proxy"
+            } else if ok {
+                "ok"
+            } else {
+                "http"
+            };
+            NetworkTestResult {
+                ok,
+                status: Some(status.as_u16()),
+                category: category.to_string(),
+                error: if ok { None } else { Some(format!("HTTP {}", status)) },
+                elapsed_ms,
+            }
+        }
+        Err(e) => {
+            let mut details = e.to_string();
+            let mut source = std::error::Error::source(&e);
+            while let Some(inner) = source {
+                details.push_str(": ");
+                details.push_str(&inner.to_string());
+                source = inner.source();
+            }
+            NetworkTestResult {
+                ok: false,
+                status: None,
+                category: error_category(&e, &details).to_string(),
+                error: Some(details),
+                elapsed_ms,
+            }
+        }
+    })
+}
@@ -0,0 +1,337 @@
+// Checks DOCX and XLSX files before they reach the parsers, so the failures people
+// actually hit get errors they can act on instead of "invalid Zip archive": empty
+// files, downloads or syncs cut short (no zip central directory), and password
+// protected documents. Protected files are an OLE compound file holding an
+// EncryptionInfo and an EncryptedPackage stream; with a password, the agile scheme
+// Office 2010 and later use is decrypted here (MS-OFFCRYPTO 2.3.4.10-2.3.4.15). The
+// package's HMAC isn't checked: a wrong password is caught by the key verifier and a
+// damaged package still fails to parse.
+use crate::error::{AppError, AppErrorKind};
+use crate::i18n;
+use aes::cipher::{Block, BlockDecrypt, KeyInit};
+use base64::Engine;
+use sha2::Digest;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Cursor, Read};
+use std::path::Path;
+
+const OLE_MAGIC: [u8; 8] = [0xD0, 0xCF, 0x11, 0xE0, 0xA1, 0xB1, 0x1A, 0xE1];
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+const END_OF_CENTRAL_DIRECTORY: &[u8] = b"PK\x05\x06";
+// The end record is 22 bytes, followed by a comment of at most 64 KiB
+const END_RECORD_SEARCH: usize = 22 + u16::MAX as usize;
+
+const ENCRYPTED_PACKAGE: &str = "/EncryptedPackage";
+const ENCRYPTION_INFO: &str = "/EncryptionInfo";
+const SEGMENT_SIZE: usize = 4096;
+// The spec's upper bound; anything larger is a damaged or hostile file
+const MAX_SPIN_COUNT: u32 = 10_000_000;
+const VERIFIER_INPUT_BLOCK: [u8; 8] = [0xfe, 0xa7, 0xd2, 0x76, 0x3b, 0x4b, 0x9e, 0x79];
+const VERIFIER_VALUE_BLOCK: [u8; 8] = [0xd7, 0xaa, 0x0f, 0x6d, 0x30, 0x61, 0x34, 0x4e];
+const KEY_VALUE_BLOCK: [u8; 8] = [0x14, 0x6e, 0x0b, 0xe7, 0xab, 0xac, 0xd0, 0xd6];
+
+fn truncated() -> AppError {
+    AppError::new(AppErrorKind::InvalidData, i18n::t("error.office_truncated")).with_recoverable(true)
+}
+
+fn encrypted(key: &str, recoverable: bool) -> AppError {
+    AppError::new(AppErrorKind::Encrypted, i18n::t(key)).with_recoverable(recoverable)
+}
+
+fn has_central_directory(bytes: &[u8]) -> bool {
+    let tail = &bytes[bytes.len().saturating_sub(END_RECORD_SEARCH)..];
+    tail.windows(END_OF_CENTRAL_DIRECTORY.len()).any(|window| window == END_OF_CENTRAL_DIRECTORY)
+}
+
+// The zip package inside a DOCX or XLSX, decrypted with `password` when the file is
+// protected. Errors carry an is_recoverable hint; the caller adds the path.
+pub fn read_package(path: &Path, password: Option<&str>) -> Result<Vec<u8>, AppError> {
+    let bytes = fs::read(path).map_err(|e| AppError::from_io(&e, format!("Failed to read file: {}", e)))?;
+    if bytes.is_empty() {
+        return Err(AppError::new(AppErrorKind::InvalidData, i18n::t("error.office_empty")).with_recoverable(true));
+    }
+    if bytes.starts_with(&OLE_MAGIC) {
+        let package = open_protected(bytes, password, path)?;
+        return if package.starts_with(ZIP_MAGIC) && has_central_directory(&package) {
+            Ok(package)
+        } else {
+            Err(truncated())
+        };
+    }
+    // A download or sync that stopped early often leaves the file preallocated with zeros
+    let head = &bytes[..bytes.len().min(512)];
+    if (bytes.starts_with(ZIP_MAGIC) && !has_central_directory(&bytes)) || head.iter().all(|&b| b == 0) {
+        return Err(truncated());
+    }
+    Ok(bytes)
+}
+
+fn open_protected(bytes: Vec<u8>, password: Option<&str>, path: &Path) -> Result<Vec<u8>, AppError> {
+    let mut compound = cfb::CompoundFile::open(Cursor::new(bytes)).map_err(|_| truncated())?;
+    if !compound.is_stream(ENCRYPTED_PACKAGE) {
+        // A .doc or .xls renamed to the newer extension
+        let extension = path.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+        let message = i18n::tf("error.office_legacy_format", &[("extension", &extension)]);
+        return Err(AppError::new(AppErrorKind::InvalidData, message).with_recoverable(false));
+    }
+    let Some(password) = password else {
+        return Err(encrypted("error.office_password_protected", true));
+    };
+    let read_stream = |compound: &mut cfb::CompoundFile<Cursor<Vec<u8>>>, name: &str| {
+        let mut data = Vec::new();
+        compound.open_stream(name).and_then(|mut stream| stream.read_to_end(&mut data)).map_err(|_| truncated())?;
+        Ok::<_, AppError>(data)
+    };
+    let info = read_stream(&mut compound, ENCRYPTION_INFO)?;
+    let package = read_stream(&mut compound, ENCRYPTED_PACKAGE)?;
+    let keys = AgileInfo::parse(&info)?;
+    let key = keys.unlock(password)?;
+    keys.decrypt_package(&key, &package)
+}
+
+#[derive(Debug, Clone, Copy)]
+enum HashAlgorithm {
+    Sha1,
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+impl HashAlgorithm {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "SHA1" | "SHA-1" => Some(Self::Sha1),
+            "SHA256" => Some(Self::Sha256),
+            "SHA384" => Some(Self::Sha384),
+            "SHA512" => Some(Self::Sha512),
+            _ => None,
+        }
+    }
+
+    fn hash(self, parts: &[&[u8]]) -> Vec<u8> {
+        fn with<D: Digest>(parts: &[&[u8]]) -> Vec<u8> {
+            let mut digest = D::new();
+            parts.iter().for_each(|part| digest.update(part));
+            digest.finalize().to_vec()
+        }
+        match self {
+            Self::Sha1 => with::<sha1::Sha1>(parts),
+            Self::Sha256 => with::<sha2::Sha256>(parts),
+            Self::Sha384 => with::<sha2::Sha384>(parts),
+            Self::Sha512 => with::<sha2::Sha512>(parts),
+        }
+    }
+}
+
+// Cipher parameters shared by <keyData> and the password <encryptedKey>
+#[derive(Debug)]
+struct CipherParams {
+    salt: Vec<u8>,
+    block_size: usize,
+    key_bytes: usize,
+    hash: HashAlgorithm,
+}
+
+impl CipherParams {
+    fn parse(attributes: &HashMap<String, String>) -> Option<Self> {
+        let number = |name: &str| attributes.get(name)?.parse::<usize>().ok();
+        if attributes.get("cipherAlgorithm")? != "AES" || attributes.get("cipherChaining")? != "ChainingModeCBC" {
+            return None;
+        }
+        let key_bytes = number("keyBits")? / 8;
+        Some(Self {
+            salt: decode(attributes.get("saltValue")?)?,
+            block_size: number("blockSize").filter(|&size| size == 16)?,
+            key_bytes: Some(key_bytes).filter(|bytes| matches!(bytes, 16 | 24 | 32))?,
+            hash: HashAlgorithm::parse(attributes.get("hashAlgorithm")?)?,
+        })
+    }
+}
+
+#[derive(Debug)]
+struct AgileInfo {
+    key_data: CipherParams,
+    password: CipherParams,
+    spin_count: u32,
+    verifier_hash_input: Vec<u8>,
+    verifier_hash_value: Vec<u8>,
+    key_value: Vec<u8>,
+}
+
+fn decode(value: &str) -> Option<Vec<u8>> {
+    base64::engine::general_purpose::STANDARD.decode(value.trim()).ok()
+}
+
+// Attributes of the first element named `local_name`, ignoring namespace prefixes.
+// EncryptionInfo XML is small and flat, so this is all the parsing it needs.
+fn element_attributes(xml: &str, local_name: &str) -> Option<HashMap<String, String>> {
+    let mut rest = xml;
+    while let Some(open) = rest.find('<') {
+        rest = &rest[open + 1..];
+        let name_end = rest.find(|c: char| c.is_whitespace() || c == '/' || c == '>')?;
+        let name = &rest[..name_end];
+        if name.rsplit(':').next() != Some(local_name) {
+            continue;
+        }
+        let tag = &rest[name_end..rest.find('>')?];
+        let mut attributes = HashMap::new();
+        let mut attrs = tag;
+        while let Some(eq) = attrs.find("=\"") {
+            let name = attrs[..eq].trim().trim_start_matches('/').trim();
+            let value_start = eq + 2;
+            let value_end = value_start + attrs[value_start..].find('"')?;
+            attributes.insert(name.rsplit(':').next()?.to_string(), attrs[value_start..value_end].to_string());
+            attrs = &attrs[value_end + 1..];
+        }
+        return Some(attributes);
+    }
+    None
+}
+
+impl AgileInfo {
+    fn parse(info: &[u8]) -> Result<Self, AppError> {
+        if info.len() < 8 {
+            return Err(truncated());
+        }
+        let (major, minor) = (u16::from_le_bytes([info[0], info[1]]), u16::from_le_bytes([info[2], info[3]]));
+        if (major, minor) != (4, 4) {
+            // 2.2, 3.2 and 4.2 are the Office 2007 "standard" scheme; others are older still
+            return Err(encrypted("error.office_unsupported_encryption", false));
+        }
+        let xml = String::from_utf8_lossy(&info[8..]);
+        let unsupported = || encrypted("error.office_unsupported_encryption", false);
+        let key_data = element_attributes(&xml, "keyData").ok_or_else(unsupported)?;
+        let password = element_attributes(&xml, "encryptedKey").ok_or_else(unsupported)?;
+        let field = |name: &str| password.get(name).and_then(|value| decode(value)).ok_or_else(unsupported);
+        Ok(Self {
+            key_data: CipherParams::parse(&key_data).ok_or_else(unsupported)?,
+            password: CipherParams::parse(&password).ok_or_else(unsupported)?,
+            spin_count: password
+                .get("spinCount")
+                .and_then(|count| count.parse().ok())
+                .filter(|&count| count <= MAX_SPIN_COUNT)
+                .ok_or_else(unsupported)?,
+            verifier_hash_input: field("encryptedVerifierHashInput")?,
+            verifier_hash_value: field("encryptedVerifierHashValue")?,
+            key_value: field("encryptedKeyValue")?,
+        })
+    }
+
+    // The package key, once the password checks out against the verifier
+    fn unlock(&self, password: &str) -> Result<Vec<u8>, AppError> {
+        let params = &self.password;
+        let utf16: Vec<u8> = password.encode_utf16().flat_map(u16::to_le_bytes).collect();
+        let mut hash = params.hash.hash(&[&params.salt, &utf16]);
+        for i in 0..self.spin_count {
+            hash = params.hash.hash(&[&i.to_le_bytes(), &hash]);
+        }
+        let iv = sized(params.salt.clone(), params.block_size);
+        let decrypt_with = |block: &[u8], data: &[u8]| {
+            let key = sized(params.hash.hash(&[&hash, block]), params.key_bytes);
+            aes_cbc_decrypt(&key, &iv, data)
+        };
+        let input = decrypt_with(&VERIFIER_INPUT_BLOCK, &self.verifier_hash_input)?;
+        let expected = decrypt_with(&VERIFIER_VALUE_BLOCK, &self.verifier_hash_value)?;
+        let actual = params.hash.hash(&[&input[..params.salt.len().min(input.len())]]);
+        if expected.get(..actual.len()) != Some(&actual[..]) {
+            return Err(encrypted("error.office_wrong_password", true));
+        }
+        let mut key = decrypt_with(&KEY_VALUE_BLOCK, &self.key_value)?;
+        key.truncate(self.key_data.key_bytes);
+        Ok(key)
+    }
+
+    // The stream is the package size followed by 4 KiB segments, each encrypted with
+    // an IV derived from the key salt and the segment's index
+    fn decrypt_package(&self, key: &[u8], stream: &[u8]) -> Result<Vec<u8>, AppError> {
+        let (size, data) = stream.split_first_chunk::<8>().ok_or_else(truncated)?;
+        let size = usize::try_from(u64::from_le_bytes(*size)).map_err(|_| truncated())?;
+        let params = &self.key_data;
+        let mut package = Vec::with_capacity(data.len());
+        for (index, segment) in data.chunks(SEGMENT_SIZE).enumerate() {
+            let iv = sized(params.hash.hash(&[&params.salt, &(index as u32).to_le_bytes()]), params.block_size);
+            package.extend(aes_cbc_decrypt(key, &iv, segment)?);
+        }
+        if package.len() < size {
+            return Err(truncated());
+        }
+        package.truncate(size);
+        Ok(package)
+    }
+}
+
+// Truncated, or padded with 0x36 as the spec requires
+fn sized(mut bytes: Vec<u8>, len: usize) -> Vec<u8> {
+    bytes.resize(len, 0x36);
+    bytes
+}
+
+fn aes_cbc_decrypt(key: &[u8], iv: &[u8], data: &[u8]) -> Result<Vec<u8>, AppError> {
+    fn with<C: BlockDecrypt + KeyInit>(key: &[u8], iv: &[u8], data: &[u8]) -> Vec<u8> {
+        let cipher = C::new_from_slice(key).expect("key length checked when parsing");
+        let mut previous = iv.to_vec();
+        let mut plain = Vec::with_capacity(data.len());
+        for chunk in data.chunks_exact(16) {
+            let mut block = Block::<C>::default();
+            block.copy_from_slice(chunk);
+            cipher.decrypt_block(&mut block);
+            plain.extend(block.iter().zip(&previous).map(|(b, p)| b ^ p));
+            previous = chunk.to_vec();
+        }
+        plain
+    }
+    if !data.len().is_multiple_of(16) {
+        return Err(truncated());
+    }
+    Ok(match key.len() {
+        16 => with::<aes::Aes128>(key, iv, data),
+        24 => with::<aes::Aes192>(key, iv, data),
+        _ => with::<aes::Aes256>(key, iv, data),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/office").join(name)
+    }
+
+    #[test]
+    fn damaged_files_get_recoverable_errors() {
+        let empty = read_package(&fixture("empty.xlsx"), None).unwrap_err();
+        assert_eq!((empty.kind, empty.is_recoverable), (AppErrorKind::InvalidData, Some(true)));
+        assert_eq!(empty.message, i18n::t("error.office_empty"));
+
+        let cut = read_package(&fixture("truncated.docx"), None).unwrap_err();
+        assert_eq!((cut.kind, cut.is_recoverable), (AppErrorKind::InvalidData, Some(true)));
+        assert_eq!(cut.message, i18n::t("error.office_truncated"));
+
+        assert!(read_package(&fixture("plain.docx"), None).is_ok());
+    }
+
+    #[test]
+    fn protected_files_need_the_right_password() {
+        let locked = read_package(&fixture("protected.docx"), None).unwrap_err();
+        assert_eq!((locked.kind, locked.is_recoverable), (AppErrorKind::Encrypted, Some(true)));
+        assert_eq!(locked.message, i18n::t("error.office_password_protected"));
+
+        let wrong = read_package(&fixture("protected.docx"), Some("guess")).unwrap_err();
+        assert_eq!(wrong.message, i18n::t("error.office_wrong_password"));
+
+        let package = read_package(&fixture("protected.docx"), Some("fixture-password")).unwrap();
+        assert_eq!(package, fs::read(fixture("plain.docx")).unwrap());
+    }
+
+    #[test]
+    fn encryption_info_attributes_are_read_without_prefixes() {
+        let xml = r#"<encryption><p:encryptedKey spinCount="5" p:saltValue="AAE="/><keyData blockSize="16"/>"#;
+        let key = element_attributes(xml, "encryptedKey").unwrap();
+        assert_eq!((key["spinCount"].as_str(), key["saltValue"].as_str()), ("5", "AAE="));
+        assert_eq!(element_attributes(xml, "keyData").unwrap()["blockSize"], "16");
+        assert!(element_attributes(xml, "dataIntegrity").is_none());
+    }
+}
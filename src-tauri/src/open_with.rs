@@ -0,0 +1,537 @@
+// Opening files from the review list. Besides the system default, a file can be opened
+// with any application registered for its type (Launch Services on macOS, the
+// registry's OpenWithList and assoc/ftype on Windows, .desktop MimeType entries on
+// Linux). Plain open refuses file types that would run as programs and reveals them
+// in the file manager instead, unless the caller insists.
+use crate::error::AppError;
+use serde::Serialize;
+use std::path::Path;
+use std::process::Command;
+
+pub const DEFAULT_DANGEROUS_EXTENSIONS: &[&str] = &[
+    "exe", "com", "bat", "cmd", "msi", "scr", "pif", "ps1", "vbs", "vbe", "js", "jse", "wsf", "hta", "lnk", "reg",
+    "sh", "bash", "zsh", "csh", "command", "tool", "app", "pkg", "jar", "run", "bin", "appimage", "desktop",
+];
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Application {
+    // Passed back to open_file_with: an .app bundle path on macOS, an executable name
+    // or path on Windows, a desktop file id on Linux
+    pub id: String,
+    pub name: String,
+    pub is_default: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OpenOutcome {
+    Opened,
+    // The file type runs as a program, so it was shown in the file manager instead
+    Revealed,
+}
+
+pub fn is_dangerous(path: &Path, extensions: &[String]) -> bool {
+    path.extension().map(|ext| ext.to_string_lossy()).is_some_and(|ext| {
+        extensions.iter().any(|dangerous| dangerous.trim_start_matches('.').eq_ignore_ascii_case(&ext))
+    })
+}
+
+fn open_error(e: std::io::Error, path: &Path) -> AppError {
+    AppError::from_io(&e, format!("Failed to open file: {}", e)).with_path(crate::paths::display_path(path))
+}
+
+fn spawn(command: &mut Command, path: &Path) -> Result<(), AppError> {
+    command.spawn().map(|_| ()).map_err(|e| open_error(e, path))
+}
+
+// With the system's default application
+pub fn open_default(path: &Path) -> Result<(), AppError> {
+    #[cfg(target_os = "macos")]
+    let opened = Command::new("open").arg(path).spawn().map(|_| ());
+    #[cfg(windows)]
+    let opened = platform::shell_execute(path.as_os_str(), None);
+    #[cfg(not(any(target_os = "macos", windows)))]
+    let opened = Command::new("xdg-open").arg(path).spawn().map(|_| ());
+    opened.map_err(|e| open_error(e, path))
+}
+
+// Shows the file selected in Finder or Explorer; Linux file managers get its folder
+pub fn reveal(path: &Path) -> Result<(), AppError> {
+    #[cfg(target_os = "macos")]
+    let mut command = {
+        let mut command = Command::new("open");
+        command.arg("-R").arg(path);
+        command
+    };
+    #[cfg(windows)]
+    let mut command = {
+        let mut command = Command::new("explorer");
+        command.arg(format!("/select,{}", path.display()));
+        command
+    };
+    #[cfg(not(any(target_os = "macos", windows)))]
+    let mut command = {
+        let mut command = Command::new("xdg-open");
+        command.arg(path.parent().unwrap_or(Path::new("/")));
+        command
+    };
+    spawn(&mut command, path)
+}
+
+// Only applications listed for the file are accepted, so an id can't name an
+// arbitrary program
+pub fn open_with(path: &Path, app_id: &str) -> Result<(), AppError> {
+    let known = applications_for(path)?.into_iter().any(|app| app.id == app_id);
+    if !known {
+        return Err(AppError::invalid_input(format!("{} is not registered to open this file", app_id))
+            .with_path(crate::paths::display_path(path)));
+    }
+    platform::open_with(path, app_id)
+}
+
+// Default application first, then the rest by name
+pub fn applications_for(path: &Path) -> Result<Vec<Application>, AppError> {
+    let mut apps = platform::applications_for(path)?;
+    apps.sort_by(|a, b| {
+        b.is_default.cmp(&a.is_default).then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase()))
+    });
+    let mut seen = std::collections::HashSet::new();
+    apps.retain(|app| seen.insert(app.id.to_lowercase()));
+    Ok(apps)
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use super::Application;
+    use crate::error::AppError;
+    use core_foundation::array::{CFArray, CFArrayRef};
+    use core_foundation::base::TCFType;
+    use core_foundation::url::{CFURLRef, CFURL};
+    use std::path::Path;
+    use std::process::Command;
+
+    const LS_ROLES_ALL: u32 = 0xFFFF_FFFF;
+
+    #[link(name = "CoreServices", kind = "framework")]
+    extern "C" {
+        fn LSCopyApplicationURLsForURL(url: CFURLRef, roles: u32) -> CFArrayRef;
+        fn LSCopyDefaultApplicationURLForURL(
+            url: CFURLRef,
+            roles: u32,
+            error: *mut *const std::ffi::c_void,
+        ) -> CFURLRef;
+    }
+
+    pub fn applications_for(path: &Path) -> Result<Vec<Application>, AppError> {
+        let url = CFURL::from_path(path, false)
+            .ok_or_else(|| AppError::invalid_input("Not a file path").with_path(crate::paths::display_path(path)))?;
+        // Both follow the Create rule: the returned objects are ours to release
+        let default = unsafe {
+            let app = LSCopyDefaultApplicationURLForURL(url.as_concrete_TypeRef(), LS_ROLES_ALL, std::ptr::null_mut());
+            (!app.is_null()).then(|| CFURL::wrap_under_create_rule(app)).and_then(|app| app.to_path())
+        };
+        let urls = unsafe {
+            let urls = LSCopyApplicationURLsForURL(url.as_concrete_TypeRef(), LS_ROLES_ALL);
+            if urls.is_null() {
+                return Ok(Vec::new());
+            }
+            CFArray::<CFURL>::wrap_under_create_rule(urls)
+        };
+        Ok(urls
+            .iter()
+            .filter_map(|app| app.to_path())
+            .map(|app| Application {
+                name: app.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default(),
+                is_default: default.as_ref() == Some(&app),
+                id: app.to_string_lossy().into_owned(),
+            })
+            .collect())
+    }
+
+    pub fn open_with(path: &Path, app_id: &str) -> Result<(), AppError> {
+        super::spawn(Command::new("open").arg("-a").arg(app_id).arg(path), path)
+    }
+}
+
+#[cfg(any(windows, test))]
+mod registry {
+    // "  MRUList    REG_SZ    ab" / "  a    REG_SZ    notepad.exe" → the executables
+    pub fn open_with_list_values(output: &str) -> Vec<String> {
+        output
+            .lines()
+            .filter_map(|line| {
+                let mut parts = line.trim().splitn(3, "    ");
+                let (name, kind, data) = (parts.next()?, parts.next()?, parts.next()?);
+                (kind.trim() == "REG_SZ" && !name.eq_ignore_ascii_case("MRUList")).then(|| data.trim().to_string())
+            })
+            .filter(|data| data.to_ascii_lowercase().ends_with(".exe"))
+            .collect()
+    }
+
+    // "HKEY_CLASSES_ROOT\.txt\OpenWithList\notepad.exe" → "notepad.exe"
+    pub fn open_with_list_subkeys(output: &str) -> Vec<String> {
+        output
+            .lines()
+            .map(str::trim)
+            .filter(|line| line.to_ascii_uppercase().starts_with("HKEY_"))
+            .filter_map(|line| line.rsplit_once('\\').map(|(parent, name)| (parent, name.to_string())))
+            .filter(|(parent, _)| parent.to_ascii_lowercase().ends_with("openwithlist"))
+            .map(|(_, name)| name)
+            .collect()
+    }
+
+    // "txtfile=%SystemRoot%\system32\NOTEPAD.EXE %1" → the executable
+    pub fn ftype_executable(output: &str) -> Option<String> {
+        let command = output.lines().next()?.split_once('=')?.1.trim();
+        let executable = match command.strip_prefix('"') {
+            Some(quoted) => quoted.split('"').next()?,
+            None => command.split_whitespace().next()?,
+        };
+        (!executable.is_empty()).then(|| executable.to_string())
+    }
+
+    // ".txt=txtfile" → "txtfile"
+    pub fn assoc_prog_id(output: &str) -> Option<String> {
+        let prog_id = output.lines().next()?.split_once('=')?.1.trim();
+        (!prog_id.is_empty()).then(|| prog_id.to_string())
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::{registry, Application};
+    use crate::error::AppError;
+    use std::ffi::OsStr;
+    use std::path::Path;
+    use std::process::Command;
+
+    fn run(program: &str, args: &[&str]) -> Option<String> {
+        let output = Command::new(program).args(args).output().ok()?;
+        output.status.success().then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    // Extensions and ProgIDs go on a cmd.exe command line, where & or | would start
+    // another command
+    fn is_plain_name(name: &str) -> bool {
+        !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'))
+    }
+
+    // ShellExecuteW rather than `cmd /C start`: cmd.exe would treat & and | in a file
+    // name as command separators and run whatever follows
+    pub fn shell_execute(file: &OsStr, parameters: Option<&OsStr>) -> std::io::Result<()> {
+        use std::os::windows::ffi::OsStrExt;
+        use windows_sys::Win32::UI::Shell::ShellExecuteW;
+        use windows_sys::Win32::UI::WindowsAndMessaging::SW_SHOWNORMAL;
+
+        let wide = |text: &OsStr| text.encode_wide().chain(std::iter::once(0)).collect::<Vec<u16>>();
+        let operation = wide(OsStr::new("open"));
+        let file = wide(file);
+        let parameters = parameters.map(wide);
+        let result = unsafe {
+            ShellExecuteW(
+                std::ptr::null_mut(),
+                operation.as_ptr(),
+                file.as_ptr(),
+                parameters.as_ref().map_or(std::ptr::null(), |p| p.as_ptr()),
+                std::ptr::null(),
+                SW_SHOWNORMAL,
+            )
+        } as isize;
+        // Values up to 32 are errors; the low ones are the usual Win32 codes
+        match result {
+            code if code > 32 => Ok(()),
+            code @ (2 | 3 | 5 | 8 | 11) => Err(std::io::Error::from_raw_os_error(code as i32)),
+            31 => Err(std::io::Error::other("No application is associated with this file type")),
+            code => Err(std::io::Error::other(format!("The shell couldn't open the file (error {})", code))),
+        }
+    }
+
+    fn expand_env(value: &str) -> String {
+        let mut expanded = value.to_string();
+        for (name, value) in std::env::vars() {
+            let pattern = format!("%{}%", name);
+            if let Some(start) = expanded.to_ascii_lowercase().find(&pattern.to_ascii_lowercase()) {
+                expanded.replace_range(start..start + pattern.len(), &value);
+            }
+        }
+        expanded
+    }
+
+    fn application(executable: &str, is_default: bool) -> Application {
+        let name = Path::new(executable).file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+        Application { id: executable.to_string(), name, is_default }
+    }
+
+    pub fn applications_for(path: &Path) -> Result<Vec<Application>, AppError> {
+        let Some(ext) = path.extension().map(|ext| format!(".{}", ext.to_string_lossy())) else {
+            return Ok(Vec::new());
+        };
+        if !is_plain_name(&ext) {
+            return Ok(Vec::new());
+        }
+        let mut apps = Vec::new();
+        let default = run("cmd", &["/C", "assoc", &ext])
+            .and_then(|output| registry::assoc_prog_id(&output))
+            .filter(|prog_id| is_plain_name(prog_id))
+            .and_then(|prog_id| run("cmd", &["/C", "ftype", &prog_id]))
+            .and_then(|output| registry::ftype_executable(&output))
+            .map(|executable| expand_env(&executable));
+        if let Some(default) = &default {
+            apps.push(application(default, true));
+        }
+        let user_key =
+            format!(r"HKCU\Software\Microsoft\Windows\CurrentVersion\Explorer\FileExts\{}\OpenWithList", ext);
+        if let Some(output) = run("reg", &["query", &user_key]) {
+            apps.extend(registry::open_with_list_values(&output).iter().map(|exe| application(exe, false)));
+        }
+        if let Some(output) = run("reg", &["query", &format!(r"HKCR\{}\OpenWithList", ext)]) {
+            apps.extend(registry::open_with_list_subkeys(&output).iter().map(|exe| application(exe, false)));
+        }
+        Ok(apps)
+    }
+
+    pub fn open_with(path: &Path, app_id: &str) -> Result<(), AppError> {
+        // Windows paths can't contain quotes, so quoting is all the escaping it needs
+        let mut parameters = std::ffi::OsString::from("\"");
+        parameters.push(path.as_os_str());
+        parameters.push("\"");
+        shell_execute(OsStr::new(app_id), Some(&parameters)).map_err(|e| super::open_error(e, path))
+    }
+}
+
+#[cfg(any(not(any(target_os = "macos", windows)), test))]
+mod desktop_entries {
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct DesktopEntry {
+        pub name: String,
+        pub exec: String,
+        pub mime_types: Vec<String>,
+    }
+
+    // The [Desktop Entry] group of a .desktop file; None for hidden entries and ones
+    // that aren't applications
+    pub fn parse(text: &str) -> Option<DesktopEntry> {
+        let mut in_entry = false;
+        let (mut name, mut exec, mut mime_types) = (None, None, Vec::new());
+        for line in text.lines().map(str::trim) {
+            if line.starts_with('[') {
+                in_entry = line == "[Desktop Entry]";
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=').filter(|_| in_entry) else { continue };
+            match key.trim() {
+                "Type" if value.trim() != "Application" => return None,
+                "Hidden" | "NoDisplay" if value.trim() == "true" => return None,
+                "Name" => name = Some(value.trim().to_string()),
+                "Exec" => exec = Some(value.trim().to_string()),
+                "MimeType" => mime_types = value.split(';').filter(|m| !m.is_empty()).map(str::to_string).collect(),
+                _ => {}
+            }
+        }
+        Some(DesktopEntry { name: name?, exec: exec?, mime_types })
+    }
+
+    // Whether an entry for `listed` can open a file of `mime`: the same type, or any
+    // text format for editors that take text/plain
+    pub fn handles(listed: &str, mime: &str) -> bool {
+        const TEXT_APPLICATION_TYPES: &[&str] = &[
+            "application/x-shellscript",
+            "application/json",
+            "application/xml",
+            "application/javascript",
+            "application/x-perl",
+            "application/x-python",
+            "application/x-desktop",
+            "application/toml",
+        ];
+        listed == mime
+            || (listed == "text/plain" && (mime.starts_with("text/") || TEXT_APPLICATION_TYPES.contains(&mime)))
+    }
+
+    // Exec split into arguments (double quotes group, backslash escapes inside them)
+    // with %f/%F/%u/%U replaced by the file and other field codes dropped. The file
+    // is appended when Exec has no place for it.
+    pub fn command_line(exec: &str, file: &str) -> Vec<String> {
+        let mut args = Vec::new();
+        let mut current = String::new();
+        let (mut quoted, mut escaped, mut started) = (false, false, false);
+        for c in exec.chars() {
+            match c {
+                _ if escaped => {
+                    current.push(c);
+                    escaped = false;
+                }
+                '\\' if quoted => escaped = true,
+                '"' => {
+                    quoted = !quoted;
+                    started = true;
+                }
+                c if c.is_whitespace() && !quoted => {
+                    if started || !current.is_empty() {
+                        args.push(std::mem::take(&mut current));
+                    }
+                    started = false;
+                }
+                c => current.push(c),
+            }
+        }
+        if started || !current.is_empty() {
+            args.push(current);
+        }
+
+        let mut placed = false;
+        let mut expanded: Vec<String> = args
+            .into_iter()
+            .filter_map(|arg| match arg.as_str() {
+                "%f" | "%F" | "%u" | "%U" => {
+                    placed = true;
+                    Some(file.to_string())
+                }
+                "%i" | "%c" | "%k" => None,
+                _ => Some(arg.replace("%%", "\u{0}").replace('%', "").replace('\u{0}', "%")),
+            })
+            .filter(|arg| !arg.is_empty())
+            .collect();
+        if !placed {
+            expanded.push(file.to_string());
+        }
+        expanded
+    }
+}
+
+#[cfg(not(any(target_os = "macos", windows)))]
+mod platform {
+    use super::desktop_entries::{self, DesktopEntry};
+    use super::Application;
+    use crate::error::AppError;
+    use std::path::{Path, PathBuf};
+    use std::process::Command;
+
+    fn query(args: &[&str]) -> Option<String> {
+        let output = Command::new("xdg-mime").args(args).output().ok()?;
+        let text = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        (output.status.success() && !text.is_empty()).then_some(text)
+    }
+
+    // XDG data directories, most specific first, each with its applications folder
+    fn application_dirs() -> Vec<PathBuf> {
+        let home = dirs::home_dir().unwrap_or_default();
+        let data_home = std::env::var_os("XDG_DATA_HOME").map(PathBuf::from).unwrap_or(home.join(".local/share"));
+        let data_dirs = std::env::var("XDG_DATA_DIRS").unwrap_or_else(|_| "/usr/local/share:/usr/share".into());
+        std::iter::once(data_home)
+            .chain(std::env::split_paths(&data_dirs))
+            .chain([home.join(".local/share/flatpak/exports/share"), "/var/lib/flatpak/exports/share".into()])
+            .map(|dir| dir.join("applications"))
+            .collect()
+    }
+
+    // Desktop file ids ("org.gnome.TextEditor.desktop", subfolders joined with '-');
+    // an id found in an earlier directory shadows later ones
+    fn entries() -> Vec<(String, DesktopEntry)> {
+        let mut seen = std::collections::HashSet::new();
+        let mut entries = Vec::new();
+        for dir in application_dirs() {
+            for entry in walkdir::WalkDir::new(&dir).into_iter().filter_map(Result::ok) {
+                let path = entry.path();
+                if path.extension().is_none_or(|ext| ext != "desktop") {
+                    continue;
+                }
+                let Ok(relative) = path.strip_prefix(&dir) else { continue };
+                let id = relative.to_string_lossy().replace('/', "-");
+                if !seen.insert(id.clone()) {
+                    continue;
+                }
+                if let Some(parsed) = std::fs::read_to_string(path).ok().and_then(|text| desktop_entries::parse(&text))
+                {
+                    entries.push((id, parsed));
+                }
+            }
+        }
+        entries
+    }
+
+    fn mime_type(path: &Path) -> Result<String, AppError> {
+        query(&["query", "filetype", &path.to_string_lossy()]).ok_or_else(|| {
+            AppError::unsupported("Couldn't tell the file's type (is xdg-utils installed?)")
+                .with_path(crate::paths::display_path(path))
+        })
+    }
+
+    pub fn applications_for(path: &Path) -> Result<Vec<Application>, AppError> {
+        let mime = mime_type(path)?;
+        let default = query(&["query", "default", &mime]);
+        Ok(entries()
+            .into_iter()
+            .filter(|(_, entry)| entry.mime_types.iter().any(|listed| desktop_entries::handles(listed, &mime)))
+            .map(|(id, entry)| Application {
+                is_default: default.as_deref() == Some(id.as_str()),
+                name: entry.name,
+                id,
+            })
+            .collect())
+    }
+
+    pub fn open_with(path: &Path, app_id: &str) -> Result<(), AppError> {
+        let (_, entry) = entries()
+            .into_iter()
+            .find(|(id, _)| id == app_id)
+            .ok_or_else(|| AppError::not_found(format!("No application {}", app_id)))?;
+        let args = desktop_entries::command_line(&entry.exec, &path.to_string_lossy());
+        let (program, rest) = args.split_first().ok_or_else(|| AppError::invalid_input("Empty Exec line"))?;
+        super::spawn(Command::new(program).args(rest), path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn executable_types_are_recognized_case_insensitively() {
+        let list: Vec<String> = DEFAULT_DANGEROUS_EXTENSIONS.iter().map(|ext| ext.to_string()).collect();
+        assert!(is_dangerous(Path::new("/tmp/setup.EXE"), &list));
+        assert!(is_dangerous(Path::new("/tmp/install.command"), &list));
+        assert!(!is_dangerous(Path::new("/tmp/report.pdf"), &list));
+        assert!(!is_dangerous(Path::new("/tmp/Makefile"), &list));
+        assert!(is_dangerous(Path::new("/tmp/run.py"), &[".py".to_string()]));
+    }
+
+    #[test]
+    fn desktop_entries_are_matched_and_launched() {
+        let entry = desktop_entries::parse(
+            "[Desktop Entry]\nType=Application\nName=Text Editor\nExec=\"/opt/My Editor/edit\" --new-window %U\n\
+             MimeType=text/plain;application/x-zerosize;\n\n[Desktop Action new]\nName=New Window\nExec=edit\n",
+        )
+        .unwrap();
+        assert_eq!(entry.name, "Text Editor");
+        assert!(desktop_entries::handles(&entry.mime_types[0], "application/x-shellscript"));
+        assert!(!desktop_entries::handles(&entry.mime_types[0], "image/png"));
+        assert_eq!(
+            desktop_entries::command_line(&entry.exec, "/tmp/run me.sh"),
+            ["/opt/My Editor/edit", "--new-window", "/tmp/run me.sh"]
+        );
+        assert_eq!(desktop_entries::command_line("viewer %i -x", "/a.png"), ["viewer", "-x", "/a.png"]);
+        let hidden = "[Desktop Entry]\nType=Application\nName=X\nExec=x\nNoDisplay=true\n";
+        assert!(desktop_entries::parse(hidden).is_none());
+    }
+
+    #[test]
+    fn windows_registry_output_is_parsed() {
+        let values =
+            "\r\nHKEY_CURRENT_USER\\Software\\...\\.txt\\OpenWithList\r\n    a    REG_SZ    notepad.exe\r\n    \
+                      b    REG_SZ    Code.exe\r\n    MRUList    REG_SZ    ba\r\n";
+        assert_eq!(registry::open_with_list_values(values), ["notepad.exe", "Code.exe"]);
+        let keys = "\r\nHKEY_CLASSES_ROOT\\.txt\\OpenWithList\\notepad.exe\r\n\
+                    HKEY_CLASSES_ROOT\\.txt\\OpenWithList\\write.exe\r\n";
+        assert_eq!(registry::open_with_list_subkeys(keys), ["notepad.exe", "write.exe"]);
+        assert_eq!(registry::assoc_prog_id(".txt=txtfile\r\n").as_deref(), Some("txtfile"));
+        assert_eq!(
+            registry::ftype_executable("txtfile=%SystemRoot%\\system32\\NOTEPAD.EXE %1").as_deref(),
+            Some("%SystemRoot%\\system32\\NOTEPAD.EXE")
+        );
+        assert_eq!(
+            registry::ftype_executable("Code=\"C:\\Program Files\\Code\\Code.exe\" \"%1\"").as_deref(),
+            Some("C:\\Program Files\\Code\\Code.exe")
+        );
+    }
+}
@@ -0,0 +1,134 @@
+// Cancellation for long-running work the frontend starts under an operation_id: batch
+// moves and renames, analysis scans and queued extractions. Cancelling only marks the
+// operation; the work checks its token before each file and stops cleanly, so whatever
+// finished before the check stays done.
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::Notify;
+
+#[derive(Default)]
+struct TokenState {
+    cancelled: AtomicBool,
+    notify: Notify,
+    completed: AtomicUsize,
+    confirmed: AtomicBool,
+}
+
+#[derive(Clone, Default)]
+pub struct CancelToken {
+    state: Arc<TokenState>,
+}
+
+impl CancelToken {
+    pub fn is_cancelled(&self) -> bool {
+        self.state.cancelled.load(Ordering::SeqCst)
+    }
+
+    pub fn cancel(&self) {
+        self.state.cancelled.store(true, Ordering::SeqCst);
+        self.state.notify.notify_waiters();
+    }
+
+    // Resolves once the operation is cancelled
+    pub async fn cancelled(&self) {
+        loop {
+            let notified = self.state.notify.notified();
+            if self.is_cancelled() {
+                return;
+            }
+            notified.await;
+        }
+    }
+
+    pub fn record_completed(&self) {
+        self.state.completed.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub fn completed(&self) -> usize {
+        self.state.completed.load(Ordering::SeqCst)
+    }
+
+    // True only for the first caller after a cancel, so the work that actually stopped
+    // sends the confirmation exactly once
+    pub fn take_confirmation(&self) -> bool {
+        self.is_cancelled() && !self.state.confirmed.swap(true, Ordering::SeqCst)
+    }
+}
+
+// Sent as "operation-cancelled" once the work has stopped. skipped is only known to
+// batch commands that were given the whole list.
+#[derive(Debug, Clone, Serialize)]
+pub struct OperationCancelled {
+    pub operation_id: String,
+    pub completed: usize,
+    pub skipped: Option<usize>,
+}
+
+#[derive(Default)]
+pub struct Operations {
+    tokens: Mutex<HashMap<String, CancelToken>>,
+}
+
+impl Operations {
+    // The token for `operation_id`, registering the operation on first use so per-file
+    // commands called in a loop share one token
+    pub fn token(&self, operation_id: &str) -> CancelToken {
+        self.tokens.lock().unwrap().entry(operation_id.to_string()).or_default().clone()
+    }
+
+    // Whether the operation was running
+    pub fn cancel(&self, operation_id: &str) -> bool {
+        match self.tokens.lock().unwrap().get(operation_id) {
+            Some(token) => {
+                token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn finish(&self, operation_id: &str) {
+        self.tokens.lock().unwrap().remove(operation_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn cancelling_reaches_every_holder_and_confirms_once() {
+        let operations = Operations::default();
+        assert!(!operations.cancel("unknown"));
+
+        let first = operations.token("batch");
+        let second = operations.token("batch");
+        first.record_completed();
+        assert!(!second.take_confirmation());
+        assert!(operations.cancel("batch"));
+        assert!(second.is_cancelled() && second.completed() == 1);
+        assert!(second.take_confirmation());
+        assert!(!first.take_confirmation());
+
+        operations.finish("batch");
+        assert!(!operations.token("batch").is_cancelled());
+    }
+
+    #[test]
+    fn waiters_wake_up_when_cancelled() {
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        runtime.block_on(async {
+            let token = CancelToken::default();
+            let waiter = tokio::spawn({
+                let token = token.clone();
+                async move { token.cancelled().await }
+            });
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            token.cancel();
+            tokio::time::timeout(Duration::from_secs(1), waiter).await.unwrap().unwrap();
+        });
+    }
+}
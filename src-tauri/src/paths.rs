@@ -0,0 +1,271 @@
+// Conversion between the paths std::fs needs and the strings the frontend sees.
+// Win32 APIs reject paths over MAX_PATH (260 chars) unless they use the
+// extended-length \\?\ form, and names that aren't valid Unicode can't cross
+// IPC as strings, so both are translated at this boundary.
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use tracing::warn;
+
+const EXTENDED_PREFIX: &str = r"\\?\";
+const EXTENDED_UNC_PREFIX: &str = r"\\?\UNC\";
+
+// Returns the path in a form std::fs can use regardless of length. Relative paths
+// and paths on other platforms are returned unchanged.
+pub fn normalize_path(path: impl AsRef<Path>) -> PathBuf {
+    let path = path.as_ref();
+    if !cfg!(windows) {
+        return path.to_path_buf();
+    }
+    match extended_length_path(&path.to_string_lossy()) {
+        Some(extended) => PathBuf::from(extended),
+        None => path.to_path_buf(),
+    }
+}
+
+// Real paths of entries whose names aren't valid Unicode, keyed by the lossy string
+// sent to the frontend. None marks a string shared by several different paths.
+static LOSSY_PATHS: OnceLock<Mutex<HashMap<String, Option<PathBuf>>>> = OnceLock::new();
+
+fn lossy_paths() -> &'static Mutex<HashMap<String, Option<PathBuf>>> {
+    LOSSY_PATHS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+// String form of a path for the frontend, without any extended-length prefix.
+// Names that can't be represented exactly are remembered so resolve_path can map
+// the replacement-character string back to the real file.
+pub fn display_path(path: impl AsRef<Path>) -> String {
+    let path = path.as_ref();
+    let lossy = path.to_string_lossy();
+    let shown = if cfg!(windows) {
+        strip_extended_prefix(&lossy)
+    } else {
+        lossy.into_owned()
+    };
+
+    if path.to_str().is_none() {
+        let mut known = lossy_paths().lock().unwrap();
+        let entry = known.entry(shown.clone()).or_insert_with(|| Some(path.to_path_buf()));
+        if entry.as_deref() != Some(path) {
+            warn!("Multiple files map to the name {}; they can't be told apart", shown);
+            *entry = None;
+        }
+    }
+    shown
+}
+
+// Turns a path string from the frontend back into the real path, undoing both the
+// lossy name conversion and the long path prefix stripping
+pub fn resolve_path(path: &str) -> PathBuf {
+    match lossy_paths().lock().unwrap().get(path) {
+        // Already the exact path display_path was given, prefix included
+        Some(Some(real)) => return real.clone(),
+        Some(None) => warn!("{} is ambiguous; using it as written", path),
+        None => {}
+    }
+    normalize_path(path)
+}
+
+// Command argument taking one path or several, so commands that grew multi-root
+// support keep accepting the single string older callers send
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum PathList {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl PathList {
+    pub fn into_vec(self) -> Vec<String> {
+        match self {
+            PathList::One(path) => vec![path],
+            PathList::Many(paths) => paths,
+        }
+    }
+}
+
+// The roots minus any that repeat or sit inside another root, in their original
+// order, so overlapping selections aren't walked twice
+pub fn distinct_roots(roots: &[PathBuf]) -> Vec<PathBuf> {
+    roots
+        .iter()
+        .enumerate()
+        .filter(|(i, root)| {
+            !roots
+                .iter()
+                .enumerate()
+                .any(|(j, other)| j != *i && root.starts_with(other) && (*root != other || j < *i))
+        })
+        .map(|(_, root)| root.clone())
+        .collect()
+}
+
+fn strip_extended_prefix(path: &str) -> String {
+    if let Some(rest) = path.strip_prefix(EXTENDED_UNC_PREFIX) {
+        format!(r"\\{}", rest)
+    } else if let Some(rest) = path.strip_prefix(EXTENDED_PREFIX) {
+        rest.to_string()
+    } else {
+        path.to_string()
+    }
+}
+
+// Builds the \\?\ form of an absolute drive or UNC path, or None when the path is
+// relative or already uses a device prefix
+fn extended_length_path(path: &str) -> Option<String> {
+    if path.starts_with(EXTENDED_PREFIX) || path.starts_with(r"\\.\") {
+        return None;
+    }
+    let path = path.replace('/', "\\");
+
+    let (prefix, rest, root_parts) = if let Some(unc) = path.strip_prefix(r"\\") {
+        // \\server\share is the root and must survive any ".." components
+        (EXTENDED_UNC_PREFIX, unc, 2)
+    } else {
+        let bytes = path.as_bytes();
+        let is_drive_absolute = bytes.len() >= 3
+            && bytes[0].is_ascii_alphabetic()
+            && bytes[1] == b':'
+            && bytes[2] == b'\\';
+        if !is_drive_absolute {
+            return None;
+        }
+        (EXTENDED_PREFIX, path.as_str(), 1)
+    };
+
+    // Extended-length paths bypass Win32 normalisation, so "." and ".." are resolved here
+    let mut parts: Vec<&str> = Vec::new();
+    for part in rest.split('\\') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                if parts.len() > root_parts {
+                    parts.pop();
+                }
+            }
+            _ => parts.push(part),
+        }
+    }
+    if parts.len() < root_parts {
+        return None;
+    }
+
+    let mut extended = format!("{}{}", prefix, parts.join("\\"));
+    if parts.len() == root_parts {
+        // Keep the drive or share root a directory ("C:\", not "C:")
+        extended.push('\\');
+    }
+    Some(extended)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drive_paths_get_the_extended_prefix() {
+        assert_eq!(
+            extended_length_path(r"C:\Users\me\Documents\report.pdf").as_deref(),
+            Some(r"\\?\C:\Users\me\Documents\report.pdf")
+        );
+        assert_eq!(extended_length_path("C:/Users/me/a/../b/./c.txt").as_deref(), Some(r"\\?\C:\Users\me\b\c.txt"));
+        assert_eq!(extended_length_path(r"C:\..\..").as_deref(), Some(r"\\?\C:\"));
+    }
+
+    #[test]
+    fn unc_paths_use_the_unc_form() {
+        assert_eq!(
+            extended_length_path(r"\\fileserver\team\projects\plan.docx").as_deref(),
+            Some(r"\\?\UNC\fileserver\team\projects\plan.docx")
+        );
+        assert_eq!(
+            extended_length_path(r"\\fileserver\team\..\..\x").as_deref(),
+            Some(r"\\?\UNC\fileserver\team\x")
+        );
+    }
+
+    #[test]
+    fn relative_and_prefixed_paths_are_left_alone() {
+        assert_eq!(extended_length_path(r"docs\report.pdf"), None);
+        assert_eq!(extended_length_path(r"C:report.pdf"), None);
+        assert_eq!(extended_length_path(r"\\?\C:\already\extended"), None);
+        assert_eq!(extended_length_path(r"\\.\pipe\name"), None);
+    }
+
+    #[test]
+    fn nested_and_repeated_roots_are_dropped() {
+        let roots = ["/data/photos/2023", "/data", "/other", "/data"].map(PathBuf::from);
+        assert_eq!(distinct_roots(&roots), [PathBuf::from("/data"), PathBuf::from("/other")]);
+        let single: PathList = serde_json::from_str("\"/data\"").unwrap();
+        assert_eq!(single.into_vec(), ["/data"]);
+    }
+
+    #[test]
+    fn prefixes_are_stripped_for_display() {
+        assert_eq!(strip_extended_prefix(r"\\?\C:\Users\me\file.txt"), r"C:\Users\me\file.txt");
+        assert_eq!(strip_extended_prefix(r"\\?\UNC\server\share\file.txt"), r"\\server\share\file.txt");
+        assert_eq!(strip_extended_prefix("/home/me/file.txt"), "/home/me/file.txt");
+    }
+
+    // Linux filesystems accept arbitrary bytes in names; macOS and Windows don't
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn non_utf8_names_round_trip_through_display_strings() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let root = crate::test_support::temp_dir("non-utf8");
+        let source = root.join(OsStr::from_bytes(b"caf\xe9 menu.txt"));
+        std::fs::write(&source, b"latin-1 name").unwrap();
+
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        let filter = crate::ScanFilter::default();
+        let listed = crate::list_files(&root.to_string_lossy(), false, false, &filter).unwrap();
+        assert_eq!(listed.len(), 1);
+        let display = listed[0].path.clone();
+        assert!(display.contains('\u{FFFD}'));
+        assert_eq!(resolve_path(&display), source);
+
+        let content = runtime.block_on(crate::file_content_json(display.clone(), None, None, None, true)).unwrap();
+        assert!(content.contains("latin-1 name"));
+
+        let destination = root.join("organized").join("cafe menu.txt");
+        runtime
+            .block_on(crate::move_path(display, destination.to_string_lossy().into_owned(), None, None))
+            .unwrap();
+        assert!(!source.exists());
+        assert!(destination.exists());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn files_beyond_max_path_can_be_hashed_and_moved() {
+        use sha2::{Digest, Sha256};
+
+        let root = crate::test_support::temp_dir("long-path");
+        let mut deep = root.clone();
+        while deep.to_string_lossy().len() < 300 {
+            deep = deep.join("a-fairly-long-directory-name-for-testing");
+        }
+        let deep = normalize_path(&deep);
+        std::fs::create_dir_all(&deep).unwrap();
+
+        let source = deep.join("source.txt");
+        std::fs::write(&source, b"long path contents").unwrap();
+        let expected = format!("{:x}", Sha256::digest(b"long path contents"));
+        assert_eq!(crate::sha256_file(&source).unwrap(), expected);
+
+        let destination = deep.join("moved").join("destination.txt");
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        runtime
+            .block_on(crate::move_path(display_path(&source), display_path(&destination), None, None))
+            .unwrap();
+        assert!(!source.exists());
+        assert_eq!(std::fs::read(&destination).unwrap(), b"long path contents");
+
+        std::fs::remove_dir_all(normalize_path(&root)).unwrap();
+    }
+}
@@ -0,0 +1,448 @@
+// The parts of run_classification_pipeline that don't need the app: its options and
+// report, the prompt sent for each file, reading the model's answer, and picking
+// destinations. The stages themselves (extract, prepare, classify, move) are driven
+// from main.rs, where the extraction limiter, the LLM router and the move journal are.
+use crate::error::AppError;
+use crate::llm_backend::BackendConfig;
+use crate::rename::OnConflict;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+// Characters of extracted text sent with a file, as the frontend's maxTextLength
+pub const DEFAULT_MAX_TEXT_CHARS: usize = 4096;
+// LLM requests in flight: the managed server works through one request at a time and
+// queues the rest, so more only adds waiting; remote APIs take a few at once
+pub const MANAGED_LLM_CONCURRENCY: usize = 1;
+pub const REMOTE_LLM_CONCURRENCY: usize = 4;
+pub const DEFAULT_SYSTEM_MESSAGE: &str =
+    "Return only valid JSON (no markdown), with keys: category_path, suggested_filename.";
+// Category for files whose answer couldn't be used, as in the frontend
+pub const UNCATEGORIZED: &str = "uncategorized";
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct PipelineOptions {
+    // Files extracted at once; by default the extraction limiter's size. Extractions
+    // still wait for the app-wide limiter too.
+    pub extract_concurrency: Option<usize>,
+    // LLM requests in flight; by default MANAGED_LLM_CONCURRENCY or REMOTE_LLM_CONCURRENCY
+    pub llm_concurrency: Option<usize>,
+    // Stops starting new files after the first one that fails
+    pub stop_on_error: bool,
+    // Classifies and picks destinations without moving anything
+    pub dry_run: bool,
+    // Used for this run only, without changing the selected backend (used when unset)
+    pub backend: Option<BackendConfig>,
+    // Directories files are organized within; a file outside all of them is organized
+    // within its own directory
+    pub roots: Vec<String>,
+    // Existing categories the model has to choose from; free to make its own when empty
+    pub categories: Vec<String>,
+    // Replaces the built-in prompt; {filename}, {content}, {type} and {categories} are
+    // filled in
+    pub prompt_template: Option<String>,
+    pub system_message: Option<String>,
+    pub max_text_chars: Option<usize>,
+    pub max_tokens: Option<u32>,
+    // Reuses and records classifications in the classification cache under this model id
+    pub model_id: Option<String>,
+    // Journal batch for the moves; generated when not given
+    pub batch_id: Option<String>,
+    pub on_conflict: Option<OnConflict>,
+    pub operation_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Stage {
+    Extracting,
+    Classifying,
+    Moving,
+    // Finished: classified in a dry run, moved otherwise
+    Done,
+    Skipped,
+    Failed,
+}
+
+// Sent as "pipeline-file" each time a file enters a stage
+#[derive(Debug, Clone, Serialize)]
+pub struct FileStage {
+    pub pipeline_id: String,
+    pub path: String,
+    pub stage: Stage,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub category_path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub destination: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<AppError>,
+}
+
+// How a file's category was arrived at
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Method {
+    Llm,
+    Cache,
+    // The file had no content to send (unsupported, unreadable or cloud-only)
+    Extension,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileStatus {
+    // Dry run: classified with a destination, not moved
+    Planned,
+    Moved,
+    // Already at its destination, the destination was taken with on_conflict skip, or
+    // the run stopped before the file was started
+    Skipped,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FileResult {
+    pub path: String,
+    pub status: FileStatus,
+    pub method: Option<Method>,
+    pub category_path: Option<String>,
+    pub suggested_filename: Option<String>,
+    pub destination: Option<String>,
+    // The backend that answered, for Llm
+    pub backend: Option<String>,
+    // Why the file was categorized by extension, or skipped
+    pub note: Option<String>,
+    pub error: Option<AppError>,
+}
+
+impl FileResult {
+    pub fn new(path: String, status: FileStatus) -> Self {
+        FileResult {
+            path,
+            status,
+            method: None,
+            category_path: None,
+            suggested_filename: None,
+            destination: None,
+            backend: None,
+            note: None,
+            error: None,
+        }
+    }
+
+    pub fn failed(path: String, error: AppError) -> Self {
+        FileResult { error: Some(error), ..FileResult::new(path, FileStatus::Failed) }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PipelineReport {
+    pub pipeline_id: String,
+    pub batch_id: Option<String>,
+    pub dry_run: bool,
+    pub total: usize,
+    pub planned: usize,
+    pub moved: usize,
+    pub skipped: usize,
+    pub failed: usize,
+    // Classifications answered by the model, and reused from the cache
+    pub llm_requests: usize,
+    pub cache_hits: usize,
+    pub cancelled: bool,
+    pub stopped_on_error: bool,
+    pub elapsed_ms: u64,
+    // One per path, in the order given
+    pub results: Vec<FileResult>,
+}
+
+impl PipelineReport {
+    pub fn new(pipeline_id: String, batch_id: Option<String>, dry_run: bool, results: Vec<FileResult>) -> Self {
+        let count = |status| results.iter().filter(|r| r.status == status).count();
+        let methods = |method| results.iter().filter(|r| r.method == Some(method)).count();
+        PipelineReport {
+            pipeline_id,
+            batch_id,
+            dry_run,
+            total: results.len(),
+            planned: count(FileStatus::Planned),
+            moved: count(FileStatus::Moved),
+            skipped: count(FileStatus::Skipped),
+            failed: count(FileStatus::Failed),
+            llm_requests: methods(Method::Llm),
+            cache_hits: methods(Method::Cache),
+            cancelled: false,
+            stopped_on_error: false,
+            elapsed_ms: 0,
+            results,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Classification {
+    pub category_path: String,
+    // Blank when the model left it out; the file keeps its own name then
+    #[serde(default)]
+    pub suggested_filename: String,
+}
+
+const FILENAME_RULES: &str = "**Task 2: Filename Suggestion**
+- Provide a descriptive filename base (no file extension) using lowercase with underscores
+- Format: {primary_topic}_{entity}_{date_or_identifier}
+  - primary_topic: main subject (1-2 words, e.g., \"invoice\", \"meeting_notes\", \"project_proposal\")
+  - entity: company/person/organization if identifiable (e.g., \"acme_corp\", \"john_smith\")
+  - date_or_identifier: date in YYYY-MM-DD or unique identifier if present
+- If any component is missing, omit it (minimum: just primary_topic)
+- Examples: \"invoice_acme_corp_2024-03-15\", \"recipe_chocolate_cake\", \"contract_freelance_2024\"
+- Keep total length under 50 characters";
+
+const OPEN_CATEGORY_RULES: &str = "**Task 1: Category Classification**
+- Create a category path with EXACTLY 2 levels separated by forward slash (/)
+- Use Title Case for all category levels (e.g., \"Personal/Medical Records\")
+- First level should be ONE of these broad categories:
+  Business, Personal, Finance, Health, Education, Entertainment, Work, Travel, Legal, Technology, Science, Art, \
+Music, Sports, Media, Documents, Archives
+- Second level should be a specific subcategory relevant to content:
+  Examples: Invoices, Reports, Photos, Recipes, Projects, Research, Contracts, Receipts, Presentations, Notes
+- If content doesn't fit clearly, use \"Uncategorized/General\"
+- Never create categories deeper than 2 levels";
+
+// The prompt the frontend's classifyViaLLM builds, for `text` (already cut to size) or
+// an attached image when `text` is None
+pub fn build_prompt(template: Option<&str>, file_name: &str, text: Option<&str>, categories: &[String]) -> String {
+    let kind = if text.is_some() { "text" } else { "image" };
+    if let Some(template) = template {
+        let mut prompt = template
+            .replace("{filename}", file_name)
+            .replace("{content}", text.unwrap_or("[Image data - see attached image]"))
+            .replace("{type}", kind)
+            .replace("{categories}", &categories.join(", "));
+        if !categories.is_empty() && !template.contains("{categories}") {
+            prompt.push_str(&format!(
+                "\n\nIMPORTANT: You MUST classify the file into one of the following existing categories. \
+                 Do NOT create new categories.\nExisting categories:\n- {}",
+                categories.join("\n- ")
+            ));
+        }
+        return prompt;
+    }
+
+    let category_rules = if categories.is_empty() {
+        OPEN_CATEGORY_RULES.to_string()
+    } else {
+        format!(
+            "**Task 1: Category Classification**\n\
+             - You MUST classify the file into one of the following existing categories.\n\
+             - Do NOT create new categories.\n\
+             - Do NOT modify the category names (preserve case and path).\n\
+             - If the file does not fit any of the categories, use \"Uncategorized\".\n\n\
+             **Allowed Categories:**\n- {}\n- Uncategorized",
+            categories.join("\n- ")
+        )
+    };
+    let subject = if text.is_some() { "text content" } else { "image" };
+    let mut prompt = format!(
+        "You are a file organizer. Analyze the {} and provide classification and naming suggestions.\n\n{}\n\n{}\n",
+        subject, category_rules, FILENAME_RULES
+    );
+    if text.is_none() {
+        prompt.push_str(
+            "\n**For images**: Describe visible content, text, objects, or documents to determine category and \
+             filename.\n",
+        );
+    }
+    prompt.push_str(
+        "\n**Output Format**: Return ONLY valid JSON with these exact keys:\n\
+         {\n  \"category_path\": \"Category/Subcategory\",\n  \"suggested_filename\": \"descriptive_name_here\"\n}\n\n",
+    );
+    prompt.push_str(&format!("Original filename: {}", file_name));
+    if let Some(text) = text {
+        prompt.push_str(&format!("\nContent:\n{}", text));
+    }
+    prompt
+}
+
+// The JSON object in the model's answer, which may come wrapped in a code fence or
+// surrounded by prose. None when there's no usable object.
+pub fn parse_classification(content: &str) -> Option<Classification> {
+    let trimmed = content.trim();
+    let unfenced = trimmed
+        .strip_prefix("```json")
+        .or_else(|| trimmed.strip_prefix("```"))
+        .and_then(|rest| rest.trim_end().strip_suffix("```"))
+        .unwrap_or(trimmed);
+    let parsed: Classification = serde_json::from_str(unfenced.trim()).ok().or_else(|| {
+        let start = unfenced.find('{')?;
+        let end = unfenced.rfind('}')?;
+        serde_json::from_str(unfenced.get(start..=end)?).ok()
+    })?;
+    (!parsed.category_path.trim().is_empty()).then_some(parsed)
+}
+
+// With existing categories, an answer outside them is moved to the category it
+// mentions, or else to the first one
+pub fn match_category(category_path: &str, categories: &[String]) -> String {
+    if categories.is_empty() {
+        return category_path.to_string();
+    }
+    let answer = category_path.to_lowercase();
+    let first = answer.split('/').next().unwrap_or_default();
+    let matches = categories
+        .iter()
+        .any(|category| category.to_lowercase() == first || answer.starts_with(&category.to_lowercase()));
+    if matches {
+        return category_path.to_string();
+    }
+    categories
+        .iter()
+        .find(|category| answer.contains(&category.to_lowercase()))
+        .or(categories.first())
+        .cloned()
+        .unwrap_or_else(|| UNCATEGORIZED.to_string())
+}
+
+// Category for a file that couldn't be read, as the frontend's getExtensionBasedCategory
+pub fn extension_category(path: &Path) -> String {
+    let extension = path.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default();
+    let category = match extension.as_str() {
+        "zip" | "rar" | "7z" | "tar" | "gz" | "bz2" => "Archives/Compressed",
+        "mp3" | "wav" | "flac" | "aac" | "ogg" | "m4a" | "wma" => "Media/Audio",
+        "mp4" | "avi" | "mkv" | "mov" | "wmv" | "flv" | "webm" | "m4v" => "Media/Video",
+        "exe" | "msi" => "Applications/Windows",
+        "dmg" | "pkg" | "app" => "Applications/macOS",
+        "deb" | "rpm" | "appimage" => "Applications/Linux",
+        "ttf" => "Fonts/TrueType",
+        "otf" => "Fonts/OpenType",
+        "woff" | "woff2" => "Fonts/Web",
+        "obj" => "3D_Models/Objects",
+        "fbx" => "3D_Models/FBX",
+        "stl" => "3D_Models/STL",
+        "blend" => "3D_Models/Blender",
+        "db" | "sqlite" | "sql" => "Data/Database",
+        "iso" => "DiskImages/ISO",
+        "img" => "DiskImages/Image",
+        "bin" => "Binary/Raw",
+        "dat" => "Binary/Data",
+        "" => "Other/Unknown",
+        _ => return format!("Other/{}", extension.to_uppercase()),
+    };
+    category.to_string()
+}
+
+// Characters Windows doesn't allow become "-", runs of whitespace a single "-"
+pub fn sanitize_file_name(name: &str) -> String {
+    let replaced: String = name
+        .trim()
+        .chars()
+        .map(|c| match c {
+            '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*' => '-',
+            '\n' | '\r' => ' ',
+            c => c,
+        })
+        .collect();
+    replaced.split_whitespace().collect::<Vec<_>>().join("-").chars().take(200).collect()
+}
+
+pub fn sanitize_dir_path(path: &str) -> String {
+    let parts: Vec<String> = path
+        .split(['/', '\\'])
+        .filter(|part| !part.is_empty() && *part != "." && *part != "..")
+        .map(sanitize_file_name)
+        .filter(|part| !part.is_empty())
+        .collect();
+    if parts.is_empty() {
+        UNCATEGORIZED.to_string()
+    } else {
+        parts.join("/")
+    }
+}
+
+// The root `path` is organized within: the first of `roots` it's under, or its own directory
+pub fn root_for(path: &Path, roots: &[PathBuf]) -> PathBuf {
+    roots
+        .iter()
+        .find(|root| path.starts_with(root))
+        .cloned()
+        .unwrap_or_else(|| path.parent().map(Path::to_path_buf).unwrap_or_default())
+}
+
+// Destinations handed out so far in a run, so two files given the same name get
+// "-1", "-2", ... like the frontend's scan
+#[derive(Debug, Default)]
+pub struct Destinations {
+    used: HashSet<PathBuf>,
+}
+
+impl Destinations {
+    pub fn assign(&mut self, root: &Path, category_path: &str, suggested_name: &str, source: &Path) -> PathBuf {
+        let directory = root.join(sanitize_dir_path(category_path));
+        let stem = source.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+        let name = match sanitize_file_name(suggested_name) {
+            name if name.is_empty() => sanitize_file_name(&stem),
+            name => name,
+        };
+        let extension = source.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+        let mut candidate = directory.join(format!("{}{}", name, extension));
+        let mut n = 1;
+        while self.used.contains(&candidate) {
+            candidate = directory.join(format!("{}-{}{}", name, n, extension));
+            n += 1;
+        }
+        self.used.insert(candidate.clone());
+        candidate
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn answers_are_read_and_matched_to_existing_categories() {
+        let fenced =
+            "```json\n{\"category_path\": \"Finance/Invoices\", \"suggested_filename\": \"invoice_acme\"}\n```";
+        let parsed = parse_classification(fenced).unwrap();
+        assert_eq!(parsed.category_path, "Finance/Invoices");
+        assert_eq!(parsed.suggested_filename, "invoice_acme");
+        let prose = "Sure! {\"category_path\": \"Work/Notes\", \"suggested_filename\": \"notes\"} Hope that helps.";
+        assert_eq!(parse_classification(prose).unwrap().category_path, "Work/Notes");
+        assert_eq!(parse_classification("I can't tell"), None);
+        assert_eq!(parse_classification("{\"category_path\": \" \", \"suggested_filename\": \"x\"}"), None);
+
+        let categories = vec!["Finance".to_string(), "Work/Notes".to_string()];
+        assert_eq!(match_category("finance/Taxes", &categories), "finance/Taxes");
+        assert_eq!(match_category("Personal/Work/Notes", &categories), "Work/Notes");
+        assert_eq!(match_category("Travel/Tickets", &categories), "Finance");
+        assert_eq!(match_category("Travel/Tickets", &[]), "Travel/Tickets");
+
+        let prompt = build_prompt(None, "scan.txt", Some("Invoice #12"), &categories);
+        assert!(prompt.contains("**Allowed Categories:**\n- Finance\n- Work/Notes\n- Uncategorized"));
+        assert!(prompt.ends_with("Original filename: scan.txt\nContent:\nInvoice #12"));
+        let custom = build_prompt(Some("{type}: {filename} {content}"), "a.png", None, &[]);
+        assert_eq!(custom, "image: a.png [Image data - see attached image]");
+    }
+
+    #[test]
+    fn destinations_are_sanitized_and_kept_apart() {
+        assert_eq!(sanitize_file_name("  Q3 report: draft?  "), "Q3-report--draft-");
+        assert_eq!(sanitize_dir_path("../Finance//Tax: 2024/"), "Finance/Tax--2024");
+        assert_eq!(sanitize_dir_path(" / "), UNCATEGORIZED);
+        assert_eq!(extension_category(Path::new("/a/song.MP3")), "Media/Audio");
+        assert_eq!(extension_category(Path::new("/a/data.xyz")), "Other/XYZ");
+        assert_eq!(extension_category(Path::new("/a/README")), "Other/Unknown");
+
+        let roots = vec![PathBuf::from("/home/me/Downloads")];
+        assert_eq!(root_for(Path::new("/home/me/Downloads/x/a.pdf"), &roots), roots[0]);
+        assert_eq!(root_for(Path::new("/home/me/Downloads2/a.pdf"), &roots), PathBuf::from("/home/me/Downloads2"));
+
+        let mut destinations = Destinations::default();
+        let root = Path::new("/r");
+        let first = destinations.assign(root, "Finance/Invoices", "invoice acme", Path::new("/r/a.pdf"));
+        let second = destinations.assign(root, "Finance/Invoices", "invoice acme", Path::new("/r/b.pdf"));
+        let unnamed = destinations.assign(root, "Finance/Invoices", " ", Path::new("/r/scan 1.pdf"));
+        assert_eq!(first, Path::new("/r/Finance/Invoices/invoice-acme.pdf"));
+        assert_eq!(second, Path::new("/r/Finance/Invoices/invoice-acme-1.pdf"));
+        assert_eq!(unnamed, Path::new("/r/Finance/Invoices/scan-1.pdf"));
+    }
+}
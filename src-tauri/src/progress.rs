@@ -0,0 +1,186 @@
+// Throughput and time-left estimates for progress events, worked out here once so every
+// view shows the same numbers. The rate is an exponential moving average with a
+// RATE_WINDOW time constant, so a burst or a slow file moves it without throwing it
+// around. When progress stops the rate decays toward zero, and after STALL_AFTER with
+// nothing done the ETA goes back to None rather than promising a time that's no
+// longer true.
+use serde::Serialize;
+use std::time::{Duration, Instant};
+
+pub const RATE_WINDOW: Duration = Duration::from_secs(10);
+pub const STALL_AFTER: Duration = Duration::from_secs(5);
+// Updates needed before an ETA is given
+const MIN_SAMPLES: u32 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateUnit {
+    Bytes,
+    Items,
+}
+
+// Flattened into progress payloads; only the field for the tracker's unit is sent
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ProgressEstimate {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bytes_per_sec: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub items_per_sec: Option<f64>,
+    // None until there are enough samples, while stalled, or when the total isn't known
+    pub eta_seconds: Option<u64>,
+    pub elapsed_ms: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct ProgressTracker {
+    unit: RateUnit,
+    total: Option<u64>,
+    started: Instant,
+    // Time and amount of the last update
+    last: Option<(Instant, u64)>,
+    last_progress: Instant,
+    rate: f64,
+    samples: u32,
+}
+
+impl ProgressTracker {
+    pub fn new(unit: RateUnit, total: Option<u64>) -> Self {
+        Self::starting_at(unit, total, Instant::now())
+    }
+
+    fn starting_at(unit: RateUnit, total: Option<u64>, now: Instant) -> Self {
+        Self { unit, total, started: now, last: None, last_progress: now, rate: 0.0, samples: 0 }
+    }
+
+    // Records that `done` of the total is finished and returns the estimate
+    pub fn update(&mut self, done: u64) -> ProgressEstimate {
+        self.update_at(done, Instant::now())
+    }
+
+    fn update_at(&mut self, done: u64, now: Instant) -> ProgressEstimate {
+        // Restarted from less (a download that began again): a new baseline, same rate
+        let restarted = self.last.is_some_and(|(_, last_done)| done < last_done);
+        match self.last {
+            Some(_) if restarted => {}
+            Some((at, last_done)) => {
+                let seconds = now.saturating_duration_since(at).as_secs_f64();
+                if seconds > 0.0 {
+                    let instant_rate = (done - last_done) as f64 / seconds;
+                    self.rate = if self.samples == 0 {
+                        instant_rate
+                    } else {
+                        let weight = 1.0 - (-seconds / RATE_WINDOW.as_secs_f64()).exp();
+                        self.rate + weight * (instant_rate - self.rate)
+                    };
+                    self.samples += 1;
+                }
+                if done > last_done {
+                    self.last_progress = now;
+                }
+            }
+            None => self.last_progress = now,
+        }
+        // Progress reported at the same instant is counted with the next update instead
+        if restarted || self.last.is_none_or(|(at, _)| now > at) {
+            self.last = Some((now, done));
+        }
+        self.estimate_at(now)
+    }
+
+    // The estimate at `now`, with the rate decayed for the time since the last update
+    fn estimate_at(&self, now: Instant) -> ProgressEstimate {
+        let since_update = self.last.map_or(Duration::ZERO, |(at, _)| now.saturating_duration_since(at));
+        let rate = self.rate * (-since_update.as_secs_f64() / RATE_WINDOW.as_secs_f64()).exp();
+        let stalled = now.saturating_duration_since(self.last_progress) >= STALL_AFTER;
+        let done = self.last.map_or(0, |(_, done)| done);
+        let eta_seconds = match self.total {
+            Some(total) if self.samples >= MIN_SAMPLES && !stalled && rate > 0.0 => {
+                Some((total.saturating_sub(done) as f64 / rate).ceil() as u64)
+            }
+            _ => None,
+        };
+        let rate = (self.samples > 0).then_some(rate);
+        ProgressEstimate {
+            bytes_per_sec: rate.filter(|_| self.unit == RateUnit::Bytes),
+            items_per_sec: rate.filter(|_| self.unit == RateUnit::Items),
+            eta_seconds,
+            elapsed_ms: now.saturating_duration_since(self.started).as_millis() as u64,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(start: Instant, ms: u64) -> Instant {
+        start + Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn the_rate_is_smoothed_and_the_eta_waits_for_samples() {
+        let start = Instant::now();
+        let mut tracker = ProgressTracker::starting_at(RateUnit::Bytes, Some(10_000), start);
+        assert_eq!(tracker.update_at(0, start).eta_seconds, None);
+        let first = tracker.update_at(100, at(start, 1000));
+        assert_eq!((first.bytes_per_sec, first.items_per_sec, first.eta_seconds), (Some(100.0), None, None));
+        tracker.update_at(200, at(start, 2000));
+        let steady = tracker.update_at(300, at(start, 3000));
+        assert_eq!(steady.bytes_per_sec, Some(100.0));
+        assert_eq!((steady.eta_seconds, steady.elapsed_ms), (Some(97), 3000));
+
+        // One fast second moves the average only part of the way
+        let burst = tracker.update_at(1300, at(start, 4000)).bytes_per_sec.unwrap();
+        assert!(burst > 150.0 && burst < 300.0, "{}", burst);
+        // Updates at the same instant don't divide by zero
+        assert_eq!(tracker.update_at(1300, at(start, 4000)).bytes_per_sec, Some(burst));
+    }
+
+    #[test]
+    fn a_stall_decays_the_rate_and_drops_the_eta() {
+        let start = Instant::now();
+        let mut tracker = ProgressTracker::starting_at(RateUnit::Items, Some(1000), start);
+        for second in 0..=4 {
+            tracker.update_at(second * 10, at(start, second * 1000));
+        }
+        assert!(tracker.estimate_at(at(start, 4000)).eta_seconds.is_some());
+
+        // Still reporting, but nothing more done
+        let mut rates = Vec::new();
+        for second in 5..=12 {
+            let estimate = tracker.update_at(40, at(start, second * 1000));
+            rates.push(estimate.items_per_sec.unwrap());
+        }
+        assert!(rates.windows(2).all(|pair| pair[1] < pair[0]), "{:?}", rates);
+        assert_eq!(tracker.estimate_at(at(start, 12_000)).eta_seconds, None);
+
+        // Not reporting at all decays the same way
+        let mut quiet = ProgressTracker::starting_at(RateUnit::Items, Some(1000), start);
+        for second in 0..=4 {
+            quiet.update_at(second * 10, at(start, second * 1000));
+        }
+        let later = quiet.estimate_at(at(start, 34_000));
+        assert!(later.items_per_sec.unwrap() < 1.0 && later.eta_seconds.is_none(), "{:?}", later);
+
+        // Progress again brings the ETA back
+        for second in 13..=15 {
+            tracker.update_at(40 + (second - 12) * 10, at(start, second * 1000));
+        }
+        assert!(tracker.estimate_at(at(start, 15_000)).eta_seconds.is_some());
+    }
+
+    #[test]
+    fn no_total_means_no_eta_and_restarts_keep_the_rate() {
+        let start = Instant::now();
+        let mut tracker = ProgressTracker::starting_at(RateUnit::Bytes, None, start);
+        for second in 0..=4 {
+            tracker.update_at(second * 500, at(start, second * 1000));
+        }
+        assert_eq!(tracker.estimate_at(at(start, 4000)).eta_seconds, None);
+        tracker.total = Some(4000);
+        assert_eq!(tracker.estimate_at(at(start, 4000)).eta_seconds, Some(4));
+        // Started over from zero: the rate carries on from the new baseline
+        let restarted = tracker.update_at(0, at(start, 5000));
+        assert!(restarted.bytes_per_sec.unwrap() > 400.0, "{:?}", restarted);
+        assert_eq!(tracker.update_at(500, at(start, 6000)).bytes_per_sec.map(f64::round), Some(500.0));
+    }
+}
@@ -0,0 +1,292 @@
+// Where a downloaded file came from, as browsers record it: on macOS the
+// com.apple.metadata:kMDItemWhereFroms extended attribute (a binary plist holding the
+// file's URL and the page it was linked from), on Windows the Zone.Identifier stream
+// with HostUrl= and ReferrerUrl=. Most files have neither, so a missing attribute or
+// stream is just None, without an error or a log line; listings ask about every file.
+use serde::Serialize;
+use std::path::Path;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Provenance {
+    pub source_url: Option<String>,
+    // The page the download was started from
+    pub referrer_url: Option<String>,
+}
+
+#[cfg(any(target_os = "macos", test))]
+const BPLIST_MAGIC: &[u8] = b"bplist00";
+#[cfg(any(target_os = "macos", test))]
+const TRAILER_LEN: usize = 32;
+
+#[cfg(target_os = "macos")]
+const WHERE_FROMS_XATTR: &str = "com.apple.metadata:kMDItemWhereFroms";
+#[cfg(windows)]
+const ZONE_STREAM: &str = "Zone.Identifier";
+
+#[cfg(target_os = "macos")]
+pub fn read(path: &Path) -> Option<Provenance> {
+    let bytes = xattr::get(path, WHERE_FROMS_XATTR).ok()??;
+    from_where_froms(parse_string_array(&bytes)?)
+}
+
+#[cfg(windows)]
+pub fn read(path: &Path) -> Option<Provenance> {
+    let mut stream = path.as_os_str().to_os_string();
+    stream.push(":");
+    stream.push(ZONE_STREAM);
+    let bytes = std::fs::read(stream).ok()?;
+    parse_zone_identifier(&decode_text(&bytes))
+}
+
+#[cfg(not(any(target_os = "macos", windows)))]
+pub fn read(_path: &Path) -> Option<Provenance> {
+    None
+}
+
+#[cfg(any(target_os = "macos", windows, test))]
+fn non_empty(value: Option<String>) -> Option<String> {
+    value.map(|v| v.trim().to_string()).filter(|v| !v.is_empty())
+}
+
+#[cfg(any(target_os = "macos", windows, test))]
+fn provenance(source_url: Option<String>, referrer_url: Option<String>) -> Option<Provenance> {
+    let provenance = Provenance { source_url: non_empty(source_url), referrer_url: non_empty(referrer_url) };
+    (provenance != Provenance::default()).then_some(provenance)
+}
+
+// WhereFroms lists the file's URL first, then usually the page it was linked from
+#[cfg(any(target_os = "macos", test))]
+fn from_where_froms(values: Vec<String>) -> Option<Provenance> {
+    let mut values = values.into_iter();
+    provenance(values.next(), values.next())
+}
+
+// Zone.Identifier is an INI file; Internet Explorer wrote it as UTF-16
+#[cfg(any(windows, test))]
+fn decode_text(bytes: &[u8]) -> String {
+    match bytes {
+        [0xFF, 0xFE, rest @ ..] => {
+            let units: Vec<u16> = rest.chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]])).collect();
+            String::from_utf16_lossy(&units)
+        }
+        _ => String::from_utf8_lossy(bytes).trim_start_matches('\u{feff}').to_string(),
+    }
+}
+
+// HostUrl is "about:internet" when the browser didn't say, which tells nothing
+#[cfg(any(windows, test))]
+fn parse_zone_identifier(text: &str) -> Option<Provenance> {
+    let mut host = None;
+    let mut referrer = None;
+    for line in text.lines() {
+        let Some((key, value)) = line.split_once('=') else { continue };
+        match key.trim() {
+            "HostUrl" if !value.trim().starts_with("about:") => host = Some(value.to_string()),
+            "ReferrerUrl" => referrer = Some(value.to_string()),
+            _ => {}
+        }
+    }
+    provenance(host, referrer)
+}
+
+// An array of strings from a plist: binary (what the xattr holds) or XML (what some
+// tools write instead). Anything else in it is skipped.
+#[cfg(any(target_os = "macos", test))]
+fn parse_string_array(bytes: &[u8]) -> Option<Vec<String>> {
+    if bytes.starts_with(BPLIST_MAGIC) {
+        BinaryPlist::new(bytes)?.string_array()
+    } else {
+        parse_xml_strings(&String::from_utf8_lossy(bytes))
+    }
+}
+
+#[cfg(any(target_os = "macos", test))]
+fn parse_xml_strings(xml: &str) -> Option<Vec<String>> {
+    let strings: Vec<String> = xml
+        .split("<string>")
+        .skip(1)
+        .filter_map(|part| part.split_once("</string>"))
+        .map(|(value, _)| {
+            value
+                .replace("&lt;", "<")
+                .replace("&gt;", ">")
+                .replace("&quot;", "\"")
+                .replace("&apos;", "'")
+                .replace("&amp;", "&")
+        })
+        .collect();
+    (!strings.is_empty()).then_some(strings)
+}
+
+// Just enough of bplist00 to read an array of strings: the trailer, the offset table,
+// and array, int and string objects
+#[cfg(any(target_os = "macos", test))]
+struct BinaryPlist<'a> {
+    bytes: &'a [u8],
+    offset_size: usize,
+    ref_size: usize,
+    num_objects: usize,
+    top_object: usize,
+    offset_table: usize,
+}
+
+#[cfg(any(target_os = "macos", test))]
+impl<'a> BinaryPlist<'a> {
+    fn new(bytes: &'a [u8]) -> Option<Self> {
+        let trailer = bytes.len().checked_sub(TRAILER_LEN).filter(|&start| start >= BPLIST_MAGIC.len())?;
+        let trailer = &bytes[trailer..];
+        let int = |range: std::ops::Range<usize>| usize::try_from(be_uint(&trailer[range])?).ok();
+        let plist = BinaryPlist {
+            bytes,
+            offset_size: usize::from(trailer[6]),
+            ref_size: usize::from(trailer[7]),
+            num_objects: int(8..16)?,
+            top_object: int(16..24)?,
+            offset_table: int(24..32)?,
+        };
+        let table_len = plist.num_objects.checked_mul(plist.offset_size)?;
+        let fits = plist.offset_table.checked_add(table_len).is_some_and(|end| end <= bytes.len() - TRAILER_LEN);
+        let sizes_valid = (1..=8).contains(&plist.offset_size) && (1..=8).contains(&plist.ref_size);
+        (fits && sizes_valid).then_some(plist)
+    }
+
+    fn slice(&self, start: usize, len: usize) -> Option<&'a [u8]> {
+        self.bytes.get(start..start.checked_add(len)?)
+    }
+
+    fn object_offset(&self, index: usize) -> Option<usize> {
+        if index >= self.num_objects {
+            return None;
+        }
+        let entry = self.slice(self.offset_table + index * self.offset_size, self.offset_size)?;
+        usize::try_from(be_uint(entry)?).ok()
+    }
+
+    // The object's marker and where its data starts, past any separate length int
+    fn header(&self, offset: usize) -> Option<(u8, usize, usize)> {
+        let marker = *self.bytes.get(offset)?;
+        let (kind, info) = (marker >> 4, marker & 0x0F);
+        if info != 0x0F {
+            return Some((kind, usize::from(info), offset + 1));
+        }
+        let int_marker = *self.bytes.get(offset + 1)?;
+        if int_marker >> 4 != 0x1 {
+            return None;
+        }
+        let width = 1usize << (int_marker & 0x0F);
+        let len = usize::try_from(be_uint(self.slice(offset + 2, width)?)?).ok()?;
+        Some((kind, len, offset + 2 + width))
+    }
+
+    fn string(&self, index: usize) -> Option<String> {
+        let (kind, len, start) = self.header(self.object_offset(index)?)?;
+        match kind {
+            // ASCII
+            0x5 => Some(String::from_utf8_lossy(self.slice(start, len)?).into_owned()),
+            // UTF-16BE, len counting code units
+            0x6 => {
+                let data = self.slice(start, len.checked_mul(2)?)?;
+                let units: Vec<u16> = data.chunks_exact(2).map(|c| u16::from_be_bytes([c[0], c[1]])).collect();
+                Some(String::from_utf16_lossy(&units))
+            }
+            _ => None,
+        }
+    }
+
+    fn string_array(&self) -> Option<Vec<String>> {
+        let (kind, len, start) = self.header(self.object_offset(self.top_object)?)?;
+        if kind != 0xA {
+            return None;
+        }
+        let refs = self.slice(start, len.checked_mul(self.ref_size)?)?;
+        let strings = refs
+            .chunks_exact(self.ref_size)
+            .filter_map(|r| usize::try_from(be_uint(r)?).ok())
+            .filter_map(|index| self.string(index))
+            .collect();
+        Some(strings)
+    }
+}
+
+#[cfg(any(target_os = "macos", test))]
+fn be_uint(bytes: &[u8]) -> Option<u64> {
+    (bytes.len() <= 8).then(|| bytes.iter().fold(0u64, |value, &b| value << 8 | u64::from(b)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A bplist00 array of strings, with 1-byte offsets and refs; non-ASCII goes as UTF-16
+    fn bplist(strings: &[&str]) -> Vec<u8> {
+        let mut bytes = BPLIST_MAGIC.to_vec();
+        let mut offsets = vec![bytes.len()];
+        bytes.push(0xA0 | strings.len() as u8);
+        bytes.extend((1..=strings.len()).map(|i| i as u8));
+        for s in strings {
+            offsets.push(bytes.len());
+            let (kind, len, data): (u8, usize, Vec<u8>) = if s.is_ascii() {
+                (0x5, s.len(), s.as_bytes().to_vec())
+            } else {
+                let units: Vec<u16> = s.encode_utf16().collect();
+                (0x6, units.len(), units.iter().flat_map(|u| u.to_be_bytes()).collect())
+            };
+            if len < 15 {
+                bytes.push(kind << 4 | len as u8);
+            } else {
+                bytes.extend([kind << 4 | 0x0F, 0x10, len as u8]);
+            }
+            bytes.extend(data);
+        }
+        let offset_table = bytes.len();
+        bytes.extend(offsets.iter().map(|&o| o as u8));
+        bytes.extend([0, 0, 0, 0, 0, 0, 1, 1]);
+        bytes.extend((offsets.len() as u64).to_be_bytes());
+        bytes.extend(0u64.to_be_bytes());
+        bytes.extend((offset_table as u64).to_be_bytes());
+        bytes
+    }
+
+    #[test]
+    fn where_froms_plists_are_parsed() {
+        let url = "https://example.com/files/Résumé.pdf";
+        let bytes = bplist(&[url, "https://example.com/"]);
+        assert_eq!(parse_string_array(&bytes).unwrap(), [url, "https://example.com/"]);
+        let provenance = from_where_froms(parse_string_array(&bytes).unwrap()).unwrap();
+        assert_eq!(provenance.source_url.as_deref(), Some(url));
+        assert_eq!(provenance.referrer_url.as_deref(), Some("https://example.com/"));
+
+        assert_eq!(parse_string_array(&bplist(&["a"])).unwrap(), ["a"]);
+        assert_eq!(from_where_froms(parse_string_array(&bplist(&[])).unwrap()), None);
+        // Truncated or mangled plists are ignored, not read out of bounds
+        for len in 0..bytes.len() {
+            let _ = parse_string_array(&bytes[..len]);
+        }
+        let mut mangled = bytes.clone();
+        let trailer = mangled.len() - TRAILER_LEN;
+        mangled[trailer + 8..trailer + 16].copy_from_slice(&u64::MAX.to_be_bytes());
+        assert_eq!(parse_string_array(&mangled), None);
+
+        let xml = "<plist version=\"1.0\"><array><string>https://a.example/?x=1&amp;y=2</string></array></plist>";
+        assert_eq!(parse_string_array(xml.as_bytes()).unwrap(), ["https://a.example/?x=1&y=2"]);
+    }
+
+    #[test]
+    fn zone_identifiers_are_parsed() {
+        let text = "[ZoneTransfer]\r\nZoneId=3\r\nReferrerUrl=https://example.com/downloads\r\n\
+                    HostUrl=https://cdn.example.com/setup.exe\r\n";
+        let provenance = parse_zone_identifier(text).unwrap();
+        assert_eq!(provenance.source_url.as_deref(), Some("https://cdn.example.com/setup.exe"));
+        assert_eq!(provenance.referrer_url.as_deref(), Some("https://example.com/downloads"));
+
+        assert_eq!(parse_zone_identifier("[ZoneTransfer]\nZoneId=3\nHostUrl=about:internet\n"), None);
+
+        let utf16: Vec<u8> = [0xFF, 0xFE]
+            .into_iter()
+            .chain("[ZoneTransfer]\r\nHostUrl=https://x.example/a\r\n".encode_utf16().flat_map(u16::to_le_bytes))
+            .collect();
+        let provenance = parse_zone_identifier(&decode_text(&utf16)).unwrap();
+        assert_eq!(provenance.source_url.as_deref(), Some("https://x.example/a"));
+        assert_eq!(provenance.referrer_url, None);
+    }
+}
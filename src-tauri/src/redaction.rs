@@ -0,0 +1,262 @@
+// Taking personal data out of file text before it goes to a remote LLM. Built-in
+// detectors cover common PII; users can add their own patterns. Each distinct value
+// becomes a numbered placeholder of its type ("[EMAIL_1]"), so the model still sees
+// that two mentions are the same address, and the counts tell the UI what was removed.
+use crate::error::AppError;
+use crate::llm_backend::BackendConfig;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::OnceLock;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PiiCategory {
+    Email,
+    Phone,
+    CreditCard,
+    Ssn,
+    IpAddress,
+    Iban,
+}
+
+pub const ALL_CATEGORIES: [PiiCategory; 6] = [
+    PiiCategory::Email,
+    PiiCategory::CreditCard,
+    PiiCategory::Iban,
+    PiiCategory::Ssn,
+    PiiCategory::Phone,
+    PiiCategory::IpAddress,
+];
+
+impl PiiCategory {
+    fn label(self) -> &'static str {
+        match self {
+            PiiCategory::Email => "EMAIL",
+            PiiCategory::Phone => "PHONE",
+            PiiCategory::CreditCard => "CARD",
+            PiiCategory::Ssn => "SSN",
+            PiiCategory::IpAddress => "IP",
+            PiiCategory::Iban => "IBAN",
+        }
+    }
+
+    // [0-9] rather than \d, which also matches other scripts' digits and would let
+    // accepts() slice through a multibyte character
+    fn pattern(self) -> &'static str {
+        match self {
+            PiiCategory::Email => r"(?i)\b[a-z0-9._%+-]+@[a-z0-9-]+(?:\.[a-z0-9-]+)*\.[a-z]{2,}\b",
+            PiiCategory::Phone => r"(?:\+[0-9]{1,3}[ .-]?)?(?:\([0-9]{2,4}\)[ .-]?|\b[0-9]{2,4}[ .-])[0-9]{3,4}[ .-]?[0-9]{3,4}\b",
+            PiiCategory::CreditCard => r"\b[0-9](?:[ -]?[0-9]){12,18}\b",
+            PiiCategory::Ssn => r"\b[0-9]{3}-[0-9]{2}-[0-9]{4}\b",
+            PiiCategory::IpAddress => r"\b(?:(?:25[0-5]|2[0-4][0-9]|1?[0-9]?[0-9])\.){3}(?:25[0-5]|2[0-4][0-9]|1?[0-9]?[0-9])\b",
+            PiiCategory::Iban => r"\b[A-Z]{2}[0-9]{2}(?: ?[A-Z0-9]{4}){2,7}(?: ?[A-Z0-9]{1,3})?\b",
+        }
+    }
+
+    // Checks that keep digit runs that merely look like PII
+    fn accepts(self, value: &str) -> bool {
+        let digits: Vec<u32> = value.chars().filter_map(|c| c.to_digit(10)).collect();
+        match self {
+            PiiCategory::CreditCard => (13..=19).contains(&digits.len()) && luhn_valid(&digits),
+            PiiCategory::Phone => {
+                // A bare run of digits is more often an id than a phone number
+                let formatted = value.starts_with(['+', '(']) || value.contains([' ', '-', '.']);
+                formatted && (10..=15).contains(&digits.len())
+            }
+            PiiCategory::Ssn => {
+                let (area, group, serial) = (&value[0..3], &value[4..6], &value[7..11]);
+                area != "000" && area != "666" && !area.starts_with('9') && group != "00" && serial != "0000"
+            }
+            PiiCategory::Iban => iban_valid(value),
+            PiiCategory::Email | PiiCategory::IpAddress => true,
+        }
+    }
+}
+
+// "4111-1111-1111" inside "4111-1111-1111-1112": digits joined on by '-' or '.' mean the
+// match is part of a longer number
+fn inside_longer_number(text: &str, start: usize, end: usize) -> bool {
+    let joined = |mut chars: std::str::Chars<'_>, reversed: bool| {
+        let mut next = || if reversed { chars.next_back() } else { chars.next() };
+        matches!(next(), Some('-' | '.')) && next().is_some_and(|c| c.is_ascii_digit())
+    };
+    joined(text[end..].chars(), false) || joined(text[..start].chars(), true)
+}
+
+pub fn luhn_valid(digits: &[u32]) -> bool {
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(index, &digit)| match index % 2 {
+            0 => digit,
+            _ if digit * 2 > 9 => digit * 2 - 9,
+            _ => digit * 2,
+        })
+        .sum();
+    !digits.is_empty() && sum.is_multiple_of(10)
+}
+
+// ISO 13616: country code and check digits moved to the end, letters as 10-35, mod 97 is 1
+fn iban_valid(value: &str) -> bool {
+    let compact: String = value.chars().filter(|c| !c.is_whitespace()).collect();
+    if !(15..=34).contains(&compact.len()) {
+        return false;
+    }
+    let (head, tail) = compact.split_at(4);
+    tail.chars().chain(head.chars()).try_fold(0u32, |rest, c| {
+        let value = c.to_digit(36)?;
+        let shift = if value >= 10 { 100 } else { 10 };
+        Some((rest * shift + value) % 97)
+    }) == Some(1)
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CustomPattern {
+    // Used for the placeholder, uppercased: "project code" gives [PROJECT_CODE_1]
+    pub name: String,
+    pub pattern: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RedactionRules {
+    pub categories: Vec<PiiCategory>,
+    pub custom: Vec<CustomPattern>,
+}
+
+impl Default for RedactionRules {
+    fn default() -> Self {
+        Self { categories: ALL_CATEGORIES.to_vec(), custom: Vec::new() }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Redacted {
+    pub text: String,
+    // Matches removed per placeholder type ("EMAIL", "CARD", custom names)
+    pub counts: BTreeMap<String, usize>,
+}
+
+// Whether text for `backend` gets redacted: always with redact_always, otherwise only
+// when it leaves the machine
+pub fn applies(backend: &BackendConfig, enabled: bool, redact_always: bool) -> bool {
+    redact_always || (enabled && !backend.is_local())
+}
+
+fn builtin(category: PiiCategory) -> &'static Regex {
+    static COMPILED: OnceLock<HashMap<PiiCategory, Regex>> = OnceLock::new();
+    &COMPILED.get_or_init(|| {
+        ALL_CATEGORIES.iter().map(|&category| (category, Regex::new(category.pattern()).unwrap())).collect()
+    })[&category]
+}
+
+fn placeholder_label(name: &str) -> String {
+    let label: String =
+        name.trim().chars().map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' }).collect();
+    if label.is_empty() {
+        "CUSTOM".to_string()
+    } else {
+        label
+    }
+}
+
+pub fn redact_sensitive_content(text: &str, rules: &RedactionRules) -> Result<Redacted, AppError> {
+    let custom = rules
+        .custom
+        .iter()
+        .map(|custom| {
+            Regex::new(&custom.pattern)
+                .map(|regex| (placeholder_label(&custom.name), regex))
+                .map_err(|e| AppError::invalid_input(format!("Invalid redaction pattern \"{}\": {}", custom.name, e)))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Every match from every detector; where they overlap, the earliest one wins, then
+    // the longest, then custom patterns and the order of ALL_CATEGORIES
+    let mut matches: Vec<(usize, usize, usize, String)> = Vec::new();
+    for (priority, (label, regex)) in custom.iter().enumerate() {
+        let found = regex.find_iter(text).filter(|m| !m.is_empty());
+        matches.extend(found.map(|m| (m.start(), m.end(), priority, label.clone())));
+    }
+    for (index, category) in ALL_CATEGORIES.iter().enumerate().filter(|(_, c)| rules.categories.contains(c)) {
+        let found = builtin(*category).find_iter(text).filter(|m| {
+            let numeric = !matches!(category, PiiCategory::Email | PiiCategory::Iban);
+            category.accepts(m.as_str()) && !(numeric && inside_longer_number(text, m.start(), m.end()))
+        });
+        matches.extend(found.map(|m| (m.start(), m.end(), custom.len() + index, category.label().to_string())));
+    }
+    matches.sort_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(&a.1)).then(a.2.cmp(&b.2)));
+
+    let mut redacted = Redacted::default();
+    let mut numbers: HashMap<(String, &str), usize> = HashMap::new();
+    let mut end = 0;
+    for (start, stop, _, label) in matches {
+        if start < end {
+            continue;
+        }
+        redacted.text.push_str(&text[end..start]);
+        let count = redacted.counts.entry(label.clone()).or_default();
+        *count += 1;
+        let next = numbers.iter().filter(|((known, _), _)| *known == label).count() + 1;
+        let number = *numbers.entry((label.clone(), &text[start..stop])).or_insert(next);
+        redacted.text.push_str(&format!("[{}_{}]", label, number));
+        end = stop;
+    }
+    redacted.text.push_str(&text[end..]);
+    Ok(redacted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn redact(text: &str) -> Redacted {
+        redact_sensitive_content(text, &RedactionRules::default()).unwrap()
+    }
+
+    #[test]
+    fn common_pii_is_replaced_with_numbered_placeholders() {
+        let redacted = redact(
+            "Contact jane.doe@example.com or +1 (555) 123-4567. Again: jane.doe@example.com, bob@corp.io.\n\
+             SSN 123-45-6789, server 192.168.1.20, IBAN GB82 WEST 1234 5698 7654 32.",
+        );
+        assert_eq!(
+            redacted.text,
+            "Contact [EMAIL_1] or [PHONE_1]. Again: [EMAIL_1], [EMAIL_2].\n\
+             SSN [SSN_1], server [IP_1], IBAN [IBAN_1]."
+        );
+        assert_eq!(redacted.counts["EMAIL"], 3);
+        assert_eq!(redacted.counts["PHONE"], 1);
+        assert_eq!(redacted.counts.len(), 5);
+    }
+
+    #[test]
+    fn card_numbers_must_pass_luhn() {
+        let redacted = redact("Paid with 4111 1111 1111 1111, order 1234567812345678, invoice 4111-1111-1111-1112.");
+        assert_eq!(redacted.text, "Paid with [CARD_1], order 1234567812345678, invoice 4111-1111-1111-1112.");
+        assert_eq!(redacted.counts, BTreeMap::from([("CARD".to_string(), 1)]));
+        // Bare ids and impossible SSNs are left alone
+        assert_eq!(redact("Ticket 5551234567, ref 000-12-3456").text, "Ticket 5551234567, ref 000-12-3456");
+    }
+
+    #[test]
+    fn custom_patterns_and_category_selection() {
+        let rules = RedactionRules {
+            categories: vec![PiiCategory::Email],
+            custom: vec![CustomPattern { name: "project code".into(), pattern: r"PRJ-\d{4}".into() }],
+        };
+        let redacted = redact_sensitive_content("PRJ-0042 for a@b.co at 10.0.0.1", &rules).unwrap();
+        assert_eq!(redacted.text, "[PROJECT_CODE_1] for [EMAIL_1] at 10.0.0.1");
+
+        let invalid = RedactionRules { custom: vec![CustomPattern { name: "x".into(), pattern: "(".into() }], ..rules };
+        assert!(redact_sensitive_content("", &invalid).is_err());
+    }
+
+    #[test]
+    fn non_ascii_digits_are_not_mistaken_for_pii() {
+        let text = "Ref ١٢٣-٤٥-٦٧٨٩ and ०१२३ ४५६ ७८९०, card ４１１１ １１１１ １１１１ １１１１";
+        assert_eq!(redact(text).text, text);
+    }
+}
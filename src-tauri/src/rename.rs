@@ -0,0 +1,412 @@
+// Renaming files in place. New names are validated for the current OS before anything
+// is touched, the original extension is kept unless the new name brings its own, and
+// a name that's already taken is numbered ("name (2).ext"), skipped or reported.
+use crate::error::{AppError, AppErrorKind};
+use crate::file_tags;
+use crate::operations::CancelToken;
+use crate::paths;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Most file systems cap a single name at 255 bytes (UTF-16 units on NTFS)
+const MAX_NAME_BYTES: usize = 255;
+// Upper bound on "name (n).ext" before giving up
+pub const MAX_NUMBERED: u32 = 9_999;
+
+const WINDOWS_RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL",
+    "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9",
+    "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+const WINDOWS_ILLEGAL_CHARS: &[char] = &['<', '>', ':', '"', '/', '\\', '|', '?', '*'];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OnConflict {
+    // Append " (2)", " (3)", ... until the name is free
+    Number,
+    // Leave the file as it is
+    Skip,
+    // Fail with AlreadyExists
+    #[default]
+    Error,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RenameStatus {
+    Renamed,
+    // The new name was the current one
+    Unchanged,
+    // The name was taken and on_conflict was skip
+    Skipped,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RenameOutcome {
+    pub from: String,
+    pub to: String,
+    pub status: RenameStatus,
+    // Set for dry runs: the file was left as it is
+    pub simulated: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RenamePlanItem {
+    pub path: String,
+    pub new_name: String,
+}
+
+// Several files in one batch that would end up with the same name
+#[derive(Debug, Clone, Serialize)]
+pub struct RenameConflict {
+    pub target: String,
+    pub sources: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RenameFailure {
+    pub path: String,
+    pub error: AppError,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RenameBatchReport {
+    pub outcomes: Vec<RenameOutcome>,
+    // Non-empty conflicts, or failures found while planning, mean nothing was renamed
+    pub conflicts: Vec<RenameConflict>,
+    pub failures: Vec<RenameFailure>,
+    // Set when the batch was cancelled part way; skipped lists the files not reached
+    pub cancelled: bool,
+    pub skipped: Vec<String>,
+}
+
+pub fn validate_file_name(name: &str) -> Result<(), AppError> {
+    validate_file_name_for(name, cfg!(windows))
+}
+
+fn validate_file_name_for(name: &str, windows: bool) -> Result<(), AppError> {
+    let invalid = |reason: &str| Err(AppError::invalid_input(format!("Invalid file name \"{}\": {}", name, reason)));
+    if name.is_empty() || name.trim().is_empty() {
+        return invalid("the name is empty");
+    }
+    if name == "." || name == ".." {
+        return invalid("the name is reserved");
+    }
+    if name.contains('/') || name.contains('\0') {
+        return invalid("it contains a path separator or NUL character");
+    }
+    if name.len() > MAX_NAME_BYTES {
+        return invalid("the name is too long");
+    }
+    if windows {
+        if let Some(c) = name.chars().find(|c| WINDOWS_ILLEGAL_CHARS.contains(c) || (*c as u32) < 32) {
+            return invalid(&format!("Windows doesn't allow {:?} in file names", c));
+        }
+        if name.ends_with('.') || name.ends_with(' ') {
+            return invalid("Windows doesn't allow names ending in a dot or space");
+        }
+        // "CON", "con.txt" and "Nul.tar.gz" are all the device name as far as Windows is concerned
+        let base = name.split('.').next().unwrap_or(name).trim_end();
+        if WINDOWS_RESERVED_NAMES.iter().any(|reserved| reserved.eq_ignore_ascii_case(base)) {
+            return invalid("the name is reserved by Windows");
+        }
+    }
+    Ok(())
+}
+
+// A trailing ".xyz" counts as an extension when it's short and alphanumeric, so
+// "Report v1.2" keeps ".pdf" appended only if the user didn't type one
+fn has_explicit_extension(name: &str) -> bool {
+    Path::new(name).extension().is_some_and(|ext| {
+        let ext = ext.to_string_lossy();
+        (1..=10).contains(&ext.len()) && ext.chars().all(|c| c.is_ascii_alphanumeric())
+    })
+}
+
+fn target_name(source: &Path, new_name: &str) -> String {
+    match source.extension() {
+        Some(ext) if !has_explicit_extension(new_name) => format!("{}.{}", new_name, ext.to_string_lossy()),
+        _ => new_name.to_string(),
+    }
+}
+
+pub fn numbered(target: &Path, n: u32) -> PathBuf {
+    let stem = target.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    let name = match target.extension() {
+        Some(ext) => format!("{} ({}).{}", stem, n, ext.to_string_lossy()),
+        None => format!("{} ({})", stem, n),
+    };
+    target.with_file_name(name)
+}
+
+// Paths that name the same entry on case-insensitive file systems
+fn comparison_key(path: &Path) -> String {
+    let path = path.to_string_lossy();
+    if cfg!(any(windows, target_os = "macos")) {
+        path.to_lowercase()
+    } else {
+        path.to_string()
+    }
+}
+
+fn plan_target(source: &Path, new_name: &str) -> Result<PathBuf, AppError> {
+    let name = target_name(source, new_name);
+    validate_file_name(&name)?;
+    let metadata = fs::symlink_metadata(source)
+        .map_err(|e| AppError::from_io(&e, format!("Failed to read file: {}", e)))?;
+    if metadata.is_dir() {
+        return Err(AppError::invalid_input("Only files can be renamed"));
+    }
+    let target = source.with_file_name(name);
+    crate::destination_policy::check(&target)?;
+    Ok(target)
+}
+
+// Names a dry run has taken or freed, so later renames in the same batch see the
+// earlier ones as a real run would
+#[derive(Default)]
+struct Simulation {
+    claimed: HashSet<String>,
+    vacated: HashSet<String>,
+}
+
+fn name_taken(path: &Path, simulation: Option<&Simulation>) -> bool {
+    let key = comparison_key(path);
+    match simulation {
+        Some(sim) if sim.claimed.contains(&key) => true,
+        Some(sim) if sim.vacated.contains(&key) => false,
+        _ => fs::symlink_metadata(path).is_ok(),
+    }
+}
+
+// Real and simulated renames share every check; only the final fs::rename is skipped
+fn execute(
+    source: &Path,
+    target: &Path,
+    on_conflict: OnConflict,
+    mut simulation: Option<&mut Simulation>,
+) -> Result<RenameOutcome, AppError> {
+    let simulated = simulation.is_some();
+    let outcome = |to: &Path, status| RenameOutcome {
+        from: paths::display_path(source),
+        to: paths::display_path(to),
+        status,
+        simulated,
+    };
+    if source == target {
+        return Ok(outcome(target, RenameStatus::Unchanged));
+    }
+    // A case-only change ("photo.jpg" -> "Photo.jpg") finds the file itself on
+    // case-insensitive systems, which isn't a conflict
+    let same_entry = comparison_key(source) == comparison_key(target);
+    let mut destination = target.to_path_buf();
+    if !same_entry && name_taken(&destination, simulation.as_deref()) {
+        match on_conflict {
+            OnConflict::Skip => return Ok(outcome(source, RenameStatus::Skipped)),
+            OnConflict::Error => {
+                return Err(AppError::new(AppErrorKind::AlreadyExists, "A file with that name already exists")
+                    .with_path(paths::display_path(target)))
+            }
+            OnConflict::Number => {
+                destination = (2..=MAX_NUMBERED)
+                    .map(|n| numbered(target, n))
+                    .find(|candidate| !name_taken(candidate, simulation.as_deref()))
+                    .ok_or_else(|| {
+                        AppError::new(AppErrorKind::AlreadyExists, "No free numbered name is left")
+                            .with_path(paths::display_path(target))
+                    })?;
+            }
+        }
+    }
+    match simulation.as_mut() {
+        Some(sim) => {
+            sim.claimed.remove(&comparison_key(source));
+            sim.vacated.insert(comparison_key(source));
+            sim.claimed.insert(comparison_key(&destination));
+        }
+        None => {
+            fs::rename(source, &destination).map_err(|e| AppError::from(e).with_path(paths::display_path(source)))?;
+            file_tags::follow_move(source, &destination);
+        }
+    }
+    Ok(outcome(&destination, RenameStatus::Renamed))
+}
+
+pub fn rename_file(
+    path: &Path,
+    new_name: &str,
+    on_conflict: OnConflict,
+    dry_run: bool,
+) -> Result<RenameOutcome, AppError> {
+    let target = plan_target(path, new_name)?;
+    execute(path, &target, on_conflict, dry_run.then(Simulation::default).as_mut())
+}
+
+// Plans the whole batch first. Invalid names, missing files, and several files (or a
+// file and another file's current name) mapping to the same target are all reported
+// without renaming anything; otherwise each rename runs in order and failures of
+// individual files don't stop the rest.
+// `cancel` is checked before each file; renames already done are kept
+pub fn rename_files(
+    plan: &[RenamePlanItem],
+    on_conflict: OnConflict,
+    dry_run: bool,
+    cancel: Option<&CancelToken>,
+) -> RenameBatchReport {
+    let mut report = RenameBatchReport::default();
+    let mut planned: Vec<(PathBuf, PathBuf)> = Vec::new();
+    for item in plan {
+        let source = paths::resolve_path(&item.path);
+        match plan_target(&source, &item.new_name) {
+            Ok(target) => planned.push((source, target)),
+            Err(error) => report.failures.push(RenameFailure { path: item.path.clone(), error }),
+        }
+    }
+
+    let mut by_target: HashMap<String, Vec<&Path>> = HashMap::new();
+    for (source, target) in &planned {
+        by_target.entry(comparison_key(target)).or_default().push(source);
+    }
+    let current_names: HashMap<String, &Path> =
+        planned.iter().map(|(source, _)| (comparison_key(source), source.as_path())).collect();
+    let mut reported = HashSet::new();
+    for (_, target) in &planned {
+        let key = comparison_key(target);
+        let sources = &by_target[&key];
+        // Another file in the batch currently has this name; renaming onto it depends on order
+        let occupant = current_names.get(&key).filter(|occupant| !sources.contains(occupant));
+        if (sources.len() > 1 || occupant.is_some()) && reported.insert(key.clone()) {
+            report.conflicts.push(RenameConflict {
+                target: paths::display_path(target),
+                sources: sources.iter().chain(occupant).map(paths::display_path).collect(),
+            });
+        }
+    }
+    if !report.conflicts.is_empty() || !report.failures.is_empty() {
+        return report;
+    }
+
+    let mut simulation = dry_run.then(Simulation::default);
+    let mut planned = planned.into_iter();
+    while let Some((source, target)) = planned.next() {
+        if cancel.is_some_and(CancelToken::is_cancelled) {
+            report.cancelled = true;
+            report.skipped = std::iter::once(source).chain(planned.map(|(source, _)| source))
+                .map(paths::display_path)
+                .collect();
+            break;
+        }
+        match execute(&source, &target, on_conflict, simulation.as_mut()) {
+            Ok(outcome) => report.outcomes.push(outcome),
+            Err(error) => report.failures.push(RenameFailure { path: paths::display_path(&source), error }),
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_dir;
+
+    #[test]
+    fn windows_names_are_validated() {
+        for bad in ["CON", "con.txt", "Nul.tar.gz", "LPT1", "trip.", "trip ", "a:b", "why?", ""] {
+            assert!(validate_file_name_for(bad, true).is_err(), "{:?} should be rejected", bad);
+        }
+        assert!(validate_file_name_for("2023-07 Beach Trip 01.jpg", true).is_ok());
+        assert!(validate_file_name_for("console.log", true).is_ok());
+        assert!(validate_file_name_for("a:b", false).is_ok());
+        assert!(validate_file_name_for("a/b", false).is_err());
+    }
+
+    #[test]
+    fn extension_is_kept_unless_given() {
+        let source = Path::new("/photos/IMG_2034.jpg");
+        assert_eq!(target_name(source, "2023-07 Beach Trip 01"), "2023-07 Beach Trip 01.jpg");
+        assert_eq!(target_name(source, "beach.png"), "beach.png");
+        assert_eq!(target_name(source, "v1.2 notes"), "v1.2 notes.jpg");
+    }
+
+    #[test]
+    fn conflicts_are_numbered_skipped_or_reported() {
+        let dir = temp_dir("conflicts");
+        for name in ["a.txt", "b.txt", "c.txt", "taken.txt"] {
+            fs::write(dir.join(name), name).unwrap();
+        }
+
+        let err = rename_file(&dir.join("a.txt"), "taken", OnConflict::Error, false).unwrap_err();
+        assert_eq!(err.kind, AppErrorKind::AlreadyExists);
+
+        let skipped = rename_file(&dir.join("a.txt"), "taken", OnConflict::Skip, false).unwrap();
+        assert_eq!(skipped.status, RenameStatus::Skipped);
+        assert!(dir.join("a.txt").exists());
+
+        let first = rename_file(&dir.join("a.txt"), "taken", OnConflict::Number, false).unwrap();
+        let second = rename_file(&dir.join("b.txt"), "taken", OnConflict::Number, false).unwrap();
+        assert_eq!(first.to, paths::display_path(dir.join("taken (2).txt")));
+        assert_eq!(second.to, paths::display_path(dir.join("taken (3).txt")));
+        assert_eq!(fs::read_to_string(dir.join("taken.txt")).unwrap(), "taken.txt");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn batch_conflicts_stop_the_whole_batch() {
+        let dir = temp_dir("batch");
+        for name in ["a.txt", "b.txt", "c.txt"] {
+            fs::write(dir.join(name), name).unwrap();
+        }
+        let item = |from: &str, to: &str| RenamePlanItem {
+            path: paths::display_path(dir.join(from)),
+            new_name: to.to_string(),
+        };
+
+        let plan = [item("a.txt", "same"), item("b.txt", "same"), item("c.txt", "other")];
+        let report = rename_files(&plan, OnConflict::Number, false, None);
+        assert_eq!(report.conflicts.len(), 1);
+        assert_eq!(report.conflicts[0].sources.len(), 2);
+        assert!(report.outcomes.is_empty());
+        assert!(dir.join("c.txt").exists());
+
+        let report = rename_files(&[item("a.txt", "first"), item("b.txt", "second")], OnConflict::Error, false, None);
+        assert!(report.conflicts.is_empty() && report.failures.is_empty());
+        assert_eq!(report.outcomes.len(), 2);
+        assert!(dir.join("first.txt").exists() && dir.join("second.txt").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn dry_runs_report_the_outcome_without_renaming() {
+        let dir = temp_dir("dry-run");
+        fs::write(dir.join("a.txt"), b"a").unwrap();
+        fs::write(dir.join("taken.txt"), b"t").unwrap();
+        let plan = [RenamePlanItem { path: paths::display_path(dir.join("a.txt")), new_name: "taken".to_string() }];
+
+        let report = rename_files(&plan, OnConflict::Number, true, None);
+        let outcome = &report.outcomes[0];
+        assert!(outcome.simulated);
+        assert_eq!(outcome.to, paths::display_path(dir.join("taken (2).txt")));
+        assert!(dir.join("a.txt").exists() && !dir.join("taken (2).txt").exists());
+        let err = rename_file(&dir.join("a.txt"), "taken", OnConflict::Error, true).unwrap_err();
+        assert_eq!(err.kind, AppErrorKind::AlreadyExists);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn cancelled_batches_stop_before_the_next_file() {
+        let dir = temp_dir("cancel");
+        fs::write(dir.join("a.txt"), b"a").unwrap();
+        let plan = [RenamePlanItem { path: paths::display_path(dir.join("a.txt")), new_name: "b".to_string() }];
+        let token = CancelToken::default();
+        token.cancel();
+
+        let report = rename_files(&plan, OnConflict::Error, false, Some(&token));
+        assert!(report.cancelled && report.outcomes.is_empty());
+        assert_eq!(report.skipped, vec![paths::display_path(dir.join("a.txt"))]);
+        assert!(dir.join("a.txt").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,415 @@
+// Organization plans run by the backend, remembered so a crash or quit halfway through
+// a thousand moves leaves a record of what remained. Before the first move the whole
+// plan goes to app_data_dir/runs/<run_id>.plan.json, next to a small cursor file saying
+// how far the run got. The cursor is rewritten every few moves rather than after each
+// one, so after a crash it can be a little behind; resuming checks every remaining
+// move again, and the batch journal tells a move that already happened from a source
+// that went missing. A run that finishes deletes both files.
+use crate::error::{AppError, AppErrorKind};
+use crate::moves::{self, Resolution};
+use crate::paths;
+use crate::rename::OnConflict;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+pub const RUN_VERSION: u32 = 1;
+pub const CURSOR_EVERY: usize = 25;
+// The cursor is saved at least this often while moves are completing
+const CURSOR_INTERVAL: Duration = Duration::from_secs(2);
+const RUN_DIR: &str = "runs";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlannedMove {
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunPlan {
+    pub version: u32,
+    pub run_id: String,
+    // Journal the moves are recorded under
+    pub batch_id: String,
+    // Unix time in milliseconds
+    pub created_at: u64,
+    pub on_conflict: OnConflict,
+    pub capture_hash: bool,
+    pub moves: Vec<PlannedMove>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RunCursor {
+    // Index of the first move not yet done
+    pub next: usize,
+    // Moves done so far, including ones found already done on resume
+    pub moved: usize,
+    pub skipped: usize,
+    pub failed: usize,
+    pub updated_at: u64,
+    // Launch of the app running it (see session_id); a run owned by an earlier launch
+    // was interrupted. Cursors written with the old owner_pid field load as unowned.
+    pub owner_session: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct InterruptedRun {
+    pub run_id: String,
+    pub batch_id: String,
+    pub created_at: u64,
+    pub updated_at: u64,
+    pub total: usize,
+    pub next: usize,
+    pub moved: usize,
+    pub skipped: usize,
+    pub failed: usize,
+}
+
+// How one move of a run ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Step {
+    Moved,
+    Skipped,
+    Failed,
+}
+
+fn plan_path(app_data_dir: &Path, run_id: &str) -> PathBuf {
+    app_data_dir.join(RUN_DIR).join(format!("{}.plan.json", run_id))
+}
+
+fn cursor_path(app_data_dir: &Path, run_id: &str) -> PathBuf {
+    app_data_dir.join(RUN_DIR).join(format!("{}.cursor.json", run_id))
+}
+
+// Run ids name files like batch ids do
+pub fn validate_id(run_id: &str) -> Result<(), AppError> {
+    crate::journal::validate_batch_id(run_id)
+        .map_err(|_| AppError::invalid_input(format!("Invalid run id \"{}\"", run_id)))
+}
+
+// Written next to `path` and renamed over it, without an fsync: a crash loses at most
+// the last few moves of progress, which resuming finds again
+fn write_json<T: Serialize>(path: &Path, value: &T) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| format!("Failed to create run directory: {}", e))?;
+    }
+    let json = serde_json::to_vec(value).map_err(|e| format!("Failed to serialize {}: {}", path.display(), e))?;
+    let temp = path.with_extension("json.tmp");
+    std::fs::write(&temp, json).map_err(|e| format!("Failed to write {}: {}", temp.display(), e))?;
+    std::fs::rename(&temp, path).map_err(|e| {
+        let _ = std::fs::remove_file(&temp);
+        format!("Failed to replace {}: {}", path.display(), e)
+    })
+}
+
+pub fn load(app_data_dir: &Path, run_id: &str) -> Result<(RunPlan, RunCursor), AppError> {
+    validate_id(run_id)?;
+    let read = |path: PathBuf| {
+        std::fs::read_to_string(path).map_err(|e| AppError::from_io(&e, format!("No run {}: {}", run_id, e)))
+    };
+    let invalid = |e: serde_json::Error| AppError::invalid_input(format!("Run {} is damaged: {}", run_id, e));
+    let plan: RunPlan = serde_json::from_str(&read(plan_path(app_data_dir, run_id))?).map_err(invalid)?;
+    if plan.version != RUN_VERSION {
+        return Err(AppError::unsupported(format!(
+            "Run {} was saved in format {} and this version of the app reads format {}",
+            run_id, plan.version, RUN_VERSION
+        )));
+    }
+    let cursor = serde_json::from_str(&read(cursor_path(app_data_dir, run_id))?).map_err(invalid)?;
+    Ok((plan, cursor))
+}
+
+pub fn delete(app_data_dir: &Path, run_id: &str) -> Result<bool, AppError> {
+    validate_id(run_id)?;
+    let mut found = false;
+    for path in [cursor_path(app_data_dir, run_id), plan_path(app_data_dir, run_id)] {
+        match std::fs::remove_file(path) {
+            Ok(()) => found = true,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(AppError::from(e)),
+        }
+    }
+    Ok(found)
+}
+
+// Random id of this launch. Not the PID: after a crash and reboot the new process can
+// get the old one's PID and would take a dead run for its own.
+fn session_id() -> &'static str {
+    static SESSION: OnceLock<String> = OnceLock::new();
+    SESSION.get_or_init(|| {
+        let mut bytes = [0u8; 16];
+        if let Err(e) = getrandom::getrandom(&mut bytes) {
+            warn!("No randomness for the run session id, using the clock: {}", e);
+            let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos();
+            bytes = nanos.to_le_bytes();
+        }
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    })
+}
+
+// Runs not being run by this launch, newest first; unreadable ones are logged and left out
+pub fn list_interrupted(app_data_dir: &Path) -> Vec<InterruptedRun> {
+    list_interrupted_for(app_data_dir, session_id())
+}
+
+fn list_interrupted_for(app_data_dir: &Path, session: &str) -> Vec<InterruptedRun> {
+    let Ok(entries) = std::fs::read_dir(app_data_dir.join(RUN_DIR)) else { return Vec::new() };
+    let mut runs: Vec<InterruptedRun> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let run_id = name.strip_suffix(".plan.json")?;
+            load(app_data_dir, run_id).map_err(|e| warn!("Skipping run {}: {}", run_id, e.message)).ok()
+        })
+        .filter(|(_, cursor)| cursor.owner_session.as_deref() != Some(session))
+        .map(|(plan, cursor)| InterruptedRun {
+            run_id: plan.run_id,
+            batch_id: plan.batch_id,
+            created_at: plan.created_at,
+            updated_at: cursor.updated_at,
+            total: plan.moves.len(),
+            next: cursor.next,
+            moved: cursor.moved,
+            skipped: cursor.skipped,
+            failed: cursor.failed,
+        })
+        .collect();
+    runs.sort_by(|a, b| b.updated_at.cmp(&a.updated_at).then_with(|| a.run_id.cmp(&b.run_id)));
+    runs
+}
+
+// What to do with a move that's still ahead of the cursor
+#[derive(Debug)]
+pub enum Remaining {
+    Move(PathBuf),
+    // The journal has it and the source is gone: it happened before the cursor was saved
+    AlreadyMoved,
+    // The destination is taken and the plan said to skip
+    Skip,
+    Invalid(AppError),
+}
+
+// Checks a move again before it's made: the source has to still be there and the
+// destination still has to pass the destination policy and the other move checks.
+// `journaled` holds the sources the batch journal already records as moved.
+pub fn check_remaining(planned: &PlannedMove, on_conflict: OnConflict, journaled: &HashSet<String>) -> Remaining {
+    let from = paths::resolve_path(&planned.from);
+    let to = paths::normalize_path(&planned.to);
+    if let Err(e) = std::fs::symlink_metadata(&from) {
+        if e.kind() == std::io::ErrorKind::NotFound && journaled.contains(&paths::display_path(&from)) {
+            return Remaining::AlreadyMoved;
+        }
+        return Remaining::Invalid(AppError::from(e).with_path(paths::display_path(&from)));
+    }
+    let destination = match moves::resolve_destination(&from, &to, false, on_conflict) {
+        Ok(Resolution::Move(destination, _)) => destination,
+        Ok(Resolution::Skip | Resolution::Duplicate(_)) => return Remaining::Skip,
+        Err(e) => return Remaining::Invalid(e),
+    };
+    match moves::validate_move(&from, &destination) {
+        Ok(()) => Remaining::Move(destination),
+        Err(e) => Remaining::Invalid(e),
+    }
+}
+
+// Keeps a run's cursor file up to date while it runs, saving every `every` moves or
+// CURSOR_INTERVAL, whichever comes first
+pub struct RunCursorWriter {
+    app_data_dir: PathBuf,
+    run_id: String,
+    cursor: RunCursor,
+    every: usize,
+    unsaved: usize,
+    saved_at: Instant,
+}
+
+impl RunCursorWriter {
+    // Saves the plan and a cursor at its start; nothing should be moved if this fails
+    pub fn start(app_data_dir: &Path, plan: &RunPlan, every: usize) -> Result<Self, AppError> {
+        validate_id(&plan.run_id)?;
+        write_json(&plan_path(app_data_dir, &plan.run_id), plan).map_err(AppError::io)?;
+        let cursor = RunCursor {
+            next: 0,
+            moved: 0,
+            skipped: 0,
+            failed: 0,
+            updated_at: crate::journal::now_ms(),
+            owner_session: None,
+        };
+        Self::resume(app_data_dir, &plan.run_id, cursor, every)
+    }
+
+    // Takes over an interrupted run's cursor; fails if this launch is already running it
+    pub fn resume(app_data_dir: &Path, run_id: &str, mut cursor: RunCursor, every: usize) -> Result<Self, AppError> {
+        if cursor.owner_session.as_deref() == Some(session_id()) {
+            return Err(AppError::new(AppErrorKind::AlreadyExists, format!("Run {} is already running", run_id)));
+        }
+        cursor.owner_session = Some(session_id().to_string());
+        let mut writer = Self {
+            app_data_dir: app_data_dir.to_path_buf(),
+            run_id: run_id.to_string(),
+            cursor,
+            every: every.max(1),
+            unsaved: 1,
+            saved_at: Instant::now(),
+        };
+        writer.save().map_err(AppError::io)?;
+        Ok(writer)
+    }
+
+    pub fn cursor(&self) -> &RunCursor {
+        &self.cursor
+    }
+
+    // Records the move at the cursor as done and moves the cursor past it
+    pub fn advance(&mut self, step: Step) {
+        match step {
+            Step::Moved => self.cursor.moved += 1,
+            Step::Skipped => self.cursor.skipped += 1,
+            Step::Failed => self.cursor.failed += 1,
+        }
+        self.cursor.next += 1;
+        self.unsaved += 1;
+        if self.unsaved >= self.every || self.saved_at.elapsed() >= CURSOR_INTERVAL {
+            self.flush();
+        }
+    }
+
+    fn save(&mut self) -> Result<(), String> {
+        self.cursor.updated_at = crate::journal::now_ms();
+        write_json(&cursor_path(&self.app_data_dir, &self.run_id), &self.cursor)?;
+        self.unsaved = 0;
+        self.saved_at = Instant::now();
+        Ok(())
+    }
+
+    pub fn flush(&mut self) {
+        if self.unsaved == 0 {
+            return;
+        }
+        if let Err(e) = self.save() {
+            warn!("Failed to save the cursor of run {}: {}", self.run_id, e);
+        }
+    }
+
+    // Stopped before the end (cancelled): saved without an owner so it can be resumed
+    pub fn stop(mut self) {
+        self.cursor.owner_session = None;
+        self.unsaved += 1;
+        self.flush();
+    }
+
+    // Every move was tried, so there's nothing left to resume
+    pub fn finish(self) {
+        if let Err(e) = delete(&self.app_data_dir, &self.run_id) {
+            warn!("Failed to delete run {}: {}", self.run_id, e.message);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_dir;
+    use std::fs;
+
+    fn plan(run_id: &str, count: usize) -> RunPlan {
+        RunPlan {
+            version: RUN_VERSION,
+            run_id: run_id.to_string(),
+            batch_id: "batch-1".to_string(),
+            created_at: 1,
+            on_conflict: OnConflict::Error,
+            capture_hash: false,
+            moves: (0..count)
+                .map(|i| PlannedMove { from: format!("/in/{}.txt", i), to: format!("/out/{}.txt", i) })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn a_crashed_run_is_listed_with_its_last_saved_cursor() {
+        let dir = temp_dir("cursor");
+        let mut writer = RunCursorWriter::start(&dir, &plan("run-1", 10), 3).unwrap();
+        // Running in this launch: not interrupted
+        assert!(list_interrupted(&dir).is_empty());
+        for step in [Step::Moved, Step::Failed, Step::Moved, Step::Skipped] {
+            writer.advance(step);
+        }
+        assert_eq!(writer.cursor().next, 4);
+        // The crash: the fourth move never reached the file, and the owner is gone
+        drop(writer);
+        let runs = list_interrupted_for(&dir, "next-launch");
+        assert_eq!(runs.len(), 1);
+        let run = &runs[0];
+        assert_eq!((run.total, run.next, run.moved, run.failed, run.skipped), (10, 3, 2, 1, 0));
+
+        let (loaded, mut cursor) = load(&dir, "run-1").unwrap();
+        assert_eq!(loaded.moves, plan("run-1", 10).moves);
+        assert!(RunCursorWriter::resume(&dir, "run-1", cursor.clone(), 3).is_err());
+        cursor.owner_session = None;
+        let writer = RunCursorWriter::resume(&dir, "run-1", cursor, 3).unwrap();
+        writer.stop();
+        assert_eq!(list_interrupted(&dir).len(), 1);
+        let (_, cursor) = load(&dir, "run-1").unwrap();
+        RunCursorWriter::resume(&dir, "run-1", cursor, 3).unwrap().finish();
+        assert!(list_interrupted_for(&dir, "next-launch").is_empty());
+        assert!(load(&dir, "../settings").is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn a_run_from_an_earlier_launch_is_interrupted_whatever_its_pid() {
+        let dir = temp_dir("earlier-launch");
+        RunCursorWriter::start(&dir, &plan("run-1", 2), 1).unwrap();
+        // As left by a crashed launch whose PID this process happened to get
+        let stale = format!(
+            r#"{{"next":1,"moved":1,"skipped":0,"failed":0,"updated_at":5,"owner_pid":{}}}"#,
+            std::process::id()
+        );
+        fs::write(cursor_path(&dir, "run-1"), stale).unwrap();
+        assert_eq!(list_interrupted(&dir).len(), 1);
+        let (_, mut cursor) = load(&dir, "run-1").unwrap();
+        assert_eq!(cursor.owner_session, None);
+        cursor.owner_session = Some("earlier-launch".to_string());
+        let writer = RunCursorWriter::resume(&dir, "run-1", cursor, 1).unwrap();
+        assert_eq!(writer.cursor().next, 1);
+        assert!(list_interrupted(&dir).is_empty());
+        writer.finish();
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn remaining_moves_are_checked_again_before_resuming() {
+        let dir = temp_dir("remaining");
+        fs::create_dir_all(dir.join("out")).unwrap();
+        fs::write(dir.join("a.txt"), b"a").unwrap();
+        fs::write(dir.join("b.txt"), b"b").unwrap();
+        fs::write(dir.join("out/b.txt"), b"taken").unwrap();
+        let planned = |from: &str, to: &str| PlannedMove {
+            from: paths::display_path(dir.join(from)),
+            to: paths::display_path(dir.join(to)),
+        };
+        let journaled: HashSet<String> = [paths::display_path(dir.join("moved.txt"))].into();
+        let check = |from, to, on_conflict| check_remaining(&planned(from, to), on_conflict, &journaled);
+
+        let free = dir.join("out/a.txt");
+        assert!(matches!(check("a.txt", "out/a.txt", OnConflict::Error), Remaining::Move(to) if to == free));
+        assert!(matches!(check("moved.txt", "out/moved.txt", OnConflict::Error), Remaining::AlreadyMoved));
+        match check("gone.txt", "out/gone.txt", OnConflict::Error) {
+            Remaining::Invalid(e) => assert_eq!(e.kind, AppErrorKind::NotFound),
+            other => panic!("{:?}", other),
+        }
+        assert!(matches!(check("b.txt", "out/b.txt", OnConflict::Skip), Remaining::Skip));
+        let numbered = dir.join("out/b (2).txt");
+        assert!(matches!(check("b.txt", "out/b.txt", OnConflict::Number), Remaining::Move(to) if to == numbered));
+        match check("b.txt", "out/b.txt", OnConflict::Error) {
+            Remaining::Invalid(e) => assert_eq!(e.kind, AppErrorKind::AlreadyExists),
+            other => panic!("{:?}", other),
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
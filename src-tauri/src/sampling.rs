@@ -0,0 +1,273 @@
+// Picks a small, representative set of files to classify before committing to a whole
+// directory, so a remote model's results (and cost) can be judged on a sample first.
+// Stratified sampling groups files by extension and size bucket and draws from every
+// group in proportion to its size. Draws come from a seeded generator, so the same seed
+// over the same files gives the same sample, e.g. to compare two models.
+use crate::cloud_files;
+use crate::composition;
+use crate::paths;
+use crate::scan_filter::ScanFilter;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+use walkdir::WalkDir;
+
+// Upper bounds of the size buckets; larger files share the last one
+const SIZE_BUCKETS: &[u64] = &[16 * 1024, 1024 * 1024, 16 * 1024 * 1024];
+// Seeds stay below 2^53 so they survive the round trip through a JavaScript number
+const MAX_SEED: u64 = (1 << 53) - 1;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SampleStrategy {
+    #[default]
+    Stratified,
+    Random,
+    Largest,
+    Newest,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SampledFile {
+    pub path: String,
+    pub size: u64,
+    // Lowercase, without the dot; empty when the file has none
+    pub extension: String,
+    pub category: String,
+    pub modified_ms: u64,
+}
+
+// How a category is represented in the sample compared to the whole directory
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct SampleCategory {
+    pub category: String,
+    pub sampled: usize,
+    pub population: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ClassificationSample {
+    pub strategy: SampleStrategy,
+    // Pass back to draw the same sample again
+    pub seed: u64,
+    pub population: usize,
+    pub files: Vec<SampledFile>,
+    // Largest categories of the population first
+    pub composition: Vec<SampleCategory>,
+    // Cloud placeholders left out because classifying them would download them
+    pub cloud_placeholders: usize,
+}
+
+// SplitMix64: small, fast and identical on every platform
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            items.swap(i, self.below(i + 1));
+        }
+    }
+}
+
+pub fn new_seed() -> u64 {
+    let nanos = std::time::SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos()).unwrap_or(0);
+    (nanos as u64) & MAX_SEED
+}
+
+fn size_bucket(size: u64) -> usize {
+    SIZE_BUCKETS.iter().position(|&limit| size < limit).unwrap_or(SIZE_BUCKETS.len())
+}
+
+// Every readable file under `root`, sorted by path so walk order can't change a sample.
+// Returns the files and the number of cloud placeholders left out.
+pub fn candidates(
+    root: &Path,
+    include_subdirectories: bool,
+    filter: &ScanFilter,
+    table: &HashMap<String, String>,
+) -> (Vec<SampledFile>, usize) {
+    let hydrate = cloud_files::hydrate_by_default();
+    let mut cloud_placeholders = 0;
+    let walker = WalkDir::new(root).max_depth(if include_subdirectories { usize::MAX } else { 1 });
+    let mut files: Vec<SampledFile> = walker
+        .into_iter()
+        .filter_entry(|e| e.depth() == 0 || !crate::walk_entry_skipped(filter, e))
+        .filter_map(crate::walk_entry_ok)
+        .filter(|entry| entry.file_type().is_file())
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok()?;
+            if !hydrate && cloud_files::is_placeholder_metadata(&metadata) {
+                cloud_placeholders += 1;
+                return None;
+            }
+            let modified_ms = metadata
+                .modified()
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |d| d.as_millis() as u64);
+            Some(SampledFile {
+                path: paths::display_path(entry.path()),
+                size: metadata.len(),
+                extension: entry.path().extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default(),
+                category: composition::category_of(entry.path(), table),
+                modified_ms,
+            })
+        })
+        .collect();
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    (files, cloud_placeholders)
+}
+
+// How many files each stratum contributes: one each while there's room (largest strata
+// first when there isn't), the rest in proportion to what's left of each, rounding by
+// largest remainder
+fn allocate(strata_sizes: &[usize], sample_size: usize) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..strata_sizes.len()).collect();
+    order.sort_by(|&a, &b| strata_sizes[b].cmp(&strata_sizes[a]).then(a.cmp(&b)));
+    let mut counts = vec![0; strata_sizes.len()];
+    for &i in order.iter().take(sample_size) {
+        counts[i] = 1;
+    }
+    let remaining = sample_size.saturating_sub(strata_sizes.len());
+    let spare: Vec<usize> = strata_sizes.iter().map(|size| size.saturating_sub(1)).collect();
+    let spare_total: usize = spare.iter().sum();
+    if remaining == 0 || spare_total == 0 {
+        return counts;
+    }
+    let mut remainders = Vec::with_capacity(spare.len());
+    let mut given = 0;
+    for (i, &extra) in spare.iter().enumerate() {
+        let exact = remaining * extra;
+        counts[i] += exact / spare_total;
+        given += exact / spare_total;
+        remainders.push((exact % spare_total, i));
+    }
+    remainders.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+    for &(_, i) in remainders.iter().take(remaining - given) {
+        counts[i] += 1;
+    }
+    counts
+}
+
+pub fn sample(
+    mut files: Vec<SampledFile>,
+    sample_size: usize,
+    strategy: SampleStrategy,
+    seed: u64,
+) -> Vec<SampledFile> {
+    let mut rng = Rng(seed);
+    let sample_size = sample_size.min(files.len());
+    match strategy {
+        SampleStrategy::Random => {
+            rng.shuffle(&mut files);
+            files.truncate(sample_size);
+            files
+        }
+        SampleStrategy::Largest => {
+            files.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.path.cmp(&b.path)));
+            files.truncate(sample_size);
+            files
+        }
+        SampleStrategy::Newest => {
+            files.sort_by(|a, b| b.modified_ms.cmp(&a.modified_ms).then_with(|| a.path.cmp(&b.path)));
+            files.truncate(sample_size);
+            files
+        }
+        SampleStrategy::Stratified => {
+            let mut strata: BTreeMap<(String, usize), Vec<SampledFile>> = BTreeMap::new();
+            for file in files {
+                strata.entry((file.extension.clone(), size_bucket(file.size))).or_default().push(file);
+            }
+            let sizes: Vec<usize> = strata.values().map(Vec::len).collect();
+            let counts = allocate(&sizes, sample_size);
+            let mut chosen = Vec::with_capacity(sample_size);
+            for (mut stratum, count) in strata.into_values().zip(counts) {
+                rng.shuffle(&mut stratum);
+                chosen.extend(stratum.into_iter().take(count));
+            }
+            chosen.sort_by(|a, b| a.path.cmp(&b.path));
+            chosen
+        }
+    }
+}
+
+pub fn composition_of(population: &[SampledFile], sampled: &[SampledFile]) -> Vec<SampleCategory> {
+    let mut categories: HashMap<&str, SampleCategory> = HashMap::new();
+    for file in population {
+        categories
+            .entry(&file.category)
+            .or_insert_with(|| SampleCategory { category: file.category.clone(), sampled: 0, population: 0 })
+            .population += 1;
+    }
+    for file in sampled {
+        if let Some(category) = categories.get_mut(file.category.as_str()) {
+            category.sampled += 1;
+        }
+    }
+    let mut composition: Vec<SampleCategory> = categories.into_values().collect();
+    composition.sort_by(|a, b| b.population.cmp(&a.population).then_with(|| a.category.cmp(&b.category)));
+    composition
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(path: &str, size: u64, modified_ms: u64) -> SampledFile {
+        let extension = path.rsplit_once('.').map(|(_, ext)| ext.to_string()).unwrap_or_default();
+        let category = if extension == "jpg" { "images" } else { "documents" }.to_string();
+        SampledFile { path: path.to_string(), size, extension, category, modified_ms }
+    }
+
+    fn population() -> Vec<SampledFile> {
+        let mut files: Vec<SampledFile> = (0..80).map(|i| file(&format!("/p/{:02}.jpg", i), 2_000_000, i)).collect();
+        files.extend((0..18).map(|i| file(&format!("/d/{:02}.pdf", i), 100_000, 100 + i)));
+        files.push(file("/d/huge.pdf", 90_000_000, 1));
+        files.push(file("/d/notes.txt", 10, 2));
+        files
+    }
+
+    #[test]
+    fn allocation_covers_every_stratum_and_stays_proportional() {
+        assert_eq!(allocate(&[80, 18, 1, 1], 10), [6, 2, 1, 1]);
+        assert_eq!(allocate(&[80, 18, 1, 1], 2), [1, 1, 0, 0]);
+        assert_eq!(allocate(&[3, 3], 6), [3, 3]);
+        assert_eq!(allocate(&[5], 0), [0]);
+    }
+
+    #[test]
+    fn samples_are_reproducible_and_follow_the_strategy() {
+        let stratified = sample(population(), 10, SampleStrategy::Stratified, 7);
+        assert_eq!(stratified.len(), 10);
+        // Rare kinds of file are still represented
+        assert!(stratified.iter().any(|f| f.path == "/d/huge.pdf"));
+        assert!(stratified.iter().any(|f| f.path == "/d/notes.txt"));
+        let paths = |files: &[SampledFile]| files.iter().map(|f| f.path.clone()).collect::<Vec<_>>();
+        assert_eq!(paths(&stratified), paths(&sample(population(), 10, SampleStrategy::Stratified, 7)));
+        assert_ne!(
+            paths(&sample(population(), 10, SampleStrategy::Random, 1)),
+            paths(&sample(population(), 10, SampleStrategy::Random, 2))
+        );
+
+        assert_eq!(sample(population(), 1, SampleStrategy::Largest, 0)[0].path, "/d/huge.pdf");
+        assert_eq!(sample(population(), 1, SampleStrategy::Newest, 0)[0].path, "/d/17.pdf");
+        assert_eq!(sample(population(), 500, SampleStrategy::Random, 0).len(), 100);
+
+        let composition = composition_of(&population(), &stratified);
+        assert_eq!(composition[0], SampleCategory { category: "images".into(), sampled: 6, population: 80 });
+        assert_eq!(composition[1].sampled, 4);
+    }
+}
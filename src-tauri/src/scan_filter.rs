@@ -0,0 +1,98 @@
+// Which entries directory walks skip: hidden files unless they were asked for, and a
+// list of well-known junk names (Finder, Explorer and Spotlight metadata). Tag sidecars
+// written by file_tags and staging folders holding files for review are always skipped.
+use crate::file_tags;
+use crate::settings;
+use crate::staging;
+use std::fs::Metadata;
+use std::path::Path;
+
+// Matched case-insensitively against file and directory names
+pub const DEFAULT_IGNORED_NAMES: &[&str] = &[
+    ".DS_Store", "Thumbs.db", "desktop.ini",
+    "ehthumbs.db", "ehthumbs_vista.db",
+    ".LSOverride", "Icon\r",
+    ".Spotlight-V100", ".Trashes", ".fseventsd",
+    ".DocumentRevisions-V100", ".TemporaryItems",
+    "$RECYCLE.BIN", "System Volume Information",
+    "__MACOSX", ".AppleDouble",
+];
+
+#[derive(Debug, Clone)]
+pub struct ScanFilter {
+    include_hidden: bool,
+    ignored_names: Vec<String>,
+}
+
+impl Default for ScanFilter {
+    fn default() -> Self {
+        Self::new(None, None)
+    }
+}
+
+impl ScanFilter {
+    // ignored_names replaces the default junk list; pass an empty list to keep everything.
+    // Arguments left out fall back to the saved scan settings.
+    pub fn new(include_hidden: Option<bool>, ignored_names: Option<Vec<String>>) -> Self {
+        let saved = settings::with(|settings| settings.scan.clone());
+        Self {
+            include_hidden: include_hidden.unwrap_or(saved.include_hidden),
+            ignored_names: ignored_names.or(saved.ignored_names).unwrap_or_else(|| {
+                DEFAULT_IGNORED_NAMES.iter().map(|name| name.to_string()).collect()
+            }),
+        }
+    }
+
+    // Whether an entry should be left out of a listing. `metadata` is needed for the
+    // hidden check on Windows; entries without it are treated as not hidden there.
+    pub fn skips(&self, name: &str, metadata: Option<&Metadata>) -> bool {
+        let ignored = self.ignored_names.iter().any(|ignored| ignored.eq_ignore_ascii_case(name));
+        if ignored || name == staging::STAGING_FOLDER || file_tags::is_sidecar(Path::new(name)) {
+            return true;
+        }
+        !self.include_hidden && is_hidden(name, metadata)
+    }
+}
+
+// Dot-prefixed names on Unix; the hidden or system attribute on Windows
+#[cfg(not(windows))]
+pub fn is_hidden(name: &str, _metadata: Option<&Metadata>) -> bool {
+    name.starts_with('.')
+}
+
+#[cfg(windows)]
+pub fn is_hidden(_name: &str, metadata: Option<&Metadata>) -> bool {
+    use std::os::windows::fs::MetadataExt;
+    const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+    const FILE_ATTRIBUTE_SYSTEM: u32 = 0x4;
+    metadata.is_some_and(|m| m.file_attributes() & (FILE_ATTRIBUTE_HIDDEN | FILE_ATTRIBUTE_SYSTEM) != 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn junk_names_are_ignored_regardless_of_case_or_hidden_setting() {
+        let filter = ScanFilter::new(Some(true), None);
+        assert!(filter.skips(".DS_Store", None));
+        assert!(filter.skips("thumbs.db", None));
+        assert!(filter.skips("Desktop.ini", None));
+        assert!(!filter.skips("report.pdf", None));
+    }
+
+    #[test]
+    fn the_junk_list_can_be_overridden() {
+        let filter = ScanFilter::new(Some(true), Some(vec!["notes.tmp".to_string()]));
+        assert!(!filter.skips("Thumbs.db", None));
+        assert!(filter.skips("NOTES.TMP", None));
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn dotfiles_are_hidden_unless_included() {
+        assert!(ScanFilter::default().skips(".env", None));
+        assert!(!ScanFilter::new(Some(true), None).skips(".env", None));
+        assert!(ScanFilter::new(Some(true), Some(vec![])).skips(".a.pdf.fileorganizer-tags.json", None));
+    }
+}
@@ -0,0 +1,478 @@
+// Scheduled directory scans: schedule definitions, when they are next due, and
+// where their results are kept. The background runner lives in main.rs.
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, NaiveDateTime, TimeZone, Timelike};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+const SCHEDULES_FILE: &str = "schedules.json";
+const RESULTS_DIR: &str = "scheduled-scans";
+const MAX_ID_LEN: usize = 64;
+pub const DEFAULT_KEEP_RESULTS: usize = 30;
+// Listing every new file would make reports for busy folders huge
+pub const MAX_NEW_FILES_LISTED: usize = 500;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum ScheduleSpec {
+    Interval { seconds: u64 },
+    Cron { expression: String },
+}
+
+impl ScheduleSpec {
+    // Accepts an interval such as "30m", "6h", "1d" or "every 12h", otherwise a
+    // five-field cron expression ("0 2 * * *" is every night at 02:00)
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let trimmed = input.trim();
+        let interval = trimmed.strip_prefix("every ").unwrap_or(trimmed).trim();
+        if let Some(seconds) = parse_interval(interval) {
+            if seconds < 60 {
+                return Err("Scan interval must be at least one minute".to_string());
+            }
+            return Ok(ScheduleSpec::Interval { seconds });
+        }
+        CronSchedule::parse(trimmed)?;
+        Ok(ScheduleSpec::Cron { expression: trimmed.to_string() })
+    }
+
+    // First run strictly after `after`, in unix seconds
+    pub fn next_run_after(&self, after: u64) -> Option<u64> {
+        match self {
+            ScheduleSpec::Interval { seconds } => Some(after + seconds),
+            ScheduleSpec::Cron { expression } => {
+                let cron = CronSchedule::parse(expression).ok()?;
+                let after = Local.timestamp_opt(after as i64, 0).single()?;
+                cron.next_after(after).map(|next| next.timestamp() as u64)
+            }
+        }
+    }
+}
+
+fn parse_interval(input: &str) -> Option<u64> {
+    let split = input.find(|c: char| !c.is_ascii_digit())?;
+    let (amount, unit) = input.split_at(split);
+    let amount: u64 = amount.parse().ok()?;
+    let multiplier = match unit.trim() {
+        "s" | "sec" | "secs" | "second" | "seconds" => 1,
+        "m" | "min" | "mins" | "minute" | "minutes" => 60,
+        "h" | "hr" | "hrs" | "hour" | "hours" => 3600,
+        "d" | "day" | "days" => 86_400,
+        _ => return None,
+    };
+    amount.checked_mul(multiplier)
+}
+
+// Standard five-field cron: minute hour day-of-month month day-of-week, each field
+// allowing *, lists, ranges and steps. Sunday is 0 (7 is accepted too).
+#[derive(Debug, Clone)]
+pub struct CronSchedule {
+    minutes: Vec<bool>,
+    hours: Vec<bool>,
+    days_of_month: Vec<bool>,
+    months: Vec<bool>,
+    days_of_week: Vec<bool>,
+    day_of_month_restricted: bool,
+    day_of_week_restricted: bool,
+}
+
+fn parse_cron_field(field: &str, min: u32, max: u32) -> Result<Vec<bool>, String> {
+    let mut allowed = vec![false; max as usize + 1];
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step: u32 = step.parse().map_err(|_| format!("Invalid step in '{}'", part))?;
+                if step == 0 {
+                    return Err(format!("Step can't be zero in '{}'", part));
+                }
+                (range, step)
+            }
+            None => (part, 1),
+        };
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((start, end)) = range.split_once('-') {
+            let start = start.parse().map_err(|_| format!("Invalid value in '{}'", part))?;
+            let end = end.parse().map_err(|_| format!("Invalid value in '{}'", part))?;
+            (start, end)
+        } else {
+            let value = range.parse().map_err(|_| format!("Invalid value in '{}'", part))?;
+            // "5/15" means every 15 starting at 5
+            (value, if step > 1 { max } else { value })
+        };
+        if start < min || end > max || start > end {
+            return Err(format!("'{}' is outside {}-{}", part, min, max));
+        }
+        for value in (start..=end).step_by(step as usize) {
+            allowed[value as usize] = true;
+        }
+    }
+    Ok(allowed)
+}
+
+impl CronSchedule {
+    pub fn parse(expression: &str) -> Result<Self, String> {
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(format!(
+                "'{}' is neither an interval (e.g. 6h) nor a five-field cron expression",
+                expression
+            ));
+        }
+        let mut days_of_week = parse_cron_field(fields[4], 0, 7)?;
+        if days_of_week[7] {
+            days_of_week[0] = true;
+        }
+        Ok(CronSchedule {
+            minutes: parse_cron_field(fields[0], 0, 59)?,
+            hours: parse_cron_field(fields[1], 0, 23)?,
+            days_of_month: parse_cron_field(fields[2], 1, 31)?,
+            months: parse_cron_field(fields[3], 1, 12)?,
+            days_of_week,
+            day_of_month_restricted: fields[2] != "*",
+            day_of_week_restricted: fields[4] != "*",
+        })
+    }
+
+    fn day_matches(&self, time: &DateTime<Local>) -> bool {
+        let day_of_month = self.days_of_month[time.day() as usize];
+        let day_of_week = self.days_of_week[time.weekday().num_days_from_sunday() as usize];
+        // Like cron, a restricted day-of-month and day-of-week match if either does
+        match (self.day_of_month_restricted, self.day_of_week_restricted) {
+            (true, true) => day_of_month || day_of_week,
+            (true, false) => day_of_month,
+            (false, true) => day_of_week,
+            (false, false) => true,
+        }
+    }
+
+    // Walks forward, skipping a whole month, day or hour when that field doesn't
+    // match, so a search takes at most a few thousand steps. Every valid expression
+    // matches within about four years (Feb 29), which bounds it.
+    pub fn next_after(&self, after: DateTime<Local>) -> Option<DateTime<Local>> {
+        let mut candidate = after.with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+        let limit = after + Duration::days(366 * 4 + 1);
+        while candidate <= limit {
+            candidate = if !self.months[candidate.month() as usize] {
+                let (year, month) = match candidate.month() {
+                    12 => (candidate.year() + 1, 1),
+                    month => (candidate.year(), month + 1),
+                };
+                local_midnight(NaiveDate::from_ymd_opt(year, month, 1)?)?
+            } else if !self.day_matches(&candidate) {
+                local_midnight(candidate.date_naive().succ_opt()?)?
+            } else if !self.hours[candidate.hour() as usize] {
+                candidate - Duration::minutes(candidate.minute() as i64) + Duration::hours(1)
+            } else if !self.minutes[candidate.minute() as usize] {
+                candidate + Duration::minutes(1)
+            } else {
+                return Some(candidate);
+            };
+        }
+        None
+    }
+}
+
+// Start of `date` in local time; where a DST change skips midnight, the first hour
+// that exists
+fn local_midnight(date: NaiveDate) -> Option<DateTime<Local>> {
+    let midnight: NaiveDateTime = date.and_hms_opt(0, 0, 0)?;
+    Local
+        .from_local_datetime(&midnight)
+        .earliest()
+        .or_else(|| Local.from_local_datetime(&(midnight + Duration::hours(1))).earliest())
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ScanOptions {
+    #[serde(default)]
+    pub include_subdirectories: bool,
+    #[serde(default)]
+    pub follow_symlinks: bool,
+    #[serde(default)]
+    pub include_hidden: bool,
+    // None keeps the default junk list (.DS_Store, Thumbs.db, ...)
+    #[serde(default)]
+    pub ignored_names: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Schedule {
+    pub id: String,
+    pub path: String,
+    pub spec: ScheduleSpec,
+    pub options: ScanOptions,
+    // "run" catches up once on the next launch after missed runs, "skip" waits for the next slot
+    #[serde(default = "default_missed_run_policy")]
+    pub missed_run_policy: String,
+    #[serde(default)]
+    pub notify: bool,
+    #[serde(default = "default_keep_results")]
+    pub keep_results: usize,
+    pub created_at: u64,
+    pub last_run: Option<u64>,
+    pub next_run: u64,
+}
+
+fn default_missed_run_policy() -> String {
+    "run".to_string()
+}
+
+fn default_keep_results() -> usize {
+    DEFAULT_KEEP_RESULTS
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ExtensionStats {
+    pub count: u64,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduledScanResult {
+    pub schedule_id: String,
+    pub path: String,
+    pub started_at: u64,
+    pub finished_at: u64,
+    pub total_files: u64,
+    pub total_bytes: u64,
+    pub by_extension: BTreeMap<String, ExtensionStats>,
+    // Files modified since the previous run (all files on the first run)
+    pub new_file_count: u64,
+    pub new_files: Vec<String>,
+    pub error: Option<String>,
+}
+
+pub fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+// Brings next_run up to date after the app was closed. Returns true when a missed
+// run should be made up now.
+pub fn reconcile_missed_run(schedule: &mut Schedule, now: u64) -> bool {
+    if schedule.next_run > now {
+        return false;
+    }
+    if schedule.missed_run_policy == "run" {
+        schedule.next_run = now;
+        true
+    } else {
+        schedule.next_run = schedule.spec.next_run_after(now).unwrap_or(u64::MAX);
+        false
+    }
+}
+
+fn schedules_path(app_data_dir: &Path) -> PathBuf {
+    app_data_dir.join(SCHEDULES_FILE)
+}
+
+// Schedule ids become directory names, so only plain ids are accepted from the UI
+pub fn validate_id(id: &str) -> Result<(), String> {
+    let valid = (1..=MAX_ID_LEN).contains(&id.len())
+        && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("Invalid schedule id \"{}\"", id))
+    }
+}
+
+fn results_dir(app_data_dir: &Path, schedule_id: &str) -> PathBuf {
+    app_data_dir.join(RESULTS_DIR).join(schedule_id)
+}
+
+pub fn load_schedules(app_data_dir: &Path) -> Vec<Schedule> {
+    let path = schedules_path(app_data_dir);
+    let Ok(json) = std::fs::read_to_string(&path) else {
+        return Vec::new();
+    };
+    serde_json::from_str(&json).unwrap_or_else(|e| {
+        warn!("Ignoring unreadable schedules file {}: {}", path.display(), e);
+        Vec::new()
+    })
+}
+
+pub fn save_schedules(app_data_dir: &Path, schedules: &[Schedule]) -> Result<(), String> {
+    std::fs::create_dir_all(app_data_dir)
+        .map_err(|e| format!("Failed to create app data directory: {}", e))?;
+    let json = serde_json::to_string_pretty(schedules)
+        .map_err(|e| format!("Failed to serialize schedules: {}", e))?;
+    std::fs::write(schedules_path(app_data_dir), json)
+        .map_err(|e| format!("Failed to write schedules: {}", e))
+}
+
+// Writes a result and prunes the oldest ones beyond `keep`
+pub fn store_result(app_data_dir: &Path, result: &ScheduledScanResult, keep: usize) -> Result<(), String> {
+    let dir = results_dir(app_data_dir, &result.schedule_id);
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| format!("Failed to create results directory: {}", e))?;
+    let json = serde_json::to_string_pretty(result)
+        .map_err(|e| format!("Failed to serialize scan result: {}", e))?;
+    // Runs started in the same second get a numbered suffix instead of overwriting
+    let mut path = dir.join(format!("{}.json", result.started_at));
+    let mut suffix = 1;
+    while path.exists() {
+        path = dir.join(format!("{}-{}.json", result.started_at, suffix));
+        suffix += 1;
+    }
+    std::fs::write(&path, json)
+        .map_err(|e| format!("Failed to write scan result: {}", e))?;
+
+    let mut files = result_files(&dir);
+    if files.len() > keep {
+        // Oldest first, so the excess is at the front
+        for (_, old) in files.drain(..files.len() - keep) {
+            if let Err(e) = std::fs::remove_file(&old) {
+                warn!("Failed to prune old scan result {}: {}", old.display(), e);
+            }
+        }
+    }
+    Ok(())
+}
+
+// Result files in `dir`, oldest first, keyed by start time and same-second suffix
+fn result_files(dir: &Path) -> Vec<((u64, u64), PathBuf)> {
+    let mut files: Vec<((u64, u64), PathBuf)> = std::fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter_map(|entry| {
+                    let path = entry.path();
+                    let stem = path.file_stem()?.to_str()?;
+                    let (started_at, suffix) = stem.split_once('-').unwrap_or((stem, "0"));
+                    Some(((started_at.parse().ok()?, suffix.parse().ok()?), path))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    files.sort();
+    files
+}
+
+// Stored results for a schedule, newest first
+pub fn load_results(app_data_dir: &Path, schedule_id: &str) -> Vec<ScheduledScanResult> {
+    result_files(&results_dir(app_data_dir, schedule_id))
+        .into_iter()
+        .rev()
+        .filter_map(|(_, path)| {
+            let json = std::fs::read_to_string(&path).ok()?;
+            serde_json::from_str(&json).ok()
+        })
+        .collect()
+}
+
+pub fn delete_results(app_data_dir: &Path, schedule_id: &str) {
+    let dir = results_dir(app_data_dir, schedule_id);
+    if dir.exists() {
+        if let Err(e) = std::fs::remove_dir_all(&dir) {
+            warn!("Failed to delete scan results {}: {}", dir.display(), e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_dir;
+
+    fn local(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(year, month, day, hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn intervals_and_cron_expressions_are_told_apart() {
+        assert_eq!(ScheduleSpec::parse("6h"), Ok(ScheduleSpec::Interval { seconds: 6 * 3600 }));
+        assert_eq!(ScheduleSpec::parse("every 30 minutes"), Ok(ScheduleSpec::Interval { seconds: 1800 }));
+        assert_eq!(
+            ScheduleSpec::parse("0 2 * * *"),
+            Ok(ScheduleSpec::Cron { expression: "0 2 * * *".to_string() })
+        );
+        assert!(ScheduleSpec::parse("10s").is_err());
+        assert!(ScheduleSpec::parse("nightly").is_err());
+        assert!(ScheduleSpec::parse("61 * * * *").is_err());
+    }
+
+    #[test]
+    fn nightly_cron_runs_at_the_next_slot() {
+        let cron = CronSchedule::parse("0 2 * * *").unwrap();
+        assert_eq!(cron.next_after(local(2024, 3, 20, 1, 30)), Some(local(2024, 3, 20, 2, 0)));
+        assert_eq!(cron.next_after(local(2024, 3, 20, 2, 0)), Some(local(2024, 3, 21, 2, 0)));
+    }
+
+    #[test]
+    fn cron_steps_lists_and_weekdays() {
+        let every_quarter_hour = CronSchedule::parse("*/15 * * * *").unwrap();
+        assert_eq!(every_quarter_hour.next_after(local(2024, 1, 1, 9, 16)), Some(local(2024, 1, 1, 9, 30)));
+
+        // 2024-01-06 is a Saturday; weekdays only at 08:00 and 18:00
+        let weekdays = CronSchedule::parse("0 8,18 * * 1-5").unwrap();
+        assert_eq!(weekdays.next_after(local(2024, 1, 5, 19, 0)), Some(local(2024, 1, 8, 8, 0)));
+    }
+
+    #[test]
+    fn rare_cron_dates_are_found_by_skipping_fields() {
+        let leap_day = CronSchedule::parse("30 6 29 2 *").unwrap();
+        assert_eq!(leap_day.next_after(local(2025, 3, 1, 0, 0)), Some(local(2028, 2, 29, 6, 30)));
+        let never = CronSchedule::parse("0 0 31 2 *").unwrap();
+        assert_eq!(never.next_after(local(2025, 3, 1, 0, 0)), None);
+    }
+
+    #[test]
+    fn ids_that_leave_the_results_directory_are_rejected() {
+        assert!(validate_id("schedule-1712345678").is_ok());
+        assert!(validate_id("../../etc").is_err());
+        assert!(validate_id("a/b").is_err());
+        assert!(validate_id("").is_err());
+    }
+
+    #[test]
+    fn results_from_the_same_second_are_all_kept() {
+        let dir = temp_dir("scheduler-results");
+        let mut result = ScheduledScanResult {
+            schedule_id: "s".to_string(),
+            path: "/tmp".to_string(),
+            started_at: 1_000,
+            finished_at: 1_000,
+            total_files: 0,
+            total_bytes: 0,
+            by_extension: BTreeMap::new(),
+            new_file_count: 0,
+            new_files: Vec::new(),
+            error: None,
+        };
+        for total_files in 1..=3 {
+            result.total_files = total_files;
+            store_result(&dir, &result, 2).unwrap();
+        }
+        let kept: Vec<u64> = load_results(&dir, "s").iter().map(|result| result.total_files).collect();
+        assert_eq!(kept, vec![3, 2]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn missed_runs_follow_the_policy() {
+        let mut schedule = Schedule {
+            id: "s".to_string(),
+            path: "/tmp".to_string(),
+            spec: ScheduleSpec::Interval { seconds: 3600 },
+            options: ScanOptions::default(),
+            missed_run_policy: "run".to_string(),
+            notify: false,
+            keep_results: DEFAULT_KEEP_RESULTS,
+            created_at: 0,
+            last_run: Some(1_000),
+            next_run: 4_600,
+        };
+        assert!(!reconcile_missed_run(&mut schedule, 4_000));
+        assert!(reconcile_missed_run(&mut schedule, 10_000));
+        assert_eq!(schedule.next_run, 10_000);
+
+        schedule.missed_run_policy = "skip".to_string();
+        schedule.next_run = 4_600;
+        assert!(!reconcile_missed_run(&mut schedule, 10_000));
+        assert_eq!(schedule.next_run, 13_600);
+    }
+}
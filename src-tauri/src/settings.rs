@@ -0,0 +1,376 @@
+// App settings kept by the backend in app_data_dir/settings.json, so they survive the
+// webview's storage being cleared and Rust code can read them directly. Writes go to a
+// temp file that is renamed over the old one, so a crash never leaves half a file.
+// Files carry a version and older ones are upgraded through MIGRATIONS when read.
+use crate::destination_policy::DestinationPolicy;
+use crate::external_extractors::ExternalExtractorSettings;
+use crate::extraction;
+use crate::open_with;
+use crate::redaction::RedactionRules;
+use crate::staging;
+use crate::ManagedLLMConfig;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::path::{Path, PathBuf};
+use std::sync::{OnceLock, RwLock};
+use tracing::{info, warn};
+
+const SETTINGS_FILE: &str = "settings.json";
+// Where the destination policy was saved before it moved into the settings file
+const LEGACY_POLICY_FILE: &str = "destination_policy.json";
+pub const SETTINGS_VERSION: u32 = 1;
+
+// Upgrades raw settings JSON one version at a time: entry i takes version i to i + 1.
+// Fields added with a serde default need no entry; renames and reshapes do.
+const MIGRATIONS: &[fn(&mut Map<String, Value>)] = &[
+    // 0 -> 1: files written before versioning have the same shape
+    |_| {},
+];
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScanSettings {
+    pub include_hidden: bool,
+    // Replaces the default junk list when set
+    pub ignored_names: Option<Vec<String>>,
+    // Read cloud placeholders (downloading them) during analysis instead of skipping them
+    pub hydrate_cloud_placeholders: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExtractionSettings {
+    pub timeout_ms: u64,
+    // Unset means one extraction per CPU
+    pub concurrency: Option<usize>,
+}
+
+impl Default for ExtractionSettings {
+    fn default() -> Self {
+        Self { timeout_ms: extraction::DEFAULT_TIMEOUT_MS, concurrency: None }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StagingSettings {
+    // Staged batches older than this are reported at startup
+    pub expiry_days: u64,
+}
+
+impl Default for StagingSettings {
+    fn default() -> Self {
+        Self { expiry_days: staging::DEFAULT_EXPIRY_DAYS }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OpenFileSettings {
+    // Extensions that open_file reveals in the file manager instead of running
+    pub dangerous_extensions: Vec<String>,
+}
+
+impl Default for OpenFileSettings {
+    fn default() -> Self {
+        let dangerous_extensions = open_with::DEFAULT_DANGEROUS_EXTENSIONS.iter().map(|ext| ext.to_string()).collect();
+        Self { dangerous_extensions }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AuditSettings {
+    // Record reads, moves, trashing and LLM submissions in the audit log
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RedactionSettings {
+    // Redact file text before it goes to a backend off this machine
+    pub enabled: bool,
+    // Redact for local backends (managed server, localhost endpoints) too
+    pub redact_always: bool,
+    pub rules: RedactionRules,
+}
+
+impl Default for RedactionSettings {
+    fn default() -> Self {
+        Self { enabled: true, redact_always: false, rules: RedactionRules::default() }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub version: u32,
+    // The last configuration the managed server was started with
+    pub managed_llm: Option<ManagedLLMConfig>,
+    pub scan: ScanSettings,
+    pub destination_policy: DestinationPolicy,
+    pub extraction: ExtractionSettings,
+    // Converters for file types read_file_content can't read itself
+    pub external_extractors: ExternalExtractorSettings,
+    pub staging: StagingSettings,
+    pub open_file: OpenFileSettings,
+    pub audit: AuditSettings,
+    pub redaction: RedactionSettings,
+    // No file content leaves the machine: see local_only
+    pub local_only: bool,
+    // Language for backend-generated messages; unset means English
+    pub locale: Option<String>,
+    // Hugging Face access token for gated model repos. Never logged or exported.
+    pub huggingface_token: Option<String>,
+    // Frontend-only values (provider configs, UI preferences), stored as given
+    pub frontend: Map<String, Value>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            version: SETTINGS_VERSION,
+            managed_llm: None,
+            scan: ScanSettings::default(),
+            destination_policy: DestinationPolicy::default(),
+            extraction: ExtractionSettings::default(),
+            external_extractors: ExternalExtractorSettings::default(),
+            staging: StagingSettings::default(),
+            open_file: OpenFileSettings::default(),
+            audit: AuditSettings::default(),
+            redaction: RedactionSettings::default(),
+            local_only: false,
+            locale: None,
+            huggingface_token: None,
+            frontend: Map::new(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Store {
+    settings: Settings,
+    // Unset until load_saved_settings runs; changes before then aren't written
+    path: Option<PathBuf>,
+}
+
+static STORE: OnceLock<RwLock<Store>> = OnceLock::new();
+
+fn store() -> &'static RwLock<Store> {
+    STORE.get_or_init(|| RwLock::new(Store::default()))
+}
+
+pub fn current() -> Settings {
+    store().read().unwrap().settings.clone()
+}
+
+// Reads part of the settings without cloning the rest
+pub fn with<R>(read: impl FnOnce(&Settings) -> R) -> R {
+    read(&store().read().unwrap().settings)
+}
+
+// Upgrades `value` to SETTINGS_VERSION and deserializes it. Files from a newer version
+// are read as far as this version understands them.
+pub fn migrate(mut value: Value) -> Result<Settings, String> {
+    let object = value.as_object_mut().ok_or("Settings must be a JSON object")?;
+    let version = object.get("version").and_then(Value::as_u64).unwrap_or(0) as usize;
+    if version > SETTINGS_VERSION as usize {
+        warn!("Settings were written by a newer version ({}); unknown fields are ignored", version);
+    }
+    for migration in MIGRATIONS.iter().skip(version) {
+        migration(object);
+    }
+    let mut settings: Settings =
+        serde_json::from_value(value).map_err(|e| format!("Invalid settings: {}", e))?;
+    settings.version = SETTINGS_VERSION;
+    Ok(settings)
+}
+
+pub fn read_from(path: &Path) -> Result<Settings, String> {
+    let json = std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let value = serde_json::from_str(&json).map_err(|e| format!("Invalid settings in {}: {}", path.display(), e))?;
+    migrate(value)
+}
+
+// Writes next to `path` and renames over it
+pub fn write_to(path: &Path, settings: &Settings) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+    let json = serde_json::to_string_pretty(settings).map_err(|e| format!("Failed to serialize settings: {}", e))?;
+    let temp = path.with_extension("json.tmp");
+    std::fs::write(&temp, json).map_err(|e| format!("Failed to write {}: {}", temp.display(), e))?;
+    std::fs::rename(&temp, path).map_err(|e| {
+        let _ = std::fs::remove_file(&temp);
+        format!("Failed to replace {}: {}", path.display(), e)
+    })
+}
+
+// Loads the saved settings at startup; from then on every change is written back. An
+// unreadable file is logged and the defaults are used.
+pub fn load_saved_settings(app_data_dir: &Path) -> Settings {
+    let path = app_data_dir.join(SETTINGS_FILE);
+    let settings = if path.exists() {
+        read_from(&path).unwrap_or_else(|e| {
+            warn!("Ignoring unreadable settings: {}", e);
+            Settings::default()
+        })
+    } else {
+        info!("No saved settings, using defaults");
+        let mut settings = Settings::default();
+        let legacy_policy = std::fs::read_to_string(app_data_dir.join(LEGACY_POLICY_FILE)).ok();
+        if let Some(policy) = legacy_policy.and_then(|json| serde_json::from_str(&json).ok()) {
+            settings.destination_policy = policy;
+        }
+        settings
+    };
+    let mut store = store().write().unwrap();
+    store.settings = settings.clone();
+    store.path = Some(path);
+    settings
+}
+
+// Applies `change` and saves. The in-memory settings are only replaced once the file
+// has been written.
+pub fn update(change: impl FnOnce(&mut Settings) -> Result<(), String>) -> Result<Settings, String> {
+    let mut store = store().write().unwrap();
+    let mut settings = store.settings.clone();
+    change(&mut settings)?;
+    settings.version = SETTINGS_VERSION;
+    if let Some(path) = &store.path {
+        write_to(path, &settings)?;
+    }
+    store.settings = settings.clone();
+    Ok(settings)
+}
+
+pub fn replace(settings: Settings) -> Result<Settings, String> {
+    update(|current| {
+        *current = settings;
+        Ok(())
+    })
+}
+
+// Applies a JSON merge patch (RFC 7396): objects merge, null removes a field (back to
+// its default), anything else replaces
+pub fn patch(patch: &Value) -> Result<Settings, String> {
+    update(|settings| {
+        let mut value = serde_json::to_value(&*settings).map_err(|e| e.to_string())?;
+        apply_patch(&mut value, patch);
+        *settings = migrate(value)?;
+        Ok(())
+    })
+}
+
+// Values under "frontend" are replaced whole rather than merged, since only the
+// frontend knows their shape
+fn apply_patch(settings: &mut Value, patch: &Value) {
+    let mut patch = patch.clone();
+    let frontend = patch.as_object_mut().and_then(|patch| patch.remove("frontend"));
+    merge_patch(settings, &patch);
+    let Some(Value::Object(frontend)) = frontend else { return };
+    if let Some(Value::Object(target)) = settings.get_mut("frontend") {
+        for (key, value) in frontend {
+            if value.is_null() {
+                target.remove(&key);
+            } else {
+                target.insert(key, value);
+            }
+        }
+    }
+}
+
+// A copy for moving to another machine, without the Hugging Face token or the API keys
+// saved with provider configs
+pub fn without_secrets(settings: &Settings) -> Settings {
+    fn strip(value: &mut Value) {
+        match value {
+            Value::Object(object) => {
+                object.remove("apiKey");
+                object.values_mut().for_each(strip);
+            }
+            Value::Array(items) => items.iter_mut().for_each(strip),
+            _ => {}
+        }
+    }
+    let mut exported = settings.clone();
+    exported.huggingface_token = None;
+    exported.frontend.values_mut().for_each(strip);
+    exported
+}
+
+fn merge_patch(target: &mut Value, patch: &Value) {
+    let Value::Object(patch) = patch else {
+        *target = patch.clone();
+        return;
+    };
+    if !target.is_object() {
+        *target = Value::Object(Map::new());
+    }
+    let Value::Object(target) = target else { unreachable!() };
+    for (key, value) in patch {
+        if value.is_null() {
+            target.remove(key);
+        } else {
+            merge_patch(target.entry(key.as_str()).or_insert(Value::Null), value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_dir;
+    use serde_json::json;
+    use std::fs;
+
+    #[test]
+    fn unversioned_and_partial_files_are_upgraded() {
+        let settings = migrate(json!({ "scan": { "include_hidden": true } })).unwrap();
+        assert_eq!(settings.version, SETTINGS_VERSION);
+        assert!(settings.scan.include_hidden);
+        assert_eq!(settings.extraction.timeout_ms, extraction::DEFAULT_TIMEOUT_MS);
+        assert!(migrate(json!([1, 2])).is_err());
+    }
+
+    #[test]
+    fn settings_are_written_atomically_and_read_back() {
+        let dir = temp_dir("write");
+        let path = dir.join("nested/settings.json");
+        let mut settings = Settings::default();
+        settings.extraction.concurrency = Some(2);
+        settings.frontend.insert("theme".to_string(), json!("dark"));
+
+        write_to(&path, &settings).unwrap();
+        assert!(!path.with_extension("json.tmp").exists());
+        let read = read_from(&path).unwrap();
+        assert_eq!(read.extraction.concurrency, Some(2));
+        assert_eq!(read.frontend["theme"], "dark");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn patches_merge_objects_and_replace_frontend_values() {
+        let mut value = json!({ "scan": { "include_hidden": true, "ignored_names": ["a"] }, "frontend": {} });
+        merge_patch(&mut value, &json!({ "scan": { "ignored_names": null }, "frontend": { "x": 1 } }));
+        assert_eq!(value, json!({ "scan": { "include_hidden": true }, "frontend": { "x": 1 } }));
+
+        let settings = migrate(value).unwrap();
+        assert!(settings.scan.include_hidden && settings.scan.ignored_names.is_none());
+
+        let mut value = json!({ "frontend": { "llmConfig": { "model": "a", "apiKey": "k" }, "theme": "dark" } });
+        apply_patch(&mut value, &json!({ "frontend": { "llmConfig": { "model": "b" }, "theme": null } }));
+        assert_eq!(value, json!({ "frontend": { "llmConfig": { "model": "b" } } }));
+    }
+
+    #[test]
+    fn exports_leave_out_api_keys() {
+        let mut settings = Settings::default();
+        settings.frontend.insert("llmConfig".to_string(), json!({ "provider": "openai", "apiKey": "sk-1" }));
+        settings.frontend.insert("llmProviderConfigs".to_string(), json!({ "openai": { "apiKey": "sk-1" } }));
+
+        let exported = without_secrets(&settings);
+        assert_eq!(exported.frontend["llmConfig"], json!({ "provider": "openai" }));
+        assert_eq!(exported.frontend["llmProviderConfigs"], json!({ "openai": {} }));
+    }
+}
@@ -0,0 +1,465 @@
+// Shortcut files and what they point at: Windows shell links (.lnk), macOS Finder
+// aliases (bookmark data), and the URL shortcuts .url and .webloc. Every format is read
+// from its bytes, so a Windows shortcut on a shared drive is understood on a Mac and
+// the other way round. A target that isn't a path on this platform (C:\... seen from a
+// Mac) can't be checked and is reported as such rather than as broken.
+use crate::paths;
+use crate::scan_filter::ScanFilter;
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+// Shortcut files are small; anything bigger isn't read
+const MAX_SHORTCUT_BYTES: u64 = 1024 * 1024;
+
+const LNK_HEADER_SIZE: u32 = 0x4C;
+const LNK_CLSID: [u8; 16] = [0x01, 0x14, 0x02, 0x00, 0, 0, 0, 0, 0xC0, 0, 0, 0, 0, 0, 0, 0x46];
+const HAS_TARGET_ID_LIST: u32 = 0x1;
+const HAS_LINK_INFO: u32 = 0x2;
+const HAS_NAME: u32 = 0x4;
+const HAS_RELATIVE_PATH: u32 = 0x8;
+const IS_UNICODE: u32 = 0x80;
+const VOLUME_ID_AND_LOCAL_BASE_PATH: u32 = 0x1;
+const COMMON_NETWORK_RELATIVE_LINK: u32 = 0x2;
+
+const BOOKMARK_TOC_MAGIC: u32 = 0xFFFF_FFFE;
+const BOOKMARK_PATH: u32 = 0x1004;
+const BOOKMARK_STRING: u32 = 0x0100;
+const BOOKMARK_ARRAY: u32 = 0x0600;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ShortcutKind {
+    WindowsShortcut,
+    MacAlias,
+    InternetShortcut,
+    Webloc,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TargetStatus {
+    Exists,
+    Missing,
+    // Not a path on this platform, or the shortcut didn't say
+    Unverifiable,
+    // A web address; nothing to check
+    Url,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Shortcut {
+    pub path: String,
+    pub kind: ShortcutKind,
+    pub target: Option<String>,
+    pub status: TargetStatus,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ShortcutReport {
+    // Working shortcuts and URL shortcuts
+    pub shortcuts: Vec<Shortcut>,
+    // Shortcuts whose target is gone: cleanup candidates
+    pub broken: Vec<Shortcut>,
+    // Existing targets, once each: files kept in use by a shortcut
+    pub referenced: Vec<String>,
+    pub files_scanned: u64,
+    pub unreadable: u64,
+}
+
+// What a shell link points at: an absolute local or network path, or (for links made
+// without one) a path relative to the .lnk
+#[derive(Debug, PartialEq)]
+pub enum LnkTarget {
+    Absolute(String),
+    Relative(String),
+}
+
+fn u16_at(bytes: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(bytes.get(offset..offset + 2)?.try_into().ok()?))
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(bytes.get(offset..offset + 4)?.try_into().ok()?))
+}
+
+// A NUL-terminated string in the system code page, read as Windows-1252
+fn ansi_at(bytes: &[u8], offset: u32) -> Option<String> {
+    let tail = bytes.get(offset as usize..)?;
+    let raw = &tail[..tail.iter().position(|&b| b == 0)?];
+    Some(encoding_rs::WINDOWS_1252.decode_without_bom_handling(raw).0.into_owned())
+}
+
+fn utf16(raw: &[u8]) -> String {
+    let units: Vec<u16> = raw.chunks_exact(2).map(|pair| u16::from_le_bytes([pair[0], pair[1]])).collect();
+    String::from_utf16_lossy(&units)
+}
+
+fn utf16_at(bytes: &[u8], offset: u32) -> Option<String> {
+    let tail = bytes.get(offset as usize..)?;
+    let end = tail.chunks_exact(2).position(|pair| pair == [0, 0])?;
+    Some(utf16(&tail[..end * 2]))
+}
+
+// The LinkInfo structure's path: local base path plus suffix, or share plus suffix
+fn link_info_path(info: &[u8]) -> Option<String> {
+    let header_size = u32_at(info, 4)?;
+    let flags = u32_at(info, 8)?;
+    let unicode = header_size >= 0x24;
+    let suffix = if unicode { utf16_at(info, u32_at(info, 0x20)?) } else { ansi_at(info, u32_at(info, 0x18)?) };
+    let suffix = suffix.unwrap_or_default();
+    if flags & VOLUME_ID_AND_LOCAL_BASE_PATH != 0 {
+        let base = if unicode && u32_at(info, 0x1C)? != 0 {
+            utf16_at(info, u32_at(info, 0x1C)?)
+        } else {
+            ansi_at(info, u32_at(info, 0x10)?)
+        }?;
+        return Some(base + &suffix);
+    }
+    if flags & COMMON_NETWORK_RELATIVE_LINK != 0 {
+        let link = info.get(u32_at(info, 0x14)? as usize..)?;
+        let share = ansi_at(link, u32_at(link, 8)?)?;
+        if suffix.is_empty() || share.ends_with('\\') {
+            return Some(share + &suffix);
+        }
+        return Some(format!("{}\\{}", share, suffix));
+    }
+    None
+}
+
+// Reads the target of a shell link (MS-SHLLINK); None if `bytes` isn't one or names
+// its target only through the shell item list
+pub fn parse_lnk(bytes: &[u8]) -> Option<LnkTarget> {
+    if u32_at(bytes, 0)? != LNK_HEADER_SIZE || bytes.get(4..20)? != LNK_CLSID {
+        return None;
+    }
+    let flags = u32_at(bytes, 0x14)?;
+    let mut offset = LNK_HEADER_SIZE as usize;
+    if flags & HAS_TARGET_ID_LIST != 0 {
+        offset += 2 + u16_at(bytes, offset)? as usize;
+    }
+    if flags & HAS_LINK_INFO != 0 {
+        let size = u32_at(bytes, offset)? as usize;
+        if let Some(target) = link_info_path(bytes.get(offset..offset + size)?) {
+            return Some(LnkTarget::Absolute(target));
+        }
+        offset += size;
+    }
+    // The string data after it starts with the name, then the relative path
+    let unicode = flags & IS_UNICODE != 0;
+    for flag in [HAS_NAME, HAS_RELATIVE_PATH] {
+        if flags & flag == 0 {
+            continue;
+        }
+        let count = u16_at(bytes, offset)? as usize;
+        let len = if unicode { count * 2 } else { count };
+        let raw = bytes.get(offset + 2..offset + 2 + len)?;
+        offset += 2 + len;
+        if flag == HAS_RELATIVE_PATH {
+            let text = if unicode {
+                utf16(raw)
+            } else {
+                encoding_rs::WINDOWS_1252.decode_without_bom_handling(raw).0.into_owned()
+            };
+            return Some(LnkTarget::Relative(text));
+        }
+    }
+    None
+}
+
+// One bookmark data item: its type and contents
+fn bookmark_item(data: &[u8], offset: u32) -> Option<(u32, &[u8])> {
+    let offset = offset as usize;
+    let len = u32_at(data, offset)? as usize;
+    let kind = u32_at(data, offset + 4)?;
+    Some((kind, data.get(offset + 8..offset + 8 + len)?))
+}
+
+// The target path in macOS bookmark data, which is what Finder alias files hold: a
+// "book" header, then tables of contents mapping keys to items. The path is an array
+// of strings, one per component.
+pub fn parse_bookmark(bytes: &[u8]) -> Option<String> {
+    if bytes.get(..4)? != b"book" {
+        return None;
+    }
+    let header_size = u32_at(bytes, 12)? as usize;
+    let data = bytes.get(header_size..)?;
+    let mut toc = u32_at(data, 0)?;
+    // Each table is visited once; a damaged file can't loop forever
+    let mut seen = BTreeSet::new();
+    while toc != 0 && seen.insert(toc) {
+        let base = toc as usize;
+        if u32_at(data, base + 4)? != BOOKMARK_TOC_MAGIC {
+            return None;
+        }
+        let count = u32_at(data, base + 16)? as usize;
+        for index in 0..count {
+            let entry = base + 20 + index * 12;
+            if u32_at(data, entry)? != BOOKMARK_PATH {
+                continue;
+            }
+            let (kind, array) = bookmark_item(data, u32_at(data, entry + 4)?)?;
+            if kind & 0xFFFF_FF00 != BOOKMARK_ARRAY {
+                return None;
+            }
+            let mut components = Vec::new();
+            for item in array.chunks_exact(4) {
+                let (kind, text) = bookmark_item(data, u32::from_le_bytes(item.try_into().ok()?))?;
+                if kind & 0xFFFF_FF00 != BOOKMARK_STRING {
+                    return None;
+                }
+                components.push(String::from_utf8_lossy(text).into_owned());
+            }
+            return Some(format!("/{}", components.join("/")));
+        }
+        toc = u32_at(data, base + 12)?;
+    }
+    None
+}
+
+// The URL= line of a Windows Internet shortcut
+pub fn parse_url_file(text: &str) -> Option<String> {
+    text.lines().find_map(|line| {
+        let (key, value) = line.split_once('=')?;
+        (key.trim().eq_ignore_ascii_case("url") && !value.trim().is_empty()).then(|| value.trim().to_string())
+    })
+}
+
+// The URL in a .webloc, an XML or binary property list
+pub fn parse_webloc(bytes: &[u8]) -> Option<String> {
+    let text = String::from_utf8_lossy(bytes);
+    if let Some(after_key) = text.split_once("<key>URL</key>").map(|(_, rest)| rest) {
+        let value = after_key.split_once("<string>")?.1.split_once("</string>")?.0;
+        return Some(value.trim().replace("&amp;", "&").replace("&lt;", "<").replace("&gt;", ">"));
+    }
+    // Binary plists store short ASCII strings as they are
+    if bytes.starts_with(b"bplist") {
+        return bytes
+            .split(|b| !b.is_ascii_graphic())
+            .map(|run| String::from_utf8_lossy(run).into_owned())
+            .find(|run| run.contains("://"));
+    }
+    None
+}
+
+// Files that may be Finder aliases: those have no extension of their own, but are
+// usually named after their original ("report.pdf alias")
+fn may_be_alias(path: &Path) -> bool {
+    match path.extension().map(|e| e.to_string_lossy().to_lowercase()) {
+        None => true,
+        Some(extension) => extension.ends_with("alias"),
+    }
+}
+
+fn status_of(target: &Path) -> TargetStatus {
+    if !target.is_absolute() {
+        return TargetStatus::Unverifiable;
+    }
+    match std::fs::symlink_metadata(target) {
+        Ok(_) => TargetStatus::Exists,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => TargetStatus::Missing,
+        Err(_) => TargetStatus::Unverifiable,
+    }
+}
+
+// Reads `path` as a shortcut; Ok(None) when it isn't one
+pub fn read(path: &Path, size: u64) -> std::io::Result<Option<Shortcut>> {
+    let extension = path.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default();
+    let known = matches!(extension.as_str(), "lnk" | "url" | "webloc");
+    if size > MAX_SHORTCUT_BYTES || !(known || may_be_alias(path)) {
+        return Ok(None);
+    }
+    let mut bytes = Vec::new();
+    let mut file = std::fs::File::open(path)?;
+    if !known {
+        // Checked before reading the rest, since most such files aren't aliases
+        file.by_ref().take(4).read_to_end(&mut bytes)?;
+        if bytes != b"book" {
+            return Ok(None);
+        }
+    }
+    file.read_to_end(&mut bytes)?;
+
+    let file_target = |target: PathBuf| (Some(paths::display_path(&target)), status_of(&target));
+    let (kind, (target, status)) = match extension.as_str() {
+        "lnk" => {
+            let target = match parse_lnk(&bytes) {
+                Some(LnkTarget::Absolute(target)) => file_target(PathBuf::from(target)),
+                // Written with backslashes; those are separators here too
+                Some(LnkTarget::Relative(relative)) => {
+                    let relative: PathBuf = relative.split('\\').collect();
+                    file_target(path.parent().unwrap_or(Path::new("")).join(relative))
+                }
+                None => (None, TargetStatus::Unverifiable),
+            };
+            (ShortcutKind::WindowsShortcut, target)
+        }
+        "url" => {
+            let url = parse_url_file(&String::from_utf8_lossy(&bytes));
+            (ShortcutKind::InternetShortcut, (url, TargetStatus::Url))
+        }
+        "webloc" => (ShortcutKind::Webloc, (parse_webloc(&bytes), TargetStatus::Url)),
+        _ => match parse_bookmark(&bytes) {
+            Some(target) => (ShortcutKind::MacAlias, file_target(PathBuf::from(target))),
+            None => return Ok(None),
+        },
+    };
+    Ok(Some(Shortcut { path: paths::display_path(path), kind, target, status }))
+}
+
+// Every shortcut under `roots`, with broken ones reported apart. Symlinks are left out
+// unless follow_symlinks is set; a cycle then counts as unreadable.
+pub fn find(roots: &[PathBuf], filter: &ScanFilter, follow_symlinks: bool) -> ShortcutReport {
+    let mut report = ShortcutReport::default();
+    let mut referenced = BTreeSet::new();
+    for root in paths::distinct_roots(roots) {
+        let entries = WalkDir::new(&root)
+            .follow_links(follow_symlinks)
+            .into_iter()
+            .filter_entry(|e| e.depth() == 0 || !crate::walk_entry_skipped(filter, e));
+        for entry in entries {
+            let Ok(entry) = entry else {
+                report.unreadable += 1;
+                continue;
+            };
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            report.files_scanned += 1;
+            let size = entry.metadata().map_or(0, |m| m.len());
+            match read(entry.path(), size) {
+                Ok(Some(shortcut)) if shortcut.status == TargetStatus::Missing => report.broken.push(shortcut),
+                Ok(Some(shortcut)) => {
+                    if shortcut.status == TargetStatus::Exists {
+                        referenced.extend(shortcut.target.clone());
+                    }
+                    report.shortcuts.push(shortcut);
+                }
+                Ok(None) => {}
+                Err(_) => report.unreadable += 1,
+            }
+        }
+    }
+    report.shortcuts.sort_by(|a, b| a.path.cmp(&b.path));
+    report.broken.sort_by(|a, b| a.path.cmp(&b.path));
+    report.referenced = referenced.into_iter().collect();
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_dir;
+    use std::fs;
+
+    // A shell link with a LinkInfo local path, or with only a Unicode relative path
+    fn lnk(local: Option<&str>, relative: Option<&str>) -> Vec<u8> {
+        let mut flags = 0;
+        let mut body = Vec::new();
+        if let Some(local) = local {
+            flags |= HAS_LINK_INFO;
+            let base = [&encoding_rs::WINDOWS_1252.encode(local).0[..], &[0]].concat();
+            let size = 0x1C + base.len() as u32 + 1;
+            for value in [size, 0x1C, VOLUME_ID_AND_LOCAL_BASE_PATH, 0, 0x1C, 0, size - 1] {
+                body.extend(value.to_le_bytes());
+            }
+            body.extend(base);
+            body.push(0);
+        }
+        if let Some(relative) = relative {
+            flags |= HAS_RELATIVE_PATH | IS_UNICODE;
+            let units: Vec<u16> = relative.encode_utf16().collect();
+            body.extend((units.len() as u16).to_le_bytes());
+            body.extend(units.iter().flat_map(|unit| unit.to_le_bytes()));
+        }
+        let mut bytes = LNK_HEADER_SIZE.to_le_bytes().to_vec();
+        bytes.extend(LNK_CLSID);
+        bytes.extend(flags.to_le_bytes());
+        bytes.resize(LNK_HEADER_SIZE as usize, 0);
+        bytes.extend(body);
+        bytes
+    }
+
+    // Bookmark data holding just a path
+    fn bookmark(components: &[&str]) -> Vec<u8> {
+        let mut data = vec![0u8; 4];
+        let mut offsets = Vec::new();
+        for component in components {
+            offsets.push(data.len() as u32);
+            data.extend((component.len() as u32).to_le_bytes());
+            data.extend((BOOKMARK_STRING | 1).to_le_bytes());
+            data.extend(component.as_bytes());
+            data.resize(data.len().next_multiple_of(4), 0);
+        }
+        let array = data.len() as u32;
+        data.extend((offsets.len() as u32 * 4).to_le_bytes());
+        data.extend((BOOKMARK_ARRAY | 1).to_le_bytes());
+        data.extend(offsets.iter().flat_map(|offset| offset.to_le_bytes()));
+        let toc = data.len() as u32;
+        data[..4].copy_from_slice(&toc.to_le_bytes());
+        for value in [20 + 12, BOOKMARK_TOC_MAGIC, 1, 0, 1, BOOKMARK_PATH, array, 0] {
+            data.extend(value.to_le_bytes());
+        }
+        let mut bytes = b"book".to_vec();
+        for value in [48 + data.len() as u32, 0x1004_0000, 48] {
+            bytes.extend(value.to_le_bytes());
+        }
+        bytes.resize(48, 0);
+        bytes.extend(data);
+        bytes
+    }
+
+    #[test]
+    fn shortcut_formats_are_read_from_bytes() {
+        let local = lnk(Some("C:\\Users\\Ren\u{e9}e\\report.pdf"), None);
+        assert_eq!(parse_lnk(&local), Some(LnkTarget::Absolute("C:\\Users\\Renée\\report.pdf".into())));
+        assert_eq!(parse_lnk(&lnk(None, Some("..\\docs\\a.txt"))), Some(LnkTarget::Relative("..\\docs\\a.txt".into())));
+        assert_eq!(parse_lnk(b"not a shortcut"), None);
+
+        assert_eq!(parse_bookmark(&bookmark(&["Users", "me", "report.pdf"])), Some("/Users/me/report.pdf".into()));
+        assert_eq!(parse_bookmark(&bookmark(&["Users", "me", "report.pdf"])[..60]), None);
+
+        let url = "[InternetShortcut]\r\nURL=https://example.com/a?b=1\r\nIconIndex=0\r\n";
+        assert_eq!(parse_url_file(url), Some("https://example.com/a?b=1".into()));
+        let webloc = "<plist><dict><key>URL</key>\n<string>https://example.com/?a=1&amp;b=2</string></dict></plist>";
+        assert_eq!(parse_webloc(webloc.as_bytes()), Some("https://example.com/?a=1&b=2".into()));
+        let binary = b"bplist00\xd1\x01\x02SURL_\x10\x18https://example.com/page\x08\x0b";
+        assert_eq!(parse_webloc(binary), Some("https://example.com/page".into()));
+    }
+
+    // The alias below needs a path starting at /, and D:\x.txt has to be another system's
+    #[cfg(unix)]
+    #[test]
+    fn broken_shortcuts_are_reported_apart_from_referenced_targets() {
+        let dir = temp_dir("find");
+        fs::create_dir_all(dir.join("docs")).unwrap();
+        fs::write(dir.join("docs/kept.txt"), b"kept").unwrap();
+        fs::write(dir.join("kept.lnk"), lnk(None, Some("docs\\kept.txt"))).unwrap();
+        fs::write(dir.join("gone.lnk"), lnk(None, Some("docs\\gone.txt"))).unwrap();
+        fs::write(dir.join("other pc.lnk"), lnk(Some("D:\\x.txt"), None)).unwrap();
+        let kept = paths::display_path(dir.join("docs/kept.txt"));
+        let components: Vec<&str> = kept.trim_start_matches('/').split('/').collect();
+        fs::write(dir.join("kept.txt alias"), bookmark(&components)).unwrap();
+        fs::write(dir.join("site.url"), "[InternetShortcut]\nURL=https://example.com\n").unwrap();
+        fs::write(dir.join("README"), b"plain text without an extension").unwrap();
+
+        let report = find(std::slice::from_ref(&dir), &ScanFilter::default(), false);
+        assert_eq!(report.files_scanned, 7);
+        let broken: Vec<&str> = report.broken.iter().map(|s| s.path.as_str()).collect();
+        assert_eq!(broken, [paths::display_path(dir.join("gone.lnk"))]);
+        assert_eq!(report.referenced, [kept]);
+        let kinds: Vec<(ShortcutKind, TargetStatus)> = report.shortcuts.iter().map(|s| (s.kind, s.status)).collect();
+        assert_eq!(
+            kinds,
+            [
+                (ShortcutKind::WindowsShortcut, TargetStatus::Exists),
+                (ShortcutKind::MacAlias, TargetStatus::Exists),
+                (ShortcutKind::WindowsShortcut, TargetStatus::Unverifiable),
+                (ShortcutKind::InternetShortcut, TargetStatus::Url),
+            ]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,112 @@
+// Ordered app shutdown. Work that leaves files behind (server downloads) registers
+// while it runs and checks `is_shutting_down` between writes; on exit new work is
+// refused, running work gets a bounded grace period to stop at a consistent point,
+// and only then are the managed server and the process torn down.
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+pub const GRACE_PERIOD: Duration = Duration::from_secs(10);
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+pub static SHUTDOWN: Shutdown = Shutdown::new();
+
+pub struct Shutdown {
+    shutting_down: AtomicBool,
+    in_flight: AtomicUsize,
+}
+
+// Held by a unit of in-flight work; dropping it tells shutdown the work has stopped
+pub struct InFlight<'a> {
+    shutdown: &'a Shutdown,
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.shutdown.in_flight.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl Shutdown {
+    pub const fn new() -> Self {
+        Shutdown { shutting_down: AtomicBool::new(false), in_flight: AtomicUsize::new(0) }
+    }
+
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutting_down.load(Ordering::SeqCst)
+    }
+
+    // None once shutdown has begun, so no new work starts
+    pub fn enter(&self) -> Option<InFlight<'_>> {
+        self.in_flight.fetch_add(1, Ordering::SeqCst);
+        let guard = InFlight { shutdown: self };
+        (!self.is_shutting_down()).then_some(guard)
+    }
+
+    // Refuses new work and waits up to `grace` for running work to stop. Returns how
+    // many units were still running when the wait ended. Only the first call waits.
+    pub fn begin(&self, grace: Duration) -> usize {
+        if self.shutting_down.swap(true, Ordering::SeqCst) {
+            return self.in_flight.load(Ordering::SeqCst);
+        }
+        let deadline = Instant::now() + grace;
+        loop {
+            let running = self.in_flight.load(Ordering::SeqCst);
+            if running == 0 || Instant::now() >= deadline {
+                return running;
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_dir;
+    use std::io::Write;
+    use std::path::PathBuf;
+
+    // A download writing chunks until told to stop, as download_to_file does
+    fn slow_download(shutdown: &'static Shutdown, path: PathBuf) -> std::thread::JoinHandle<Result<(), String>> {
+        let guard = shutdown.enter().unwrap();
+        std::thread::spawn(move || {
+            let _guard = guard;
+            let mut file = std::fs::File::create(&path).unwrap();
+            for _ in 0..1_000 {
+                if shutdown.is_shutting_down() {
+                    file.sync_data().unwrap();
+                    return Err("interrupted by shutdown".to_string());
+                }
+                file.write_all(&[7u8; 1024]).unwrap();
+                std::thread::sleep(Duration::from_millis(5));
+            }
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn shutdown_waits_for_downloads_to_stop_between_chunks() {
+        static SHUTDOWN: Shutdown = Shutdown::new();
+        let dir = temp_dir("shutdown");
+        let path = dir.join("download.part");
+        let download = slow_download(&SHUTDOWN, path.clone());
+        std::thread::sleep(Duration::from_millis(50));
+
+        assert_eq!(SHUTDOWN.begin(Duration::from_secs(5)), 0);
+        assert!(SHUTDOWN.enter().is_none());
+        assert_eq!(download.join().unwrap(), Err("interrupted by shutdown".to_string()));
+        // What's on disk is whole chunks, so resuming with a Range request is safe
+        let len = std::fs::metadata(&path).unwrap().len();
+        assert!(len > 0 && len.is_multiple_of(1024), "partial file is {} bytes", len);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn stuck_work_doesnt_hold_up_shutdown_past_the_grace_period() {
+        static SHUTDOWN: Shutdown = Shutdown::new();
+        let _stuck = SHUTDOWN.enter().unwrap();
+        let started = Instant::now();
+        assert_eq!(SHUTDOWN.begin(Duration::from_millis(200)), 1);
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+}
@@ -0,0 +1,167 @@
+// Spotlight metadata on macOS, read with mdls. kMDItemLastUsedDate is when the user last
+// opened a file through Launch Services, which backups and indexers don't touch the way
+// they touch atime, and kMDItemWhereFroms says where a download came from. Other
+// platforms have neither, so there every lookup returns None and callers fall back to
+// file times.
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+#[cfg(target_os = "macos")]
+use std::process::Command;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct SpotlightMetadata {
+    // Unix ms the file was last opened; None if it never was or Spotlight doesn't know
+    pub last_used_ms: Option<u64>,
+    // For downloads: the file's URL, then usually the page it was linked from
+    pub where_froms: Vec<String>,
+    // e.g. "PDF document"
+    pub content_kind: Option<String>,
+    pub authors: Vec<String>,
+}
+
+#[cfg(any(target_os = "macos", test))]
+const LAST_USED: &str = "kMDItemLastUsedDate";
+#[cfg(any(target_os = "macos", test))]
+const WHERE_FROMS: &str = "kMDItemWhereFroms";
+#[cfg(any(target_os = "macos", test))]
+const KIND: &str = "kMDItemKind";
+#[cfg(any(target_os = "macos", test))]
+const AUTHORS: &str = "kMDItemAuthors";
+// Files per mdls call when looking up last-used dates
+#[cfg(target_os = "macos")]
+const LAST_USED_BATCH: usize = 200;
+
+#[cfg(target_os = "macos")]
+pub fn metadata(path: &Path) -> Option<SpotlightMetadata> {
+    let mut command = Command::new("mdls");
+    for key in [LAST_USED, WHERE_FROMS, KIND, AUTHORS] {
+        command.args(["-name", key]);
+    }
+    let output = command.arg(path).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(parse_metadata(&String::from_utf8_lossy(&output.stdout)))
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn metadata(_path: &Path) -> Option<SpotlightMetadata> {
+    None
+}
+
+// Last-used dates of `paths`, in the same order; None when Spotlight can't be asked. A
+// batch mdls fails on (say, a file deleted meanwhile) gets None for each of its files.
+#[cfg(target_os = "macos")]
+pub fn last_used_ms(paths: &[PathBuf]) -> Option<Vec<Option<u64>>> {
+    let mut dates = Vec::with_capacity(paths.len());
+    for batch in paths.chunks(LAST_USED_BATCH) {
+        let output = Command::new("mdls").args(["-raw", "-name", LAST_USED]).args(batch).output().ok()?;
+        let values = output.status.success().then(|| parse_raw_dates(&String::from_utf8_lossy(&output.stdout)));
+        match values {
+            Some(values) if values.len() == batch.len() => dates.extend(values),
+            _ => dates.extend(std::iter::repeat(None).take(batch.len())),
+        }
+    }
+    Some(dates)
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn last_used_ms(_paths: &[PathBuf]) -> Option<Vec<Option<u64>>> {
+    None
+}
+
+// mdls prints dates like "2024-03-05 14:02:11 +0000", and "(null)" for a missing value
+#[cfg(any(target_os = "macos", test))]
+fn parse_date(value: &str) -> Option<u64> {
+    let date = chrono::DateTime::parse_from_str(value.trim(), "%Y-%m-%d %H:%M:%S %z").ok()?;
+    u64::try_from(date.timestamp_millis()).ok()
+}
+
+// With -raw, values of several files come separated by NUL characters
+#[cfg(any(target_os = "macos", test))]
+fn parse_raw_dates(output: &str) -> Vec<Option<u64>> {
+    output.strip_suffix('\0').unwrap_or(output).split('\0').map(parse_date).collect()
+}
+
+// A quoted mdls string, with its \" and \\ escapes and \Uxxxx characters undone
+#[cfg(any(target_os = "macos", test))]
+fn unquote(value: &str) -> String {
+    let value = value.trim().trim_end_matches(',');
+    let inner = value.strip_prefix('"').and_then(|v| v.strip_suffix('"')).unwrap_or(value);
+    let mut text = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            text.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('U') | Some('u') => {
+                let hex: String = chars.by_ref().take(4).collect();
+                text.extend(u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32));
+            }
+            Some(other) => text.push(other),
+            None => {}
+        }
+    }
+    text
+}
+
+// The default mdls listing: "key = value" lines, with lists spread over lines between
+// "(" and ")"
+#[cfg(any(target_os = "macos", test))]
+fn parse_metadata(output: &str) -> SpotlightMetadata {
+    let mut metadata = SpotlightMetadata::default();
+    let mut lines = output.lines();
+    while let Some(line) = lines.next() {
+        let Some((key, value)) = line.split_once('=') else { continue };
+        let value = value.trim();
+        let values: Vec<String> = if value == "(" {
+            lines.by_ref().take_while(|line| line.trim() != ")").map(unquote).collect()
+        } else if value == "(null)" {
+            Vec::new()
+        } else {
+            vec![unquote(value)]
+        };
+        match key.trim() {
+            LAST_USED => metadata.last_used_ms = values.first().and_then(|value| parse_date(value)),
+            WHERE_FROMS => metadata.where_froms = values,
+            KIND => metadata.content_kind = values.into_iter().next(),
+            AUTHORS => metadata.authors = values,
+            _ => {}
+        }
+    }
+    metadata
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mdls_listings_and_raw_dates_are_parsed() {
+        let output = r#"kMDItemAuthors       = (
+    "Ren\U00e9e Smith",
+    "J. \"Doc\" Brown"
+)
+kMDItemKind          = "PDF document"
+kMDItemLastUsedDate  = 2024-03-05 14:02:11 +0000
+kMDItemWhereFroms    = (
+    "https://example.com/files/report.pdf",
+    "https://example.com/downloads"
+)
+"#;
+        let metadata = parse_metadata(output);
+        assert_eq!(metadata.authors, ["Renée Smith", "J. \"Doc\" Brown"]);
+        assert_eq!(metadata.content_kind.as_deref(), Some("PDF document"));
+        assert_eq!(metadata.last_used_ms, Some(1_709_647_331_000));
+        assert_eq!(metadata.where_froms.len(), 2);
+        assert_eq!(metadata.where_froms[0], "https://example.com/files/report.pdf");
+
+        let empty = "kMDItemAuthors = (null)\nkMDItemLastUsedDate = (null)\nkMDItemWhereFroms = (null)\n";
+        assert_eq!(parse_metadata(empty), SpotlightMetadata::default());
+
+        let raw = "2024-03-05 14:02:11 +0000\0(null)\x002024-03-05 15:02:11 +0100\0";
+        assert_eq!(parse_raw_dates(raw), [Some(1_709_647_331_000), None, Some(1_709_647_331_000)]);
+    }
+}
@@ -0,0 +1,214 @@
+// Files held for review before their final move. Staging moves each file into
+// <root>/_Organizer Staging/<batch_id>/ and records its original and proposed paths in
+// a manifest at app_data_dir/staging/<batch_id>.json, saved after every file so a
+// batch survives a crash or restart. Committing moves each file on to its proposed
+// destination or back to where it came from. The moves themselves are journaled
+// under the batch id like any other organization, so verification sees them too.
+use crate::error::{AppError, AppErrorKind};
+use crate::rename;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+pub const STAGING_FOLDER: &str = "_Organizer Staging";
+pub const MANIFEST_VERSION: u32 = 1;
+pub const DEFAULT_EXPIRY_DAYS: u64 = 14;
+const MANIFEST_DIR: &str = "staging";
+const DAY_MS: u64 = 24 * 60 * 60 * 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StagedStatus {
+    Staged,
+    // Moved on to the proposed destination
+    Committed,
+    // Moved back to the original path
+    Restored,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StagedFile {
+    pub original: String,
+    pub staged: String,
+    pub proposed: String,
+    pub status: StagedStatus,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StagedBatch {
+    pub version: u32,
+    pub batch_id: String,
+    pub root: String,
+    // Unix time in milliseconds
+    pub created_at: u64,
+    pub files: Vec<StagedFile>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StagedBatchSummary {
+    pub batch_id: String,
+    pub root: String,
+    pub created_at: u64,
+    pub staged: usize,
+    pub committed: usize,
+    pub restored: usize,
+    // Older than the staging expiry setting and still holding files
+    pub expiring: bool,
+}
+
+impl StagedBatch {
+    pub fn new(batch_id: &str, root: &str, now_ms: u64) -> Self {
+        StagedBatch {
+            version: MANIFEST_VERSION,
+            batch_id: batch_id.to_string(),
+            root: root.to_string(),
+            created_at: now_ms,
+            files: Vec::new(),
+        }
+    }
+
+    pub fn pending(&self) -> usize {
+        self.files.iter().filter(|file| file.status == StagedStatus::Staged).count()
+    }
+
+    fn summary(&self, expiry_days: u64, now_ms: u64) -> StagedBatchSummary {
+        let count = |status| self.files.iter().filter(|file| file.status == status).count();
+        let staged = count(StagedStatus::Staged);
+        StagedBatchSummary {
+            batch_id: self.batch_id.clone(),
+            root: self.root.clone(),
+            created_at: self.created_at,
+            staged,
+            committed: count(StagedStatus::Committed),
+            restored: count(StagedStatus::Restored),
+            expiring: staged > 0 && now_ms.saturating_sub(self.created_at) >= expiry_days.saturating_mul(DAY_MS),
+        }
+    }
+}
+
+pub fn staging_folder(root: &Path, batch_id: &str) -> PathBuf {
+    root.join(STAGING_FOLDER).join(batch_id)
+}
+
+// A path in the batch's folder named like the original, numbered when a file already
+// staged (or on disk) has that name
+pub fn staged_path(folder: &Path, original: &Path, batch: &StagedBatch) -> Result<PathBuf, AppError> {
+    let name = original
+        .file_name()
+        .ok_or_else(|| AppError::invalid_input(format!("{} is not a file", original.display())))?;
+    let used: HashSet<&str> = batch.files.iter().map(|file| file.staged.as_str()).collect();
+    let free = |path: &PathBuf| {
+        !used.contains(crate::paths::display_path(path).as_str()) && std::fs::symlink_metadata(path).is_err()
+    };
+    let named = folder.join(name);
+    std::iter::once(named.clone())
+        .chain((2..=rename::MAX_NUMBERED).map(|n| rename::numbered(&named, n)))
+        .find(free)
+        .ok_or_else(|| AppError::new(AppErrorKind::AlreadyExists, "No free name is left in the staging folder"))
+}
+
+fn manifest_path(app_data_dir: &Path, batch_id: &str) -> PathBuf {
+    app_data_dir.join(MANIFEST_DIR).join(format!("{}.json", batch_id))
+}
+
+pub fn load(app_data_dir: &Path, batch_id: &str) -> Result<StagedBatch, AppError> {
+    crate::journal::validate_batch_id(batch_id)?;
+    let text = std::fs::read_to_string(manifest_path(app_data_dir, batch_id))
+        .map_err(|e| AppError::from_io(&e, format!("No staged batch {}: {}", batch_id, e)))?;
+    let batch: StagedBatch = serde_json::from_str(&text)
+        .map_err(|e| AppError::invalid_input(format!("Staged batch {} is damaged: {}", batch_id, e)))?;
+    if batch.version != MANIFEST_VERSION {
+        return Err(AppError::unsupported(format!(
+            "Staged batch {} was saved in format {} and this version of the app reads format {}",
+            batch_id, batch.version, MANIFEST_VERSION
+        )));
+    }
+    Ok(batch)
+}
+
+// Written next to the manifest and renamed over it
+pub fn save(app_data_dir: &Path, batch: &StagedBatch) -> Result<(), String> {
+    let path = manifest_path(app_data_dir, &batch.batch_id);
+    std::fs::create_dir_all(app_data_dir.join(MANIFEST_DIR))
+        .map_err(|e| format!("Failed to create staging directory: {}", e))?;
+    let json = serde_json::to_vec_pretty(batch).map_err(|e| format!("Failed to serialize staged batch: {}", e))?;
+    let temp = path.with_extension("json.tmp");
+    std::fs::write(&temp, json).map_err(|e| format!("Failed to write {}: {}", temp.display(), e))?;
+    std::fs::rename(&temp, &path).map_err(|e| {
+        let _ = std::fs::remove_file(&temp);
+        format!("Failed to replace {}: {}", path.display(), e)
+    })
+}
+
+// Once nothing is left staged: drops the manifest and the (now empty) staging folders
+pub fn finish(app_data_dir: &Path, batch: &StagedBatch) {
+    if let Err(e) = std::fs::remove_file(manifest_path(app_data_dir, &batch.batch_id)) {
+        warn!("Failed to delete staging manifest {}: {}", batch.batch_id, e);
+    }
+    let folder = staging_folder(Path::new(&batch.root), &batch.batch_id);
+    // remove_dir only removes empty folders, so anything put there by hand stays
+    let _ = std::fs::remove_dir(&folder);
+    if let Some(parent) = folder.parent() {
+        let _ = std::fs::remove_dir(parent);
+    }
+}
+
+// Oldest first; unreadable manifests are logged and left out
+pub fn list(app_data_dir: &Path, expiry_days: u64, now_ms: u64) -> Vec<StagedBatchSummary> {
+    let Ok(entries) = std::fs::read_dir(app_data_dir.join(MANIFEST_DIR)) else { return Vec::new() };
+    let mut summaries: Vec<StagedBatchSummary> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let batch_id = name.strip_suffix(".json")?;
+            load(app_data_dir, batch_id)
+                .map_err(|e| warn!("Skipping staged batch {}: {}", batch_id, e.message))
+                .ok()
+        })
+        .map(|batch| batch.summary(expiry_days, now_ms))
+        .collect();
+    summaries.sort_by(|a, b| a.created_at.cmp(&b.created_at).then_with(|| a.batch_id.cmp(&b.batch_id)));
+    summaries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_dir;
+
+    #[test]
+    fn batches_survive_restarts_and_warn_when_old() {
+        let dir = temp_dir("manifest");
+        let folder = staging_folder(&dir, "review-1");
+        let mut batch = StagedBatch::new("review-1", &crate::paths::display_path(&dir), 0);
+        for original in ["/inbox/scan.pdf", "/downloads/scan.pdf"] {
+            let staged = staged_path(&folder, Path::new(original), &batch).unwrap();
+            batch.files.push(StagedFile {
+                original: original.into(),
+                staged: crate::paths::display_path(&staged),
+                proposed: "/docs/scan.pdf".into(),
+                status: StagedStatus::Staged,
+            });
+        }
+        // Same name from two folders: the second is numbered
+        assert!(batch.files[1].staged.ends_with("scan (2).pdf"), "{}", batch.files[1].staged);
+        save(&dir, &batch).unwrap();
+
+        let loaded = load(&dir, "review-1").unwrap();
+        assert_eq!(loaded.files, batch.files);
+        assert!(!list(&dir, 14, 13 * DAY_MS)[0].expiring);
+        assert!(list(&dir, 14, 14 * DAY_MS)[0].expiring);
+
+        let mut done = loaded;
+        done.files.iter_mut().for_each(|file| file.status = StagedStatus::Committed);
+        assert_eq!(done.pending(), 0);
+        assert!(!done.summary(14, 30 * DAY_MS).expiring);
+        std::fs::create_dir_all(&folder).unwrap();
+        finish(&dir, &done);
+        assert!(list(&dir, 14, 0).is_empty());
+        assert!(!dir.join(STAGING_FOLDER).exists());
+        assert!(load(&dir, "../settings").is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
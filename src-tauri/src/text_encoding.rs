@@ -0,0 +1,128 @@
+// Decoding plain text files that aren't UTF-8: Windows-1252 and Shift-JIS exports,
+// UTF-16 from Windows tools. A byte order mark decides when there is one. Otherwise
+// UTF-16 is recognised by its zero bytes, valid UTF-8 is taken as it is, and anything
+// else goes to chardetng's guess. Decoding replaces what doesn't fit, so a wrong guess
+// never fails the read, but text that comes out mostly control characters is refused
+// as binary rather than handed to the model as mojibake.
+use crate::error::AppError;
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8};
+
+// Share of control and replacement characters above which a file counts as binary
+pub const MAX_CONTROL_RATIO: f64 = 0.1;
+// How much of the file the UTF-16 and binary checks look at
+const SAMPLE_BYTES: usize = 64 * 1024;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodedText {
+    pub text: String,
+    // WHATWG name: "UTF-8", "windows-1252", "Shift_JIS", "UTF-16LE"...
+    pub encoding: &'static str,
+}
+
+// UTF-16 without a BOM: mostly-ASCII text has a zero in every other byte, on the high
+// side of each code unit
+fn sniff_utf16(bytes: &[u8]) -> Option<&'static Encoding> {
+    let sample = &bytes[..bytes.len().min(SAMPLE_BYTES)];
+    let units = sample.len() / 2;
+    if units < 2 {
+        return None;
+    }
+    let zeros_at = |offset: usize| sample.iter().skip(offset).step_by(2).take(units).filter(|&&b| b == 0).count();
+    let (even, odd) = (zeros_at(0), zeros_at(1));
+    let mostly = |count: usize| count * 10 >= units * 4;
+    let rarely = |count: usize| count * 20 <= units;
+    if mostly(odd) && rarely(even) {
+        Some(UTF_16LE)
+    } else if mostly(even) && rarely(odd) {
+        Some(UTF_16BE)
+    } else {
+        None
+    }
+}
+
+pub fn detect(bytes: &[u8]) -> &'static Encoding {
+    if let Some((encoding, _)) = Encoding::for_bom(bytes) {
+        return encoding;
+    }
+    if let Some(encoding) = sniff_utf16(bytes) {
+        return encoding;
+    }
+    if std::str::from_utf8(bytes).is_ok() {
+        return UTF_8;
+    }
+    let mut detector = chardetng::EncodingDetector::new();
+    detector.feed(bytes, true);
+    detector.guess(None, true)
+}
+
+// Tabs, line breaks and form feeds are text; other C0/C1 controls and U+FFFD aren't
+fn looks_binary(text: &str) -> bool {
+    let sample = text.char_indices().take_while(|(index, _)| *index < SAMPLE_BYTES).map(|(_, c)| c);
+    let (mut total, mut control) = (0usize, 0usize);
+    for c in sample {
+        total += 1;
+        if (c.is_control() && !matches!(c, '\t' | '\n' | '\r' | '\u{c}')) || c == char::REPLACEMENT_CHARACTER {
+            control += 1;
+        }
+    }
+    total > 0 && control as f64 / total as f64 > MAX_CONTROL_RATIO
+}
+
+// Decodes `bytes` as text, or an Unsupported error when they look binary
+pub fn decode(bytes: &[u8]) -> Result<DecodedText, AppError> {
+    let encoding = detect(bytes);
+    // decode() strips a BOM for the encoding it finds, which is the one detect() chose
+    let (text, _, _) = encoding.decode(bytes);
+    if looks_binary(&text) {
+        return Err(AppError::unsupported("This looks like a binary file, not text"));
+    }
+    Ok(DecodedText { text: text.into_owned(), encoding: encoding.name() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::AppErrorKind;
+
+    fn utf16(text: &str, little_endian: bool, bom: bool) -> Vec<u8> {
+        let units = (if bom { "\u{feff}" } else { "" }).encode_utf16().chain(text.encode_utf16());
+        units.flat_map(|unit| if little_endian { unit.to_le_bytes() } else { unit.to_be_bytes() }).collect()
+    }
+
+    #[test]
+    fn utf16_is_read_with_and_without_bom() {
+        let text = "Rechnung Nr. 42 – München\r\nBetrag: 1.234,56 €";
+        for (little_endian, bom) in [(true, true), (true, false), (false, true), (false, false)] {
+            let decoded = decode(&utf16(text, little_endian, bom)).unwrap();
+            assert_eq!(decoded.text, text, "little_endian {} bom {}", little_endian, bom);
+            assert_eq!(decoded.encoding, if little_endian { "UTF-16LE" } else { "UTF-16BE" });
+        }
+        let with_bom = decode(b"\xef\xbb\xbfplain").unwrap();
+        assert_eq!((with_bom.text.as_str(), with_bom.encoding), ("plain", "UTF-8"));
+    }
+
+    #[test]
+    fn legacy_encodings_are_detected() {
+        let (cp1252, _, _) = encoding_rs::WINDOWS_1252
+            .encode("Café crème brûlée, déjà vu. Prix : 12 € la pièce, façade élégante à Besançon.");
+        let decoded = decode(&cp1252).unwrap();
+        assert_eq!(decoded.encoding, "windows-1252");
+        assert!(decoded.text.contains("crème brûlée"), "{}", decoded.text);
+
+        let invoice = "請求書番号：四十二。お支払い期限は来月末日です。ご確認ください。";
+        let (sjis, _, _) = encoding_rs::SHIFT_JIS.encode(invoice);
+        let decoded = decode(&sjis).unwrap();
+        assert_eq!(decoded.encoding, "Shift_JIS");
+        assert!(decoded.text.contains("請求書"), "{}", decoded.text);
+    }
+
+    #[test]
+    fn binary_data_is_refused() {
+        let binary: Vec<u8> = (0..4096u32).map(|i| (i.wrapping_mul(2_654_435_761) >> 13) as u8).collect();
+        assert_eq!(decode(&binary).unwrap_err().kind, AppErrorKind::Unsupported);
+        let mut header = b"\x7fELF\x02\x01\x01\0\0\0\0\0\0\0\0\0".to_vec();
+        header.extend_from_slice(&[0u8; 64]);
+        assert_eq!(decode(&header).unwrap_err().kind, AppErrorKind::Unsupported);
+        assert_eq!(decode(b"").unwrap().text, "");
+    }
+}
@@ -0,0 +1,312 @@
+// Preview thumbnails for the review list, cached on disk under app_data_dir/thumbnails.
+// Cache files are named by a hash of path, size, mtime and requested size, so an edited
+// file gets a fresh thumbnail and stale ones age out through eviction.
+use crate::error::{AppError, AppErrorKind};
+use image::{DynamicImage, ImageDecoder, ImageReader};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::{Cursor, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const CACHE_DIR: &str = "thumbnails";
+// Least recently used thumbnails are removed once the cache grows past this
+const MAX_CACHE_BYTES: u64 = 200 * 1024 * 1024;
+const JPEG_QUALITY: u8 = 80;
+pub const DEFAULT_MAX_PX: u32 = 256;
+const MAX_MAX_PX: u32 = 1024;
+// A moov atom larger than this isn't read looking for cover art
+const MAX_MOOV_BYTES: u64 = 64 * 1024 * 1024;
+
+const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "gif", "bmp", "webp", "tif", "tiff", "ico"];
+const MP4_EXTENSIONS: &[&str] = &["mp4", "m4v", "mov", "m4a"];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Thumbnail {
+    pub base64: String,
+    pub mime_type: String,
+    pub width: u32,
+    pub height: u32,
+    // Served from the on-disk cache rather than generated
+    pub cached: bool,
+}
+
+pub fn cache_dir(app_data_dir: &Path) -> PathBuf {
+    app_data_dir.join(CACHE_DIR)
+}
+
+fn cache_key(path: &Path, metadata: &fs::Metadata, max_px: u32) -> String {
+    let modified_ms = metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_millis())
+        .unwrap_or(0);
+    let mut hasher = Sha256::new();
+    hasher.update(path.to_string_lossy().as_bytes());
+    hasher.update(format!("|{}|{}|{}", metadata.len(), modified_ms, max_px).as_bytes());
+    format!("{:x}", hasher.finalize())[..32].to_string()
+}
+
+// Returns the thumbnail for `path`, generating and caching it when needed
+pub fn thumbnail(app_data_dir: &Path, path: &Path, max_px: u32) -> Result<Thumbnail, AppError> {
+    use base64::Engine;
+
+    let max_px = max_px.clamp(16, MAX_MAX_PX);
+    let metadata = fs::metadata(path)
+        .map_err(|e| AppError::from_io(&e, format!("Failed to read file: {}", e)).with_path(path))?;
+    let dir = cache_dir(app_data_dir);
+    let cache_path = dir.join(format!("{}.jpg", cache_key(path, &metadata, max_px)));
+
+    if let Ok(bytes) = fs::read(&cache_path) {
+        if let Ok((width, height)) = image::image_dimensions(&cache_path) {
+            // Bump the mtime so eviction sees this thumbnail as recently used
+            if let Ok(file) = fs::File::options().write(true).open(&cache_path) {
+                let _ = file.set_modified(SystemTime::now());
+            }
+            return Ok(Thumbnail {
+                base64: base64::engine::general_purpose::STANDARD.encode(&bytes),
+                mime_type: "image/jpeg".to_string(),
+                width,
+                height,
+                cached: true,
+            });
+        }
+    }
+
+    let image = load_preview_image(path)?;
+    let thumb = image.thumbnail(max_px, max_px);
+    let (width, height) = (thumb.width(), thumb.height());
+    let mut bytes = Vec::new();
+    image::codecs::jpeg::JpegEncoder::new_with_quality(&mut bytes, JPEG_QUALITY)
+        .encode_image(&DynamicImage::ImageRgb8(thumb.to_rgb8()))
+        .map_err(|e| AppError::io(format!("Failed to encode thumbnail: {}", e)).with_path(path))?;
+
+    // A cache that can't be written only costs speed
+    let stored = fs::create_dir_all(&dir).and_then(|_| fs::write(&cache_path, &bytes));
+    match stored {
+        Ok(()) => evict(&dir, MAX_CACHE_BYTES),
+        Err(e) => tracing::warn!("Failed to cache thumbnail {}: {}", cache_path.display(), e),
+    }
+
+    Ok(Thumbnail {
+        base64: base64::engine::general_purpose::STANDARD.encode(&bytes),
+        mime_type: "image/jpeg".to_string(),
+        width,
+        height,
+        cached: false,
+    })
+}
+
+fn invalid_data(path: &Path, message: String) -> AppError {
+    AppError::new(AppErrorKind::InvalidData, message).with_path(path)
+}
+
+fn load_preview_image(path: &Path) -> Result<DynamicImage, AppError> {
+    let extension = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    if IMAGE_EXTENSIONS.contains(&extension.as_str()) {
+        let reader = ImageReader::open(path)
+            .map_err(|e| AppError::from_io(&e, format!("Failed to open image: {}", e)).with_path(path))?;
+        decode_oriented(reader).map_err(|e| invalid_data(path, format!("Failed to decode image: {}", e)))
+    } else if MP4_EXTENSIONS.contains(&extension.as_str()) {
+        let cover = mp4_cover_art(path)
+            .map_err(|e| AppError::from_io(&e, format!("Failed to read video: {}", e)).with_path(path))?
+            .ok_or_else(|| AppError::unsupported("Video has no embedded cover art").with_path(path))?;
+        let reader = ImageReader::new(Cursor::new(cover));
+        decode_oriented(reader).map_err(|e| invalid_data(path, format!("Failed to decode cover art: {}", e)))
+    } else if extension == "pdf" {
+        Err(AppError::unsupported("No PDF renderer is available for thumbnails").with_path(path))
+    } else {
+        Err(AppError::unsupported(format!("No thumbnail available for .{} files", extension)).with_path(path))
+    }
+}
+
+// Decodes with the EXIF orientation applied, so portrait phone photos aren't sideways.
+// Decoders for malformed files have been known to panic; that is reported as an error.
+fn decode_oriented<R: std::io::BufRead + Seek>(reader: ImageReader<R>) -> Result<DynamicImage, String> {
+    let decode = move || -> image::ImageResult<DynamicImage> {
+        let mut decoder = reader.with_guessed_format()?.into_decoder()?;
+        let orientation = decoder.orientation()?;
+        let mut image = DynamicImage::from_decoder(decoder)?;
+        image.apply_orientation(orientation);
+        Ok(image)
+    };
+    match std::panic::catch_unwind(std::panic::AssertUnwindSafe(decode)) {
+        Ok(result) => result.map_err(|e| e.to_string()),
+        Err(_) => Err("the file is corrupt or uses an unsupported encoding".to_string()),
+    }
+}
+
+// Reads the next atom header, returning (type, payload size)
+fn read_atom_header(reader: &mut impl Read, remaining: u64) -> std::io::Result<Option<([u8; 4], u64)>> {
+    if remaining < 8 {
+        return Ok(None);
+    }
+    let mut header = [0u8; 8];
+    reader.read_exact(&mut header)?;
+    let size = u32::from_be_bytes(header[0..4].try_into().unwrap()) as u64;
+    let kind: [u8; 4] = header[4..8].try_into().unwrap();
+    let payload = match size {
+        // Extends to the end of the enclosing atom / file
+        0 => remaining - 8,
+        1 => {
+            let mut large = [0u8; 8];
+            reader.read_exact(&mut large)?;
+            u64::from_be_bytes(large).saturating_sub(16)
+        }
+        size => size.saturating_sub(8),
+    };
+    Ok(Some((kind, payload)))
+}
+
+// Finds the child atom `kind` in an in-memory atom payload
+fn child_atom<'a>(mut data: &'a [u8], kind: &[u8; 4]) -> Option<&'a [u8]> {
+    while data.len() >= 8 {
+        let size = u32::from_be_bytes(data[0..4].try_into().unwrap()) as usize;
+        let size = if size == 0 { data.len() } else { size };
+        if size < 8 || size > data.len() {
+            return None;
+        }
+        if &data[4..8] == kind {
+            return Some(&data[8..size]);
+        }
+        data = &data[size..];
+    }
+    None
+}
+
+// Cover art stored by iTunes-style tagging in moov/udta/meta/ilst/covr/data
+fn mp4_cover_art(path: &Path) -> std::io::Result<Option<Vec<u8>>> {
+    let mut file = fs::File::open(path)?;
+    let len = file.metadata()?.len();
+    let mut position = 0;
+    while let Some((kind, payload)) = read_atom_header(&mut file, len - position)? {
+        let header_len = file.stream_position()? - position;
+        if &kind == b"moov" {
+            if payload > MAX_MOOV_BYTES {
+                return Ok(None);
+            }
+            let mut moov = vec![0u8; payload as usize];
+            file.read_exact(&mut moov)?;
+            let covr = child_atom(&moov, b"udta")
+                .and_then(|udta| child_atom(udta, b"meta"))
+                // meta is a full atom: version and flags precede its children
+                .and_then(|meta| meta.get(4..))
+                .and_then(|meta| child_atom(meta, b"ilst"))
+                .and_then(|ilst| child_atom(ilst, b"covr"))
+                .and_then(|covr| child_atom(covr, b"data"))
+                // data payload: type indicator and locale, then the image bytes
+                .and_then(|data| data.get(8..))
+                .filter(|image| !image.is_empty());
+            return Ok(covr.map(|image| image.to_vec()));
+        }
+        position += header_len + payload;
+        if position >= len {
+            break;
+        }
+        file.seek(SeekFrom::Start(position))?;
+    }
+    Ok(None)
+}
+
+// Removes the least recently used thumbnails until the cache fits in max_bytes
+fn evict(dir: &Path, max_bytes: u64) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    let mut files: Vec<(SystemTime, u64, PathBuf)> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok()?;
+            metadata.is_file().then(|| (metadata.modified().unwrap_or(UNIX_EPOCH), metadata.len(), entry.path()))
+        })
+        .collect();
+    let mut total: u64 = files.iter().map(|(_, size, _)| size).sum();
+    if total <= max_bytes {
+        return;
+    }
+    files.sort();
+    for (_, size, path) in files {
+        if total <= max_bytes {
+            break;
+        }
+        match fs::remove_file(&path) {
+            Ok(()) => total -= size,
+            Err(e) => tracing::warn!("Failed to evict thumbnail {}: {}", path.display(), e),
+        }
+    }
+}
+
+pub fn clear_cache(app_data_dir: &Path) -> Result<(), AppError> {
+    let dir = cache_dir(app_data_dir);
+    match fs::remove_dir_all(&dir) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(AppError::from_io(&e, format!("Failed to clear thumbnail cache: {}", e)).with_path(&dir)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_dir;
+
+    #[test]
+    fn images_are_scaled_and_cached() {
+        let dir = temp_dir("scale");
+        let source = dir.join("photo.png");
+        image::RgbImage::from_pixel(400, 200, image::Rgb([200, 30, 30])).save(&source).unwrap();
+
+        let first = thumbnail(&dir, &source, 100).unwrap();
+        assert_eq!((first.width, first.height), (100, 50));
+        assert!(!first.cached);
+
+        let second = thumbnail(&dir, &source, 100).unwrap();
+        assert!(second.cached);
+        assert_eq!((second.width, second.height), (100, 50));
+
+        clear_cache(&dir).unwrap();
+        assert!(!cache_dir(&dir).exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn corrupt_images_return_invalid_data() {
+        let dir = temp_dir("corrupt");
+        let source = dir.join("broken.jpg");
+        fs::write(&source, b"\xFF\xD8\xFF\xE0 definitely not a jpeg").unwrap();
+
+        let err = thumbnail(&dir, &source, 64).unwrap_err();
+        assert_eq!(err.kind, AppErrorKind::InvalidData);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn cover_art_is_found_in_mp4_metadata() {
+        fn atom(kind: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+            let mut atom = ((payload.len() + 8) as u32).to_be_bytes().to_vec();
+            atom.extend_from_slice(kind);
+            atom.extend_from_slice(payload);
+            atom
+        }
+        let image = b"\x89PNG fake cover";
+        let data = atom(b"data", &[&[0, 0, 0, 14, 0, 0, 0, 0][..], image].concat());
+        let meta_payload = [&[0, 0, 0, 0][..], &atom(b"ilst", &atom(b"covr", &data))].concat();
+        let moov = atom(b"moov", &atom(b"udta", &atom(b"meta", &meta_payload)));
+
+        let dir = temp_dir("mp4");
+        let source = dir.join("clip.mp4");
+        fs::write(&source, [atom(b"ftyp", b"isom"), atom(b"mdat", &[0; 64]), moov].concat()).unwrap();
+        assert_eq!(mp4_cover_art(&source).unwrap().as_deref(), Some(&image[..]));
+
+        let bare = dir.join("bare.mp4");
+        fs::write(&bare, [atom(b"ftyp", b"isom"), atom(b"moov", &[])].concat()).unwrap();
+        assert_eq!(mp4_cover_art(&bare).unwrap(), None);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,292 @@
+// Compact, prompt-sized overview of a directory tree for folder-structure proposals.
+// The tree is walked once; directories below max_depth are folded into their
+// ancestor, and when the serialized summary would exceed the character budget the
+// deepest levels are dropped first (breadth-first inclusion).
+use crate::scan_filter::ScanFilter;
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+pub const DEFAULT_MAX_DEPTH: usize = 4;
+pub const DEFAULT_MAX_ENTRIES_PER_DIR: usize = 20;
+pub const DEFAULT_MAX_CHARS: usize = 8_000;
+const TOP_EXTENSIONS: usize = 5;
+const SAMPLE_FILES: usize = 5;
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ExtensionCount {
+    pub extension: String,
+    pub count: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DirectorySummary {
+    pub name: String,
+    // Relative to the summarized root, "" for the root itself
+    pub path: String,
+    // Files directly inside, or anywhere beneath when `collapsed`
+    pub file_count: u64,
+    // Every file beneath, including subdirectories
+    pub total_files: u64,
+    pub total_size: u64,
+    pub top_extensions: Vec<ExtensionCount>,
+    pub sample_files: Vec<String>,
+    // The "and N more" markers for files and subdirectories not listed
+    pub more_files: u64,
+    pub more_directories: u64,
+    // Set at max_depth: deeper directories were folded into this one's counts
+    pub collapsed: bool,
+    pub children: Vec<DirectorySummary>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TreeSummary {
+    pub root: DirectorySummary,
+    // Serialized length of `root`, to compare against the budget
+    pub chars: usize,
+    // Whether directories were left out to stay within the budget
+    pub budget_exceeded: bool,
+}
+
+#[derive(Default)]
+struct Node {
+    name: String,
+    path: String,
+    parent: Option<usize>,
+    children: Vec<usize>,
+    file_count: u64,
+    total_files: u64,
+    total_size: u64,
+    extensions: HashMap<String, u64>,
+    sample_files: Vec<String>,
+    collapsed: bool,
+}
+
+pub fn summarize(
+    root: &Path,
+    filter: &ScanFilter,
+    max_depth: usize,
+    max_entries_per_dir: usize,
+    max_chars: usize,
+) -> std::io::Result<TreeSummary> {
+    if !std::fs::metadata(root)?.is_dir() {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "Not a directory"));
+    }
+    let nodes = walk(root, filter, max_depth)?;
+    Ok(trim_to_budget(&nodes, max_entries_per_dir, max_chars))
+}
+
+fn walk(root: &Path, filter: &ScanFilter, max_depth: usize) -> std::io::Result<Vec<Node>> {
+    let root_name = root
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| root.to_string_lossy().to_string());
+    let mut nodes = vec![Node { name: root_name, ..Default::default() }];
+    let mut index: HashMap<PathBuf, usize> = HashMap::from([(PathBuf::new(), 0)]);
+
+    let entries = WalkDir::new(root)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|e| e.depth() == 0 || !crate::walk_entry_skipped(filter, e))
+        .filter_map(crate::walk_entry_ok);
+    for entry in entries {
+        let Ok(relative) = entry.path().strip_prefix(root) else { continue };
+        if entry.file_type().is_dir() {
+            if entry.depth() == 0 || entry.depth() > max_depth {
+                continue;
+            }
+            let parent = index[relative.parent().unwrap_or(Path::new(""))];
+            let id = nodes.len();
+            nodes.push(Node {
+                name: entry.file_name().to_string_lossy().to_string(),
+                path: relative.to_string_lossy().to_string(),
+                parent: Some(parent),
+                ..Default::default()
+            });
+            nodes[parent].children.push(id);
+            index.insert(relative.to_path_buf(), id);
+        } else if entry.file_type().is_file() {
+            // Files below max_depth count towards the deepest listed ancestor
+            let dir = relative.parent().unwrap_or(Path::new(""));
+            let listed: PathBuf = dir.components().take(max_depth).collect();
+            let Some(&id) = index.get(&listed) else { continue };
+            let size = entry.metadata().map(|m| m.len()).unwrap_or(0);
+            let node = &mut nodes[id];
+            node.file_count += 1;
+            if listed.as_path() != dir {
+                node.collapsed = true;
+            }
+            if node.sample_files.len() < SAMPLE_FILES {
+                node.sample_files.push(entry.file_name().to_string_lossy().to_string());
+            }
+            if let Some(ext) = entry.path().extension() {
+                *node.extensions.entry(ext.to_string_lossy().to_lowercase()).or_default() += 1;
+            }
+            let mut current = Some(id);
+            while let Some(i) = current {
+                nodes[i].total_files += 1;
+                nodes[i].total_size += size;
+                current = nodes[i].parent;
+            }
+        }
+    }
+    Ok(nodes)
+}
+
+// The node on its own, without children
+fn summary_of(node: &Node, max_entries_per_dir: usize) -> DirectorySummary {
+    let mut top_extensions: Vec<ExtensionCount> = node
+        .extensions
+        .iter()
+        .map(|(extension, &count)| ExtensionCount { extension: extension.clone(), count })
+        .collect();
+    top_extensions.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.extension.cmp(&b.extension)));
+    top_extensions.truncate(TOP_EXTENSIONS);
+    let sample_files: Vec<String> = node.sample_files.iter().take(max_entries_per_dir).cloned().collect();
+    DirectorySummary {
+        name: node.name.clone(),
+        path: node.path.clone(),
+        file_count: node.file_count,
+        total_files: node.total_files,
+        total_size: node.total_size,
+        top_extensions,
+        more_files: node.file_count - sample_files.len() as u64,
+        sample_files,
+        more_directories: 0,
+        collapsed: node.collapsed,
+        children: Vec::new(),
+    }
+}
+
+fn serialized_len(summary: &DirectorySummary) -> usize {
+    serde_json::to_string(summary).map(|s| s.len()).unwrap_or(0)
+}
+
+fn trim_to_budget(nodes: &[Node], max_entries_per_dir: usize, max_chars: usize) -> TreeSummary {
+    let summaries: Vec<DirectorySummary> = nodes.iter().map(|n| summary_of(n, max_entries_per_dir)).collect();
+    let mut included = vec![false; nodes.len()];
+    let mut more_directories = vec![0u64; nodes.len()];
+    let mut budget_exceeded = false;
+
+    // The root is always present; everything else is admitted level by level
+    included[0] = true;
+    let mut used = serialized_len(&summaries[0]);
+    let mut queue = VecDeque::from([0usize]);
+    while let Some(id) = queue.pop_front() {
+        // Busiest subdirectories first, so the cut drops the least informative ones
+        let mut children = nodes[id].children.clone();
+        children.sort_by(|&a, &b| {
+            nodes[b].total_files.cmp(&nodes[a].total_files).then_with(|| nodes[a].name.cmp(&nodes[b].name))
+        });
+        for (position, child) in children.into_iter().enumerate() {
+            // +1 for the separating comma
+            let cost = serialized_len(&summaries[child]) + 1;
+            if position >= max_entries_per_dir {
+                more_directories[id] += 1;
+            } else if used + cost > max_chars {
+                more_directories[id] += 1;
+                budget_exceeded = true;
+            } else {
+                used += cost;
+                included[child] = true;
+                queue.push_back(child);
+            }
+        }
+    }
+
+    fn assemble(
+        id: usize,
+        nodes: &[Node],
+        summaries: &[DirectorySummary],
+        included: &[bool],
+        more_directories: &[u64],
+    ) -> DirectorySummary {
+        let mut summary = summaries[id].clone();
+        summary.more_directories = more_directories[id];
+        let mut children: Vec<usize> = nodes[id].children.iter().copied().filter(|&c| included[c]).collect();
+        children.sort_by(|&a, &b| nodes[a].name.cmp(&nodes[b].name));
+        summary.children = children
+            .into_iter()
+            .map(|c| assemble(c, nodes, summaries, included, more_directories))
+            .collect();
+        summary
+    }
+
+    let root = assemble(0, nodes, &summaries, &included, &more_directories);
+    TreeSummary {
+        chars: serialized_len(&root),
+        root,
+        budget_exceeded,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_dir;
+    use std::fs;
+
+    fn touch(dir: &Path, relative: &str, bytes: usize) {
+        let path = dir.join(relative);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, vec![b'x'; bytes]).unwrap();
+    }
+
+    #[test]
+    fn directories_are_counted_and_deep_levels_collapsed() {
+        let dir = temp_dir("counts");
+        touch(&dir, "a.jpg", 10);
+        touch(&dir, "b.JPG", 10);
+        touch(&dir, "notes.txt", 5);
+        touch(&dir, ".DS_Store", 1);
+        touch(&dir, "Photos/2023/Trip/c.jpg", 100);
+        touch(&dir, "Photos/2023/Trip/deeper/d.png", 50);
+
+        let summary = summarize(&dir, &ScanFilter::default(), 2, 20, DEFAULT_MAX_CHARS).unwrap();
+        let root = &summary.root;
+        assert_eq!((root.file_count, root.total_files, root.total_size), (3, 5, 175));
+        assert_eq!(root.top_extensions[0], ExtensionCount { extension: "jpg".to_string(), count: 2 });
+        assert!(!summary.budget_exceeded);
+
+        let year = &root.children[0].children[0];
+        assert_eq!(year.path, Path::new("Photos").join("2023").to_string_lossy());
+        assert!(year.collapsed);
+        assert!(year.children.is_empty());
+        assert_eq!(year.file_count, 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn wide_directories_are_truncated() {
+        let dir = temp_dir("wide");
+        for i in 0..8 {
+            touch(&dir, &format!("dir{}/file.txt", i), 1);
+            touch(&dir, &format!("file{}.txt", i), 1);
+        }
+        let summary = summarize(&dir, &ScanFilter::default(), 3, 3, DEFAULT_MAX_CHARS).unwrap();
+        assert_eq!(summary.root.children.len(), 3);
+        assert_eq!(summary.root.more_directories, 5);
+        assert_eq!(summary.root.sample_files.len(), 3);
+        assert_eq!(summary.root.more_files, 5);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn budget_drops_the_deepest_levels_first() {
+        let dir = temp_dir("budget");
+        for i in 0..4 {
+            touch(&dir, &format!("top{}/nested/file.txt", i), 1);
+        }
+        let own_len = |s: &DirectorySummary| serialized_len(&DirectorySummary { children: Vec::new(), ..s.clone() });
+        let full = summarize(&dir, &ScanFilter::default(), 4, 20, DEFAULT_MAX_CHARS).unwrap();
+        // Room for the root and its direct children, but not the level below
+        let budget = own_len(&full.root) + full.root.children.iter().map(|c| own_len(c) + 1).sum::<usize>();
+
+        let trimmed = summarize(&dir, &ScanFilter::default(), 4, 20, budget).unwrap();
+        assert!(trimmed.budget_exceeded);
+        assert_eq!(trimmed.root.children.len(), 4);
+        assert!(trimmed.root.children.iter().all(|c| c.children.is_empty() && c.more_directories == 1));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
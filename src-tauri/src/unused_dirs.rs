@@ -0,0 +1,325 @@
+// Directories nobody has touched in a long time, for archiving whole project folders at
+// once. A file's activity is the later of its modified and accessed times; a directory's
+// is the newest activity of anything under it, or its own modified time when it holds no
+// files. A directory is unused when that activity is older than the cutoff, and only the
+// highest unused directory is reported, so an old project shows up once rather than
+// with every subfolder. A directory with anything unreadable under it is never called
+// unused, since the part that couldn't be read may be what's still in use.
+// On macOS, a file's Spotlight last-used date takes the place of its accessed time when
+// Spotlight has one, since backups and indexers bump atime without anyone using the
+// file. Each entry says which of the three signals its newest activity came from.
+use crate::paths;
+use crate::scan_filter::ScanFilter;
+use crate::spotlight;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use walkdir::WalkDir;
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct UnusedDirectory {
+    pub path: String,
+    pub total_bytes: u64,
+    pub file_count: u64,
+    // Unix ms; 0 when the file system doesn't say
+    pub newest_activity_ms: u64,
+    // The file with that activity; None for a directory without files
+    pub newest_path: Option<String>,
+    pub activity_signal: ActivitySignal,
+}
+
+// Where an activity time came from, from least to most telling of actual use
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ActivitySignal {
+    Mtime,
+    Atime,
+    Spotlight,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct UnusedDirectoryReport {
+    // Largest first
+    pub directories: Vec<UnusedDirectory>,
+    pub reclaimable_bytes: u64,
+    // Unix ms; activity before this counts as unused
+    pub cutoff_ms: u64,
+    pub directories_scanned: u64,
+    pub files_scanned: u64,
+    // Entries that couldn't be read; the directories above them aren't reported
+    pub unreadable: u64,
+    // Whether Spotlight last-used dates were available
+    pub spotlight: bool,
+}
+
+#[derive(Default)]
+struct DirStats {
+    total_bytes: u64,
+    file_count: u64,
+    newest: Option<(u64, PathBuf, ActivitySignal)>,
+    own_modified_ms: u64,
+    incomplete: bool,
+}
+
+impl DirStats {
+    fn activity_ms(&self) -> u64 {
+        self.newest.as_ref().map_or(self.own_modified_ms, |(ms, _, _)| *ms)
+    }
+
+    fn activity_signal(&self) -> ActivitySignal {
+        self.newest.as_ref().map_or(ActivitySignal::Mtime, |(_, _, signal)| *signal)
+    }
+}
+
+struct FoundFile {
+    path: PathBuf,
+    size: u64,
+    modified_ms: u64,
+    accessed_ms: u64,
+}
+
+fn millis(time: std::io::Result<SystemTime>) -> u64 {
+    time.ok().and_then(|t| t.duration_since(UNIX_EPOCH).ok()).map_or(0, |d| d.as_millis() as u64)
+}
+
+// The later of the modified time and the last-used date, or the accessed time when
+// there's no last-used date; a tie goes to the modified time
+fn activity(file: &FoundFile, last_used_ms: Option<u64>) -> (u64, ActivitySignal) {
+    let (used_ms, signal) = match last_used_ms {
+        Some(ms) => (ms, ActivitySignal::Spotlight),
+        None => (file.accessed_ms, ActivitySignal::Atime),
+    };
+    if used_ms > file.modified_ms {
+        (used_ms, signal)
+    } else {
+        (file.modified_ms, ActivitySignal::Mtime)
+    }
+}
+
+// `path` and every directory above it up to `root`
+fn up_to<'a>(path: &'a Path, root: &'a Path) -> impl Iterator<Item = &'a Path> {
+    path.ancestors().take_while(move |dir| dir.starts_with(root))
+}
+
+// Directories under `roots` with no activity since `cutoff_ms`, and at least min_bytes
+// in size. Roots nested inside another root are only walked once. Symlinks are left
+// out unless follow_symlinks is set; a cycle then counts as unreadable.
+pub fn find(
+    roots: &[PathBuf],
+    filter: &ScanFilter,
+    follow_symlinks: bool,
+    cutoff_ms: u64,
+    min_bytes: u64,
+) -> UnusedDirectoryReport {
+    find_with(roots, filter, follow_symlinks, cutoff_ms, min_bytes, &spotlight::last_used_ms)
+}
+
+// Last-used dates for a list of files, or None when there's nothing to ask
+type LastUsedLookup<'a> = dyn Fn(&[PathBuf]) -> Option<Vec<Option<u64>>> + 'a;
+
+fn find_with(
+    roots: &[PathBuf],
+    filter: &ScanFilter,
+    follow_symlinks: bool,
+    cutoff_ms: u64,
+    min_bytes: u64,
+    last_used: &LastUsedLookup<'_>,
+) -> UnusedDirectoryReport {
+    let mut report = UnusedDirectoryReport { cutoff_ms, ..Default::default() };
+    for root in paths::distinct_roots(roots) {
+        let mut dirs: HashMap<PathBuf, DirStats> = HashMap::new();
+        let mut files: Vec<FoundFile> = Vec::new();
+        let entries = WalkDir::new(&root)
+            .follow_links(follow_symlinks)
+            .into_iter()
+            .filter_entry(|e| e.depth() == 0 || !crate::walk_entry_skipped(filter, e));
+        for entry in entries {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    report.unreadable += 1;
+                    for dir in up_to(e.path().unwrap_or(&root), &root) {
+                        dirs.entry(dir.to_path_buf()).or_default().incomplete = true;
+                    }
+                    continue;
+                }
+            };
+            let metadata = match entry.metadata() {
+                Ok(metadata) => metadata,
+                Err(_) => {
+                    report.unreadable += 1;
+                    for dir in up_to(entry.path(), &root).skip(1) {
+                        dirs.entry(dir.to_path_buf()).or_default().incomplete = true;
+                    }
+                    continue;
+                }
+            };
+            if entry.file_type().is_dir() {
+                report.directories_scanned += 1;
+                dirs.entry(entry.path().to_path_buf()).or_default().own_modified_ms = millis(metadata.modified());
+                continue;
+            }
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            report.files_scanned += 1;
+            files.push(FoundFile {
+                path: entry.into_path(),
+                size: metadata.len(),
+                modified_ms: millis(metadata.modified()),
+                accessed_ms: millis(metadata.accessed()),
+            });
+        }
+
+        // Asked all at once after the walk, since each lookup runs mdls
+        let paths: Vec<PathBuf> = files.iter().map(|file| file.path.clone()).collect();
+        let last_used = last_used(&paths).filter(|dates| dates.len() == files.len());
+        report.spotlight |= last_used.is_some();
+        for (index, file) in files.iter().enumerate() {
+            let (activity, signal) = activity(file, last_used.as_ref().and_then(|dates| dates[index]));
+            for dir in up_to(&file.path, &root).skip(1) {
+                let stats = dirs.entry(dir.to_path_buf()).or_default();
+                stats.total_bytes += file.size;
+                stats.file_count += 1;
+                if stats.newest.as_ref().is_none_or(|(newest, _, _)| activity > *newest) {
+                    stats.newest = Some((activity, file.path.clone(), signal));
+                }
+            }
+        }
+
+        let unused = |stats: &DirStats| !stats.incomplete && stats.activity_ms() < cutoff_ms;
+        for (dir, stats) in &dirs {
+            let parent_unused = dir != &root && dir.parent().and_then(|parent| dirs.get(parent)).is_some_and(unused);
+            if !unused(stats) || parent_unused || stats.total_bytes < min_bytes {
+                continue;
+            }
+            report.directories.push(UnusedDirectory {
+                path: paths::display_path(dir),
+                total_bytes: stats.total_bytes,
+                file_count: stats.file_count,
+                newest_activity_ms: stats.activity_ms(),
+                newest_path: stats.newest.as_ref().map(|(_, path, _)| paths::display_path(path)),
+                activity_signal: stats.activity_signal(),
+            });
+        }
+    }
+    report.directories.sort_by(|a, b| b.total_bytes.cmp(&a.total_bytes).then_with(|| a.path.cmp(&b.path)));
+    report.reclaimable_bytes = report.directories.iter().map(|d| d.total_bytes).sum();
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_dir;
+    use std::fs::{self, FileTimes};
+    use std::time::Duration;
+
+    const DAY_MS: u64 = 24 * 60 * 60 * 1000;
+
+    // Writes `path` with its modified and accessed times `days` ago
+    fn write_aged(path: &Path, bytes: usize, days: u64) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, vec![0u8; bytes]).unwrap();
+        let time = SystemTime::now() - Duration::from_millis(days * DAY_MS);
+        let file = fs::File::options().write(true).open(path).unwrap();
+        file.set_times(FileTimes::new().set_modified(time).set_accessed(time)).unwrap();
+    }
+
+    #[test]
+    fn nested_unused_directories_collapse_to_the_highest() {
+        let dir = temp_dir("collapse");
+        write_aged(&dir.join("old-project/src/main.rs"), 300, 900);
+        write_aged(&dir.join("old-project/docs/notes.md"), 200, 800);
+        write_aged(&dir.join("old-project/docs/drafts/x.md"), 50, 1000);
+        write_aged(&dir.join("active/old-part/data.bin"), 400, 900);
+        write_aged(&dir.join("active/today.txt"), 10, 0);
+        write_aged(&dir.join("tiny-old/a.txt"), 5, 900);
+
+        let cutoff_ms = millis(Ok(SystemTime::now())) - 730 * DAY_MS;
+        let report = find(std::slice::from_ref(&dir), &ScanFilter::default(), false, cutoff_ms, 0);
+        let found: Vec<&str> = report.directories.iter().map(|d| d.path.as_str()).collect();
+        let shown = |relative: &str| paths::display_path(dir.join(relative));
+        assert_eq!(found, [shown("old-project"), shown("active/old-part"), shown("tiny-old")]);
+        let project = &report.directories[0];
+        assert_eq!((project.total_bytes, project.file_count), (550, 3));
+        assert_eq!(project.newest_path, Some(shown("old-project/docs/notes.md")));
+        assert_eq!(report.reclaimable_bytes, 955);
+        assert_eq!((report.files_scanned, report.unreadable), (6, 0));
+
+        let report = find(std::slice::from_ref(&dir), &ScanFilter::default(), false, cutoff_ms, 100);
+        assert_eq!(report.directories.len(), 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn a_root_untouched_as_a_whole_is_reported_itself() {
+        let dir = temp_dir("root");
+        write_aged(&dir.join("a/one.txt"), 10, 400);
+        write_aged(&dir.join("b/two.txt"), 20, 500);
+        let cutoff_ms = millis(Ok(SystemTime::now())) - 365 * DAY_MS;
+        let report = find(std::slice::from_ref(&dir), &ScanFilter::default(), false, cutoff_ms, 0);
+        assert_eq!(report.directories.len(), 1);
+        assert_eq!(report.directories[0].path, paths::display_path(&dir));
+        assert_eq!(report.directories[0].newest_path, Some(paths::display_path(dir.join("a/one.txt"))));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn spotlight_dates_replace_accessed_times_when_there_are_any() {
+        let dir = temp_dir("spotlight");
+        write_aged(&dir.join("opened/report.pdf"), 10, 900);
+        write_aged(&dir.join("untracked/notes.txt"), 10, 900);
+        write_aged(&dir.join("old/a.txt"), 10, 900);
+        // Touched by a backup yesterday, but last opened long ago
+        let accessed = SystemTime::now() - Duration::from_millis(DAY_MS);
+        let file = fs::File::options().write(true).open(dir.join("untracked/notes.txt")).unwrap();
+        file.set_times(FileTimes::new().set_accessed(accessed)).unwrap();
+        let file = fs::File::options().write(true).open(dir.join("opened/report.pdf")).unwrap();
+        file.set_times(FileTimes::new().set_accessed(accessed)).unwrap();
+
+        let now = millis(Ok(SystemTime::now()));
+        let last_used = |paths: &[PathBuf]| -> Option<Vec<Option<u64>>> {
+            Some(paths.iter().map(|path| path.ends_with("opened/report.pdf").then_some(now - 800 * DAY_MS)).collect())
+        };
+        let cutoff_ms = now - 365 * DAY_MS;
+        let report = find_with(std::slice::from_ref(&dir), &ScanFilter::default(), false, cutoff_ms, 0, &last_used);
+        assert!(report.spotlight);
+        let found: Vec<(&str, ActivitySignal)> =
+            report.directories.iter().map(|d| (d.path.as_str(), d.activity_signal)).collect();
+        let shown = |relative: &str| paths::display_path(dir.join(relative));
+        // notes.txt has no last-used date, so its recent atime still counts
+        assert_eq!(
+            found,
+            [(shown("old").as_str(), ActivitySignal::Mtime), (shown("opened").as_str(), ActivitySignal::Spotlight)]
+        );
+
+        let report = find_with(std::slice::from_ref(&dir), &ScanFilter::default(), false, cutoff_ms, 0, &|_| None);
+        assert_eq!((report.spotlight, report.directories.len()), (false, 1));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unreadable_subdirectories_keep_their_parents_from_being_reported() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = temp_dir("unreadable");
+        write_aged(&dir.join("old/readable/a.txt"), 10, 900);
+        write_aged(&dir.join("old/locked/b.txt"), 10, 900);
+        fs::set_permissions(dir.join("old/locked"), fs::Permissions::from_mode(0o000)).unwrap();
+        // Root ignores permissions, so there's nothing to check
+        if fs::read_dir(dir.join("old/locked")).is_ok() {
+            fs::set_permissions(dir.join("old/locked"), fs::Permissions::from_mode(0o755)).unwrap();
+            fs::remove_dir_all(&dir).unwrap();
+            return;
+        }
+        let cutoff_ms = millis(Ok(SystemTime::now())) - 365 * DAY_MS;
+        let report = find(std::slice::from_ref(&dir), &ScanFilter::default(), false, cutoff_ms, 0);
+        fs::set_permissions(dir.join("old/locked"), fs::Permissions::from_mode(0o755)).unwrap();
+        let found: Vec<&str> = report.directories.iter().map(|d| d.path.as_str()).collect();
+        assert_eq!(found, [paths::display_path(dir.join("old/readable"))]);
+        assert_eq!(report.unreadable, 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
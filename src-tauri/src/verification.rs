@@ -0,0 +1,212 @@
+// Checks a finished batch against its journal: every destination exists with the
+// recorded size (and hash, when one was captured), no source reappeared, and the
+// destination folders hold no temp files left by interrupted copies.
+use crate::i18n;
+use crate::journal::JournalEntry;
+use crate::paths;
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::Path;
+
+// Names interrupted copies and downloads leave behind, matched case-insensitively
+const TEMP_SUFFIXES: &[&str] = &[".tmp", ".temp", ".part", ".partial", ".crdownload"];
+const TEMP_PREFIXES: &[&str] = &[".file-organizer-", "~$"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiscrepancyKind {
+    // Neither the destination nor the source exists
+    FileLost,
+    // The destination is missing but the source is still there
+    MoveNotApplied,
+    // Both exist: the source came back or was copied rather than moved
+    SourceStillPresent,
+    SizeMismatch,
+    HashMismatch,
+    // The destination exists but couldn't be read to compare its hash
+    Unreadable,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Discrepancy {
+    pub kind: DiscrepancyKind,
+    pub from: String,
+    pub to: String,
+    pub detail: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct VerificationReport {
+    pub batch_id: String,
+    pub entries: usize,
+    // Entries whose destination checked out completely
+    pub verified: usize,
+    pub hashes_checked: usize,
+    pub discrepancies: Vec<Discrepancy>,
+    pub stray_temp_files: Vec<String>,
+}
+
+fn is_temp_name(name: &str) -> bool {
+    let lower = name.to_lowercase();
+    TEMP_SUFFIXES.iter().any(|suffix| lower.ends_with(suffix))
+        || TEMP_PREFIXES.iter().any(|prefix| lower.starts_with(prefix))
+}
+
+fn same_file(a: &Path, b: &Path) -> bool {
+    matches!((fs::canonicalize(a), fs::canonicalize(b)), (Ok(a), Ok(b)) if a == b)
+}
+
+pub fn verify(batch_id: &str, entries: &[JournalEntry]) -> VerificationReport {
+    let mut report = VerificationReport {
+        batch_id: batch_id.to_string(),
+        entries: entries.len(),
+        ..Default::default()
+    };
+    let mut destination_dirs = BTreeSet::new();
+    // Last index each path was moved from, so "moved again later" is one lookup
+    let last_moved_from: HashMap<&str, usize> =
+        entries.iter().enumerate().map(|(index, entry)| (entry.from.as_str(), index)).collect();
+
+    for (index, entry) in entries.iter().enumerate() {
+        let from = paths::resolve_path(&entry.from);
+        let to = paths::resolve_path(&entry.to);
+        // A file moved again later in the batch is checked at its final place
+        if last_moved_from.get(entry.to.as_str()).is_some_and(|&later| later > index) {
+            report.verified += 1;
+            continue;
+        }
+        if let Some(parent) = to.parent() {
+            destination_dirs.insert(parent.to_path_buf());
+        }
+
+        let mut discrepancy = |kind, detail: String| {
+            report.discrepancies.push(Discrepancy { kind, from: entry.from.clone(), to: entry.to.clone(), detail });
+        };
+        let source_present = fs::symlink_metadata(&from).is_ok();
+        // Archived files only need their archive; it was read back in full when it was
+        // made, and keeping the originals is allowed
+        if entry.archive_member.is_some() {
+            if fs::symlink_metadata(&to).is_ok() {
+                report.verified += 1;
+            } else if source_present {
+                discrepancy(DiscrepancyKind::MoveNotApplied, i18n::t("verification.move_not_applied"));
+            } else {
+                discrepancy(DiscrepancyKind::FileLost, i18n::t("verification.file_lost"));
+            }
+            continue;
+        }
+        let Ok(metadata) = fs::symlink_metadata(&to) else {
+            if source_present {
+                discrepancy(DiscrepancyKind::MoveNotApplied, i18n::t("verification.move_not_applied"));
+            } else {
+                discrepancy(DiscrepancyKind::FileLost, i18n::t("verification.file_lost"));
+            }
+            continue;
+        };
+
+        let mut ok = true;
+        if source_present && !same_file(&from, &to) {
+            ok = false;
+            discrepancy(DiscrepancyKind::SourceStillPresent, i18n::t("verification.source_still_present"));
+        }
+        if metadata.len() != entry.size {
+            ok = false;
+            let (actual, expected) = (i18n::format_size(metadata.len()), i18n::format_size(entry.size));
+            discrepancy(
+                DiscrepancyKind::SizeMismatch,
+                i18n::tf("verification.size_mismatch", &[("actual", &actual), ("expected", &expected)]),
+            );
+        } else if let Some(expected) = &entry.sha256 {
+            report.hashes_checked += 1;
+            match crate::sha256_file(&to) {
+                Ok(actual) if &actual == expected => {}
+                Ok(actual) => {
+                    ok = false;
+                    discrepancy(
+                        DiscrepancyKind::HashMismatch,
+                        i18n::tf("verification.hash_mismatch", &[("actual", &actual), ("expected", expected)]),
+                    );
+                }
+                Err(e) => {
+                    ok = false;
+                    discrepancy(DiscrepancyKind::Unreadable, e);
+                }
+            }
+        }
+        if ok {
+            report.verified += 1;
+        }
+    }
+
+    for dir in destination_dirs {
+        let Ok(listing) = fs::read_dir(&dir) else { continue };
+        report.stray_temp_files.extend(
+            listing
+                .flatten()
+                .filter(|entry| is_temp_name(&entry.file_name().to_string_lossy()))
+                .map(|entry| paths::display_path(entry.path())),
+        );
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_dir;
+
+    fn entry(dir: &Path, from: &str, to: &str, size: u64) -> JournalEntry {
+        JournalEntry {
+            from: paths::display_path(dir.join(from)),
+            to: paths::display_path(dir.join(to)),
+            size,
+            sha256: None,
+            moved_at: 0,
+            duplicate_of: None,
+            archive_member: None,
+        }
+    }
+
+    #[test]
+    fn discrepancies_say_what_went_wrong() {
+        let dir = temp_dir("report");
+        fs::create_dir_all(dir.join("out")).unwrap();
+        fs::write(dir.join("out/ok.txt"), b"abc").unwrap();
+        fs::write(dir.join("out/short.txt"), b"a").unwrap();
+        fs::write(dir.join("out/hashed.txt"), b"xyz").unwrap();
+        fs::write(dir.join("never.txt"), b"abc").unwrap();
+        fs::write(dir.join("out/copy.part"), b"").unwrap();
+
+        let mut hashed = entry(&dir, "hashed.txt", "out/hashed.txt", 3);
+        hashed.sha256 = Some("0".repeat(64));
+        let entries = [
+            entry(&dir, "ok.txt", "out/ok.txt", 3),
+            entry(&dir, "short.txt", "out/short.txt", 3),
+            hashed,
+            entry(&dir, "never.txt", "out/never.txt", 3),
+            entry(&dir, "gone.txt", "out/gone.txt", 3),
+        ];
+        let report = verify("batch", &entries);
+        let kinds: Vec<DiscrepancyKind> = report.discrepancies.iter().map(|d| d.kind).collect();
+        assert_eq!(kinds, [
+            DiscrepancyKind::SizeMismatch,
+            DiscrepancyKind::HashMismatch,
+            DiscrepancyKind::MoveNotApplied,
+            DiscrepancyKind::FileLost,
+        ]);
+        assert_eq!((report.verified, report.hashes_checked), (1, 1));
+        assert_eq!(report.stray_temp_files, [paths::display_path(dir.join("out/copy.part"))]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn files_moved_twice_are_checked_at_their_last_place() {
+        let dir = temp_dir("chain");
+        fs::write(dir.join("c.txt"), b"abc").unwrap();
+        let report = verify("batch", &[entry(&dir, "a.txt", "b.txt", 3), entry(&dir, "b.txt", "c.txt", 3)]);
+        assert!(report.discrepancies.is_empty());
+        assert_eq!(report.verified, 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
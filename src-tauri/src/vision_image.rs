@@ -0,0 +1,191 @@
+// Images prepared for vision models: decoded with their EXIF orientation (the first
+// frame, for animations), scaled to fit max_dimension and re-encoded. Transparent
+// images stay PNG while small and are otherwise flattened onto white for JPEG, and an
+// RGB ICC profile is carried over so wide-gamut photos keep their colors.
+use crate::error::{AppError, AppErrorKind};
+use image::codecs::gif::GifDecoder;
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::PngEncoder;
+use image::{AnimationDecoder, DynamicImage, ImageDecoder, ImageEncoder, ImageFormat, ImageReader, Rgb, RgbImage};
+use std::io::BufReader;
+use std::path::Path;
+
+pub const DEFAULT_MAX_DIMENSION: u32 = 1024;
+// What image's JPEG encoder used when this went through write_to
+pub const DEFAULT_QUALITY: u8 = 75;
+const MIN_MAX_DIMENSION: u32 = 16;
+const MAX_MAX_DIMENSION: u32 = 8192;
+// Transparent images whose PNG stays under this are sent as PNG instead of flattened
+const MAX_PNG_BYTES: usize = 1024 * 1024;
+
+#[derive(Debug, Clone, Copy)]
+pub struct EncodeOptions {
+    // JPEG quality, 1-100
+    pub quality: u8,
+    // Longest side in pixels; larger images are scaled down to fit
+    pub max_dimension: u32,
+}
+
+impl Default for EncodeOptions {
+    fn default() -> Self {
+        Self { quality: DEFAULT_QUALITY, max_dimension: DEFAULT_MAX_DIMENSION }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct EncodedImage {
+    pub bytes: Vec<u8>,
+    pub mime_type: &'static str,
+}
+
+struct Decoded {
+    image: DynamicImage,
+    icc_profile: Option<Vec<u8>>,
+}
+
+fn invalid_data(path: &Path, message: String) -> AppError {
+    AppError::new(AppErrorKind::InvalidData, message).with_path(path)
+}
+
+// Only RGB profiles describe the RGB pixels written out; a CMYK JPEG's profile doesn't
+// (the decoder has already converted it to RGB)
+fn is_rgb_profile(profile: &[u8]) -> bool {
+    profile.get(16..20) == Some(b"RGB ")
+}
+
+fn decode(path: &Path) -> Result<Decoded, AppError> {
+    let open_error = |e: std::io::Error| AppError::from_io(&e, format!("Failed to open image: {}", e)).with_path(path);
+    let decode_error = |e: image::ImageError| invalid_data(path, format!("Failed to open image: {}", e));
+    let reader = ImageReader::open(path).map_err(open_error)?.with_guessed_format().map_err(open_error)?;
+
+    if reader.format() == Some(ImageFormat::Gif) {
+        let file = BufReader::new(std::fs::File::open(path).map_err(open_error)?);
+        let frame = GifDecoder::new(file)
+            .map_err(decode_error)?
+            .into_frames()
+            .next()
+            .ok_or_else(|| invalid_data(path, "GIF has no frames".to_string()))?
+            .map_err(decode_error)?;
+        return Ok(Decoded { image: DynamicImage::ImageRgba8(frame.into_buffer()), icc_profile: None });
+    }
+
+    let mut decoder = reader.into_decoder().map_err(decode_error)?;
+    let icc_profile = decoder.icc_profile().ok().flatten().filter(|profile| is_rgb_profile(profile));
+    let orientation = decoder.orientation().map_err(decode_error)?;
+    let mut image = DynamicImage::from_decoder(decoder).map_err(decode_error)?;
+    image.apply_orientation(orientation);
+    Ok(Decoded { image, icc_profile })
+}
+
+fn has_transparency(image: &DynamicImage) -> bool {
+    image.color().has_alpha() && image.to_rgba8().pixels().any(|pixel| pixel[3] < u8::MAX)
+}
+
+fn flatten_onto_white(image: &DynamicImage) -> RgbImage {
+    let rgba = image.to_rgba8();
+    RgbImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+        let [r, g, b, a] = rgba.get_pixel(x, y).0;
+        let blend = |channel: u8| ((channel as u32 * a as u32 + 255 * (255 - a as u32) + 127) / 255) as u8;
+        Rgb([blend(r), blend(g), blend(b)])
+    })
+}
+
+pub fn encode(path: &Path, options: EncodeOptions) -> Result<EncodedImage, AppError> {
+    let Decoded { image, icc_profile } = decode(path)?;
+    let max_dimension = options.max_dimension.clamp(MIN_MAX_DIMENSION, MAX_MAX_DIMENSION);
+    let image = if image.width() > max_dimension || image.height() > max_dimension {
+        image.resize(max_dimension, max_dimension, image::imageops::FilterType::Lanczos3)
+    } else {
+        image
+    };
+    let encode_error = |e: image::ImageError| AppError::io(format!("Failed to encode image: {}", e)).with_path(path);
+
+    let transparent = has_transparency(&image);
+    if transparent {
+        let rgba = image.to_rgba8();
+        let mut bytes = Vec::new();
+        let mut encoder = PngEncoder::new(&mut bytes);
+        if let Some(profile) = icc_profile.clone() {
+            let _ = encoder.set_icc_profile(profile);
+        }
+        encoder
+            .write_image(&rgba, rgba.width(), rgba.height(), image::ExtendedColorType::Rgba8)
+            .map_err(encode_error)?;
+        if bytes.len() <= MAX_PNG_BYTES {
+            return Ok(EncodedImage { bytes, mime_type: "image/png" });
+        }
+    }
+
+    // 16-bit and grayscale images are converted to 8-bit RGB here
+    let rgb = if transparent { flatten_onto_white(&image) } else { image.to_rgb8() };
+    let mut bytes = Vec::new();
+    let mut encoder = JpegEncoder::new_with_quality(&mut bytes, options.quality.clamp(1, 100));
+    if let Some(profile) = icc_profile {
+        let _ = encoder.set_icc_profile(profile);
+    }
+    encoder
+        .write_image(&rgb, rgb.width(), rgb.height(), image::ExtendedColorType::Rgb8)
+        .map_err(encode_error)?;
+    Ok(EncodedImage { bytes, mime_type: "image/jpeg" })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_dir;
+    use image::{Rgba, RgbaImage};
+    use std::fs;
+
+    fn decoded(encoded: &EncodedImage) -> DynamicImage {
+        image::load_from_memory(&encoded.bytes).unwrap()
+    }
+
+    #[test]
+    fn transparency_is_kept_as_png_or_flattened_onto_white() {
+        let dir = temp_dir("alpha");
+        let source = dir.join("screenshot.png");
+        RgbaImage::from_pixel(64, 32, Rgba([0, 0, 0, 0])).save(&source).unwrap();
+
+        let encoded = encode(&source, EncodeOptions::default()).unwrap();
+        assert_eq!(encoded.mime_type, "image/png");
+        assert_eq!(decoded(&encoded).to_rgba8().get_pixel(0, 0)[3], 0);
+
+        let half = DynamicImage::ImageRgba8(RgbaImage::from_pixel(1, 1, Rgba([0, 0, 0, 128])));
+        assert_eq!(flatten_onto_white(&half).get_pixel(0, 0).0, [127, 127, 127]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn large_and_16_bit_images_are_scaled_into_jpeg() {
+        let dir = temp_dir("sixteen");
+        let source = dir.join("scan.png");
+        image::ImageBuffer::<Rgb<u16>, _>::from_pixel(400, 200, Rgb([65_535, 0, 0])).save(&source).unwrap();
+
+        let encoded = encode(&source, EncodeOptions { quality: 90, max_dimension: 100 }).unwrap();
+        assert_eq!(encoded.mime_type, "image/jpeg");
+        let image = decoded(&encoded);
+        assert_eq!((image.width(), image.height()), (100, 50));
+        assert!(image.to_rgb8().get_pixel(50, 25)[0] > 240);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn animated_gifs_use_the_first_frame() {
+        use image::codecs::gif::{GifEncoder, Repeat};
+        use image::Frame;
+
+        let dir = temp_dir("gif");
+        let source = dir.join("animation.gif");
+        let mut encoder = GifEncoder::new(fs::File::create(&source).unwrap());
+        encoder.set_repeat(Repeat::Infinite).unwrap();
+        for color in [[0, 0, 255, 255], [255, 0, 0, 255]] {
+            encoder.encode_frame(Frame::new(RgbaImage::from_pixel(8, 8, Rgba(color)))).unwrap();
+        }
+        drop(encoder);
+
+        let image = decoded(&encode(&source, EncodeOptions::default()).unwrap()).to_rgb8();
+        let [r, _, b] = image.get_pixel(4, 4).0;
+        assert!(b > 200 && r < 50);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,380 @@
+// Mounted volumes and drives, so roots on an external drive or network share can be
+// picked directly, and a watcher that notices when one comes or goes. Moves touching
+// a volume that disappeared fail fast with VolumeUnavailable instead of a NotFound for
+// every remaining file.
+use crate::error::{AppError, AppErrorKind};
+use crate::i18n;
+use crate::paths;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+pub const WATCH_INTERVAL: Duration = Duration::from_secs(2);
+
+// Mount points of volumes the watcher saw disappear and that haven't come back
+static DISAPPEARED: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Volume {
+    // A drive root on Windows ("E:\"), a mount point elsewhere
+    pub mount_point: String,
+    pub label: Option<String>,
+    pub file_system: String,
+    pub total_bytes: u64,
+    // Available to this user
+    pub free_bytes: u64,
+    pub removable: bool,
+    pub network: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VolumeChange {
+    Mounted,
+    Unmounted,
+}
+
+impl VolumeChange {
+    pub fn event_name(self) -> &'static str {
+        match self {
+            Self::Mounted => "volume-mounted",
+            Self::Unmounted => "volume-unmounted",
+        }
+    }
+}
+
+// Volumes in `after` that weren't in `before` and the other way round, by mount point
+pub fn changes(before: &[Volume], after: &[Volume]) -> Vec<(VolumeChange, Volume)> {
+    let mounted_in = |list: &[Volume], volume: &Volume| list.iter().any(|v| v.mount_point == volume.mount_point);
+    let unmounted = before.iter().filter(|v| !mounted_in(after, v)).map(|v| (VolumeChange::Unmounted, v.clone()));
+    let mounted = after.iter().filter(|v| !mounted_in(before, v)).map(|v| (VolumeChange::Mounted, v.clone()));
+    unmounted.chain(mounted).collect()
+}
+
+fn record(change: VolumeChange, volume: &Volume) {
+    let mount_point = PathBuf::from(&volume.mount_point);
+    let mut disappeared = DISAPPEARED.lock().unwrap_or_else(|e| e.into_inner());
+    disappeared.retain(|gone| gone != &mount_point);
+    if change == VolumeChange::Unmounted {
+        disappeared.push(mount_point);
+    }
+}
+
+// Polls the mounted volumes on a background thread and reports every change
+pub fn watch(on_change: impl Fn(VolumeChange, &Volume) + Send + 'static) {
+    let spawned = std::thread::Builder::new().name("volume-watcher".into()).spawn(move || {
+        let mut known = list();
+        loop {
+            std::thread::sleep(WATCH_INTERVAL);
+            let current = list();
+            for (change, volume) in changes(&known, &current) {
+                record(change, &volume);
+                on_change(change, &volume);
+            }
+            known = current;
+        }
+    });
+    if let Err(e) = spawned {
+        tracing::warn!("Volume watcher not started: {}", e);
+    }
+}
+
+// Fails when `path` is on a volume the watcher saw disappear
+pub fn ensure_mounted(path: &Path) -> Result<(), AppError> {
+    let disappeared = DISAPPEARED.lock().unwrap_or_else(|e| e.into_inner());
+    match disappeared.iter().filter(|gone| path.starts_with(gone)).max_by_key(|gone| gone.as_os_str().len()) {
+        Some(gone) => {
+            let message = i18n::tf("error.volume_disappeared", &[("volume", &paths::display_path(gone))]);
+            Err(AppError::new(AppErrorKind::VolumeUnavailable, message).with_path(paths::display_path(path)))
+        }
+        None => Ok(()),
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub fn list() -> Vec<Volume> {
+    linux::list()
+}
+
+#[cfg(target_os = "macos")]
+pub fn list() -> Vec<Volume> {
+    macos::list()
+}
+
+#[cfg(windows)]
+pub fn list() -> Vec<Volume> {
+    windows::list()
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+pub fn list() -> Vec<Volume> {
+    Vec::new()
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use super::Volume;
+    use std::ffi::CString;
+    use std::fs;
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::{Path, PathBuf};
+
+    const NETWORK_FILE_SYSTEMS: &[&str] =
+        &["nfs", "nfs4", "cifs", "smb3", "smbfs", "ncpfs", "afs", "9p", "davfs", "fuse.sshfs", "fuse.rclone"];
+    // Snap packages and similar images are block devices but not volumes anyone organizes
+    const IMAGE_FILE_SYSTEMS: &[&str] = &["squashfs", "iso9660"];
+
+    // /proc/mounts escapes spaces, tabs, newlines and backslashes as octal (\040)
+    pub(super) fn unescape_octal(field: &str) -> String {
+        let bytes = field.as_bytes();
+        let mut out = Vec::with_capacity(bytes.len());
+        let mut i = 0;
+        while i < bytes.len() {
+            let digits = bytes.get(i + 1..i + 4).filter(|d| d.iter().all(|b| (b'0'..=b'7').contains(b)));
+            match (bytes[i], digits) {
+                (b'\\', Some(d)) => {
+                    out.push(d.iter().fold(0u8, |n, b| n.wrapping_mul(8) + (b - b'0')));
+                    i += 4;
+                }
+                (b, _) => {
+                    out.push(b);
+                    i += 1;
+                }
+            }
+        }
+        String::from_utf8_lossy(&out).into_owned()
+    }
+
+    // udev names labels with \x20 style escapes
+    fn unescape_hex(name: &str) -> String {
+        let mut out = Vec::with_capacity(name.len());
+        let bytes = name.as_bytes();
+        let mut i = 0;
+        while i < bytes.len() {
+            let hex = (bytes[i] == b'\\' && bytes.get(i + 1) == Some(&b'x'))
+                .then(|| name.get(i + 2..i + 4).and_then(|h| u8::from_str_radix(h, 16).ok()))
+                .flatten();
+            match hex {
+                Some(byte) => {
+                    out.push(byte);
+                    i += 4;
+                }
+                None => {
+                    out.push(bytes[i]);
+                    i += 1;
+                }
+            }
+        }
+        String::from_utf8_lossy(&out).into_owned()
+    }
+
+    fn label(device: &Path) -> Option<String> {
+        fs::read_dir("/dev/disk/by-label")
+            .ok()?
+            .flatten()
+            .find(|link| fs::canonicalize(link.path()).ok().as_deref() == Some(device))
+            .map(|link| unescape_hex(&link.file_name().to_string_lossy()))
+    }
+
+    // USB sticks set the removable flag; USB hard disks often don't, but sit on a USB bus
+    fn removable(device: &Path) -> bool {
+        let Some(name) = device.file_name() else { return false };
+        let Ok(sys) = fs::canonicalize(Path::new("/sys/class/block").join(name)) else { return false };
+        let flag = |dir: &Path| fs::read_to_string(dir.join("removable")).is_ok_and(|v| v.trim() == "1");
+        // Partitions keep the flag on their parent disk
+        flag(&sys) || sys.parent().is_some_and(flag) || sys.to_string_lossy().contains("/usb")
+    }
+
+    fn space(mount_point: &str) -> (u64, u64) {
+        let Ok(path) = CString::new(Path::new(mount_point).as_os_str().as_bytes()) else { return (0, 0) };
+        let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+        if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+            return (0, 0);
+        }
+        let unit = stat.f_frsize as u64;
+        (stat.f_blocks as u64 * unit, stat.f_bavail as u64 * unit)
+    }
+
+    pub fn list() -> Vec<Volume> {
+        let Ok(mounts) = fs::read_to_string("/proc/self/mounts") else { return Vec::new() };
+        let mut volumes: Vec<Volume> = Vec::new();
+        for line in mounts.lines() {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let [device, mount_point, file_system, ..] = fields[..] else { continue };
+            let network = NETWORK_FILE_SYSTEMS.contains(&file_system);
+            if !(network || device.starts_with("/dev/")) || IMAGE_FILE_SYSTEMS.contains(&file_system) {
+                continue;
+            }
+            let mount_point = unescape_octal(mount_point);
+            if volumes.iter().any(|v| v.mount_point == mount_point) {
+                continue;
+            }
+            let device = fs::canonicalize(unescape_octal(device)).unwrap_or_else(|_| PathBuf::from(device));
+            let (total_bytes, free_bytes) = space(&mount_point);
+            volumes.push(Volume {
+                label: if network { None } else { label(&device) },
+                removable: !network && removable(&device),
+                mount_point,
+                file_system: file_system.to_string(),
+                total_bytes,
+                free_bytes,
+                network,
+            });
+        }
+        volumes
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod macos {
+    use super::Volume;
+    use std::ffi::CStr;
+    use std::path::Path;
+
+    // From sys/mount.h
+    const MNT_NOWAIT: libc::c_int = 2;
+    const MNT_LOCAL: u32 = 0x0000_1000;
+    const MNT_REMOVABLE: u32 = 0x0000_0200;
+    const MNT_DONTBROWSE: u32 = 0x0010_0000;
+
+    pub fn list() -> Vec<Volume> {
+        let mut mounts: *mut libc::statfs = std::ptr::null_mut();
+        let count = unsafe { libc::getmntinfo(&mut mounts, MNT_NOWAIT) };
+        if count <= 0 || mounts.is_null() {
+            return Vec::new();
+        }
+        // getmntinfo owns the buffer; it stays valid until the next call on this thread
+        let mounts = unsafe { std::slice::from_raw_parts(mounts, count as usize) };
+        mounts
+            .iter()
+            // Hidden system volumes (VM, Preboot, Recovery) are marked "don't browse"
+            .filter(|mount| mount.f_flags & MNT_DONTBROWSE == 0)
+            .map(|mount| {
+                let text = |chars: &[libc::c_char]| {
+                    unsafe { CStr::from_ptr(chars.as_ptr()) }.to_string_lossy().into_owned()
+                };
+                let mount_point = text(&mount.f_mntonname);
+                let block = mount.f_bsize as u64;
+                let network = mount.f_flags & MNT_LOCAL == 0;
+                Volume {
+                    label: if mount_point == "/" {
+                        None
+                    } else {
+                        Path::new(&mount_point).file_name().map(|name| name.to_string_lossy().into_owned())
+                    },
+                    file_system: text(&mount.f_fstypename),
+                    total_bytes: mount.f_blocks * block,
+                    free_bytes: mount.f_bavail * block,
+                    removable: !network && mount.f_flags & MNT_REMOVABLE != 0,
+                    network,
+                    mount_point,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(windows)]
+mod windows {
+    use super::Volume;
+    use windows_sys::Win32::Storage::FileSystem::{
+        GetDiskFreeSpaceExW, GetDriveTypeW, GetLogicalDrives, GetVolumeInformationW,
+    };
+
+    // From winbase.h
+    const DRIVE_REMOVABLE: u32 = 2;
+    const DRIVE_REMOTE: u32 = 4;
+    const DRIVE_CDROM: u32 = 5;
+
+    fn wide(text: &str) -> Vec<u16> {
+        text.encode_utf16().chain(std::iter::once(0)).collect()
+    }
+
+    fn from_wide(buffer: &[u16]) -> String {
+        let len = buffer.iter().position(|&c| c == 0).unwrap_or(buffer.len());
+        String::from_utf16_lossy(&buffer[..len])
+    }
+
+    pub fn list() -> Vec<Volume> {
+        let drives = unsafe { GetLogicalDrives() };
+        (0..26u8)
+            .filter(|bit| drives & (1 << bit) != 0)
+            .filter_map(|bit| {
+                let root = format!("{}:\\", (b'A' + bit) as char);
+                let root_wide = wide(&root);
+                let drive_type = unsafe { GetDriveTypeW(root_wide.as_ptr()) };
+                let (mut label, mut file_system) = ([0u16; 261], [0u16; 261]);
+                // Fails for empty card readers and optical drives, which aren't worth listing
+                let ok = unsafe {
+                    GetVolumeInformationW(
+                        root_wide.as_ptr(),
+                        label.as_mut_ptr(),
+                        label.len() as u32,
+                        std::ptr::null_mut(),
+                        std::ptr::null_mut(),
+                        std::ptr::null_mut(),
+                        file_system.as_mut_ptr(),
+                        file_system.len() as u32,
+                    )
+                };
+                if ok == 0 {
+                    return None;
+                }
+                let (mut free, mut total) = (0u64, 0u64);
+                unsafe { GetDiskFreeSpaceExW(root_wide.as_ptr(), &mut free, &mut total, std::ptr::null_mut()) };
+                let label = from_wide(&label);
+                Some(Volume {
+                    mount_point: root,
+                    label: (!label.is_empty()).then_some(label),
+                    file_system: from_wide(&file_system),
+                    total_bytes: total,
+                    free_bytes: free,
+                    removable: matches!(drive_type, DRIVE_REMOVABLE | DRIVE_CDROM),
+                    network: drive_type == DRIVE_REMOTE,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn volume(mount_point: &str) -> Volume {
+        Volume {
+            mount_point: mount_point.to_string(),
+            label: None,
+            file_system: "exfat".into(),
+            total_bytes: 0,
+            free_bytes: 0,
+            removable: true,
+            network: false,
+        }
+    }
+
+    #[test]
+    fn moves_on_a_vanished_volume_fail_fast() {
+        let mount_point = std::env::temp_dir().join(format!("file-organizer-volume-{}", std::process::id()));
+        let usb = volume(&mount_point.to_string_lossy());
+        let found = changes(&[], std::slice::from_ref(&usb));
+        assert_eq!(found, [(VolumeChange::Mounted, usb.clone())]);
+        let lost = changes(std::slice::from_ref(&usb), &[]);
+        assert_eq!(lost[0].0, VolumeChange::Unmounted);
+
+        record(VolumeChange::Unmounted, &usb);
+        let err = ensure_mounted(&mount_point.join("Photos/a.jpg")).unwrap_err();
+        assert_eq!(err.kind, AppErrorKind::VolumeUnavailable);
+        assert!(ensure_mounted(&std::env::temp_dir().join("elsewhere")).is_ok());
+        record(VolumeChange::Mounted, &usb);
+        assert!(ensure_mounted(&mount_point.join("Photos/a.jpg")).is_ok());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn mount_table_escapes_are_decoded() {
+        assert_eq!(linux::unescape_octal(r"/media/me/My\040Drive"), "/media/me/My Drive");
+        assert_eq!(linux::unescape_octal(r"C:\\x"), r"C:\\x");
+        assert!(list().iter().all(|v| !v.mount_point.is_empty()));
+    }
+}
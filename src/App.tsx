@@ -1,10 +1,10 @@
 import React, { useEffect, useMemo, useState, useRef, useCallback } from 'react';
-import { classifyViaLLM, optimizeCategoriesViaLLM, LLMConfig, DEFAULT_CONFIGS, LLMProviderType, openFile, FileContent, checkLLMServerUpdate, checkAppUpdate, AppUpdateInfo, LLMServerUpdateInfo } from './api';
+import { classifyViaLLM, optimizeCategoriesViaLLM, LLMConfig, DEFAULT_CONFIGS, LLMProviderType, openFile, FileContent, checkLLMServerUpdate, checkAppUpdate, AppUpdateInfo, LLMServerUpdateInfo, checkDirectoryAccess, notifyIfUnfocused, classificationModelId, getCachedClassification, cacheClassification, setOrganizationRoots, getSettings, patchSettings, newOperationId, cancelOperation, finishOperation, verifyOrganization, setFileTags, getDirectoryTags, setLocale, sampleDirectoryForClassification } from './api';
 import { invoke } from '@tauri-apps/api/tauri';
 import { listen } from '@tauri-apps/api/event';
 import { open as openUrl } from '@tauri-apps/api/shell';
-import { ScanState, ManagedLLMConfig, SavedProcessedState } from './types';
-import { LLMConfigPanel, HelpDialog, AboutDialog, ManagedLLMDialog, UpdateCheckDialog } from './components';
+import { ScanState, ManagedLLMConfig, SavedProcessedState, LLMBackendFallbackEvent, AppError, MoveOutcome, ExtractionQueuedEvent, OperationCancelledEvent, Volume, DirectoryListing, DirectoryScanProgress } from './types';
+import { LLMConfigPanel, HelpDialog, AboutDialog, ManagedLLMDialog, UpdateCheckDialog, FileThumbnail } from './components';
 import { debugLogger } from './debug-logger';
 
 function sanitizeFilename(name: string) {
@@ -30,6 +30,17 @@ function splitPath(p: string) {
   return { dir, name, ext };
 }
 
+// Selected directories minus repeats and any inside another selection, which a
+// recursive scan already covers
+function distinctRoots(dirs: string[]): string[] {
+  const norm = (p: string) => p.replace(/\\/g, '/').replace(/\/+$/, '');
+  return dirs.filter((dir, i) => !dirs.some((other, j) => {
+    if (j === i) return false;
+    const [a, b] = [norm(dir), norm(other)];
+    return a === b ? j < i : a.startsWith(b + '/');
+  }));
+}
+
 // Get category based on file extension for non-text/non-image files
 function getExtensionBasedCategory(ext: string): string {
   const extension = ext.toLowerCase().replace('.', '');
@@ -110,7 +121,9 @@ function configsEqual(a?: LLMConfig, b?: LLMConfig) {
     a.maxTokens === b.maxTokens &&
     a.maxTextLength === b.maxTextLength &&
     a.systemMessage === b.systemMessage &&
-    a.supportsVision === b.supportsVision
+    a.supportsVision === b.supportsVision &&
+    a.imageMaxDimension === b.imageMaxDimension &&
+    a.imageQuality === b.imageQuality
   );
 }
 
@@ -165,6 +178,10 @@ export default function App() {
   
   // Track if this is the initial mount to prevent clearing saved state on mount
   const isInitialMount = useRef(true);
+
+  // With webview storage cleared, configs are restored from the backend's settings file,
+  // and nothing is written back until that's done
+  const restoringSettings = useRef(localStorage.getItem('llmConfig') === null);
   
   // Managed LLM state
   const [managedLLMConfig, setManagedLLMConfig] = useState<ManagedLLMConfig>(() => {
@@ -183,7 +200,11 @@ export default function App() {
           env_vars: config.env_vars || {},
           mmproj_repo_id: config.mmproj_repo_id,
           mmproj_filename: config.mmproj_filename,
-          chat_format: config.chat_format
+          chat_format: config.chat_format,
+          restart_policy: config.restart_policy,
+          max_restart_attempts: config.max_restart_attempts,
+          auto_port: config.auto_port,
+          startup_timeout: config.startup_timeout
         };
         return migratedConfig;
       }
@@ -201,6 +222,9 @@ export default function App() {
   });
   const [directories, setDirectories] = useState<string[]>([]);
   const [includeSubdirectories, setIncludeSubdirectories] = useState(false);
+  const [includeHidden, setIncludeHidden] = useState(false);
+  // When set, only a representative sample of this many files per directory is classified
+  const [sampleSize, setSampleSize] = useState(0);
   const [useExistingCategories, setUseExistingCategories] = useState(false);
   const [existingCategories, setExistingCategories] = useState<string[]>([]);
   const existingCategoriesRef = useRef<string[]>([]);
@@ -223,6 +247,13 @@ export default function App() {
   const [isOptimizing, setIsOptimizing] = useState(false);
   const [showOptimizationResult, setShowOptimizationResult] = useState(false);
   const optimizationCancelRef = useRef(false);
+  // Operation ids of the running scan and move batch, for cancel_operation
+  const scanOperationRef = useRef<string | null>(null);
+  const moveOperationRef = useRef<string | null>(null);
+  // Record each file's hash before moving it so verification can compare contents
+  const [captureHashes, setCaptureHashes] = useState(false);
+  // Save each moved file's category with it (extended attribute, stream or sidecar)
+  const [tagMovedFiles, setTagMovedFiles] = useState(true);
   
   // Search and replace state
   const [searchReplaceExpanded, setSearchReplaceExpanded] = useState(false);
@@ -351,6 +382,11 @@ export default function App() {
     } catch (error) {
       debugLogger.error('APP_CONFIG', 'Failed to save LLM config to localStorage', { error });
     }
+    if (!restoringSettings.current) {
+      patchSettings({ frontend: { llmConfig } }).catch(error => {
+        debugLogger.error('APP_CONFIG', 'Failed to save LLM config to settings', { error });
+      });
+    }
 
     setProviderConfigs(prev => {
       const current = prev[llmConfig.provider];
@@ -364,10 +400,48 @@ export default function App() {
       } catch (error) {
         debugLogger.error('APP_CONFIG', 'Failed to save provider configs to localStorage', { error });
       }
+      if (!restoringSettings.current) {
+        patchSettings({ frontend: { llmProviderConfigs: updated } }).catch(error => {
+          debugLogger.error('APP_CONFIG', 'Failed to save provider configs to settings', { error });
+        });
+      }
       return updated;
     });
   }, [llmConfig]);
 
+  // Restore configs from the backend when webview storage has been cleared
+  useEffect(() => {
+    if (!restoringSettings.current) {
+      return;
+    }
+    getSettings()
+      .then(settings => {
+        const { llmConfig: savedConfig, llmProviderConfigs: savedProviderConfigs } = settings.frontend;
+        if (savedProviderConfigs) {
+          setProviderConfigs(savedProviderConfigs);
+        }
+        if (savedConfig) {
+          setLlmConfig(savedConfig);
+        }
+        if (settings.managed_llm) {
+          setManagedLLMConfig(settings.managed_llm);
+        }
+        // Backend messages follow the system language until a locale is chosen
+        if (!settings.locale) {
+          setLocale(navigator.language).catch(error => {
+            debugLogger.debug('APP_INIT', 'System language has no backend translation', { error });
+          });
+        }
+        debugLogger.info('APP_INIT', 'Restored configs from settings', { restored: !!savedConfig });
+      })
+      .catch(error => {
+        debugLogger.error('APP_INIT', 'Failed to load settings', { error });
+      })
+      .finally(() => {
+        restoringSettings.current = false;
+      });
+  }, []);
+
   // Save managed LLM config to localStorage whenever it changes
   useEffect(() => {
     try {
@@ -426,7 +500,7 @@ export default function App() {
         saveProcessedState();
       }
     };
-  }, [rows, directories, scanState, progress, includeSubdirectories]);
+  }, [rows, directories, scanState, progress, includeSubdirectories, includeHidden]);
 
   // Migrate old config format on first load
   useEffect(() => {
@@ -447,7 +521,11 @@ export default function App() {
             env_vars: config.env_vars || config.envVars || {},
             mmproj_repo_id: config.mmproj_repo_id,
             mmproj_filename: config.mmproj_filename,
-            chat_format: config.chat_format
+            chat_format: config.chat_format,
+            restart_policy: config.restart_policy,
+            max_restart_attempts: config.max_restart_attempts,
+            auto_port: config.auto_port,
+            startup_timeout: config.startup_timeout
           };
           setManagedLLMConfig(migratedConfig);
           // Save the migrated config immediately
@@ -519,7 +597,14 @@ export default function App() {
           if (status.status === 'stopped' || status.status === 'downloaded') {
             // Auto-start the server
             debugLogger.info('MANAGED_LLM', 'Starting managed LLM server with config', { config: managedLLMConfig });
-            await startManagedLLMServer(managedLLMConfig);
+            // While configs are being restored, start with the one saved by the last start
+            const saved = restoringSettings.current ? (await getSettings()).managed_llm : undefined;
+            await startManagedLLMServer(saved ? undefined : managedLLMConfig);
+            // The server may have been moved to a free port if the configured one was busy
+            const started = await getManagedLLMServerStatus();
+            if (started.port && started.port !== managedLLMConfig.port) {
+              setLlmConfig(prev => ({ ...prev, baseUrl: `http://${managedLLMConfig.host}:${started.port}` }));
+            }
             setEvents((prev: string[]) => ['Auto-started managed LLM server', ...prev]);
           } else {
             debugLogger.debug('MANAGED_LLM', 'Server already running or starting, skipping auto-start', {});
@@ -828,11 +913,43 @@ export default function App() {
     const unlistenOpenDirectory = listen('open-directory', () => {
       pickDirectory();
     });
+    const unlistenBackendFallback = listen<LLMBackendFallbackEvent>('llm-backend-fallback', (event) => {
+      debugLogger.warn('LLM_FALLBACK', 'Request served by fallback backend', event.payload);
+      setEvents((prev: string[]) => [
+        `LLM fallback: served by ${event.payload.served_by}, results may differ in quality`,
+        ...prev,
+      ]);
+    });
+    const unlistenExtractionQueued = listen<ExtractionQueuedEvent>('extraction-queued', (event) => {
+      debugLogger.info('EXTRACTION_QUEUE', 'Waiting for an extraction slot', event.payload);
+      // Only the first hint per file goes to the activity log
+      if (event.payload.waiting_ms < 2000) {
+        setEvents((prev: string[]) => [
+          `Waiting to read ${event.payload.path} (position ${event.payload.position} in queue)`,
+          ...prev,
+        ]);
+      }
+    });
+    const unlistenOperationCancelled = listen<OperationCancelledEvent>('operation-cancelled', (event) => {
+      debugLogger.info('OPERATION', 'Cancellation took effect', event.payload);
+    });
+    const unlistenVolumeUnmounted = listen<Volume>('volume-unmounted', (event) => {
+      const name = event.payload.label || event.payload.mount_point;
+      setEvents((prev: string[]) => [`Drive disconnected: ${name}`, ...prev]);
+    });
+    const unlistenModelReloading = listen('model-reloading', () => {
+      setEvents((prev: string[]) => ['Reloading the local model after it was unloaded while idle...', ...prev]);
+    });
     return () => {
       unlistenHelp.then(f => f());
       unlistenAbout.then(f => f());
       unlistenCheckUpdates.then(f => f());
       unlistenOpenDirectory.then(f => f());
+      unlistenBackendFallback.then(f => f());
+      unlistenExtractionQueued.then(f => f());
+      unlistenOperationCancelled.then(f => f());
+      unlistenVolumeUnmounted.then(f => f());
+      unlistenModelReloading.then(f => f());
     };
   }, [handleCheckForUpdates]);
 
@@ -1001,8 +1118,9 @@ export default function App() {
       }
     } catch (error: any) {
       // User cancelled or error occurred
-      if (error && !error.includes('cancelled')) {
-        setEvents((prev: string[]) => [`Error selecting directories: ${error}`, ...prev]);
+      const appError = error as AppError;
+      if (appError && appError.kind !== 'Cancelled') {
+        setEvents((prev: string[]) => [`Error selecting directories: ${appError.message || String(error)}`, ...prev]);
       }
     }
   };
@@ -1017,6 +1135,7 @@ export default function App() {
       const state: SavedProcessedState = {
         directories,
         includeSubdirectories,
+        includeHidden,
         useExistingCategories,
         existingCategories,
         rows,
@@ -1070,6 +1189,7 @@ export default function App() {
       const dirs = state.directories || (state.directory ? [state.directory] : []);
       setDirectories(dirs);
       setIncludeSubdirectories(state.includeSubdirectories);
+      setIncludeHidden(state.includeHidden || false);
       setUseExistingCategories(state.useExistingCategories || false);
       updateExistingCategories(state.existingCategories || []);
       setRows(state.rows);
@@ -1115,7 +1235,7 @@ export default function App() {
     
     for (const dir of directories) {
       try {
-        const subdirs: string[] = await invoke('list_subdirectories', { path: dir });
+        const subdirs: string[] = await invoke('list_subdirectories', { path: dir, includeHidden });
         subdirs.forEach(subdir => allSubdirs.add(subdir));
       } catch (error: any) {
         debugLogger.error('SCAN_SUBDIRS', `Failed to scan subdirectories for ${dir}`, { error });
@@ -1130,6 +1250,10 @@ export default function App() {
   const stopScan = () => {
     if (scanState === 'scanning') {
       scanControlRef.current.shouldStop = true;
+      // Files still waiting for an extraction slot give up their place
+      if (scanOperationRef.current) {
+        cancelOperation(scanOperationRef.current).catch(() => {});
+      }
       setScanState('stopped');
       setEvents((prev: string[]) => ['Scan stopped by user', ...prev]);
     }
@@ -1161,6 +1285,10 @@ export default function App() {
   };
 
   const finalizeScan = async () => {
+    if (scanOperationRef.current) {
+      finishOperation(scanOperationRef.current).catch(() => {});
+      scanOperationRef.current = null;
+    }
     setProgress({ current: scanControlRef.current.currentFileIndex, total: scanControlRef.current.allFiles.length });
     setBusy(false);
     // Save the processed state when scan completes or stops
@@ -1170,6 +1298,9 @@ export default function App() {
 
   const processRemainingFiles = async () => {
     const { allFiles, currentFileIndex, processedFiles, used } = scanControlRef.current;
+    let cacheHits = 0;
+    const operationId = newOperationId('scan');
+    scanOperationRef.current = operationId;
     
     for (let i = currentFileIndex; i < allFiles.length; i++) {
       // Check for stop signal
@@ -1190,7 +1321,12 @@ export default function App() {
       let reason = 'unsupported';
 
       try {
-        const contentJson = await invoke<string>('read_file_content', { path: f });
+        const contentJson = await invoke<string>('read_file_content', {
+          path: f,
+          quality: llmConfig.imageQuality,
+          maxDimension: llmConfig.imageMaxDimension,
+          operationId,
+        });
         fileContent = JSON.parse(contentJson);
         
         // Determine if the file is readable
@@ -1213,9 +1349,21 @@ export default function App() {
           reason = 'image';
           text = ''; // No text content for images
         }
-      } catch (e) {
-        // File not supported or error reading
-        reason = 'unsupported';
+      } catch (e: any) {
+        // Stopped while this file was waiting to be read; it's read again on resume
+        if (e?.kind === 'Cancelled') {
+          scanControlRef.current.currentFileIndex = i;
+          setScanState('stopped');
+          await finalizeScan();
+          return;
+        }
+        // Cloud placeholders are categorized by extension instead of being downloaded
+        reason = e?.kind === 'CloudPlaceholder' ? 'cloud-only' : 'unsupported';
+        // Truncated, empty or password protected documents: say what the user can do about it
+        if (e?.is_recoverable) {
+          reason = e.kind === 'Encrypted' ? 'password-protected' : 'damaged';
+          setEvents((prev: string[]) => [`  ${f}: ${e.message}`, ...prev]);
+        }
       }
 
       setEvents((prev: string[]) => [`Reading ${f} (${reason})`, ...prev]);
@@ -1253,8 +1401,9 @@ export default function App() {
         continue;
       }
       
-      // For readable files (text/images), use LLM classification
-      setEvents((prev: string[]) => [`Classifying ${f}`, ...prev]);
+      // Unchanged files classified by the same model before don't need another LLM call
+      const modelId = classificationModelId(llmConfig);
+      const cached = await getCachedClassification(f, modelId);
       let result: { category_path: string; suggested_filename: string; raw?: any };
       
       // Build categories hint - use existing categories if enabled, otherwise use hints from already processed files
@@ -1263,38 +1412,52 @@ export default function App() {
         ? existingCategoriesList 
         : categoriesHint;
       
-      try {
-        result = await classifyViaLLM({ 
-          config: llmConfig, 
-          text, 
-          originalName: originalName, 
-          categoriesHint: effectiveCategoriesHint,
-          fileContent: fileContent || undefined,
-        });
+      if (cached) {
+        cacheHits += 1;
+        setEvents((prev: string[]) => [`Using cached classification for ${f}`, ...prev]);
+        result = { category_path: cached.category, suggested_filename: cached.destination, raw: { method: 'cache', cached_at: cached.cached_at } };
+      } else {
+        try {
+          setEvents((prev: string[]) => [`Classifying ${f}`, ...prev]);
+          result = await classifyViaLLM({ 
+            config: llmConfig, 
+            text, 
+            originalName: originalName, 
+            categoriesHint: effectiveCategoriesHint,
+            fileContent: fileContent || undefined,
+            path: f,
+          });
+          const redactions: Record<string, number> | undefined = result.raw?.redactions;
+          if (redactions) {
+            const removed = Object.entries(redactions).map(([label, count]) => `${count} ${label}`).join(', ');
+            setEvents((prev: string[]) => [`Redacted before sending ${f}: ${removed}`, ...prev]);
+          }
         
-        // If using existing categories, verify the result matches one of the existing categories
-        if (useExistingCategories && existingCategoriesList.length > 0) {
-          const suggestedCategory = result.category_path?.split('/')[0] || '';
-          const matchesExisting = existingCategoriesList.some(cat => 
-            cat.toLowerCase() === suggestedCategory.toLowerCase() ||
-            result.category_path?.toLowerCase().startsWith(cat.toLowerCase())
-          );
+          // If using existing categories, verify the result matches one of the existing categories
+          if (useExistingCategories && existingCategoriesList.length > 0) {
+            const suggestedCategory = result.category_path?.split('/')[0] || '';
+            const matchesExisting = existingCategoriesList.some(cat => 
+              cat.toLowerCase() === suggestedCategory.toLowerCase() ||
+              result.category_path?.toLowerCase().startsWith(cat.toLowerCase())
+            );
           
-          if (!matchesExisting) {
-            // Find the best matching existing category
-            const bestMatch = existingCategoriesList.find(cat => 
-              result.category_path?.toLowerCase().includes(cat.toLowerCase())
-            ) || existingCategoriesList[0] || 'uncategorized';
+            if (!matchesExisting) {
+              // Find the best matching existing category
+              const bestMatch = existingCategoriesList.find(cat => 
+                result.category_path?.toLowerCase().includes(cat.toLowerCase())
+              ) || existingCategoriesList[0] || 'uncategorized';
             
-            debugLogger.info('CLASSIFY', 'Adjusted category to match existing', { 
-              original: result.category_path, 
-              adjusted: bestMatch 
-            });
-            result.category_path = bestMatch;
+              debugLogger.info('CLASSIFY', 'Adjusted category to match existing', { 
+                original: result.category_path, 
+                adjusted: bestMatch 
+              });
+              result.category_path = bestMatch;
+            }
           }
+          await cacheClassification(f, result.category_path, result.suggested_filename, modelId);
+        } catch (e: any) {
+          result = { category_path: 'uncategorized', suggested_filename: originalName, raw: { error: e?.message || String(e) } };
         }
-      } catch (e: any) {
-        result = { category_path: 'uncategorized', suggested_filename: originalName, raw: { error: e?.message || String(e) } };
       }
       
       // Use suggested_filename directly - it already has the extension removed by the LLM
@@ -1320,8 +1483,12 @@ export default function App() {
     }
     
     // If we reach here, scan completed normally
+    if (cacheHits > 0) {
+      setEvents((prev: string[]) => [`${cacheHits} files reused cached classifications instead of calling the LLM`, ...prev]);
+    }
     setScanState('completed');
     await finalizeScan();
+    notifyIfUnfocused('Scan complete', `${processedFiles.length} files analyzed and ready for review`, 'success');
   };
 
   const categoriesHint = useMemo(() => {
@@ -1423,19 +1590,75 @@ export default function App() {
 
       // Collect files from all selected directories
       let allFilesFromAllDirs: string[] = [];
-      
-      for (const directory of directories) {
+      const roots = includeSubdirectories ? distinctRoots(directories) : directories;
+      for (const skipped of directories.filter(dir => !roots.includes(dir))) {
+        setEvents((prev: string[]) => [`Skipping ${skipped}: already included in another selected directory`, ...prev]);
+      }
+
+      for (const directory of roots) {
+        // Surface permission problems once, before any file is processed
+        const access = await checkDirectoryAccess(directory);
+        for (const failure of access.failures) {
+          setEvents((prev: string[]) => [`Warning: ${failure.message}. ${failure.suggestion}`, ...prev]);
+        }
+        if (!access.can_list) {
+          throw new Error(`Cannot scan ${directory}`);
+        }
+
         setEvents((prev: string[]) => [`Scanning directory: ${directory}`, ...prev]);
-        const files: string[] = await invoke('read_directory', { path: directory, includeSubdirectories: includeSubdirectories });
-        const processableFiles = files.filter(f => !splitPath(f).name.startsWith('.'));
-        allFilesFromAllDirs = allFilesFromAllDirs.concat(processableFiles);
+        // Hidden files and OS junk (.DS_Store, Thumbs.db, ...) are filtered by the backend
+        const scanId = newOperationId('scan');
+        // Progress replaces its own line instead of adding one every quarter second
+        const unlistenScanProgress = await listen<DirectoryScanProgress>('directory-scan-progress', (event) => {
+          if (event.payload.scan_id !== scanId) return;
+          const line = `  Scanning ${event.payload.current_path} (${event.payload.files_found} files so far)`;
+          setEvents((prev: string[]) => prev[0]?.startsWith('  Scanning ') ? [line, ...prev.slice(1)] : [line, ...prev]);
+        });
+        let listing: DirectoryListing;
+        try {
+          listing = await invoke<DirectoryListing>('read_directory', { path: directory, includeSubdirectories, includeHidden, scanId });
+        } finally {
+          unlistenScanProgress();
+        }
+        let processableFiles = listing.files;
         setEvents((prev: string[]) => [`  Found ${processableFiles.length} files in ${directory}`, ...prev]);
+        if (listing.skipped.length > 0) {
+          debugLogger.warn('SCAN', 'Skipped unreadable entries', listing.skipped);
+          const denied = listing.skipped.filter(s => s.reason === 'permission_denied').length;
+          setEvents((prev: string[]) => [
+            `  Warning: skipped ${listing.skipped.length} unreadable entries${denied > 0 ? ` (${denied} permission denied)` : ''}`,
+            ...prev,
+          ]);
+        }
+        if (sampleSize > 0 && processableFiles.length > sampleSize) {
+          const sample = await sampleDirectoryForClassification(directory, sampleSize, { includeSubdirectories, includeHidden });
+          processableFiles = sample.files.map(f => f.path);
+          const mix = sample.composition
+            .filter(c => c.sampled > 0)
+            .map(c => `${c.category} ${c.sampled}/${c.population}`)
+            .join(', ');
+          setEvents((prev: string[]) => [`  Classifying a sample of ${processableFiles.length} (seed ${sample.seed}): ${mix}`, ...prev]);
+        }
+        allFilesFromAllDirs = allFilesFromAllDirs.concat(processableFiles);
+        try {
+          const tagged = await getDirectoryTags(directory, includeSubdirectories);
+          if (tagged.length > 0) {
+            setEvents((prev: string[]) => [`  ${tagged.length} of them were organized before and carry category tags`, ...prev]);
+          }
+        } catch (e: any) {
+          debugLogger.warn('SCAN', 'Failed to read directory tags', { directory, error: e?.message || String(e) });
+        }
+        // Names that aren't valid Unicode arrive with U+FFFD; the backend maps them back to the real file
+        const unreadableNames = processableFiles.filter(f => f.includes('\uFFFD')).length;
+        if (unreadableNames > 0) {
+          setEvents((prev: string[]) => [`  Warning: ${unreadableNames} file name${unreadableNames === 1 ? '' : 's'} in ${directory} contain characters that can't be displayed and are shown with �`, ...prev]);
+        }
       }
 
       scanControlRef.current.allFiles = allFilesFromAllDirs;
       setProgress({ current: 0, total: allFilesFromAllDirs.length });
 
-      setEvents((prev: string[]) => [`Total: ${allFilesFromAllDirs.length} files to process from ${directories.length} director${directories.length === 1 ? 'y' : 'ies'}`, ...prev]);
+      setEvents((prev: string[]) => [`Total: ${allFilesFromAllDirs.length} files to process from ${roots.length} director${roots.length === 1 ? 'y' : 'ies'}`, ...prev]);
 
       // Start processing files
       await processRemainingFiles();
@@ -1560,50 +1783,129 @@ export default function App() {
     const totalAnalyzed = rows.length;
     let movedCount = 0;
     let failedCount = 0;
+    // Rows not reached before a cancel stay in the list
+    let skipped: Row[] = [];
+    const operationId = newOperationId('move');
+    moveOperationRef.current = operationId;
     
     // Update progress to show organizing phase
     setProgress({ current: 0, total: totalToMove });
+
+    // Moves are only allowed into the selected directories (unless the policy lists others)
+    try {
+      await setOrganizationRoots(directories);
+    } catch (e: any) {
+      setEvents((prev: string[]) => [e?.message || String(e), ...prev]);
+    }
     
     for (let i = 0; i < selected.length; i++) {
       const row = selected[i];
       const to = toPath(row);
       try {
-        await invoke('move_file', { from: row.src, to });
+        // Retry briefly in case the file is still being written, e.g. by a browser download
+        // An identical copy already at the destination is left alone instead of failing the move
+        const outcome = await invoke<MoveOutcome>('move_file', {
+          from: row.src,
+          to,
+          retry: true,
+          operationId,
+          batchId: operationId,
+          captureHash: captureHashes,
+          dedupeAtDestination: true,
+        });
         movedCount++;
         setProgress({ current: movedCount, total: totalToMove });
+        if (outcome.action === 'skipped_duplicate') {
+          setEvents((prev: string[]) => [`Skipped ${row.src}: an identical copy is already at ${outcome.to}`, ...prev]);
+          continue;
+        }
         setEvents((prev: string[]) => [`Moved ${row.src} to ${to}`, ...prev]);
+        if (tagMovedFiles) {
+          setFileTags(to, [row.category]).catch((e: any) => {
+            setEvents((prev: string[]) => [`Couldn't tag ${to}: ${e?.message || String(e)}`, ...prev]);
+          });
+        }
       } catch (e: any) {
+        if (e?.kind === 'Cancelled') {
+          skipped = selected.slice(i);
+          setEvents((prev: string[]) => [
+            `Organizing cancelled: ${movedCount} moved, ${skipped.length} not moved`,
+            ...prev,
+          ]);
+          break;
+        }
+        // The drive was unplugged: the rest would fail the same way, so stop here
+        if (e?.kind === 'VolumeUnavailable') {
+          skipped = selected.slice(i);
+          setEvents((prev: string[]) => [
+            `Organizing stopped: ${e.message}. ${movedCount} moved, ${skipped.length} not moved`,
+            ...prev,
+          ]);
+          break;
+        }
         failedCount++;
-        setEvents((prev: string[]) => [`Failed to move ${row.src}: ${e}`, ...prev]);
+        const reason = e?.kind === 'FileInUse'
+          ? 'the file is in use by another process; close it and try again'
+          : e?.kind === 'PolicyViolation'
+            ? `blocked by the destination policy (${e.rule}): ${e.message}`
+            : e?.message || String(e);
+        setEvents((prev: string[]) => [`Failed to move ${row.src}: ${reason}`, ...prev]);
       }
     }
     
-    // Keep unselected rows for further review or processing
-    setRows(unselected);
+    finishOperation(operationId).catch(() => {});
+    moveOperationRef.current = null;
+
+    // Cross-check the batch's journal so anything lost or left behind is reported
+    if (movedCount > 0) {
+      try {
+        const report = await verifyOrganization(operationId);
+        const problems = [
+          ...report.discrepancies.map(d => `Verification: ${d.from} -> ${d.to}: ${d.detail}`),
+          ...report.stray_temp_files.map(f => `Verification: leftover temp file ${f}`),
+        ];
+        setEvents((prev: string[]) => [
+          `Verified ${report.verified} of ${report.entries} moves${report.hashes_checked > 0 ? ` (${report.hashes_checked} by hash)` : ''}${problems.length > 0 ? `, ${problems.length} problems found` : ''}`,
+          ...problems,
+          ...prev,
+        ]);
+      } catch (e: any) {
+        setEvents((prev: string[]) => [e?.message || String(e), ...prev]);
+      }
+    }
+
+    // Keep unselected rows (and any skipped by a cancel) for further review or processing
+    const remaining = [...skipped, ...unselected];
+    setRows(remaining);
     
-    // Update scanControlRef to reflect only unselected files remain
+    // Update scanControlRef to reflect only the remaining files
     if (scanControlRef.current.processedFiles.length > 0) {
-      const unselectedSrcs = new Set(unselected.map(r => r.src));
+      const remainingSrcs = new Set(remaining.map(r => r.src));
       scanControlRef.current.processedFiles = scanControlRef.current.processedFiles.filter(
-        file => unselectedSrcs.has(file.src)
+        file => remainingSrcs.has(file.src)
       );
     }
     
     // Check if there are more files to scan
     const hasMoreFilesToScan = scanControlRef.current.currentFileIndex < scanControlRef.current.allFiles.length;
     
-    const summary = `Done. Analyzed ${totalAnalyzed} files, organized ${movedCount} files${failedCount > 0 ? `, ${failedCount} failed` : ''}${unselected.length > 0 ? `. ${unselected.length} files remain for review.` : '.'}`;
+    const summary = `Done. Analyzed ${totalAnalyzed} files, organized ${movedCount} files${failedCount > 0 ? `, ${failedCount} failed` : ''}${remaining.length > 0 ? `. ${remaining.length} files remain for review.` : '.'}`;
     setEvents((prev: string[]) => [summary, ...prev]);
+    notifyIfUnfocused(
+      'Organizing complete',
+      `${movedCount} files organized${failedCount > 0 ? `, ${failedCount} failed` : ''}`,
+      failedCount > 0 ? 'error' : 'success'
+    );
     
-    // If there are unselected files OR more files to scan, allow resuming the scan
-    if (unselected.length > 0 || hasMoreFilesToScan) {
+    // If there are remaining files OR more files to scan, allow resuming the scan
+    if (remaining.length > 0 || hasMoreFilesToScan) {
       setScanState('stopped');
-      if (hasMoreFilesToScan && unselected.length === 0) {
+      if (hasMoreFilesToScan && remaining.length === 0) {
         const remainingCount = scanControlRef.current.allFiles.length - scanControlRef.current.currentFileIndex;
         setEvents((prev: string[]) => [`${remainingCount} files remaining to scan. Press "Resume Scan" to continue.`, ...prev]);
         // Update progress to reflect remaining files to scan
         setProgress({ current: scanControlRef.current.currentFileIndex, total: scanControlRef.current.allFiles.length });
-      } else if (unselected.length > 0) {
+      } else if (remaining.length > 0) {
         setEvents((prev: string[]) => ['You can continue to review remaining files or resume scanning.', ...prev]);
       }
     } else {
@@ -1615,6 +1917,48 @@ export default function App() {
     setBusy(false);
   };
 
+  const cancelMoves = () => {
+    if (moveOperationRef.current) {
+      cancelOperation(moveOperationRef.current).catch((e: any) => {
+        setEvents((prev: string[]) => [e?.message || String(e), ...prev]);
+      });
+      setEvents((prev: string[]) => ['Cancelling organizing...', ...prev]);
+    }
+  };
+
+  // Runs every selected move as a dry run: the same checks as Approve, but nothing is moved
+  const simulateMoves = async () => {
+    setBusy(true);
+    const selected = rows.filter((r: Row) => r.enabled);
+    let okCount = 0;
+    try {
+      await setOrganizationRoots(directories);
+    } catch (e: any) {
+      setEvents((prev: string[]) => [e?.message || String(e), ...prev]);
+    }
+    for (const row of selected) {
+      const to = toPath(row);
+      try {
+        const outcome = await invoke<MoveOutcome>('move_file', { from: row.src, to, dryRun: true, dedupeAtDestination: true });
+        okCount++;
+        setEvents((prev: string[]) => [
+          outcome.action === 'skipped_duplicate'
+            ? `[Simulated] Would skip ${row.src}: an identical copy is already at ${outcome.to}`
+            : `[Simulated] Would move ${row.src} to ${to}`,
+          ...prev,
+        ]);
+      } catch (e: any) {
+        setEvents((prev: string[]) => [`[Simulated] Moving ${row.src} would fail: ${e?.message || String(e)}`, ...prev]);
+      }
+    }
+    const failed = selected.length - okCount;
+    setEvents((prev: string[]) => [
+      `[Simulated] ${okCount} of ${selected.length} moves would succeed${failed > 0 ? `, ${failed} would fail` : ''}. Nothing was moved.`,
+      ...prev,
+    ]);
+    setBusy(false);
+  };
+
   const updateRow = (i: number, patch: Partial<Row>) => {
     // If category is being updated manually, remove it from optimized set
     if (patch.category !== undefined) {
@@ -1694,7 +2038,10 @@ export default function App() {
   // Handle file opening
   const handleOpenFile = async (filePath: string) => {
     try {
-      await openFile(filePath);
+      const outcome = await openFile(filePath);
+      if (outcome === 'revealed') {
+        setEvents((prev: string[]) => [`${filePath} can run as a program, so it was shown in its folder instead of opened`, ...prev]);
+      }
     } catch (error: any) {
       setEvents((prev: string[]) => [`Failed to open ${filePath}: ${error.message}`, ...prev]);
     }
@@ -1993,6 +2340,10 @@ export default function App() {
               {scanState === 'scanning' && (
                 <button className="danger" onClick={stopScan} disabled={!busy}>Stop</button>
               )}
+
+              {scanState === 'organizing' && (
+                <button className="danger" onClick={cancelMoves} disabled={!busy}>Cancel</button>
+              )}
               
               {(scanState === 'completed' || scanState === 'stopped') && (
                 <button className="secondary" onClick={resetScan}>New Scan</button>
@@ -2057,6 +2408,26 @@ export default function App() {
                   />
                   Include subdirectories
                 </label>
+                <label className="mt8" title="Dotfiles on macOS/Linux, files with the hidden or system attribute on Windows. OS metadata like .DS_Store and Thumbs.db is always skipped.">
+                  <input 
+                    type="checkbox" 
+                    checked={includeHidden} 
+                    onChange={e => setIncludeHidden(e.target.checked)}
+                    disabled={busy || scanState === 'scanning' || scanState === 'stopped'}
+                  />
+                  Include hidden files
+                </label>
+                <label className="mt8" title="Classify a representative sample first (spread over file types and sizes) to see how the model does before running it on everything. 0 classifies every file.">
+                  Sample size
+                  <input
+                    type="number"
+                    min={0}
+                    value={sampleSize}
+                    onChange={e => setSampleSize(Math.max(0, parseInt(e.target.value, 10) || 0))}
+                    disabled={busy || scanState === 'scanning' || scanState === 'stopped'}
+                    style={{ width: '5em', marginLeft: '0.5em' }}
+                  />
+                </label>
                 <label className="mt8" title="Classify files into existing subdirectories only. Files that don't match will be categorized by extension.">
                   <input 
                     type="checkbox" 
@@ -2104,6 +2475,25 @@ export default function App() {
                   >
                     {searchReplaceExpanded ? 'Hide Find & Replace' : 'Find & Replace'}
                   </button>
+                  <button className="secondary" onClick={simulateMoves} disabled={busy} title="Check the selected moves without moving anything">Simulate</button>
+                  <label title="Hash each file before moving it so the check after organizing can compare contents (slower)">
+                    <input
+                      type="checkbox"
+                      checked={captureHashes}
+                      onChange={(e) => setCaptureHashes(e.target.checked)}
+                      disabled={busy}
+                    />
+                    Record hashes
+                  </label>
+                  <label title="Save each file's category with it so other tools and later scans can see it">
+                    <input
+                      type="checkbox"
+                      checked={tagMovedFiles}
+                      onChange={(e) => setTagMovedFiles(e.target.checked)}
+                      disabled={busy}
+                    />
+                    Tag with category
+                  </label>
                   <button onClick={applyMoves} disabled={busy}>Approve Selected</button>
                 </div>
               </div>
@@ -2315,6 +2705,7 @@ export default function App() {
                         <tr key={originalIndex} className={isOptimized ? 'optimized-row' : ''}>
                           <td><input aria-label={`Select ${r.src}`} type="checkbox" checked={!!r.enabled} onChange={e => updateRow(originalIndex, { enabled: e.target.checked })} /></td>
                           <td>
+                            <FileThumbnail path={r.src} ext={r.ext} />
                             <code 
                               className="clickable-file-path" 
                               onClick={() => handleOpenFile(r.src)}
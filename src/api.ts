@@ -1,28 +1,215 @@
 import { invoke } from '@tauri-apps/api/tauri';
+import { appWindow } from '@tauri-apps/api/window';
+import { isPermissionGranted, requestPermission } from '@tauri-apps/api/notification';
 import { debugLogger } from './debug-logger';
-import { ManagedLLMServerInfo, ManagedLLMConfig } from './types';
+import {
+  ManagedLLMServerInfo,
+  ManagedLLMConfig,
+  ManagedServerModel,
+  LLMBackendConfig,
+  ActiveLLMBackend,
+  LLMClassifyRequest,
+  LLMClassifyResponse,
+  LLMMetrics,
+  HfSearchFilters,
+  HfModel,
+  HfModelFile,
+  DownloadedModel,
+  ModelRecommendations,
+  LLMFallbackSettings,
+  HttpStreamChunkEvent,
+  HttpStreamDoneEvent,
+  HttpStreamErrorEvent,
+  DirectoryStreamOptions,
+  DirectoryEntriesEvent,
+  DirectoryStreamComplete,
+  NetworkConfig,
+  DestinationPolicy,
+  Settings,
+  AppInfo,
+  NetworkTestResult,
+  DirectoryAccessReport,
+  Schedule,
+  ScanOptions,
+  ScheduledScanResult,
+  CachedClassification,
+  ClassificationCacheStats,
+  PreparedContent,
+  Thumbnail,
+  TreeSummary,
+  DestinationSuggestions,
+  DirectoryComposition,
+  UnusedDirectoryReport,
+  DuplicateDirectoryReport,
+  IoPriority,
+  AnalysisOptions,
+  CheckpointSummary,
+  Volume,
+  DirectoryEntry,
+  DateGranularity,
+  DatePlan,
+  RenameConflictMode,
+  RenameOutcome,
+  RenamePlanItem,
+  RenameBatchReport,
+  VerificationReport,
+  ArchiveFilesResult,
+  RestoreReport,
+  StagedBatch,
+  StagedBatchSummary,
+  StagingReport,
+  PlannedMove,
+  RunReport,
+  UndoReport,
+  PipelineOptions,
+  PipelineReport,
+  InterruptedRun,
+  SpotlightMetadata,
+  Provenance,
+  ShortcutReport,
+  Application,
+  OpenOutcome,
+  AuditRange,
+  AuditExportSummary,
+  AuditVerification,
+  RedactionRules,
+  RedactedContent,
+  TagStorage,
+  FileTags,
+  IndexOptions,
+  ClassificationSample,
+  SampleOptions,
+  ComparedFile,
+  ComparisonSummary,
+  IndexReport,
+  SearchMatch,
+  ReductionStrategy,
+  ExternalExtractor,
+  ExtractorOutput,
+} from './types';
+
+// Raw response from the http_request command
+export interface HttpResponse {
+  status: number;
+  headers: Record<string, string>; // lowercased names
+  body: string; // base64 when responseAsBase64 was set
+  url: string;
+  attempts: number;
+}
+
+export interface HttpRequestOptions {
+  method: string;
+  headers?: Record<string, string>;
+  body?: string;
+  bodyBase64?: string; // binary payload, mutually exclusive with body
+  timeoutMs?: number; // defaults to 120 s
+  retries?: number; // only applied to GET, HEAD, PUT and DELETE; defaults to 2
+  responseAsBase64?: boolean;
+}
+
+// Direct access to the http_request command, for binary bodies and responses
+export async function httpRequest(url: string, options: HttpRequestOptions): Promise<HttpResponse> {
+  return await invoke<HttpResponse>('http_request', {
+    url,
+    method: options.method,
+    headers: options.headers ?? {},
+    body: options.body,
+    bodyBase64: options.bodyBase64,
+    timeoutMs: options.timeoutMs,
+    retries: options.retries,
+    responseAsBase64: options.responseAsBase64,
+  });
+}
+
+export interface HttpStreamOptions {
+  method: 'GET' | 'POST';
+  headers?: Record<string, string>;
+  body?: string;
+  timeoutMs?: number; // no timeout unless set
+  onFrames: (frames: string[]) => void; // SSE data payloads, possibly several per call
+}
+
+export interface HttpStreamHandle {
+  requestId: string;
+  cancel: () => Promise<void>;
+  done: Promise<{ cancelled: boolean }>; // rejects with the error message on failure
+}
+
+// Streams Server-Sent Events through the backend, e.g. for `stream: true` chat completions
+export async function streamHttpRequest(url: string, options: HttpStreamOptions): Promise<HttpStreamHandle> {
+  const requestId = `stream-${Date.now()}-${Math.random().toString(36).slice(2, 10)}`;
+
+  let resolveDone!: (value: { cancelled: boolean }) => void;
+  let rejectDone!: (reason: Error) => void;
+  const done = new Promise<{ cancelled: boolean }>((resolve, reject) => {
+    resolveDone = resolve;
+    rejectDone = reject;
+  });
+
+  // Listeners are registered before the request starts so no frame is missed
+  const unlisteners = await Promise.all([
+    appWindow.listen<HttpStreamChunkEvent>('http-stream-chunk', (event) => {
+      if (event.payload.request_id === requestId) {
+        options.onFrames(event.payload.frames);
+      }
+    }),
+    appWindow.listen<HttpStreamDoneEvent>('http-stream-done', (event) => {
+      if (event.payload.request_id === requestId) {
+        resolveDone({ cancelled: event.payload.cancelled });
+      }
+    }),
+    appWindow.listen<HttpStreamErrorEvent>('http-stream-error', (event) => {
+      if (event.payload.request_id === requestId) {
+        rejectDone(new Error(event.payload.error));
+      }
+    }),
+  ]);
+  const cleanup = () => unlisteners.forEach((unlisten) => unlisten());
+  done.then(cleanup, cleanup);
+
+  try {
+    await invoke('http_request_stream', {
+      requestId,
+      url,
+      method: options.method,
+      headers: options.headers ?? {},
+      body: options.body,
+      timeoutMs: options.timeoutMs,
+    });
+  } catch (error: any) {
+    cleanup();
+    throw error;
+  }
+
+  return {
+    requestId,
+    cancel: async () => {
+      await invoke('cancel_http_stream', { requestId });
+    },
+    done,
+  };
+}
 
 // Helper function to make HTTP requests via Tauri backend (bypasses CORS)
 async function tauriFetch(url: string, options: {
   method: string;
   headers: Record<string, string>;
   body?: any;
-}): Promise<{ ok: boolean; status: number; data: string }> {
+}): Promise<{ ok: boolean; status: number; data: string; headers: Record<string, string> }> {
   try {
     const bodyString = options.body ? JSON.stringify(options.body) : undefined;
-    const text = await invoke<string>('http_request', {
+    const response = await invoke<HttpResponse>('http_request', {
       url,
       method: options.method,
       headers: options.headers,
       body: bodyString,
     });
-    return { ok: true, status: 200, data: text };
+    const ok = response.status >= 200 && response.status < 300;
+    return { ok, status: response.status, data: response.body, headers: response.headers };
   } catch (error: any) {
-    // Parse error message to extract status code if present
-    const errorMessage = error?.toString() || String(error);
-    const statusMatch = errorMessage.match(/HTTP (\d+):/);
-    const status = statusMatch ? parseInt(statusMatch[1]) : 500;
-    return { ok: false, status, data: errorMessage };
+    // The request never got a response (DNS, connection, TLS...); status 0 like fetch()
+    const errorMessage = error?.message || String(error);
+    return { ok: false, status: 0, data: errorMessage, headers: {} };
   }
 }
 
@@ -41,12 +228,17 @@ export interface LLMConfig {
   customCategoryPrompt?: string; // Custom prompt template for category optimization
   customHeaders?: Record<string, string>;
   supportsVision?: boolean; // Whether the model supports image inputs
+  imageMaxDimension?: number; // Longest side of images sent to vision models (default 1024)
+  imageQuality?: number; // JPEG quality of images sent to vision models, 1-100 (default 75)
 }
 
 export interface FileContent {
   text?: string;
   image_base64?: string;
   mime_type?: string;
+  encoding?: string; // what plain text was decoded from, e.g. "windows-1252"
+  language?: string; // source code: "rust", "python"... the text is an outline unless summarizeCode was false
+  line_count?: number; // source code: lines in the file itself
 }
 
 export const DEFAULT_CONFIGS: Record<LLMProviderType, Partial<LLMConfig>> = {
@@ -102,8 +294,7 @@ function getCompletionEndpoint(config: LLMConfig): string {
     case 'anthropic':
       return `${base}/v1/messages`;
     case 'gemini':
-      // Gemini uses API key in URL
-      return `${base}/v1beta/models/${config.model}:generateContent?key=${config.apiKey || ''}`;
+      return `${base}/v1beta/models/${config.model}:generateContent`;
     case 'openai':
     case 'groq':
     case 'lmstudio':
@@ -135,8 +326,9 @@ function buildHeaders(config: LLMConfig): Record<string, string> {
         anthropicVersion: '2023-06-01',
       });
     } else if (config.provider === 'gemini') {
-      // Gemini uses API key in URL, not in headers
-      debugLogger.debug('BUILD_HEADERS', 'Gemini provider - API key in URL', {});
+      // A header rather than ?key=, which would end up in proxy and server logs
+      headers['x-goog-api-key'] = config.apiKey;
+      debugLogger.debug('BUILD_HEADERS', 'Added Gemini API key header', {});
     } else {
       // Default to Bearer token for OpenAI-compatible APIs
       headers['Authorization'] = `Bearer ${config.apiKey}`;
@@ -409,8 +601,9 @@ export async function classifyViaLLM(opts: {
   originalName: string,
   categoriesHint: string[],
   fileContent?: FileContent,
+  path?: string, // recorded in the audit log when audit mode is on
 }): Promise<{ category_path: string; suggested_filename: string; raw?: any }>{
-  const { config, text, originalName, categoriesHint, fileContent } = opts;
+  const { config, text: extractedText, originalName, categoriesHint, fileContent, path } = opts;
   
   // Check if we have an image but the model doesn't support vision
   if (fileContent?.image_base64 && !config.supportsVision) {
@@ -421,6 +614,14 @@ export async function classifyViaLLM(opts: {
       raw: { skipped: 'Model does not support vision' },
     };
   }
+
+  // Personal data is swapped for placeholders before the text goes to a remote backend
+  // (and to local ones with redaction.redact_always), as the redaction settings say
+  const redaction = extractedText
+    ? await redactSensitiveContent(extractedText, undefined, toBackendConfig(config))
+    : undefined;
+  const text = redaction?.text ?? extractedText;
+  const redactions = redaction && Object.keys(redaction.counts).length > 0 ? redaction.counts : undefined;
   
   const hint = categoriesHint?.length ? `\n\nIMPORTANT: You MUST classify the file into one of the following existing categories. Do NOT create new categories.\nExisting categories:\n- ${categoriesHint.join('\n- ')}` : '';
   const maxTextLength = config.maxTextLength || 4096;
@@ -539,6 +740,34 @@ export async function classifyViaLLM(opts: {
     ? { base64: fileContent.image_base64, mimeType: fileContent.mime_type }
     : undefined;
   
+  // With a fallback chain configured, route through the Rust backend router so an
+  // unreachable provider falls back instead of failing the file
+  const fallbackSettings = await getLLMFallbackSettings().catch(() => null);
+  if (fallbackSettings && fallbackSettings.chain.length > 0) {
+    await selectLLMBackend(toBackendConfig(config));
+    const result = await llmClassify({
+      system_message: systemMessage,
+      prompt,
+      image_base64: imageData?.base64,
+      mime_type: imageData?.mimeType,
+      max_tokens: config.maxTokens,
+      source_path: path,
+    });
+    debugLogger.info('LLM_RESPONSE', 'Received routed response', { backend: result.backend, fellBack: result.fell_back });
+    const parsed = safeParseJson(normalizeLLMContent(result.content), () => ({
+      category_path: 'uncategorized',
+      suggested_filename: originalName.replace(/\.[^/.]+$/, ''),
+    }));
+    // The router redacts again per backend, which only finds something when the text
+    // wasn't redacted here (a local primary falling back to a remote backend)
+    const routedRedactions =
+      result.redactions && Object.keys(result.redactions).length > 0 ? result.redactions : undefined;
+    return {
+      ...parsed,
+      raw: { backend: result.backend, fell_back: result.fell_back, redactions: redactions ?? routedRedactions },
+    };
+  }
+
   const body = buildRequestBody(config, prompt, systemMessage, imageData);
 
   // Comprehensive debug logging
@@ -561,6 +790,12 @@ export async function classifyViaLLM(opts: {
     hasImageData: !!imageData,
   });
 
+  // Throws when local-only mode doesn't allow sending file content to this endpoint
+  await checkNetworkPolicy(endpoint, path);
+  if (path) {
+    await recordLLMSubmission(path, `${config.provider} (${endpoint})`, JSON.stringify(body).length);
+  }
+
   let resp;
   try {
     debugLogger.debug('LLM_REQUEST', 'Calling tauriFetch', { endpoint, method: 'POST' });
@@ -607,10 +842,11 @@ export async function classifyViaLLM(opts: {
   }
 
   const content = extractContent(config, data);
-  return safeParseJson(content, () => ({
+  const parsed = safeParseJson(content, () => ({
     category_path: 'uncategorized',
     suggested_filename: originalName.replace(/\.[^/.]+$/, ''),
   }));
+  return redactions ? { ...parsed, raw: { redactions } } : parsed;
 }
 
 export async function optimizeCategoriesViaLLM(opts: {
@@ -725,105 +961,1231 @@ export function processOptimizationResponse(config: LLMConfig, resp: { ok: boole
   return jsonResult;
 }
 
-// Open a file using the operating system's default application
-export async function openFile(path: string): Promise<void> {
+// Open a file using the operating system's default application. Executables and scripts
+// are revealed in the file manager instead unless allowDangerous is set.
+export async function openFile(path: string, allowDangerous?: boolean): Promise<OpenOutcome> {
   try {
-    await invoke('open_file', { path });
+    return await invoke<OpenOutcome>('open_file', { path, allowDangerous });
   } catch (error: any) {
     throw new Error(`Failed to open file: ${error.message || String(error)}`);
   }
 }
 
-// Helpers to list available local models for Ollama and LM Studio
-export async function listOllamaModels(baseUrl: string): Promise<string[]> {
-  const url = baseUrl.replace(/\/$/, '') + '/api/tags';
+// Signed bundle of the audit records in range (all by default)
+export async function exportAuditLog(path: string, range?: AuditRange): Promise<AuditExportSummary> {
   try {
-    const resp = await tauriFetch(url, { method: 'GET', headers: {} });
-    if (!resp.ok) {
-      throw new Error(`Ollama error ${resp.status}: ${resp.data}`);
-    }
-    const data = JSON.parse(resp.data);
-    // Ollama returns { models: [...] } where each model has a `name` property
-    if (data && Array.isArray(data.models)) {
-      return data.models.map((m: any) => m.name || String(m));
-    }
-    return [];
-  } catch (e) {
-    debugLogger.warn('API', 'Failed to list Ollama models', { error: e });
-    return [];
+    return await invoke<AuditExportSummary>('export_audit_log', { path, range });
+  } catch (error: any) {
+    throw new Error(`Failed to export audit log: ${error.message || String(error)}`);
   }
 }
 
-export async function listLMStudioModels(baseUrl: string): Promise<string[]> {
-  const url = baseUrl.replace(/\/$/, '') + '/v1/models';
+// Checks this app's audit log, or an exported bundle and its signature. Bundles from
+// another install need expectedKey: the public key or fingerprint from its export.
+export async function verifyAuditLog(bundlePath?: string, expectedKey?: string): Promise<AuditVerification> {
   try {
-    const resp = await tauriFetch(url, { method: 'GET', headers: {} });
-    if (!resp.ok) {
-      throw new Error(`LM Studio error ${resp.status}: ${resp.data}`);
+    return await invoke<AuditVerification>('verify_audit_log', { bundlePath, expectedKey });
+  } catch (error: any) {
+    throw new Error(`Failed to verify audit log: ${error.message || String(error)}`);
+  }
+}
+
+// Rejects with a policy_violation error when local-only mode doesn't allow sending file
+// content to url (requests made with tauriFetch don't go through http_request)
+export async function checkNetworkPolicy(url: string, path?: string): Promise<void> {
+  try {
+    await invoke('check_network_policy', { url, path });
+  } catch (error: any) {
+    throw new Error(`Blocked by local-only mode: ${error.message || String(error)}`);
+  }
+}
+
+// Audit record for a request sent from here rather than through llm_classify; a no-op
+// while audit mode is off. Failures are logged, never thrown, so classification goes on.
+export async function recordLLMSubmission(path: string, backend: string, bytes: number): Promise<void> {
+  try {
+    await invoke('record_llm_submission', { path, backend, bytes });
+  } catch (error: any) {
+    debugLogger.warn('AUDIT', 'Failed to record LLM submission', { error: error?.message || String(error) });
+  }
+}
+
+// Replaces emails, phone numbers, card numbers and other PII with typed placeholders
+// ("[EMAIL_1]"). Without rules the saved ones are used; with a backend, text is only
+// redacted when the redaction settings call for it for that backend.
+export async function redactSensitiveContent(
+  text: string,
+  rules?: RedactionRules,
+  backend?: LLMBackendConfig,
+): Promise<RedactedContent> {
+  try {
+    return await invoke<RedactedContent>('redact_sensitive_content', { text, rules, backend });
+  } catch (error: any) {
+    throw new Error(`Failed to redact content: ${error.message || String(error)}`);
+  }
+}
+
+// Applications registered for the file's type, the default first
+export async function listApplicationsFor(path: string): Promise<Application[]> {
+  try {
+    return await invoke<Application[]>('list_applications_for', { path });
+  } catch (error: any) {
+    throw new Error(`Failed to list applications: ${error.message || String(error)}`);
+  }
+}
+
+// appId must come from listApplicationsFor for the same file
+export async function openFileWith(path: string, appId: string): Promise<void> {
+  try {
+    await invoke('open_file_with', { path, appId });
+  } catch (error: any) {
+    throw new Error(`Failed to open file: ${error.message || String(error)}`);
+  }
+}
+
+export interface CreateScheduleOptions {
+  options?: ScanOptions;
+  missedRunPolicy?: 'run' | 'skip';
+  notify?: boolean;
+  keepResults?: number; // defaults to 30
+}
+
+// cronOrInterval is an interval like "6h" / "every 30m" or a cron expression like "0 2 * * *"
+export async function createSchedule(path: string, cronOrInterval: string, settings: CreateScheduleOptions = {}): Promise<Schedule> {
+  try {
+    return await invoke<Schedule>('create_schedule', {
+      path,
+      cronOrInterval,
+      options: settings.options,
+      missedRunPolicy: settings.missedRunPolicy,
+      notify: settings.notify,
+      keepResults: settings.keepResults,
+    });
+  } catch (error: any) {
+    throw new Error(`Failed to create schedule: ${error.message || String(error)}`);
+  }
+}
+
+export async function listSchedules(): Promise<Schedule[]> {
+  try {
+    return await invoke<Schedule[]>('list_schedules');
+  } catch (error: any) {
+    throw new Error(`Failed to list schedules: ${error.message || String(error)}`);
+  }
+}
+
+export async function deleteSchedule(id: string): Promise<void> {
+  try {
+    await invoke('delete_schedule', { id });
+  } catch (error: any) {
+    throw new Error(`Failed to delete schedule: ${error.message || String(error)}`);
+  }
+}
+
+// Newest first
+export async function getScheduleResults(id: string): Promise<ScheduledScanResult[]> {
+  try {
+    return await invoke<ScheduledScanResult[]>('get_schedule_results', { id });
+  } catch (error: any) {
+    throw new Error(`Failed to load schedule results: ${error.message || String(error)}`);
+  }
+}
+
+export type NotificationKind = 'info' | 'success' | 'error';
+
+// 'default' means the user hasn't been asked yet (macOS asks on first request)
+export async function getNotificationPermission(): Promise<'granted' | 'denied' | 'default'> {
+  if (await isPermissionGranted()) {
+    return 'granted';
+  }
+  return typeof window.Notification !== 'undefined' && window.Notification.permission === 'denied' ? 'denied' : 'default';
+}
+
+export async function requestNotificationPermission(): Promise<boolean> {
+  return (await requestPermission()) === 'granted';
+}
+
+export async function notify(title: string, body: string, kind: NotificationKind = 'info'): Promise<void> {
+  try {
+    await invoke('notify', { title, body, kind });
+  } catch (error: any) {
+    throw new Error(`Failed to show notification: ${error.message || String(error)}`);
+  }
+}
+
+// Notifies only while the app is in the background; there is nothing to announce otherwise
+export async function notifyIfUnfocused(title: string, body: string, kind: NotificationKind = 'info'): Promise<void> {
+  if (document.hasFocus() || !(await isPermissionGranted())) {
+    return;
+  }
+  await notify(title, body, kind).catch((error) => {
+    debugLogger.error('NOTIFY', 'Failed to show completion notification', { error });
+  });
+}
+
+// Identifies the model a cached classification came from; a different model means a cache miss
+export function classificationModelId(config: LLMConfig): string {
+  return `${config.provider}:${config.model}`;
+}
+
+// Cached result for an unchanged file classified by the same model, or null
+export async function getCachedClassification(path: string, modelId: string): Promise<CachedClassification | null> {
+  try {
+    return await invoke<CachedClassification | null>('get_cached_classification', { path, modelId });
+  } catch (error: any) {
+    debugLogger.warn('CLASSIFICATION_CACHE', 'Cache lookup failed', { path, error: error?.message || String(error) });
+    return null;
+  }
+}
+
+// contentHash is optional; the backend hashes the file when it's omitted
+export async function cacheClassification(
+  path: string,
+  category: string,
+  destination: string,
+  modelId: string,
+  contentHash?: string,
+): Promise<void> {
+  try {
+    await invoke('cache_classification', { path, contentHash, category, destination, modelId });
+  } catch (error: any) {
+    debugLogger.warn('CLASSIFICATION_CACHE', 'Failed to cache classification', { path, error: error?.message || String(error) });
+  }
+}
+
+export async function getClassificationCacheStats(): Promise<ClassificationCacheStats> {
+  try {
+    return await invoke<ClassificationCacheStats>('get_classification_cache_stats');
+  } catch (error: any) {
+    throw new Error(`Failed to load classification cache stats: ${error.message || String(error)}`);
+  }
+}
+
+export async function clearClassificationCache(): Promise<void> {
+  try {
+    await invoke('clear_classification_cache');
+  } catch (error: any) {
+    throw new Error(`Failed to clear classification cache: ${error.message || String(error)}`);
+  }
+}
+
+// Extracts a file's text and reduces it to fit maxTokens. Counts are a heuristic estimate,
+// since the bundled servers don't expose their tokenizer.
+// The text is redacted for a remote `backend` (or the selected one) per the redaction settings
+export async function prepareClassificationContent(
+  path: string,
+  maxTokens: number,
+  strategy: ReductionStrategy = 'head',
+  backend?: LLMBackendConfig,
+): Promise<PreparedContent> {
+  try {
+    return await invoke<PreparedContent>('prepare_classification_content', { path, maxTokens, strategy, backend });
+  } catch (error: any) {
+    throw new Error(`Failed to prepare content: ${error.message || String(error)}`);
+  }
+}
+
+// Per-category counts and sizes. categories (category -> extensions) replaces the built-in grouping.
+export async function getDirectoryComposition(
+  path: string | string[],
+  includeSubdirectories: boolean,
+  categories?: Record<string, string[]>,
+  includeHidden?: boolean,
+): Promise<DirectoryComposition> {
+  try {
+    return await invoke<DirectoryComposition>('get_directory_composition', { path, includeSubdirectories, categories, includeHidden });
+  } catch (error: any) {
+    throw new Error(`Failed to analyze directory composition: ${error.message || String(error)}`);
+  }
+}
+
+// Whole directories untouched for unusedDays, e.g. old projects to archive; smaller than minBytes are left out
+export async function findUnusedDirectories(
+  path: string | string[],
+  unusedDays: number,
+  minBytes?: number,
+  includeHidden?: boolean,
+  followSymlinks?: boolean,
+): Promise<UnusedDirectoryReport> {
+  try {
+    return await invoke<UnusedDirectoryReport>('find_unused_directories', {
+      path,
+      unusedDays,
+      minBytes,
+      includeHidden,
+      followSymlinks,
+    });
+  } catch (error: any) {
+    throw new Error(`Failed to find unused directories: ${error.message || String(error)}`);
+  }
+}
+
+// .lnk shortcuts, Finder aliases and .url/.webloc links under path, with broken ones reported apart
+export async function findShortcuts(
+  path: string | string[],
+  includeHidden?: boolean,
+  followSymlinks?: boolean,
+): Promise<ShortcutReport> {
+  try {
+    return await invoke<ShortcutReport>('find_shortcuts', { path, includeHidden, followSymlinks });
+  } catch (error: any) {
+    throw new Error(`Failed to find shortcuts: ${error.message || String(error)}`);
+  }
+}
+
+// Spotlight's last-used date, download origin, kind and authors; null off macOS
+export async function getSpotlightMetadata(path: string): Promise<SpotlightMetadata | null> {
+  try {
+    return await invoke<SpotlightMetadata | null>('get_spotlight_metadata', { path });
+  } catch (error: any) {
+    throw new Error(`Failed to read Spotlight metadata: ${error.message || String(error)}`);
+  }
+}
+
+// Source URL and referrer of a downloaded file; null when none was recorded
+export async function getFileProvenance(path: string): Promise<Provenance | null> {
+  try {
+    return await invoke<Provenance | null>('get_file_provenance', { path });
+  } catch (error: any) {
+    throw new Error(`Failed to read file provenance: ${error.message || String(error)}`);
+  }
+}
+
+// Directories with identical content trees across roots; minSimilarity (percent) also reports near-copies
+// Files in a directory, each marked as a cloud placeholder or not
+export async function readDirectoryEntries(
+  path: string,
+  includeSubdirectories: boolean,
+  includeHidden?: boolean,
+): Promise<DirectoryEntry[]> {
+  try {
+    return await invoke<DirectoryEntry[]>('read_directory_entries', { path, includeSubdirectories, includeHidden });
+  } catch (error: any) {
+    throw new Error(`Failed to read directory: ${error.message || String(error)}`);
+  }
+}
+
+export interface DirectoryStreamHandle {
+  streamId: string;
+  cancel: () => Promise<boolean>;
+  done: Promise<DirectoryStreamComplete>; // rejects with the error message when the directory can't be listed
+}
+
+// read_directory_entries for directories too big for one reply: files arrive through
+// onEntries a chunk at a time instead of all at once
+export async function streamDirectory(
+  path: string,
+  includeSubdirectories: boolean,
+  onEntries: (entries: DirectoryEntry[]) => void,
+  options: DirectoryStreamOptions = {},
+  includeHidden?: boolean,
+): Promise<DirectoryStreamHandle> {
+  let resolveDone!: (value: DirectoryStreamComplete) => void;
+  let rejectDone!: (reason: Error) => void;
+  const done = new Promise<DirectoryStreamComplete>((resolve, reject) => {
+    resolveDone = resolve;
+    rejectDone = reject;
+  });
+
+  // The backend picks the stream id, so events that arrive before it's known are held
+  // back and replayed once it is
+  let streamId: string | null = null;
+  let early: (
+    | { kind: 'entries'; payload: DirectoryEntriesEvent }
+    | { kind: 'complete'; payload: DirectoryStreamComplete }
+  )[] = [];
+  const handleEntries = (payload: DirectoryEntriesEvent) => {
+    if (payload.stream_id === streamId) {
+      onEntries(payload.entries);
     }
-    const data = JSON.parse(resp.data);
-    // LM Studio returns { data: [...] } where each model has an `id` property
-    if (data && Array.isArray(data.data)) {
-      return data.data.map((m: any) => m.id || String(m));
+  };
+  const handleComplete = (payload: DirectoryStreamComplete) => {
+    if (payload.stream_id !== streamId) {
+      return;
     }
-    return [];
-  } catch (e) {
-    debugLogger.warn('API', 'Failed to list LM Studio models', { error: e });
-    return [];
+    if (payload.error) {
+      rejectDone(new Error(payload.error));
+    } else {
+      resolveDone(payload);
+    }
+  };
+  const unlisteners = await Promise.all([
+    appWindow.listen<DirectoryEntriesEvent>('directory-entries', (event) => {
+      if (streamId === null) {
+        early.push({ kind: 'entries', payload: event.payload });
+      } else {
+        handleEntries(event.payload);
+      }
+    }),
+    appWindow.listen<DirectoryStreamComplete>('directory-stream-complete', (event) => {
+      if (streamId === null) {
+        early.push({ kind: 'complete', payload: event.payload });
+      } else {
+        handleComplete(event.payload);
+      }
+    }),
+  ]);
+  const cleanup = () => unlisteners.forEach((unlisten) => unlisten());
+  done.then(cleanup, cleanup);
+
+  try {
+    streamId = await invoke<string>('start_directory_stream', { path, includeSubdirectories, includeHidden, options });
+  } catch (error: any) {
+    cleanup();
+    throw new Error(`Failed to read directory: ${error.message || String(error)}`);
   }
+  early.forEach((event) => (event.kind === 'entries' ? handleEntries(event.payload) : handleComplete(event.payload)));
+  early = [];
+
+  const id = streamId;
+  return {
+    streamId: id,
+    cancel: async () => {
+      try {
+        return await invoke<boolean>('cancel_directory_stream', { streamId: id });
+      } catch (error: any) {
+        throw new Error(`Failed to cancel directory stream: ${error.message || String(error)}`);
+      }
+    },
+    done,
+  };
 }
 
-// Managed LLM Server API functions
+export async function findDuplicateDirectories(
+  roots: string | string[],
+  minSimilarity?: number,
+  includeHidden?: boolean,
+  hydrate?: boolean, // hash cloud placeholders too, downloading them
+  ioPriority?: IoPriority,
+  checkpointEvery?: number, // save progress every this many files so the search can be resumed
+  followSymlinks?: boolean, // off by default, so a link and its target aren't reported as copies
+): Promise<DuplicateDirectoryReport> {
+  try {
+    return await invoke<DuplicateDirectoryReport>('find_duplicate_directories', {
+      roots,
+      minSimilarity,
+      includeHidden,
+      hydrate,
+      ioPriority,
+      checkpointEvery,
+      followSymlinks,
+    });
+  } catch (error: any) {
+    throw new Error(`Failed to find duplicate directories: ${error.message || String(error)}`);
+  }
+}
 
-export async function getManagedLLMServerStatus(): Promise<ManagedLLMServerInfo> {
+// Continues an interrupted analysis; only files changed since the checkpoint are read again
+export async function resumeAnalysis(
+  checkpointId: string,
+  options?: AnalysisOptions,
+  ioPriority?: IoPriority,
+  checkpointEvery?: number,
+): Promise<DuplicateDirectoryReport> {
   try {
-    return await invoke<ManagedLLMServerInfo>('get_llm_server_status');
+    return await invoke<DuplicateDirectoryReport>('resume_analysis', {
+      checkpointId,
+      options,
+      ioPriority,
+      checkpointEvery,
+    });
   } catch (error: any) {
-    throw new Error(`Failed to get server status: ${error.message || String(error)}`);
+    throw new Error(`Failed to resume analysis: ${error.message || String(error)}`);
   }
 }
 
-export async function downloadManagedLLMServer(
-  version: string,
-  onProgress?: (percent: number) => void
-): Promise<string> {
+export async function listCheckpoints(): Promise<CheckpointSummary[]> {
   try {
-    // Call the download command - progress tracking would need to be implemented with events
-    // For now, we'll simulate progress in the UI and call the actual download
-    const result = await invoke<string>('download_llm_server', { version });
-    
-    // Ensure progress reaches 100% when download completes
-    if (onProgress) {
-      onProgress(100);
-    }
-    
-    return result;
+    return await invoke<CheckpointSummary[]>('list_checkpoints');
   } catch (error: any) {
-    throw new Error(`Failed to download server: ${error.message || String(error)}`);
+    throw new Error(`Failed to list checkpoints: ${error.message || String(error)}`);
   }
 }
 
-export async function startManagedLLMServer(config: ManagedLLMConfig): Promise<string> {
+// Resolves to false when there was no such checkpoint
+export async function deleteCheckpoint(checkpointId: string): Promise<boolean> {
   try {
-    return await invoke<string>('start_llm_server', { config });
+    return await invoke<boolean>('delete_checkpoint', { checkpointId });
   } catch (error: any) {
-    throw new Error(`Failed to start server: ${error.message || String(error)}`);
+    throw new Error(`Failed to delete checkpoint: ${error.message || String(error)}`);
   }
 }
 
-export async function stopManagedLLMServer(): Promise<string> {
+// Mounted drives and volumes; listen for volume-mounted / volume-unmounted to hear about changes
+export async function listVolumes(): Promise<Volume[]> {
   try {
-    return await invoke<string>('stop_llm_server');
+    return await invoke<Volume[]>('list_volumes');
   } catch (error: any) {
-    throw new Error(`Failed to stop server: ${error.message || String(error)}`);
+    throw new Error(`Failed to list volumes: ${error.message || String(error)}`);
   }
 }
 
-export async function getManagedLLMServerInfo(): Promise<ManagedLLMServerInfo> {
+// Read rate for low-priority scans in MB/s (20 by default); 0 removes the limit, even mid-scan
+export async function setScanThrottle(mbPerSecond: number): Promise<void> {
   try {
-    return await invoke<ManagedLLMServerInfo>('get_llm_server_info');
+    await invoke('set_scan_throttle', { mbPerSecond });
   } catch (error: any) {
-    throw new Error(`Failed to get server info: ${error.message || String(error)}`);
+    throw new Error(`Failed to set scan throttle: ${error.message || String(error)}`);
+  }
+}
+
+export async function getScanThrottle(): Promise<number> {
+  try {
+    return await invoke<number>('get_scan_throttle');
+  } catch (error: any) {
+    throw new Error(`Failed to get scan throttle: ${error.message || String(error)}`);
+  }
+}
+
+// Year/month folder plan by EXIF capture date or modification time, for use without an LLM.
+// Execute it with the move_file command for each entry.
+export async function planDateOrganization(
+  path: string,
+  includeSubdirectories: boolean,
+  granularity: DateGranularity = 'year_month',
+  useExif = true,
+  includeHidden?: boolean,
+): Promise<DatePlan> {
+  try {
+    return await invoke<DatePlan>('plan_date_organization', { path, includeSubdirectories, granularity, useExif, includeHidden });
+  } catch (error: any) {
+    throw new Error(`Failed to plan date organization: ${error.message || String(error)}`);
+  }
+}
+
+// With dryRun every check runs and the outcomes come back marked simulated, but nothing is renamed
+export async function renameFile(
+  path: string,
+  newName: string,
+  onConflict: RenameConflictMode = 'error',
+  dryRun = false,
+  batchId?: string, // journals the rename with the batch's moves
+): Promise<RenameOutcome> {
+  try {
+    return await invoke<RenameOutcome>('rename_file', { path, newName, onConflict, dryRun, batchId });
+  } catch (error: any) {
+    throw new Error(`Failed to rename file: ${error.message || String(error)}`);
+  }
+}
+
+export async function renameFiles(
+  plan: RenamePlanItem[],
+  onConflict: RenameConflictMode = 'error',
+  dryRun = false,
+  operationId?: string,
+  batchId?: string, // journals each rename with the batch's moves
+): Promise<RenameBatchReport> {
+  try {
+    return await invoke<RenameBatchReport>('rename_files', { plan, onConflict, dryRun, operationId, batchId });
+  } catch (error: any) {
+    throw new Error(`Failed to rename files: ${error.message || String(error)}`);
+  }
+}
+
+// Compact nested overview of a directory for folder-structure prompts, bounded to maxChars when serialized
+export async function summarizeDirectoryTree(
+  path: string,
+  options: { maxDepth?: number; maxEntriesPerDir?: number; maxChars?: number; includeHidden?: boolean; ignoredNames?: string[] } = {},
+): Promise<TreeSummary> {
+  try {
+    return await invoke<TreeSummary>('summarize_directory_tree', { path, ...options });
+  } catch (error: any) {
+    throw new Error(`Failed to summarize directory: ${error.message || String(error)}`);
+  }
+}
+
+// Existing folders under searchRoots that fit each category, matched by folder name
+export async function suggestDestinations(
+  categories: string[],
+  searchRoots: string[],
+  options: { maxDepth?: number; minScore?: number; includeHidden?: boolean; ignoredNames?: string[] } = {},
+): Promise<DestinationSuggestions> {
+  try {
+    return await invoke<DestinationSuggestions>('suggest_destinations', { categories, searchRoots, ...options });
+  } catch (error: any) {
+    throw new Error(`Failed to suggest destinations: ${error.message || String(error)}`);
+  }
+}
+
+// Content of one entry inside a .zip or .tar.gz, in the same shape as read_file_content's result.
+// Rejects with kind 'Encrypted' for password-protected entries and 'Unsupported' for entries over 100 MB.
+export async function readArchivedFileContent(archivePath: string, innerPath: string): Promise<FileContent> {
+  try {
+    const json = await invoke<string>('read_archived_file_content', { archivePath, innerPath });
+    return JSON.parse(json) as FileContent;
+  } catch (error: any) {
+    throw new Error(`Failed to read ${innerPath} from archive: ${error.message || String(error)}`);
+  }
+}
+
+// Preview for images (and MP4/MOV cover art); rejects with Unsupported for other files
+export async function generateThumbnail(path: string, maxPx?: number): Promise<Thumbnail> {
+  try {
+    return await invoke<Thumbnail>('generate_thumbnail', { path, maxPx });
+  } catch (error: any) {
+    throw new Error(`Failed to generate thumbnail: ${error.message || String(error)}`);
+  }
+}
+
+export async function clearThumbnailCache(): Promise<void> {
+  try {
+    await invoke('clear_thumbnail_cache');
+  } catch (error: any) {
+    throw new Error(`Failed to clear thumbnail cache: ${error.message || String(error)}`);
+  }
+}
+
+export type LogLevel = 'error' | 'warn' | 'info' | 'debug' | 'trace';
+
+// Most recent lines of the application log file, oldest first. levelFilter keeps that level and more severe ones.
+export async function getAppLogs(lines = 500, levelFilter?: LogLevel): Promise<string[]> {
+  try {
+    return await invoke<string[]>('get_app_logs', { lines, levelFilter });
+  } catch (error: any) {
+    throw new Error(`Failed to load application logs: ${error.message || String(error)}`);
+  }
+}
+
+// Applies until the app restarts
+export async function setLogLevel(level: LogLevel | 'off'): Promise<void> {
+  try {
+    await invoke('set_log_level', { level });
+  } catch (error: any) {
+    throw new Error(`Failed to set log level: ${error.message || String(error)}`);
+  }
+}
+
+// Verify a directory can be listed and written to before scanning it
+export async function checkDirectoryAccess(path: string): Promise<DirectoryAccessReport> {
+  try {
+    return await invoke<DirectoryAccessReport>('check_directory_access', { path });
+  } catch (error: any) {
+    throw new Error(`Failed to check directory access: ${error.message || String(error)}`);
+  }
+}
+
+// Helpers to list available local models for Ollama and LM Studio
+export async function listOllamaModels(baseUrl: string): Promise<string[]> {
+  const url = baseUrl.replace(/\/$/, '') + '/api/tags';
+  try {
+    const resp = await tauriFetch(url, { method: 'GET', headers: {} });
+    if (!resp.ok) {
+      throw new Error(`Ollama error ${resp.status}: ${resp.data}`);
+    }
+    const data = JSON.parse(resp.data);
+    // Ollama returns { models: [...] } where each model has a `name` property
+    if (data && Array.isArray(data.models)) {
+      return data.models.map((m: any) => m.name || String(m));
+    }
+    return [];
+  } catch (e) {
+    debugLogger.warn('API', 'Failed to list Ollama models', { error: e });
+    return [];
+  }
+}
+
+export async function listLMStudioModels(baseUrl: string): Promise<string[]> {
+  const url = baseUrl.replace(/\/$/, '') + '/v1/models';
+  try {
+    const resp = await tauriFetch(url, { method: 'GET', headers: {} });
+    if (!resp.ok) {
+      throw new Error(`LM Studio error ${resp.status}: ${resp.data}`);
+    }
+    const data = JSON.parse(resp.data);
+    // LM Studio returns { data: [...] } where each model has an `id` property
+    if (data && Array.isArray(data.data)) {
+      return data.data.map((m: any) => m.id || String(m));
+    }
+    return [];
+  } catch (e) {
+    debugLogger.warn('API', 'Failed to list LM Studio models', { error: e });
+    return [];
+  }
+}
+
+// Managed LLM Server API functions
+
+export async function getManagedLLMServerStatus(): Promise<ManagedLLMServerInfo> {
+  try {
+    return await invoke<ManagedLLMServerInfo>('get_llm_server_status');
+  } catch (error: any) {
+    throw new Error(`Failed to get server status: ${error.message || String(error)}`);
+  }
+}
+
+export async function downloadManagedLLMServer(
+  version: string,
+  onProgress?: (percent: number) => void,
+  notifyOnComplete?: boolean
+): Promise<string> {
+  try {
+    // Call the download command - progress tracking would need to be implemented with events
+    // For now, we'll simulate progress in the UI and call the actual download
+    const result = await invoke<string>('download_llm_server', { version, notifyOnComplete });
+    
+    // Ensure progress reaches 100% when download completes
+    if (onProgress) {
+      onProgress(100);
+    }
+    
+    return result;
+  } catch (error: any) {
+    throw new Error(`Failed to download server: ${error.message || String(error)}`);
+  }
+}
+
+// Without a config the server starts with the one saved by the last start
+export async function startManagedLLMServer(config?: ManagedLLMConfig): Promise<string> {
+  try {
+    return await invoke<string>('start_llm_server', { config });
+  } catch (error: any) {
+    throw new Error(`Failed to start server: ${error.message || String(error)}`);
+  }
+}
+
+export async function stopManagedLLMServer(): Promise<string> {
+  try {
+    return await invoke<string>('stop_llm_server');
+  } catch (error: any) {
+    throw new Error(`Failed to stop server: ${error.message || String(error)}`);
+  }
+}
+
+export async function getManagedLLMServerInfo(): Promise<ManagedLLMServerInfo> {
+  try {
+    return await invoke<ManagedLLMServerInfo>('get_llm_server_info');
+  } catch (error: any) {
+    throw new Error(`Failed to get server info: ${error.message || String(error)}`);
+  }
+}
+
+export async function listManagedServerModels(): Promise<ManagedServerModel[]> {
+  try {
+    return await invoke<ManagedServerModel[]>('list_managed_server_models');
+  } catch (error: any) {
+    throw new Error(`Failed to list server models: ${error.message || String(error)}`);
+  }
+}
+
+// Maps the frontend provider config onto a backend selection for the Rust router
+export function toBackendConfig(config: LLMConfig): LLMBackendConfig {
+  if (config.provider === 'managed-local') {
+    return { kind: 'managed', model: config.model || undefined };
+  }
+  return {
+    kind: 'remote',
+    provider: config.provider,
+    base_url: config.baseUrl,
+    api_key: config.apiKey,
+    model: config.model,
+    headers: config.customHeaders,
+    supports_vision: config.supportsVision,
+  };
+}
+
+export async function selectLLMBackend(config: LLMBackendConfig): Promise<void> {
+  try {
+    await invoke('select_llm_backend', { config });
+  } catch (error: any) {
+    throw new Error(`Failed to select LLM backend: ${error.message || String(error)}`);
+  }
+}
+
+export async function getActiveLLMBackend(): Promise<ActiveLLMBackend | null> {
+  try {
+    return await invoke<ActiveLLMBackend | null>('get_active_backend');
+  } catch (error: any) {
+    throw new Error(`Failed to get active LLM backend: ${error.message || String(error)}`);
+  }
+}
+
+export async function llmClassify(request: LLMClassifyRequest): Promise<LLMClassifyResponse> {
+  try {
+    return await invoke<LLMClassifyResponse>('llm_classify', { request });
+  } catch (error: any) {
+    throw new Error(`LLM classification failed: ${error.message || String(error)}`);
+  }
+}
+
+// Latency percentiles, token counts and generation speed of LLM requests, plus the
+// last `last` requests (20 by default)
+export async function getLLMMetrics(last?: number): Promise<LLMMetrics> {
+  try {
+    return await invoke<LLMMetrics>('get_llm_metrics', { last });
+  } catch (error: any) {
+    throw new Error(`Failed to get LLM metrics: ${error.message || String(error)}`);
+  }
+}
+
+export async function resetLLMMetrics(): Promise<void> {
+  try {
+    await invoke('reset_llm_metrics');
+  } catch (error: any) {
+    throw new Error(`Failed to reset LLM metrics: ${error.message || String(error)}`);
+  }
+}
+
+// GGUF repos on the Hugging Face Hub matching query
+export async function searchHFModels(query: string, filters?: HfSearchFilters): Promise<HfModel[]> {
+  try {
+    return await invoke<HfModel[]>('search_hf_models', { query, filters });
+  } catch (error: any) {
+    throw new Error(`Failed to search Hugging Face models: ${error.message || String(error)}`);
+  }
+}
+
+export async function listHFModelFiles(repoId: string): Promise<HfModelFile[]> {
+  try {
+    return await invoke<HfModelFile[]>('list_hf_model_files', { repoId });
+  } catch (error: any) {
+    throw new Error(`Failed to list model files: ${error.message || String(error)}`);
+  }
+}
+
+// Resumable and checksum-verified; progress arrives as model-download-progress events
+export async function downloadHFModel(repoId: string, filename: string): Promise<DownloadedModel> {
+  try {
+    return await invoke<DownloadedModel>('download_hf_model', { repoId, filename });
+  } catch (error: any) {
+    throw new Error(`Failed to download model: ${error.message || String(error)}`);
+  }
+}
+
+// Catalog models ranked for this machine; useCase is 'text' (default) or 'vision'
+export async function recommendModels(useCase?: string): Promise<ModelRecommendations> {
+  try {
+    return await invoke<ModelRecommendations>('recommend_models', { useCase });
+  } catch (error: any) {
+    throw new Error(`Failed to recommend models: ${error.message || String(error)}`);
+  }
+}
+
+export async function getLLMFallbackSettings(): Promise<LLMFallbackSettings> {
+  try {
+    return await invoke<LLMFallbackSettings>('get_llm_fallback_settings');
+  } catch (error: any) {
+    throw new Error(`Failed to get LLM fallback settings: ${error.message || String(error)}`);
+  }
+}
+
+export async function setLLMFallbackSettings(settings: LLMFallbackSettings): Promise<void> {
+  try {
+    await invoke('set_llm_fallback_settings', { settings });
+  } catch (error: any) {
+    throw new Error(`Failed to save LLM fallback settings: ${error.message || String(error)}`);
+  }
+}
+
+// Network (proxy / CA bundle) settings, applied to every backend HTTP request
+export async function getNetworkConfig(): Promise<NetworkConfig> {
+  try {
+    return await invoke<NetworkConfig>('get_network_config');
+  } catch (error: any) {
+    throw new Error(`Failed to get network config: ${error.message || String(error)}`);
+  }
+}
+
+export async function setNetworkConfig(config: NetworkConfig): Promise<void> {
+  try {
+    await invoke('set_network_config', { config });
+  } catch (error: any) {
+    throw new Error(`Failed to save network config: ${error.message || String(error)}`);
+  }
+}
+
+export async function getAppInfo(): Promise<AppInfo> {
+  try {
+    return await invoke<AppInfo>('get_app_info');
+  } catch (error: any) {
+    throw new Error(`Failed to get app info: ${error.message || String(error)}`);
+  }
+}
+
+// Deadline for extracting one file's content (30 s by default); slower files reject with kind 'Timeout'
+export async function getExtractionTimeout(): Promise<number> {
+  try {
+    return await invoke<number>('get_extraction_timeout');
+  } catch (error: any) {
+    throw new Error(`Failed to get extraction timeout: ${error.message || String(error)}`);
+  }
+}
+
+export async function setExtractionTimeout(timeoutMs: number): Promise<void> {
+  try {
+    await invoke('set_extraction_timeout', { timeoutMs });
+  } catch (error: any) {
+    throw new Error(`Failed to set extraction timeout: ${error.message || String(error)}`);
+  }
+}
+
+// Runs a converter on a sample file, enabled or not, so its output can be checked
+export async function testExternalExtractor(
+  handler: ExternalExtractor,
+  samplePath: string
+): Promise<ExtractorOutput> {
+  try {
+    return await invoke<ExtractorOutput>('test_external_extractor', { handler, samplePath });
+  } catch (error: any) {
+    throw new Error(`Failed to test external extractor: ${error.message || String(error)}`);
+  }
+}
+
+// How many file contents are extracted at once (one per CPU core by default); further reads queue
+export async function getExtractionConcurrency(): Promise<number> {
+  try {
+    return await invoke<number>('get_extraction_concurrency');
+  } catch (error: any) {
+    throw new Error(`Failed to get extraction concurrency: ${error.message || String(error)}`);
+  }
+}
+
+export async function setExtractionConcurrency(permits: number): Promise<void> {
+  try {
+    await invoke('set_extraction_concurrency', { permits });
+  } catch (error: any) {
+    throw new Error(`Failed to set extraction concurrency: ${error.message || String(error)}`);
+  }
+}
+
+// Identifies a batch move or rename, scan or set of extractions so it can be cancelled
+export function newOperationId(kind: string): string {
+  return `${kind}-${Date.now()}-${Math.random().toString(36).slice(2, 10)}`;
+}
+
+// Returns false if the operation had already finished
+export async function cancelOperation(operationId: string): Promise<boolean> {
+  try {
+    return await invoke<boolean>('cancel_operation', { operationId });
+  } catch (error: any) {
+    throw new Error(`Failed to cancel operation: ${error.message || String(error)}`);
+  }
+}
+
+// Called when a loop of per-file commands sharing an operation id is done
+export async function finishOperation(operationId: string): Promise<void> {
+  try {
+    await invoke('finish_operation', { operationId });
+  } catch (error: any) {
+    throw new Error(`Failed to finish operation: ${error.message || String(error)}`);
+  }
+}
+
+// Files to classify first to judge a model before running it on the whole directory
+export async function sampleDirectoryForClassification(
+  path: string,
+  sampleSize: number,
+  options: SampleOptions = {},
+): Promise<ClassificationSample> {
+  try {
+    return await invoke<ClassificationSample>('sample_directory_for_classification', { path, sampleSize, ...options });
+  } catch (error: any) {
+    throw new Error(`Failed to sample directory: ${error.message || String(error)}`);
+  }
+}
+
+// With reportPath every row is also written there as CSV; labels name the backends in its header
+export async function compareClassifications(
+  rows: ComparedFile[],
+  reportPath?: string,
+  labelA?: string,
+  labelB?: string,
+): Promise<ComparisonSummary> {
+  try {
+    return await invoke<ComparisonSummary>('compare_classifications', { rows, reportPath, labelA, labelB });
+  } catch (error: any) {
+    throw new Error(`Failed to compare classifications: ${error.message || String(error)}`);
+  }
+}
+
+// Only files changed since the last run are extracted again
+export async function indexDirectory(path: string, options?: IndexOptions, operationId?: string): Promise<IndexReport> {
+  try {
+    return await invoke<IndexReport>('index_directory', { path, options, operationId });
+  } catch (error: any) {
+    throw new Error(`Failed to index directory: ${error.message || String(error)}`);
+  }
+}
+
+// Every word must appear; a trailing * matches prefixes
+export async function searchIndexedContent(query: string, pathFilter?: string, limit?: number): Promise<SearchMatch[]> {
+  try {
+    return await invoke<SearchMatch[]>('search_indexed_content', { query, pathFilter, limit });
+  } catch (error: any) {
+    throw new Error(`Failed to search indexed content: ${error.message || String(error)}`);
+  }
+}
+
+// Resolves to the number of documents removed
+export async function deleteIndex(path: string): Promise<number> {
+  try {
+    return await invoke<number>('delete_index', { path });
+  } catch (error: any) {
+    throw new Error(`Failed to delete index: ${error.message || String(error)}`);
+  }
+}
+
+// Stores tags with the file itself; an empty list removes them. Resolves to null then.
+export async function setFileTags(path: string, tags: string[]): Promise<TagStorage | null> {
+  try {
+    return await invoke<TagStorage | null>('set_file_tags', { path, tags });
+  } catch (error: any) {
+    throw new Error(`Failed to save tags: ${error.message || String(error)}`);
+  }
+}
+
+export async function getFileTags(path: string): Promise<string[]> {
+  try {
+    return await invoke<string[]>('get_file_tags', { path });
+  } catch (error: any) {
+    throw new Error(`Failed to read tags: ${error.message || String(error)}`);
+  }
+}
+
+// Tagged files in a directory, for showing what was organized before
+export async function getDirectoryTags(path: string, recursive = false): Promise<FileTags[]> {
+  try {
+    return await invoke<FileTags[]>('get_directory_tags', { path, recursive });
+  } catch (error: any) {
+    throw new Error(`Failed to read tags: ${error.message || String(error)}`);
+  }
+}
+
+// Checks the moves journaled under batchId (the batchId given to move_file)
+export async function verifyOrganization(batchId: string): Promise<VerificationReport> {
+  try {
+    return await invoke<VerificationReport>('verify_organization', { batchId });
+  } catch (error: any) {
+    throw new Error(`Failed to verify organization: ${error.message || String(error)}`);
+  }
+}
+
+// Bundles files into a new .tar.gz, stored relative to baseRoot; with removeOriginals they're deleted
+// once the archive has been read back. Stops without deleting anything if a file changed since the scan.
+export async function archiveFiles(
+  paths: string[],
+  baseRoot: string,
+  archivePath: string,
+  removeOriginals: boolean,
+  batchId?: string,
+): Promise<ArchiveFilesResult> {
+  try {
+    return await invoke<ArchiveFilesResult>('archive_files', {
+      paths,
+      baseRoot,
+      archivePath,
+      removeOriginals,
+      batchId,
+    });
+  } catch (error: any) {
+    throw new Error(`Failed to archive files: ${error.message || String(error)}`);
+  }
+}
+
+// Restores an archive made by archiveFiles under restoreRoot without overwriting anything
+export async function unarchiveFiles(archivePath: string, restoreRoot: string): Promise<RestoreReport> {
+  try {
+    return await invoke<RestoreReport>('unarchive_files', { archivePath, restoreRoot });
+  } catch (error: any) {
+    throw new Error(`Failed to restore archive: ${error.message || String(error)}`);
+  }
+}
+
+// Moves files into <root>/_Organizer Staging/<batchId>/ for review; proposedDestinations
+// pairs with paths. The moves are journaled under batchId.
+export async function stageFiles(
+  batchId: string,
+  root: string,
+  paths: string[],
+  proposedDestinations: string[],
+): Promise<StagingReport> {
+  try {
+    return await invoke<StagingReport>('stage_files', { batchId, root, paths, proposedDestinations });
+  } catch (error: any) {
+    throw new Error(`Failed to stage files: ${error.message || String(error)}`);
+  }
+}
+
+// approvals maps original paths to true (move to the proposed destination) or false
+// (restore); files left out stay staged
+export async function commitStaged(batchId: string, approvals: Record<string, boolean>): Promise<StagingReport> {
+  try {
+    return await invoke<StagingReport>('commit_staged', { batchId, approvals });
+  } catch (error: any) {
+    throw new Error(`Failed to commit staged files: ${error.message || String(error)}`);
+  }
+}
+
+export async function listStagedBatches(): Promise<StagedBatchSummary[]> {
+  try {
+    return await invoke<StagedBatchSummary[]>('list_staged_batches');
+  } catch (error: any) {
+    throw new Error(`Failed to list staged batches: ${error.message || String(error)}`);
+  }
+}
+
+export async function getStagedBatch(batchId: string): Promise<StagedBatch> {
+  try {
+    return await invoke<StagedBatch>('get_staged_batch', { batchId });
+  } catch (error: any) {
+    throw new Error(`Failed to load staged batch: ${error.message || String(error)}`);
+  }
+}
+
+// Moves a batch's files back where they were, newest first, including duplicate sources
+// that were moved to the batch's trash folder
+export async function undoBatch(batchId: string): Promise<UndoReport> {
+  try {
+    return await invoke<UndoReport>('undo_batch', { batchId });
+  } catch (error: any) {
+    throw new Error(`Failed to undo batch: ${error.message || String(error)}`);
+  }
+}
+
+// Moves files in plan order, journaled under batchId. The plan is saved first, so a run cut
+// short by a crash is listed by getInterruptedRuns; progress arrives as run-progress events.
+export async function executePlan(
+  moves: PlannedMove[],
+  batchId?: string,
+  onConflict: RenameConflictMode = 'error',
+  captureHash = false,
+  operationId?: string,
+): Promise<RunReport> {
+  try {
+    return await invoke<RunReport>('execute_plan', { moves, batchId, onConflict, captureHash, operationId });
+  } catch (error: any) {
+    throw new Error(`Failed to execute plan: ${error.message || String(error)}`);
+  }
+}
+
+// Extracts, classifies and moves `paths` in the backend with bounded concurrency per stage.
+// Stages arrive as pipeline-file events and progress as pipeline-progress; with dry_run
+// nothing is moved and the planned destinations can go to executePlan once approved.
+export async function runClassificationPipeline(
+  paths: string[],
+  options: PipelineOptions = {},
+): Promise<PipelineReport> {
+  try {
+    return await invoke<PipelineReport>('run_classification_pipeline', { paths, options });
+  } catch (error: any) {
+    throw new Error(`Classification pipeline failed: ${error.message || String(error)}`);
+  }
+}
+
+export async function getInterruptedRuns(): Promise<InterruptedRun[]> {
+  try {
+    return await invoke<InterruptedRun[]>('get_interrupted_runs');
+  } catch (error: any) {
+    throw new Error(`Failed to list interrupted runs: ${error.message || String(error)}`);
+  }
+}
+
+// Continues an interrupted run; remaining moves are checked again before they're made
+export async function resumeRun(runId: string, operationId?: string): Promise<RunReport> {
+  try {
+    return await invoke<RunReport>('resume_run', { runId, operationId });
+  } catch (error: any) {
+    throw new Error(`Failed to resume run: ${error.message || String(error)}`);
+  }
+}
+
+export async function discardRun(runId: string): Promise<boolean> {
+  try {
+    return await invoke<boolean>('discard_run', { runId });
+  } catch (error: any) {
+    throw new Error(`Failed to discard run: ${error.message || String(error)}`);
+  }
+}
+
+// Accepts tags like "de-AT"; resolves to the supported locale now in use
+export async function setLocale(locale: string): Promise<string> {
+  try {
+    return await invoke<string>('set_locale', { locale });
+  } catch (error: any) {
+    throw new Error(`Failed to set locale: ${error.message || String(error)}`);
+  }
+}
+
+export async function getLocale(): Promise<string> {
+  try {
+    return await invoke<string>('get_locale');
+  } catch (error: any) {
+    throw new Error(`Failed to get locale: ${error.message || String(error)}`);
+  }
+}
+
+export async function getSettings(): Promise<Settings> {
+  try {
+    return await invoke<Settings>('get_settings');
+  } catch (error: any) {
+    throw new Error(`Failed to get settings: ${error.message || String(error)}`);
+  }
+}
+
+export async function setSettings(settings: Settings): Promise<Settings> {
+  try {
+    return await invoke<Settings>('set_settings', { settings });
+  } catch (error: any) {
+    throw new Error(`Failed to save settings: ${error.message || String(error)}`);
+  }
+}
+
+// JSON merge patch: nested objects merge and a null value resets that field
+export async function patchSettings(patch: Record<string, any>): Promise<Settings> {
+  try {
+    return await invoke<Settings>('patch_settings', { patch });
+  } catch (error: any) {
+    throw new Error(`Failed to update settings: ${error.message || String(error)}`);
+  }
+}
+
+// API keys are left out of exported files
+export async function exportSettings(path: string): Promise<void> {
+  try {
+    await invoke('export_settings', { path });
+  } catch (error: any) {
+    throw new Error(`Failed to export settings: ${error.message || String(error)}`);
+  }
+}
+
+export async function importSettings(path: string): Promise<Settings> {
+  try {
+    return await invoke<Settings>('import_settings', { path });
+  } catch (error: any) {
+    throw new Error(`Failed to import settings: ${error.message || String(error)}`);
+  }
+}
+
+export async function getDestinationPolicy(): Promise<DestinationPolicy> {
+  try {
+    return await invoke<DestinationPolicy>('get_destination_policy');
+  } catch (error: any) {
+    throw new Error(`Failed to get destination policy: ${error.message || String(error)}`);
+  }
+}
+
+export async function setDestinationPolicy(policy: DestinationPolicy): Promise<void> {
+  try {
+    await invoke('set_destination_policy', { policy });
+  } catch (error: any) {
+    throw new Error(`Failed to save destination policy: ${error.message || String(error)}`);
+  }
+}
+
+// Registers the directories being organized as the default move destinations
+export async function setOrganizationRoots(roots: string[]): Promise<void> {
+  try {
+    await invoke('set_organization_roots', { roots });
+  } catch (error: any) {
+    throw new Error(`Failed to set organization roots: ${error.message || String(error)}`);
+  }
+}
+
+// Sends a HEAD to url using config (or the saved settings) without saving anything
+export async function testNetworkConfig(url: string, config?: NetworkConfig): Promise<NetworkTestResult> {
+  try {
+    return await invoke<NetworkTestResult>('test_network_config', { url, config });
+  } catch (error: any) {
+    throw new Error(`Failed to test network config: ${error.message || String(error)}`);
   }
 }
 
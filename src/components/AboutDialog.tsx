@@ -1,7 +1,7 @@
 import React, { useEffect, useState } from 'react';
-import { invoke } from '@tauri-apps/api';
 import ManagedLLMDialog from './ManagedLLMDialog';
-import { ManagedLLMConfig } from '../types';
+import { AppInfo, ManagedLLMConfig } from '../types';
+import { getAppInfo } from '../api';
 
 interface AboutDialogProps {
   isOpen: boolean;
@@ -9,22 +9,17 @@ interface AboutDialogProps {
   managedLLMConfig?: ManagedLLMConfig;
 }
 
-interface AppVersionInfo {
-  version: string;
-  build_timestamp: string;
-}
-
 export default function AboutDialog({
   isOpen,
   onClose,
   managedLLMConfig
 }: AboutDialogProps) {
-  const [versionInfo, setVersionInfo] = useState<AppVersionInfo | null>(null);
+  const [versionInfo, setVersionInfo] = useState<AppInfo | null>(null);
   const [showDownloadDialog, setShowDownloadDialog] = useState(false);
 
   useEffect(() => {
     if (isOpen) {
-      invoke<AppVersionInfo>('get_app_version')
+      getAppInfo()
         .then(setVersionInfo)
         .catch(err => console.error('Failed to fetch version:', err));
     }
@@ -51,6 +46,11 @@ export default function AboutDialog({
                   <span className="build-timestamp"> ({versionInfo.build_timestamp})</span>
                 )}
               </p>
+              {versionInfo && (
+                <p className="build-timestamp">
+                  Commit {versionInfo.git_commit} · {versionInfo.os} {versionInfo.arch} · Tauri {versionInfo.tauri_version}
+                </p>
+              )}
               <p className="description">
                 An intelligent file organization assistant powered by AI.
                 Automatically categorize and rename your files using advanced language models,
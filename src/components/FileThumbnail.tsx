@@ -0,0 +1,40 @@
+import React, { useEffect, useState } from 'react';
+import { generateThumbnail } from '../api';
+import { Thumbnail } from '../types';
+
+// Extensions generate_thumbnail can render; other files show no preview
+const PREVIEWABLE_EXTENSIONS = new Set(['png', 'jpg', 'jpeg', 'gif', 'bmp', 'webp', 'tif', 'tiff', 'ico', 'mp4', 'm4v', 'mov']);
+
+interface FileThumbnailProps {
+  path: string;
+  ext: string; // including the leading dot, as in splitPath
+  maxPx?: number;
+}
+
+export default function FileThumbnail({ path, ext, maxPx = 64 }: FileThumbnailProps) {
+  const [thumbnail, setThumbnail] = useState<Thumbnail | null>(null);
+  const previewable = PREVIEWABLE_EXTENSIONS.has(ext.replace(/^\./, '').toLowerCase());
+
+  useEffect(() => {
+    if (!previewable) return;
+    let cancelled = false;
+    generateThumbnail(path, maxPx)
+      .then(result => { if (!cancelled) setThumbnail(result); })
+      // Corrupt or unsupported files simply have no preview
+      .catch(() => { if (!cancelled) setThumbnail(null); });
+    return () => { cancelled = true; };
+  }, [path, maxPx, previewable]);
+
+  if (!thumbnail) return null;
+
+  return (
+    <img
+      className="file-thumbnail"
+      src={`data:${thumbnail.mime_type};base64,${thumbnail.base64}`}
+      width={thumbnail.width}
+      height={thumbnail.height}
+      alt=""
+      loading="lazy"
+    />
+  );
+}
@@ -22,8 +22,8 @@ export default function HelpDialog({ isOpen, onClose }: HelpDialogProps) {
       
       // Clear success message after 5 seconds
       setTimeout(() => setExportStatus(''), 5000);
-    } catch (error) {
-      setExportStatus(`❌ Failed to save: ${error}`);
+    } catch (error: any) {
+      setExportStatus(`❌ Failed to save: ${error?.message || String(error)}`);
       debugLogger.error('HELP_DIALOG', 'Failed to export diagnostics', { error });
       
       // Clear error message after 5 seconds
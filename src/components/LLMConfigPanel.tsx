@@ -168,7 +168,10 @@ export default function LLMConfigPanel({ config, onChange, onTest, disabled, pro
         const status = await getManagedLLMServerStatus();
         
         if (status.status === 'running') {
-          // Server has successfully started
+          // Server has successfully started; follow it if it had to pick another port
+          if (status.port && status.port !== currentManagedConfig.port) {
+            onChange({ ...config, baseUrl: "http://" + currentManagedConfig.host + ":" + status.port });
+          }
           await loadManagedLLMStatus();
           setIsStarting(false);
           return;
@@ -530,6 +533,38 @@ export default function LLMConfigPanel({ config, onChange, onTest, disabled, pro
                   Enable if your model supports image inputs (e.g., GPT-4 Vision, Claude 3, Gemini Vision)
                 </div>
               </div>
+              {config.supportsVision && (
+                <div className="config-section">
+                  <label className="config-label">
+                    Image Size
+                    <input
+                      type="number"
+                      className="config-input"
+                      value={config.imageMaxDimension || ''}
+                      onChange={(e) => onChange({ ...config, imageMaxDimension: e.target.value ? parseInt(e.target.value, 10) : undefined })}
+                      placeholder="1024"
+                      min={16}
+                      disabled={disabled}
+                    />
+                  </label>
+                  <label className="config-label">
+                    Image Quality
+                    <input
+                      type="number"
+                      className="config-input"
+                      value={config.imageQuality || ''}
+                      onChange={(e) => onChange({ ...config, imageQuality: e.target.value ? parseInt(e.target.value, 10) : undefined })}
+                      placeholder="75"
+                      min={1}
+                      max={100}
+                      disabled={disabled}
+                    />
+                  </label>
+                  <div className="config-hint">
+                    Longest side in pixels and JPEG quality of images sent to the model. Raise them if your model accepts larger images.
+                  </div>
+                </div>
+              )}
               <div className="config-section">
                 <label className="config-label">
                   System Message
@@ -690,6 +725,25 @@ export default function LLMConfigPanel({ config, onChange, onTest, disabled, pro
                   </div>
                   )}
 
+                  <div className="config-section">
+                    <label className="config-label">
+                      Restart Policy
+                      <select
+                        className="config-input"
+                        value={currentManagedConfig.restart_policy || 'on-failure'}
+                        onChange={(e) => updateManagedConfig({ restart_policy: e.target.value as ManagedLLMConfig['restart_policy'] })}
+                        disabled={disabled}
+                      >
+                        <option value="on-failure">Restart on failure</option>
+                        <option value="always">Always restart</option>
+                        <option value="never">Never restart</option>
+                      </select>
+                    </label>
+                    <div className="config-hint">
+                      Automatically restart the server if it exits unexpectedly (up to {currentManagedConfig.max_restart_attempts ?? 3} attempts)
+                    </div>
+                  </div>
+
                   {/* Multi-modal Configuration */}
                   {config.supportsVision && !isMac && (<>
                   <div className="config-section">
@@ -866,6 +920,17 @@ export default function LLMConfigPanel({ config, onChange, onTest, disabled, pro
                       />
                     </label>
                   </div>
+                  <div className="config-section">
+                    <label className="checkbox-label">
+                      <input
+                        type="checkbox"
+                        checked={currentManagedConfig.auto_port || false}
+                        onChange={(e) => updateManagedConfig({ auto_port: e.target.checked })}
+                        disabled={disabled}
+                      />
+                      Pick a free port if this one is in use
+                    </label>
+                  </div>
                   
                   <div className="config-section">
                     <label className="config-label">
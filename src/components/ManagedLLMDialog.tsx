@@ -70,10 +70,11 @@ export default function ManagedLLMDialog({
           });
         }, 200);
         
+        // The download can take minutes, so let the OS tell the user when it's done
         await downloadManagedLLMServer(version, (percent) => {
           clearInterval(progressInterval);
           setDownloadProgress(percent);
-        });
+        }, true);
         
         clearInterval(progressInterval);
       }
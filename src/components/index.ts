@@ -5,3 +5,4 @@ export { default as HelpDialog } from './HelpDialog';
 export { default as AboutDialog } from './AboutDialog';
 export { default as ManagedLLMDialog } from './ManagedLLMDialog';
 export { default as UpdateCheckDialog } from './UpdateCheckDialog';
+export { default as FileThumbnail } from './FileThumbnail';
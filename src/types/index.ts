@@ -30,6 +30,100 @@ export interface ScanControl {
   processedFiles: any[];
 }
 
+// Error value every backend command rejects with
+export type AppErrorKind =
+  | 'NotFound'
+  | 'PermissionDenied'
+  | 'Unsupported'
+  | 'Io'
+  | 'Network'
+  | 'LlmBackend'
+  | 'Cancelled'
+  | 'InvalidInput'
+  | 'FileInUse'
+  | 'InvalidData'
+  | 'AlreadyExists'
+  | 'Encrypted'
+  | 'PolicyViolation'
+  | 'Timeout'
+  | 'CloudPlaceholder' // reading the file would download it from the cloud
+  | 'VolumeUnavailable' // the drive or share was unplugged or disconnected
+  | 'ContextOverflow'; // the prompt doesn't fit the model's context window
+
+export interface AppError {
+  kind: AppErrorKind;
+  message: string;
+  path?: string;
+  source?: string; // underlying error text
+  retry_after_ms?: number; // set when retrying later may succeed, e.g. FileInUse
+  rule?: 'system_path' | 'app_data' | 'allowed_roots'; // the rule behind a PolicyViolation
+  elapsed_ms?: number; // how long a Timeout ran before it was abandoned
+  is_recoverable?: boolean; // whether the user can fix it, e.g. by redownloading or entering a password
+  prompt_tokens?: number; // for ContextOverflow, when the backend reported them
+  context_tokens?: number;
+}
+
+// Result of check_directory_access, shown before a scan starts
+export interface AccessFailure {
+  check: 'exists' | 'directory' | 'list' | 'write' | 'cleanup' | 'protected_location';
+  message: string;
+  suggestion: string;
+}
+
+export interface DirectoryAccessReport {
+  path: string;
+  exists: boolean;
+  is_directory: boolean;
+  can_list: boolean;
+  can_write: boolean;
+  protected_location?: string;
+  failures: AccessFailure[];
+}
+
+// Scheduled scans (create_schedule / list_schedules / get_schedule_results)
+export interface ScanOptions {
+  include_subdirectories: boolean;
+  follow_symlinks: boolean;
+  include_hidden?: boolean;
+  ignored_names?: string[]; // replaces the default junk list (.DS_Store, Thumbs.db, desktop.ini, ...)
+}
+
+export interface Schedule {
+  id: string;
+  path: string;
+  spec: { type: 'interval'; seconds: number } | { type: 'cron'; expression: string };
+  options: ScanOptions;
+  missed_run_policy: 'run' | 'skip';
+  notify: boolean;
+  keep_results: number;
+  created_at: number; // unix seconds
+  last_run?: number;
+  next_run: number;
+}
+
+export interface ScheduledScanResult {
+  schedule_id: string;
+  path: string;
+  started_at: number;
+  finished_at: number;
+  total_files: number;
+  total_bytes: number;
+  by_extension: Record<string, { count: number; bytes: number }>;
+  new_file_count: number;
+  new_files: string[]; // capped at 500
+  error?: string;
+}
+
+// Payload of the scheduled-scan-complete event
+export interface ScheduledScanCompleteEvent {
+  schedule_id: string;
+  path: string;
+  started_at: number;
+  total_files: number;
+  new_file_count: number;
+  error?: string;
+}
+
 // Managed LLM Server types
 export type LLMServerStatus = 'not_downloaded' | 'downloaded' | 'running' | 'stopped' | 'error';
 
@@ -39,6 +133,29 @@ export interface ManagedLLMServerInfo {
   path?: string;
   port?: number;
   error?: string;
+  model_metadata?: GgufMetadata;
+  warmed_up?: boolean;
+  warm_up_ms?: number; // duration of the warm-up completion
+  idle_unload_at?: number; // unix seconds when the idle timer will stop the server
+}
+
+export interface ManagedServerModel {
+  id: string;
+  owned_by?: string;
+  created?: number;
+}
+
+export interface GgufMetadata {
+  version: number;
+  architecture: string;
+  name?: string;
+  quantization?: string;
+  context_length?: number;
+  block_count?: number;
+  chat_template?: string; // tokenizer.chat_template
+  chat_format?: string; // recognized template family, e.g. 'chatml', 'llama-3', 'gemma'
+  tensor_count: number;
+  file_size: number;
 }
 
 export interface ManagedLLMConfig {
@@ -48,10 +165,16 @@ export interface ManagedLLMConfig {
   model_filename?: string;
   model_path?: string;
   log_level: string;
+  auto_port?: boolean;
   env_vars: Record<string, string>;
   mmproj_repo_id?: string;
   mmproj_filename?: string;
   chat_format?: string;
+  startup_timeout?: number;
+  restart_policy?: 'never' | 'on-failure' | 'always';
+  max_restart_attempts?: number;
+  warm_up?: boolean; // run a one-token completion after start so the first file isn't slow
+  idle_unload_minutes?: number; // stop the server after this long idle; the next request restarts it
   max_tokens?: number;
   max_text_length?: number;
   system_prompt?: string;
@@ -61,11 +184,972 @@ export interface ManagedLLMConfig {
   supports_vision?: boolean;
 }
 
+// LLM backend routing (select_llm_backend / llm_classify)
+export type LLMBackendConfig =
+  | { kind: 'managed'; model?: string }
+  | {
+      kind: 'remote';
+      provider: string;
+      base_url: string;
+      api_key?: string;
+      model: string;
+      headers?: Record<string, string>;
+      supports_vision?: boolean;
+      max_context?: number;
+    };
+
+export interface LLMBackendCapabilities {
+  vision: boolean;
+  json_mode: boolean;
+  max_context?: number;
+}
+
+export interface ActiveLLMBackend {
+  kind: 'managed' | 'remote';
+  name?: string;
+  capabilities?: LLMBackendCapabilities;
+  healthy: boolean;
+  error?: string;
+}
+
+export interface LLMClassifyRequest {
+  system_message: string;
+  prompt: string;
+  image_base64?: string;
+  mime_type?: string;
+  max_tokens?: number;
+  source_path?: string; // the file whose content this is, for the audit log
+}
+
+export interface LLMClassifyResponse {
+  content: string;
+  backend: string;
+  image_sent: boolean;
+  fell_back: boolean;
+  usage: LLMTokenUsage;
+  redactions?: Record<string, number>; // placeholders put into the prompt, per type
+}
+
+// Token counts and timings, as far as the backend reported them
+export interface LLMTokenUsage {
+  prompt_tokens?: number | null;
+  completion_tokens?: number | null;
+  prompt_ms?: number | null; // prompt processing, from servers that time it separately
+  generation_ms?: number | null;
+}
+
+// One llm_classify attempt against one backend
+export interface LLMRequestRecord extends LLMTokenUsage {
+  at: number; // unix seconds
+  backend: string;
+  wait_ms: number; // before this backend was asked (idle reload, earlier fallbacks)
+  latency_ms: number;
+  tokens_per_second?: number | null;
+  failure?: 'unavailable' | 'rejected' | 'context_overflow' | null;
+}
+
+// get_llm_metrics result: aggregates since startup or the last reset
+export interface LLMMetrics {
+  since: number;
+  requests: number;
+  failures: Record<string, number>;
+  p50_latency_ms?: number | null;
+  p95_latency_ms?: number | null;
+  prompt_tokens: number;
+  completion_tokens: number;
+  generation_tokens_per_second?: number | null;
+  recent: LLMRequestRecord[]; // newest first
+}
+
+export interface HfSearchFilters {
+  author?: string;
+  sort?: 'downloads' | 'likes' | 'lastModified' | 'trending';
+  limit?: number; // 30 by default, at most 100
+}
+
+export interface HfModel {
+  id: string; // owner/name
+  downloads: number;
+  likes: number;
+  last_modified?: string | null;
+  gated: boolean; // needs an access token
+}
+
+export interface HfModelFile {
+  filename: string;
+  size: number;
+  quantization?: string | null; // from the filename, e.g. Q4_K_M
+  sha256?: string | null;
+  download_url: string;
+}
+
+// download_hf_model result
+export interface DownloadedModel {
+  path: string;
+  sha256?: string | null;
+  metadata: GgufMetadata;
+}
+
+export interface ModelCatalogEntry {
+  name: string;
+  repo_id: string;
+  filename: string;
+  mmproj_filename?: string | null; // vision models
+  size_bytes: number;
+  min_ram_bytes: number;
+  full_offload_vram_bytes: number;
+  quality_tier: number; // 1-5
+  use_cases: string[];
+}
+
+export interface ModelRecommendation extends ModelCatalogEntry {
+  score: number;
+  offload: 'full' | 'partial' | 'none';
+  estimated_tokens_per_second: number;
+  explanation: string; // e.g. "Fits fully on your 8 GB GPU, expect ~45 tok/s"
+}
+
+// recommend_models result
+export interface ModelRecommendations {
+  hardware: {
+    total_ram_bytes: number;
+    cpu_cores: number;
+    gpu_backend: 'metal' | 'cuda' | 'vulkan' | 'cpu';
+    vram_bytes?: number | null;
+    unified_memory: boolean;
+  };
+  use_case: string;
+  recommendations: ModelRecommendation[]; // best first; models too big for RAM are left out
+}
+
+export interface LLMFallbackSettings {
+  chain: LLMBackendConfig[];
+  timeout_secs: number;
+  failure_threshold: number;
+  cooldown_minutes: number;
+}
+
+// Payload of the llm-backend-fallback event
+export interface LLMBackendFallbackEvent {
+  served_by: string;
+  failures: string[];
+}
+
+// Emitted every second while a content read waits for an extraction slot
+// Payload of "operation-cancelled", sent once a cancelled operation has stopped
+export interface OperationCancelledEvent {
+  operation_id: string;
+  completed: number;
+  skipped?: number | null; // only known for batch commands
+}
+
+export interface ExtractionQueuedEvent {
+  path: string;
+  position: number; // 1 when next in line
+  waiting_ms: number;
+}
+
+// Payloads of the http-stream-* events emitted by http_request_stream
+export interface HttpStreamChunkEvent {
+  request_id: string;
+  frames: string[];
+}
+
+export interface HttpStreamDoneEvent {
+  request_id: string;
+  cancelled: boolean;
+}
+
+export interface HttpStreamErrorEvent {
+  request_id: string;
+  status?: number;
+  error: string;
+}
+
+export interface NetworkConfig {
+  proxy_url?: string;
+  proxy_username?: string;
+  proxy_password?: string;
+  ca_bundle_path?: string; // PEM bundle with extra root certificates
+  use_system_proxy: boolean;
+}
+
+// Returned by get_app_info and written at the top of every diagnostics bundle
+export interface AppInfo {
+  version: string;
+  build_timestamp: string;
+  git_commit: string; // short hash, 'unknown' for builds from a source tarball
+  os: string;
+  os_version?: string;
+  arch: string;
+  tauri_version: string;
+  gpu_backend: 'metal' | 'cuda' | 'vulkan' | 'cpu' | 'unknown';
+  app_data_dir?: string;
+}
+
+// Where moves and renames may write. With no allowed_roots the selected directories are used;
+// system paths and the app's data directory are always refused.
+export interface DestinationPolicy {
+  allowed_roots: string[];
+}
+
+// Settings kept by the backend in app_data_dir/settings.json
+export interface Settings {
+  version: number;
+  // The last configuration the managed server was started with
+  managed_llm?: ManagedLLMConfig | null;
+  scan: {
+    include_hidden: boolean;
+    ignored_names?: string[] | null;
+    hydrate_cloud_placeholders: boolean;
+  };
+  destination_policy: DestinationPolicy;
+  extraction: {
+    timeout_ms: number;
+    concurrency?: number | null;
+  };
+  // Converters for file types the built-in extractors can't read
+  external_extractors: {
+    enabled: boolean;
+    handlers: ExternalExtractor[];
+  };
+  staging: {
+    expiry_days: number; // staged batches older than this are reported at startup
+  };
+  open_file: {
+    dangerous_extensions: string[]; // revealed in the file manager instead of opened
+  };
+  audit: {
+    enabled: boolean;
+  };
+  redaction: {
+    enabled: boolean; // redact before sending to backends off this machine
+    redact_always: boolean; // local backends too
+    rules: RedactionRules;
+  };
+  // No file content leaves the machine: payloads only go to loopback hosts and only the
+  // built-in LLM server is used
+  local_only: boolean;
+  // Language of backend notifications and messages; unset means English
+  locale?: string | null;
+  // Hugging Face access token for gated repos; never included in exports
+  huggingface_token?: string | null;
+  // Frontend-only values, stored as given
+  frontend: Record<string, any>;
+}
+
+export interface NetworkTestResult {
+  ok: boolean;
+  status?: number;
+  category: 'ok' | 'http' | 'timeout' | 'dns' | 'tls' | 'proxy' | 'connect' | 'other';
+  error?: string;
+  elapsed_ms: number;
+}
+
+export interface CachedClassification {
+  path: string;
+  content_hash: string; // SHA-256 hex
+  category: string;
+  destination: string; // suggested file name without extension
+  model_id: string;
+  cached_at: number; // unix seconds
+}
+
+export interface ClassificationCacheStats {
+  entries: number;
+  hits: number;
+  misses: number;
+}
+
+export type ReductionStrategy = 'head' | 'head_tail' | 'salient';
+
+// File text cut down to a token budget by prepare_classification_content
+export interface PreparedContent {
+  text: string;
+  mime_type?: string;
+  redactions?: Record<string, number>; // placeholders put into the text, per type
+  original_tokens: number;
+  kept_tokens: number;
+  strategy: ReductionStrategy;
+  applied_strategy: ReductionStrategy | null; // null when the text already fit
+  token_counter: 'heuristic'; // the bundled servers don't expose their tokenizer
+}
+
+// One directory in a summarize_directory_tree result
+export interface DirectorySummary {
+  name: string;
+  path: string; // relative to the summarized root, '' for the root
+  file_count: number; // files directly inside, or anywhere beneath when collapsed
+  total_files: number;
+  total_size: number;
+  top_extensions: { extension: string; count: number }[];
+  sample_files: string[];
+  more_files: number; // files not listed in sample_files
+  more_directories: number; // subdirectories left out of children
+  collapsed: boolean; // deeper directories were folded into this one
+  children: DirectorySummary[];
+}
+
+export interface TreeSummary {
+  root: DirectorySummary;
+  chars: number;
+  budget_exceeded: boolean;
+}
+
+// suggest_destinations result, one entry per category in the order given
+export interface DestinationSuggestions {
+  categories: {
+    category: string;
+    candidates: { path: string; score: number }[]; // best first, score 0-1
+    matched: boolean; // false: no good existing folder, ask the user
+  }[];
+  directories_scanned: number;
+  truncated: boolean;
+}
+
+// get_directory_composition result; categories are sorted by total_bytes, largest first
+export interface DirectoryComposition {
+  categories: {
+    category: string;
+    file_count: number;
+    total_bytes: number;
+    largest: { path: string; size: number; root: string }[];
+  }[];
+  roots: { root: string; file_count: number; total_bytes: number }[]; // nested roots are only counted once
+  total_files: number;
+  total_bytes: number;
+  unreadable: number;
+}
+
+export interface Shortcut {
+  path: string;
+  kind: 'windows_shortcut' | 'mac_alias' | 'internet_shortcut' | 'webloc';
+  target?: string | null; // file path, or the URL of a .url/.webloc
+  status: 'exists' | 'missing' | 'unverifiable' | 'url'; // unverifiable: another platform's path
+}
+
+// find_shortcuts result
+export interface ShortcutReport {
+  shortcuts: Shortcut[]; // working and URL shortcuts
+  broken: Shortcut[]; // target gone; cleanup candidates
+  referenced: string[]; // existing targets, each once
+  files_scanned: number;
+  unreadable: number;
+}
+
+// From get_spotlight_metadata (macOS only)
+export interface SpotlightMetadata {
+  last_used_ms?: number | null; // when the file was last opened
+  where_froms: string[]; // for downloads: the file's URL, then the page it came from
+  content_kind?: string | null; // e.g. "PDF document"
+  authors: string[];
+}
+
+// find_unused_directories result; only the highest unused directory of a tree is listed,
+// largest first
+export interface UnusedDirectoryReport {
+  directories: {
+    path: string;
+    total_bytes: number;
+    file_count: number;
+    newest_activity_ms: number; // latest modified, accessed or Spotlight last-used time of anything inside
+    newest_path?: string | null; // the file with that time; null for a directory without files
+    activity_signal: 'mtime' | 'atime' | 'spotlight'; // which time that was
+  }[];
+  reclaimable_bytes: number;
+  cutoff_ms: number; // activity before this counts as unused
+  directories_scanned: number;
+  files_scanned: number;
+  unreadable: number; // directories above these aren't reported
+  spotlight: boolean; // Spotlight last-used dates were available (macOS)
+}
+
+// list_volumes entry, also the payload of volume-mounted / volume-unmounted events
+export interface Volume {
+  mount_point: string; // drive root on Windows ("E:\\")
+  label: string | null;
+  file_system: string;
+  total_bytes: number;
+  free_bytes: number; // available to this user
+  removable: boolean;
+  network: boolean;
+}
+
+// 'low' yields the disk to other programs and holds reads to the scan throttle (setScanThrottle)
+export type IoPriority = 'normal' | 'low';
+
+// Payload of the duplicate-scan-progress event
+export interface HashProgress extends ProgressEstimate {
+  files_hashed: number;
+  files_to_hash: number;
+  bytes_hashed: number;
+  bytes_to_hash: number;
+  files_from_checkpoint: number; // of files_hashed, taken from a checkpoint instead of read
+}
+
+// What a checkpointed analysis was run with; resume_analysis reruns it with these
+export interface AnalysisOptions {
+  kind: 'duplicate_directories';
+  roots: string[];
+  min_similarity?: number | null;
+  include_hidden?: boolean | null;
+  ignored_names?: string[] | null;
+  follow_symlinks?: boolean; // false when missing
+  hydrate: boolean;
+}
+
+// An interrupted analysis that resume_analysis can continue
+export interface CheckpointSummary {
+  id: string;
+  options: AnalysisOptions;
+  created_at: number; // unix ms
+  updated_at: number; // unix ms
+  files_hashed: number;
+}
+
+// find_duplicate_directories result
+export interface DuplicateDirectoryReport {
+  groups: {
+    directories: string[];
+    total_size: number; // of one copy
+    file_count: number;
+    reclaimable_bytes: number;
+  }[];
+  similar: { first: string; second: string; similarity: number; shared_bytes: number }[]; // similarity in percent
+  files_hashed: number;
+  hash_errors: number;
+  cloud_placeholders: number; // left unhashed so they aren't downloaded
+  scanned_at: number; // unix ms when the files were listed
+  files_changed: number; // changed between listing and hashing; treated as unique
+  files_vanished: number;
+  changed_files: { path: string; vanished: boolean }[]; // the first 100
+}
+
+// Rate and time left, computed by the backend and included in progress events (the
+// duplicate scan, downloads, directory scans, archiving). Only the rate for the event's
+// unit is set; eta_seconds is null until a few samples are in, while stalled, and when
+// the total isn't known.
+export interface ProgressEstimate {
+  bytes_per_sec?: number;
+  items_per_sec?: number;
+  eta_seconds?: number | null;
+  elapsed_ms: number;
+}
+
+// Payload of model-download-progress and llm-server-download-progress events
+export interface DownloadProgress extends ProgressEstimate {
+  downloaded_bytes: number;
+  total_bytes?: number | null; // unknown when the server doesn't send a length
+  attempt: number;
+}
+
+// Result of read_directory
+export interface DirectoryListing {
+  files: string[];
+  skipped: SkippedPath[]; // subdirectories and entries that couldn't be read
+  total?: number | null; // files in the whole listing; null when an unsorted walk stopped after this page
+  next_offset?: number | null; // offset of the next page, when there is one
+  sort_forced_full_walk: boolean; // a limit was given, but sorting needed every file first
+}
+
+// Sorting and paging for read_directory. Ties are broken by full path, so sorted pages
+// are stable across calls; without a sort files come in file system order.
+export interface ListingOptions {
+  sort?: 'name' | 'size' | 'modified' | null;
+  order?: 'asc' | 'desc';
+  offset?: number;
+  limit?: number | null;
+}
+
+// start_directory_stream options: read_directory's sorting and paging plus pacing. A
+// sort makes the backend collect every file before the first chunk goes out.
+export interface DirectoryStreamOptions extends ListingOptions {
+  chunk_size?: number; // files per directory-entries event, 1000 by default
+  chunk_interval_ms?: number; // least time between two events, 50 by default
+}
+
+// Payload of the directory-entries events of a directory stream
+export interface DirectoryEntriesEvent {
+  stream_id: string;
+  entries: DirectoryEntry[];
+}
+
+// Emitted once when a directory stream ends, cancelled or not
+export interface DirectoryStreamComplete {
+  stream_id: string;
+  files_sent: number;
+  total?: number | null; // files in the whole listing; null when the walk stopped early
+  next_offset?: number | null;
+  sort_forced_full_walk: boolean;
+  directories_visited: number;
+  skipped: SkippedPath[];
+  cancelled: boolean;
+  elapsed_ms: number;
+  error?: string | null; // the directory itself couldn't be listed
+}
+
+export interface SkippedPath {
+  path: string;
+  reason: 'permission_denied' | 'not_found' | 'symlink_cycle' | 'other';
+  message: string;
+}
+
+// Emitted a few times a second while read_directory walks with a scanId; items_per_sec
+// counts files found
+export interface DirectoryScanProgress extends ProgressEstimate {
+  scan_id: string;
+  directories_visited: number;
+  files_found: number;
+  current_path: string; // the directory being read
+}
+
+// Emitted once when a read_directory scan with a scanId ends
+export interface DirectoryScanComplete {
+  scan_id: string;
+  directories_visited: number;
+  files_found: number;
+  skipped: number;
+  elapsed_ms: number;
+  error?: string | null; // the directory itself couldn't be listed
+}
+
+// File listed by read_directory_entries
+export interface DirectoryEntry {
+  path: string;
+  is_symlink: boolean; // a link to the file rather than the file itself
+  is_cloud_placeholder: boolean; // only stored in the cloud; reading it would download it
+  size: number;
+  modified_ms: number; // 0 when the file system doesn't say
+  provenance?: Provenance; // where a download came from, when the browser recorded it
+}
+
+// From get_file_provenance: macOS kMDItemWhereFroms or the Windows Zone.Identifier stream
+export interface Provenance {
+  source_url: string | null;
+  referrer_url: string | null; // the page the download was started from
+}
+
+export type DateGranularity = 'year' | 'year_month' | 'year_month_day';
+
+// Move plan from plan_date_organization; nothing has been moved yet
+export interface DatePlan {
+  moves: {
+    source: string;
+    destination: string;
+    date: string | null; // YYYY-MM-DD, null for files planned into "Undated"
+    date_source: 'exif' | 'modified' | 'undated';
+  }[];
+  already_in_place: number;
+}
+
+// What rename_file / rename_files do when the new name is already taken
+export type RenameConflictMode = 'number' | 'skip' | 'error';
+
+export interface RenameOutcome {
+  from: string;
+  to: string;
+  status: 'renamed' | 'unchanged' | 'skipped';
+  simulated: boolean; // dry run: nothing was renamed
+}
+
+// Result of move_file; simulated moves were validated but not performed
+export interface MoveOutcome {
+  from: string;
+  to: string; // for duplicates, the identical file already there
+  simulated: boolean;
+  // kept_both: moved under a numbered name; skipped: name taken and onConflict was skip;
+  // skipped_duplicate / source_trashed: an identical copy was already at the destination
+  action: 'moved' | 'kept_both' | 'skipped' | 'skipped_duplicate' | 'source_trashed';
+  trashed_to?: string; // where a trashed duplicate source went
+}
+
+export type SampleStrategy = 'stratified' | 'random' | 'largest' | 'newest';
+
+export interface SampledFile {
+  path: string;
+  size: number;
+  extension: string; // lowercase, no dot
+  category: string; // coarse content type, as in DirectoryComposition
+  modified_ms: number;
+}
+
+export interface ClassificationSample {
+  strategy: SampleStrategy;
+  seed: number; // pass back to draw the same sample again
+  population: number;
+  files: SampledFile[];
+  composition: { category: string; sampled: number; population: number }[];
+  cloud_placeholders: number; // left out; classifying them would download them
+}
+
+export interface SampleOptions {
+  strategy?: SampleStrategy; // default 'stratified'
+  seed?: number;
+  includeSubdirectories?: boolean; // default true
+  includeHidden?: boolean;
+}
+
+export interface ClassificationOutcome {
+  category_path: string;
+  suggested_filename?: string;
+  confidence?: number; // 0..1, when the backend reports one
+}
+
+// A missing result means that backend failed on the file
+export interface ComparedFile {
+  path: string;
+  result_a: ClassificationOutcome | null;
+  result_b: ClassificationOutcome | null;
+}
+
+export interface ComparisonSummary {
+  files: number;
+  compared: number; // files both backends classified
+  failed_a: number;
+  failed_b: number;
+  exact_matches: number;
+  match_rate: number; // 0..1
+  top_level_match_rate: number;
+  average_confidence_a: number | null;
+  average_confidence_b: number | null;
+  confusion: { category_a: string; category_b: string; count: number }[]; // most frequent first
+  report_path: string | null;
+}
+
+export interface IndexOptions {
+  include_subdirectories?: boolean; // default true
+  include_hidden?: boolean;
+  max_file_bytes?: number; // larger files aren't extracted (default 50 MB)
+  io_priority?: IoPriority; // default 'normal'
+}
+
+export interface IndexReport {
+  root: string;
+  files_seen: number;
+  indexed: number;
+  unchanged: number; // same size and mtime as when last indexed
+  removed: number; // indexed before, now gone
+  skipped: number; // images, binaries, oversized files, cloud placeholders
+  failed: number;
+  cancelled: boolean;
+}
+
+// Byte range in the indexed (whitespace-normalized) text
+export interface TextRange {
+  start: number;
+  end: number;
+}
+
+export interface SearchMatch {
+  path: string;
+  score: number; // bm25; lower is better
+  snippet: string;
+  snippet_start: number; // byte offset of the snippet in the indexed text
+  offsets: TextRange[];
+}
+
+// Where set_file_tags stored a file's tags; sidecars are used on FAT/exFAT and other
+// file systems without extended attributes or streams
+export type TagStorage = 'extended_attribute' | 'alternate_stream' | 'sidecar';
+
+export interface FileTags {
+  path: string;
+  tags: string[];
+  storage: TagStorage;
+}
+
+// What verify_organization found wrong with one journaled move
+export type DiscrepancyKind =
+  | 'file_lost' // destination and source both missing
+  | 'move_not_applied' // destination missing, source still present
+  | 'source_still_present'
+  | 'size_mismatch'
+  | 'hash_mismatch'
+  | 'unreadable';
+
+export interface Discrepancy {
+  kind: DiscrepancyKind;
+  from: string;
+  to: string;
+  detail: string;
+}
+
+export interface StagedFile {
+  original: string;
+  staged: string;
+  proposed: string;
+  status: 'staged' | 'committed' | 'restored';
+}
+
+// A batch of files held in <root>/_Organizer Staging/<batch_id>/ for review
+export interface StagedBatch {
+  version: number;
+  batch_id: string;
+  root: string;
+  created_at: number; // unix ms
+  files: StagedFile[];
+}
+
+// stage_files and commit_staged result; failed files were left where they were
+export interface StagingReport {
+  batch: StagedBatch;
+  failures: { path: string; error: AppError }[];
+}
+
+export interface StagedBatchSummary {
+  batch_id: string;
+  root: string;
+  created_at: number;
+  staged: number;
+  committed: number;
+  restored: number;
+  expiring: boolean; // older than staging.expiry_days with files still staged
+}
+
+export interface PlannedMove {
+  from: string;
+  to: string;
+}
+
+// What undoBatch put back; failed entries stay in the journal for another try
+export interface UndoReport {
+  batch_id: string;
+  restored: number;
+  archived: number; // packed into an archive; restoreArchive puts those back
+  failures: { from: string; to: string; error: AppError }[];
+}
+
+// What one executePlan or resumeRun call did
+export interface RunReport {
+  run_id: string;
+  batch_id: string; // journal the moves were recorded under
+  total: number;
+  resumed_from: number; // index this call started at; 0 unless resumed
+  moved: number;
+  already_moved: number; // found done when resuming
+  skipped: number;
+  failures: { from: string; to: string; error: AppError }[];
+  cancelled: boolean; // the run can be resumed
+}
+
+// A run that stopped before the end (crash, quit or cancel), from get_interrupted_runs
+export interface InterruptedRun {
+  run_id: string;
+  batch_id: string;
+  created_at: number;
+  updated_at: number;
+  total: number;
+  next: number; // index of the first move not yet done
+  moved: number;
+  skipped: number;
+  failed: number;
+}
+
+// Payload of run-progress events
+export interface RunProgress extends ProgressEstimate {
+  run_id: string;
+  done: number;
+  total: number;
+}
+
+// Options of run_classification_pipeline; everything is optional
+export interface PipelineOptions {
+  extract_concurrency?: number; // files extracted at once; the extraction limiter's size by default
+  llm_concurrency?: number; // LLM requests in flight; 1 for the managed server, 4 for remote backends by default
+  stop_on_error?: boolean; // start no new files after the first failure
+  dry_run?: boolean; // classify and plan destinations without moving
+  backend?: LLMBackendConfig; // used for this run only; the selected backend otherwise
+  roots?: string[]; // files are organized within the root they're under, else their own directory
+  categories?: string[]; // existing categories the model must choose from
+  prompt_template?: string; // {filename}, {content}, {type} and {categories} are filled in
+  system_message?: string;
+  max_text_chars?: number; // 4096 by default
+  max_tokens?: number;
+  model_id?: string; // reuse and record classifications in the cache under this model id
+  batch_id?: string; // journal batch for the moves; generated when not given
+  on_conflict?: RenameConflictMode;
+  operation_id?: string;
+}
+
+export type PipelineStage = 'extracting' | 'classifying' | 'moving' | 'done' | 'skipped' | 'failed';
+
+// Sent as pipeline-file each time a file enters a stage
+export interface PipelineFileEvent {
+  pipeline_id: string;
+  path: string;
+  stage: PipelineStage;
+  category_path?: string;
+  destination?: string;
+  error?: AppError;
+}
+
+export interface PipelineProgress extends ProgressEstimate {
+  pipeline_id: string;
+  done: number;
+  total: number;
+}
+
+export interface PipelineFileResult {
+  path: string;
+  status: 'planned' | 'moved' | 'skipped' | 'failed'; // planned: dry run, not moved
+  method: 'llm' | 'cache' | 'extension' | null;
+  category_path: string | null;
+  suggested_filename: string | null;
+  destination: string | null;
+  backend: string | null; // the backend that answered, for 'llm'
+  note: string | null; // why a file was categorized by extension or skipped
+  error: AppError | null;
+}
+
+export interface PipelineReport {
+  pipeline_id: string;
+  batch_id: string | null; // journal of the moves; null for a dry run
+  dry_run: boolean;
+  total: number;
+  planned: number;
+  moved: number;
+  skipped: number;
+  failed: number;
+  llm_requests: number;
+  cache_hits: number;
+  cancelled: boolean;
+  stopped_on_error: boolean;
+  elapsed_ms: number;
+  results: PipelineFileResult[]; // one per path, in the order given
+}
+
+// An application that can open a file, from list_applications_for
+export interface Application {
+  id: string; // pass to openFileWith; .app path on macOS, executable on Windows, desktop file id on Linux
+  name: string;
+  is_default: boolean;
+}
+
+// 'revealed' means the file type runs as a program, so it was shown in the file manager instead
+export type OpenOutcome = 'opened' | 'revealed';
+
+// A command line converter whose stdout becomes a file's text. {input} in the command is
+// replaced by the file's path, passed as one argument (never through a shell); without
+// it the path is appended.
+export interface ExternalExtractor {
+  extensions: string[]; // without the dot
+  command: string; // e.g. "dwg2txt {input}"
+  timeout_ms?: number | null; // 20 seconds by default
+  max_output_bytes?: number | null; // 4 MiB by default; longer output is cut off
+}
+
+export interface ExtractorOutput {
+  text: string;
+  truncated: boolean;
+  exit_code?: number | null;
+  stderr: string;
+  elapsed_ms: number;
+}
+
+export type PiiCategory = 'email' | 'phone' | 'credit_card' | 'ssn' | 'ip_address' | 'iban';
+
+export interface RedactionRules {
+  categories?: PiiCategory[]; // all by default
+  custom?: { name: string; pattern: string }[]; // regexes; name becomes the placeholder, e.g. [PROJECT_CODE_1]
+}
+
+export interface RedactedContent {
+  text: string;
+  counts: Record<string, number>; // matches replaced per placeholder type: EMAIL, PHONE, CARD, SSN, IP, IBAN, custom
+}
+
+// Inclusive Unix milliseconds; a missing end is unbounded
+export interface AuditRange {
+  from_ms?: number;
+  to_ms?: number;
+}
+
+export interface AuditExportSummary {
+  path: string;
+  records: number;
+  log_valid: boolean; // whether the whole log verified at export time
+  public_key: string; // hex Ed25519 key the bundle was signed with
+  fingerprint: string; // SHA-256 of public_key; give it to auditors to verify with
+}
+
+export interface AuditVerification {
+  records: number;
+  valid: boolean;
+  first_invalid_seq?: number;
+  first_invalid_line?: number;
+  problem?: string;
+  head_hash?: string; // note it down to detect later truncation
+  signature_valid?: boolean; // bundles only
+  signer_trusted?: boolean; // bundles only: signed by this install's key or the expected one
+  signer_fingerprint?: string; // bundles only
+}
+
+// archive_files result
+export interface ArchiveFilesResult {
+  archive_path: string;
+  files: { path: string; member: string; size: number }[]; // member: path inside the archive, relative to base_root
+  total_bytes: number;
+  archive_bytes: number; // size of the .tar.gz itself
+  originals_removed: number;
+  batch_id: string; // journal the files were recorded under
+}
+
+// unarchive_files result
+export interface RestoreReport {
+  restored: string[];
+  conflicts: string[]; // already existed, left alone
+  total_bytes: number;
+}
+
+// Payload of archive-progress events; totals stay 0 while restoring until the end
+export interface ArchiveProgress extends ProgressEstimate {
+  archive_path: string;
+  phase: 'archiving' | 'verifying' | 'removing' | 'restoring';
+  files_done: number;
+  files_total: number;
+  bytes_done: number;
+  bytes_total: number;
+}
+
+export interface VerificationReport {
+  batch_id: string;
+  entries: number;
+  verified: number;
+  hashes_checked: number;
+  discrepancies: Discrepancy[];
+  stray_temp_files: string[]; // left in destination folders by interrupted copies
+}
+
+export interface RenamePlanItem {
+  path: string;
+  new_name: string; // extension is kept unless this includes one
+}
+
+// Nothing is renamed when conflicts is non-empty or failures were found while planning
+export interface RenameBatchReport {
+  outcomes: RenameOutcome[];
+  conflicts: { target: string; sources: string[] }[];
+  failures: { path: string; error: AppError }[];
+  // Set when the batch was cancelled part way; skipped lists the files not reached
+  cancelled: boolean;
+  skipped: string[];
+}
+
+// Preview image returned by generate_thumbnail
+export interface Thumbnail {
+  base64: string;
+  mime_type: string;
+  width: number;
+  height: number;
+  cached: boolean; // served from the on-disk thumbnail cache
+}
+
 // Saved processed files state for persistence
 export interface SavedProcessedState {
   directory?: string; // For backwards compatibility
   directories?: string[]; // New field for multiple directories
   includeSubdirectories: boolean;
+  includeHidden?: boolean;
   useExistingCategories?: boolean; // Whether to use existing subdirectories as categories
   existingCategories?: string[]; // List of existing subdirectory names
   rows: Row[];